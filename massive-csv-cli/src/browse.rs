@@ -0,0 +1,480 @@
+//! `browse`: a full-screen viewer and editor. Only the rows on screen are
+//! read, so it opens files of any size as fast as `view`.
+
+use std::iter::once;
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+use massive_csv_core::{searcher, CsvEditor, SearchOptions};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Position};
+use ratatui::style::{Modifier, Style};
+use ratatui::widgets::{Cell, Paragraph, Row, Table};
+use ratatui::{DefaultTerminal, Frame};
+
+use crate::format::format_number;
+
+/// Widest a column is drawn, in characters.
+const MAX_COL_WIDTH: usize = 30;
+
+/// How long typing must pause before the search being typed runs, so each
+/// key press doesn't start a scan of the file.
+const SEARCH_DELAY: Duration = Duration::from_millis(150);
+
+/// Rows a search scans between checks for key presses.
+const SEARCH_CHUNK: usize = 64 * 1024;
+
+const HELP: &str =
+    "arrows move  / search  n next  : go to row  Enter edit  u undo row  s save  q quit";
+
+/// Browse the rows of `editor`'s file until the user quits, saving edits
+/// through the editor when asked. `name` is shown in the status line.
+pub fn browse(editor: CsvEditor, name: String) -> Result<(), Box<dyn std::error::Error>> {
+    let mut browser = Browser::new(editor, name);
+    let mut terminal = ratatui::init();
+    let result = browser.run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+/// What a line of input typed in the status line is for.
+enum Prompt {
+    /// Incremental search, started with the cursor at row `origin`.
+    Search {
+        origin: usize,
+    },
+    GoTo,
+    Edit,
+}
+
+/// A search run a chunk of rows at a time by the event loop, so the screen
+/// keeps responding to keys while it scans.
+struct Search {
+    query: String,
+    /// Next row to scan; the search wraps around to the start of the file.
+    next: usize,
+    /// Rows scanned so far.
+    scanned: usize,
+    /// When to start scanning.
+    due: Instant,
+}
+
+struct Browser {
+    editor: CsvEditor,
+    name: String,
+    row_count: usize,
+    /// Selected cell.
+    row: usize,
+    col: usize,
+    /// First row and column on screen.
+    top: usize,
+    left: usize,
+    /// Rows that fit on screen, as of the last draw.
+    page: usize,
+    input: Option<(Prompt, String)>,
+    last_search: Option<String>,
+    /// The search in progress, if any.
+    search: Option<Search>,
+    message: Option<String>,
+    /// `q` was pressed with unsaved edits; a second `q` quits anyway.
+    confirm_quit: bool,
+}
+
+impl Browser {
+    fn new(editor: CsvEditor, name: String) -> Self {
+        Self {
+            row_count: editor.row_count(),
+            editor,
+            name,
+            row: 0,
+            col: 0,
+            top: 0,
+            left: 0,
+            page: 1,
+            input: None,
+            last_search: None,
+            search: None,
+            message: None,
+            confirm_quit: false,
+        }
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<(), Box<dyn std::error::Error>> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            if let Some(search) = &self.search {
+                let wait = search.due.saturating_duration_since(Instant::now());
+                if !event::poll(wait)? {
+                    self.search_step();
+                    continue;
+                }
+            }
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press && !self.handle_key(key) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Act on a key press. Returns false to quit.
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        if self.input.is_some() {
+            self.handle_input_key(key);
+            return true;
+        }
+        let quitting = std::mem::take(&mut self.confirm_quit);
+        self.message = None;
+        self.search = None;
+        let width = self.editor.headers().len();
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => {
+                if !self.editor.has_changes() || quitting {
+                    return false;
+                }
+                self.confirm_quit = true;
+                self.message = Some(format!(
+                    "{} edited row(s) not saved: s to save, q again to quit anyway",
+                    self.editor.edit_count()
+                ));
+            }
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return false,
+            KeyCode::Up | KeyCode::Char('k') => self.row = self.row.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => self.select_row(self.row + 1),
+            KeyCode::PageUp => self.row = self.row.saturating_sub(self.page),
+            KeyCode::PageDown | KeyCode::Char(' ') => self.select_row(self.row + self.page),
+            KeyCode::Home | KeyCode::Char('g') => self.row = 0,
+            KeyCode::End | KeyCode::Char('G') => self.select_row(usize::MAX),
+            KeyCode::Left | KeyCode::Char('h') => self.col = self.col.saturating_sub(1),
+            KeyCode::Right | KeyCode::Char('l') => self.col = (self.col + 1).min(width.max(1) - 1),
+            KeyCode::Char('0') | KeyCode::Char('^') => self.col = 0,
+            KeyCode::Char('$') => self.col = width.max(1) - 1,
+            KeyCode::Char('/') => {
+                let origin = self.row;
+                self.input = Some((Prompt::Search { origin }, String::new()));
+            }
+            KeyCode::Char('n') => match self.last_search.clone() {
+                Some(query) => {
+                    let from = self.row + 1;
+                    self.find(query, from, Duration::ZERO);
+                }
+                None => self.message = Some("No search yet: press / to search".to_string()),
+            },
+            KeyCode::Char(':') => self.input = Some((Prompt::GoTo, String::new())),
+            KeyCode::Enter | KeyCode::Char('e') if self.row < self.row_count => {
+                let value = self
+                    .editor
+                    .get_row(self.row)
+                    .ok()
+                    .and_then(|mut fields| fields.get_mut(self.col).map(std::mem::take))
+                    .unwrap_or_default();
+                self.input = Some((Prompt::Edit, value));
+            }
+            KeyCode::Char('u') => {
+                self.editor.revert_row(self.row);
+                self.message = Some(format!("Reverted row {}", format_number(self.row)));
+            }
+            KeyCode::Char('s') => {
+                self.message = Some(match self.editor.save() {
+                    Ok(()) => format!("Saved {}", self.name),
+                    Err(e) => format!("Save failed: {e}"),
+                });
+            }
+            _ => {}
+        }
+        true
+    }
+
+    fn handle_input_key(&mut self, key: KeyEvent) {
+        let Some((prompt, text)) = self.input.as_mut() else {
+            return;
+        };
+        match key.code {
+            KeyCode::Esc => {
+                if let Prompt::Search { origin } = prompt {
+                    self.row = *origin;
+                    self.search = None;
+                }
+                self.input = None;
+            }
+            KeyCode::Enter => {
+                let (prompt, text) = self.input.take().expect("checked above");
+                self.finish_input(prompt, text);
+            }
+            KeyCode::Backspace => {
+                text.pop();
+                self.search_as_typed();
+            }
+            KeyCode::Char(c) => {
+                text.push(c);
+                self.search_as_typed();
+            }
+            _ => {}
+        }
+    }
+
+    /// Jump to the first match of the search being typed, once typing
+    /// pauses.
+    fn search_as_typed(&mut self) {
+        let Some((Prompt::Search { origin }, text)) = &self.input else {
+            return;
+        };
+        let (origin, query) = (*origin, text.clone());
+        if query.is_empty() {
+            self.row = origin;
+            self.message = None;
+            self.search = None;
+        } else {
+            self.find(query, origin, SEARCH_DELAY);
+        }
+    }
+
+    fn finish_input(&mut self, prompt: Prompt, text: String) {
+        match prompt {
+            Prompt::Search { .. } => {
+                if !text.is_empty() {
+                    self.last_search = Some(text);
+                }
+            }
+            Prompt::GoTo => match text.trim().replace(',', "").parse::<usize>() {
+                Ok(row) => self.select_row(row),
+                Err(_) => self.message = Some(format!("Not a row number: {text}")),
+            },
+            Prompt::Edit => {
+                if let Err(e) = self.editor.set_cell(self.row, self.col, text) {
+                    self.message = Some(format!("Edit failed: {e}"));
+                }
+            }
+        }
+    }
+
+    /// Start selecting the first row at or after `from` containing `query`,
+    /// ignoring case unless the query has capitals, after `delay`. The
+    /// search runs in [`Self::search_step`]s, between key presses.
+    fn find(&mut self, query: String, from: usize, delay: Duration) {
+        self.search = Some(Search {
+            query,
+            next: from.min(self.row_count),
+            scanned: 0,
+            due: Instant::now() + delay,
+        });
+    }
+
+    /// Scan the next chunk of rows for the search in progress, selecting
+    /// the match and ending the search if there is one.
+    fn search_step(&mut self) {
+        let Some(search) = self.search.as_mut() else {
+            return;
+        };
+        let end = (search.next + SEARCH_CHUNK)
+            .min(self.row_count)
+            .min(search.next + self.row_count - search.scanned);
+        let found = first_match(&self.editor, &search.query, search.next..end);
+        search.scanned += end - search.next;
+        search.next = if end == self.row_count { 0 } else { end };
+        self.message = match found {
+            Ok(Some(row)) => {
+                self.row = row;
+                None
+            }
+            Ok(None) if search.scanned < self.row_count => {
+                let percent = search.scanned * 100 / self.row_count;
+                self.message = Some(format!("Searching for '{}'... {percent}%", search.query));
+                return;
+            }
+            Ok(None) => Some(format!("No match for '{}'", search.query)),
+            Err(e) => Some(e.to_string()),
+        };
+        self.search = None;
+    }
+
+    fn select_row(&mut self, row: usize) {
+        self.row = row.min(self.row_count.saturating_sub(1));
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [table_area, status_area] =
+            Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(frame.area());
+
+        // The header takes a line
+        self.page = (table_area.height as usize).saturating_sub(1).max(1);
+        if self.row < self.top {
+            self.top = self.row;
+        } else if self.row >= self.top + self.page {
+            self.top = self.row + 1 - self.page;
+        }
+        let end = (self.top + self.page).min(self.row_count);
+        let rows: Vec<(usize, Vec<String>)> = (self.top..end)
+            .map(|row| (row, self.editor.get_row(row).unwrap_or_default()))
+            .collect();
+
+        let headers = self.editor.headers();
+        let widths: Vec<usize> = (0..headers.len())
+            .map(|col| {
+                rows.iter()
+                    .filter_map(|(_, fields)| fields.get(col))
+                    .chain(once(&headers[col]))
+                    .map(|value| value.chars().count())
+                    .max()
+                    .unwrap_or(0)
+                    .clamp(1, MAX_COL_WIDTH)
+            })
+            .collect();
+        let label_width = format_number(end.max(1) - 1).len().max(3);
+        let room = (table_area.width as usize).saturating_sub(label_width + 1);
+
+        // Scroll sideways until the selected column fits
+        let fits_from = |left: usize| -> usize {
+            let mut used = 0;
+            let mut last = left;
+            for (col, width) in widths.iter().enumerate().skip(left) {
+                used += width + 1;
+                if used > room + 1 && col > left {
+                    break;
+                }
+                last = col;
+            }
+            last
+        };
+        self.left = self.left.min(self.col);
+        while self.left < self.col && fits_from(self.left) < self.col {
+            self.left += 1;
+        }
+        let shown = self.left..(fits_from(self.left) + 1).min(headers.len());
+
+        let bold = Style::new().add_modifier(Modifier::BOLD);
+        let dim = Style::new().add_modifier(Modifier::DIM);
+        let header = Row::new(
+            once(Cell::from("Row"))
+                .chain(shown.clone().map(|col| Cell::from(headers[col].as_str()))),
+        )
+        .style(bold.add_modifier(Modifier::UNDERLINED));
+        let body = rows.iter().map(|(row, fields)| {
+            let cells =
+                once(Cell::from(format_number(*row)).style(dim)).chain(shown.clone().map(|col| {
+                    let value = fields.get(col).map(|v| v.replace(['\r', '\n'], " "));
+                    let cell = Cell::from(value.unwrap_or_default());
+                    if *row == self.row && col == self.col {
+                        cell.style(Style::new().add_modifier(Modifier::REVERSED))
+                    } else {
+                        cell
+                    }
+                }));
+            let line = Row::new(cells);
+            if *row == self.row {
+                line.style(bold)
+            } else {
+                line
+            }
+        });
+        let constraints = once(Constraint::Length(label_width as u16)).chain(
+            shown
+                .clone()
+                .map(|col| Constraint::Length(widths[col] as u16)),
+        );
+        frame.render_widget(
+            Table::new(body, constraints)
+                .header(header)
+                .column_spacing(1),
+            table_area,
+        );
+
+        let status = match &self.input {
+            Some((prompt, text)) => {
+                let label = match prompt {
+                    Prompt::Search { .. } => "/".to_string(),
+                    Prompt::GoTo => "Go to row: ".to_string(),
+                    Prompt::Edit => format!("{}: ", headers.get(self.col).map_or("", |h| h)),
+                };
+                let x = status_area.x + (label.chars().count() + text.chars().count()) as u16;
+                frame.set_cursor_position(Position::new(
+                    x.min(status_area.right().saturating_sub(1)),
+                    status_area.y,
+                ));
+                format!("{label}{text}")
+            }
+            None => {
+                let position = format!(
+                    "{} | row {} of {} | {} ({})",
+                    self.name,
+                    format_number(self.row),
+                    format_number(self.row_count),
+                    headers.get(self.col).map_or("", |h| h),
+                    self.col
+                );
+                let edits = match self.editor.edit_count() {
+                    0 => String::new(),
+                    n => format!(" | {n} edited row(s)"),
+                };
+                let hint = self.message.as_deref().unwrap_or(HELP);
+                format!("{position}{edits} | {hint}")
+            }
+        };
+        frame.render_widget(
+            Paragraph::new(status).style(Style::new().add_modifier(Modifier::REVERSED)),
+            status_area,
+        );
+    }
+}
+
+/// The first row in `rows` containing `query`, ignoring case unless the
+/// query has capitals. Rows with pending edits are matched as the grid
+/// shows them, not as they are on disk; once rows are moved, inserted or
+/// deleted, or columns changed, every row is.
+fn first_match(
+    editor: &CsvEditor,
+    query: &str,
+    rows: Range<usize>,
+) -> massive_csv_core::Result<Option<usize>> {
+    let case_insensitive = !query.chars().any(char::is_uppercase);
+    let needle = if case_insensitive {
+        query.to_lowercase()
+    } else {
+        query.to_string()
+    };
+    let contains = |row: usize| -> massive_csv_core::Result<bool> {
+        Ok(editor.get_row(row)?.iter().any(|field| {
+            if case_insensitive {
+                field.to_lowercase().contains(&needle)
+            } else {
+                field.contains(&needle)
+            }
+        }))
+    };
+    // The file's rows no longer line up with the grid's, so searching it
+    // would land on the wrong rows
+    if editor.has_moved_rows() || editor.column_changes().next().is_some() {
+        for row in rows {
+            if contains(row)? {
+                return Ok(Some(row));
+            }
+        }
+        return Ok(None);
+    }
+
+    let edited = editor.edited_rows();
+    let edited = edited.iter().copied().filter(|row| rows.contains(row));
+    let mut start = rows.start;
+    for stop in edited.map(Some).chain(once(None)) {
+        let end = stop.unwrap_or(rows.end);
+        if start < end {
+            let options = SearchOptions {
+                case_insensitive,
+                max_results: 1,
+                rows: Some(start..end),
+                ..Default::default()
+            };
+            if let Some(found) = searcher::search(editor.reader(), query, &options)?.first() {
+                return Ok(Some(found.row_num));
+            }
+        }
+        let Some(row) = stop else {
+            break;
+        };
+        if contains(row)? {
+            return Ok(Some(row));
+        }
+        start = row + 1;
+    }
+    Ok(None)
+}
@@ -0,0 +1,323 @@
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use clap::Command;
+use serde::Deserialize;
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// User configuration, read from `~/.config/massive-csv/config.toml` or
+/// the file given with `--config`.
+///
+/// ```toml
+/// # Defaults for global options, by their long names, then for each
+/// # command's options (nested for commands like `views list`)
+/// [defaults]
+/// color = "always"
+/// max-width = 60
+///
+/// [defaults.search]
+/// max-results = 100
+/// format = "csv"
+///
+/// # Read options for files matching a glob pattern; the first match wins
+/// [[files]]
+/// pattern = "*.psv"
+/// delimiter = "|"
+///
+/// [aliases.monthly-clean]
+/// description = "Clean the monthly vendor export"
+/// command = ["pipeline", "specs/clean.json", "--input", "{file}", "--output", "{dir}/{stem}.clean.csv"]
+///
+/// [aliases.lookup]
+/// command = ["search", "{file}", "{query}", "--column", "{column}"]
+/// defaults = { column = "id" }
+///
+/// # Per-file overrides, matched on the file name
+/// [aliases.lookup.files."vendor.csv"]
+/// column = "sku"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default)]
+    pub aliases: BTreeMap<String, Alias>,
+    /// Option defaults, applied to the command line before it is parsed.
+    #[serde(default)]
+    pub defaults: toml::Table,
+    #[serde(default)]
+    pub files: Vec<FileDefaults>,
+    /// Where the config was read from, if anywhere.
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
+}
+
+/// Read options for the files matching `pattern`: their name, or their
+/// path if the pattern has a `/`.
+#[derive(Debug, Deserialize)]
+pub struct FileDefaults {
+    pub pattern: String,
+    /// Read options by long name, as in `[defaults]`.
+    #[serde(flatten)]
+    pub options: toml::Table,
+}
+
+/// A named, saved subcommand invocation.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Alias {
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Arguments after `massive-csv`, with `{placeholders}`.
+    pub command: Vec<String>,
+    /// Placeholder values used when neither the file entry nor `--set` gives one.
+    #[serde(default)]
+    pub defaults: BTreeMap<String, String>,
+    /// Placeholder values for specific files, keyed by file name.
+    #[serde(default)]
+    pub files: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl Config {
+    /// Default config location: `$XDG_CONFIG_HOME/massive-csv/config.toml`,
+    /// falling back to `~/.config/massive-csv/config.toml`.
+    pub fn default_path() -> Option<PathBuf> {
+        let base = std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))?;
+        Some(base.join("massive-csv").join("config.toml"))
+    }
+
+    /// Load the config at `path`, or the default location if `None`.
+    ///
+    /// A missing file yields an empty config.
+    pub fn load(path: Option<&Path>) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = match path {
+            Some(p) => p.to_path_buf(),
+            None => match Self::default_path() {
+                Some(p) if p.exists() => p,
+                _ => return Ok(Self::default()),
+            },
+        };
+        let text = std::fs::read_to_string(&path)
+            .map_err(|e| format!("cannot read config {}: {e}", path.display()))?;
+        let mut config: Self =
+            toml::from_str(&text).map_err(|e| format!("invalid config {}: {e}", path.display()))?;
+        for files in &config.files {
+            glob::Pattern::new(&files.pattern).map_err(|e| {
+                format!(
+                    "invalid config {}: bad pattern '{}': {e}",
+                    path.display(),
+                    files.pattern
+                )
+            })?;
+        }
+        config.path = Some(path);
+        Ok(config)
+    }
+
+    /// Load the config for this run, as [`load`](Self::load) does, and
+    /// keep it for [`global`](Self::global).
+    pub fn init(path: Option<&Path>) -> Result<&'static Self, Box<dyn std::error::Error>> {
+        let config = Self::load(path)?;
+        Ok(CONFIG.get_or_init(|| config))
+    }
+
+    /// The config loaded by [`init`](Self::init), or an empty one.
+    pub fn global() -> &'static Self {
+        CONFIG.get_or_init(Self::default)
+    }
+
+    /// `command` with the `[defaults]` set as its options' default values,
+    /// so options given on the command line still win.
+    pub fn apply_defaults(&self, command: Command) -> Result<Command, Box<dyn std::error::Error>> {
+        apply_defaults(command, &self.defaults, "defaults")
+    }
+
+    /// Read options for `path`, as command-line arguments, from the first
+    /// `[[files]]` entry whose pattern matches it.
+    pub fn file_args(&self, path: &Path) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let name = path.file_name().map(Path::new).unwrap_or(path);
+        let Some(files) = self.files.iter().find(|files| {
+            let target = if files.pattern.contains('/') {
+                path
+            } else {
+                name
+            };
+            glob::Pattern::new(&files.pattern).is_ok_and(|p| p.matches_path(target))
+        }) else {
+            return Ok(Vec::new());
+        };
+        let mut args = Vec::new();
+        for (key, value) in &files.options {
+            let context = || format!("[[files]] '{}'", files.pattern);
+            match value {
+                toml::Value::Boolean(true) => args.push(format!("--{key}")),
+                toml::Value::Boolean(false) => {}
+                value => args.extend([
+                    format!("--{key}"),
+                    default_values(value, &context)?.join(","),
+                ]),
+            }
+        }
+        Ok(args)
+    }
+}
+
+/// The `--config` path in `args`, found before they are parsed since the
+/// config's defaults change how they are parsed.
+pub fn path_from_args(args: &[OsString]) -> Option<PathBuf> {
+    let mut args = args.iter().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            break;
+        }
+        if arg == "--config" {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(path) = arg.to_str().and_then(|a| a.strip_prefix("--config=")) {
+            return Some(PathBuf::from(path));
+        }
+    }
+    None
+}
+
+/// Set the defaults in `table` on `command`'s options, and on its
+/// subcommands' for nested tables. `section` names the table in errors.
+fn apply_defaults(
+    mut command: Command,
+    table: &toml::Table,
+    section: &str,
+) -> Result<Command, Box<dyn std::error::Error>> {
+    for (key, value) in table {
+        if let toml::Value::Table(options) = value {
+            let subcommand = command
+                .find_subcommand(key)
+                .cloned()
+                .ok_or_else(|| format!("config [{section}]: no command '{key}'"))?;
+            let subcommand = apply_defaults(subcommand, options, &format!("{section}.{key}"))?;
+            command = command.mut_subcommand(key, |_| subcommand);
+            continue;
+        }
+        let id = command
+            .get_arguments()
+            .find(|arg| arg.get_long() == Some(key.as_str()))
+            .map(|arg| arg.get_id().clone())
+            .ok_or_else(|| match section {
+                "defaults" => format!("config [defaults]: no global option '--{key}'"),
+                _ => format!(
+                    "config [{section}]: no option '--{key}' (global options go under [defaults])"
+                ),
+            })?;
+        let values = default_values(value, &|| format!("[{section}]"))?;
+        command = command.mut_arg(id, |arg| arg.default_values(values));
+    }
+    Ok(command)
+}
+
+/// A config value as option values: arrays give one per element.
+fn default_values(
+    value: &toml::Value,
+    context: &dyn Fn() -> String,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    match value {
+        toml::Value::String(s) => Ok(vec![s.clone()]),
+        toml::Value::Integer(n) => Ok(vec![n.to_string()]),
+        toml::Value::Float(n) => Ok(vec![n.to_string()]),
+        toml::Value::Boolean(b) => Ok(vec![b.to_string()]),
+        toml::Value::Array(items) => items
+            .iter()
+            .map(|item| match item {
+                toml::Value::Array(_) | toml::Value::Table(_) => {
+                    Err(format!("config {}: nested lists aren't option values", context()).into())
+                }
+                item => Ok(default_values(item, context)?.remove(0)),
+            })
+            .collect(),
+        _ => Err(format!("config {}: '{value}' isn't an option value", context()).into()),
+    }
+}
+
+impl Alias {
+    /// Expand the alias for `file`, returning the full argument list.
+    ///
+    /// Placeholders are resolved from `overrides`, then the entry for the file's
+    /// name in `files`, then `defaults`. `{file}`, `{dir}` and `{stem}` come from
+    /// `file` itself. `extra` is appended unchanged.
+    pub fn expand(
+        &self,
+        file: Option<&Path>,
+        overrides: &BTreeMap<String, String>,
+        extra: &[String],
+    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let mut vars = self.defaults.clone();
+        if let Some(file) = file {
+            let name = file
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            if let Some(per_file) = self.files.get(&name) {
+                vars.extend(per_file.clone());
+            }
+            let dir = match file.parent() {
+                Some(p) if !p.as_os_str().is_empty() => p.to_string_lossy().into_owned(),
+                _ => ".".to_string(),
+            };
+            let stem = file
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            vars.insert("file".to_string(), file.to_string_lossy().into_owned());
+            vars.insert("dir".to_string(), dir);
+            vars.insert("stem".to_string(), stem);
+        }
+        vars.extend(overrides.clone());
+
+        let mut args = self
+            .command
+            .iter()
+            .map(|arg| substitute(arg, &vars))
+            .collect::<Result<Vec<_>, _>>()?;
+        args.extend(extra.iter().cloned());
+        Ok(args)
+    }
+}
+
+/// Replace every `{name}` in `arg` with its value; `{{` and `}}` are literal braces.
+fn substitute(
+    arg: &str,
+    vars: &BTreeMap<String, String>,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let mut out = String::with_capacity(arg.len());
+    let mut rest = arg;
+
+    while let Some(i) = rest.find(['{', '}']) {
+        out.push_str(&rest[..i]);
+        let tail = &rest[i..];
+        if tail.starts_with("{{") || tail.starts_with("}}") {
+            out.push_str(&tail[..1]);
+            rest = &tail[2..];
+            continue;
+        }
+        if tail.starts_with('}') {
+            return Err(format!("unmatched '}}' in alias argument '{arg}'").into());
+        }
+        let end = tail
+            .find('}')
+            .ok_or_else(|| format!("unclosed '{{' in alias argument '{arg}'"))?;
+        let name = &tail[1..end];
+        let value = vars.get(name).ok_or_else(|| match name {
+            "file" | "dir" | "stem" => format!("alias needs a file for {{{name}}}"),
+            _ => format!(
+                "no value for {{{name}}}: add it to the alias defaults or pass --set {name}=..."
+            ),
+        })?;
+        out.push_str(value);
+        rest = &tail[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
@@ -1,14 +1,187 @@
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::OnceLock;
+
+use massive_csv_core::{Expr, Patch, PatchOp, SearchOptions};
+
+const BOLD_CYAN: &str = "\x1b[1;36m";
+const BOLD_RED: &str = "\x1b[1;31m";
+const DIM: &str = "\x1b[2m";
+const RESET: &str = "\x1b[0m";
+
+static COLOR: AtomicBool = AtomicBool::new(false);
+static MAX_WIDTH: AtomicUsize = AtomicUsize::new(40);
+static COLUMN_WIDTHS: OnceLock<HashMap<String, usize>> = OnceLock::new();
+static VERTICAL: AtomicBool = AtomicBool::new(false);
+/// Columns per table window; 0 for all.
+static MAX_COLS: AtomicUsize = AtomicUsize::new(0);
+
+/// Color table headers, row numbers and search matches from now on.
+pub fn set_color(enabled: bool) {
+    COLOR.store(enabled, Ordering::Relaxed);
+}
+
+fn color() -> bool {
+    COLOR.load(Ordering::Relaxed)
+}
+
+/// Cut table columns to `width` characters from now on.
+pub fn set_max_width(width: usize) {
+    MAX_WIDTH.store(width.max(4), Ordering::Relaxed);
+}
+
+/// Cut the named columns to their own widths instead, from now on. Only
+/// the first call takes effect.
+pub fn set_column_widths(widths: HashMap<String, usize>) {
+    let _ = COLUMN_WIDTHS.set(widths);
+}
+
+/// Print rows as vertical records, or tables `max_cols` columns at a time,
+/// from now on.
+pub fn set_layout(vertical: bool, max_cols: Option<usize>) {
+    VERTICAL.store(vertical, Ordering::Relaxed);
+    MAX_COLS.store(max_cols.unwrap_or(0), Ordering::Relaxed);
+}
+
+/// Widest the column `header` is printed in a table.
+fn max_width(header: &str) -> usize {
+    COLUMN_WIDTHS
+        .get()
+        .and_then(|widths| widths.get(header))
+        .map_or_else(|| MAX_WIDTH.load(Ordering::Relaxed), |&w| w.max(4))
+}
+
+/// `text` in `style`, when color is on.
+fn paint(text: &str, style: &str) -> String {
+    if color() {
+        format!("{style}{text}{RESET}")
+    } else {
+        text.to_string()
+    }
+}
+
+/// What a search matched, to highlight it in the rows printed.
+pub struct Highlight {
+    query: String,
+    case_insensitive: bool,
+    /// Only in this column, when the search was restricted to one.
+    column: Option<usize>,
+}
+
+impl Highlight {
+    /// Highlight for a search for `query` with `options`, shown under
+    /// `headers`. `None` if there is nothing to highlight: an empty query,
+    /// or a search in a column that isn't shown.
+    pub fn new(query: &str, options: &SearchOptions, headers: &[String]) -> Option<Self> {
+        if query.is_empty() {
+            return None;
+        }
+        let column = match &options.column {
+            Some(name) => Some(headers.iter().position(|h| h == name)?),
+            None => None,
+        };
+        Some(Self {
+            query: query.to_string(),
+            case_insensitive: options.case_insensitive,
+            column,
+        })
+    }
+
+    /// `text` (a cell of `column`) with each occurrence of the query painted.
+    fn apply(&self, text: &str, column: usize) -> String {
+        if !color() || self.column.is_some_and(|c| c != column) {
+            return text.to_string();
+        }
+        let (haystack, needle) = if self.case_insensitive {
+            (text.to_lowercase(), self.query.to_lowercase())
+        } else {
+            (text.to_string(), self.query.clone())
+        };
+        // Lowercasing can change byte lengths outside ASCII; don't guess
+        if haystack.len() != text.len() {
+            return text.to_string();
+        }
+        let mut out = String::with_capacity(text.len());
+        let mut last = 0;
+        for (start, _) in haystack.match_indices(&needle) {
+            if start < last || !text.is_char_boundary(start) {
+                continue;
+            }
+            let end = start + needle.len();
+            out.push_str(&text[last..start]);
+            out.push_str(&paint(&text[start..end], BOLD_RED));
+            last = end;
+        }
+        out.push_str(&text[last..]);
+        out
+    }
+}
+
 /// Print rows as a formatted table to stdout.
 ///
 /// `row_numbers` maps each row in `rows` to its original row number in the file.
 pub fn print_table(headers: &[String], rows: &[Vec<String>], row_numbers: &[usize]) {
+    print_rows(headers, rows, row_numbers, None, None);
+}
+
+/// Print search results, with `highlight` painted in them. With context
+/// rows, a `>` marks the rows whose `is_match` is set and a `--` line
+/// separates runs of rows that aren't adjacent in the file.
+pub fn print_matches(
+    headers: &[String],
+    rows: &[Vec<String>],
+    row_numbers: &[usize],
+    is_match: Option<&[bool]>,
+    highlight: Option<&Highlight>,
+) {
+    print_rows(headers, rows, row_numbers, is_match, highlight);
+}
+
+fn print_rows(
+    headers: &[String],
+    rows: &[Vec<String>],
+    row_numbers: &[usize],
+    is_match: Option<&[bool]>,
+    highlight: Option<&Highlight>,
+) {
     if headers.is_empty() {
         return;
     }
+    if VERTICAL.load(Ordering::Relaxed) {
+        print_records(headers, rows, row_numbers, is_match, highlight);
+        return;
+    }
 
-    let max_col_width: usize = 40;
     let num_cols = headers.len();
+    let window = match MAX_COLS.load(Ordering::Relaxed) {
+        0 => num_cols,
+        n => n,
+    };
+    if window >= num_cols {
+        print_window(headers, rows, row_numbers, is_match, highlight, 0..num_cols);
+        return;
+    }
+    for start in (0..num_cols).step_by(window) {
+        let end = (start + window).min(num_cols);
+        if start > 0 {
+            println!();
+        }
+        let title = format!("Columns {}-{} of {}", start, end - 1, num_cols);
+        println!("{}", paint(&title, DIM));
+        print_window(headers, rows, row_numbers, is_match, highlight, start..end);
+    }
+}
 
+/// Print the columns `cols` of `rows` as a table.
+fn print_window(
+    headers: &[String],
+    rows: &[Vec<String>],
+    row_numbers: &[usize],
+    is_match: Option<&[bool]>,
+    highlight: Option<&Highlight>,
+    cols: Range<usize>,
+) {
     // "Row" label column width: at least 3 chars, or as wide as the largest row number
     let row_label_width = row_numbers
         .iter()
@@ -17,36 +190,36 @@ pub fn print_table(headers: &[String], rows: &[Vec<String>], row_numbers: &[usiz
         .unwrap_or(3)
         .max(3);
 
-    // Compute column widths from headers and data
-    let mut col_widths: Vec<usize> = headers.iter().map(|h| h.len()).collect();
-    for row in rows {
-        for (i, field) in row.iter().enumerate() {
-            if i < num_cols {
-                col_widths[i] = col_widths[i].max(field.len());
-            }
-        }
-    }
-    // Cap each column
-    for w in col_widths.iter_mut() {
-        *w = (*w).min(max_col_width);
-    }
+    // Compute column widths from headers and data, capping each column
+    let col_widths: Vec<usize> = cols
+        .clone()
+        .map(|i| {
+            rows.iter()
+                .filter_map(|row| row.get(i))
+                .map(|field| field.len())
+                .fold(headers[i].len(), usize::max)
+                .min(max_width(&headers[i]))
+        })
+        .collect();
 
     // Print header
-    print!(" {:>width$} ", "Row", width = row_label_width);
-    for (i, header) in headers.iter().enumerate() {
-        if i > 0 {
+    let label = format!("{:>width$}", "Row", width = row_label_width);
+    print!(" {} ", paint(&label, BOLD_CYAN));
+    for (n, (header, &width)) in headers[cols.clone()].iter().zip(&col_widths).enumerate() {
+        if n > 0 {
             print!(" | ");
         } else {
             print!("| ");
         }
-        print!("{:<width$}", truncate(header, col_widths[i]), width = col_widths[i]);
+        let padded = format!("{:<width$}", truncate(header, width), width = width);
+        print!("{}", paint(&padded, BOLD_CYAN));
     }
     println!();
 
     // Print separator
     print!("-{:-<width$}-", "", width = row_label_width);
-    for (i, w) in col_widths.iter().enumerate() {
-        if i > 0 {
+    for (n, w) in col_widths.iter().enumerate() {
+        if n > 0 {
             print!("-+-");
         } else {
             print!("+-");
@@ -58,20 +231,180 @@ pub fn print_table(headers: &[String], rows: &[Vec<String>], row_numbers: &[usiz
     // Print rows
     for (row_idx, row) in rows.iter().enumerate() {
         let row_num = row_numbers.get(row_idx).copied().unwrap_or(row_idx);
-        print!(" {:>width$} ", format_number(row_num), width = row_label_width);
-        for i in 0..num_cols {
-            if i > 0 {
+        let marker = match_marker(row_numbers, is_match, row_idx);
+        let label = format!(
+            "{:>width$}",
+            format_number(row_num),
+            width = row_label_width
+        );
+        print!("{marker}{} ", paint(&label, DIM));
+        for (i, &width) in cols.clone().zip(&col_widths) {
+            if i > cols.start {
                 print!(" | ");
             } else {
                 print!("| ");
             }
             let field = row.get(i).map(|s| s.as_str()).unwrap_or("");
-            print!("{:<width$}", truncate(field, col_widths[i]), width = col_widths[i]);
+            let padded = format!("{:<width$}", truncate(field, width), width = width);
+            match highlight {
+                Some(highlight) => print!("{}", highlight.apply(&padded, i)),
+                None => print!("{padded}"),
+            }
         }
         println!();
     }
 }
 
+/// Print each row as a record: a heading with its row number, then a
+/// "column | value" line per field, with values in full.
+fn print_records(
+    headers: &[String],
+    rows: &[Vec<String>],
+    row_numbers: &[usize],
+    is_match: Option<&[bool]>,
+    highlight: Option<&Highlight>,
+) {
+    let name_width = headers.iter().map(|h| h.len()).max().unwrap_or(0);
+
+    for (row_idx, row) in rows.iter().enumerate() {
+        let row_num = row_numbers.get(row_idx).copied().unwrap_or(row_idx);
+        let marker = match_marker(row_numbers, is_match, row_idx);
+        let heading = format!("-[ Row {} ]", format_number(row_num));
+        let rule = format!("{heading:-<width$}", width = name_width + 3);
+        println!("{marker}{}", paint(&rule, DIM));
+        for (i, header) in headers.iter().enumerate() {
+            let name = format!("{header:<name_width$}");
+            let field = row.get(i).map(|s| s.as_str()).unwrap_or("");
+            let value = match highlight {
+                Some(highlight) => highlight.apply(field, i),
+                None => field.to_string(),
+            };
+            println!(" {} | {value}", paint(&name, BOLD_CYAN));
+        }
+    }
+}
+
+/// The character to print before row `row_idx`: `>` for a search match
+/// among context rows, else a space. Prints the `--` line that separates
+/// context runs that aren't adjacent in the file.
+fn match_marker(row_numbers: &[usize], is_match: Option<&[bool]>, row_idx: usize) -> char {
+    let Some(is_match) = is_match else {
+        return ' ';
+    };
+    let row_num = row_numbers.get(row_idx).copied().unwrap_or(row_idx);
+    if row_idx > 0 && row_numbers.get(row_idx - 1).map(|n| n + 1) != Some(row_num) {
+        println!("--");
+    }
+    if is_match.get(row_idx) == Some(&true) {
+        '>'
+    } else {
+        ' '
+    }
+}
+
+/// Print a patch as a readable row/cell-level diff.
+///
+/// Updates to the same row are grouped under one line per row.
+pub fn print_patch(patch: &Patch) {
+    let mut updated_rows = 0;
+    let mut updated_cells = 0;
+    let mut deleted = 0;
+    let mut inserted = 0;
+    let mut current_key: Option<&[String]> = None;
+
+    for op in &patch.ops {
+        match op {
+            PatchOp::Update {
+                key,
+                column,
+                old,
+                new,
+            } => {
+                if current_key != Some(key.as_slice()) {
+                    println!("~ {}", describe_key(&patch.key_columns, key));
+                    current_key = Some(key.as_slice());
+                    updated_rows += 1;
+                }
+                println!("    {column}: {old:?} -> {new:?}");
+                updated_cells += 1;
+            }
+            PatchOp::Delete { fields } => {
+                current_key = None;
+                println!("- {}", fields.join(","));
+                deleted += 1;
+            }
+            PatchOp::Insert { fields } => {
+                current_key = None;
+                println!("+ {}", fields.join(","));
+                inserted += 1;
+            }
+        }
+    }
+
+    println!(
+        "{} row{} updated ({} cell{}), {} deleted, {} inserted",
+        format_number(updated_rows),
+        if updated_rows == 1 { "" } else { "s" },
+        format_number(updated_cells),
+        if updated_cells == 1 { "" } else { "s" },
+        format_number(deleted),
+        format_number(inserted),
+    );
+}
+
+/// Print a filter expression as an indented predicate tree.
+pub fn print_expr_tree(expr: &Expr) {
+    print_expr_node(expr, "", "");
+}
+
+fn print_expr_node(expr: &Expr, first: &str, rest: &str) {
+    let children = match expr {
+        Expr::And(parts) => {
+            println!("{first}AND");
+            parts.iter().collect::<Vec<_>>()
+        }
+        Expr::Or(parts) => {
+            println!("{first}OR");
+            parts.iter().collect()
+        }
+        Expr::Not(inner) => {
+            println!("{first}NOT");
+            vec![inner.as_ref()]
+        }
+        Expr::Compare(c) => {
+            let kind = if c.is_numeric() { "numeric" } else { "text" };
+            match c.index {
+                Some(i) => println!("{first}{c}    [{kind}, column #{i}]"),
+                None => println!("{first}{c}    [{kind}]"),
+            }
+            vec![]
+        }
+    };
+
+    for (i, child) in children.iter().enumerate() {
+        let last = i + 1 == children.len();
+        let (branch, indent) = if last {
+            ("└─ ", "   ")
+        } else {
+            ("├─ ", "│  ")
+        };
+        print_expr_node(
+            child,
+            &format!("{rest}{branch}"),
+            &format!("{rest}{indent}"),
+        );
+    }
+}
+
+fn describe_key(columns: &[String], values: &[String]) -> String {
+    columns
+        .iter()
+        .zip(values)
+        .map(|(c, v)| format!("{c}={v}"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
 /// Truncate a string to `max_len`, appending "..." if truncated.
 fn truncate(s: &str, max_len: usize) -> String {
     if s.len() <= max_len {
@@ -88,7 +421,7 @@ pub fn format_number(n: usize) -> String {
     let s = n.to_string();
     let mut result = String::with_capacity(s.len() + s.len() / 3);
     for (i, c) in s.chars().enumerate() {
-        if i > 0 && (s.len() - i) % 3 == 0 {
+        if i > 0 && (s.len() - i).is_multiple_of(3) {
             result.push(',');
         }
         result.push(c);
@@ -114,6 +447,20 @@ pub fn format_size(bytes: u64) -> String {
     }
 }
 
+/// Format a duration as a coarse age (e.g., "3h ago").
+pub fn format_age(age: std::time::Duration) -> String {
+    let secs = age.as_secs();
+    if secs < 60 {
+        format!("{secs}s ago")
+    } else if secs < 3600 {
+        format!("{}m ago", secs / 60)
+    } else if secs < 86400 {
+        format!("{}h ago", secs / 3600)
+    } else {
+        format!("{}d ago", secs / 86400)
+    }
+}
+
 /// Get a human-readable name for a delimiter byte.
 pub fn delimiter_name(delim: u8) -> &'static str {
     match delim {
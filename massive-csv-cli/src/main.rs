@@ -1,11 +1,37 @@
+#[cfg(feature = "tui")]
+mod browse;
+mod config;
 mod format;
+mod pager;
+mod progress;
 
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::io::IsTerminal;
+use std::iter::once;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 use std::process;
-use std::time::Instant;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use clap::{Parser, Subcommand};
-use massive_csv_core::{CsvEditor, CsvReader, SearchOptions};
+use clap::{Args, CommandFactory, FromArgMatches, Parser, Subcommand};
+use config::Config;
+use massive_csv_core::ndjson::is_ndjson_path;
+use massive_csv_core::parser::{serialize_row, Dialect};
+#[cfg(feature = "http")]
+use massive_csv_core::RemoteReader;
+use massive_csv_core::{
+    anonymize, checksum, concat, dedupe, diff, export, filter, join, materialize, normalize_dates,
+    pipeline, read_cell_edits, sample, searcher, select_columns, sort, split, stats, validate,
+    validate_schema, view, CellEdit, ChecksumOptions, Compression, ComputedColumns, ConcatOptions,
+    CsvEditor, CsvReader, CsvRepair, DateFormat, DateTarget, Durability, Encoding, EscapeStyle,
+    ExportFormat, ExportOptions, Expr, Filter, FixedWidth, IndexStore, JoinOptions, JoinType, Keep,
+    LineEnding, LineageManifest, LongRows, Mask, MassiveCsvError, NormalizeDatesOptions,
+    OpenOptions, Patch, PatchOutput, PipelineSpec, Provenance, Query, QuoteStyle, SampleMethod,
+    SaveOptions, SavedView, Schema, SearchOptions, SearchResult, SortKey, SortOptions,
+    ValidateOptions, VersionStore, ViewStore,
+};
+use serde_json::{json, Value};
 
 #[derive(Parser)]
 #[command(name = "massive-csv")]
@@ -14,228 +40,4714 @@ use massive_csv_core::{CsvEditor, CsvReader, SearchOptions};
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+
+    #[command(flatten)]
+    read: ReadArgs,
+
+    /// Print results, timings and errors as JSON, for scripts
+    ///
+    /// Supported by info, view, head, tail, get, search, filter, count, hash,
+    /// validate, stats, freq, query, sort, join, concat, split, sample,
+    /// compute, dedupe, anonymize, normalize-dates, apply, insert,
+    /// delete-rows, column, views list and plugins; errors are JSON for
+    /// every command.
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// Color table headers, row numbers and search matches: auto (when
+    /// printing to a terminal and NO_COLOR is unset), always or never
+    #[arg(long, global = true, value_parser = ["auto", "always", "never"], default_value = "auto")]
+    color: String,
+
+    /// Print view and search tables straight to the terminal instead of
+    /// through $PAGER
+    #[arg(long, global = true)]
+    no_pager: bool,
+
+    /// Don't show progress bars; validate prints only its summary
+    #[arg(short, long, global = true)]
+    quiet: bool,
+
+    /// Widest a table column is printed, in characters; longer values are
+    /// cut short with "..."
+    #[arg(long, global = true, default_value_t = 40, value_name = "CHARS")]
+    max_width: usize,
+
+    /// Widest one column is printed, overriding --max-width, e.g.
+    /// description=80 (repeatable)
+    #[arg(long, global = true, value_name = "COLUMN=CHARS", value_parser = parse_column_width)]
+    col_width: Vec<(String, usize)>,
+
+    /// Print tables a window of this many columns at a time, one after
+    /// another, for files too wide to read across
+    #[arg(long, global = true, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    max_cols: Option<u64>,
+
+    /// Print each row as a record of "column | value" lines, with values in
+    /// full, instead of as a table
+    #[arg(long, global = true)]
+    vertical: bool,
+
+    /// Read option defaults and aliases from this file instead of
+    /// ~/.config/massive-csv/config.toml
+    #[arg(long, global = true, value_name = "PATH")]
+    config: Option<PathBuf>,
+}
+
+/// How to read input files; accepted before or after any subcommand.
+#[derive(Args)]
+struct ReadArgs {
+    /// Field delimiter: a single character, or "tab" (default: detect)
+    #[arg(long, global = true, value_parser = parse_delimiter)]
+    delimiter: Option<u8>,
+
+    /// Quote character (default: detect)
+    #[arg(long, global = true, value_parser = parse_delimiter)]
+    quote_char: Option<u8>,
+
+    /// How quotes inside quoted fields are escaped: doubled or backslash (default: detect)
+    #[arg(long, global = true, value_parser = ["doubled", "backslash"])]
+    escape: Option<String>,
+
+    /// Recover malformed rows instead of failing on them
+    #[arg(long, global = true)]
+    lenient: bool,
+
+    /// Read a fixed-width file with these column widths, e.g. 10,25,8
+    #[arg(long, global = true, value_delimiter = ',')]
+    widths: Vec<usize>,
+
+    /// Text encoding: utf-8, latin1 (windows-1252), utf-16le or utf-16be (default: detect)
+    #[arg(long, global = true, value_parser = parse_encoding)]
+    encoding: Option<Encoding>,
+}
+
+/// Read options from a `[[files]]` entry in the config.
+#[derive(Parser)]
+#[command(no_binary_name = true)]
+struct FileReadArgs {
+    #[command(flatten)]
+    read: ReadArgs,
+}
+
+impl ReadArgs {
+    /// How to read `path`: these options, with any the command line and
+    /// `[defaults]` leave unset taken from the config's `[[files]]` entry
+    /// for it.
+    fn open_options(&self, path: &Path) -> Result<OpenOptions, Box<dyn std::error::Error>> {
+        let args = Config::global().file_args(path)?;
+        if args.is_empty() {
+            return Ok(self.options());
+        }
+        let file = FileReadArgs::try_parse_from(&args)
+            .map_err(|e| {
+                let message = e.to_string();
+                let message = message.lines().next().unwrap_or_default();
+                format!(
+                    "config [[files]] entry for {}: {}",
+                    path.display(),
+                    message.trim_start_matches("error: ")
+                )
+            })?
+            .read;
+        let read = ReadArgs {
+            delimiter: self.delimiter.or(file.delimiter),
+            quote_char: self.quote_char.or(file.quote_char),
+            escape: self.escape.clone().or(file.escape),
+            lenient: self.lenient || file.lenient,
+            widths: match self.widths.as_slice() {
+                [] => file.widths,
+                widths => widths.to_vec(),
+            },
+            encoding: self.encoding.or(file.encoding),
+        };
+        Ok(read.options())
+    }
+
+    fn options(&self) -> OpenOptions {
+        OpenOptions {
+            delimiter: self.delimiter,
+            quote_char: self.quote_char,
+            escape: self.escape.as_deref().map(|e| match e {
+                "backslash" => EscapeStyle::Backslash,
+                _ => EscapeStyle::Doubled,
+            }),
+            lenient: self.lenient,
+            fixed_width: (!self.widths.is_empty()).then(|| FixedWidth::new(self.widths.clone())),
+            encoding: self.encoding,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Show file metadata: row count, columns, size, delimiter
+    Info {
+        /// Path to the CSV file, or an http(s):// or s3:// URL
+        file: PathBuf,
+    },
+
+    /// View rows from a CSV file as a formatted table
+    View {
+        /// Path to the CSV file, or an http(s):// or s3:// URL
+        file: PathBuf,
+
+        /// Row range to display, e.g. "100-200" or "100" (default: first 20 rows);
+        /// with --view, positions in the view
+        #[arg(short, long)]
+        rows: Option<String>,
+
+        /// Show a view saved with `views save`
+        #[arg(long, value_name = "NAME")]
+        view: Option<String>,
+
+        /// Add a computed column, e.g. "total = price * qty" (repeatable; see
+        /// `compute`)
+        #[arg(long, value_name = "NAME = EXPR", conflicts_with = "view")]
+        computed: Vec<String>,
+
+        /// Columns to show, comma-separated, in order: names, 0-based indices
+        /// or index ranges like 3-7 (default: all)
+        #[arg(long, value_delimiter = ',')]
+        columns: Vec<String>,
+    },
+
+    /// Show the first rows
+    Head {
+        /// Path to the CSV file, or an http(s):// or s3:// URL
+        file: PathBuf,
+
+        /// Number of rows
+        #[arg(short = 'n', long = "lines", default_value_t = 10)]
+        lines: usize,
+
+        /// How to print rows: table, or csv (the rows as written in the
+        /// file, under a header, for piping)
+        #[arg(long, alias = "output", value_parser = ["table", "csv"], default_value = "table")]
+        format: String,
+    },
+
+    /// Show the last rows, read straight from the row index
+    Tail {
+        /// Path to the CSV file
+        file: PathBuf,
+
+        /// Number of rows
+        #[arg(short = 'n', long = "lines", default_value_t = 10)]
+        lines: usize,
+
+        /// How to print rows: table, or csv (the rows as written in the
+        /// file, under a header, for piping)
+        #[arg(long, alias = "output", value_parser = ["table", "csv"], default_value = "table")]
+        format: String,
+
+        /// Keep printing rows as they are appended to the file, until
+        /// interrupted
+        #[arg(short, long)]
+        follow: bool,
+
+        /// How often to check for new rows with --follow, in milliseconds
+        #[arg(long, default_value_t = 500, requires = "follow")]
+        interval: u64,
+    },
+
+    /// Print one cell's value, or one row as written, for shell scripts;
+    /// exits with status 1 if the row or column doesn't exist
+    Get {
+        /// Path to the CSV file
+        file: PathBuf,
+
+        /// Row number (0-indexed)
+        #[arg(long)]
+        row: usize,
+
+        /// Column name or 0-indexed column number
+        #[arg(long, required_unless_present = "raw")]
+        col: Option<String>,
+
+        /// Print the whole row as written in the file instead of one cell
+        #[arg(long, conflicts_with = "col")]
+        raw: bool,
+    },
+
+    /// Browse a file full-screen: scroll rows and columns, search with /,
+    /// jump to a row with :, edit cells with Enter and save with s
+    Browse {
+        /// Path to the CSV file
+        file: PathBuf,
+    },
+
+    /// Search for rows matching a query
+    ///
+    /// Takes `FILE QUERY`, or `QUERY FILE...` to search several files (or
+    /// glob patterns like 'logs/*.csv') at once, in parallel.
+    Search {
+        /// FILE QUERY, or QUERY FILE...
+        #[arg(required = true, num_args = 2.., value_name = "ARGS")]
+        args: Vec<String>,
+
+        /// Restrict search to a specific column name
+        #[arg(short, long)]
+        column: Option<String>,
+
+        /// Case-insensitive matching
+        #[arg(short = 'i', long)]
+        ignore_case: bool,
+
+        /// Maximum number of results, per file (default: 100, 0 = unlimited)
+        #[arg(short = 'n', long, default_value_t = 100)]
+        max_results: usize,
+
+        /// Skip this many matches first, per file
+        #[arg(long, default_value_t = 0, conflicts_with = "page")]
+        offset: usize,
+
+        /// Show this page of matches, -n per page, starting at 1
+        #[arg(long)]
+        page: Option<usize>,
+
+        /// Save the matching rows to this file instead of printing them: an
+        /// Excel workbook if it ends in .xlsx, else CSV (one input file only)
+        #[arg(short, long, value_parser = parse_search_output)]
+        output: Option<PathBuf>,
+
+        /// How to print matches: table, or csv (with a header, in the file's
+        /// dialect, streamed as they are found, for piping)
+        #[arg(long, alias = "output-format", value_parser = ["table", "csv"], default_value = "table", conflicts_with = "output")]
+        format: String,
+
+        /// Only rows also matching this filter expression, as in `filter`
+        #[arg(long = "where", value_name = "EXPR")]
+        filter: Option<String>,
+
+        /// Add a computed column to the matches, e.g. "total = price * qty"
+        /// (repeatable; see `compute`)
+        #[arg(long, value_name = "NAME = EXPR", conflicts_with = "output")]
+        computed: Vec<String>,
+
+        /// Columns to show, comma-separated, in order: names, 0-based indices
+        /// or index ranges like 3-7 (default: all)
+        #[arg(long, value_delimiter = ',')]
+        columns: Vec<String>,
+
+        /// Also show N rows before each match
+        #[arg(short = 'B', long, value_name = "N", conflicts_with = "output")]
+        before: Option<usize>,
+
+        /// Also show N rows after each match
+        #[arg(short = 'A', long, value_name = "N", conflicts_with = "output")]
+        after: Option<usize>,
+
+        /// Also show N rows before and after each match
+        #[arg(short = 'C', long, value_name = "N", conflicts_with = "output")]
+        context: Option<usize>,
+    },
+
+    /// Show rows matching a filter expression
+    Filter {
+        /// Path to the CSV file
+        file: PathBuf,
+
+        /// Filter expression, e.g. 'status == "active" && amount > 100'
+        #[arg(required_unless_present = "values_file")]
+        expr: Option<String>,
+
+        /// Keep rows whose --column value is listed in this file (one value per line)
+        #[arg(long, requires = "column")]
+        values_file: Option<PathBuf>,
+
+        /// Column matched against --values-file
+        #[arg(short, long)]
+        column: Option<String>,
+
+        /// Maximum number of results (0 = unlimited)
+        #[arg(short = 'n', long, default_value_t = 100)]
+        max_results: usize,
+
+        /// Write every matching row to this file instead of printing them; the
+        /// format follows the extension as in `export`
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Print the number of rows, or of rows matching a filter, for scripts
+    Count {
+        /// Path to the CSV file
+        file: PathBuf,
+
+        /// Count only rows matching this filter expression, as in `filter`
+        #[arg(long = "where", value_name = "EXPR")]
+        filter: Option<String>,
+    },
+
+    /// Print a SHA-256 hash of the data, to check that a copy or a
+    /// transformation didn't change it
+    Hash {
+        /// Path to the CSV file
+        file: PathBuf,
+
+        /// Hash only these columns, in this order (names, indices or ranges)
+        #[arg(short, long, value_delimiter = ',')]
+        column: Vec<String>,
+
+        /// Ignore the order of rows and whitespace around values
+        #[arg(long)]
+        normalize: bool,
+    },
+
+    /// Edit a specific cell and save
+    Edit {
+        /// Path to the CSV file
+        file: PathBuf,
+
+        /// Row number to edit (0-indexed)
+        #[arg(long, required_unless_present = "filter", conflicts_with = "filter")]
+        row: Option<usize>,
+
+        /// Edit every row matching this filter expression, as in `filter`
+        #[arg(long = "where", value_name = "EXPR")]
+        filter: Option<String>,
+
+        /// Column name or 0-indexed column number
+        #[arg(long, requires = "value", required_unless_present = "set")]
+        col: Option<String>,
+
+        /// New value for the cell
+        #[arg(long, requires = "col")]
+        value: Option<String>,
+
+        /// COLUMN=VALUE to set; repeat to change several columns in one save
+        #[arg(long = "set", value_name = "COLUMN=VALUE")]
+        set: Vec<String>,
+
+        /// Also write the change as a patch file (same format as make-patch)
+        #[arg(long, requires = "key")]
+        emit_patch: Option<PathBuf>,
+
+        /// Key column(s) for --emit-patch, comma-separated
+        #[arg(short, long, value_delimiter = ',')]
+        key: Vec<String>,
+
+        /// Copy the original file to <file>.bak before saving
+        #[arg(long)]
+        backup: bool,
+
+        /// Keep a compressed copy of the original in the version history
+        #[arg(long)]
+        snapshot: bool,
+
+        /// Sync policy before replacing the file: none, data or full (default: none)
+        #[arg(long, value_parser = ["none", "data", "full"])]
+        durability: Option<String>,
+
+        /// Write the edited file here instead of overwriting the original
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Compress the saved file: gzip, zstd or none (default: keep the source's,
+        /// or follow the --output extension)
+        #[arg(long, value_parser = parse_compression)]
+        compress: Option<Compression>,
+
+        /// Refuse the new value unless it fits the column's rules in this JSON
+        /// schema
+        #[arg(long, value_name = "SCHEMA")]
+        schema: Option<PathBuf>,
+    },
+
+    /// Export a CSV file to a new file
+    Export {
+        /// Path to the CSV file
+        file: PathBuf,
+
+        /// Path of the file to write (default: stdout, as is `-`)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Append _source_file, _source_row and _ingested_at columns to every row
+        #[arg(long)]
+        provenance: bool,
+
+        /// Name recorded in the _source_file column (default: the file name)
+        #[arg(long, requires = "provenance")]
+        source_name: Option<String>,
+
+        /// Export only rows matching this filter expression, e.g. 'status == "active"'
+        #[arg(long = "where", value_name = "EXPR")]
+        filter: Option<String>,
+
+        /// Rhai script run on every row, e.g. 'row.total = row.price * row.qty; row.qty > 0'
+        /// (assign row.<column> to transform; a final `false` drops the row)
+        #[arg(long)]
+        script: Option<String>,
+
+        /// Compress the output: gzip, zstd or none (default: from the output extension)
+        #[arg(long, value_parser = parse_compression)]
+        compress: Option<Compression>,
+
+        /// Output format: csv, json, ndjson, sqlite or xlsx (default: from the output extension)
+        #[arg(long, value_parser = ["csv", "json", "ndjson", "sqlite", "xlsx"])]
+        format: Option<String>,
+
+        /// Table to create when exporting to SQLite (default: the output file name)
+        #[arg(long)]
+        table: Option<String>,
+
+        /// Row range to export, e.g. "0-1000" (default: all rows)
+        #[arg(short, long)]
+        rows: Option<String>,
+
+        /// Columns to export, comma-separated, in output order: names, 0-based
+        /// indices or index ranges like 3-7 (default: all)
+        #[arg(long, value_delimiter = ',')]
+        columns: Vec<String>,
+    },
+
+    /// Compare two versions of a file by key and write the changes as a patch
+    MakePatch {
+        /// Path to the original CSV file
+        old: PathBuf,
+
+        /// Path to the modified CSV file
+        new: PathBuf,
+
+        /// Key column(s) identifying a row, comma-separated
+        #[arg(short, long, value_delimiter = ',', required = true)]
+        key: Vec<String>,
+
+        /// Write the patch to this file (default: stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Recover unsaved edits from a leftover edit journal and save them
+    Recover {
+        /// Path to the CSV file
+        file: PathBuf,
+
+        /// Delete the journal without applying it
+        #[arg(long)]
+        discard: bool,
+    },
+
+    /// Add, drop or rename a column and save
+    Column {
+        #[command(subcommand)]
+        action: ColumnAction,
+    },
+
+    /// List or restore versions kept by `edit --snapshot`
+    Versions {
+        #[command(subcommand)]
+        action: VersionsAction,
+    },
+
+    /// List, save or remove named views and row bookmarks of a file
+    Views {
+        #[command(subcommand)]
+        action: ViewsAction,
+    },
+
+    /// Show a row/cell-level diff of two CSV files, usable as a git diff driver
+    ///
+    /// Accepts either `OLD NEW` or the seven arguments git passes to an
+    /// external diff command (path old-file old-hex old-mode new-file new-hex new-mode).
+    GitDiff {
+        /// Files to compare
+        #[arg(num_args = 2..=7, required = true)]
+        files: Vec<PathBuf>,
+
+        /// Key column(s) identifying a row, comma-separated (default: first column)
+        #[arg(short, long, value_delimiter = ',')]
+        key: Vec<String>,
+    },
+
+    /// Apply a patch produced by make-patch to a file
+    ApplyPatch {
+        /// Path to the CSV file to patch
+        file: PathBuf,
+
+        /// Path to the .mcpatch file
+        patch: PathBuf,
+    },
+
+    /// Set many cells at once from an edit list, saving once
+    Apply {
+        /// Path to the CSV file to edit
+        file: PathBuf,
+
+        /// Edits to make: a CSV file with row, column and value columns, or
+        /// a .json file holding [{"row": 5, "column": "status", "value": "ok"}, ...].
+        /// Columns are names or 0-based indices
+        #[arg(long, required_unless_present = "plugin")]
+        patch: Option<PathBuf>,
+
+        /// Set every cell a row-transform plugin changes instead, by name (see
+        /// `plugins`) or path. Rows it filters out are left as they are
+        #[arg(long, conflicts_with = "patch")]
+        plugin: Option<String>,
+
+        /// Print the cells that would change without saving
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Delete a range of rows, or the rows matching a filter, and save
+    DeleteRows {
+        /// Path to the CSV file
+        file: PathBuf,
+
+        /// Rows to delete, e.g. "100-200" or "100"
+        #[arg(
+            short,
+            long,
+            required_unless_present = "filter",
+            conflicts_with = "filter"
+        )]
+        rows: Option<String>,
+
+        /// Delete the rows matching this filter expression, as in `filter`
+        #[arg(long = "where", value_name = "EXPR")]
+        filter: Option<String>,
+
+        /// Write the result here instead of overwriting the original
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Insert rows and save
+    Insert {
+        /// Path to the CSV file
+        file: PathBuf,
+
+        /// Row number the first new row gets (default: append at the end)
+        #[arg(long)]
+        at: Option<usize>,
+
+        /// A row to insert, written as a line of the file, e.g. "1,foo,bar"
+        /// (repeatable; missing fields are left empty)
+        #[arg(long, required = true)]
+        values: Vec<String>,
+
+        /// Write the result here instead of overwriting the original
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Convert a file to another delimiter, quoting style, line ending, encoding or compression
+    Convert {
+        /// Path to the source CSV file
+        input: PathBuf,
+
+        /// Path to write the converted file to
+        output: PathBuf,
+
+        /// Delimiter to write: a single character, or "tab" (default: keep the source's)
+        #[arg(long, value_parser = parse_delimiter)]
+        to_delimiter: Option<u8>,
+
+        /// Quote fields: necessary, always, non-numeric or never (default: necessary)
+        #[arg(long, value_parser = ["necessary", "always", "non-numeric", "never"])]
+        quote_style: Option<String>,
+
+        /// Line ending to write: lf or crlf (default: keep the source's)
+        #[arg(long, value_parser = ["lf", "crlf"])]
+        line_ending: Option<String>,
+
+        /// Text encoding to write: utf-8, latin1, utf-16le or utf-16be (default: keep the source's)
+        #[arg(long, value_parser = parse_encoding)]
+        to_encoding: Option<Encoding>,
+
+        /// Compress the output: gzip, zstd or none (default: from the output extension)
+        #[arg(long, value_parser = parse_compression)]
+        compress: Option<Compression>,
+    },
+
+    /// Run a JSON pipeline spec and write a lineage manifest next to the output
+    Pipeline {
+        /// Path to the pipeline spec (JSON)
+        spec: PathBuf,
+
+        /// Input CSV file (overrides the spec's `input`)
+        #[arg(short, long)]
+        input: Option<PathBuf>,
+
+        /// Output CSV file (overrides the spec's `output`)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// List the row-transform plugins `pipeline` and `apply` can use by name,
+    /// found in the directories in $MASSIVE_CSV_PLUGIN_PATH
+    Plugins,
+
+    /// Build, list or drop on-disk column indexes used by `filter`
+    Index {
+        /// Path to the CSV file
+        file: PathBuf,
+
+        /// Columns to index (comma-separated); lists existing indexes if omitted
+        #[arg(short, long, value_delimiter = ',')]
+        columns: Vec<String>,
+
+        /// Delete the indexes for the given columns instead of building them
+        #[arg(long, requires = "columns")]
+        drop: bool,
+    },
+
+    /// Check rows for structural problems; exits with status 1 if any are found
+    Validate {
+        /// Path to the CSV file
+        file: PathBuf,
+
+        /// Stop after this many issues (0 = unlimited)
+        #[arg(short = 'n', long, default_value_t = 1000)]
+        max_issues: usize,
+
+        /// Check values against the column types and constraints in this JSON
+        /// schema instead of checking the file's structure
+        #[arg(long, value_name = "SCHEMA")]
+        schema: Option<PathBuf>,
+    },
+
+    /// Per-column statistics: counts, numeric range, mean and spread, value
+    /// lengths and an estimate of distinct values
+    Stats {
+        /// Path to the CSV file
+        file: PathBuf,
+
+        /// Columns to summarize (comma-separated); all columns if omitted
+        #[arg(short, long, value_delimiter = ',')]
+        column: Vec<String>,
+    },
+
+    /// Most frequent values of a column, with counts and shares
+    Freq {
+        /// Path to the CSV file
+        file: PathBuf,
+
+        /// Column to count values of
+        #[arg(short, long)]
+        column: String,
+
+        /// How many values to show (0 = all)
+        #[arg(short = 'n', long, default_value_t = 20)]
+        top: usize,
+    },
+
+    /// Run a SQL query over CSV files, e.g.
+    /// "SELECT region, sum(amount) FROM 'sales.csv' GROUP BY region"
+    Query {
+        /// The query; files are quoted paths in FROM and JOIN
+        sql: String,
+
+        /// How to print the result: table, or csv (with a header, for piping)
+        #[arg(long, value_parser = ["table", "csv"], default_value = "table")]
+        format: String,
+    },
+
+    /// Write a copy sorted by one or more columns, for files of any size
+    Sort {
+        /// Path to the CSV file
+        file: PathBuf,
+
+        /// Sort keys, most significant first: column[:asc|:desc][:num|:text]
+        /// (comma-separated), e.g. date:desc,amount:asc
+        #[arg(long, value_delimiter = ',', required = true)]
+        by: Vec<String>,
+
+        /// Where to write the sorted file
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+
+    /// Append files with the same columns into one, in the first file's
+    /// column order and dialect
+    Concat {
+        /// Paths to the CSV files, in order
+        #[arg(required = true, num_args = 2..)]
+        files: Vec<PathBuf>,
+
+        /// Where to write the combined file
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Allow different columns: write all of them, leaving the ones a
+        /// file lacks empty
+        #[arg(long)]
+        union: bool,
+    },
+
+    /// Add columns computed from expressions over each row, e.g.
+    /// "total = price * qty" or "full_name = first || ' ' || last"
+    ///
+    /// Expressions use the syntax of `query` (arithmetic, ||, comparisons,
+    /// lower/upper/length/trim/abs/round/coalesce); each may use the columns
+    /// computed before it. An empty field is NULL, and so is any arithmetic
+    /// on it.
+    Compute {
+        /// Path to the CSV file
+        file: PathBuf,
+
+        /// Column definitions, as NAME = EXPR
+        #[arg(required = true)]
+        definitions: Vec<String>,
+
+        /// Write the result here instead of overwriting the original
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Split into smaller files, each with the header, by row count or by
+    /// the value of a column
+    Split {
+        /// Path to the CSV file
+        file: PathBuf,
+
+        /// Rows per output file
+        #[arg(
+            long,
+            required_unless_present = "by_column",
+            conflicts_with = "by_column"
+        )]
+        rows_per_file: Option<usize>,
+
+        /// Write one file per distinct value of this column
+        #[arg(long)]
+        by_column: Option<String>,
+
+        /// Directory for the output files (default: next to the input)
+        #[arg(short, long)]
+        output_dir: Option<PathBuf>,
+    },
+
+    /// Write a reproducible random or systematic sample of rows
+    Sample {
+        /// Path to the CSV file
+        file: PathBuf,
+
+        /// Number of rows to sample
+        #[arg(short = 'n', long)]
+        rows: usize,
+
+        /// Seed for the random choice (default: picked and printed, so the
+        /// sample can be repeated)
+        #[arg(long)]
+        seed: Option<u64>,
+
+        /// How to pick rows
+        #[arg(long, value_parser = ["random", "reservoir", "every-kth"], default_value = "random")]
+        method: String,
+
+        /// Where to write the sample
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+
+    /// Join two files on key columns into a new CSV
+    Join {
+        /// Left CSV file; output rows follow its order
+        left: PathBuf,
+
+        /// Right CSV file, indexed by key
+        right: PathBuf,
+
+        /// Key columns (comma-separated)
+        #[arg(long, value_delimiter = ',', required = true)]
+        on: Vec<String>,
+
+        /// Key columns of the right file, if named differently (comma-separated)
+        #[arg(long, value_delimiter = ',')]
+        right_on: Vec<String>,
+
+        /// Join type
+        #[arg(long = "type", value_parser = ["inner", "left", "right", "full"], default_value = "inner")]
+        join_type: String,
+
+        /// Where to write the joined file
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Compress the output: gzip, zstd or none (default: from the output extension)
+        #[arg(long, value_parser = parse_compression)]
+        compress: Option<Compression>,
+    },
+
+    /// Write a copy with one row per key, dropping duplicates
+    Dedupe {
+        /// Path to the CSV file
+        file: PathBuf,
+
+        /// Key columns (comma-separated); whole rows are compared if omitted
+        #[arg(short, long, value_delimiter = ',')]
+        key: Vec<String>,
+
+        /// Where to write the deduplicated file
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Which occurrence of a key to keep
+        #[arg(long, value_parser = ["first", "last"], default_value = "first")]
+        keep: String,
+    },
+
+    /// Write a shareable copy with sensitive columns hashed, redacted,
+    /// partly masked or randomized
+    Anonymize {
+        /// Path to the CSV file
+        file: PathBuf,
+
+        /// COLUMN=MASK, where MASK is hash, redact, lastN (e.g. last4) or randomize
+        #[arg(short, long = "column", value_name = "COLUMN=MASK", required = true)]
+        columns: Vec<String>,
+
+        /// Where to write the anonymized file
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Secret mixed into hashed and randomized values, so they can't be
+        /// recomputed from guesses without it
+        #[arg(long, default_value = "")]
+        salt: String,
+    },
+
+    /// Rewrite a date column into one format, detecting the current one and
+    /// listing values that aren't dates
+    NormalizeDates {
+        /// Path to the CSV file
+        file: PathBuf,
+
+        /// Column holding the dates
+        #[arg(short, long)]
+        column: String,
+
+        /// Target format: iso8601, or a pattern such as %d/%m/%Y
+        #[arg(long, default_value = "iso8601")]
+        to: String,
+
+        /// Current format, as a pattern, instead of detecting it
+        #[arg(long)]
+        from: Option<String>,
+
+        /// Write the result here instead of overwriting the original
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Stop listing bad values after this many (0 = no limit)
+        #[arg(long, default_value_t = 100)]
+        max_errors: usize,
+    },
+
+    /// Rewrite a broken CSV so every row is well-formed, reporting each fix
+    Repair {
+        /// Path to the CSV file
+        file: PathBuf,
+
+        /// Where to write the repaired file
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Keep extra fields in appended _overflow_N columns instead of dropping them
+        #[arg(long)]
+        overflow: bool,
+    },
+
+    /// Show how a filter expression is parsed and would be executed
+    Explain {
+        /// Filter expression, e.g. 'status == "active" && amount > 100'
+        expr: String,
+
+        /// Resolve columns and plan the scan against this file
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+    },
+
+    /// Print the man page (roff) to stdout, e.g. `massive-csv man > massive-csv.1`
+    Man,
+
+    /// Run a saved alias from the config file (lists aliases if none is given)
+    Run {
+        /// Alias name, as defined under [aliases] in ~/.config/massive-csv/config.toml
+        alias: Option<String>,
+
+        /// File the alias runs on; fills {file}, {dir} and {stem}
+        file: Option<PathBuf>,
+
+        /// Set a placeholder value, e.g. --set column=sku (repeatable)
+        #[arg(short, long, value_name = "NAME=VALUE")]
+        set: Vec<String>,
+
+        /// Extra arguments appended to the expanded command (after --)
+        #[arg(last = true)]
+        args: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ColumnAction {
+    /// Append a column
+    Add {
+        /// Path to the CSV file
+        file: PathBuf,
+
+        /// Name of the new column
+        #[arg(long)]
+        name: String,
+
+        /// Value of the new column in every row
+        #[arg(long, default_value = "")]
+        default: String,
+
+        /// Write the result here instead of overwriting the original
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Remove a column
+    Drop {
+        /// Path to the CSV file
+        file: PathBuf,
+
+        /// Column to remove
+        #[arg(long)]
+        name: String,
+
+        /// Write the result here instead of overwriting the original
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Rename a column
+    Rename {
+        /// Path to the CSV file
+        file: PathBuf,
+
+        /// Current name of the column
+        #[arg(long)]
+        from: String,
+
+        /// New name
+        #[arg(long)]
+        to: String,
+
+        /// Write the result here instead of overwriting the original
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum VersionsAction {
+    /// List stored versions of a file
+    List {
+        /// Path to the CSV file
+        file: PathBuf,
+    },
+
+    /// Replace a file with a stored version
+    Restore {
+        /// Path to the CSV file
+        file: PathBuf,
+
+        /// Version id, as shown by `versions list`
+        id: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum ViewsAction {
+    /// List the saved views and bookmarks of a file
+    List {
+        /// Path to the CSV file
+        file: PathBuf,
+    },
+
+    /// Save a view: a filter, a sort order and the columns to show
+    Save {
+        /// Path to the CSV file
+        file: PathBuf,
+
+        /// Name of the view; replaces a view with the same name
+        name: String,
+
+        /// Only rows matching this filter expression, as in `filter`
+        #[arg(long = "where", value_name = "EXPR")]
+        filter: Option<String>,
+
+        /// Sort keys, comma-separated, as in `sort`
+        #[arg(short, long, value_delimiter = ',')]
+        sort: Vec<String>,
+
+        /// Columns to show, comma-separated (default: all)
+        #[arg(short, long, value_delimiter = ',')]
+        columns: Vec<String>,
+    },
+
+    /// Remove a saved view
+    Remove {
+        /// Path to the CSV file
+        file: PathBuf,
+
+        /// Name of the view
+        name: String,
+    },
+
+    /// Bookmark a row, or remove its bookmark
+    Bookmark {
+        /// Path to the CSV file
+        file: PathBuf,
+
+        /// Row number
+        row: usize,
+
+        /// Note to keep with the bookmark
+        #[arg(short, long)]
+        label: Option<String>,
+
+        /// Remove the bookmark instead
+        #[arg(long, conflicts_with = "label")]
+        remove: bool,
+    },
+}
+
+fn main() {
+    let args: Vec<OsString> = std::env::args_os().collect();
+    let cli = match Config::init(config::path_from_args(&args).as_deref())
+        .and_then(|config| parse_args(config, args))
+    {
+        Ok(cli) => cli,
+        Err(e) => match e.downcast::<clap::Error>() {
+            Ok(e) => e.exit(),
+            Err(e) => {
+                eprintln!("Error: {e}");
+                process::exit(2);
+            }
+        },
+    };
+    format::set_max_width(cli.max_width);
+    format::set_column_widths(cli.col_width.iter().cloned().collect());
+    format::set_layout(cli.vertical, cli.max_cols.map(|n| n as usize));
+    format::set_color(match cli.color.as_str() {
+        "always" => true,
+        "never" => false,
+        _ => {
+            std::io::stdout().is_terminal()
+                && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+        }
+    });
+
+    let pager = (!cli.no_pager && pages(&cli.command, cli.json))
+        .then(pager::start)
+        .flatten();
+    progress::set_enabled(!cli.quiet && pager.is_none() && std::io::stderr().is_terminal());
+    let result = run(cli.command, &cli.read, cli.json, cli.quiet);
+    if let Some(pager) = pager {
+        pager.finish();
+    }
+    if let Err(e) = result {
+        if cli.json {
+            println!("{}", json!({ "error": e.to_string() }));
+        } else {
+            eprintln!("Error: {e}");
+        }
+        process::exit(1);
+    }
+}
+
+/// Parse `args`, the program name first, with `config`'s defaults.
+fn parse_args(
+    config: &Config,
+    args: impl IntoIterator<Item = impl Into<OsString> + Clone>,
+) -> Result<Cli, Box<dyn std::error::Error>> {
+    let matches = config
+        .apply_defaults(Cli::command())?
+        .try_get_matches_from(args)?;
+    Ok(Cli::from_arg_matches(&matches)?)
+}
+
+/// Whether `command` prints a table long enough to page.
+fn pages(command: &Commands, json: bool) -> bool {
+    match command {
+        _ if json => false,
+        Commands::View { .. } => true,
+        Commands::Search { format, output, .. } => format == "table" && output.is_none(),
+        _ => false,
+    }
+}
+
+fn run(
+    command: Commands,
+    read: &ReadArgs,
+    json: bool,
+    quiet: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        Commands::Info { file } => cmd_info(&file, read, json),
+        Commands::View {
+            file,
+            rows,
+            view,
+            computed,
+            columns,
+        } => match view {
+            Some(name) => cmd_view_saved(&file, &name, rows.as_deref(), &columns, read, json),
+            None => {
+                let columns = ColumnArgs { computed, columns };
+                cmd_view(&file, rows.as_deref(), &columns, read, json)
+            }
+        },
+        Commands::Head {
+            file,
+            lines,
+            format,
+        } => cmd_head_tail(&file, lines, false, None, format == "csv", read, json),
+        Commands::Tail {
+            file,
+            lines,
+            format,
+            follow,
+            interval,
+        } => {
+            let follow = follow.then(|| Duration::from_millis(interval));
+            cmd_head_tail(&file, lines, true, follow, format == "csv", read, json)
+        }
+        // --raw and --col are exclusive and one is required, so no column
+        // means --raw
+        Commands::Get { file, row, col, .. } => cmd_get(&file, row, col.as_deref(), read, json),
+        Commands::Browse { file } => cmd_browse(&file, read),
+        Commands::Search {
+            args,
+            column,
+            ignore_case,
+            max_results,
+            offset,
+            page,
+            output,
+            format,
+            filter,
+            computed,
+            columns,
+            before,
+            after,
+            context,
+        } => {
+            let display = MatchDisplay {
+                columns: ColumnArgs { computed, columns },
+                before: before.or(context).unwrap_or(0),
+                after: after.or(context).unwrap_or(0),
+            };
+            let offset = match page {
+                Some(0) => return Err("pages start at 1".into()),
+                Some(_) if max_results == 0 => {
+                    return Err("--page needs a page size: -n 1 or more".into())
+                }
+                Some(page) => (page - 1) * max_results,
+                None => offset,
+            };
+            let options = SearchOptions {
+                column,
+                case_insensitive: ignore_case,
+                max_results,
+                offset,
+                filter: filter.as_deref().map(Expr::parse).transpose()?,
+                ..Default::default()
+            };
+            let (query, files) = search_targets(args)?;
+            if let [_, _, ..] = files.as_slice() {
+                if output.is_some() {
+                    return Err("search --output takes a single input file".into());
+                }
+                let csv = format == "csv" && !json;
+                return cmd_search_files(&query, &files, &options, &display, csv, read, json);
+            }
+            let file = &files[0];
+            if format == "csv" && !json {
+                return cmd_search_csv(file, &query, &options, &display, read);
+            }
+            cmd_search(
+                file,
+                &query,
+                &options,
+                output.as_deref(),
+                &display,
+                read,
+                json,
+            )
+        }
+        Commands::Filter {
+            file,
+            expr,
+            values_file,
+            column,
+            max_results,
+            output,
+        } => {
+            let values = match (values_file, column) {
+                (Some(values_file), Some(column)) => Some((values_file, column)),
+                _ => None,
+            };
+            cmd_filter(
+                &file,
+                expr.as_deref(),
+                values.as_ref(),
+                max_results,
+                output.as_deref(),
+                read,
+                json,
+            )
+        }
+        Commands::Count { file, filter } => cmd_count(&file, filter.as_deref(), read, json),
+        Commands::Hash {
+            file,
+            column,
+            normalize,
+        } => {
+            let options = ChecksumOptions {
+                columns: (!column.is_empty()).then_some(column),
+                normalize,
+            };
+            cmd_hash(&file, &options, read, json)
+        }
+        Commands::Edit {
+            file,
+            row,
+            filter,
+            col,
+            value,
+            set,
+            emit_patch,
+            key,
+            backup,
+            snapshot,
+            durability,
+            output,
+            compress,
+            schema,
+        } => {
+            let options = SaveOptions {
+                patch: emit_patch.map(|path| PatchOutput {
+                    path,
+                    key_columns: key,
+                }),
+                backup,
+                snapshot,
+                durability: match durability.as_deref() {
+                    Some("data") => Durability::DataSync,
+                    Some("full") => Durability::Full,
+                    _ => Durability::None,
+                },
+                compression: compress.or(output.as_deref().map(Compression::for_path)),
+                ..Default::default()
+            };
+            let mut sets: Vec<(String, String)> = col.into_iter().zip(value).collect();
+            for pair in &set {
+                let (column, value) = pair
+                    .split_once('=')
+                    .ok_or_else(|| format!("invalid --set '{pair}': expected COLUMN=VALUE"))?;
+                sets.push((column.trim().to_string(), value.to_string()));
+            }
+            if output.is_none() {
+                require_file(&file, "edit without --output")?;
+            }
+            let mut editor = open_editor(&file, read)?;
+            if let Some(schema) = schema {
+                editor.set_schema(Some(Schema::load(&schema)?))?;
+            }
+            cmd_edit(
+                editor,
+                &file,
+                row,
+                filter.as_deref(),
+                &sets,
+                output.as_deref(),
+                &options,
+            )
+        }
+        Commands::Export {
+            file,
+            output,
+            provenance,
+            source_name,
+            filter,
+            script,
+            compress,
+            format,
+            rows,
+            columns,
+            table,
+        } => {
+            let output = output.filter(|path| !is_stdin(path));
+            let named = output.as_deref().unwrap_or(Path::new(""));
+            let options = ExportOptions {
+                provenance: provenance.then(|| Provenance {
+                    source_name,
+                    ..Default::default()
+                }),
+                filter: match filter {
+                    Some(expr) => Some(Expr::parse(&expr)?),
+                    None => None,
+                },
+                script,
+                compression: compress.unwrap_or_else(|| Compression::for_path(named)),
+                format: format
+                    .as_deref()
+                    .and_then(ExportFormat::from_name)
+                    .unwrap_or_else(|| ExportFormat::for_path(named)),
+                rows: None,
+                columns: (!columns.is_empty()).then_some(columns),
+                table,
+                ..Default::default()
+            };
+            cmd_export(&file, output.as_deref(), options, rows.as_deref(), read)
+        }
+        Commands::MakePatch {
+            old,
+            new,
+            key,
+            output,
+        } => cmd_make_patch(&old, &new, &key, output.as_deref(), read),
+        Commands::GitDiff { files, key } => cmd_git_diff(&files, &key, read),
+        Commands::ApplyPatch { file, patch } => cmd_apply_patch(&file, &patch, read),
+        Commands::Apply {
+            file,
+            patch,
+            plugin,
+            dry_run,
+        } => cmd_apply(
+            &file,
+            patch.as_deref(),
+            plugin.as_deref(),
+            dry_run,
+            read,
+            json,
+        ),
+        Commands::DeleteRows {
+            file,
+            rows,
+            filter,
+            output,
+        } => cmd_delete_rows(
+            &file,
+            rows.as_deref(),
+            filter.as_deref(),
+            output.as_deref(),
+            read,
+            json,
+        ),
+        Commands::Insert {
+            file,
+            at,
+            values,
+            output,
+        } => cmd_insert(&file, at, &values, output.as_deref(), read, json),
+        Commands::Convert {
+            input,
+            output,
+            to_delimiter,
+            quote_style,
+            line_ending,
+            to_encoding,
+            compress,
+        } => {
+            let quote_style = quote_style.map(|q| match q.as_str() {
+                "always" => QuoteStyle::Always,
+                "non-numeric" => QuoteStyle::NonNumeric,
+                "never" => QuoteStyle::Never,
+                _ => QuoteStyle::Necessary,
+            });
+            let options = SaveOptions {
+                delimiter: to_delimiter,
+                // A delimiter change alone still gets RFC 4180 quoting
+                quote_style: quote_style.or(to_delimiter.map(|_| QuoteStyle::Necessary)),
+                line_ending: line_ending.map(|le| match le.as_str() {
+                    "crlf" => LineEnding::CrLf,
+                    _ => LineEnding::Lf,
+                }),
+                encoding: to_encoding,
+                compression: Some(compress.unwrap_or_else(|| Compression::for_path(&output))),
+                ..Default::default()
+            };
+            cmd_convert(&input, &output, &options, read)
+        }
+        Commands::Pipeline {
+            spec,
+            input,
+            output,
+        } => cmd_pipeline(&spec, input, output),
+        Commands::Plugins => cmd_plugins(json),
+        Commands::Recover { file, discard } => cmd_recover(&file, discard, read),
+        Commands::Column { action } => cmd_column(action, read, json),
+        Commands::Versions { action } => match action {
+            VersionsAction::List { file } => cmd_versions_list(&file),
+            VersionsAction::Restore { file, id } => cmd_versions_restore(&file, &id),
+        },
+        Commands::Views { action } => match action {
+            ViewsAction::List { file } => cmd_views_list(&file, json),
+            ViewsAction::Save {
+                file,
+                name,
+                filter,
+                sort,
+                columns,
+            } => {
+                let view = SavedView {
+                    name,
+                    filter,
+                    sort,
+                    columns,
+                };
+                cmd_views_save(&file, view, read)
+            }
+            ViewsAction::Remove { file, name } => {
+                if !ViewStore::for_file(&file)?.remove_view(&name)? {
+                    return Err(format!("no view named '{name}'").into());
+                }
+                println!("Removed view '{name}'");
+                Ok(())
+            }
+            ViewsAction::Bookmark {
+                file,
+                row,
+                label,
+                remove,
+            } => cmd_views_bookmark(&file, row, label, remove, read),
+        },
+        Commands::Index {
+            file,
+            columns,
+            drop,
+        } => cmd_index(&file, &columns, drop, read),
+        Commands::Validate {
+            file,
+            max_issues,
+            schema,
+        } => match schema {
+            Some(schema) => {
+                let schema = Schema::load(&schema)?;
+                cmd_validate_schema(&file, &schema, max_issues, quiet, read, json)
+            }
+            None => cmd_validate(&file, max_issues, quiet, read, json),
+        },
+        Commands::Stats { file, column } => cmd_stats(&file, &column, read, json),
+        Commands::Freq { file, column, top } => cmd_freq(&file, &column, top, read, json),
+        Commands::Query { sql, format } => cmd_query(&sql, &format, read, json),
+        Commands::Sort { file, by, output } => cmd_sort(&file, &by, &output, read, json),
+        Commands::Concat {
+            files,
+            output,
+            union,
+        } => cmd_concat(&files, &output, union, read, json),
+        Commands::Compute {
+            file,
+            definitions,
+            output,
+        } => cmd_compute(&file, &definitions, output.as_deref(), read, json),
+        Commands::Split {
+            file,
+            rows_per_file,
+            by_column,
+            output_dir,
+        } => cmd_split(
+            &file,
+            rows_per_file,
+            by_column.as_deref(),
+            output_dir.as_deref(),
+            read,
+            json,
+        ),
+        Commands::Sample {
+            file,
+            rows,
+            seed,
+            method,
+            output,
+        } => {
+            let method = SampleMethod::from_name(&method).unwrap_or_default();
+            cmd_sample(&file, rows, method, seed, &output, read, json)
+        }
+        Commands::Join {
+            left,
+            right,
+            on,
+            right_on,
+            join_type,
+            output,
+            compress,
+        } => {
+            let options = JoinOptions {
+                join_type: JoinType::from_name(&join_type).unwrap_or_default(),
+                left_on: on,
+                right_on,
+                compression: compress.unwrap_or_else(|| Compression::for_path(&output)),
+                ..JoinOptions::default()
+            };
+            cmd_join(&left, &right, &output, &options, read, json)
+        }
+        Commands::Dedupe {
+            file,
+            key,
+            output,
+            keep,
+        } => {
+            let keep = if keep == "last" {
+                Keep::Last
+            } else {
+                Keep::First
+            };
+            cmd_dedupe(&file, &key, keep, &output, read, json)
+        }
+        Commands::Anonymize {
+            file,
+            columns,
+            output,
+            salt,
+        } => cmd_anonymize(&file, &columns, &output, &salt, read, json),
+        Commands::NormalizeDates {
+            file,
+            column,
+            to,
+            from,
+            output,
+            max_errors,
+        } => {
+            let options = NormalizeDatesOptions {
+                from: from.as_deref().map(DateFormat::parse).transpose()?,
+                to: DateTarget::parse(&to)?,
+                max_errors,
+                ..NormalizeDatesOptions::default()
+            };
+            cmd_normalize_dates(&file, &column, &options, output.as_deref(), read, json)
+        }
+        Commands::Repair {
+            file,
+            output,
+            overflow,
+        } => cmd_repair(&file, &output, overflow, read),
+        Commands::Explain { expr, file } => cmd_explain(&expr, file.as_deref(), read),
+        Commands::Man => cmd_man(),
+        Commands::Run {
+            alias,
+            file,
+            set,
+            args,
+        } => cmd_run(alias.as_deref(), file.as_deref(), &set, &args, json, quiet),
+    }
+}
+
+/// Below this, `info` warns that the detected delimiter may be wrong.
+const LOW_DELIMITER_CONFIDENCE: f64 = 0.8;
+
+fn cmd_info(path: &Path, read: &ReadArgs, json: bool) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(url) = remote_url(path) {
+        return cmd_info_remote(url, read, json);
+    }
+    let start = Instant::now();
+    let reader = open_reader(path, read)?;
+    let elapsed = start.elapsed();
+
+    // A stream has no file of its own; report the data that was read
+    let size = if is_stdin(path) {
+        reader.data_len()
+    } else {
+        std::fs::metadata(path)?.len()
+    };
+    let headers = reader.canonical_headers();
+    let dialect = reader.dialect();
+    let confidence = reader.delimiter_confidence();
+
+    if json {
+        let renamed: serde_json::Map<String, Value> = reader
+            .headers()
+            .iter()
+            .zip(headers)
+            .filter(|(raw, canonical)| raw != canonical)
+            .map(|(raw, canonical)| (raw.clone(), json!(canonical)))
+            .collect();
+        return print_json(&json!({
+            "file": display_path(path).to_string(),
+            "size": size,
+            "compression": match reader.compression() {
+                Compression::None => None,
+                compression => Some(compression.to_string()),
+            },
+            "uncompressed_size": reader.data_len(),
+            "rows": reader.row_count(),
+            "columns": headers.len(),
+            "encoding": reader.encoding().to_string(),
+            "fixed_width": reader.fixed_width().map(|layout| &layout.widths),
+            "delimiter": (dialect.delimiter as char).to_string(),
+            "delimiter_confidence": confidence,
+            "quote": (dialect.quote as char).to_string(),
+            "escape": match dialect.escape {
+                EscapeStyle::Doubled => "doubled",
+                EscapeStyle::Backslash => "backslash",
+            },
+            "headers": headers,
+            "renamed": renamed,
+            "load_time_ms": millis(elapsed),
+        }));
+    }
+
+    // Show first 10 headers, abbreviate if more
+    let header_display = if headers.len() <= 10 {
+        headers.join(", ")
+    } else {
+        format!(
+            "{}, ... (+{} more)",
+            headers[..10].join(", "),
+            headers.len() - 10
+        )
+    };
+
+    println!("File:       {}", display_path(path));
+    match reader.compression() {
+        Compression::None => println!("Size:       {}", format::format_size(size)),
+        compression => println!(
+            "Size:       {} ({compression}, {} uncompressed)",
+            format::format_size(size),
+            format::format_size(reader.data_len())
+        ),
+    }
+    println!("Rows:       {}", format::format_number(reader.row_count()));
+    println!("Columns:    {}", headers.len());
+    println!("Encoding:   {}", reader.encoding());
+    if let Some(layout) = reader.fixed_width() {
+        let widths: Vec<String> = layout.widths.iter().map(|w| w.to_string()).collect();
+        println!("Layout:     fixed width ({})", widths.join(", "));
+    } else {
+        println!(
+            "Delimiter:  {} (confidence {:.0}%)",
+            format::delimiter_name(dialect.delimiter),
+            confidence * 100.0
+        );
+        println!(
+            "Quoting:    {} (escaped {})",
+            dialect.quote as char,
+            match dialect.escape {
+                EscapeStyle::Doubled => "by doubling",
+                EscapeStyle::Backslash => "with backslash",
+            }
+        );
+    }
+    println!("Headers:    {header_display}");
+    if reader.has_irregular_headers() {
+        let renamed: Vec<String> = reader
+            .headers()
+            .iter()
+            .zip(headers)
+            .filter(|(raw, canonical)| raw != canonical)
+            .map(|(raw, canonical)| format!("{raw:?} -> {canonical}"))
+            .collect();
+        println!("Renamed:    {} (empty or duplicate)", renamed.join(", "));
+    }
+    println!("Load time:  {:.2?}", elapsed);
+    if confidence < LOW_DELIMITER_CONFIDENCE {
+        eprintln!(
+            "\nWarning: the delimiter guess is uncertain; rows may not split into the columns above."
+        );
+    }
+
+    Ok(())
+}
+
+fn cmd_view(
+    path: &Path,
+    rows_arg: Option<&str>,
+    columns: &ColumnArgs,
+    read: &ReadArgs,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(url) = remote_url(path) {
+        if !columns.computed.is_empty() {
+            return Err("--computed needs a local file".into());
+        }
+        return cmd_view_remote(url, rows_arg, &columns.columns, read, json);
+    }
+    let reader = open_reader(path, read)?;
+    let projection = columns.resolve(reader.canonical_headers())?;
+    let headers = projection.headers(reader.canonical_headers());
+    let row_count = reader.row_count();
+
+    let (start, end) = parse_row_range(rows_arg, row_count)?;
+
+    if start >= row_count {
+        return Err(MassiveCsvError::RowOutOfRange(start, row_count).into());
+    }
+
+    let mut rows = reader.get_rows(start, end)?;
+    let row_numbers: Vec<usize> = (start..start + rows.len()).collect();
+    for row in &mut rows {
+        projection.apply(row);
+    }
+
+    let mut malformed = Vec::new();
+    if read.lenient {
+        for &row in &row_numbers {
+            if let (_, Some(issue)) = reader.get_row_checked(row)? {
+                malformed.push((row, issue.to_string()));
+            }
+        }
+    }
+
+    if json {
+        let malformed: Vec<Value> = malformed
+            .iter()
+            .map(|(row, issue)| json!({ "row": row, "issue": issue }))
+            .collect();
+        return print_json(&json!({
+            "headers": headers,
+            "rows": json_rows(&row_numbers, &rows),
+            "malformed": malformed,
+        }));
+    }
+
+    format::print_table(&headers, &rows, &row_numbers);
+
+    if !malformed.is_empty() {
+        println!("\nRecovered {} malformed row(s):", malformed.len());
+        for (row, issue) in malformed {
+            println!("  Row {row}: {issue}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Print the first `n` rows, or the last if `tail`, as a table or as
+/// written in the file.
+/// `head` or `tail`. With `follow`, keep checking the file for appended
+/// rows at that interval and print them as they come.
+fn cmd_head_tail(
+    path: &Path,
+    n: usize,
+    tail: bool,
+    follow: Option<Duration>,
+    csv: bool,
+    read: &ReadArgs,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if remote_url(path).is_some() {
+        if tail || csv {
+            return Err(format!(
+                "{} needs a local file",
+                if tail { "tail" } else { "head --format csv" }
+            )
+            .into());
+        }
+        let rows = format!("0-{}", n.max(1) - 1);
+        return cmd_view(path, Some(&rows), &ColumnArgs::default(), read, json);
+    }
+    if follow.is_some() && is_stdin(path) {
+        return Err("tail --follow needs a file".into());
+    }
+    let mut reader = open_reader(path, read)?;
+    // When following, the last row may be cut mid-write, so it waits for
+    // its line ending
+    let complete = |reader: &CsvReader| match follow {
+        Some(_) if !reader.has_trailing_newline() => reader.row_count().saturating_sub(1),
+        _ => reader.row_count(),
+    };
+    let row_count = complete(&reader);
+    let mut rows = if tail {
+        row_count.saturating_sub(n)..row_count
+    } else {
+        0..n.min(row_count)
+    };
+
+    let Some(interval) = follow else {
+        print_head_tail(&reader, rows, csv, json, true)?;
+        return Ok(());
+    };
+    if !print_head_tail(&reader, rows.clone(), csv, json, true)? {
+        return Ok(());
+    }
+    loop {
+        std::thread::sleep(interval);
+        reader.refresh_appended()?;
+        let new_rows = rows.end..complete(&reader);
+        if !new_rows.is_empty() {
+            rows = new_rows;
+            if !print_head_tail(&reader, rows.clone(), csv, json, false)? {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Print `rows` for `head`/`tail`: as a table, raw CSV (under the header
+/// when `first`), or JSON. New rows found by `tail --follow` are printed as
+/// they come, as JSON lines with `--json`. Returns false once nothing is
+/// reading the output.
+fn print_head_tail(
+    reader: &CsvReader,
+    rows: Range<usize>,
+    csv: bool,
+    json: bool,
+    first: bool,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    use std::io::Write;
+
+    if csv && !json {
+        let line_ending = reader.line_ending().as_str();
+        let mut out = std::io::stdout().lock();
+        let written = (|| -> massive_csv_core::Result<()> {
+            if first {
+                write!(
+                    out,
+                    "{}{line_ending}",
+                    serialize_row(reader.headers(), reader.dialect())
+                )?;
+            }
+            for row in rows {
+                write!(out, "{}{line_ending}", reader.get_row_raw(row)?)?;
+            }
+            out.flush()?;
+            Ok(())
+        })();
+        return match written {
+            // The reader went away, e.g. `| head`
+            Err(MassiveCsvError::Io(e)) if e.kind() == std::io::ErrorKind::BrokenPipe => Ok(false),
+            other => Ok(other.map(|()| true)?),
+        };
+    }
+
+    let row_numbers: Vec<usize> = rows.clone().collect();
+    let fields = reader.get_rows(rows.start, rows.end)?;
+    if json && first {
+        print_json(&json!({
+            "headers": reader.canonical_headers(),
+            "total_rows": reader.row_count(),
+            "rows": json_rows(&row_numbers, &fields),
+        }))?;
+    } else if json {
+        for row in json_rows(&row_numbers, &fields) {
+            println!("{row}");
+        }
+    } else if !fields.is_empty() || first {
+        format::print_table(reader.canonical_headers(), &fields, &row_numbers);
+    }
+    std::io::stdout().flush()?;
+    Ok(true)
+}
+
+/// Print column `col` of row `row`, or the row's text with no column.
+fn cmd_get(
+    path: &Path,
+    row: usize,
+    col: Option<&str>,
+    read: &ReadArgs,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let reader = open_reader(path, read)?;
+    let Some(col) = col else {
+        let raw = reader.get_row_raw(row)?;
+        if json {
+            return print_json(&json!({ "row": row, "raw": raw }));
+        }
+        println!("{raw}");
+        return Ok(());
+    };
+
+    let headers = reader.canonical_headers();
+    let col_idx = reader
+        .column_index(col)
+        .or_else(|| col.parse::<usize>().ok().filter(|&i| i < headers.len()))
+        .ok_or_else(|| {
+            format!(
+                "Column '{}' not found. Available: {}",
+                col,
+                headers.join(", ")
+            )
+        })?;
+    let mut fields = reader.get_row(row)?;
+    let value = fields
+        .get_mut(col_idx)
+        .map(std::mem::take)
+        .unwrap_or_default();
+    if json {
+        return print_json(&json!({
+            "row": row,
+            "column": headers[col_idx],
+            "value": value,
+        }));
+    }
+    println!("{value}");
+    Ok(())
+}
+
+#[cfg(feature = "tui")]
+fn cmd_browse(path: &Path, read: &ReadArgs) -> Result<(), Box<dyn std::error::Error>> {
+    require_file(path, "browse")?;
+    if !std::io::stdout().is_terminal() {
+        return Err("browse needs a terminal; use view or head to print rows".into());
+    }
+    let editor = open_editor(path, read)?;
+    let name = path.file_name().map_or_else(
+        || path.display().to_string(),
+        |n| n.to_string_lossy().into_owned(),
+    );
+    browse::browse(editor, name)
+}
+
+#[cfg(not(feature = "tui"))]
+fn cmd_browse(_path: &Path, _read: &ReadArgs) -> Result<(), Box<dyn std::error::Error>> {
+    Err(MassiveCsvError::FeatureDisabled("The terminal browser", "tui").into())
+}
+
+fn cmd_view_saved(
+    path: &Path,
+    name: &str,
+    rows_arg: Option<&str>,
+    columns: &[String],
+    read: &ReadArgs,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    require_local(path, "view --view")?;
+    let store = ViewStore::for_file(path)?;
+    let saved = store
+        .view(name)
+        .ok_or_else(|| format!("no view named '{name}' (see `views list`)"))?;
+    let reader = open_reader(path, read)?;
+    let view = saved.apply(&reader)?;
+    // --columns replaces the view's own
+    let columns = match columns {
+        [] => saved.column_indices(&reader)?,
+        columns => select_columns(reader.canonical_headers(), columns)?,
+    };
+
+    let (start, end) = parse_row_range(rows_arg, view.row_count())?;
+    if start >= view.row_count() && view.row_count() > 0 {
+        return Err(MassiveCsvError::RowOutOfRange(start, view.row_count()).into());
+    }
+    let row_numbers: Vec<usize> = view.rows()[start.min(end)..end].to_vec();
+    let rows: Vec<Vec<String>> = view
+        .get_rows(start, end)?
+        .into_iter()
+        .map(|fields| view::project(&fields, &columns))
+        .collect();
+    let headers = view::project(reader.canonical_headers(), &columns);
+
+    if json {
+        return print_json(&json!({
+            "view": name,
+            "headers": headers,
+            "view_rows": view.row_count(),
+            "rows": json_rows(&row_numbers, &rows),
+        }));
+    }
+
+    format::print_table(&headers, &rows, &row_numbers);
+    println!(
+        "\nView '{name}': {} of {} rows",
+        format::format_number(view.row_count()),
+        format::format_number(reader.row_count())
+    );
+    Ok(())
+}
+
+/// `--computed` and `--columns`: columns to add to each row, and which
+/// columns to show.
+#[derive(Default)]
+struct ColumnArgs {
+    computed: Vec<String>,
+    columns: Vec<String>,
+}
+
+impl ColumnArgs {
+    /// Resolve against a file's canonical `headers`. `--columns` may name
+    /// computed columns too.
+    fn resolve(&self, headers: &[String]) -> Result<Projection, Box<dyn std::error::Error>> {
+        let computed = ComputedColumns::parse(&self.computed, headers)?;
+        let columns = match self.columns.as_slice() {
+            [] => None,
+            columns => Some(select_columns(&computed.headers(headers), columns)?),
+        };
+        Ok(Projection { computed, columns })
+    }
+}
+
+/// What [`ColumnArgs`] do to a file's rows.
+struct Projection {
+    computed: ComputedColumns,
+    columns: Option<Vec<usize>>,
+}
+
+impl Projection {
+    /// The header row for the file's `headers` (raw or canonical).
+    fn headers(&self, headers: &[String]) -> Vec<String> {
+        let headers = self.computed.headers(headers);
+        match &self.columns {
+            Some(columns) => view::project(&headers, columns),
+            None => headers,
+        }
+    }
+
+    /// Add the computed values to a row's `fields` and keep the selected
+    /// columns.
+    fn apply(&self, fields: &mut Vec<String>) {
+        self.computed.extend(fields);
+        if let Some(columns) = &self.columns {
+            *fields = view::project(fields, columns);
+        }
+    }
+}
+
+/// How `search` shows each match: the columns, and how many rows around it.
+struct MatchDisplay {
+    columns: ColumnArgs,
+    before: usize,
+    after: usize,
+}
+
+impl MatchDisplay {
+    /// The matches in `results` with the rows around them, or `None` when
+    /// no context was asked for.
+    fn context(
+        &self,
+        reader: &CsvReader,
+        results: &[SearchResult],
+        projection: &Projection,
+    ) -> massive_csv_core::Result<Option<ContextRows>> {
+        if self.before == 0 && self.after == 0 {
+            return Ok(None);
+        }
+        let matches: Vec<usize> = results.iter().map(|r| r.row_num).collect();
+        let windows =
+            searcher::context_windows(&matches, self.before, self.after, reader.row_count());
+        let mut context = ContextRows::default();
+        for window in windows {
+            for (row, mut fields) in window
+                .clone()
+                .zip(reader.get_rows(window.start, window.end)?)
+            {
+                projection.apply(&mut fields);
+                context.row_numbers.push(row);
+                context.rows.push(fields);
+                context.is_match.push(matches.binary_search(&row).is_ok());
+            }
+        }
+        Ok(Some(context))
+    }
+}
+
+/// Search matches and the rows around them, in file order.
+#[derive(Default)]
+struct ContextRows {
+    row_numbers: Vec<usize>,
+    rows: Vec<Vec<String>>,
+    is_match: Vec<bool>,
+}
+
+impl ContextRows {
+    /// The rows as `{"row", "fields", "match"}` objects, for `--json`.
+    fn to_json(&self) -> Value {
+        (0..self.rows.len())
+            .map(|i| {
+                json!({
+                    "row": self.row_numbers[i],
+                    "fields": self.rows[i],
+                    "match": self.is_match[i],
+                })
+            })
+            .collect()
+    }
+}
+
+/// Print `value` on stdout, for `--json`.
+fn print_json(value: &Value) -> Result<(), Box<dyn std::error::Error>> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+/// Rows as `{"row": <number>, "fields": [...]}` objects, for `--json`.
+fn json_rows(row_numbers: &[usize], rows: &[Vec<String>]) -> Vec<Value> {
+    row_numbers
+        .iter()
+        .zip(rows)
+        .map(|(row, fields)| json!({ "row": row, "fields": fields }))
+        .collect()
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// `path` as a URL, if it is one.
+fn remote_url(path: &Path) -> Option<&str> {
+    let path = path.to_str()?;
+    ["http://", "https://", "s3://"]
+        .iter()
+        .any(|scheme| path.starts_with(scheme))
+        .then_some(path)
+}
+
+#[cfg(feature = "http")]
+fn cmd_info_remote(
+    url: &str,
+    read: &ReadArgs,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let start = Instant::now();
+    let reader = RemoteReader::open_url_with(url, &read.open_options(Path::new(url))?)?;
+    let elapsed = start.elapsed();
+    let headers = reader.canonical_headers();
+
+    if json {
+        // The row count isn't known without scanning the whole file
+        return print_json(&json!({
+            "file": url,
+            "size": reader.len(),
+            "rows": null,
+            "columns": headers.len(),
+            "fixed_width": reader.fixed_width().map(|layout| &layout.widths),
+            "delimiter": (reader.delimiter() as char).to_string(),
+            "headers": headers,
+            "load_time_ms": millis(elapsed),
+        }));
+    }
+
+    println!("File:       {url}");
+    println!("Size:       {}", format::format_size(reader.len()));
+    println!("Rows:       unknown (remote file, not scanned)");
+    println!("Columns:    {}", headers.len());
+    match reader.fixed_width() {
+        Some(layout) => {
+            let widths: Vec<String> = layout.widths.iter().map(|w| w.to_string()).collect();
+            println!("Layout:     fixed width ({})", widths.join(", "));
+        }
+        None => println!("Delimiter:  {}", format::delimiter_name(reader.delimiter())),
+    }
+    println!("Headers:    {}", headers.join(", "));
+    println!("Load time:  {:.2?}", elapsed);
+    Ok(())
+}
+
+#[cfg(feature = "http")]
+fn cmd_view_remote(
+    url: &str,
+    rows_arg: Option<&str>,
+    columns: &[String],
+    read: &ReadArgs,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let reader = RemoteReader::open_url_with(url, &read.open_options(Path::new(url))?)?;
+    let projection = ColumnArgs {
+        computed: Vec::new(),
+        columns: columns.to_vec(),
+    }
+    .resolve(reader.canonical_headers())?;
+    // The row count isn't known without scanning the whole file
+    let (start, end) = parse_row_range(rows_arg, usize::MAX)?;
+    let mut rows = reader.get_rows(start, end)?;
+    if rows.is_empty() {
+        return Err(MassiveCsvError::RowOutOfRange(start, reader.rows_indexed()).into());
+    }
+    let row_numbers: Vec<usize> = (start..start + rows.len()).collect();
+    for row in &mut rows {
+        projection.apply(row);
+    }
+    let headers = projection.headers(reader.canonical_headers());
+    if json {
+        return print_json(&json!({
+            "headers": headers,
+            "rows": json_rows(&row_numbers, &rows),
+        }));
+    }
+    format::print_table(&headers, &rows, &row_numbers);
+    Ok(())
+}
+
+#[cfg(not(feature = "http"))]
+fn cmd_info_remote(
+    _url: &str,
+    _read: &ReadArgs,
+    _json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    Err(MassiveCsvError::FeatureDisabled("Opening URLs", "http").into())
+}
+
+#[cfg(not(feature = "http"))]
+fn cmd_view_remote(
+    _url: &str,
+    _rows_arg: Option<&str>,
+    _columns: &[String],
+    _read: &ReadArgs,
+    _json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    Err(MassiveCsvError::FeatureDisabled("Opening URLs", "http").into())
+}
+
+/// Open `path` as `read` describes; `-` reads stdin, and `.ndjson` or
+/// `.jsonl` files are read as JSON Lines.
+fn open_reader(path: &Path, read: &ReadArgs) -> Result<CsvReader, Box<dyn std::error::Error>> {
+    if is_ndjson_path(path) {
+        return Ok(CsvReader::from_ndjson(path)?);
+    }
+    if is_stdin(path) {
+        return Ok(CsvReader::from_reader_with(
+            std::io::stdin().lock(),
+            &read.open_options(path)?,
+        )?);
+    }
+    let bar = progress::Bar::new("Indexing", "bytes");
+    Ok(CsvReader::open_with_progress(
+        path,
+        &read.open_options(path)?,
+        &bar.progress(),
+    )?)
+}
+
+fn open_editor(path: &Path, read: &ReadArgs) -> Result<CsvEditor, Box<dyn std::error::Error>> {
+    Ok(CsvEditor::new(open_reader(path, read)?))
+}
+
+/// Whether `path` is `-`, meaning stdin (or stdout, for an output).
+fn is_stdin(path: &Path) -> bool {
+    path.as_os_str() == "-"
+}
+
+fn display_path(path: &Path) -> std::path::Display<'_> {
+    if is_stdin(path) {
+        Path::new("<stdin>").display()
+    } else {
+        path.display()
+    }
+}
+
+/// Fail if `path` is stdin or NDJSON, for commands that write back to their input.
+fn require_file(path: &Path, command: &str) -> Result<(), Box<dyn std::error::Error>> {
+    if is_stdin(path) {
+        return Err(format!("{command} writes to its input file and can't read stdin").into());
+    }
+    if is_ndjson_path(path) {
+        return Err(format!("{command} writes to its input file, which can't be NDJSON").into());
+    }
+    Ok(())
+}
+
+/// Saved views live next to a local file, so `command` can't use stdin or a URL.
+fn require_local(path: &Path, command: &str) -> Result<(), Box<dyn std::error::Error>> {
+    if is_stdin(path) || remote_url(path).is_some() {
+        return Err(format!("{command} needs a local file").into());
+    }
+    Ok(())
+}
+
+fn cmd_search(
+    path: &Path,
+    query: &str,
+    options: &SearchOptions,
+    output: Option<&Path>,
+    display: &MatchDisplay,
+    read: &ReadArgs,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let reader = open_reader(path, read)?;
+    let projection = display.columns.resolve(reader.canonical_headers())?;
+    let headers = projection.headers(reader.canonical_headers());
+
+    let start = Instant::now();
+    let bar = progress::Bar::new("Searching", "rows");
+    let search_options = SearchOptions {
+        // One match past the limit tells whether there is another page
+        max_results: match options.max_results {
+            0 => 0,
+            max => max + 1,
+        },
+        progress: bar.progress(),
+        ..options.clone()
+    };
+    let mut results = massive_csv_core::search(&reader, query, &search_options)?;
+    bar.finish();
+    let elapsed = start.elapsed();
+    let more = options.max_results > 0 && results.len() > options.max_results;
+    results.truncate(if more {
+        options.max_results
+    } else {
+        results.len()
+    });
+    let context = display.context(&reader, &results, &projection)?;
+    for result in &mut results {
+        projection.apply(&mut result.fields);
+    }
+
+    let total = results.len();
+    if json {
+        let row_numbers: Vec<usize> = results.iter().map(|r| r.row_num).collect();
+        let rows: Vec<Vec<String>> = results.into_iter().map(|r| r.fields).collect();
+        if let Some(output) = output {
+            save_rows(&reader, &projection, output, &rows)?;
+        }
+        let mut value = json!({
+            "matches": total,
+            "offset": options.offset,
+            "more": more,
+            "rows_searched": reader.row_count(),
+            "elapsed_ms": millis(elapsed),
+            "saved_to": output.map(|p| p.display().to_string()),
+            "headers": headers,
+            "results": json_rows(&row_numbers, &rows),
+        });
+        if let Some(context) = &context {
+            value["context"] = context.to_json();
+        }
+        return print_json(&value);
+    }
+    let skipped = match options.offset {
+        0 => String::new(),
+        n => format!(" after the first {}", format::format_number(n)),
+    };
+    println!(
+        "Found {} match{}{skipped} (searched {} rows in {:.2?}):\n",
+        format::format_number(total),
+        if total == 1 { "" } else { "es" },
+        format::format_number(reader.row_count()),
+        elapsed,
+    );
+
+    if results.is_empty() {
+        return Ok(());
+    }
+
+    let row_numbers: Vec<usize> = results.iter().map(|r| r.row_num).collect();
+    let rows: Vec<Vec<String>> = results.into_iter().map(|r| r.fields).collect();
+
+    if let Some(output) = output {
+        save_rows(&reader, &projection, output, &rows)?;
+        println!(
+            "Saved {} rows to {}",
+            format::format_number(rows.len()),
+            output.display()
+        );
+        return Ok(());
+    }
+    let highlight = format::Highlight::new(query, options, &headers);
+    match context {
+        Some(c) => format::print_matches(
+            &headers,
+            &c.rows,
+            &c.row_numbers,
+            Some(&c.is_match),
+            highlight.as_ref(),
+        ),
+        None => format::print_matches(&headers, &rows, &row_numbers, None, highlight.as_ref()),
+    }
+    if more {
+        println!(
+            "\nMore matches follow: add --offset {} for the next {}",
+            options.offset + total,
+            format::format_number(options.max_results)
+        );
+    }
+
+    Ok(())
+}
+
+/// Split `search`'s arguments into the query and the files to search. Two
+/// arguments are `FILE QUERY` unless only the second names a file or is a
+/// glob pattern; any more are `QUERY FILE...`, where each file may be a
+/// glob pattern.
+fn search_targets(
+    mut args: Vec<String>,
+) -> Result<(String, Vec<PathBuf>), Box<dyn std::error::Error>> {
+    let exists = |arg: &str| {
+        let path = Path::new(arg);
+        is_stdin(path) || remote_url(path).is_some() || path.exists()
+    };
+    if let [file, query] = args.as_slice() {
+        if exists(file) || !(exists(query) || expand_glob(query).is_some()) {
+            return Ok((query.clone(), vec![PathBuf::from(file)]));
+        }
+    }
+    let query = args.remove(0);
+    let mut files = Vec::new();
+    for arg in &args {
+        match expand_glob(arg) {
+            Some(matches) if matches.is_empty() => {
+                return Err(format!("no files match '{arg}'").into())
+            }
+            Some(matches) => files.extend(matches),
+            None => files.push(PathBuf::from(arg)),
+        }
+    }
+    Ok((query, files))
+}
+
+/// The files matching `pattern`, sorted, or `None` if it isn't a glob
+/// pattern (or names an existing file as written).
+fn expand_glob(pattern: &str) -> Option<Vec<PathBuf>> {
+    if !pattern.contains(['*', '?', '[']) || Path::new(pattern).exists() {
+        return None;
+    }
+    let matches = glob::glob(pattern).ok()?;
+    let mut files: Vec<PathBuf> = matches
+        .filter_map(|m| m.ok())
+        .filter(|p| p.is_file())
+        .collect();
+    files.sort();
+    Some(files)
+}
+
+/// Matches of `search` in one of several files.
+struct FileMatches {
+    headers: Vec<String>,
+    rows_searched: usize,
+    results: Vec<SearchResult>,
+    context: Option<ContextRows>,
+}
+
+/// Search `files` in parallel, then print each file's matches in order,
+/// under its name (as a `file` column with `csv`). A file that can't be
+/// searched is reported and skipped.
+fn cmd_search_files(
+    query: &str,
+    files: &[PathBuf],
+    options: &SearchOptions,
+    display: &MatchDisplay,
+    csv: bool,
+    read: &ReadArgs,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    use std::io::Write;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    // Bars for files searched side by side would draw over each other
+    progress::set_enabled(false);
+    let search_file = |path: &Path| -> Result<FileMatches, Box<dyn std::error::Error>> {
+        let reader = open_reader(path, read)?;
+        let projection = display.columns.resolve(reader.canonical_headers())?;
+        let mut results = massive_csv_core::search(&reader, query, options)?;
+        let context = display.context(&reader, &results, &projection)?;
+        for result in &mut results {
+            projection.apply(&mut result.fields);
+        }
+        Ok(FileMatches {
+            headers: projection.headers(reader.canonical_headers()),
+            rows_searched: reader.row_count(),
+            results,
+            context,
+        })
+    };
+
+    // Each file's search is itself parallel, so a few files at a time keep
+    // every core busy while opening (indexing) overlaps
+    let start = Instant::now();
+    let next = AtomicUsize::new(0);
+    let found: Vec<Mutex<Option<Result<FileMatches, String>>>> =
+        files.iter().map(|_| Mutex::new(None)).collect();
+    let workers = std::thread::available_parallelism()
+        .map_or(4, |n| n.get())
+        .min(files.len());
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(path) = files.get(i) else { break };
+                let result = search_file(path).map_err(|e| e.to_string());
+                *found[i].lock().unwrap() = Some(result);
+            });
+        }
+    });
+    let elapsed = start.elapsed();
+    let found: Vec<(&PathBuf, Result<FileMatches, String>)> = files
+        .iter()
+        .zip(found)
+        .map(|(path, slot)| {
+            (
+                path,
+                slot.into_inner().unwrap().expect("every file searched"),
+            )
+        })
+        .collect();
+
+    let mut failed = 0;
+    for (path, result) in &found {
+        if let Err(e) = result {
+            eprintln!("Error: {}: {e}", display_path(path));
+            failed += 1;
+        }
+    }
+    let searched: Vec<(&PathBuf, &FileMatches)> = found
+        .iter()
+        .filter_map(|(path, result)| result.as_ref().ok().map(|m| (*path, m)))
+        .collect();
+    let total: usize = searched.iter().map(|(_, m)| m.results.len()).sum();
+    let rows_searched: usize = searched.iter().map(|(_, m)| m.rows_searched).sum();
+
+    if json {
+        let files_json: Vec<Value> = searched
+            .iter()
+            .map(|(path, m)| {
+                let row_numbers: Vec<usize> = m.results.iter().map(|r| r.row_num).collect();
+                let rows: Vec<Vec<String>> = m.results.iter().map(|r| r.fields.clone()).collect();
+                let mut value = json!({
+                    "file": display_path(path).to_string(),
+                    "matches": m.results.len(),
+                    "rows_searched": m.rows_searched,
+                    "headers": m.headers,
+                    "results": json_rows(&row_numbers, &rows),
+                });
+                if let Some(context) = &m.context {
+                    value["context"] = context.to_json();
+                }
+                value
+            })
+            .collect();
+        print_json(&json!({
+            "matches": total,
+            "rows_searched": rows_searched,
+            "elapsed_ms": millis(elapsed),
+            "files": files_json,
+        }))?;
+    } else if csv {
+        // A header row starts the output and each file whose columns differ
+        let dialect = Dialect::default();
+        let mut out = std::io::stdout().lock();
+        let mut last_headers: Option<&[String]> = None;
+        let written = (|| -> std::io::Result<()> {
+            for (path, m) in &searched {
+                if m.results.is_empty() {
+                    continue;
+                }
+                let name = display_path(path).to_string();
+                if last_headers != Some(m.headers.as_slice()) {
+                    let header = once("file".to_string()).chain(m.headers.iter().cloned());
+                    writeln!(
+                        out,
+                        "{}",
+                        serialize_row(&header.collect::<Vec<_>>(), dialect)
+                    )?;
+                    last_headers = Some(&m.headers);
+                }
+                let rows = match &m.context {
+                    Some(context) => context.rows.iter().collect::<Vec<_>>(),
+                    None => m.results.iter().map(|r| &r.fields).collect(),
+                };
+                for fields in rows {
+                    let row = once(name.clone()).chain(fields.iter().cloned());
+                    writeln!(out, "{}", serialize_row(&row.collect::<Vec<_>>(), dialect))?;
+                }
+            }
+            out.flush()
+        })();
+        match written {
+            // The reader went away, e.g. `| head`
+            Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => return Ok(()),
+            other => other?,
+        }
+        eprintln!(
+            "Found {} match{} in {} file{} (searched {} rows in {:.2?})",
+            format::format_number(total),
+            if total == 1 { "" } else { "es" },
+            format::format_number(searched.len()),
+            if searched.len() == 1 { "" } else { "s" },
+            format::format_number(rows_searched),
+            elapsed,
+        );
+    } else {
+        println!(
+            "Found {} match{} in {} file{} (searched {} rows in {:.2?})",
+            format::format_number(total),
+            if total == 1 { "" } else { "es" },
+            format::format_number(searched.len()),
+            if searched.len() == 1 { "" } else { "s" },
+            format::format_number(rows_searched),
+            elapsed,
+        );
+        for (path, m) in &searched {
+            if m.results.is_empty() {
+                continue;
+            }
+            let count = m.results.len();
+            println!(
+                "\n{}: {} match{}",
+                display_path(path),
+                format::format_number(count),
+                if count == 1 { "" } else { "es" }
+            );
+            let highlight = format::Highlight::new(query, options, &m.headers);
+            match &m.context {
+                Some(c) => format::print_matches(
+                    &m.headers,
+                    &c.rows,
+                    &c.row_numbers,
+                    Some(&c.is_match),
+                    highlight.as_ref(),
+                ),
+                None => {
+                    let row_numbers: Vec<usize> = m.results.iter().map(|r| r.row_num).collect();
+                    let rows: Vec<Vec<String>> =
+                        m.results.iter().map(|r| r.fields.clone()).collect();
+                    format::print_matches(
+                        &m.headers,
+                        &rows,
+                        &row_numbers,
+                        None,
+                        highlight.as_ref(),
+                    );
+                }
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(format!("{failed} of {} files could not be searched", files.len()).into());
+    }
+    Ok(())
+}
+
+/// Print the matches as CSV, writing each batch as soon as it is searched.
+fn cmd_search_csv(
+    path: &Path,
+    query: &str,
+    options: &SearchOptions,
+    display: &MatchDisplay,
+    read: &ReadArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    use std::io::Write;
+
+    let reader = open_reader(path, read)?;
+    let projection = display.columns.resolve(reader.canonical_headers())?;
+    let dialect = reader.dialect();
+    let line_ending = reader.line_ending().as_str();
+    let mut out = std::io::stdout().lock();
+
+    let start = Instant::now();
+    let written = (|| -> massive_csv_core::Result<usize> {
+        write!(
+            out,
+            "{}{line_ending}",
+            serialize_row(&projection.headers(reader.headers()), dialect)
+        )?;
+        // Context rows are written once, however many matches they are near
+        let mut written_to = 0;
+        let found = searcher::search_each(&reader, query, options, &mut |result| {
+            let first = result
+                .row_num
+                .saturating_sub(display.before)
+                .max(written_to);
+            let end = (result.row_num + display.after + 1).min(reader.row_count());
+            for row in first..end {
+                let mut fields = if row == result.row_num {
+                    result.fields.clone()
+                } else {
+                    reader.get_row(row)?
+                };
+                projection.apply(&mut fields);
+                write!(out, "{}{line_ending}", serialize_row(&fields, dialect))?;
+            }
+            written_to = written_to.max(end);
+            Ok(())
+        })?;
+        out.flush()?;
+        Ok(found)
+    })();
+    let found = match written {
+        Ok(found) => found,
+        // The reader went away, e.g. `| head`
+        Err(MassiveCsvError::Io(e)) if e.kind() == std::io::ErrorKind::BrokenPipe => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    eprintln!(
+        "Found {} match{} (searched {} rows in {:.2?})",
+        format::format_number(found),
+        if found == 1 { "" } else { "es" },
+        format::format_number(reader.row_count()),
+        start.elapsed(),
+    );
+    Ok(())
+}
+
+/// Write `rows` under the header row `projection` gives them: an Excel
+/// workbook for `.xlsx`, else CSV in the source file's dialect.
+fn save_rows(
+    reader: &CsvReader,
+    projection: &Projection,
+    path: &Path,
+    rows: &[Vec<String>],
+) -> Result<(), Box<dyn std::error::Error>> {
+    if ExportFormat::for_path(path) == ExportFormat::Xlsx {
+        return save_xlsx(&projection.headers(reader.canonical_headers()), path, rows);
+    }
+    let line_ending = reader.line_ending().as_str();
+    let mut out =
+        serialize_row(&projection.headers(reader.headers()), reader.dialect()) + line_ending;
+    for row in rows {
+        out.push_str(&serialize_row(row, reader.dialect()));
+        out.push_str(line_ending);
+    }
+    std::fs::write(path, out)?;
+    Ok(())
+}
+
+#[cfg(feature = "xlsx")]
+fn save_xlsx(
+    headers: &[String],
+    path: &Path,
+    rows: &[Vec<String>],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut workbook = massive_csv_core::XlsxWriter::new(headers)?;
+    for row in rows {
+        workbook.write_row(row)?;
+    }
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    workbook.finish(&mut file)?;
+    file.into_inner().map_err(|e| e.into_error())?;
+    Ok(())
+}
+
+#[cfg(not(feature = "xlsx"))]
+fn save_xlsx(_: &[String], _: &Path, _: &[Vec<String>]) -> Result<(), Box<dyn std::error::Error>> {
+    Err(MassiveCsvError::FeatureDisabled("Saving Excel files", "xlsx").into())
+}
+
+/// Print how many rows of `path` match `expr`, or how many it has.
+fn cmd_count(
+    path: &Path,
+    expr: Option<&str>,
+    read: &ReadArgs,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let reader = open_reader(path, read)?;
+    let start = Instant::now();
+    let count = match expr {
+        Some(expr) => Filter::parse(expr, reader.headers())?.count_matching(&reader)?,
+        None => reader.row_count(),
+    };
+    if json {
+        return print_json(&json!({
+            "count": count,
+            "rows_searched": reader.row_count(),
+            "elapsed_ms": millis(start.elapsed()),
+        }));
+    }
+    println!("{count}");
+    Ok(())
+}
+
+fn cmd_hash(
+    path: &Path,
+    options: &ChecksumOptions,
+    read: &ReadArgs,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let reader = open_reader(path, read)?;
+    let start = Instant::now();
+    let result = checksum(&reader, options)?;
+    if json {
+        return print_json(&json!({
+            "hash": result.hash,
+            "algorithm": "sha256",
+            "rows": result.rows,
+            "columns": result.columns,
+            "normalized": options.normalize,
+            "elapsed_ms": millis(start.elapsed()),
+        }));
+    }
+    // Laid out like sha256sum's output
+    println!("{}  {}", result.hash, path.display());
+    Ok(())
+}
+
+fn cmd_filter(
+    path: &Path,
+    expr: Option<&str>,
+    values: Option<&(PathBuf, String)>,
+    max_results: usize,
+    output: Option<&Path>,
+    read: &ReadArgs,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let reader = open_reader(path, read)?;
+
+    let mut parts = Vec::new();
+    if let Some(expr) = expr {
+        parts.push(Expr::parse(expr)?);
+    }
+    if let Some((values_file, column)) = values {
+        let text = std::fs::read_to_string(values_file)
+            .map_err(|e| format!("cannot read {}: {e}", values_file.display()))?;
+        let values: Vec<String> = text
+            .lines()
+            .map(|line| line.trim_end_matches('\r'))
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect();
+        parts.push(Expr::Compare(filter::Comparison::new(
+            column.as_str(),
+            filter::CmpOp::In,
+            values,
+        )));
+    }
+    let expr = match parts.len() {
+        1 => parts.pop().expect("one part"),
+        _ => Expr::And(parts),
+    };
+
+    if let Some(output) = output {
+        let bar = progress::Bar::new("Filtering", "rows");
+        let options = ExportOptions {
+            filter: Some(expr),
+            compression: Compression::for_path(output),
+            format: ExportFormat::for_path(output),
+            progress: bar.progress(),
+            ..ExportOptions::default()
+        };
+        let start = Instant::now();
+        let summary = export::export(&reader, output, &options)?;
+        bar.finish();
+        let elapsed = start.elapsed();
+        if json {
+            return print_json(&json!({
+                "output": output,
+                "matches": summary.rows_written,
+                "rows_searched": reader.row_count(),
+                "elapsed_ms": millis(elapsed),
+            }));
+        }
+        println!(
+            "Wrote {} matching row{} of {} -> {} ({:.2?})",
+            format::format_number(summary.rows_written),
+            if summary.rows_written == 1 { "" } else { "s" },
+            format::format_number(reader.row_count()),
+            output.display(),
+            elapsed
+        );
+        return Ok(());
+    }
+
+    let filter = Filter::new(expr, reader.headers())?;
+
+    let start = Instant::now();
+    let row_numbers = filter.matching_rows(&reader, max_results)?;
+    let elapsed = start.elapsed();
+
+    let total = row_numbers.len();
+    let rows = row_numbers
+        .iter()
+        .map(|&row| reader.get_row(row))
+        .collect::<Result<Vec<_>, _>>()?;
+    if json {
+        return print_json(&json!({
+            "matches": total,
+            "rows_searched": reader.row_count(),
+            "elapsed_ms": millis(elapsed),
+            "headers": reader.canonical_headers(),
+            "results": json_rows(&row_numbers, &rows),
+        }));
+    }
+    println!(
+        "Found {} match{} (filtered {} rows in {:.2?}):\n",
+        format::format_number(total),
+        if total == 1 { "" } else { "es" },
+        format::format_number(reader.row_count()),
+        elapsed,
+    );
+
+    if row_numbers.is_empty() {
+        return Ok(());
+    }
+
+    format::print_table(reader.canonical_headers(), &rows, &row_numbers);
+
+    Ok(())
+}
+
+/// Set `sets` (column, value) in `row`, or in every row matching `filter`,
+/// and save once.
+fn cmd_edit(
+    mut editor: CsvEditor,
+    path: &Path,
+    row: Option<usize>,
+    filter: Option<&str>,
+    sets: &[(String, String)],
+    output: Option<&Path>,
+    options: &SaveOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let headers: Vec<String> = editor.reader().canonical_headers().to_vec();
+
+    // Resolve columns: try name first, then numeric index
+    let columns = sets
+        .iter()
+        .map(|(col, _)| {
+            editor
+                .reader()
+                .column_index(col)
+                .or_else(|| col.parse::<usize>().ok().filter(|&i| i < headers.len()))
+                .ok_or_else(|| {
+                    format!(
+                        "Column '{}' not found. Available: {}",
+                        col,
+                        headers.join(", ")
+                    )
+                })
+        })
+        .collect::<Result<Vec<usize>, String>>()?;
+
+    let rows = match (row, filter) {
+        (Some(row), _) => vec![row],
+        (None, Some(expr)) => {
+            Filter::parse(expr, &headers)?.matching_rows(editor.reader(), usize::MAX)?
+        }
+        (None, None) => unreachable!("clap requires --row or --where"),
+    };
+    if rows.is_empty() {
+        println!("No rows match; nothing changed.");
+        return Ok(());
+    }
+
+    // Old values for display, when editing a single row
+    let old_row = match row {
+        Some(row) => Some(editor.get_row(row)?),
+        None => None,
+    };
+
+    let edits: Vec<CellEdit> = rows
+        .iter()
+        .flat_map(|&row| {
+            sets.iter()
+                .map(move |(col, value)| CellEdit::new(row, col.clone(), value.clone()))
+        })
+        .collect();
+    let changed = editor.apply_cell_edits(&edits)?;
+    let target = output.unwrap_or(path);
+    let backup_path = (options.backup && target.exists()).then(|| options.backup_path(target));
+    save_editor(&mut editor, Some(target), options)?;
+
+    match old_row {
+        Some(old_row) => {
+            for (&col, (_, value)) in columns.iter().zip(sets) {
+                let old_value = old_row.get(col).map(|s| s.as_str()).unwrap_or("<missing>");
+                println!(
+                    "Updated row {}, column \"{}\": \"{}\" -> \"{}\"",
+                    format::format_number(rows[0]),
+                    headers[col],
+                    old_value,
+                    value
+                );
+            }
+        }
+        None => println!(
+            "Updated {} of {} matching row(s).",
+            format::format_number(changed),
+            format::format_number(rows.len())
+        ),
+    }
+    if output.is_some() {
+        println!("Saved to {}.", target.display());
+    } else {
+        println!("Saved.");
+    }
+    if let Some(p) = backup_path {
+        println!("Backup written to {}", p.display());
+    }
+    if let Some(p) = &options.patch {
+        println!("Patch written to {}", p.path.display());
+    }
+
+    Ok(())
+}
+
+fn cmd_export(
+    path: &Path,
+    output: Option<&Path>,
+    mut options: ExportOptions,
+    rows_arg: Option<&str>,
+    read: &ReadArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let reader = open_reader(path, read)?;
+    if rows_arg.is_some() {
+        let (start, end) = parse_row_range(rows_arg, reader.row_count())?;
+        options.rows = Some(start..end);
+    }
+
+    let start = Instant::now();
+    let bar = progress::Bar::new("Exporting", "rows");
+    options.progress = bar.progress();
+    let Some(output) = output else {
+        let mut stdout = std::io::stdout().lock();
+        export::export_to_writer(&reader, &mut stdout, &options)?;
+        return Ok(());
+    };
+    let summary = export::export(&reader, output, &options)?;
+    bar.finish();
+    let elapsed = start.elapsed();
+
+    println!(
+        "Exported {} rows to {} in {:.2?}",
+        format::format_number(summary.rows_written),
+        output.display(),
+        elapsed
+    );
+    if summary.rows_filtered > 0 {
+        println!(
+            "Filtered out {} rows",
+            format::format_number(summary.rows_filtered)
+        );
+    }
+
+    Ok(())
+}
+
+fn cmd_make_patch(
+    old: &Path,
+    new: &Path,
+    key: &[String],
+    output: Option<&Path>,
+    read: &ReadArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let old_reader = open_reader(old, read)?;
+    let new_reader = open_reader(new, read)?;
+
+    let start = Instant::now();
+    let patch = diff::diff(&old_reader, &new_reader, key)?;
+    let elapsed = start.elapsed();
+
+    match output {
+        Some(out) => {
+            patch.save(out)?;
+            eprintln!(
+                "Wrote {} change{} to {} (compared {} rows in {:.2?})",
+                format::format_number(patch.len()),
+                if patch.len() == 1 { "" } else { "s" },
+                out.display(),
+                format::format_number(new_reader.row_count()),
+                elapsed,
+            );
+        }
+        None => patch.write_to(std::io::stdout().lock())?,
+    }
+
+    Ok(())
+}
+
+fn cmd_git_diff(
+    files: &[PathBuf],
+    key: &[String],
+    read: &ReadArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (old, new) = match files {
+        [old, new] => (old, new),
+        [_path, old, _, _, new, _, _] => (old, new),
+        _ => return Err("expected OLD NEW or the 7 arguments git passes to a diff driver".into()),
+    };
+
+    // git passes /dev/null (an empty file) for added or deleted files
+    let old_reader = open_unless_empty(old, read)?;
+    let new_reader = open_unless_empty(new, read)?;
+
+    println!("--- {}", old.display());
+    println!("+++ {}", new.display());
+
+    let (old_reader, new_reader) = match (old_reader, new_reader) {
+        (Some(o), Some(n)) => (o, n),
+        (None, Some(n)) => return print_all_rows(&n, '+'),
+        (Some(o), None) => return print_all_rows(&o, '-'),
+        (None, None) => return Ok(()),
+    };
+
+    if old_reader.headers() != new_reader.headers() {
+        println!(
+            "! headers changed: {} -> {}",
+            old_reader.headers().join(","),
+            new_reader.headers().join(",")
+        );
+        return Ok(());
+    }
+
+    let key = if key.is_empty() {
+        vec![old_reader.headers()[0].clone()]
+    } else {
+        key.to_vec()
+    };
+
+    let patch = diff::diff(&old_reader, &new_reader, &key)?;
+    format::print_patch(&patch);
+
+    Ok(())
+}
+
+fn open_unless_empty(
+    path: &Path,
+    read: &ReadArgs,
+) -> Result<Option<CsvReader>, Box<dyn std::error::Error>> {
+    let options = read.open_options(path)?;
+    let opened = if is_stdin(path) {
+        CsvReader::from_reader_with(std::io::stdin().lock(), &options)
+    } else {
+        CsvReader::open_with(path, &options)
+    };
+    match opened {
+        Err(MassiveCsvError::EmptyFile) => Ok(None),
+        result => Ok(Some(result?)),
+    }
+}
+
+fn print_all_rows(reader: &CsvReader, marker: char) -> Result<(), Box<dyn std::error::Error>> {
+    for i in 0..reader.row_count() {
+        println!("{marker} {}", reader.get_row_raw(i)?);
+    }
+    Ok(())
+}
+
+fn cmd_apply_patch(
+    path: &Path,
+    patch_path: &Path,
+    read: &ReadArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    require_file(path, "apply-patch")?;
+    let patch = Patch::load(patch_path)?;
+    let reader = open_reader(path, read)?;
+
+    let summary = diff::apply_patch(&reader, &patch, path)?;
+
+    println!(
+        "Applied patch: {} cell{} updated in {} row{}, {} deleted, {} inserted",
+        format::format_number(summary.updated_cells),
+        if summary.updated_cells == 1 { "" } else { "s" },
+        format::format_number(summary.updated_rows),
+        if summary.updated_rows == 1 { "" } else { "s" },
+        format::format_number(summary.deleted),
+        format::format_number(summary.inserted),
+    );
+    println!("Saved.");
+
+    Ok(())
+}
+
+fn cmd_apply(
+    path: &Path,
+    edits_path: Option<&Path>,
+    plugin: Option<&str>,
+    dry_run: bool,
+    read: &ReadArgs,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !dry_run {
+        require_file(path, "apply")?;
+    }
+    let mut editor = open_editor(path, read)?;
+    let edits: Vec<CellEdit> = match (edits_path, plugin) {
+        (_, Some(plugin)) => plugin_cell_edits(plugin, editor.reader())?,
+        (Some(edits_path), None) if edits_path.extension().is_some_and(|e| e == "json") => {
+            serde_json::from_str(&std::fs::read_to_string(edits_path)?)?
+        }
+        (Some(edits_path), None) => read_cell_edits(edits_path)?,
+        (None, None) => unreachable!("clap requires --patch or --plugin"),
+    };
+    let rows_changed = editor.apply_cell_edits(&edits)?;
+
+    // (row, column, old, new) for every cell that changes
+    let mut rows: Vec<usize> = edits.iter().map(|e| e.row).collect();
+    rows.sort_unstable();
+    rows.dedup();
+    let headers = editor.headers().to_vec();
+    let mut changes = Vec::new();
+    for row in rows {
+        let old = editor.reader().get_row(row)?;
+        let new = editor.get_row(row)?;
+        for (col, value) in new.iter().enumerate() {
+            let before = old.get(col).map_or("", |v| v.as_str());
+            if before != value {
+                changes.push((row, &headers[col], before.to_string(), value.clone()));
+            }
+        }
+    }
+
+    if !dry_run {
+        save_editor(&mut editor, None, &SaveOptions::default())?;
+    }
+    if json {
+        let changes: Vec<Value> = changes
+            .iter()
+            .map(|(row, column, old, new)| {
+                json!({ "row": row, "column": column, "old": old, "new": new })
+            })
+            .collect();
+        return print_json(&json!({
+            "edits": edits.len(),
+            "rows_changed": rows_changed,
+            "cells_changed": changes.len(),
+            "changes": changes,
+            "saved": !dry_run,
+        }));
+    }
+    if dry_run {
+        let mut current = None;
+        for (row, column, old, new) in &changes {
+            if current != Some(*row) {
+                println!("~ row {}", format::format_number(*row));
+                current = Some(*row);
+            }
+            println!("    {column}: {old:?} -> {new:?}");
+        }
+    }
+    println!(
+        "{} {} cell{} in {} row{} ({} edit{} listed){}",
+        if dry_run { "Would change" } else { "Changed" },
+        format::format_number(changes.len()),
+        if changes.len() == 1 { "" } else { "s" },
+        format::format_number(rows_changed),
+        if rows_changed == 1 { "" } else { "s" },
+        format::format_number(edits.len()),
+        if edits.len() == 1 { "" } else { "s" },
+        if dry_run { "; nothing saved" } else { "" },
+    );
+    Ok(())
+}
+
+fn cmd_delete_rows(
+    path: &Path,
+    rows_arg: Option<&str>,
+    filter: Option<&str>,
+    output: Option<&Path>,
+    read: &ReadArgs,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if output.is_none() {
+        require_file(path, "delete-rows without --output")?;
+    }
+    let mut editor = open_editor(path, read)?;
+    let before = editor.row_count();
+    match filter {
+        Some(expr) => {
+            let filter = Filter::parse(expr, editor.reader().headers())?;
+            let rows = filter.matching_rows(editor.reader(), 0)?;
+            editor.delete_rows_at(&rows)?;
+        }
+        None => {
+            let (start, end) = parse_row_range(rows_arg, before)?;
+            if start >= before {
+                return Err(MassiveCsvError::RowOutOfRange(start, before).into());
+            }
+            editor.delete_rows(start..end)?;
+        }
+    }
+    let deleted = before - editor.row_count();
+    save_editor(&mut editor, output, &SaveOptions::default())?;
+
+    if json {
+        return print_json(&json!({
+            "deleted": deleted,
+            "rows": editor.row_count(),
+            "saved_to": output.unwrap_or(path).display().to_string(),
+        }));
+    }
+    println!(
+        "Deleted {} row{}; {} left.",
+        format::format_number(deleted),
+        if deleted == 1 { "" } else { "s" },
+        format::format_number(editor.row_count())
+    );
+    Ok(())
+}
+
+fn cmd_insert(
+    path: &Path,
+    at: Option<usize>,
+    values: &[String],
+    output: Option<&Path>,
+    read: &ReadArgs,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if output.is_none() {
+        require_file(path, "insert without --output")?;
+    }
+    let mut editor = open_editor(path, read)?;
+    let rows = values
+        .iter()
+        .map(|line| editor.reader().parse_raw(line))
+        .collect::<massive_csv_core::Result<Vec<_>>>()?;
+    let at = at.unwrap_or(editor.row_count());
+    editor.insert_rows(at, rows)?;
+    save_editor(&mut editor, output, &SaveOptions::default())?;
+
+    if json {
+        return print_json(&json!({
+            "inserted": values.len(),
+            "at": at,
+            "rows": editor.row_count(),
+            "saved_to": output.unwrap_or(path).display().to_string(),
+        }));
+    }
+    println!(
+        "Inserted {} row{} at row {}; {} rows now.",
+        format::format_number(values.len()),
+        if values.len() == 1 { "" } else { "s" },
+        format::format_number(at),
+        format::format_number(editor.row_count())
+    );
+    Ok(())
+}
+
+fn cmd_column(
+    action: ColumnAction,
+    read: &ReadArgs,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (path, output) = match &action {
+        ColumnAction::Add { file, output, .. }
+        | ColumnAction::Drop { file, output, .. }
+        | ColumnAction::Rename { file, output, .. } => (file.as_path(), output.as_deref()),
+    };
+    if output.is_none() {
+        require_file(path, "column without --output")?;
+    }
+    let mut editor = open_editor(path, read)?;
+    let (verb, message) = match &action {
+        ColumnAction::Add { name, default, .. } => {
+            editor.add_column(name, default)?;
+            ("added", format!("Added column '{name}'"))
+        }
+        ColumnAction::Drop { name, .. } => {
+            editor.drop_column(name)?;
+            ("dropped", format!("Dropped column '{name}'"))
+        }
+        ColumnAction::Rename { from, to, .. } => {
+            editor.rename_column(from, to)?;
+            ("renamed", format!("Renamed column '{from}' to '{to}'"))
+        }
+    };
+    save_editor(&mut editor, output, &SaveOptions::default())?;
+
+    if json {
+        return print_json(&json!({
+            "action": verb,
+            "headers": editor.headers(),
+            "saved_to": output.unwrap_or(path).display().to_string(),
+        }));
+    }
+    println!("{message}; {} columns now.", editor.headers().len());
+    Ok(())
+}
+
+/// Save `editor` to `output`, or over its own file when there is none,
+/// showing a progress bar.
+fn save_editor(
+    editor: &mut CsvEditor,
+    output: Option<&Path>,
+    options: &SaveOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let bar = progress::Bar::new("Saving", "rows");
+    let options = SaveOptions {
+        progress: bar.progress(),
+        ..options.clone()
+    };
+    match output {
+        Some(output) => editor.save_as(output, &options)?,
+        None => editor.save_with_options(&options)?,
+    }
+    Ok(())
+}
+
+fn cmd_convert(
+    input: &Path,
+    output: &Path,
+    options: &SaveOptions,
+    read: &ReadArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut editor = open_editor(input, read)?;
+
+    let start = Instant::now();
+    save_editor(&mut editor, Some(output), options)?;
+    let elapsed = start.elapsed();
+
+    println!(
+        "Converted {} rows to {} in {:.2?}",
+        format::format_number(editor.reader().row_count()),
+        output.display(),
+        elapsed
+    );
+
+    Ok(())
+}
+
+fn cmd_pipeline(
+    spec_path: &Path,
+    input: Option<PathBuf>,
+    output: Option<PathBuf>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let spec = PipelineSpec::load(spec_path)?;
+    let input = input
+        .or_else(|| spec.input.clone())
+        .ok_or("no input file: set `input` in the spec or pass --input")?;
+    let output = output
+        .or_else(|| spec.output.clone())
+        .ok_or("no output file: set `output` in the spec or pass --output")?;
+
+    let reader = CsvReader::open(&input)?;
+
+    let start = Instant::now();
+    let manifest = pipeline::run(&reader, &spec, &output)?;
+    let elapsed = start.elapsed();
+
+    let manifest_path = LineageManifest::path_for(&output);
+    manifest.save(&manifest_path)?;
+
+    for step in &manifest.steps {
+        println!(
+            "  {:>3}. {:<10} {} -> {} rows",
+            step.index + 1,
+            step.op,
+            format::format_number(step.rows_in),
+            format::format_number(step.rows_out),
+        );
+    }
+    println!(
+        "Wrote {} of {} rows to {} in {:.2?}",
+        format::format_number(manifest.rows_out),
+        format::format_number(manifest.rows_in),
+        output.display(),
+        elapsed
+    );
+    println!("Lineage: {}", manifest_path.display());
+
+    Ok(())
+}
+
+#[cfg(feature = "plugins")]
+fn cmd_plugins(json: bool) -> Result<(), Box<dyn std::error::Error>> {
+    use massive_csv_core::plugin::{discover, plugin_dirs, PLUGIN_PATH_VAR};
+
+    let plugins = discover();
+    if json {
+        let plugins: Vec<Value> = plugins
+            .iter()
+            .map(|(name, path)| json!({ "name": name, "path": path }))
+            .collect();
+        return print_json(&json!({ "dirs": plugin_dirs(), "plugins": plugins }));
+    }
+    if plugin_dirs().is_empty() {
+        println!("No plugin directories: set ${PLUGIN_PATH_VAR}");
+        return Ok(());
+    }
+    if plugins.is_empty() {
+        println!("No plugins found in ${PLUGIN_PATH_VAR}");
+    }
+    for (name, path) in &plugins {
+        println!("{name:<20} {}", path.display());
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "plugins"))]
+fn cmd_plugins(_json: bool) -> Result<(), Box<dyn std::error::Error>> {
+    Err(MassiveCsvError::FeatureDisabled("Plugins", "plugins").into())
+}
+
+#[cfg(feature = "plugins")]
+fn plugin_cell_edits(
+    name: &str,
+    reader: &CsvReader,
+) -> Result<Vec<CellEdit>, Box<dyn std::error::Error>> {
+    Ok(massive_csv_core::Plugin::find(name)?.cell_edits(reader)?)
+}
+
+#[cfg(not(feature = "plugins"))]
+fn plugin_cell_edits(_: &str, _: &CsvReader) -> Result<Vec<CellEdit>, Box<dyn std::error::Error>> {
+    Err(MassiveCsvError::FeatureDisabled("Plugins", "plugins").into())
+}
+
+fn cmd_index(
+    path: &Path,
+    columns: &[String],
+    drop: bool,
+    read: &ReadArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    require_file(path, "index")?;
+    let reader = open_reader(path, read)?;
+    let store = IndexStore::for_file(path);
+
+    if drop {
+        for column in columns {
+            if store.remove(&reader, column)? {
+                println!("Dropped index on {column}");
+            } else {
+                println!("No index on {column}");
+            }
+        }
+        return Ok(());
+    }
+
+    if columns.is_empty() {
+        let infos = store.list(&reader)?;
+        if infos.is_empty() {
+            println!("No indexes for {}", path.display());
+            return Ok(());
+        }
+        println!("Indexes in {}:\n", store.dir().display());
+        for info in infos {
+            println!(
+                "  {:<20} {:>12} values  {:>10}  {}",
+                info.column,
+                format::format_number(info.values),
+                format::format_size(info.size),
+                if info.fresh { "fresh" } else { "stale" },
+            );
+        }
+        return Ok(());
+    }
+
+    for column in columns {
+        let start = Instant::now();
+        let bar = progress::Bar::new(&format!("Indexing {column}"), "rows");
+        let info = store.build_with_progress(&reader, column, &bar.progress())?;
+        bar.finish();
+        println!(
+            "Indexed {} ({} distinct values, {} rows, {}) in {:.2?}",
+            info.column,
+            format::format_number(info.values),
+            format::format_number(info.rows),
+            format::format_size(info.size),
+            start.elapsed()
+        );
+    }
+
+    Ok(())
+}
+
+fn cmd_query(
+    sql: &str,
+    format: &str,
+    read: &ReadArgs,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let query = Query::parse(sql)?;
+    let readers = query
+        .tables()
+        .into_iter()
+        .map(|path| open_reader(Path::new(path), read))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let start = Instant::now();
+    let result = query.execute(&readers)?;
+    let elapsed = start.elapsed();
+
+    if json {
+        return print_json(&json!({
+            "columns": result.columns,
+            "rows": result.rows,
+            "rows_scanned": result.rows_scanned,
+            "elapsed_ms": millis(elapsed),
+        }));
+    }
+    if format == "csv" {
+        println!("{}", serialize_row(&result.columns, b','));
+        for row in &result.rows {
+            println!("{}", serialize_row(row, b','));
+        }
+        return Ok(());
+    }
+    format::print_table(&result.columns, &result.rows, &[]);
+    println!(
+        "\n{} row{} (scanned {} rows in {:.2?})",
+        format::format_number(result.rows.len()),
+        if result.rows.len() == 1 { "" } else { "s" },
+        format::format_number(result.rows_scanned),
+        elapsed
+    );
+    Ok(())
+}
+
+fn cmd_concat(
+    paths: &[PathBuf],
+    output: &Path,
+    union: bool,
+    read: &ReadArgs,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let readers = paths
+        .iter()
+        .map(|path| open_reader(path, read))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let start = Instant::now();
+    let summary = concat::concat(&readers, output, &ConcatOptions { union })?;
+    let elapsed = start.elapsed();
+
+    if json {
+        return print_json(&json!({
+            "output": output,
+            "columns": summary.columns,
+            "files": summary.files.iter().map(|(path, rows)| json!({
+                "path": path,
+                "rows": rows,
+            })).collect::<Vec<_>>(),
+            "rows_written": summary.rows_written,
+            "elapsed_ms": millis(elapsed),
+        }));
+    }
+    for (path, rows) in &summary.files {
+        println!(
+            "{:>12} rows <- {}",
+            format::format_number(*rows),
+            path.display()
+        );
+    }
+    println!(
+        "Wrote {} rows, {} columns -> {} ({:.2?})",
+        format::format_number(summary.rows_written),
+        summary.columns.len(),
+        output.display(),
+        elapsed
+    );
+    Ok(())
+}
+
+fn cmd_compute(
+    path: &Path,
+    definitions: &[String],
+    output: Option<&Path>,
+    read: &ReadArgs,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if output.is_none() {
+        require_file(path, "compute without --output")?;
+    }
+    let reader = open_reader(path, read)?;
+    let computed = ComputedColumns::parse(definitions, reader.canonical_headers())?;
+    let target = output.unwrap_or(path);
+
+    let start = Instant::now();
+    let rows = materialize(&reader, &computed, target)?;
+    let elapsed = start.elapsed();
+    let names: Vec<&str> = computed.columns().iter().map(|c| c.name.as_str()).collect();
+
+    if json {
+        return print_json(&json!({
+            "output": target.display().to_string(),
+            "columns": names,
+            "rows": rows,
+            "elapsed_ms": millis(elapsed),
+        }));
+    }
+
+    println!(
+        "Added {} to {} rows of {} ({:.2?})",
+        names.join(", "),
+        format::format_number(rows),
+        target.display(),
+        elapsed
+    );
+    Ok(())
+}
+
+fn cmd_split(
+    path: &Path,
+    rows_per_file: Option<usize>,
+    by_column: Option<&str>,
+    output_dir: Option<&Path>,
+    read: &ReadArgs,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let reader = open_reader(path, read)?;
+    let output_dir = match output_dir {
+        Some(dir) => dir.to_path_buf(),
+        None => path
+            .parent()
+            .filter(|p| !p.as_os_str().is_empty())
+            .map_or_else(|| PathBuf::from("."), Path::to_path_buf),
+    };
+
+    let start = Instant::now();
+    let parts = match (by_column, rows_per_file) {
+        (Some(column), _) => split::split_by_column(&reader, column, &output_dir)?,
+        (None, Some(rows)) => split::split_rows(&reader, rows, &output_dir)?,
+        (None, None) => unreachable!("clap requires one of the modes"),
+    };
+    let elapsed = start.elapsed();
+
+    if json {
+        return print_json(&json!({
+            "files": parts.iter().map(|part| json!({
+                "path": part.path,
+                "rows": part.rows,
+                "value": part.value,
+            })).collect::<Vec<_>>(),
+            "elapsed_ms": millis(elapsed),
+        }));
+    }
+    for part in &parts {
+        println!(
+            "{:>12} rows -> {}",
+            format::format_number(part.rows),
+            part.path.display()
+        );
+    }
+    println!(
+        "Wrote {} file{} in {:.2?}",
+        format::format_number(parts.len()),
+        if parts.len() == 1 { "" } else { "s" },
+        elapsed
+    );
+    Ok(())
+}
+
+fn cmd_sort(
+    path: &Path,
+    by: &[String],
+    output: &Path,
+    read: &ReadArgs,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let options = SortOptions {
+        keys: by
+            .iter()
+            .map(|spec| SortKey::parse(spec))
+            .collect::<Result<_, _>>()?,
+        ..SortOptions::default()
+    };
+    let reader = open_reader(path, read)?;
+
+    let start = Instant::now();
+    let summary = sort(&reader, output, &options)?;
+    let elapsed = start.elapsed();
+
+    if json {
+        return print_json(&json!({
+            "output": output,
+            "rows_written": summary.rows_written,
+            "runs": summary.runs,
+            "elapsed_ms": millis(elapsed),
+        }));
+    }
+    println!(
+        "Sorted {} rows -> {} ({:.2?}{})",
+        format::format_number(summary.rows_written),
+        output.display(),
+        elapsed,
+        if summary.runs > 0 {
+            format!(", merged {} runs from disk", summary.runs)
+        } else {
+            String::new()
+        }
+    );
+    Ok(())
+}
+
+fn cmd_sample(
+    path: &Path,
+    n: usize,
+    method: SampleMethod,
+    seed: Option<u64>,
+    output: &Path,
+    read: &ReadArgs,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let reader = open_reader(path, read)?;
+    let seed = seed.unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64)
+    });
+
+    let start = Instant::now();
+    let rows = sample(&reader, n, method, seed)?;
+    sample::write_rows(&reader, &rows, output)?;
+    let elapsed = start.elapsed();
+
+    if json {
+        return print_json(&json!({
+            "output": output,
+            "rows_written": rows.len(),
+            "rows": reader.row_count(),
+            "seed": seed,
+            "elapsed_ms": millis(elapsed),
+        }));
+    }
+    println!(
+        "Sampled {} of {} rows -> {} (seed {}, {:.2?})",
+        format::format_number(rows.len()),
+        format::format_number(reader.row_count()),
+        output.display(),
+        seed,
+        elapsed
+    );
+    Ok(())
+}
+
+fn cmd_join(
+    left: &Path,
+    right: &Path,
+    output: &Path,
+    options: &JoinOptions,
+    read: &ReadArgs,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let left = open_reader(left, read)?;
+    let right = open_reader(right, read)?;
+
+    let start = Instant::now();
+    let summary = join(&left, &right, output, options)?;
+    let elapsed = start.elapsed();
+
+    if json {
+        return print_json(&json!({
+            "output": output,
+            "rows_written": summary.rows_written,
+            "left_matched": summary.left_matched,
+            "left_unmatched": summary.left_unmatched,
+            "right_unmatched": summary.right_unmatched,
+            "spilled": summary.spilled,
+            "indexed": summary.indexed,
+            "elapsed_ms": millis(elapsed),
+        }));
+    }
+    println!(
+        "Wrote {} rows -> {} ({:.2?})",
+        format::format_number(summary.rows_written),
+        output.display(),
+        elapsed
+    );
+    println!(
+        "  Left rows matched: {} of {}",
+        format::format_number(summary.left_matched),
+        format::format_number(left.row_count())
+    );
+    println!(
+        "  Right rows unmatched: {} of {}",
+        format::format_number(summary.right_unmatched),
+        format::format_number(right.row_count())
+    );
+    if summary.indexed {
+        println!("  (used the right file's stored index on its key column)");
+    }
+    if summary.spilled {
+        println!("  (right-side index spilled to disk)");
+    }
+    Ok(())
+}
+
+fn cmd_dedupe(
+    path: &Path,
+    key: &[String],
+    keep: Keep,
+    output: &Path,
+    read: &ReadArgs,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let reader = open_reader(path, read)?;
+
+    let start = Instant::now();
+    let summary = dedupe(&reader, key, keep, output)?;
+    let elapsed = start.elapsed();
+
+    if json {
+        return print_json(&json!({
+            "output": output,
+            "rows_written": summary.rows_written,
+            "rows_removed": summary.rows_removed,
+            "duplicate_keys": summary.groups,
+            "elapsed_ms": millis(elapsed),
+        }));
+    }
+    println!(
+        "Removed {} duplicate row{} ({} repeated key{}), wrote {} rows -> {} ({:.2?})",
+        format::format_number(summary.rows_removed),
+        if summary.rows_removed == 1 { "" } else { "s" },
+        format::format_number(summary.groups),
+        if summary.groups == 1 { "" } else { "s" },
+        format::format_number(summary.rows_written),
+        output.display(),
+        elapsed
+    );
+    Ok(())
+}
+
+fn cmd_anonymize(
+    path: &Path,
+    columns: &[String],
+    output: &Path,
+    salt: &str,
+    read: &ReadArgs,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let masks = columns
+        .iter()
+        .map(|spec| {
+            let (column, mask) = spec
+                .split_once('=')
+                .ok_or_else(|| format!("expected COLUMN=MASK, got '{spec}'"))?;
+            Ok((column.trim().to_string(), Mask::parse(mask)?))
+        })
+        .collect::<Result<Vec<_>, Box<dyn std::error::Error>>>()?;
+    let reader = open_reader(path, read)?;
+
+    let start = Instant::now();
+    let rows = anonymize(&reader, &masks, salt, output)?;
+    let elapsed = start.elapsed();
+
+    if json {
+        let masked: serde_json::Map<String, Value> = masks
+            .iter()
+            .map(|(column, mask)| (column.clone(), Value::from(mask.to_string())))
+            .collect();
+        return print_json(&json!({
+            "output": output,
+            "columns": masked,
+            "rows": rows,
+            "elapsed_ms": millis(elapsed),
+        }));
+    }
+    let masked: Vec<String> = masks
+        .iter()
+        .map(|(column, mask)| format!("{column} ({mask})"))
+        .collect();
+    println!(
+        "Masked {} in {} rows -> {} ({:.2?})",
+        masked.join(", "),
+        format::format_number(rows),
+        output.display(),
+        elapsed
+    );
+    Ok(())
+}
+
+fn cmd_normalize_dates(
+    path: &Path,
+    column: &str,
+    options: &NormalizeDatesOptions,
+    output: Option<&Path>,
+    read: &ReadArgs,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if output.is_none() {
+        require_file(path, "normalize-dates without --output")?;
+    }
+    let reader = open_reader(path, read)?;
+    let target = output.unwrap_or(path);
+
+    let start = Instant::now();
+    let report = normalize_dates(&reader, column, options, target)?;
+    let elapsed = start.elapsed();
+
+    if json {
+        let errors: Vec<Value> = report
+            .errors
+            .iter()
+            .map(|e| json!({"row": e.row, "value": e.value}))
+            .collect();
+        return print_json(&json!({
+            "output": target.display().to_string(),
+            "format": report.format.pattern(),
+            "converted": report.converted,
+            "other_format": report.other_format,
+            "error_count": report.error_count,
+            "errors": errors,
+            "elapsed_ms": millis(elapsed),
+        }));
+    }
+
+    for error in &report.errors {
+        println!("{error}");
+    }
+    if report.error_count > report.errors.len() {
+        println!(
+            "... and {} more",
+            format::format_number(report.error_count - report.errors.len())
+        );
+    }
+    if report.error_count > 0 {
+        println!();
+    }
+    println!(
+        "Converted {} value{} from {} ({} in other formats, {} not dates) -> {} ({:.2?})",
+        format::format_number(report.converted),
+        if report.converted == 1 { "" } else { "s" },
+        report.format,
+        format::format_number(report.other_format),
+        format::format_number(report.error_count),
+        target.display(),
+        elapsed
+    );
+    Ok(())
+}
+
+fn cmd_repair(
+    path: &Path,
+    output: &Path,
+    overflow: bool,
+    read: &ReadArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let reader = open_reader(path, read)?;
+    let repair = CsvRepair {
+        long_rows: if overflow {
+            LongRows::Overflow
+        } else {
+            LongRows::Truncate
+        },
+    };
+
+    let start = Instant::now();
+    let report = repair.run(&reader, output)?;
+    let elapsed = start.elapsed();
+
+    for fix in &report.repairs {
+        let actions: Vec<String> = fix.actions.iter().map(|a| a.to_string()).collect();
+        println!("Row {}: {}", fix.row, actions.join("; "));
+    }
+    if !report.repairs.is_empty() {
+        println!();
+    }
+    if report.overflow_columns > 0 {
+        println!(
+            "Added {} overflow column(s) to the header",
+            report.overflow_columns
+        );
+    }
+    println!(
+        "Repaired {} of {} rows -> {} ({:.2?})",
+        format::format_number(report.repairs.len()),
+        format::format_number(report.rows_written),
+        output.display(),
+        elapsed
+    );
+
+    Ok(())
 }
 
-#[derive(Subcommand)]
-enum Commands {
-    /// Show file metadata: row count, columns, size, delimiter
-    Info {
-        /// Path to the CSV file
-        file: PathBuf,
-    },
+fn cmd_validate(
+    path: &Path,
+    max_issues: usize,
+    quiet: bool,
+    read: &ReadArgs,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let reader = open_reader(path, read)?;
 
-    /// View rows from a CSV file as a formatted table
-    View {
-        /// Path to the CSV file
-        file: PathBuf,
+    let start = Instant::now();
+    let report = validate(&reader, &ValidateOptions { max_issues })?;
+    let elapsed = start.elapsed();
 
-        /// Row range to display, e.g. "100-200" or "100" (default: first 20 rows)
-        #[arg(short, long)]
-        rows: Option<String>,
-    },
+    if json {
+        let issues: Vec<Value> = report
+            .issues
+            .iter()
+            .map(|issue| json!({ "row": issue.row, "issue": issue.kind.to_string() }))
+            .collect();
+        print_json(&json!({
+            "valid": report.is_valid(),
+            "rows": reader.row_count(),
+            "rows_checked": report.rows_checked,
+            "rows_with_issues": report.rows_with_issues(),
+            "truncated": report.truncated,
+            "elapsed_ms": millis(elapsed),
+            "issues": if quiet { Vec::new() } else { issues },
+        }))?;
+        if !report.is_valid() {
+            process::exit(1);
+        }
+        return Ok(());
+    }
 
-    /// Search for rows matching a query
-    Search {
-        /// Path to the CSV file
-        file: PathBuf,
+    if !quiet {
+        for issue in &report.issues {
+            println!("Row {}: {}", issue.row, issue.kind);
+        }
+        if !report.issues.is_empty() {
+            println!();
+        }
+    }
 
-        /// Text to search for
-        query: String,
+    if report.is_valid() {
+        println!(
+            "OK: {} rows, no issues ({:.2?})",
+            format::format_number(report.rows_checked),
+            elapsed
+        );
+        return Ok(());
+    }
 
-        /// Restrict search to a specific column name
-        #[arg(short, long)]
-        column: Option<String>,
+    println!(
+        "{} issue{} in {} row{} (checked {} of {} rows in {:.2?}){}",
+        format::format_number(report.issues.len()),
+        if report.issues.len() == 1 { "" } else { "s" },
+        format::format_number(report.rows_with_issues()),
+        if report.rows_with_issues() == 1 {
+            ""
+        } else {
+            "s"
+        },
+        format::format_number(report.rows_checked),
+        format::format_number(reader.row_count()),
+        elapsed,
+        if report.truncated {
+            "; stopped at --max-issues"
+        } else {
+            ""
+        },
+    );
+    process::exit(1);
+}
 
-        /// Case-insensitive matching
-        #[arg(short = 'i', long)]
-        ignore_case: bool,
+fn cmd_validate_schema(
+    path: &Path,
+    schema: &Schema,
+    max_issues: usize,
+    quiet: bool,
+    read: &ReadArgs,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let reader = open_reader(path, read)?;
 
-        /// Maximum number of results (default: 100)
-        #[arg(short = 'n', long, default_value_t = 100)]
-        max_results: usize,
-    },
+    let start = Instant::now();
+    let report = validate_schema(&reader, schema, &ValidateOptions { max_issues })?;
+    let elapsed = start.elapsed();
 
-    /// Edit a specific cell and save
-    Edit {
-        /// Path to the CSV file
-        file: PathBuf,
+    if json {
+        let violations: Vec<Value> = report
+            .violations
+            .iter()
+            .map(|v| {
+                json!({
+                    "row": v.row,
+                    "column": v.column,
+                    "value": v.value,
+                    "issue": v.to_string(),
+                })
+            })
+            .collect();
+        print_json(&json!({
+            "valid": report.is_valid(),
+            "rows": reader.row_count(),
+            "rows_checked": report.rows_checked,
+            "truncated": report.truncated,
+            "elapsed_ms": millis(elapsed),
+            "violations": if quiet { Vec::new() } else { violations },
+        }))?;
+        if !report.is_valid() {
+            process::exit(1);
+        }
+        return Ok(());
+    }
 
-        /// Row number to edit (0-indexed)
-        #[arg(long)]
-        row: usize,
+    if !quiet {
+        for violation in &report.violations {
+            println!("{violation}");
+        }
+        if !report.violations.is_empty() {
+            println!();
+        }
+    }
 
-        /// Column name or 0-indexed column number
-        #[arg(long)]
-        col: String,
+    if report.is_valid() {
+        println!(
+            "OK: {} rows match the schema ({:.2?})",
+            format::format_number(report.rows_checked),
+            elapsed
+        );
+        return Ok(());
+    }
 
-        /// New value for the cell
-        #[arg(long)]
-        value: String,
-    },
+    println!(
+        "{} violation{} (checked {} of {} rows in {:.2?}){}",
+        format::format_number(report.violations.len()),
+        if report.violations.len() == 1 {
+            ""
+        } else {
+            "s"
+        },
+        format::format_number(report.rows_checked),
+        format::format_number(reader.row_count()),
+        elapsed,
+        if report.truncated {
+            "; stopped at --max-issues"
+        } else {
+            ""
+        },
+    );
+    process::exit(1);
 }
 
-fn main() {
-    let cli = Cli::parse();
+fn cmd_stats(
+    path: &Path,
+    columns: &[String],
+    read: &ReadArgs,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let reader = open_reader(path, read)?;
 
-    let result = match cli.command {
-        Commands::Info { file } => cmd_info(&file),
-        Commands::View { file, rows } => cmd_view(&file, rows.as_deref()),
-        Commands::Search {
-            file,
-            query,
-            column,
-            ignore_case,
-            max_results,
-        } => cmd_search(&file, &query, column.as_deref(), ignore_case, max_results),
-        Commands::Edit {
-            file,
-            row,
-            col,
-            value,
-        } => cmd_edit(&file, row, &col, &value),
-    };
+    let start = Instant::now();
+    let stats = stats::column_stats(&reader, columns)?;
+    let elapsed = start.elapsed();
 
-    if let Err(e) = result {
-        eprintln!("Error: {e}");
-        process::exit(1);
+    if json {
+        let columns: Vec<Value> = stats
+            .iter()
+            .map(|column| {
+                json!({
+                    "name": column.name,
+                    "count": column.count,
+                    "empty": column.empty,
+                    "numeric": column.numeric.map(|n| json!({
+                        "min": n.min,
+                        "max": n.max,
+                        "mean": n.mean,
+                        "stddev": n.stddev,
+                        "p50": n.p50,
+                        "p95": n.p95,
+                        "p99": n.p99,
+                    })),
+                    "min_length": column.min_length,
+                    "max_length": column.max_length,
+                    "distinct": column.distinct,
+                    "distinct_ratio": column.distinct_ratio(),
+                    "near_unique": column.is_near_unique(),
+                    "low_cardinality": column.is_low_cardinality(),
+                })
+            })
+            .collect();
+        return print_json(&json!({
+            "rows": reader.row_count(),
+            "elapsed_ms": millis(elapsed),
+            "columns": columns,
+        }));
+    }
+
+    for column in &stats {
+        println!("{}", column.name);
+        println!(
+            "  Values:   {} ({} empty)",
+            format::format_number(column.count),
+            format::format_number(column.empty)
+        );
+        if let Some(numeric) = column.numeric {
+            println!("  Min/max:  {} / {}", numeric.min, numeric.max);
+            println!(
+                "  Mean:     {:.4} (std dev {:.4})",
+                numeric.mean, numeric.stddev
+            );
+            println!(
+                "  Median:   {:.4} (p95 {:.4}, p99 {:.4})",
+                numeric.p50, numeric.p95, numeric.p99
+            );
+        }
+        if column.count > 0 {
+            println!(
+                "  Length:   {}-{} chars",
+                column.min_length, column.max_length
+            );
+        }
+        println!(
+            "  Distinct: ~{} ({:.1}%){}",
+            format::format_number(column.distinct as usize),
+            column.distinct_ratio() * 100.0,
+            if column.is_near_unique() {
+                ", near-unique (key?)"
+            } else if column.is_low_cardinality() {
+                ", low cardinality (enum?)"
+            } else {
+                ""
+            }
+        );
+        println!();
     }
+    println!(
+        "{} rows, {} column{} in {:.2?}",
+        format::format_number(reader.row_count()),
+        stats.len(),
+        if stats.len() == 1 { "" } else { "s" },
+        elapsed
+    );
+    Ok(())
 }
 
-fn cmd_info(path: &PathBuf) -> Result<(), Box<dyn std::error::Error>> {
+fn cmd_freq(
+    path: &Path,
+    column: &str,
+    top: usize,
+    read: &ReadArgs,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let reader = open_reader(path, read)?;
+
     let start = Instant::now();
-    let reader = CsvReader::open(path)?;
+    let counts = stats::value_counts(&reader, column, top)?;
     let elapsed = start.elapsed();
 
-    let metadata = std::fs::metadata(path)?;
-    let headers = reader.headers();
+    let share = |count: usize| count as f64 * 100.0 / counts.rows.max(1) as f64;
+    if json {
+        let values: Vec<Value> = counts
+            .values
+            .iter()
+            .map(|(value, count)| json!({ "value": value, "count": count, "percent": share(*count) }))
+            .collect();
+        return print_json(&json!({
+            "column": column,
+            "rows": counts.rows,
+            "distinct": counts.distinct,
+            "elapsed_ms": millis(elapsed),
+            "values": values,
+        }));
+    }
 
-    // Show first 10 headers, abbreviate if more
-    let header_display = if headers.len() <= 10 {
-        headers.join(", ")
-    } else {
-        format!(
-            "{}, ... (+{} more)",
-            headers[..10].join(", "),
-            headers.len() - 10
-        )
+    let labels: Vec<String> = counts
+        .values
+        .iter()
+        .map(|(value, _)| {
+            if value.is_empty() {
+                "(empty)".to_string()
+            } else {
+                value.chars().take(40).collect()
+            }
+        })
+        .collect();
+    let numbers: Vec<String> = counts
+        .values
+        .iter()
+        .map(|(_, count)| format::format_number(*count))
+        .collect();
+    let label_width = labels.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+    let number_width = numbers.iter().map(String::len).max().unwrap_or(0);
+    for ((label, number), (_, count)) in labels.iter().zip(&numbers).zip(&counts.values) {
+        println!(
+            "{:<label_width$}  {:>number_width$}  {:>6.2}%",
+            label,
+            number,
+            share(*count)
+        );
+    }
+    if !counts.values.is_empty() {
+        println!();
+    }
+    println!(
+        "{} distinct value{} in {} rows{} ({:.2?})",
+        format::format_number(counts.distinct),
+        if counts.distinct == 1 { "" } else { "s" },
+        format::format_number(counts.rows),
+        if counts.values.len() < counts.distinct {
+            format!(", top {} shown", counts.values.len())
+        } else {
+            String::new()
+        },
+        elapsed
+    );
+    Ok(())
+}
+
+fn cmd_explain(
+    expr: &str,
+    file: Option<&Path>,
+    read: &ReadArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(path) = file else {
+        let parsed = Expr::parse(expr)?;
+        println!("Expression: {parsed}");
+        println!("Columns:    {}", parsed.columns().join(", "));
+        println!();
+        format::print_expr_tree(&parsed);
+        println!();
+        println!("Pass --file to check the columns and see the execution plan.");
+        return Ok(());
     };
 
-    println!("File:       {}", path.display());
-    println!("Size:       {}", format::format_size(metadata.len()));
-    println!("Rows:       {}", format::format_number(reader.row_count()));
-    println!("Columns:    {}", headers.len());
-    println!("Delimiter:  {}", format::delimiter_name(reader.delimiter()));
-    println!("Headers:    {header_display}");
-    println!("Load time:  {:.2?}", elapsed);
+    let reader = open_reader(path, read)?;
+    let filter = Filter::parse(expr, reader.headers())?;
+    let plan = filter.plan(&reader);
+
+    let columns: Vec<String> = plan
+        .columns
+        .iter()
+        .map(|(name, i)| format!("{name} (#{i})"))
+        .collect();
+    println!("Expression: {}", filter.expr());
+    println!("Columns:    {}", columns.join(", "));
+    println!();
+    format::print_expr_tree(filter.expr());
+    println!();
+    if plan.prefilters.is_empty() {
+        println!("Prefilter:  none (every candidate row is parsed)");
+    } else {
+        let groups: Vec<String> = plan
+            .prefilters
+            .iter()
+            .map(|group| {
+                let literals: Vec<String> = group.iter().map(|l| format!("{l:?}")).collect();
+                literals.join(" | ")
+            })
+            .collect();
+        println!("Prefilter:  raw line contains {}", groups.join(" & "));
+    }
+    match plan.access {
+        filter::Access::FullScan => println!(
+            "Access:     full parallel scan of {} rows (no index or sorted column applies)",
+            format::format_number(plan.rows)
+        ),
+        filter::Access::Index { columns, rows } => println!(
+            "Access:     index on {} -> {} candidate rows of {}",
+            columns.join(", "),
+            format::format_number(rows.len()),
+            format::format_number(plan.rows)
+        ),
+        filter::Access::SortedRange { columns, rows } => println!(
+            "Access:     binary search on sorted {} -> rows {}..{} ({} of {} rows scanned)",
+            columns.join(", "),
+            rows.start,
+            rows.end,
+            format::format_number(rows.len()),
+            format::format_number(plan.rows)
+        ),
+    }
 
     Ok(())
 }
 
-fn cmd_view(path: &PathBuf, rows_arg: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
-    let reader = CsvReader::open(path)?;
-    let row_count = reader.row_count();
+fn cmd_man() -> Result<(), Box<dyn std::error::Error>> {
+    let man = clap_mangen::Man::new(Cli::command());
+    man.render(&mut std::io::stdout().lock())?;
+    Ok(())
+}
 
-    let (start, end) = parse_row_range(rows_arg, row_count)?;
+fn cmd_run(
+    alias: Option<&str>,
+    file: Option<&Path>,
+    set: &[String],
+    extra: &[String],
+    json: bool,
+    quiet: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let config = Config::global();
 
-    if start >= row_count {
-        eprintln!("Row {start} is out of range (file has {row_count} rows)");
-        process::exit(1);
-    }
+    let Some(name) = alias else {
+        if config.aliases.is_empty() {
+            println!("No aliases defined.");
+            if let Some(path) = config.path.clone().or_else(Config::default_path) {
+                println!("Add them under [aliases] in {}", path.display());
+            }
+        }
+        for (name, alias) in &config.aliases {
+            match &alias.description {
+                Some(d) => println!("{name:<20} {d}"),
+                None => println!("{name:<20} {}", alias.command.join(" ")),
+            }
+        }
+        return Ok(());
+    };
 
-    let rows = reader.get_rows(start, end)?;
-    let row_numbers: Vec<usize> = (start..start + rows.len()).collect();
+    let alias = config
+        .aliases
+        .get(name)
+        .ok_or_else(|| format!("unknown alias '{name}' (run `massive-csv run` to list them)"))?;
+
+    let mut overrides = BTreeMap::new();
+    for pair in set {
+        let (key, value) = pair
+            .split_once('=')
+            .ok_or_else(|| format!("invalid --set '{pair}': expected NAME=VALUE"))?;
+        overrides.insert(key.to_string(), value.to_string());
+    }
 
-    format::print_table(reader.headers(), &rows, &row_numbers);
+    let args = alias.expand(file, &overrides, extra)?;
+    eprintln!("massive-csv {}", args.join(" "));
 
-    Ok(())
+    let cli = parse_args(
+        config,
+        std::iter::once("massive-csv".to_string()).chain(args),
+    )?;
+    if matches!(cli.command, Commands::Run { .. }) {
+        return Err(format!("alias '{name}' cannot invoke `run`").into());
+    }
+    if cli.quiet {
+        progress::set_enabled(false);
+    }
+    run(cli.command, &cli.read, json || cli.json, quiet || cli.quiet)
 }
 
-fn cmd_search(
-    path: &PathBuf,
-    query: &str,
-    column: Option<&str>,
-    ignore_case: bool,
-    max_results: usize,
+fn cmd_recover(
+    path: &Path,
+    discard: bool,
+    read: &ReadArgs,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let reader = CsvReader::open(path)?;
+    require_file(path, "recover")?;
+    let mut editor = open_editor(path, read)?;
 
-    let options = SearchOptions {
-        column: column.map(|s| s.to_string()),
-        case_insensitive: ignore_case,
-        max_results,
-    };
+    if !editor.has_recoverable_journal() {
+        println!("No edit journal found for {}", path.display());
+        return Ok(());
+    }
 
-    let start = Instant::now();
-    let results = massive_csv_core::search(&reader, query, &options)?;
-    let elapsed = start.elapsed();
+    if discard {
+        editor.discard_journal()?;
+        println!("Discarded {}", editor.journal_path().display());
+        return Ok(());
+    }
+
+    let recovered = editor.recover()?;
+    save_editor(&mut editor, None, &SaveOptions::default())?;
+    editor.discard_journal()?;
 
-    let total = results.len();
     println!(
-        "Found {} match{} (searched {} rows in {:.2?}):\n",
-        format::format_number(total),
-        if total == 1 { "" } else { "es" },
-        format::format_number(reader.row_count()),
-        elapsed,
+        "Recovered {} edited row{}",
+        format::format_number(recovered),
+        if recovered == 1 { "" } else { "s" }
     );
+    println!("Saved.");
 
-    if results.is_empty() {
-        return Ok(());
+    Ok(())
+}
+
+fn cmd_views_list(path: &Path, json: bool) -> Result<(), Box<dyn std::error::Error>> {
+    require_local(path, "views")?;
+    let store = ViewStore::for_file(path)?;
+
+    if json {
+        return print_json(&json!({
+            "views": store.views(),
+            "bookmarks": store.bookmarks(),
+        }));
     }
 
-    let row_numbers: Vec<usize> = results.iter().map(|r| r.row_num).collect();
-    let rows: Vec<Vec<String>> = results.into_iter().map(|r| r.fields).collect();
+    if store.views().is_empty() && store.bookmarks().is_empty() {
+        println!("No views or bookmarks saved for {}", path.display());
+        return Ok(());
+    }
+    for view in store.views() {
+        println!("{}", view.name);
+        if let Some(filter) = &view.filter {
+            println!("  where   {filter}");
+        }
+        if !view.sort.is_empty() {
+            println!("  sort    {}", view.sort.join(", "));
+        }
+        if !view.columns.is_empty() {
+            println!("  columns {}", view.columns.join(", "));
+        }
+    }
+    if !store.bookmarks().is_empty() {
+        println!("Bookmarks:");
+        for bookmark in store.bookmarks() {
+            match &bookmark.label {
+                Some(label) => println!("  Row {}: {label}", bookmark.row),
+                None => println!("  Row {}", bookmark.row),
+            }
+        }
+    }
+    Ok(())
+}
 
-    format::print_table(reader.headers(), &rows, &row_numbers);
+fn cmd_views_save(
+    path: &Path,
+    view: SavedView,
+    read: &ReadArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    require_local(path, "views")?;
+    // Check the view against the file as it is now
+    let reader = open_reader(path, read)?;
+    let rows = view.apply(&reader)?.row_count();
+    view.column_indices(&reader)?;
 
+    let name = view.name.clone();
+    ViewStore::for_file(path)?.save_view(view)?;
+    println!(
+        "Saved view '{name}' ({} of {} rows)",
+        format::format_number(rows),
+        format::format_number(reader.row_count())
+    );
     Ok(())
 }
 
-fn cmd_edit(
-    path: &PathBuf,
+fn cmd_views_bookmark(
+    path: &Path,
     row: usize,
-    col: &str,
-    value: &str,
+    label: Option<String>,
+    remove: bool,
+    read: &ReadArgs,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let mut editor = CsvEditor::open(path)?;
-    let headers: Vec<String> = editor.reader().headers().to_vec();
+    require_local(path, "views")?;
+    let mut store = ViewStore::for_file(path)?;
+    if remove {
+        if !store.remove_bookmark(row)? {
+            return Err(format!("row {row} is not bookmarked").into());
+        }
+        println!("Removed bookmark on row {row}");
+        return Ok(());
+    }
+    let row_count = open_reader(path, read)?.row_count();
+    if row >= row_count {
+        return Err(MassiveCsvError::RowOutOfRange(row, row_count).into());
+    }
+    store.add_bookmark(row, label)?;
+    println!("Bookmarked row {row}");
+    Ok(())
+}
 
-    // Resolve column: try name first, then numeric index
-    let col_idx = headers
-        .iter()
-        .position(|h| h == col)
-        .or_else(|| col.parse::<usize>().ok().filter(|&i| i < headers.len()))
-        .ok_or_else(|| {
-            format!(
-                "Column '{}' not found. Available: {}",
-                col,
-                headers.join(", ")
-            )
-        })?;
+fn cmd_versions_list(path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    require_file(path, "versions")?;
+    let store = VersionStore::for_file(path);
+    let versions = store.list()?;
+
+    if versions.is_empty() {
+        println!("No versions stored for {}", path.display());
+        return Ok(());
+    }
 
-    let col_name = &headers[col_idx];
+    let now = SystemTime::now();
+    println!("{:<15}  {:>10}  Age", "Version", "Size");
+    for v in &versions {
+        let age = now.duration_since(v.created).unwrap_or_default();
+        println!(
+            "{:<15}  {:>10}  {}",
+            v.id,
+            format::format_size(v.compressed_size),
+            format::format_age(age)
+        );
+    }
 
-    // Get old value for display
-    let old_row = editor.reader().get_row(row)?;
-    let old_value = old_row
-        .get(col_idx)
-        .map(|s| s.as_str())
-        .unwrap_or("<missing>");
+    Ok(())
+}
 
-    editor.set_cell(row, col_idx, value.to_string())?;
-    editor.save()?;
+fn cmd_versions_restore(path: &Path, id: &str) -> Result<(), Box<dyn std::error::Error>> {
+    require_file(path, "versions")?;
+    let store = VersionStore::for_file(path);
+    let previous = store.restore(id)?;
 
-    println!(
-        "Updated row {}, column \"{}\": \"{}\" -> \"{}\"",
-        format::format_number(row),
-        col_name,
-        old_value,
-        value
-    );
-    println!("Saved.");
+    println!("Restored {} to version {}", path.display(), id);
+    println!("Previous contents saved as version {}", previous.id);
 
     Ok(())
 }
 
+fn parse_delimiter(value: &str) -> Result<u8, String> {
+    match value {
+        "tab" | "\\t" | "\t" => Ok(b'\t'),
+        d if d.len() == 1 && d.is_ascii() => Ok(d.as_bytes()[0]),
+        d => Err(format!(
+            "invalid character '{d}': expected one ASCII character or \"tab\""
+        )),
+    }
+}
+
+fn parse_column_width(value: &str) -> Result<(String, usize), String> {
+    value
+        .split_once('=')
+        .and_then(|(column, width)| Some((column.trim().to_string(), width.trim().parse().ok()?)))
+        .ok_or_else(|| format!("invalid column width '{value}': expected COLUMN=CHARS"))
+}
+
+fn parse_compression(label: &str) -> Result<Compression, String> {
+    Compression::from_label(label)
+        .ok_or_else(|| format!("unknown compression '{label}': expected gzip, zstd or none"))
+}
+
+/// `search --output`, a file path: bare `csv` and `table` are refused, as
+/// they are print formats for `--format`, not files to save to.
+fn parse_search_output(path: &str) -> Result<PathBuf, String> {
+    match path {
+        "csv" | "table" => Err(format!(
+            "'{path}' is a print format: use --format {path} (or ./{path} to save to a file by that name)"
+        )),
+        _ => Ok(PathBuf::from(path)),
+    }
+}
+
+fn parse_encoding(label: &str) -> Result<Encoding, String> {
+    Encoding::from_label(label).ok_or_else(|| {
+        format!("unknown encoding '{label}': expected utf-8, latin1, utf-16le or utf-16be")
+    })
+}
+
 /// Parse a row range string like "100-200" or "100" into (start, end).
 /// Returns (start, end) where end is exclusive.
 fn parse_row_range(
@@ -256,3 +4768,92 @@ fn parse_row_range(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn row_range_is_inclusive_and_clamped() {
+        assert_eq!(parse_row_range(None, 100).unwrap(), (0, 20));
+        assert_eq!(parse_row_range(None, 5).unwrap(), (0, 5));
+        assert_eq!(parse_row_range(Some("10-19"), 100).unwrap(), (10, 20));
+        assert_eq!(parse_row_range(Some(" 10 - 500 "), 100).unwrap(), (10, 100));
+        assert_eq!(parse_row_range(Some("7"), 100).unwrap(), (7, 8));
+        assert_eq!(parse_row_range(Some("99"), 50).unwrap(), (99, 50));
+        assert!(parse_row_range(Some("a-3"), 100).is_err());
+        assert!(parse_row_range(Some("3-"), 100).is_err());
+        assert!(parse_row_range(Some("-3"), 100).is_err());
+    }
+
+    #[test]
+    fn delimiter_is_one_ascii_character_or_tab() {
+        assert_eq!(parse_delimiter(";"), Ok(b';'));
+        assert_eq!(parse_delimiter("tab"), Ok(b'\t'));
+        assert_eq!(parse_delimiter("\\t"), Ok(b'\t'));
+        assert!(parse_delimiter("ab").is_err());
+        assert!(parse_delimiter("é").is_err());
+        assert!(parse_delimiter("").is_err());
+    }
+
+    #[test]
+    fn column_width_is_column_equals_chars() {
+        assert_eq!(
+            parse_column_width("description = 80"),
+            Ok(("description".to_string(), 80))
+        );
+        assert!(parse_column_width("description").is_err());
+        assert!(parse_column_width("description=wide").is_err());
+    }
+
+    #[test]
+    fn compression_labels() {
+        assert_eq!(parse_compression("gzip"), Ok(Compression::Gzip));
+        assert_eq!(parse_compression("none"), Ok(Compression::None));
+        assert!(parse_compression("lzma").is_err());
+    }
+
+    #[test]
+    fn search_output_refuses_print_formats() {
+        assert_eq!(parse_search_output("out.csv"), Ok(PathBuf::from("out.csv")));
+        assert_eq!(parse_search_output("./csv"), Ok(PathBuf::from("./csv")));
+        assert!(parse_search_output("csv")
+            .unwrap_err()
+            .contains("--format csv"));
+        assert!(parse_search_output("table").is_err());
+    }
+
+    #[test]
+    fn search_output_conflicts_with_format_and_context() {
+        let config = Config::default();
+        let parse = |args: &[&str]| {
+            parse_args(&config, [&["massive-csv", "search"][..], args].concat()).map(|_| ())
+        };
+        assert!(parse(&["f.csv", "q", "-o", "out.csv"]).is_ok());
+        assert!(parse(&["f.csv", "q", "-o", "out.csv", "--format", "csv"]).is_err());
+        assert!(parse(&["f.csv", "q", "-o", "out.csv", "-C", "2"]).is_err());
+        assert!(parse(&["f.csv", "q", "--offset", "1", "--page", "2"]).is_err());
+    }
+
+    #[test]
+    fn pages_only_tables_of_view_and_search() {
+        let config = Config::default();
+        let command = |args: &[&str]| {
+            parse_args(&config, [&["massive-csv"][..], args].concat())
+                .unwrap()
+                .command
+        };
+        assert!(pages(&command(&["view", "f.csv"]), false));
+        assert!(!pages(&command(&["view", "f.csv"]), true));
+        assert!(pages(&command(&["search", "f.csv", "q"]), false));
+        assert!(!pages(
+            &command(&["search", "f.csv", "q", "--format", "csv"]),
+            false
+        ));
+        assert!(!pages(
+            &command(&["search", "f.csv", "q", "-o", "out.csv"]),
+            false
+        ));
+        assert!(!pages(&command(&["head", "f.csv"]), false));
+    }
+}
@@ -0,0 +1,78 @@
+//! Sending long tables through `$PAGER`, as git does.
+
+use std::process::Child;
+
+/// A pager reading this process's standard output, from [`start`].
+pub struct Pager {
+    child: Child,
+}
+
+/// When stdout is a terminal, send it to `$PAGER` (default `less`, with
+/// `LESS=FRSX` unless `LESS` is set, so output that fits on the screen is
+/// printed as usual and long lines are cut rather than wrapped). Returns
+/// `None` if output isn't paged.
+#[cfg(unix)]
+pub fn start() -> Option<Pager> {
+    use std::io::IsTerminal;
+    use std::os::fd::AsRawFd;
+    use std::process::{Command, Stdio};
+
+    if !std::io::stdout().is_terminal() {
+        return None;
+    }
+    let pager = std::env::var("PAGER").unwrap_or_else(|_| "less".to_string());
+    if matches!(pager.trim(), "" | "cat") {
+        return None;
+    }
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(&pager).stdin(Stdio::piped());
+    if std::env::var_os("LESS").is_none() {
+        command.env("LESS", "FRSX");
+    }
+    let mut child = command.spawn().ok()?;
+    let Some(stdin) = child.stdin.take() else {
+        stop(child);
+        return None;
+    };
+    // SAFETY: plain libc calls on descriptors this process owns. Standard
+    // output now writes to the pager; the pipe stays open through it after
+    // `stdin` is dropped.
+    unsafe {
+        if libc::dup2(stdin.as_raw_fd(), libc::STDOUT_FILENO) < 0 {
+            drop(stdin);
+            stop(child);
+            return None;
+        }
+        // Quitting the pager early ends this process, as it would `git log`,
+        // instead of failing the next write
+        libc::signal(libc::SIGPIPE, libc::SIG_DFL);
+    }
+    Some(Pager { child })
+}
+
+/// End a pager that won't be written to, so it doesn't hold the terminal.
+#[cfg(unix)]
+fn stop(mut child: Child) {
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+#[cfg(not(unix))]
+pub fn start() -> Option<Pager> {
+    None
+}
+
+impl Pager {
+    /// Close standard output and wait for the user to quit the pager.
+    pub fn finish(mut self) {
+        use std::io::Write;
+
+        let _ = std::io::stdout().flush();
+        #[cfg(unix)]
+        // SAFETY: nothing writes to standard output after this
+        unsafe {
+            libc::close(libc::STDOUT_FILENO);
+        }
+        let _ = self.child.wait();
+    }
+}
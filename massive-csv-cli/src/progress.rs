@@ -0,0 +1,93 @@
+//! Progress bars on stderr for slow work: indexing a file as it opens,
+//! searching, saving and exporting.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use massive_csv_core::Progress;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Turn progress bars on or off for the rest of the run.
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// A bar labelled with what is being done, drawn once the work has taken
+/// long enough to be worth watching, and cleared when dropped.
+#[cfg(feature = "progress")]
+pub struct Bar {
+    bar: Option<indicatif::ProgressBar>,
+}
+
+#[cfg(feature = "progress")]
+impl Bar {
+    /// Work shorter than this finishes without drawing a bar.
+    const DELAY: std::time::Duration = std::time::Duration::from_millis(500);
+
+    /// A bar for work measured in `unit` ("bytes" or "rows").
+    pub fn new(label: &str, unit: &str) -> Self {
+        use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+
+        if !ENABLED.load(Ordering::Relaxed) {
+            return Self { bar: None };
+        }
+        let counts = match unit {
+            "bytes" => "{bytes}/{total_bytes}",
+            _ => "{human_pos}/{human_len} rows",
+        };
+        let template = format!("{{msg}} [{{bar:30}}] {counts}, {{eta}} left");
+        let style = ProgressStyle::with_template(&template)
+            .expect("valid template")
+            .progress_chars("=> ");
+        let bar = ProgressBar::with_draw_target(None, ProgressDrawTarget::hidden())
+            .with_style(style)
+            .with_message(label.to_string());
+        Self { bar: Some(bar) }
+    }
+
+    /// A reporter that moves this bar.
+    pub fn progress(&self) -> Progress {
+        use indicatif::ProgressDrawTarget;
+
+        let Some(bar) = self.bar.clone() else {
+            return Progress::default();
+        };
+        let started = std::time::Instant::now();
+        Progress::new(move |done, total| {
+            bar.set_length(total as u64);
+            bar.set_position(done as u64);
+            if bar.is_hidden() && started.elapsed() >= Self::DELAY && done < total {
+                bar.set_draw_target(ProgressDrawTarget::stderr());
+            }
+        })
+    }
+
+    /// Clear the bar now, before printing results.
+    pub fn finish(self) {}
+}
+
+#[cfg(feature = "progress")]
+impl Drop for Bar {
+    fn drop(&mut self) {
+        if let Some(bar) = &self.bar {
+            bar.finish_and_clear();
+        }
+    }
+}
+
+/// Without the `progress` feature nothing is drawn.
+#[cfg(not(feature = "progress"))]
+pub struct Bar;
+
+#[cfg(not(feature = "progress"))]
+impl Bar {
+    pub fn new(_label: &str, _unit: &str) -> Self {
+        Self
+    }
+
+    pub fn progress(&self) -> Progress {
+        Progress::default()
+    }
+
+    pub fn finish(self) {}
+}
@@ -0,0 +1,66 @@
+// Each test binary uses only some of these helpers.
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::PathBuf;
+
+use assert_cmd::Command;
+use tempfile::TempDir;
+
+pub const PEOPLE: &str = "id,name,city
+1,Alice,Paris
+2,Bob,Berlin
+3,Carol,Paris
+4,Dave,Rome
+5,Eve,Paris
+";
+
+/// A scratch directory holding `people.csv`, where `massive-csv` runs with
+/// no user config, color or pager unless a test sets them.
+pub struct Fixture {
+    dir: TempDir,
+}
+
+impl Fixture {
+    pub fn new() -> Self {
+        let fixture = Fixture {
+            dir: TempDir::new().unwrap(),
+        };
+        fixture.write("people.csv", PEOPLE);
+        fixture
+    }
+
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.dir.path().join(name)
+    }
+
+    pub fn write(&self, name: &str, contents: &str) {
+        let path = self.path(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+        fs::write(path, contents).unwrap();
+    }
+
+    pub fn read(&self, name: &str) -> String {
+        fs::read_to_string(self.path(name)).unwrap()
+    }
+
+    /// `massive-csv` with `args`, run in the fixture directory and reading
+    /// `config/massive-csv/config.toml` under it, if written.
+    pub fn run(&self, args: &[&str]) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_massive-csv"));
+        command
+            .current_dir(self.dir.path())
+            .env("XDG_CONFIG_HOME", self.path("config"))
+            .env_remove("NO_COLOR")
+            .env_remove("PAGER")
+            .args(args);
+        command
+    }
+
+    /// Write the user config read by [`Fixture::run`].
+    pub fn config(&self, contents: &str) {
+        self.write("config/massive-csv/config.toml", contents);
+    }
+}
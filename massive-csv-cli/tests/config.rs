@@ -0,0 +1,105 @@
+mod common;
+
+use common::Fixture;
+use predicates::str::contains;
+
+#[test]
+fn config_defaults_apply_to_global_and_command_options() {
+    let fixture = Fixture::new();
+    fixture.config(
+        r#"
+[defaults]
+max-width = 3
+
+[defaults.head]
+lines = 1
+"#,
+    );
+    fixture
+        .run(&["head", "people.csv"])
+        .assert()
+        .success()
+        .stdout(
+            " Row | id | name | city\n\
+             -----+----+------+-----\n\
+             \x20  0 | 1  | A... | P...\n",
+        );
+}
+
+#[test]
+fn command_line_overrides_config_defaults() {
+    let fixture = Fixture::new();
+    fixture.config(
+        r#"
+[defaults]
+color = "always"
+
+[defaults.head]
+lines = 1
+format = "csv"
+"#,
+    );
+    fixture
+        .run(&["head", "people.csv", "-n", "2", "--format", "table"])
+        .assert()
+        .success()
+        .stdout(contains("\x1b[1;36m"))
+        .stdout(contains("Bob"));
+    fixture
+        .run(&["head", "people.csv", "--color", "never"])
+        .assert()
+        .success()
+        .stdout("id,name,city\n1,Alice,Paris\n");
+}
+
+#[test]
+fn config_flag_reads_another_file() {
+    let fixture = Fixture::new();
+    fixture.write(
+        "other.toml",
+        "[defaults.count]\nwhere = 'city == \"Paris\"'\n",
+    );
+    fixture
+        .run(&["--config", "other.toml", "count", "people.csv"])
+        .assert()
+        .success()
+        .stdout("3\n");
+    fixture
+        .run(&["count", "people.csv"])
+        .assert()
+        .success()
+        .stdout("5\n");
+}
+
+#[test]
+fn config_files_entry_sets_read_options() {
+    let fixture = Fixture::new();
+    fixture.write("people.txt", "idname \n1 Alice\n2 Bob  \n");
+    fixture
+        .run(&["get", "people.txt", "--row", "1", "--col", "name"])
+        .assert()
+        .code(1);
+    fixture.config(
+        r#"
+[[files]]
+pattern = "*.txt"
+widths = [2, 5]
+"#,
+    );
+    fixture
+        .run(&["get", "people.txt", "--row", "1", "--col", "name"])
+        .assert()
+        .success()
+        .stdout("Bob\n");
+}
+
+#[test]
+fn config_rejects_an_unknown_option() {
+    let fixture = Fixture::new();
+    fixture.config("[defaults.head]\nnope = 1\n");
+    fixture
+        .run(&["head", "people.csv"])
+        .assert()
+        .code(2)
+        .stderr(contains("nope"));
+}
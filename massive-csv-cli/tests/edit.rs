@@ -0,0 +1,106 @@
+mod common;
+
+use common::Fixture;
+use predicates::str::contains;
+
+#[test]
+fn edit_sets_one_cell() {
+    let fixture = Fixture::new();
+    fixture
+        .run(&[
+            "edit",
+            "people.csv",
+            "--row",
+            "1",
+            "--col",
+            "name",
+            "--value",
+            "Bobby",
+        ])
+        .assert()
+        .success();
+    assert_eq!(
+        fixture.read("people.csv"),
+        common::PEOPLE.replace("Bob,", "Bobby,")
+    );
+}
+
+#[test]
+fn edit_where_sets_every_matching_row() {
+    let fixture = Fixture::new();
+    fixture
+        .run(&[
+            "edit",
+            "people.csv",
+            "--where",
+            r#"city == "Paris""#,
+            "--set",
+            "city=Lyon",
+            "--set",
+            "name=X",
+        ])
+        .assert()
+        .success()
+        .stdout(contains("Updated 3 of 3 matching row(s)."));
+    assert_eq!(
+        fixture.read("people.csv"),
+        "id,name,city\n1,X,Lyon\n2,Bob,Berlin\n3,X,Lyon\n4,Dave,Rome\n5,X,Lyon\n"
+    );
+}
+
+#[test]
+fn edit_set_on_one_row_writes_to_output() {
+    let fixture = Fixture::new();
+    fixture
+        .run(&[
+            "edit",
+            "people.csv",
+            "--row",
+            "3",
+            "--set",
+            "city=Milan",
+            "-o",
+            "out.csv",
+        ])
+        .assert()
+        .success();
+    assert_eq!(fixture.read("people.csv"), common::PEOPLE);
+    assert_eq!(
+        fixture.read("out.csv"),
+        common::PEOPLE.replace("Rome", "Milan")
+    );
+}
+
+#[test]
+fn edit_leaves_the_file_alone_on_an_unknown_column() {
+    let fixture = Fixture::new();
+    fixture
+        .run(&["edit", "people.csv", "--row", "0", "--set", "nope=1"])
+        .assert()
+        .code(1)
+        .stderr(contains("Column 'nope' not found"));
+    assert_eq!(fixture.read("people.csv"), common::PEOPLE);
+}
+
+#[test]
+fn edit_needs_a_row_or_a_filter() {
+    let fixture = Fixture::new();
+    fixture
+        .run(&["edit", "people.csv", "--set", "city=Lyon"])
+        .assert()
+        .code(2);
+    fixture
+        .run(&[
+            "edit",
+            "people.csv",
+            "--row",
+            "0",
+            "--where",
+            "id == 1",
+            "--set",
+            "city=Lyon",
+        ])
+        .assert()
+        .code(2);
+    assert_eq!(fixture.read("people.csv"), common::PEOPLE);
+}
@@ -0,0 +1,185 @@
+mod common;
+
+use common::Fixture;
+use predicates::prelude::*;
+use predicates::str::contains;
+
+const BOLD_CYAN: &str = "\x1b[1;36m";
+
+#[test]
+fn color_always_colors_headers_and_row_numbers() {
+    let fixture = Fixture::new();
+    fixture
+        .run(&["--color", "always", "head", "people.csv", "-n", "1"])
+        .assert()
+        .success()
+        .stdout(contains(format!("{BOLD_CYAN}Row\x1b[0m")))
+        .stdout(contains("\x1b[2m  0\x1b[0m"));
+}
+
+#[test]
+fn color_is_off_when_piped_or_never() {
+    let fixture = Fixture::new();
+    for args in [&["head"][..], &["--color", "never", "head"]] {
+        fixture
+            .run(&[args, &["people.csv"]].concat())
+            .assert()
+            .success()
+            .stdout(contains("\x1b[").not());
+    }
+}
+
+#[test]
+fn no_color_does_not_override_color_always() {
+    let fixture = Fixture::new();
+    fixture
+        .run(&["head", "people.csv"])
+        .env("NO_COLOR", "1")
+        .assert()
+        .success()
+        .stdout(contains("\x1b[").not());
+    fixture
+        .run(&["head", "people.csv", "--color", "always"])
+        .env("NO_COLOR", "1")
+        .assert()
+        .success()
+        .stdout(contains(BOLD_CYAN));
+}
+
+#[test]
+fn vertical_prints_one_record_per_row() {
+    let fixture = Fixture::new();
+    fixture
+        .run(&["head", "people.csv", "-n", "2", "--vertical"])
+        .assert()
+        .success()
+        .stdout(
+            " -[ Row 0 ]\n \
+             id   | 1\n \
+             name | Alice\n \
+             city | Paris\n \
+             -[ Row 1 ]\n \
+             id   | 2\n \
+             name | Bob\n \
+             city | Berlin\n",
+        );
+}
+
+#[test]
+fn vertical_prints_values_in_full() {
+    let fixture = Fixture::new();
+    fixture
+        .run(&[
+            "--max-width",
+            "3",
+            "--vertical",
+            "head",
+            "people.csv",
+            "-n",
+            "1",
+        ])
+        .assert()
+        .success()
+        .stdout(contains("name | Alice\n"));
+}
+
+#[test]
+fn max_width_cuts_values_and_col_width_overrides_it() {
+    let fixture = Fixture::new();
+    fixture
+        .run(&[
+            "head",
+            "people.csv",
+            "-n",
+            "2",
+            "--max-width",
+            "3",
+            "--col-width",
+            "name=5",
+        ])
+        .assert()
+        .success()
+        .stdout(
+            " Row | id | name  | city\n\
+             -----+----+-------+-----\n\
+             \x20  0 | 1  | Alice | P...\n\
+             \x20  1 | 2  | Bob   | B...\n",
+        );
+}
+
+#[test]
+fn col_width_needs_column_and_width() {
+    let fixture = Fixture::new();
+    fixture
+        .run(&["head", "people.csv", "--col-width", "name"])
+        .assert()
+        .code(2)
+        .stderr(contains("expected COLUMN=CHARS"));
+}
+
+#[test]
+fn max_cols_prints_a_window_of_columns_at_a_time() {
+    let fixture = Fixture::new();
+    let output = fixture
+        .run(&["head", "people.csv", "-n", "1", "--max-cols", "2"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let output = String::from_utf8(output).unwrap();
+    let headers: Vec<_> = output.lines().filter(|l| l.starts_with(" Row")).collect();
+    assert_eq!(headers.len(), 2, "{output}");
+    assert!(headers[0].contains("id") && headers[0].contains("name"));
+    assert!(!headers[0].contains("city") && headers[1].contains("city"));
+}
+
+#[test]
+fn export_streams_to_stdout_without_output() {
+    let fixture = Fixture::new();
+    for args in [&[][..], &["-o", "-"]] {
+        fixture
+            .run(
+                &[
+                    &["export", "people.csv", "--where", r#"city == "Rome""#][..],
+                    args,
+                ]
+                .concat(),
+            )
+            .assert()
+            .success()
+            .stdout("id,name,city\n4,Dave,Rome\n");
+    }
+    fixture
+        .run(&[
+            "export",
+            "people.csv",
+            "--format",
+            "ndjson",
+            "--rows",
+            "0-0",
+        ])
+        .assert()
+        .success()
+        .stdout("{\"id\":\"1\",\"name\":\"Alice\",\"city\":\"Paris\"}\n");
+}
+
+#[test]
+fn export_writes_a_file() {
+    let fixture = Fixture::new();
+    fixture
+        .run(&[
+            "export",
+            "people.csv",
+            "-o",
+            "copy.csv",
+            "--columns",
+            "name",
+        ])
+        .assert()
+        .success();
+    assert_eq!(
+        fixture.read("copy.csv"),
+        "name\nAlice\nBob\nCarol\nDave\nEve\n"
+    );
+}
@@ -0,0 +1,125 @@
+mod common;
+
+use common::Fixture;
+use predicates::str::contains;
+
+#[test]
+fn head_prints_the_first_rows() {
+    let fixture = Fixture::new();
+    fixture
+        .run(&["head", "people.csv", "-n", "2"])
+        .assert()
+        .success()
+        .stdout(
+            " Row | id | name  | city  \n\
+             -----+----+-------+-------\n\
+             \x20  0 | 1  | Alice | Paris \n\
+             \x20  1 | 2  | Bob   | Berlin\n",
+        );
+}
+
+#[test]
+fn head_and_tail_print_csv() {
+    let fixture = Fixture::new();
+    fixture
+        .run(&["head", "people.csv", "-n", "2", "--format", "csv"])
+        .assert()
+        .success()
+        .stdout("id,name,city\n1,Alice,Paris\n2,Bob,Berlin\n");
+    fixture
+        .run(&["tail", "people.csv", "-n", "2", "--format", "csv"])
+        .assert()
+        .success()
+        .stdout("id,name,city\n4,Dave,Rome\n5,Eve,Paris\n");
+}
+
+#[test]
+fn tail_of_more_rows_than_the_file_has_prints_them_all() {
+    let fixture = Fixture::new();
+    fixture
+        .run(&["tail", "people.csv", "-n", "50", "--format", "csv"])
+        .assert()
+        .success()
+        .stdout(common::PEOPLE);
+}
+
+#[test]
+fn get_prints_one_cell_or_row() {
+    let fixture = Fixture::new();
+    fixture
+        .run(&["get", "people.csv", "--row", "1", "--col", "name"])
+        .assert()
+        .success()
+        .stdout("Bob\n");
+    fixture
+        .run(&["get", "people.csv", "--row", "1", "--col", "2"])
+        .assert()
+        .success()
+        .stdout("Berlin\n");
+    fixture
+        .run(&["get", "people.csv", "--row", "1", "--raw"])
+        .assert()
+        .success()
+        .stdout("2,Bob,Berlin\n");
+}
+
+#[test]
+fn get_exits_1_for_a_missing_row_or_column() {
+    let fixture = Fixture::new();
+    fixture
+        .run(&["get", "people.csv", "--row", "9", "--col", "name"])
+        .assert()
+        .code(1)
+        .stdout("")
+        .stderr(contains("Row 9 is out of range"));
+    fixture
+        .run(&["get", "people.csv", "--row", "1", "--col", "nope"])
+        .assert()
+        .code(1)
+        .stdout("")
+        .stderr(contains("Column 'nope' not found"));
+}
+
+#[test]
+fn get_needs_a_column_or_raw() {
+    let fixture = Fixture::new();
+    fixture
+        .run(&["get", "people.csv", "--row", "1"])
+        .assert()
+        .code(2);
+}
+
+#[test]
+fn count_prints_rows_or_matches() {
+    let fixture = Fixture::new();
+    fixture
+        .run(&["count", "people.csv"])
+        .assert()
+        .success()
+        .stdout("5\n");
+    fixture
+        .run(&["count", "people.csv", "--where", r#"city == "Paris""#])
+        .assert()
+        .success()
+        .stdout("3\n");
+    fixture
+        .run(&[
+            "--json",
+            "count",
+            "people.csv",
+            "--where",
+            r#"city == "Rome""#,
+        ])
+        .assert()
+        .success()
+        .stdout(contains(r#""count": 1"#));
+}
+
+#[test]
+fn count_rejects_a_bad_filter() {
+    let fixture = Fixture::new();
+    fixture
+        .run(&["count", "people.csv", "--where", "nope == 1"])
+        .assert()
+        .code(1);
+}
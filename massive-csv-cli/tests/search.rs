@@ -0,0 +1,105 @@
+mod common;
+
+use common::Fixture;
+use predicates::prelude::*;
+use predicates::str::contains;
+
+#[test]
+fn search_pages_through_matches() {
+    let fixture = Fixture::new();
+    fixture
+        .run(&["search", "people.csv", "Paris", "-n", "1", "--page", "2"])
+        .assert()
+        .success()
+        .stdout(contains("   2 | 3  | Carol | Paris"))
+        .stdout(contains("Alice").not())
+        .stdout(contains("add --offset 2 for the next 1"));
+    fixture
+        .run(&[
+            "search",
+            "people.csv",
+            "Paris",
+            "-n",
+            "1",
+            "--offset",
+            "2",
+            "--format",
+            "csv",
+        ])
+        .assert()
+        .success()
+        .stdout("id,name,city\n5,Eve,Paris\n");
+}
+
+#[test]
+fn search_offset_and_page_conflict() {
+    let fixture = Fixture::new();
+    fixture
+        .run(&[
+            "search",
+            "people.csv",
+            "Paris",
+            "--offset",
+            "1",
+            "--page",
+            "1",
+        ])
+        .assert()
+        .code(2);
+}
+
+#[test]
+fn search_shows_context_rows() {
+    let fixture = Fixture::new();
+    fixture
+        .run(&["search", "people.csv", "Bob", "-C", "1"])
+        .assert()
+        .success()
+        .stdout(contains(
+            "   0 | 1  | Alice | Paris \n\
+             >  1 | 2  | Bob   | Berlin\n\
+             \x20  2 | 3  | Carol | Paris \n",
+        ));
+    fixture
+        .run(&["search", "people.csv", "Bob", "-A", "1", "--format", "csv"])
+        .assert()
+        .success()
+        .stdout("id,name,city\n2,Bob,Berlin\n3,Carol,Paris\n");
+    fixture
+        .run(&["search", "people.csv", "Bob", "-B", "1", "--format", "csv"])
+        .assert()
+        .success()
+        .stdout("id,name,city\n1,Alice,Paris\n2,Bob,Berlin\n");
+}
+
+#[test]
+fn search_format_csv_streams_matches_and_reports_on_stderr() {
+    let fixture = Fixture::new();
+    fixture
+        .run(&["search", "people.csv", "Paris", "--format", "csv"])
+        .assert()
+        .success()
+        .stdout("id,name,city\n1,Alice,Paris\n3,Carol,Paris\n5,Eve,Paris\n")
+        .stderr(contains("Found 3 matches"));
+}
+
+#[test]
+fn search_output_saves_matches() {
+    let fixture = Fixture::new();
+    fixture
+        .run(&["search", "people.csv", "Rome", "-o", "rome.csv"])
+        .assert()
+        .success();
+    assert_eq!(fixture.read("rome.csv"), "id,name,city\n4,Dave,Rome\n");
+}
+
+#[test]
+fn search_output_refuses_a_print_format() {
+    let fixture = Fixture::new();
+    fixture
+        .run(&["search", "people.csv", "Paris", "--output", "csv"])
+        .assert()
+        .code(2)
+        .stderr(contains("use --format csv"));
+    assert!(!fixture.path("csv").exists());
+}
@@ -0,0 +1,478 @@
+//! Rows as an Arrow IPC stream, for Arrow-based consumers.
+//!
+//! [arrow-js](https://arrow.apache.org/docs/js/) (`tableFromIPC`),
+//! DuckDB-Wasm, pyarrow and the like read the output directly. This avoids
+//! the cost of building one string per field on the other side of an FFI
+//! boundary. Every column is a non-nullable `Utf8` column named by its
+//! canonical header; empty fields are empty strings. Rows are written in
+//! record batches of at most [`BATCH_ROWS`].
+//!
+//! The stream is written without the `arrow` crate: the few flatbuffer
+//! tables it needs (`Schema`, `Field`, `RecordBatch`) are encoded here.
+
+use std::io::Write;
+use std::ops::Range;
+
+use rayon::prelude::*;
+
+use crate::editor::CsvEditor;
+use crate::error::{MassiveCsvError, Result};
+use crate::reader::CsvReader;
+
+/// Rows per record batch written by [`CsvReader::arrow_ipc`] and
+/// [`CsvEditor::arrow_ipc`].
+pub const BATCH_ROWS: usize = 64 * 1024;
+
+/// Arrow IPC metadata version 5.
+const METADATA_V5: i16 = 4;
+/// `MessageHeader` union tags.
+const HEADER_SCHEMA: u8 = 1;
+const HEADER_RECORD_BATCH: u8 = 3;
+/// `Type` union tag for `Utf8`.
+const TYPE_UTF8: u8 = 5;
+
+/// Writes an Arrow IPC stream of `Utf8` columns: the schema when created,
+/// then one record batch per [`write_batch`](Self::write_batch), then the
+/// end-of-stream marker on [`finish`](Self::finish).
+pub struct IpcWriter<W: Write> {
+    out: W,
+    columns: usize,
+}
+
+impl<W: Write> IpcWriter<W> {
+    pub fn new(mut out: W, columns: &[String]) -> Result<Self> {
+        let fields = columns
+            .iter()
+            .map(|name| {
+                Fb::Table(vec![
+                    (0, Fb::String(name.clone())),
+                    (1, Fb::Bool(false)),
+                    (2, Fb::Byte(TYPE_UTF8)),
+                    (3, Fb::Table(vec![])),
+                    (5, Fb::Tables(vec![])),
+                ])
+            })
+            .collect();
+        let schema = Fb::Table(vec![(0, Fb::Short(0)), (1, Fb::Tables(fields))]);
+        write_message(&mut out, HEADER_SCHEMA, schema, &[])?;
+        Ok(Self {
+            out,
+            columns: columns.len(),
+        })
+    }
+
+    /// Write `rows` as one record batch. Missing fields are empty strings
+    /// and extra fields are ignored.
+    pub fn write_batch(&mut self, rows: &[Vec<String>]) -> Result<()> {
+        let mut body = Vec::new();
+        let mut nodes = Vec::with_capacity(self.columns * 16);
+        let mut buffers = Vec::with_capacity(self.columns * 48);
+        let mut push_buffer = |body: &mut Vec<u8>, data: &[u8]| {
+            buffers.extend_from_slice(&(body.len() as i64).to_le_bytes());
+            buffers.extend_from_slice(&(data.len() as i64).to_le_bytes());
+            body.extend_from_slice(data);
+            pad_to_8(body);
+        };
+
+        for column in 0..self.columns {
+            nodes.extend_from_slice(&(rows.len() as i64).to_le_bytes());
+            nodes.extend_from_slice(&0i64.to_le_bytes());
+
+            let mut offsets = Vec::with_capacity((rows.len() + 1) * 4);
+            let mut data = Vec::new();
+            offsets.extend_from_slice(&0i32.to_le_bytes());
+            for row in rows {
+                data.extend_from_slice(row.get(column).map_or("", String::as_str).as_bytes());
+                let end = i32::try_from(data.len()).map_err(|_| {
+                    MassiveCsvError::Parse(format!(
+                        "column {column} holds over 2 GB of text in one Arrow batch"
+                    ))
+                })?;
+                offsets.extend_from_slice(&end.to_le_bytes());
+            }
+            // No validity bitmap: no field is null
+            push_buffer(&mut body, &[]);
+            push_buffer(&mut body, &offsets);
+            push_buffer(&mut body, &data);
+        }
+
+        let batch = Fb::Table(vec![
+            (0, Fb::Long(rows.len() as i64)),
+            (1, Fb::Structs(nodes)),
+            (2, Fb::Structs(buffers)),
+        ]);
+        write_message(&mut self.out, HEADER_RECORD_BATCH, batch, &body)
+    }
+
+    /// Write the end-of-stream marker and return the output.
+    pub fn finish(mut self) -> Result<W> {
+        self.out.write_all(&[0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0, 0])?;
+        Ok(self.out)
+    }
+}
+
+impl CsvReader {
+    /// Rows `start..end` (clamped to the file) as an Arrow IPC stream.
+    pub fn arrow_ipc(&self, start: usize, end: usize) -> Result<Vec<u8>> {
+        ipc_stream(
+            self.canonical_headers(),
+            start..end.min(self.row_count()),
+            |i| self.get_row(i),
+        )
+    }
+}
+
+impl CsvEditor {
+    /// Rows `start..end` (clamped to the file) as an Arrow IPC stream,
+    /// with pending edits applied.
+    pub fn arrow_ipc(&self, start: usize, end: usize) -> Result<Vec<u8>> {
+        let reader = self.reader();
+        ipc_stream(
+            reader.canonical_headers(),
+            start..end.min(reader.row_count()),
+            |i| self.get_row(i),
+        )
+    }
+}
+
+fn ipc_stream(
+    headers: &[String],
+    rows: Range<usize>,
+    get_row: impl Fn(usize) -> Result<Vec<String>> + Sync,
+) -> Result<Vec<u8>> {
+    let mut writer = IpcWriter::new(Vec::new(), headers)?;
+    let mut start = rows.start;
+    while start < rows.end {
+        let end = (start + BATCH_ROWS).min(rows.end);
+        let batch: Vec<Vec<String>> = (start..end)
+            .into_par_iter()
+            .map(&get_row)
+            .collect::<Result<_>>()?;
+        writer.write_batch(&batch)?;
+        start = end;
+    }
+    writer.finish()
+}
+
+/// Write an encapsulated IPC message: continuation marker, metadata size,
+/// the flatbuffer `Message` padded to 8 bytes, then the body.
+fn write_message(out: &mut dyn Write, header_type: u8, header: Fb, body: &[u8]) -> Result<()> {
+    let message = Fb::Table(vec![
+        (0, Fb::Short(METADATA_V5)),
+        (1, Fb::Byte(header_type)),
+        (2, header),
+        (3, Fb::Long(body.len() as i64)),
+    ]);
+    let mut metadata = message.finish();
+    pad_to_8(&mut metadata);
+    out.write_all(&[0xFF, 0xFF, 0xFF, 0xFF])?;
+    out.write_all(&(metadata.len() as i32).to_le_bytes())?;
+    out.write_all(&metadata)?;
+    out.write_all(body)?;
+    Ok(())
+}
+
+fn pad_to_8(buf: &mut Vec<u8>) {
+    buf.resize(buf.len().next_multiple_of(8), 0);
+}
+
+/// Just enough of a flatbuffer to describe Arrow messages.
+///
+/// Objects are written front to back: a table comes before the strings,
+/// tables and vectors it refers to, so every offset points forward as
+/// flatbuffers require.
+enum Fb {
+    Bool(bool),
+    Byte(u8),
+    Short(i16),
+    Long(i64),
+    String(String),
+    /// Fields by id; absent ids take their schema default.
+    Table(Vec<(u16, Fb)>),
+    Tables(Vec<Fb>),
+    /// A vector of 8-byte aligned structs, already encoded.
+    Structs(Vec<u8>),
+}
+
+impl Fb {
+    /// Encode with this table as the root.
+    fn finish(&self) -> Vec<u8> {
+        let mut buf = vec![0; 4];
+        let root = self.write(&mut buf);
+        patch_offset(&mut buf, 0, root);
+        buf
+    }
+
+    /// Inline size of a table field.
+    fn inline_size(&self) -> usize {
+        match self {
+            Fb::Bool(_) | Fb::Byte(_) => 1,
+            Fb::Short(_) => 2,
+            Fb::Long(_) => 8,
+            _ => 4,
+        }
+    }
+
+    /// Append this object and return its position.
+    fn write(&self, buf: &mut Vec<u8>) -> usize {
+        match self {
+            Fb::String(s) => {
+                align(buf, 4, 0);
+                let pos = buf.len();
+                buf.extend_from_slice(&(s.len() as u32).to_le_bytes());
+                buf.extend_from_slice(s.as_bytes());
+                buf.push(0);
+                pos
+            }
+            Fb::Structs(bytes) => {
+                // The elements after the length must be 8-aligned
+                align(buf, 8, 4);
+                let pos = buf.len();
+                buf.extend_from_slice(&((bytes.len() / 16) as u32).to_le_bytes());
+                buf.extend_from_slice(bytes);
+                pos
+            }
+            Fb::Tables(items) => {
+                align(buf, 4, 0);
+                let pos = buf.len();
+                buf.extend_from_slice(&(items.len() as u32).to_le_bytes());
+                buf.resize(buf.len() + 4 * items.len(), 0);
+                for (i, item) in items.iter().enumerate() {
+                    let child = item.write(buf);
+                    patch_offset(buf, pos + 4 + 4 * i, child);
+                }
+                pos
+            }
+            Fb::Table(fields) => write_table(fields, buf),
+            _ => unreachable!("scalars are written inline in their table"),
+        }
+    }
+}
+
+fn write_table(fields: &[(u16, Fb)], buf: &mut Vec<u8>) -> usize {
+    // Lay out fields after the vtable offset, largest first, each aligned
+    // to its size (the table itself is 8-aligned)
+    let mut order: Vec<&(u16, Fb)> = fields.iter().collect();
+    order.sort_by_key(|(_, value)| std::cmp::Reverse(value.inline_size()));
+    let mut layout = Vec::with_capacity(order.len());
+    let mut size: usize = 4;
+    for (id, value) in order {
+        let width = value.inline_size();
+        size = size.next_multiple_of(width);
+        layout.push((*id, value, size));
+        size += width;
+    }
+
+    let slots = fields
+        .iter()
+        .map(|(id, _)| *id as usize + 1)
+        .max()
+        .unwrap_or(0);
+    let mut vtable = vec![0u16; 2 + slots];
+    vtable[0] = (4 + 2 * slots) as u16;
+    vtable[1] = size as u16;
+    for &(id, _, at) in &layout {
+        vtable[2 + id as usize] = at as u16;
+    }
+    align(buf, 2, 0);
+    let vtable_pos = buf.len();
+    for entry in vtable {
+        buf.extend_from_slice(&entry.to_le_bytes());
+    }
+
+    align(buf, 8, 0);
+    let table = buf.len();
+    buf.resize(table + size, 0);
+    buf[table..table + 4].copy_from_slice(&((table - vtable_pos) as i32).to_le_bytes());
+    let mut children = Vec::new();
+    for (_, value, at) in layout {
+        let at = table + at;
+        match value {
+            Fb::Bool(v) => buf[at] = u8::from(*v),
+            Fb::Byte(v) => buf[at] = *v,
+            Fb::Short(v) => buf[at..at + 2].copy_from_slice(&v.to_le_bytes()),
+            Fb::Long(v) => buf[at..at + 8].copy_from_slice(&v.to_le_bytes()),
+            child => children.push((at, child)),
+        }
+    }
+    for (at, child) in children {
+        let pos = child.write(buf);
+        patch_offset(buf, at, pos);
+    }
+    table
+}
+
+/// Pad until the length is `offset` past a multiple of `to`.
+fn align(buf: &mut Vec<u8>, to: usize, offset: usize) {
+    while buf.len() % to != offset {
+        buf.push(0);
+    }
+}
+
+/// Point the offset stored at `at` to `target`.
+fn patch_offset(buf: &mut [u8], at: usize, target: usize) {
+    buf[at..at + 4].copy_from_slice(&((target - at) as u32).to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reads back the flatbuffer tables written above, checking alignment.
+    struct Table<'a> {
+        buf: &'a [u8],
+        pos: usize,
+    }
+
+    impl<'a> Table<'a> {
+        fn root(buf: &'a [u8]) -> Self {
+            Self::at(buf, 0)
+        }
+
+        /// The table an offset at `at` points to.
+        fn at(buf: &'a [u8], at: usize) -> Self {
+            let pos = at + u32_at(buf, at) as usize;
+            assert_eq!(pos % 4, 0);
+            Self { buf, pos }
+        }
+
+        fn field(&self, id: usize) -> Option<usize> {
+            let vtable = self.pos
+                - i32::from_le_bytes(self.buf[self.pos..self.pos + 4].try_into().unwrap()) as usize;
+            let vtable_size = u16_at(self.buf, vtable) as usize;
+            if 4 + 2 * id >= vtable_size {
+                return None;
+            }
+            match u16_at(self.buf, vtable + 4 + 2 * id) {
+                0 => None,
+                offset => Some(self.pos + offset as usize),
+            }
+        }
+
+        fn long(&self, id: usize) -> i64 {
+            let at = self.field(id).unwrap();
+            assert_eq!(at % 8, 0, "misaligned long");
+            i64::from_le_bytes(self.buf[at..at + 8].try_into().unwrap())
+        }
+
+        fn byte(&self, id: usize) -> u8 {
+            self.buf[self.field(id).unwrap()]
+        }
+
+        fn table(&self, id: usize) -> Table<'a> {
+            Table::at(self.buf, self.field(id).unwrap())
+        }
+
+        fn string(&self, id: usize) -> &'a str {
+            let at = self.field(id).unwrap();
+            let pos = at + u32_at(self.buf, at) as usize;
+            let len = u32_at(self.buf, pos) as usize;
+            std::str::from_utf8(&self.buf[pos + 4..pos + 4 + len]).unwrap()
+        }
+
+        /// Elements of a vector of tables.
+        fn tables(&self, id: usize) -> Vec<Table<'a>> {
+            let at = self.field(id).unwrap();
+            let pos = at + u32_at(self.buf, at) as usize;
+            (0..u32_at(self.buf, pos) as usize)
+                .map(|i| Table::at(self.buf, pos + 4 + 4 * i))
+                .collect()
+        }
+
+        /// Pairs of longs in a vector of 16-byte structs.
+        fn structs(&self, id: usize) -> Vec<(i64, i64)> {
+            let at = self.field(id).unwrap();
+            let pos = at + u32_at(self.buf, at) as usize;
+            assert_eq!((pos + 4) % 8, 0, "misaligned structs");
+            let long = |i: usize| i64::from_le_bytes(self.buf[i..i + 8].try_into().unwrap());
+            (0..u32_at(self.buf, pos) as usize)
+                .map(|i| (long(pos + 4 + 16 * i), long(pos + 12 + 16 * i)))
+                .collect()
+        }
+    }
+
+    fn u32_at(buf: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes(buf[at..at + 4].try_into().unwrap())
+    }
+
+    fn u16_at(buf: &[u8], at: usize) -> u16 {
+        u16::from_le_bytes(buf[at..at + 2].try_into().unwrap())
+    }
+
+    /// Split a stream into (message, body) pairs.
+    fn messages(mut stream: &[u8]) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let mut out = Vec::new();
+        loop {
+            assert_eq!(&stream[..4], &[0xFF; 4]);
+            let len = u32_at(stream, 4) as usize;
+            if len == 0 {
+                assert_eq!(stream.len(), 8);
+                return out;
+            }
+            assert_eq!(len % 8, 0);
+            let metadata = stream[8..8 + len].to_vec();
+            let body_len = Table::root(&metadata).long(3) as usize;
+            out.push((metadata, stream[8 + len..8 + len + body_len].to_vec()));
+            stream = &stream[8 + len + body_len..];
+        }
+    }
+
+    fn make_csv(content: &str) -> tempfile::NamedTempFile {
+        let mut f = tempfile::NamedTempFile::new().unwrap();
+        f.write_all(content.as_bytes()).unwrap();
+        f.flush().unwrap();
+        f
+    }
+
+    #[test]
+    fn writes_schema_and_batches() {
+        let f = make_csv("id,name,\n1,Ann,x\n2,\"Bö, b\",\n3,Cy,z\n");
+        let reader = CsvReader::open(f.path()).unwrap();
+        let mut editor = CsvEditor::new(reader);
+        editor.set_cell(2, 1, "Cyd".to_string()).unwrap();
+        let stream = editor.arrow_ipc(1, 100).unwrap();
+
+        let messages = messages(&stream);
+        assert_eq!(messages.len(), 2);
+
+        let schema_message = Table::root(&messages[0].0);
+        assert_eq!(schema_message.byte(1), HEADER_SCHEMA);
+        let fields = schema_message.table(2).tables(1);
+        let names: Vec<&str> = fields.iter().map(|f| f.string(0)).collect();
+        assert_eq!(names, ["id", "name", "column_3"]);
+        assert!(fields.iter().all(|f| f.byte(2) == TYPE_UTF8));
+
+        let (metadata, body) = &messages[1];
+        let batch_message = Table::root(metadata);
+        assert_eq!(batch_message.byte(1), HEADER_RECORD_BATCH);
+        let batch = batch_message.table(2);
+        assert_eq!(batch.long(0), 2);
+        assert_eq!(batch.structs(1), vec![(2, 0); 3]);
+
+        // validity, offsets and data for each column
+        let buffers = batch.structs(2);
+        assert_eq!(buffers.len(), 9);
+        let column = |c: usize| -> Vec<String> {
+            let (offsets_at, _) = buffers[3 * c + 1];
+            let (data_at, data_len) = buffers[3 * c + 2];
+            let data = &body[data_at as usize..(data_at + data_len) as usize];
+            (0..2)
+                .map(|i| {
+                    let offset = |i: usize| u32_at(body, offsets_at as usize + 4 * i) as usize;
+                    String::from_utf8(data[offset(i)..offset(i + 1)].to_vec()).unwrap()
+                })
+                .collect()
+        };
+        assert_eq!(column(0), ["2", "3"]);
+        assert_eq!(column(1), ["Bö, b", "Cyd"]);
+        assert_eq!(column(2), ["", "z"]);
+        assert!(buffers.iter().all(|(offset, _)| offset % 8 == 0));
+    }
+
+    #[test]
+    fn empty_range_has_only_a_schema() {
+        let f = make_csv("a\n1\n");
+        let reader = CsvReader::open(f.path()).unwrap();
+        assert_eq!(messages(&reader.arrow_ipc(5, 10).unwrap()).len(), 1);
+    }
+}
@@ -0,0 +1,242 @@
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use crate::error::{MassiveCsvError, Result};
+
+/// State of a background build.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildStatus {
+    /// Queued; the thread has not started work yet.
+    Pending,
+    /// Running, with the percentage done.
+    Building(u8),
+    /// Finished successfully; the result can be taken.
+    Ready,
+    /// Finished with an error.
+    Failed(String),
+}
+
+const PENDING: u8 = 0;
+const BUILDING: u8 = 1;
+const READY: u8 = 2;
+const FAILED: u8 = 3;
+
+#[derive(Debug, Default)]
+struct Shared {
+    phase: AtomicU8,
+    percent: AtomicU8,
+    error: Mutex<Option<String>>,
+}
+
+/// Called with the units of work done and the total.
+type ProgressCallback = Arc<dyn Fn(usize, usize) + Send + Sync>;
+
+/// Progress reporter handed to work running under [`BuildHandle::spawn`],
+/// or to long operations such as opening, searching and saving. It can
+/// also carry a flag that cancels the work.
+///
+/// The default reporter goes nowhere, so the same build code can run in the
+/// foreground.
+#[derive(Clone, Default)]
+pub struct Progress {
+    shared: Option<Arc<Shared>>,
+    callback: Option<ProgressCallback>,
+    cancel: Option<Arc<AtomicBool>>,
+}
+
+impl Progress {
+    /// A reporter that passes each update to `callback`, which may be
+    /// called from several threads.
+    pub fn new(callback: impl Fn(usize, usize) + Send + Sync + 'static) -> Self {
+        Self {
+            shared: None,
+            callback: Some(Arc::new(callback)),
+            cancel: None,
+        }
+    }
+
+    /// This reporter, stopping the work it is handed to once `cancel` is
+    /// set: its next [`update`](Self::update) fails.
+    pub fn with_cancel(mut self, cancel: Arc<AtomicBool>) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    /// Record that `done` of `total` units of work are complete. Fails with
+    /// [`MassiveCsvError::Cancelled`] if the work has been cancelled, so
+    /// work stops at its next update and returns the error.
+    pub fn update(&self, done: usize, total: usize) -> Result<()> {
+        if let Some(shared) = &self.shared {
+            let percent = (done.min(total) * 100).checked_div(total).unwrap_or(100) as u8;
+            shared.percent.store(percent, Ordering::Relaxed);
+        }
+        if let Some(callback) = &self.callback {
+            callback(done, total);
+        }
+        match &self.cancel {
+            Some(cancel) if cancel.load(Ordering::Relaxed) => Err(MassiveCsvError::Cancelled),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl std::fmt::Debug for Progress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Progress")
+            .field("shared", &self.shared)
+            .field("callback", &self.callback.is_some())
+            .field("cancel", &self.cancel)
+            .finish()
+    }
+}
+
+/// Handle to work running on its own thread, such as an index build.
+///
+/// Callers poll [`status`](Self::status) and keep answering queries the slow
+/// way until it reports [`BuildStatus::Ready`].
+pub struct BuildHandle<T> {
+    shared: Arc<Shared>,
+    result: Arc<Mutex<Option<T>>>,
+    cancel: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl<T: Send + 'static> BuildHandle<T> {
+    /// Run `work` on a new thread.
+    pub fn spawn<F>(work: F) -> Self
+    where
+        F: FnOnce(&Progress) -> Result<T> + Send + 'static,
+    {
+        let shared = Arc::new(Shared::default());
+        let result = Arc::new(Mutex::new(None));
+        let cancel = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let shared = Arc::clone(&shared);
+            let result = Arc::clone(&result);
+            let cancel = Arc::clone(&cancel);
+            thread::spawn(move || {
+                shared.phase.store(BUILDING, Ordering::Release);
+                let progress = Progress {
+                    shared: Some(Arc::clone(&shared)),
+                    callback: None,
+                    cancel: Some(cancel),
+                };
+                match work(&progress) {
+                    Ok(value) => {
+                        *result.lock().unwrap_or_else(|e| e.into_inner()) = Some(value);
+                        shared.percent.store(100, Ordering::Relaxed);
+                        shared.phase.store(READY, Ordering::Release);
+                    }
+                    Err(e) => {
+                        *shared.error.lock().unwrap_or_else(|e| e.into_inner()) =
+                            Some(e.to_string());
+                        shared.phase.store(FAILED, Ordering::Release);
+                    }
+                }
+            })
+        };
+
+        Self {
+            shared,
+            result,
+            cancel,
+            thread: Some(thread),
+        }
+    }
+
+    /// Current state of the build.
+    pub fn status(&self) -> BuildStatus {
+        match self.shared.phase.load(Ordering::Acquire) {
+            PENDING => BuildStatus::Pending,
+            BUILDING => BuildStatus::Building(self.shared.percent.load(Ordering::Relaxed)),
+            READY => BuildStatus::Ready,
+            _ => BuildStatus::Failed(
+                self.shared
+                    .error
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .clone()
+                    .unwrap_or_default(),
+            ),
+        }
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.shared.phase.load(Ordering::Acquire) == READY
+    }
+
+    /// Take the result if the build is ready. Returns `None` before that, and
+    /// after the result has been taken.
+    pub fn take(&self) -> Option<T> {
+        if !self.is_ready() {
+            return None;
+        }
+        self.result.lock().unwrap_or_else(|e| e.into_inner()).take()
+    }
+
+    /// Stop the build at its next progress update and wait for its thread
+    /// to finish, releasing whatever the work holds, such as an open file.
+    pub fn cancel(mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+
+    /// Block until the build finishes and return its result.
+    pub fn wait(mut self) -> Result<T> {
+        if let Some(thread) = self.thread.take() {
+            thread
+                .join()
+                .map_err(|_| MassiveCsvError::Background("build thread panicked".to_string()))?;
+        }
+        match self.status() {
+            BuildStatus::Failed(message) => Err(MassiveCsvError::Background(message)),
+            _ => self.take().ok_or_else(|| {
+                MassiveCsvError::Background("build result already taken".to_string())
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn reports_progress_then_ready() {
+        let (go, wait) = mpsc::channel::<()>();
+        let handle = BuildHandle::spawn(move |progress| {
+            progress.update(1, 4)?;
+            wait.recv().unwrap();
+            Ok(42)
+        });
+
+        while handle.status() != BuildStatus::Building(25) {
+            thread::yield_now();
+        }
+        assert_eq!(handle.take(), None);
+
+        go.send(()).unwrap();
+        assert_eq!(handle.wait().unwrap(), 42);
+    }
+
+    #[test]
+    fn cancel_stops_the_build() {
+        let handle: BuildHandle<()> = BuildHandle::spawn(|progress| loop {
+            progress.update(1, 2)?;
+            thread::yield_now();
+        });
+        handle.cancel();
+    }
+
+    #[test]
+    fn failures_are_reported() {
+        let handle: BuildHandle<()> =
+            BuildHandle::spawn(|_| Err(MassiveCsvError::Parse("bad".to_string())));
+        assert!(matches!(handle.wait(), Err(MassiveCsvError::Background(_))));
+    }
+}
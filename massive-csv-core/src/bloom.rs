@@ -0,0 +1,80 @@
+/// A fixed-size Bloom filter over byte strings.
+///
+/// Membership tests never give false negatives; false positives occur at
+/// roughly the rate the filter was sized for. Keys are hashed once with
+/// FNV-1a and the probe positions derived by double hashing, so a miss costs
+/// far less than a hash-set lookup.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BloomFilter {
+    bits: Vec<u64>,
+    hashes: u32,
+}
+
+impl BloomFilter {
+    /// A filter sized for `items` keys at the given false-positive rate.
+    pub fn new(items: usize, false_positive_rate: f64) -> Self {
+        let items = items.max(1) as f64;
+        let rate = false_positive_rate.clamp(1e-9, 0.5);
+        let ln2 = std::f64::consts::LN_2;
+        let bits = (-(items * rate.ln()) / (ln2 * ln2)).ceil().max(64.0) as usize;
+        let hashes = ((bits as f64 / items) * ln2).round().clamp(1.0, 16.0) as u32;
+        Self {
+            bits: vec![0; bits.div_ceil(64)],
+            hashes,
+        }
+    }
+
+    /// Add a key.
+    pub fn insert(&mut self, key: &[u8]) {
+        for bit in self.positions(key) {
+            self.bits[bit / 64] |= 1 << (bit % 64);
+        }
+    }
+
+    /// Whether the key may have been inserted. `false` is definite.
+    pub fn might_contain(&self, key: &[u8]) -> bool {
+        self.positions(key)
+            .all(|bit| self.bits[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+
+    /// Size of the bit array in bits.
+    pub fn bit_len(&self) -> usize {
+        self.bits.len() * 64
+    }
+
+    fn positions(&self, key: &[u8]) -> impl Iterator<Item = usize> {
+        let hash = fnv1a(key);
+        let h1 = hash & 0xFFFF_FFFF;
+        let h2 = (hash >> 32) | 1;
+        let len = self.bit_len() as u64;
+        (0..self.hashes as u64).map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % len) as usize)
+    }
+}
+
+pub(crate) fn fnv1a(key: &[u8]) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
+    for &b in key {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_false_negatives_and_few_false_positives() {
+        let mut bloom = BloomFilter::new(10_000, 0.01);
+        for i in 0..10_000 {
+            bloom.insert(format!("key-{i}").as_bytes());
+        }
+        assert!((0..10_000).all(|i| bloom.might_contain(format!("key-{i}").as_bytes())));
+
+        let false_positives = (0..10_000)
+            .filter(|i| bloom.might_contain(format!("other-{i}").as_bytes()))
+            .count();
+        assert!(false_positives < 300, "{false_positives} false positives");
+    }
+}
@@ -0,0 +1,197 @@
+//! Content hashes of a file's data, for checking that a copy or a
+//! transformation left it unchanged.
+
+use rayon::prelude::*;
+use sha2::{Digest, Sha256};
+
+use crate::error::Result;
+use crate::reader::CsvReader;
+use crate::view::select_columns;
+
+/// Rows hashed per parallel batch.
+const CHUNK_ROWS: usize = 64 * 1024;
+
+/// Options for [`checksum`].
+#[derive(Debug, Clone, Default)]
+pub struct ChecksumOptions {
+    /// Hash only these columns, in this order (default: all): names,
+    /// indices or ranges, as in [`select_columns`].
+    pub columns: Option<Vec<String>>,
+    /// Ignore the order of rows and whitespace around values, so a sorted
+    /// or re-padded copy of the data hashes the same.
+    pub normalize: bool,
+}
+
+/// The result of [`checksum`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checksum {
+    /// SHA-256 of the data, as 64 hex digits.
+    pub hash: String,
+    /// Rows hashed.
+    pub rows: usize,
+    /// Names of the columns hashed, in order.
+    pub columns: Vec<String>,
+}
+
+/// Hash the data in `reader`'s file: the header names and the value of
+/// every row, in each of the chosen columns.
+///
+/// Values are hashed as parsed rather than as written, so the same data
+/// with another delimiter, quoting, line ending, encoding or compression
+/// hashes the same. Each row is hashed on its own, in parallel; the row
+/// hashes are then combined in order, or with `normalize` summed so their
+/// order doesn't matter.
+pub fn checksum(reader: &CsvReader, options: &ChecksumOptions) -> Result<Checksum> {
+    let headers = reader.headers();
+    let selected = match &options.columns {
+        Some(columns) => Some(select_columns(headers, columns)?),
+        None => None,
+    };
+    let columns: Vec<String> = match &selected {
+        Some(selected) => selected.iter().map(|&i| headers[i].clone()).collect(),
+        None => headers.to_vec(),
+    };
+
+    let digest = |values: &mut dyn Iterator<Item = &str>| -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        for value in values {
+            let value = if options.normalize {
+                value.trim()
+            } else {
+                value
+            };
+            // Length-prefixed, so no two rows of values hash the same input
+            hasher.update((value.len() as u64).to_le_bytes());
+            hasher.update(value.as_bytes());
+        }
+        hasher.finalize().into()
+    };
+    let row_digest = |row: usize| -> Result<[u8; 32]> {
+        let fields = reader.get_row(row)?;
+        Ok(match &selected {
+            Some(selected) => digest(
+                &mut selected
+                    .iter()
+                    .map(|&c| fields.get(c).map_or("", String::as_str)),
+            ),
+            None => digest(&mut fields.iter().map(String::as_str)),
+        })
+    };
+
+    let mut hasher = Sha256::new();
+    hasher.update(if options.normalize {
+        b"unordered" as &[u8]
+    } else {
+        b"ordered"
+    });
+    hasher.update(digest(&mut columns.iter().map(String::as_str)));
+
+    let rows = reader.row_count();
+    let mut sum = [0u8; 32];
+    let mut start = 0;
+    while start < rows {
+        let end = (start + CHUNK_ROWS).min(rows);
+        let digests: Vec<[u8; 32]> = (start..end)
+            .into_par_iter()
+            .map(row_digest)
+            .collect::<Result<_>>()?;
+        for digest in &digests {
+            if options.normalize {
+                add_digest(&mut sum, digest);
+            } else {
+                hasher.update(digest);
+            }
+        }
+        start = end;
+    }
+    if options.normalize {
+        hasher.update(sum);
+    }
+    hasher.update((rows as u64).to_le_bytes());
+
+    Ok(Checksum {
+        hash: hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect(),
+        rows,
+        columns,
+    })
+}
+
+/// Add `digest` to `sum` as 256-bit little-endian numbers, wrapping.
+fn add_digest(sum: &mut [u8; 32], digest: &[u8; 32]) {
+    let mut carry = 0u16;
+    for (s, d) in sum.iter_mut().zip(digest) {
+        let total = *s as u16 + *d as u16 + carry;
+        *s = total as u8;
+        carry = total >> 8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn make_csv(content: &str) -> tempfile::NamedTempFile {
+        let mut f = tempfile::NamedTempFile::new().unwrap();
+        f.write_all(content.as_bytes()).unwrap();
+        f.flush().unwrap();
+        f
+    }
+
+    fn hash(content: &str, options: &ChecksumOptions) -> String {
+        let f = make_csv(content);
+        checksum(&CsvReader::open(f.path()).unwrap(), options)
+            .unwrap()
+            .hash
+    }
+
+    #[test]
+    fn hashes_values_not_formatting() {
+        let plain = ChecksumOptions::default();
+        let original = hash("id,name\n1,Ann\n2,\"Bo, Jr\"\n", &plain);
+        assert_eq!(original.len(), 64);
+        assert_eq!(hash("id;name\r\n1;\"Ann\"\r\n2;Bo, Jr", &plain), original);
+        assert_ne!(hash("id,name\n2,\"Bo, Jr\"\n1,Ann\n", &plain), original);
+        assert_ne!(hash("id,name\n1,Ann\n2,Bo\n", &plain), original);
+        assert_ne!(hash("id,nom\n1,Ann\n2,\"Bo, Jr\"\n", &plain), original);
+        // Values can't run into each other
+        assert_ne!(hash("a,b\nxy,z\n", &plain), hash("a,b\nx,yz\n", &plain));
+
+        let normalized = ChecksumOptions {
+            normalize: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            hash("id,name\n2, Bo \n1,Ann\n", &normalized),
+            hash("id,name\n1,Ann\n2,Bo\n", &normalized)
+        );
+        assert_ne!(
+            hash("id,name\n1,Ann\n1,Ann\n", &normalized),
+            hash("id,name\n1,Ann\n", &normalized)
+        );
+    }
+
+    #[test]
+    fn hashes_chosen_columns() {
+        let ids = ChecksumOptions {
+            columns: Some(vec!["id".to_string()]),
+            ..Default::default()
+        };
+        let f = make_csv("id,name\n1,Ann\n2,Bo\n");
+        let result = checksum(&CsvReader::open(f.path()).unwrap(), &ids).unwrap();
+        assert_eq!(result.columns, vec!["id"]);
+        assert_eq!(result.rows, 2);
+        assert_eq!(result.hash, hash("id,name\n1,Al\n2,Cy\n", &ids));
+        assert_ne!(result.hash, hash("id,name\n1,Ann\n3,Bo\n", &ids));
+
+        let missing = ChecksumOptions {
+            columns: Some(vec!["nope".to_string()]),
+            ..Default::default()
+        };
+        assert!(checksum(&CsvReader::open(f.path()).unwrap(), &missing).is_err());
+    }
+}
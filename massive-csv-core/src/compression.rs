@@ -0,0 +1,194 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::error::Result;
+
+/// Compression of a file, recognised by its leading magic bytes.
+///
+/// Compressed files are decompressed into an anonymous temporary file when
+/// opened, so they need free disk space for the uncompressed data. Saving
+/// compresses again in the same format unless
+/// [`SaveOptions::compression`](crate::SaveOptions::compression) says
+/// otherwise; output is compressed as it is written, so memory use does not
+/// grow with file size. Needs the `compression` feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    pub fn name(self) -> &'static str {
+        match self {
+            Compression::None => "none",
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+        }
+    }
+
+    /// Parse `gzip` (or `gz`), `zstd` (or `zst`) or `none`.
+    pub fn from_label(label: &str) -> Option<Self> {
+        match label.to_ascii_lowercase().as_str() {
+            "none" => Some(Compression::None),
+            "gzip" | "gz" => Some(Compression::Gzip),
+            "zstd" | "zst" => Some(Compression::Zstd),
+            _ => None,
+        }
+    }
+
+    /// The compression a file name asks for: `.gz` or `.zst`, else none.
+    pub fn for_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("gz") => Compression::Gzip,
+            Some(ext) if ext.eq_ignore_ascii_case("zst") => Compression::Zstd,
+            _ => Compression::None,
+        }
+    }
+
+    /// The compression `data` starts with, if any.
+    pub fn detect(data: &[u8]) -> Self {
+        match data {
+            [0x1F, 0x8B, ..] => Compression::Gzip,
+            [0x28, 0xB5, 0x2F, 0xFD, ..] => Compression::Zstd,
+            _ => Compression::None,
+        }
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Decompress `data` into an anonymous temporary file. Concatenated gzip
+/// members and zstd frames are all read.
+#[cfg(feature = "compression")]
+pub(crate) fn decompress_to_tempfile(data: &[u8], compression: Compression) -> Result<File> {
+    let mut out = io::BufWriter::new(tempfile::tempfile()?);
+    match compression {
+        Compression::None => out.write_all(data)?,
+        Compression::Gzip => {
+            io::copy(&mut flate2::bufread::MultiGzDecoder::new(data), &mut out)?;
+        }
+        Compression::Zstd => {
+            io::copy(&mut zstd::Decoder::with_buffer(data)?, &mut out)?;
+        }
+    }
+    out.into_inner().map_err(|e| e.into_error().into())
+}
+
+#[cfg(not(feature = "compression"))]
+pub(crate) fn decompress_to_tempfile(_data: &[u8], compression: Compression) -> Result<File> {
+    Err(disabled(compression))
+}
+
+#[cfg(not(feature = "compression"))]
+fn disabled(compression: Compression) -> crate::error::MassiveCsvError {
+    crate::error::MassiveCsvError::FeatureDisabled(
+        match compression {
+            Compression::Zstd => "Reading or writing zstd files",
+            _ => "Reading or writing gzip files",
+        },
+        "compression",
+    )
+}
+
+/// Compresses everything written through it; [`finish`](Self::finish)
+/// writes the trailer.
+pub(crate) enum Compressor<'a> {
+    Plain(&'a mut dyn Write),
+    #[cfg(feature = "compression")]
+    Gzip(flate2::write::GzEncoder<&'a mut dyn Write>),
+    #[cfg(feature = "compression")]
+    Zstd(zstd::Encoder<'static, &'a mut dyn Write>),
+}
+
+impl<'a> Compressor<'a> {
+    pub(crate) fn new(out: &'a mut dyn Write, compression: Compression) -> Result<Self> {
+        Ok(match compression {
+            Compression::None => Compressor::Plain(out),
+            #[cfg(feature = "compression")]
+            Compression::Gzip => Compressor::Gzip(flate2::write::GzEncoder::new(
+                out,
+                flate2::Compression::default(),
+            )),
+            #[cfg(feature = "compression")]
+            Compression::Zstd => Compressor::Zstd(zstd::Encoder::new(out, 0)?),
+            #[cfg(not(feature = "compression"))]
+            compression => return Err(disabled(compression)),
+        })
+    }
+
+    pub(crate) fn finish(self) -> Result<()> {
+        match self {
+            Compressor::Plain(_) => {}
+            #[cfg(feature = "compression")]
+            Compressor::Gzip(encoder) => drop(encoder.finish()?),
+            #[cfg(feature = "compression")]
+            Compressor::Zstd(encoder) => drop(encoder.finish()?),
+        }
+        Ok(())
+    }
+}
+
+impl Write for Compressor<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Compressor::Plain(out) => out.write(buf),
+            #[cfg(feature = "compression")]
+            Compressor::Gzip(encoder) => encoder.write(buf),
+            #[cfg(feature = "compression")]
+            Compressor::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Compressor::Plain(out) => out.flush(),
+            #[cfg(feature = "compression")]
+            Compressor::Gzip(encoder) => encoder.flush(),
+            #[cfg(feature = "compression")]
+            Compressor::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_magic_bytes() {
+        assert_eq!(Compression::detect(b"\x1f\x8b\x08\0"), Compression::Gzip);
+        assert_eq!(
+            Compression::detect(b"\x28\xb5\x2f\xfd\0"),
+            Compression::Zstd
+        );
+        assert_eq!(Compression::detect(b"a,b\n"), Compression::None);
+        assert_eq!(Compression::detect(b""), Compression::None);
+    }
+
+    #[test]
+    fn labels_and_extensions() {
+        assert_eq!(Compression::from_label("ZSTD"), Some(Compression::Zstd));
+        assert_eq!(Compression::from_label("gz"), Some(Compression::Gzip));
+        assert_eq!(Compression::from_label("bz2"), None);
+        assert_eq!(
+            Compression::for_path(Path::new("out.csv.GZ")),
+            Compression::Gzip
+        );
+        assert_eq!(
+            Compression::for_path(Path::new("out.csv.zst")),
+            Compression::Zstd
+        );
+        assert_eq!(
+            Compression::for_path(Path::new("out.csv")),
+            Compression::None
+        );
+    }
+}
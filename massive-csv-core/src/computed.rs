@@ -0,0 +1,255 @@
+//! Virtual columns computed from expressions over a row's fields, shown
+//! alongside the real ones and written into the file only on request.
+
+use std::path::Path;
+
+use crate::editor::{write_atomic, LineWriter};
+use crate::error::{MassiveCsvError, Result};
+use crate::parser::serialize_row;
+use crate::query::RowExpr;
+use crate::reader::CsvReader;
+
+/// A column whose value is an expression over other columns, e.g.
+/// `total = price * qty`. See [`RowExpr`] for the expression syntax.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComputedColumn {
+    pub name: String,
+    /// The expression as written.
+    pub expression: String,
+    expr: RowExpr,
+}
+
+impl ComputedColumn {
+    /// A column called `name` computing `expression` over `headers`.
+    pub fn new(name: impl Into<String>, expression: &str, headers: &[String]) -> Result<Self> {
+        let name = name.into();
+        if name.trim().is_empty() {
+            return Err(MassiveCsvError::Parse(
+                "computed column has no name".to_string(),
+            ));
+        }
+        if headers.contains(&name) {
+            return Err(MassiveCsvError::Parse(format!(
+                "column '{name}' already exists"
+            )));
+        }
+        Ok(Self {
+            expr: RowExpr::parse(expression, headers)?,
+            expression: expression.trim().to_string(),
+            name,
+        })
+    }
+
+    /// Parse a definition written `name = expression`.
+    pub fn parse(definition: &str, headers: &[String]) -> Result<Self> {
+        let (name, expression) = definition.split_once('=').ok_or_else(|| {
+            MassiveCsvError::Parse(format!("expected 'name = expression', got '{definition}'"))
+        })?;
+        Self::new(name.trim(), expression, headers)
+    }
+
+    /// The value for a row with `fields`.
+    pub fn value(&self, fields: &[String]) -> String {
+        self.expr.eval(fields)
+    }
+}
+
+/// Computed columns shown after a file's own columns. Each may use the
+/// columns defined before it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComputedColumns {
+    base_width: usize,
+    columns: Vec<ComputedColumn>,
+}
+
+impl ComputedColumns {
+    /// No computed columns, for a file with `headers`.
+    pub fn new(headers: &[String]) -> Self {
+        Self {
+            base_width: headers.len(),
+            columns: Vec::new(),
+        }
+    }
+
+    /// Parse `name = expression` definitions, in order, for a file with
+    /// `headers`.
+    pub fn parse<S: AsRef<str>>(definitions: &[S], headers: &[String]) -> Result<Self> {
+        let mut computed = Self::new(headers);
+        for definition in definitions {
+            let column = ComputedColumn::parse(definition.as_ref(), &computed.headers(headers))?;
+            computed.columns.push(column);
+        }
+        Ok(computed)
+    }
+
+    /// Add `name = expression` after the existing computed columns.
+    pub fn add(&mut self, definition: &str, headers: &[String]) -> Result<&ComputedColumn> {
+        let column = ComputedColumn::parse(definition, &self.headers(headers))?;
+        self.columns.push(column);
+        Ok(self.columns.last().expect("just added"))
+    }
+
+    /// Remove the computed column `name`, and every later one using it.
+    /// Returns whether there was one.
+    pub fn remove(&mut self, name: &str, headers: &[String]) -> bool {
+        let Some(position) = self.columns.iter().position(|c| c.name == name) else {
+            return false;
+        };
+        let later: Vec<ComputedColumn> = self.columns.drain(position..).skip(1).collect();
+        for column in later {
+            let headers = self.headers(headers);
+            if let Ok(column) = ComputedColumn::new(column.name, &column.expression, &headers) {
+                self.columns.push(column);
+            }
+        }
+        true
+    }
+
+    pub fn columns(&self) -> &[ComputedColumn] {
+        &self.columns
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.columns.is_empty()
+    }
+
+    /// The file's `headers` followed by the computed columns' names.
+    pub fn headers(&self, headers: &[String]) -> Vec<String> {
+        let mut all = headers.to_vec();
+        all.resize(self.base_width, String::new());
+        all.extend(self.columns.iter().map(|c| c.name.clone()));
+        all
+    }
+
+    /// Append the computed values to a row's `fields`. A row with more or
+    /// fewer fields than the header is first cut or padded to its width.
+    pub fn extend(&self, fields: &mut Vec<String>) {
+        if self.columns.is_empty() {
+            return;
+        }
+        fields.resize(self.base_width, String::new());
+        for column in &self.columns {
+            let value = column.value(fields);
+            fields.push(value);
+        }
+    }
+}
+
+/// Write `reader`'s file with `computed`'s columns as real columns at the
+/// end, to `output` (which may be the file itself). Returns the number of
+/// rows written.
+///
+/// Rows with as many fields as the header keep their original bytes, with
+/// the new values added after a delimiter.
+pub fn materialize(reader: &CsvReader, computed: &ComputedColumns, output: &Path) -> Result<usize> {
+    if reader.fixed_width().is_some() {
+        return Err(MassiveCsvError::Parse(
+            "can't add columns to a fixed-width file".to_string(),
+        ));
+    }
+    let dialect = reader.dialect();
+    let width = reader.headers().len();
+    let names: Vec<String> = computed.columns().iter().map(|c| c.name.clone()).collect();
+    let delimiter = char::from(dialect.delimiter);
+
+    write_atomic(output, |writer| {
+        let mut lines = LineWriter::new(writer, reader.line_ending());
+        let header = format!(
+            "{}{delimiter}{}",
+            reader.header_raw()?,
+            serialize_row(&names, dialect)
+        );
+        lines.write_line(&header)?;
+        for row in 0..reader.row_count() {
+            let mut fields = reader.get_row(row)?;
+            let verbatim = fields.len() == width;
+            computed.extend(&mut fields);
+            if verbatim {
+                let line = format!(
+                    "{}{delimiter}{}",
+                    reader.get_row_raw(row)?,
+                    serialize_row(&fields[width..], dialect)
+                );
+                lines.write_line(&line)?;
+            } else {
+                lines.write_line(&serialize_row(&fields, dialect))?;
+            }
+        }
+        lines.finish(reader.has_trailing_newline())
+    })?;
+    Ok(reader.row_count())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn make_csv(content: &str) -> tempfile::NamedTempFile {
+        let mut f = tempfile::NamedTempFile::new().unwrap();
+        f.write_all(content.as_bytes()).unwrap();
+        f.flush().unwrap();
+        f
+    }
+
+    const ORDERS: &str = "first,last,price,qty\nAda,Lovelace,2.5,4\n\"Grace, R\",Hopper,10,\n";
+
+    #[test]
+    fn computes_values_per_row() {
+        let f = make_csv(ORDERS);
+        let reader = CsvReader::open(f.path()).unwrap();
+        let computed = ComputedColumns::parse(
+            &[
+                "total = price * qty",
+                "full_name = first || ' ' || last",
+                "big = total > 5",
+            ],
+            reader.headers(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            computed.headers(reader.headers()),
+            vec!["first", "last", "price", "qty", "total", "full_name", "big"]
+        );
+        let mut row = reader.get_row(0).unwrap();
+        computed.extend(&mut row);
+        assert_eq!(&row[4..], &["10", "Ada Lovelace", "1"]);
+        // Empty qty is NULL, so the total and the comparison are too
+        let mut row = reader.get_row(1).unwrap();
+        computed.extend(&mut row);
+        assert_eq!(&row[4..], &["", "Grace, R Hopper", ""]);
+
+        assert!(ComputedColumns::parse(&["price = 1"], reader.headers()).is_err());
+        assert!(ComputedColumns::parse(&["total"], reader.headers()).is_err());
+        assert!(ComputedColumns::parse(&["x = nope + 1"], reader.headers()).is_err());
+    }
+
+    #[test]
+    fn removes_dependent_columns() {
+        let headers: Vec<String> = vec!["a".into()];
+        let mut computed =
+            ComputedColumns::parse(&["b = a + 1", "c = b * 2", "d = a"], &headers).unwrap();
+        assert!(computed.remove("b", &headers));
+        let names: Vec<&str> = computed.columns().iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["d"]);
+        assert!(!computed.remove("b", &headers));
+    }
+
+    #[test]
+    fn materializes_into_the_file() {
+        let f = make_csv(ORDERS);
+        let reader = CsvReader::open(f.path()).unwrap();
+        let computed =
+            ComputedColumns::parse(&["total = price * qty", "note = 'a, b'"], reader.headers())
+                .unwrap();
+
+        assert_eq!(materialize(&reader, &computed, f.path()).unwrap(), 2);
+        assert_eq!(
+            std::fs::read_to_string(f.path()).unwrap(),
+            "first,last,price,qty,total,note\n\
+             Ada,Lovelace,2.5,4,10,\"a, b\"\n\
+             \"Grace, R\",Hopper,10,,,\"a, b\"\n"
+        );
+    }
+}
@@ -0,0 +1,183 @@
+//! Concatenating files with the same (or overlapping) columns into one.
+
+use std::path::{Path, PathBuf};
+
+use crate::editor::{write_atomic, LineWriter};
+use crate::error::{MassiveCsvError, Result};
+use crate::parser::serialize_row;
+use crate::reader::CsvReader;
+
+/// Options for [`concat`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConcatOptions {
+    /// Write the union of all files' columns, leaving the ones a file lacks
+    /// empty, instead of requiring every file to have the first file's
+    /// columns.
+    pub union: bool,
+}
+
+/// Result of [`concat`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConcatSummary {
+    /// The output's columns.
+    pub columns: Vec<String>,
+    /// Rows written from each input, in order.
+    pub files: Vec<(PathBuf, usize)>,
+    pub rows_written: usize,
+}
+
+/// Write the rows of every reader, in order, under one header to `output`.
+///
+/// Without `union`, each file must have the same columns as the first,
+/// though possibly in another order. Columns are matched by name and
+/// written in the first file's order and dialect; rows of files that already
+/// match it are copied byte-for-byte.
+pub fn concat(
+    readers: &[CsvReader],
+    output: &Path,
+    options: &ConcatOptions,
+) -> Result<ConcatSummary> {
+    let Some(first) = readers.first() else {
+        return Err(MassiveCsvError::Parse(
+            "no files to concatenate".to_string(),
+        ));
+    };
+
+    let mut columns: Vec<String> = first.canonical_headers().to_vec();
+    for reader in &readers[1..] {
+        let headers = reader.canonical_headers();
+        if options.union {
+            for header in headers {
+                if !columns.contains(header) {
+                    columns.push(header.clone());
+                }
+            }
+            continue;
+        }
+        let mut sorted = headers.to_vec();
+        let mut expected = columns.clone();
+        sorted.sort_unstable();
+        expected.sort_unstable();
+        if sorted != expected {
+            return Err(MassiveCsvError::HeaderMismatch(format!(
+                "{} has [{}], {} has [{}]",
+                first.path().display(),
+                columns.join(", "),
+                reader.path().display(),
+                headers.join(", ")
+            )));
+        }
+    }
+
+    // For each input, the output position of each of its columns
+    let mappings: Vec<Vec<usize>> = readers
+        .iter()
+        .map(|reader| {
+            reader
+                .canonical_headers()
+                .iter()
+                .map(|h| columns.iter().position(|c| c == h).expect("column"))
+                .collect()
+        })
+        .collect();
+    let dialect = first.dialect();
+    let verbatim = |i: usize| {
+        readers[i].dialect() == dialect
+            && mappings[i].len() == columns.len()
+            && mappings[i].iter().enumerate().all(|(a, &b)| a == b)
+    };
+
+    let mut files = Vec::with_capacity(readers.len());
+    write_atomic(output, |writer| {
+        let mut lines = LineWriter::new(writer, first.line_ending());
+        if verbatim(0) {
+            lines.write_line(first.header_raw()?)?;
+        } else {
+            lines.write_line(&serialize_row(&columns, dialect))?;
+        }
+        for (i, reader) in readers.iter().enumerate() {
+            let verbatim = verbatim(i);
+            for row in 0..reader.row_count() {
+                if verbatim {
+                    lines.write_line(reader.get_row_raw(row)?)?;
+                    continue;
+                }
+                let mut fields = vec![String::new(); columns.len()];
+                for (field, &to) in reader.get_row(row)?.into_iter().zip(&mappings[i]) {
+                    fields[to] = field;
+                }
+                lines.write_line(&serialize_row(&fields, dialect))?;
+            }
+            files.push((reader.path().to_path_buf(), reader.row_count()));
+        }
+        lines.finish(true)
+    })?;
+
+    Ok(ConcatSummary {
+        rows_written: files.iter().map(|(_, rows)| rows).sum(),
+        columns,
+        files,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn make_csv(content: &str) -> tempfile::NamedTempFile {
+        let mut f = tempfile::NamedTempFile::new().unwrap();
+        f.write_all(content.as_bytes()).unwrap();
+        f.flush().unwrap();
+        f
+    }
+
+    fn open(files: &[&tempfile::NamedTempFile]) -> Vec<CsvReader> {
+        files
+            .iter()
+            .map(|f| CsvReader::open(f.path()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn concatenates_matching_files() {
+        let a = make_csv("id,name\n1,\"Ann, A\"\n2,Bo\n");
+        let b = make_csv("name,id\nCy,3\n");
+        let c = make_csv("id;name\n4;Di");
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("all.csv");
+
+        let summary = concat(&open(&[&a, &b, &c]), &output, &ConcatOptions::default()).unwrap();
+        assert_eq!(summary.columns, vec!["id", "name"]);
+        assert_eq!(
+            summary.files.iter().map(|f| f.1).collect::<Vec<_>>(),
+            vec![2, 1, 1]
+        );
+        assert_eq!(summary.rows_written, 4);
+        assert_eq!(
+            std::fs::read_to_string(&output).unwrap(),
+            "id,name\n1,\"Ann, A\"\n2,Bo\n3,Cy\n4,Di\n"
+        );
+
+        let d = make_csv("id,email\n5,e@x.io\n");
+        assert!(matches!(
+            concat(&open(&[&a, &d]), &output, &ConcatOptions::default()),
+            Err(MassiveCsvError::HeaderMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn unions_columns() {
+        let a = make_csv("id,name\n1,Ann\n");
+        let b = make_csv("id,email\n2,b@x.io\n");
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("all.csv");
+
+        let summary = concat(&open(&[&a, &b]), &output, &ConcatOptions { union: true }).unwrap();
+        assert_eq!(summary.columns, vec!["id", "name", "email"]);
+        assert_eq!(
+            std::fs::read_to_string(&output).unwrap(),
+            "id,name,email\n1,Ann,\n2,,b@x.io\n"
+        );
+    }
+}
@@ -0,0 +1,568 @@
+//! Detecting the format of a date column and rewriting it into another.
+//!
+//! Formats are strftime-style patterns using `%Y` (four-digit year), `%m`,
+//! `%d` (one or two digits), `%b` (month name, `Jan` or `January`), `%H`,
+//! `%M` and `%S`; every other character must appear as written.
+
+use std::fmt;
+use std::path::Path;
+
+use rayon::prelude::*;
+
+use crate::editor::{write_atomic, LineWriter};
+use crate::error::{MassiveCsvError, Result};
+use crate::infer::{sample_row_numbers, ColumnType};
+use crate::parser::serialize_row;
+use crate::reader::CsvReader;
+
+/// Rows processed per parallel batch.
+const CHUNK_ROWS: usize = 64 * 1024;
+
+/// Formats tried by [`detect_date_format`], and as fallbacks for values
+/// the detected format doesn't fit. Where day and month could be either
+/// way round, month first wins ties.
+pub const KNOWN_FORMATS: &[&str] = &[
+    "%Y-%m-%d",
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%d %H:%M",
+    "%Y/%m/%d",
+    "%Y%m%d",
+    "%m/%d/%Y",
+    "%m/%d/%Y %H:%M:%S",
+    "%m/%d/%Y %H:%M",
+    "%d/%m/%Y",
+    "%d/%m/%Y %H:%M:%S",
+    "%d/%m/%Y %H:%M",
+    "%m-%d-%Y",
+    "%d-%m-%Y",
+    "%d.%m.%Y",
+    "%d.%m.%Y %H:%M",
+    "%d %b %Y",
+    "%d-%b-%Y",
+    "%b %d, %Y",
+    "%b %d %Y",
+];
+
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Item {
+    Year,
+    Month,
+    MonthName,
+    Day,
+    Hour,
+    Minute,
+    Second,
+    Literal(char),
+}
+
+/// A date or date-and-time value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DateValue {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    /// Hour, minute and second, if the value had a time.
+    pub time: Option<(u8, u8, u8)>,
+}
+
+impl DateValue {
+    /// `YYYY-MM-DD`, or `YYYY-MM-DDTHH:MM:SS` if there is a time.
+    pub fn to_iso8601(&self) -> String {
+        let date = format!("{:04}-{:02}-{:02}", self.year, self.month, self.day);
+        match self.time {
+            Some((h, m, s)) => format!("{date}T{h:02}:{m:02}:{s:02}"),
+            None => date,
+        }
+    }
+}
+
+/// A date pattern such as `%d/%m/%Y`; see the [module docs](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DateFormat {
+    pattern: String,
+    items: Vec<Item>,
+}
+
+impl DateFormat {
+    /// Parse a pattern. It must contain a year, a month and a day.
+    pub fn parse(pattern: &str) -> Result<Self> {
+        let mut items = Vec::new();
+        let mut chars = pattern.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                items.push(Item::Literal(c));
+                continue;
+            }
+            items.push(match chars.next() {
+                Some('Y') => Item::Year,
+                Some('m') => Item::Month,
+                Some('b') => Item::MonthName,
+                Some('d') => Item::Day,
+                Some('H') => Item::Hour,
+                Some('M') => Item::Minute,
+                Some('S') => Item::Second,
+                Some('%') => Item::Literal('%'),
+                other => {
+                    return Err(MassiveCsvError::Parse(format!(
+                        "unknown date field '%{}' in '{pattern}'",
+                        other.map(String::from).unwrap_or_default()
+                    )))
+                }
+            });
+        }
+        let has = |item| items.contains(&item);
+        if !has(Item::Year) || !(has(Item::Month) || has(Item::MonthName)) || !has(Item::Day) {
+            return Err(MassiveCsvError::Parse(format!(
+                "date format '{pattern}' needs a year, month and day"
+            )));
+        }
+        Ok(Self {
+            pattern: pattern.to_string(),
+            items,
+        })
+    }
+
+    /// The pattern as written.
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Whether the format includes a time of day.
+    pub fn has_time(&self) -> bool {
+        self.items.contains(&Item::Hour)
+    }
+
+    /// Read `value` in this format, if it fits and is a real date.
+    pub fn read(&self, value: &str) -> Option<DateValue> {
+        let mut rest = value;
+        let (mut year, mut month, mut day) = (None, None, None);
+        let mut time = [None::<u8>; 3];
+        for item in &self.items {
+            match *item {
+                Item::Literal(c) => rest = rest.strip_prefix(c)?,
+                Item::Year => {
+                    let digits = rest
+                        .get(..4)
+                        .filter(|d| d.bytes().all(|b| b.is_ascii_digit()))?;
+                    year = Some(digits.parse().ok()?);
+                    rest = &rest[4..];
+                }
+                Item::MonthName => {
+                    let (number, len) = MONTHS.iter().zip(1..).find_map(|(name, number)| {
+                        [name.len(), 3].into_iter().find_map(|len| {
+                            let prefix = rest.get(..len)?;
+                            prefix
+                                .eq_ignore_ascii_case(&name[..len])
+                                .then_some((number, len))
+                        })
+                    })?;
+                    month = Some(number);
+                    rest = &rest[len..];
+                }
+                Item::Month | Item::Day | Item::Hour | Item::Minute | Item::Second => {
+                    let len = rest.bytes().take(2).take_while(u8::is_ascii_digit).count();
+                    if len == 0 {
+                        return None;
+                    }
+                    let n: u8 = rest[..len].parse().ok()?;
+                    rest = &rest[len..];
+                    match item {
+                        Item::Month => month = Some(n),
+                        Item::Day => day = Some(n),
+                        Item::Hour => time[0] = Some(n),
+                        Item::Minute => time[1] = Some(n),
+                        _ => time[2] = Some(n),
+                    }
+                }
+            }
+        }
+        if !rest.is_empty() {
+            return None;
+        }
+        let (year, month, day) = (year?, month?, day?);
+        if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+            return None;
+        }
+        let time = match time {
+            [None, None, None] => None,
+            [h, m, s] => {
+                let (h, m, s) = (h.unwrap_or(0), m.unwrap_or(0), s.unwrap_or(0));
+                if h > 23 || m > 59 || s > 59 {
+                    return None;
+                }
+                Some((h, m, s))
+            }
+        };
+        Some(DateValue {
+            year,
+            month,
+            day,
+            time,
+        })
+    }
+
+    /// Write `date` in this format. Time fields of a date without a time
+    /// are written as zero.
+    pub fn write(&self, date: &DateValue) -> String {
+        let (h, m, s) = date.time.unwrap_or((0, 0, 0));
+        let mut out = String::with_capacity(self.pattern.len() + 4);
+        for item in &self.items {
+            match *item {
+                Item::Literal(c) => out.push(c),
+                Item::Year => out.push_str(&format!("{:04}", date.year)),
+                Item::Month => out.push_str(&format!("{:02}", date.month)),
+                Item::MonthName => out.push_str(&MONTHS[date.month as usize - 1][..3]),
+                Item::Day => out.push_str(&format!("{:02}", date.day)),
+                Item::Hour => out.push_str(&format!("{h:02}")),
+                Item::Minute => out.push_str(&format!("{m:02}")),
+                Item::Second => out.push_str(&format!("{s:02}")),
+            }
+        }
+        out
+    }
+}
+
+impl fmt::Display for DateFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.pattern)
+    }
+}
+
+/// What [`normalize_dates`] rewrites values into.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DateTarget {
+    /// `YYYY-MM-DD`, with `THH:MM:SS` added for values that have a time.
+    Iso8601,
+    Format(DateFormat),
+}
+
+impl DateTarget {
+    /// Parse `iso8601` (or `iso`) or a pattern such as `%d/%m/%Y`.
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "iso8601" | "iso" => Ok(DateTarget::Iso8601),
+            _ => Ok(DateTarget::Format(DateFormat::parse(s)?)),
+        }
+    }
+
+    /// Write `date` in the target format.
+    pub fn write(&self, date: &DateValue) -> String {
+        match self {
+            DateTarget::Iso8601 => date.to_iso8601(),
+            DateTarget::Format(format) => format.write(date),
+        }
+    }
+}
+
+/// The [`KNOWN_FORMATS`] entry fitting the most of up to `sample_rows`
+/// non-empty values of column `col`, or `None` if none fits any.
+pub fn detect_date_format(
+    reader: &CsvReader,
+    col: usize,
+    sample_rows: usize,
+) -> Result<Option<DateFormat>> {
+    let formats = known_formats();
+    let mut counts = vec![0usize; formats.len()];
+    for row in sample_row_numbers(reader.row_count(), sample_rows) {
+        let fields = reader.get_row(row)?;
+        let Some(value) = fields.get(col).map(|v| v.trim()).filter(|v| !v.is_empty()) else {
+            continue;
+        };
+        for (count, format) in counts.iter_mut().zip(&formats) {
+            if format.read(value).is_some() {
+                *count += 1;
+            }
+        }
+    }
+    // The first of the formats with the highest count
+    let best = counts
+        .iter()
+        .enumerate()
+        .rev()
+        .max_by_key(|&(_, &count)| count)
+        .filter(|&(_, &count)| count > 0)
+        .map(|(i, _)| i);
+    Ok(best.map(|i| formats[i].clone()))
+}
+
+fn known_formats() -> Vec<DateFormat> {
+    KNOWN_FORMATS
+        .iter()
+        .map(|p| DateFormat::parse(p).expect("known formats are valid"))
+        .collect()
+}
+
+fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => {
+            29
+        }
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Options for [`normalize_dates`].
+#[derive(Debug, Clone)]
+pub struct NormalizeDatesOptions {
+    /// The column's format; detected from the values if `None`.
+    pub from: Option<DateFormat>,
+    pub to: DateTarget,
+    /// Rows sampled to detect the format.
+    pub sample_rows: usize,
+    /// Stop recording errors after this many (0 = unlimited). Every row is
+    /// still rewritten or kept.
+    pub max_errors: usize,
+}
+
+impl Default for NormalizeDatesOptions {
+    fn default() -> Self {
+        Self {
+            from: None,
+            to: DateTarget::Iso8601,
+            sample_rows: crate::infer::DEFAULT_SAMPLE_ROWS,
+            max_errors: 0,
+        }
+    }
+}
+
+/// A value [`normalize_dates`] could not read as a date, left as it was.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DateError {
+    pub row: usize,
+    pub value: String,
+}
+
+impl fmt::Display for DateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "row {}: {:?} is not a date", self.row, self.value)
+    }
+}
+
+/// Result of [`normalize_dates`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NormalizeDatesReport {
+    /// The format values were read in first.
+    pub format: DateFormat,
+    /// Rows whose value was rewritten.
+    pub converted: usize,
+    /// Of those, rows read in another of the [`KNOWN_FORMATS`] because
+    /// `format` didn't fit.
+    pub other_format: usize,
+    /// Rows whose value could not be read, in row order (up to
+    /// `max_errors`).
+    pub errors: Vec<DateError>,
+    /// Rows whose value could not be read, in total.
+    pub error_count: usize,
+}
+
+enum Outcome {
+    Kept,
+    Converted { line: String, fallback: bool },
+    Error(String),
+}
+
+/// Write `reader`'s file to `output` (which may be the file itself) with
+/// `column`'s dates rewritten into `options.to`.
+///
+/// Each value is read in `options.from`, or the detected format, and
+/// failing that in the first of the [`KNOWN_FORMATS`] that fits. Values
+/// that fit none are kept as they are and reported; so are empty values
+/// (silently), and values already ISO 8601 when that is the target.
+pub fn normalize_dates(
+    reader: &CsvReader,
+    column: &str,
+    options: &NormalizeDatesOptions,
+    output: &Path,
+) -> Result<NormalizeDatesReport> {
+    if reader.fixed_width().is_some() {
+        return Err(MassiveCsvError::Parse(
+            "can't rewrite columns of a fixed-width file".to_string(),
+        ));
+    }
+    let col = reader
+        .column_index(column)
+        .ok_or_else(|| MassiveCsvError::ColumnNotFound(column.to_string()))?;
+    let format = match &options.from {
+        Some(format) => format.clone(),
+        None => detect_date_format(reader, col, options.sample_rows)?.ok_or_else(|| {
+            MassiveCsvError::Parse(format!("no known date format fits column '{column}'"))
+        })?,
+    };
+    let fallbacks: Vec<DateFormat> = known_formats()
+        .into_iter()
+        .filter(|f| *f != format)
+        .collect();
+    let keep_iso = options.to == DateTarget::Iso8601;
+    let dialect = reader.dialect();
+    let row_count = reader.row_count();
+
+    let mut report = NormalizeDatesReport {
+        format: format.clone(),
+        converted: 0,
+        other_format: 0,
+        errors: Vec::new(),
+        error_count: 0,
+    };
+    write_atomic(output, |writer| {
+        let mut lines = LineWriter::new(writer, reader.line_ending());
+        lines.write_line(reader.header_raw()?)?;
+        let mut start = 0;
+        while start < row_count {
+            let end = (start + CHUNK_ROWS).min(row_count);
+            let outcomes: Vec<Outcome> = (start..end)
+                .into_par_iter()
+                .map(|row| {
+                    let mut fields = reader.get_row(row)?;
+                    let Some(value) = fields.get(col).map(|v| v.trim()) else {
+                        return Ok(Outcome::Kept);
+                    };
+                    if value.is_empty()
+                        || (keep_iso
+                            && matches!(
+                                ColumnType::of(value),
+                                Some(ColumnType::Date | ColumnType::DateTime)
+                            ))
+                    {
+                        return Ok(Outcome::Kept);
+                    }
+                    let (date, fallback) = match format.read(value) {
+                        Some(date) => (date, false),
+                        None => match fallbacks.iter().find_map(|f| f.read(value)) {
+                            Some(date) => (date, true),
+                            None => return Ok(Outcome::Error(value.to_string())),
+                        },
+                    };
+                    let normalized = options.to.write(&date);
+                    if normalized == fields[col] {
+                        return Ok(Outcome::Kept);
+                    }
+                    fields[col] = normalized;
+                    Ok(Outcome::Converted {
+                        line: serialize_row(&fields, dialect),
+                        fallback,
+                    })
+                })
+                .collect::<Result<_>>()?;
+            for (row, outcome) in (start..end).zip(outcomes) {
+                match outcome {
+                    Outcome::Converted { line, fallback } => {
+                        lines.write_line(&line)?;
+                        report.converted += 1;
+                        report.other_format += usize::from(fallback);
+                    }
+                    Outcome::Kept => lines.write_line(reader.get_row_raw(row)?)?,
+                    Outcome::Error(value) => {
+                        lines.write_line(reader.get_row_raw(row)?)?;
+                        report.error_count += 1;
+                        if options.max_errors == 0 || report.errors.len() < options.max_errors {
+                            report.errors.push(DateError { row, value });
+                        }
+                    }
+                }
+            }
+            start = end;
+        }
+        lines.finish(reader.has_trailing_newline())
+    })?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn make_csv(content: &str) -> tempfile::NamedTempFile {
+        let mut f = tempfile::NamedTempFile::new().unwrap();
+        f.write_all(content.as_bytes()).unwrap();
+        f.flush().unwrap();
+        f
+    }
+
+    #[test]
+    fn reads_and_writes_formats() {
+        let us = DateFormat::parse("%m/%d/%Y").unwrap();
+        let date = us.read("2/29/2024").unwrap();
+        assert_eq!(date.to_iso8601(), "2024-02-29");
+        assert_eq!(us.read("2/29/2023"), None);
+        assert_eq!(us.read("13/01/2024"), None);
+        assert_eq!(us.read("1/2/2024x"), None);
+
+        let named = DateFormat::parse("%d %b %Y %H:%M").unwrap();
+        let date = named.read("5 september 2024 7:05").unwrap();
+        assert_eq!(date.to_iso8601(), "2024-09-05T07:05:00");
+        assert_eq!(named.write(&date), "05 Sep 2024 07:05");
+        assert!(named.read("5 Sept 2024 7:05").is_none());
+
+        assert!(DateFormat::parse("%Y-%m").is_err());
+        assert!(DateFormat::parse("%Y-%m-%q").is_err());
+        assert_eq!(
+            DateTarget::parse("%d.%m.%Y").unwrap().write(
+                &DateFormat::parse("%Y%m%d")
+                    .unwrap()
+                    .read("20240131")
+                    .unwrap()
+            ),
+            "31.01.2024"
+        );
+    }
+
+    #[test]
+    fn detects_the_column_format() {
+        let f = make_csv("id,when\n1,31/01/2024\n2,05/02/2024\n3,\n4,12/12/2024\n");
+        let reader = CsvReader::open(f.path()).unwrap();
+        // 31/01 rules out month first
+        let detected = detect_date_format(&reader, 1, 100).unwrap().unwrap();
+        assert_eq!(detected.pattern(), "%d/%m/%Y");
+        assert_eq!(detect_date_format(&reader, 0, 100).unwrap(), None);
+    }
+
+    #[test]
+    fn normalizes_mixed_formats_with_errors() {
+        let f = make_csv(
+            "id,when\n1,01/31/2024\n2,02/05/2024\n3,2024-03-01\n4,\"Mar 4, 2024\"\n5,soon\n6,\n",
+        );
+        let out = tempfile::NamedTempFile::new().unwrap();
+        let reader = CsvReader::open(f.path()).unwrap();
+
+        let report = normalize_dates(
+            &reader,
+            "when",
+            &NormalizeDatesOptions::default(),
+            out.path(),
+        )
+        .unwrap();
+        assert_eq!(report.format.pattern(), "%m/%d/%Y");
+        assert_eq!(report.converted, 3);
+        assert_eq!(report.other_format, 1);
+        assert_eq!(
+            report.errors,
+            vec![DateError {
+                row: 4,
+                value: "soon".to_string()
+            }]
+        );
+        assert_eq!(
+            std::fs::read_to_string(out.path()).unwrap(),
+            "id,when\n1,2024-01-31\n2,2024-02-05\n3,2024-03-01\n4,2024-03-04\n5,soon\n6,\n"
+        );
+    }
+}
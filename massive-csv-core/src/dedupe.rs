@@ -0,0 +1,224 @@
+//! Finding rows that share a key, and writing a copy without the repeats.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+
+use rayon::prelude::*;
+
+use crate::editor::{write_atomic, LineWriter};
+use crate::error::{MassiveCsvError, Result};
+use crate::reader::CsvReader;
+
+/// Rows sharing one key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateGroup {
+    /// The key columns' values (the whole row when keyed on every column).
+    pub key: Vec<String>,
+    /// Rows with this key, in file order; at least two.
+    pub rows: Vec<usize>,
+}
+
+/// Which row of a duplicate group [`dedupe`] keeps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Keep {
+    #[default]
+    First,
+    Last,
+}
+
+/// Result of [`dedupe`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DedupeSummary {
+    pub rows_written: usize,
+    pub rows_removed: usize,
+    /// Keys that occurred more than once.
+    pub groups: usize,
+}
+
+/// Groups of rows with the same values in `key_columns` (every column if
+/// empty), ordered by their first row. Missing fields count as empty.
+///
+/// Rows are hashed in parallel and sorted by hash, so memory stays at a few
+/// words per row; only rows whose hashes collide are compared field by field.
+pub fn find_duplicates(reader: &CsvReader, key_columns: &[String]) -> Result<Vec<DuplicateGroup>> {
+    let columns: Vec<usize> = key_columns
+        .iter()
+        .map(|name| {
+            reader
+                .column_index(name)
+                .ok_or_else(|| MassiveCsvError::ColumnNotFound(name.clone()))
+        })
+        .collect::<Result<_>>()?;
+    let key_of = |row: usize| -> Result<Vec<String>> {
+        let fields = reader.get_row(row)?;
+        if columns.is_empty() {
+            return Ok(fields);
+        }
+        Ok(columns
+            .iter()
+            .map(|&c| fields.get(c).cloned().unwrap_or_default())
+            .collect())
+    };
+
+    let mut hashes: Vec<(u64, usize)> = (0..reader.row_count())
+        .into_par_iter()
+        .map(|row| {
+            let mut hasher = DefaultHasher::new();
+            key_of(row)?.hash(&mut hasher);
+            Ok((hasher.finish(), row))
+        })
+        .collect::<Result<_>>()?;
+    hashes.par_sort_unstable();
+
+    let runs: Vec<&[(u64, usize)]> = hashes
+        .chunk_by(|a, b| a.0 == b.0)
+        .filter(|run| run.len() > 1)
+        .collect();
+    let mut groups: Vec<DuplicateGroup> = runs
+        .into_par_iter()
+        .map(|run| {
+            // Rows are in order within a run; split it by the actual key
+            let mut by_key: Vec<DuplicateGroup> = Vec::new();
+            let mut positions: HashMap<Vec<String>, usize> = HashMap::new();
+            for &(_, row) in run {
+                let key = key_of(row)?;
+                match positions.get(&key) {
+                    Some(&i) => by_key[i].rows.push(row),
+                    None => {
+                        positions.insert(key.clone(), by_key.len());
+                        by_key.push(DuplicateGroup {
+                            key,
+                            rows: vec![row],
+                        });
+                    }
+                }
+            }
+            Ok(by_key)
+        })
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .flatten()
+        .filter(|group| group.rows.len() > 1)
+        .collect();
+    groups.sort_unstable_by_key(|group| group.rows[0]);
+    Ok(groups)
+}
+
+/// Write the reader's file to `output` (which may be the same file) with
+/// one row per key, keeping the first or last occurrence. Kept rows are
+/// copied byte-for-byte.
+pub fn dedupe(
+    reader: &CsvReader,
+    key_columns: &[String],
+    keep: Keep,
+    output: &Path,
+) -> Result<DedupeSummary> {
+    let groups = find_duplicates(reader, key_columns)?;
+    let mut removed: Vec<usize> = groups
+        .iter()
+        .flat_map(|group| match keep {
+            Keep::First => &group.rows[1..],
+            Keep::Last => &group.rows[..group.rows.len() - 1],
+        })
+        .copied()
+        .collect();
+    removed.par_sort_unstable();
+
+    let rows = reader.row_count();
+    write_atomic(output, |writer| {
+        let mut lines = LineWriter::new(writer, reader.line_ending());
+        lines.write_line(reader.header_raw()?)?;
+        let mut removed = removed.iter().peekable();
+        for row in 0..rows {
+            if removed.next_if(|&&r| r == row).is_none() {
+                lines.write_line(reader.get_row_raw(row)?)?;
+            }
+        }
+        lines.finish(reader.has_trailing_newline())
+    })?;
+
+    Ok(DedupeSummary {
+        rows_written: rows - removed.len(),
+        rows_removed: removed.len(),
+        groups: groups.len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn make_csv(content: &str) -> tempfile::NamedTempFile {
+        let mut f = tempfile::NamedTempFile::new().unwrap();
+        f.write_all(content.as_bytes()).unwrap();
+        f.flush().unwrap();
+        f
+    }
+
+    const PEOPLE: &str =
+        "email,name\na@x.io,Ann\nb@x.io,Bo\na@x.io,Ann B\nc@x.io,Cy\nb@x.io,Bo\na@x.io,Ann\n";
+
+    #[test]
+    fn finds_duplicate_groups() {
+        let f = make_csv(PEOPLE);
+        let reader = CsvReader::open(f.path()).unwrap();
+
+        let groups = find_duplicates(&reader, &["email".to_string()]).unwrap();
+        assert_eq!(
+            groups,
+            vec![
+                DuplicateGroup {
+                    key: vec!["a@x.io".to_string()],
+                    rows: vec![0, 2, 5],
+                },
+                DuplicateGroup {
+                    key: vec!["b@x.io".to_string()],
+                    rows: vec![1, 4],
+                },
+            ]
+        );
+
+        // Whole rows
+        let groups = find_duplicates(&reader, &[]).unwrap();
+        assert_eq!(
+            groups.iter().map(|g| g.rows.clone()).collect::<Vec<_>>(),
+            vec![vec![0, 5], vec![1, 4]]
+        );
+        assert!(matches!(
+            find_duplicates(&reader, &["phone".to_string()]),
+            Err(MassiveCsvError::ColumnNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn dedupes_keeping_first_or_last() {
+        let f = make_csv(PEOPLE);
+        let reader = CsvReader::open(f.path()).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("clean.csv");
+        let key = ["email".to_string()];
+
+        let summary = dedupe(&reader, &key, Keep::First, &output).unwrap();
+        assert_eq!(
+            summary,
+            DedupeSummary {
+                rows_written: 3,
+                rows_removed: 3,
+                groups: 2,
+            }
+        );
+        assert_eq!(
+            std::fs::read_to_string(&output).unwrap(),
+            "email,name\na@x.io,Ann\nb@x.io,Bo\nc@x.io,Cy\n"
+        );
+
+        dedupe(&reader, &key, Keep::Last, &output).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&output).unwrap(),
+            "email,name\nc@x.io,Cy\nb@x.io,Bo\na@x.io,Ann\n"
+        );
+    }
+}
@@ -0,0 +1,668 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use rayon::prelude::*;
+
+use crate::editor::{write_atomic, LineWriter};
+use crate::error::{MassiveCsvError, Result};
+use crate::parser::{column_position, serialize_row, serialize_row_preserving};
+use crate::reader::CsvReader;
+
+/// First field of the first record of every patch file.
+pub const PATCH_MAGIC: &str = "massive-csv-patch";
+/// Patch format version written by this crate.
+pub const PATCH_VERSION: &str = "1";
+
+/// Rows processed per parallel batch when applying a patch.
+const APPLY_CHUNK_ROWS: usize = 64 * 1024;
+
+/// A single change between two versions of a file.
+///
+/// Every op carries the old values it replaces, so a patch can be verified
+/// against its baseline before applying and reversed with [`Patch::reverse`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatchOp {
+    /// One cell changed in the row identified by `key`.
+    Update {
+        key: Vec<String>,
+        column: String,
+        old: String,
+        new: String,
+    },
+    /// A row of the old file that is missing from the new one.
+    Delete { fields: Vec<String> },
+    /// A row of the new file that is missing from the old one.
+    Insert { fields: Vec<String> },
+}
+
+/// A column-level, key-based set of changes that can be shipped and applied later.
+///
+/// Serialized as a `.mcpatch` file: a small CSV document whose first records
+/// describe the patch and whose remaining records are the ops.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Patch {
+    /// Columns whose values identify a row.
+    pub key_columns: Vec<String>,
+    /// Headers of the file the patch applies to.
+    pub headers: Vec<String>,
+    pub ops: Vec<PatchOp>,
+}
+
+/// Counts of what [`apply_patch`] changed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PatchSummary {
+    pub updated_rows: usize,
+    pub updated_cells: usize,
+    pub deleted: usize,
+    pub inserted: usize,
+}
+
+impl Patch {
+    /// Number of ops in the patch.
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    /// Whether the patch contains no changes.
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    /// Build the patch that undoes this one.
+    ///
+    /// Deleted rows come back as inserts, so they are appended at the end of
+    /// the file rather than restored to their original position.
+    pub fn reverse(&self) -> Patch {
+        let ops = self
+            .ops
+            .iter()
+            .rev()
+            .map(|op| match op {
+                PatchOp::Update {
+                    key,
+                    column,
+                    old,
+                    new,
+                } => PatchOp::Update {
+                    key: key.clone(),
+                    column: column.clone(),
+                    old: new.clone(),
+                    new: old.clone(),
+                },
+                PatchOp::Delete { fields } => PatchOp::Insert {
+                    fields: fields.clone(),
+                },
+                PatchOp::Insert { fields } => PatchOp::Delete {
+                    fields: fields.clone(),
+                },
+            })
+            .collect();
+
+        Patch {
+            key_columns: self.key_columns.clone(),
+            headers: self.headers.clone(),
+            ops,
+        }
+    }
+
+    /// Serialize the patch in `.mcpatch` format.
+    pub fn write_to<W: Write>(&self, writer: W) -> Result<()> {
+        let mut w = csv::WriterBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_writer(writer);
+
+        w.write_record([PATCH_MAGIC, PATCH_VERSION])?;
+        w.write_record(std::iter::once("key").chain(self.key_columns.iter().map(|s| s.as_str())))?;
+        w.write_record(std::iter::once("columns").chain(self.headers.iter().map(|s| s.as_str())))?;
+
+        for op in &self.ops {
+            match op {
+                PatchOp::Update {
+                    key,
+                    column,
+                    old,
+                    new,
+                } => {
+                    let record = ["update", column, old, new]
+                        .into_iter()
+                        .chain(key.iter().map(|s| s.as_str()));
+                    w.write_record(record)?;
+                }
+                PatchOp::Delete { fields } => {
+                    w.write_record(
+                        std::iter::once("delete").chain(fields.iter().map(|s| s.as_str())),
+                    )?;
+                }
+                PatchOp::Insert { fields } => {
+                    w.write_record(
+                        std::iter::once("insert").chain(fields.iter().map(|s| s.as_str())),
+                    )?;
+                }
+            }
+        }
+
+        w.flush()?;
+        Ok(())
+    }
+
+    /// Parse a patch in `.mcpatch` format.
+    pub fn read_from<R: Read>(reader: R) -> Result<Patch> {
+        let mut r = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(reader);
+        let mut records = r.records();
+
+        let mut next_record = |what: &str| -> Result<Vec<String>> {
+            match records.next() {
+                Some(record) => Ok(record?.iter().map(|f| f.to_string()).collect()),
+                None => Err(MassiveCsvError::InvalidPatch(format!(
+                    "missing {what} record"
+                ))),
+            }
+        };
+
+        let magic = next_record("format")?;
+        if magic.first().map(|s| s.as_str()) != Some(PATCH_MAGIC) {
+            return Err(MassiveCsvError::InvalidPatch(
+                "not a massive-csv patch file".to_string(),
+            ));
+        }
+        if magic.get(1).map(|s| s.as_str()) != Some(PATCH_VERSION) {
+            return Err(MassiveCsvError::InvalidPatch(format!(
+                "unsupported patch version {:?}",
+                magic.get(1)
+            )));
+        }
+
+        let key_columns = expect_tagged(next_record("key")?, "key")?;
+        let headers = expect_tagged(next_record("columns")?, "columns")?;
+        if key_columns.is_empty() {
+            return Err(MassiveCsvError::InvalidPatch("no key columns".to_string()));
+        }
+        for k in &key_columns {
+            if !headers.contains(k) {
+                return Err(MassiveCsvError::InvalidPatch(format!(
+                    "key column '{k}' is not in the column list"
+                )));
+            }
+        }
+
+        let mut ops = Vec::new();
+        for record in records {
+            let record = record?;
+            let mut fields = record.iter().map(|f| f.to_string());
+            let tag = fields.next().unwrap_or_default();
+            let rest: Vec<String> = fields.collect();
+            let line = record.position().map(|p| p.line()).unwrap_or(0);
+
+            let op = match tag.as_str() {
+                "update" => {
+                    if rest.len() != 3 + key_columns.len() {
+                        return Err(MassiveCsvError::InvalidPatch(format!(
+                            "line {line}: update needs column, old, new and {} key value(s)",
+                            key_columns.len()
+                        )));
+                    }
+                    let mut it = rest.into_iter();
+                    let column = it.next().unwrap_or_default();
+                    if !headers.contains(&column) {
+                        return Err(MassiveCsvError::InvalidPatch(format!(
+                            "line {line}: unknown column '{column}'"
+                        )));
+                    }
+                    PatchOp::Update {
+                        column,
+                        old: it.next().unwrap_or_default(),
+                        new: it.next().unwrap_or_default(),
+                        key: it.collect(),
+                    }
+                }
+                "delete" | "insert" => {
+                    if rest.len() != headers.len() {
+                        return Err(MassiveCsvError::InvalidPatch(format!(
+                            "line {line}: {tag} has {} fields, expected {}",
+                            rest.len(),
+                            headers.len()
+                        )));
+                    }
+                    if tag == "delete" {
+                        PatchOp::Delete { fields: rest }
+                    } else {
+                        PatchOp::Insert { fields: rest }
+                    }
+                }
+                other => {
+                    return Err(MassiveCsvError::InvalidPatch(format!(
+                        "line {line}: unknown op '{other}'"
+                    )))
+                }
+            };
+            ops.push(op);
+        }
+
+        Ok(Patch {
+            key_columns,
+            headers,
+            ops,
+        })
+    }
+
+    /// Write the patch to a file.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let file = File::create(path)?;
+        self.write_to(BufWriter::new(file))
+    }
+
+    /// Read a patch from a file.
+    pub fn load(path: &Path) -> Result<Patch> {
+        let file = File::open(path)?;
+        Self::read_from(BufReader::new(file))
+    }
+
+    fn key_indices(&self) -> Result<Vec<usize>> {
+        resolve_columns(&self.headers, &self.key_columns)
+    }
+}
+
+/// Compute the changes that turn `old` into `new`, matching rows by `key_columns`.
+///
+/// Both files must have the same headers and keys must be unique in each file.
+/// Ops are ordered as updates and inserts in `new` row order, followed by
+/// deletes in `old` row order.
+pub fn diff(old: &CsvReader, new: &CsvReader, key_columns: &[String]) -> Result<Patch> {
+    if old.headers() != new.headers() {
+        return Err(MassiveCsvError::HeaderMismatch(format!(
+            "old file has [{}], new file has [{}]",
+            old.headers().join(", "),
+            new.headers().join(", ")
+        )));
+    }
+    if key_columns.is_empty() {
+        return Err(MassiveCsvError::ColumnNotFound(
+            "no key columns given".to_string(),
+        ));
+    }
+
+    let headers = old.headers().to_vec();
+    let key_idx = resolve_columns(&headers, key_columns)?;
+
+    // Index old rows by key
+    let old_keys: Vec<Vec<String>> = (0..old.row_count())
+        .into_par_iter()
+        .map(|i| old.get_row(i).map(|fields| key_of(&fields, &key_idx)))
+        .collect::<Result<_>>()?;
+    let mut old_by_key: HashMap<&[String], usize> = HashMap::with_capacity(old_keys.len());
+    for (i, key) in old_keys.iter().enumerate() {
+        if old_by_key.insert(key.as_slice(), i).is_some() {
+            return Err(MassiveCsvError::DuplicateKey(format!(
+                "{} (old file, row {i})",
+                key.join(",")
+            )));
+        }
+    }
+
+    // Compare each new row against its old counterpart
+    let per_row: Vec<(Vec<String>, Option<usize>, Vec<PatchOp>)> = (0..new.row_count())
+        .into_par_iter()
+        .map(|i| {
+            let fields = new.get_row(i)?;
+            let key = key_of(&fields, &key_idx);
+            match old_by_key.get(key.as_slice()) {
+                None => Ok((key, None, vec![PatchOp::Insert { fields }])),
+                Some(&j) => {
+                    let old_fields = old.get_row(j)?;
+                    let ops = headers
+                        .iter()
+                        .enumerate()
+                        .filter_map(|(c, name)| {
+                            let before = old_fields.get(c).map(|s| s.as_str()).unwrap_or("");
+                            let after = fields.get(c).map(|s| s.as_str()).unwrap_or("");
+                            (before != after).then(|| PatchOp::Update {
+                                key: key.clone(),
+                                column: name.clone(),
+                                old: before.to_string(),
+                                new: after.to_string(),
+                            })
+                        })
+                        .collect();
+                    Ok((key, Some(j), ops))
+                }
+            }
+        })
+        .collect::<Result<_>>()?;
+
+    let mut seen_new: HashSet<&[String]> = HashSet::with_capacity(per_row.len());
+    let mut matched = vec![false; old.row_count()];
+    for (i, (key, old_row, _)) in per_row.iter().enumerate() {
+        if !seen_new.insert(key.as_slice()) {
+            return Err(MassiveCsvError::DuplicateKey(format!(
+                "{} (new file, row {i})",
+                key.join(",")
+            )));
+        }
+        if let Some(j) = old_row {
+            matched[*j] = true;
+        }
+    }
+
+    let mut ops: Vec<PatchOp> = per_row.into_iter().flat_map(|(_, _, ops)| ops).collect();
+    for (j, was_matched) in matched.iter().enumerate() {
+        if !was_matched {
+            ops.push(PatchOp::Delete {
+                fields: old.get_row(j)?,
+            });
+        }
+    }
+
+    Ok(Patch {
+        key_columns: key_columns.to_vec(),
+        headers,
+        ops,
+    })
+}
+
+/// What happens to one baseline row when a patch is applied.
+enum RowAction {
+    Keep,
+    Drop,
+    Replace(String),
+}
+
+/// Apply `patch` to the file behind `reader`, writing the result atomically to `output`.
+///
+/// `output` may be the reader's own path. Every update and delete is checked
+/// against the baseline's current values first; on any conflict nothing is written.
+pub fn apply_patch(reader: &CsvReader, patch: &Patch, output: &Path) -> Result<PatchSummary> {
+    if reader.headers() != patch.headers.as_slice() {
+        return Err(MassiveCsvError::HeaderMismatch(format!(
+            "patch expects [{}], file has [{}]",
+            patch.headers.join(", "),
+            reader.headers().join(", ")
+        )));
+    }
+
+    let key_idx = patch.key_indices()?;
+    let dialect = reader.dialect();
+
+    let mut updates: HashMap<Vec<String>, Vec<(usize, &str, &str)>> = HashMap::new();
+    let mut deletes: HashMap<Vec<String>, &[String]> = HashMap::new();
+    let mut inserts: Vec<&[String]> = Vec::new();
+    let mut insert_keys: HashSet<Vec<String>> = HashSet::new();
+
+    for op in &patch.ops {
+        match op {
+            PatchOp::Update {
+                key,
+                column,
+                old,
+                new,
+            } => {
+                let col = resolve_columns(&patch.headers, std::slice::from_ref(column))?[0];
+                updates
+                    .entry(key.clone())
+                    .or_default()
+                    .push((col, old.as_str(), new.as_str()));
+            }
+            PatchOp::Delete { fields } => {
+                deletes.insert(key_of(fields, &key_idx), fields.as_slice());
+            }
+            PatchOp::Insert { fields } => {
+                if !insert_keys.insert(key_of(fields, &key_idx)) {
+                    return Err(MassiveCsvError::DuplicateKey(format!(
+                        "{} (inserted twice by patch)",
+                        key_of(fields, &key_idx).join(",")
+                    )));
+                }
+                inserts.push(fields.as_slice());
+            }
+        }
+    }
+
+    let mut summary = PatchSummary::default();
+    let mut applied: HashSet<Vec<String>> = HashSet::new();
+
+    write_atomic(output, |writer| {
+        let mut lines = LineWriter::new(writer, reader.line_ending());
+        lines.write_line(&serialize_row(reader.headers(), dialect))?;
+
+        let row_count = reader.row_count();
+        let mut start = 0;
+        while start < row_count {
+            let end = (start + APPLY_CHUNK_ROWS).min(row_count);
+
+            let actions: Vec<(RowAction, Option<Vec<String>>)> = (start..end)
+                .into_par_iter()
+                .map(|i| {
+                    let mut fields = reader.get_row(i)?;
+                    let key = key_of(&fields, &key_idx);
+
+                    if insert_keys.contains(&key) {
+                        return Err(MassiveCsvError::PatchConflict(format!(
+                            "row {i}: key {} already exists, cannot insert",
+                            key.join(",")
+                        )));
+                    }
+                    if let Some(expected) = deletes.get(&key) {
+                        if fields.as_slice() != *expected {
+                            return Err(MassiveCsvError::PatchConflict(format!(
+                                "row {i}: key {} does not match the deleted row",
+                                key.join(",")
+                            )));
+                        }
+                        return Ok((RowAction::Drop, Some(key)));
+                    }
+                    if let Some(changes) = updates.get(&key) {
+                        for &(col, old, new) in changes {
+                            let current = fields.get(col).map(|s| s.as_str()).unwrap_or("");
+                            if current != old {
+                                return Err(MassiveCsvError::PatchConflict(format!(
+                                    "row {i}: column '{}' is \"{current}\", patch expects \"{old}\"",
+                                    patch.headers[col]
+                                )));
+                            }
+                            if col >= fields.len() {
+                                fields.resize(col + 1, String::new());
+                            }
+                            fields[col] = new.to_string();
+                        }
+                        let raw = reader.get_row_raw(i)?;
+                        let line = serialize_row_preserving(&fields, raw, dialect);
+                        return Ok((RowAction::Replace(line), Some(key)));
+                    }
+                    Ok((RowAction::Keep, None))
+                })
+                .collect::<Result<_>>()?;
+
+            for (offset, (action, key)) in actions.into_iter().enumerate() {
+                match action {
+                    RowAction::Keep => lines.write_line(reader.get_row_raw(start + offset)?)?,
+                    RowAction::Drop => summary.deleted += 1,
+                    RowAction::Replace(line) => {
+                        lines.write_line(&line)?;
+                        summary.updated_rows += 1;
+                    }
+                }
+                if let Some(key) = key {
+                    applied.insert(key);
+                }
+            }
+
+            start = end;
+        }
+
+        if let Some(missing) = updates
+            .keys()
+            .chain(deletes.keys())
+            .find(|k| !applied.contains(*k))
+        {
+            return Err(MassiveCsvError::PatchConflict(format!(
+                "key {} not found in file",
+                missing.join(",")
+            )));
+        }
+
+        for fields in &inserts {
+            lines.write_line(&serialize_row(fields, dialect))?;
+        }
+        summary.inserted = inserts.len();
+        summary.updated_cells = updates.values().map(|c| c.len()).sum();
+
+        lines.finish(reader.has_trailing_newline())
+    })?;
+
+    Ok(summary)
+}
+
+pub(crate) fn key_of(fields: &[String], key_idx: &[usize]) -> Vec<String> {
+    key_idx
+        .iter()
+        .map(|&i| fields.get(i).cloned().unwrap_or_default())
+        .collect()
+}
+
+pub(crate) fn resolve_columns(headers: &[String], names: &[String]) -> Result<Vec<usize>> {
+    names
+        .iter()
+        .map(|name| {
+            column_position(headers, name)
+                .ok_or_else(|| MassiveCsvError::ColumnNotFound(name.clone()))
+        })
+        .collect()
+}
+
+fn expect_tagged(record: Vec<String>, tag: &str) -> Result<Vec<String>> {
+    let mut it = record.into_iter();
+    if it.next().as_deref() != Some(tag) {
+        return Err(MassiveCsvError::InvalidPatch(format!(
+            "expected '{tag}' record"
+        )));
+    }
+    Ok(it.collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write as IoWrite;
+
+    fn make_csv(content: &str) -> tempfile::NamedTempFile {
+        let mut f = tempfile::NamedTempFile::new().unwrap();
+        f.write_all(content.as_bytes()).unwrap();
+        f.flush().unwrap();
+        f
+    }
+
+    fn key(s: &str) -> Vec<String> {
+        vec![s.to_string()]
+    }
+
+    #[test]
+    fn diff_detects_updates_inserts_deletes() {
+        let old = make_csv("id,name,status\n1,Alice,active\n2,Bob,active\n3,Carol,active\n");
+        let new = make_csv("id,name,status\n1,Alice,done\n3,Carol,active\n4,Dan,new\n");
+        let old = CsvReader::open(old.path()).unwrap();
+        let new = CsvReader::open(new.path()).unwrap();
+
+        let patch = diff(&old, &new, &key("id")).unwrap();
+        assert_eq!(
+            patch.ops,
+            vec![
+                PatchOp::Update {
+                    key: key("1"),
+                    column: "status".to_string(),
+                    old: "active".to_string(),
+                    new: "done".to_string(),
+                },
+                PatchOp::Insert {
+                    fields: vec!["4".into(), "Dan".into(), "new".into()],
+                },
+                PatchOp::Delete {
+                    fields: vec!["2".into(), "Bob".into(), "active".into()],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn patch_round_trip() {
+        let patch = Patch {
+            key_columns: key("id"),
+            headers: vec!["id".into(), "note".into()],
+            ops: vec![
+                PatchOp::Update {
+                    key: key("1"),
+                    column: "note".into(),
+                    old: "a, b".into(),
+                    new: "say \"hi\"".into(),
+                },
+                PatchOp::Insert {
+                    fields: vec!["2".into(), "".into()],
+                },
+            ],
+        };
+
+        let mut buf = Vec::new();
+        patch.write_to(&mut buf).unwrap();
+        let parsed = Patch::read_from(buf.as_slice()).unwrap();
+        assert_eq!(parsed, patch);
+    }
+
+    #[test]
+    fn apply_reproduces_new_file() {
+        let old = make_csv("id,v\n1,a\n2,b\n3,c\n");
+        let new = make_csv("id,v\n1,a\n3,C\n5,e\n");
+        let patch = {
+            let old = CsvReader::open(old.path()).unwrap();
+            let new = CsvReader::open(new.path()).unwrap();
+            diff(&old, &new, &key("id")).unwrap()
+        };
+
+        let reader = CsvReader::open(old.path()).unwrap();
+        let summary = apply_patch(&reader, &patch, old.path()).unwrap();
+        assert_eq!(summary.updated_cells, 1);
+        assert_eq!(summary.deleted, 1);
+        assert_eq!(summary.inserted, 1);
+
+        let patched = std::fs::read_to_string(old.path()).unwrap();
+        assert_eq!(patched, "id,v\n1,a\n3,C\n5,e\n");
+    }
+
+    #[test]
+    fn apply_rejects_conflicting_baseline() {
+        let f = make_csv("id,v\n1,changed\n");
+        let reader = CsvReader::open(f.path()).unwrap();
+        let patch = Patch {
+            key_columns: key("id"),
+            headers: vec!["id".into(), "v".into()],
+            ops: vec![PatchOp::Update {
+                key: key("1"),
+                column: "v".into(),
+                old: "a".into(),
+                new: "b".into(),
+            }],
+        };
+
+        let result = apply_patch(&reader, &patch, f.path());
+        assert!(matches!(result, Err(MassiveCsvError::PatchConflict(_))));
+        assert_eq!(
+            std::fs::read_to_string(f.path()).unwrap(),
+            "id,v\n1,changed\n"
+        );
+    }
+
+    #[test]
+    fn duplicate_keys_are_rejected() {
+        let old = make_csv("id,v\n1,a\n1,b\n");
+        let new = make_csv("id,v\n1,a\n");
+        let old = CsvReader::open(old.path()).unwrap();
+        let new = CsvReader::open(new.path()).unwrap();
+        assert!(matches!(
+            diff(&old, &new, &key("id")),
+            Err(MassiveCsvError::DuplicateKey(_))
+        ));
+    }
+}
@@ -1,18 +1,187 @@
-use std::collections::HashMap;
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fs;
 use std::io::{BufWriter, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use tempfile::NamedTempFile;
 
+use crate::background::Progress;
+use crate::compression::{Compression, Compressor};
+use crate::diff::{key_of, resolve_columns, Patch, PatchOp};
+use crate::edits::CellEdit;
+use crate::encoding::Encoding;
 use crate::error::{MassiveCsvError, Result};
-use crate::parser::serialize_row;
-use crate::reader::CsvReader;
+use crate::journal::{read_journal, remove_if_exists, Journal, JournalEntry};
+use crate::order::RowOrder;
+use crate::parser::{
+    serialize_row_preserving, serialize_row_with, Dialect, LineEnding, QuoteStyle,
+};
+use crate::reader::{CsvReader, OpenBuilder, OpenOptions};
+use crate::record::{HeaderMap, Record};
+use crate::reshape::{BoundChange, ColumnChange};
+#[cfg(feature = "schema")]
+use crate::schema::{BoundSchema, Schema, SchemaReport};
+#[cfg(feature = "schema")]
+use crate::validate::ValidateOptions;
+#[cfg(feature = "versions")]
+use crate::versions::VersionStore;
+
+/// Rows written between progress reports while saving.
+const SAVE_PROGRESS_ROWS: usize = 64 * 1024;
+
+/// Changes [`CsvEditor::undo`] can step back through; older ones are forgotten.
+const UNDO_LIMIT: usize = 1000;
+
+/// Options controlling how [`CsvEditor::save_with_options`] writes the file.
+#[derive(Debug, Clone)]
+pub struct SaveOptions {
+    /// Also write the saved edits as a patch file, for consumers that ingest deltas.
+    pub patch: Option<PatchOutput>,
+    /// Copy the original file aside before replacing it.
+    pub backup: bool,
+    /// Appended to the file name to form the backup name (default: `.bak`).
+    /// `{timestamp}` is replaced with the current Unix time in seconds.
+    pub backup_suffix: String,
+    /// Store a compressed copy of the original in the file's [`VersionStore`].
+    pub snapshot: bool,
+    /// Line terminator to write (default: the one detected in the source file).
+    pub line_ending: Option<LineEnding>,
+    /// Whether to end the file with a terminator (default: as in the source file).
+    pub trailing_newline: Option<bool>,
+    /// Field delimiter to write (default: the one detected in the source file).
+    pub delimiter: Option<u8>,
+    /// Re-quote every field in this style (default: keep the source's quoting).
+    pub quote_style: Option<QuoteStyle>,
+    /// Text encoding to write (default: the source file's). UTF-16 output
+    /// starts with a byte order mark.
+    pub encoding: Option<Encoding>,
+    /// Compression to write (default: the source file's). `Some(Compression::None)`
+    /// writes plain text.
+    pub compression: Option<Compression>,
+    /// How hard to push the new file to stable storage before returning.
+    pub durability: Durability,
+    /// Told how many rows have been written as the file is saved.
+    pub progress: Progress,
+}
+
+impl Default for SaveOptions {
+    fn default() -> Self {
+        Self {
+            patch: None,
+            backup: false,
+            backup_suffix: ".bak".to_string(),
+            snapshot: false,
+            line_ending: None,
+            trailing_newline: None,
+            delimiter: None,
+            quote_style: None,
+            encoding: None,
+            compression: None,
+            durability: Durability::default(),
+            progress: Progress::default(),
+        }
+    }
+}
+
+/// Sync policy for atomic writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Durability {
+    /// Don't sync file data; the directory entry is synced best-effort. Fastest.
+    #[default]
+    None,
+    /// `sync_data` the new file before it replaces the old one.
+    DataSync,
+    /// `sync_all` the new file before it replaces the old one, and fail if the
+    /// directory entry can't be synced afterwards.
+    Full,
+}
+
+impl SaveOptions {
+    /// Path the backup of `path` is written to.
+    pub fn backup_path(&self, path: &Path) -> PathBuf {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let suffix = self
+            .backup_suffix
+            .replace("{timestamp}", &timestamp.to_string());
+        let mut name = path.as_os_str().to_os_string();
+        name.push(suffix);
+        PathBuf::from(name)
+    }
+}
+
+/// Where and how to emit the change patch produced by a save.
+#[derive(Debug, Clone)]
+pub struct PatchOutput {
+    /// Path of the `.mcpatch` file to write.
+    pub path: PathBuf,
+    /// Key column(s) identifying rows in the patch.
+    pub key_columns: Vec<String>,
+}
+
+/// How a row differs from the file, from [`CsvEditor::row_diff`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RowDiff {
+    /// The row as read from the file, with pending column changes applied;
+    /// `None` for an inserted row.
+    pub original: Option<Vec<String>>,
+    /// The row with pending edits.
+    pub current: Vec<String>,
+    /// Columns whose value was edited, in order; every column of an
+    /// inserted row.
+    pub changed: Vec<usize>,
+}
+
+/// Memory a [`CsvEditor`] holds, from [`CsvEditor::memory_usage`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryUsage {
+    /// Bytes of the file mapped into memory. The operating system pages
+    /// these in as rows are read, so only part of them may be resident.
+    pub mapped: u64,
+    /// Bytes of the row index.
+    pub index: usize,
+    /// Bytes of pending edits and undo history, roughly: the length of
+    /// their values.
+    pub edits: usize,
+}
+
+/// What one change to a [`CsvEditor`] replaced, so it can be put back.
+#[derive(Debug, Default)]
+struct Step {
+    /// Edits of the file rows the change touched, as they were (`None` for
+    /// a row that wasn't edited).
+    rows: Vec<(usize, Option<Vec<String>>)>,
+    /// The row order, if the change replaced it.
+    order: Option<Option<RowOrder>>,
+    /// The column changes, if the change replaced them.
+    columns: Option<Vec<BoundChange>>,
+}
 
 /// A CSV editor that tracks changes in memory and saves atomically.
 pub struct CsvEditor {
     reader: CsvReader,
-    /// Pending edits: row_num -> edited fields
+    /// Pending edits: file row -> edited fields
     edits: HashMap<usize, Vec<String>>,
+    /// Row order after pending moves; `None` while rows are in file order.
+    order: Option<RowOrder>,
+    /// Pending column changes, in order. Edited rows above already have
+    /// them applied; rows read from the file get them on the way out.
+    columns: Vec<BoundChange>,
+    /// Write-ahead log of pending edits, if enabled.
+    journal: Option<Journal>,
+    /// Changes since the last save, latest last, and changes undone since
+    /// the last change.
+    undo: Vec<Step>,
+    redo: Vec<Step>,
+    /// Rules edits must follow, if attached.
+    #[cfg(feature = "schema")]
+    schema: Option<(Schema, BoundSchema)>,
 }
 
 impl CsvEditor {
@@ -21,131 +190,1361 @@ impl CsvEditor {
         Self {
             reader,
             edits: HashMap::new(),
+            order: None,
+            columns: Vec::new(),
+            journal: None,
+            undo: Vec::new(),
+            redo: Vec::new(),
+            #[cfg(feature = "schema")]
+            schema: None,
         }
     }
 
     /// Open a file for editing.
-    pub fn open(path: &std::path::Path) -> Result<Self> {
+    pub fn open(path: &Path) -> Result<Self> {
         let reader = CsvReader::open(path)?;
         Ok(Self::new(reader))
     }
 
+    /// Open a file for editing with dialect overrides.
+    pub fn open_with(path: &Path, options: &OpenOptions) -> Result<Self> {
+        Ok(Self::new(CsvReader::open_with(path, options)?))
+    }
+
+    /// Set open options one at a time, then [`open`](OpenBuilder::open).
+    pub fn builder(path: impl Into<PathBuf>) -> OpenBuilder<Self> {
+        OpenBuilder::new(path)
+    }
+
     /// Access the underlying reader.
     pub fn reader(&self) -> &CsvReader {
         &self.reader
     }
 
-    /// Number of pending edits.
+    /// Number of rows, after pending inserts and deletes.
+    pub fn row_count(&self) -> usize {
+        match &self.order {
+            Some(order) => order.len(),
+            None => self.reader.row_count(),
+        }
+    }
+
+    /// Number of pending edits, counting each inserted row as one.
     pub fn edit_count(&self) -> usize {
         self.edits.len()
     }
 
+    /// Memory held for the file, its index and pending edits.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let row_bytes = |fields: &Vec<String>| -> usize {
+            fields
+                .iter()
+                .map(|f| f.len() + std::mem::size_of::<String>())
+                .sum()
+        };
+        let history: usize = self
+            .undo
+            .iter()
+            .chain(&self.redo)
+            .flat_map(|step| &step.rows)
+            .filter_map(|(_, fields)| fields.as_ref())
+            .map(row_bytes)
+            .sum();
+        MemoryUsage {
+            mapped: self.reader.data_len(),
+            index: self.reader.index_bytes(),
+            edits: self.edits.values().map(row_bytes).sum::<usize>() + history,
+        }
+    }
+
     /// Check if there are any unsaved changes.
     pub fn has_changes(&self) -> bool {
-        !self.edits.is_empty()
+        !self.edits.is_empty() || !self.columns.is_empty() || self.order.is_some()
+    }
+
+    /// The headers with pending column changes applied.
+    pub fn headers(&self) -> &[String] {
+        match self.columns.last() {
+            Some(change) => &change.headers,
+            None => self.reader.headers(),
+        }
     }
 
     /// Get the current state of a row (edited version if modified, otherwise from file).
+    ///
+    /// Rows are numbered in their current order, after pending
+    /// [`move_rows`](Self::move_rows), [`insert_rows`](Self::insert_rows)
+    /// and [`delete_rows`](Self::delete_rows); so are rows given to every
+    /// other method taking one.
     pub fn get_row(&self, row: usize) -> Result<Vec<String>> {
-        if let Some(edited) = self.edits.get(&row) {
+        self.current_row(self.file_row(row)?)
+    }
+
+    /// The file row now at position `row`. Inserted rows are numbered from
+    /// the file's row count up.
+    pub fn file_row(&self, row: usize) -> Result<usize> {
+        let count = self.row_count();
+        if row >= count {
+            return Err(MassiveCsvError::RowOutOfRange(row, count));
+        }
+        Ok(match &self.order {
+            Some(order) => order.file_row(row).expect("order covers every row"),
+            None => row,
+        })
+    }
+
+    /// File row `file_row` with pending edits and column changes applied.
+    fn current_row(&self, file_row: usize) -> Result<Vec<String>> {
+        if let Some(edited) = self.edits.get(&file_row) {
             Ok(edited.clone())
         } else {
-            self.reader.get_row(row)
+            let mut fields = self.reader.get_row(file_row)?;
+            for change in &self.columns {
+                change.apply(&mut fields);
+            }
+            Ok(fields)
+        }
+    }
+
+    /// Move the rows at positions `rows` so the first of them ends up at
+    /// position `dest`, e.g. after a drag and drop. Edits move with their
+    /// rows, and the new order is written on save.
+    pub fn move_rows(&mut self, rows: Range<usize>, dest: usize) -> Result<()> {
+        let count = self.row_count();
+        if rows.start > rows.end || rows.end > count {
+            return Err(MassiveCsvError::RowOutOfRange(rows.end, count));
+        }
+        if dest + rows.len() > count {
+            return Err(MassiveCsvError::RowOutOfRange(dest + rows.len() - 1, count));
+        }
+        if rows.is_empty() || rows.start == dest {
+            return Ok(());
+        }
+        self.remember(Step {
+            order: Some(self.order.clone()),
+            ..Default::default()
+        });
+        let mut order = self.order.take().unwrap_or_else(|| RowOrder::new(count));
+        order.move_rows(rows, dest);
+        self.set_order(order)
+    }
+
+    /// Insert `rows` at position `at`; `at` may be
+    /// [`row_count`](Self::row_count), to append. Each row is cut or padded
+    /// to the width of the headers, and the rows after them move down.
+    pub fn insert_rows(&mut self, at: usize, mut rows: Vec<Vec<String>>) -> Result<()> {
+        let count = self.row_count();
+        if at > count {
+            return Err(MassiveCsvError::RowOutOfRange(at, count));
+        }
+        if rows.is_empty() {
+            return Ok(());
+        }
+        let width = self.headers().len();
+        for (i, fields) in rows.iter_mut().enumerate() {
+            fields.resize(width, String::new());
+            self.check_row(at + i, fields)?;
+        }
+        // Inserted rows are numbered past every row in the order
+        let first = self
+            .order
+            .iter()
+            .flat_map(|order| order.runs())
+            .map(|run| run.end)
+            .max()
+            .unwrap_or(0)
+            .max(self.reader.row_count());
+        let ids = first..first + rows.len();
+        self.remember(Step {
+            rows: ids.clone().map(|id| (id, None)).collect(),
+            order: Some(self.order.clone()),
+            ..Default::default()
+        });
+        for (id, fields) in ids.clone().zip(&rows) {
+            self.log(JournalEntry::SetRow(id, fields.clone()))?;
+        }
+        self.edits.extend(ids.clone().zip(rows));
+        let mut order = self.order.take().unwrap_or_else(|| RowOrder::new(count));
+        order.insert(at, ids);
+        self.set_order(order)
+    }
+
+    /// Delete the rows at positions `rows`; the rows after them move up.
+    pub fn delete_rows(&mut self, rows: Range<usize>) -> Result<()> {
+        let count = self.row_count();
+        if rows.start > rows.end || rows.end > count {
+            return Err(MassiveCsvError::RowOutOfRange(rows.end, count));
+        }
+        if rows.is_empty() {
+            return Ok(());
+        }
+        let mut step = Step {
+            order: Some(self.order.clone()),
+            ..Default::default()
+        };
+        let mut order = self.order.take().unwrap_or_else(|| RowOrder::new(count));
+        for run in order.delete(rows) {
+            let deleted: Vec<usize> = self
+                .edits
+                .keys()
+                .filter(|row| run.contains(row))
+                .copied()
+                .collect();
+            for row in deleted {
+                step.rows.push((row, self.edits.remove(&row)));
+            }
+        }
+        self.remember(step);
+        self.set_order(order)
+    }
+
+    /// Delete the rows at `positions`, given in any order, e.g. the rows a
+    /// filter matched.
+    pub fn delete_rows_at(&mut self, positions: &[usize]) -> Result<()> {
+        let count = self.row_count();
+        let mut positions = positions.to_vec();
+        positions.sort_unstable();
+        positions.dedup();
+        match positions.last() {
+            None => return Ok(()),
+            Some(&last) if last >= count => {
+                return Err(MassiveCsvError::RowOutOfRange(last, count));
+            }
+            Some(_) => {}
+        }
+        let mut step = Step {
+            order: Some(self.order.clone()),
+            ..Default::default()
+        };
+        let mut order = self.order.take().unwrap_or_else(|| RowOrder::new(count));
+        for row in order.delete_positions(&positions) {
+            if let Some(fields) = self.edits.remove(&row) {
+                step.rows.push((row, Some(fields)));
+            }
+        }
+        self.remember(step);
+        self.set_order(order)
+    }
+
+    /// Whether rows have been moved, inserted or deleted since the last save.
+    pub fn has_moved_rows(&self) -> bool {
+        self.order.is_some()
+    }
+
+    /// Replace the row order, dropping it if it is the file's own, and
+    /// journal it.
+    fn set_order(&mut self, order: RowOrder) -> Result<()> {
+        let entry = JournalEntry::Order(order.runs().to_vec());
+        self.order = (!order.is_identity(self.reader.row_count())).then_some(order);
+        self.log(entry)
+    }
+
+    /// Get a row as a [`Record`] (the edited version if modified).
+    pub fn get_record(&self, row: usize) -> Result<Record> {
+        let fields = self.get_row(row)?;
+        if self.columns.is_empty() {
+            return Ok(self.reader.record(row, fields));
         }
+        let headers = Arc::new(HeaderMap::new(self.headers().to_vec()));
+        Ok(Record::new(row, fields, headers))
+    }
+
+    /// Split `column` into new columns `names`, in its place, e.g. "city, ST"
+    /// at `,` into `city` and `state`. Parts are trimmed; the last new
+    /// column takes the rest of the value. Applied to every row on save.
+    pub fn split_column<S: AsRef<str>>(
+        &mut self,
+        column: &str,
+        delimiter: &str,
+        names: &[S],
+    ) -> Result<()> {
+        self.change_columns(ColumnChange::Split {
+            column: column.to_string(),
+            delimiter: delimiter.to_string(),
+            names: names.iter().map(|n| n.as_ref().to_string()).collect(),
+        })
+    }
+
+    /// Replace `columns` with one column `name`, in place of the first of
+    /// them, holding their non-empty values joined by `separator`. Applied
+    /// to every row on save.
+    pub fn merge_columns<S: AsRef<str>>(
+        &mut self,
+        columns: &[S],
+        separator: &str,
+        name: &str,
+    ) -> Result<()> {
+        self.change_columns(ColumnChange::Merge {
+            columns: columns.iter().map(|c| c.as_ref().to_string()).collect(),
+            separator: separator.to_string(),
+            name: name.to_string(),
+        })
+    }
+
+    /// Append a column `name` holding `default` in every row. Applied to
+    /// every row on save.
+    pub fn add_column(&mut self, name: &str, default: &str) -> Result<()> {
+        self.change_columns(ColumnChange::Add {
+            name: name.to_string(),
+            default: default.to_string(),
+        })
+    }
+
+    /// Remove `column`. Applied to every row on save.
+    pub fn drop_column(&mut self, column: &str) -> Result<()> {
+        self.change_columns(ColumnChange::Drop {
+            column: column.to_string(),
+        })
+    }
+
+    /// Rename the column `from` to `to`.
+    pub fn rename_column(&mut self, from: &str, to: &str) -> Result<()> {
+        self.change_columns(ColumnChange::Rename {
+            from: from.to_string(),
+            to: to.to_string(),
+        })
+    }
+
+    /// Pending column changes, in the order they were made.
+    pub fn column_changes(&self) -> impl Iterator<Item = &ColumnChange> {
+        self.columns.iter().map(|c| &c.change)
+    }
+
+    fn change_columns(&mut self, change: ColumnChange) -> Result<()> {
+        if self.reader.fixed_width().is_some() {
+            return Err(MassiveCsvError::Parse(
+                "can't change the columns of a fixed-width file".to_string(),
+            ));
+        }
+        let bound = change.bind(self.headers())?;
+        #[cfg(feature = "schema")]
+        let schema = match &self.schema {
+            Some((schema, _)) => Some((schema.clone(), schema.bind(&bound.headers)?)),
+            None => None,
+        };
+        self.log(JournalEntry::Columns(bound.change.clone()))?;
+        self.remember(Step {
+            rows: self
+                .edits
+                .iter()
+                .map(|(&row, fields)| (row, Some(fields.clone())))
+                .collect(),
+            order: None,
+            columns: Some(self.columns.clone()),
+        });
+        for fields in self.edits.values_mut() {
+            bound.apply(fields);
+        }
+        self.columns.push(bound);
+        #[cfg(feature = "schema")]
+        {
+            self.schema = schema;
+        }
+        Ok(())
+    }
+
+    /// Headers attached schemas are bound to.
+    #[cfg(feature = "schema")]
+    fn schema_headers(&self) -> &[String] {
+        match self.columns.last() {
+            Some(change) => &change.headers,
+            None => self.reader.canonical_headers(),
+        }
+    }
+
+    /// Re-bind the attached schema after the headers changed.
+    #[cfg(feature = "schema")]
+    fn rebind_schema(&mut self) -> Result<()> {
+        if let Some((schema, _)) = self.schema.take() {
+            let bound = schema.bind(self.schema_headers())?;
+            self.schema = Some((schema, bound));
+        }
+        Ok(())
     }
 
     /// Replace an entire row with new fields.
     pub fn set_row(&mut self, row: usize, fields: Vec<String>) -> Result<()> {
-        let count = self.reader.row_count();
-        if row >= count {
-            return Err(MassiveCsvError::RowOutOfRange(row, count));
-        }
-        self.edits.insert(row, fields);
+        let step = self.write_row(row, fields)?;
+        self.remember(step);
         Ok(())
     }
 
+    /// [`set_row`](Self::set_row), returning what to undo it with.
+    fn write_row(&mut self, row: usize, fields: Vec<String>) -> Result<Step> {
+        let file_row = self.file_row(row)?;
+        self.check_row(row, &fields)?;
+        self.log(JournalEntry::SetRow(file_row, fields.clone()))?;
+        let previous = self.edits.insert(file_row, fields);
+        Ok(Step {
+            rows: vec![(file_row, previous)],
+            ..Default::default()
+        })
+    }
+
     /// Edit a single cell (row, column_index).
     pub fn set_cell(&mut self, row: usize, col: usize, value: String) -> Result<()> {
-        let mut fields = self.get_row(row)?;
+        self.set_cell_at(self.file_row(row)?, row, col, value)
+    }
+
+    /// Apply a batch of cell edits, e.g. from
+    /// [`read_cell_edits`](crate::edits::read_cell_edits). Every row and
+    /// column is looked up and every value checked before anything changes,
+    /// so one bad entry leaves the editor as it was. Later edits to a cell
+    /// win, and the batch is undone as one change. Returns the number of
+    /// rows that changed.
+    pub fn apply_cell_edits(&mut self, edits: &[CellEdit]) -> Result<usize> {
+        // row -> (current fields, edited fields)
+        let mut rows: BTreeMap<usize, (Vec<String>, Vec<String>)> = BTreeMap::new();
+        for edit in edits {
+            let col = edit.column_index(self.headers())?;
+            let (_, fields) = match rows.entry(edit.row) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    let fields = self.get_row(edit.row)?;
+                    entry.insert((fields.clone(), fields))
+                }
+            };
+            if col >= fields.len() {
+                return Err(MassiveCsvError::ColumnNotFound(format!("index {col}")));
+            }
+            self.check_cell(edit.row, col, &edit.value)?;
+            fields[col].clone_from(&edit.value);
+        }
+        let mut step = Step::default();
+        let written = rows
+            .into_iter()
+            .filter(|(_, (current, fields))| fields != current)
+            .try_for_each(|(row, (_, fields))| {
+                step.rows.extend(self.write_row(row, fields)?.rows);
+                Ok(())
+            });
+        let changed = step.rows.len();
+        self.remember(step);
+        written.map(|()| changed)
+    }
+
+    /// Edit a cell of file row `file_row`, shown at position `row`.
+    fn set_cell_at(
+        &mut self,
+        file_row: usize,
+        row: usize,
+        col: usize,
+        value: String,
+    ) -> Result<()> {
+        let mut fields = self.current_row(file_row)?;
 
         if col >= fields.len() {
             return Err(MassiveCsvError::ColumnNotFound(format!("index {col}")));
         }
+        self.check_cell(row, col, &value)?;
 
         fields[col] = value;
-        self.edits.insert(row, fields);
+        self.log(JournalEntry::SetRow(file_row, fields.clone()))?;
+        let previous = self.edits.insert(file_row, fields);
+        self.remember(Step {
+            rows: vec![(file_row, previous)],
+            ..Default::default()
+        });
+        Ok(())
+    }
+
+    /// Attach a schema that [`set_cell`](Self::set_cell) and
+    /// [`set_row`](Self::set_row) check edits against, or detach it with
+    /// `None`. Edits already pending are not checked; see
+    /// [`validate_against_schema`](Self::validate_against_schema).
+    #[cfg(feature = "schema")]
+    pub fn set_schema(&mut self, schema: Option<Schema>) -> Result<()> {
+        self.schema = match schema {
+            Some(schema) => {
+                let bound = schema.bind(self.schema_headers())?;
+                Some((schema, bound))
+            }
+            None => None,
+        };
+        Ok(())
+    }
+
+    /// The attached schema.
+    #[cfg(feature = "schema")]
+    pub fn schema(&self) -> Option<&Schema> {
+        self.schema.as_ref().map(|(schema, _)| schema)
+    }
+
+    #[cfg(feature = "schema")]
+    fn bound_schema(&self) -> Option<&BoundSchema> {
+        self.schema.as_ref().map(|(_, bound)| bound)
+    }
+
+    /// Check a new value for a cell against the attached schema.
+    #[cfg(feature = "schema")]
+    fn check_cell(&self, row: usize, col: usize, value: &str) -> Result<()> {
+        match self
+            .bound_schema()
+            .and_then(|s| s.check_cell(row, col, value))
+        {
+            Some(violation) => Err(MassiveCsvError::SchemaViolation(violation.to_string())),
+            None => Ok(()),
+        }
+    }
+
+    #[cfg(not(feature = "schema"))]
+    fn check_cell(&self, _: usize, _: usize, _: &str) -> Result<()> {
+        Ok(())
+    }
+
+    /// Check a new row against the attached schema.
+    #[cfg(feature = "schema")]
+    fn check_row(&self, row: usize, fields: &[String]) -> Result<()> {
+        match self.bound_schema().and_then(|s| s.check_row(row, fields)) {
+            Some(violation) => Err(MassiveCsvError::SchemaViolation(violation.to_string())),
+            None => Ok(()),
+        }
+    }
+
+    #[cfg(not(feature = "schema"))]
+    fn check_row(&self, _: usize, _: &[String]) -> Result<()> {
         Ok(())
     }
 
-    /// Revert a row to its original state.
+    /// Check every row, with pending edits applied, against the attached
+    /// schema.
+    #[cfg(feature = "schema")]
+    pub fn validate_against_schema(&self, options: &ValidateOptions) -> Result<SchemaReport> {
+        let bound = self
+            .bound_schema()
+            .ok_or_else(|| MassiveCsvError::InvalidSchema("no schema is attached".to_string()))?;
+        bound.scan(self.row_count(), |row| self.get_row(row), options)
+    }
+
+    /// The current state of the row at position `row` of a view with rows
+    /// `view` (see [`CsvView::rows`](crate::CsvView::rows)). View rows are
+    /// file rows, wherever pending moves have put them.
+    pub fn get_row_in_view(&self, view: &[usize], row: usize) -> Result<Vec<String>> {
+        self.current_row(crate::view::resolve(view, row)?)
+    }
+
+    /// Edit a cell of the row at position `row` of a view with rows `view`.
+    /// Returns the file row that was edited.
+    pub fn set_cell_in_view(
+        &mut self,
+        view: &[usize],
+        row: usize,
+        col: usize,
+        value: String,
+    ) -> Result<usize> {
+        let file_row = crate::view::resolve(view, row)?;
+        self.set_cell_at(file_row, file_row, col, value)?;
+        Ok(file_row)
+    }
+
+    /// Revert a row to its original state, keeping column changes and its
+    /// position. Reverting an inserted row deletes it.
+    ///
+    /// Journaling a revert is best-effort: if it cannot be written, a later
+    /// recovery may restore the reverted edit.
     pub fn revert_row(&mut self, row: usize) {
-        self.edits.remove(&row);
+        let Ok(file_row) = self.file_row(row) else {
+            return;
+        };
+        if file_row >= self.reader.row_count() {
+            let _ = self.delete_rows(row..row + 1);
+            return;
+        }
+        let _ = self.log(JournalEntry::RevertRow(file_row));
+        if let Some(fields) = self.edits.remove(&file_row) {
+            self.remember(Step {
+                rows: vec![(file_row, Some(fields))],
+                ..Default::default()
+            });
+        }
+    }
+
+    /// Revert all pending edits, moves, inserts, deletes and column changes.
+    ///
+    /// Journaling is best-effort, as for [`CsvEditor::revert_row`].
+    pub fn revert_all(&mut self) {
+        let _ = self.log(JournalEntry::RevertAll);
+        if self.has_changes() {
+            let step = Step {
+                rows: self.edits.drain().map(|(row, f)| (row, Some(f))).collect(),
+                order: Some(self.order.take()),
+                columns: Some(self.columns.clone()),
+            };
+            self.remember(step);
+        }
+        if !self.columns.is_empty() {
+            self.columns.clear();
+            // The schema was bound to the file's headers before
+            #[cfg(feature = "schema")]
+            let _ = self.rebind_schema();
+        }
+    }
+
+    /// Undo the latest change not yet undone: an edit, a batch of cell
+    /// edits, a move, insert or delete of rows, a column change or a
+    /// revert. Returns false if there is nothing to undo. Saving or
+    /// reloading the file starts the history afresh.
+    pub fn undo(&mut self) -> Result<bool> {
+        let Some(step) = self.undo.pop() else {
+            return Ok(false);
+        };
+        let inverse = self.restore(step)?;
+        self.redo.push(inverse);
+        Ok(true)
+    }
+
+    /// Redo the latest change undone, unless a change was made since.
+    /// Returns false if there is nothing to redo.
+    pub fn redo(&mut self) -> Result<bool> {
+        let Some(step) = self.redo.pop() else {
+            return Ok(false);
+        };
+        let inverse = self.restore(step)?;
+        self.undo.push(inverse);
+        Ok(true)
+    }
+
+    /// Whether [`undo`](Self::undo) has a change to undo.
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    /// Whether [`redo`](Self::redo) has a change to redo.
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Positions of the rows with pending edits, inserted rows included, in
+    /// order.
+    pub fn edited_rows(&self) -> Vec<usize> {
+        let edited: BTreeSet<usize> = self.edits.keys().copied().collect();
+        let Some(order) = &self.order else {
+            return edited.into_iter().collect();
+        };
+        let mut rows = Vec::with_capacity(edited.len());
+        let mut position = 0;
+        for run in order.runs() {
+            rows.extend(
+                edited
+                    .range(run.clone())
+                    .map(|row| position + row - run.start),
+            );
+            position += run.len();
+        }
+        rows
+    }
+
+    /// The row at position `row` before and after its pending edits.
+    pub fn row_diff(&self, row: usize) -> Result<RowDiff> {
+        let file_row = self.file_row(row)?;
+        let current = self.current_row(file_row)?;
+        if !self.edits.contains_key(&file_row) {
+            return Ok(RowDiff {
+                original: Some(current.clone()),
+                current,
+                changed: Vec::new(),
+            });
+        }
+        if file_row >= self.reader.row_count() {
+            return Ok(RowDiff {
+                original: None,
+                changed: (0..current.len()).collect(),
+                current,
+            });
+        }
+        let mut original = self.reader.get_row(file_row)?;
+        for change in &self.columns {
+            change.apply(&mut original);
+        }
+        let changed = (0..current.len().max(original.len()))
+            .filter(|&col| original.get(col) != current.get(col))
+            .collect();
+        Ok(RowDiff {
+            original: Some(original),
+            current,
+            changed,
+        })
+    }
+
+    /// Whether the cell at `row`, `col` has a pending edit that changed its
+    /// value.
+    pub fn is_cell_edited(&self, row: usize, col: usize) -> Result<bool> {
+        if !self.edits.contains_key(&self.file_row(row)?) {
+            return Ok(false);
+        }
+        Ok(self.row_diff(row)?.changed.contains(&col))
+    }
+
+    /// Record a change for [`undo`](Self::undo), forgetting what was undone.
+    fn remember(&mut self, step: Step) {
+        if step.rows.is_empty() && step.order.is_none() && step.columns.is_none() {
+            return;
+        }
+        self.redo.clear();
+        if self.undo.len() == UNDO_LIMIT {
+            self.undo.remove(0);
+        }
+        self.undo.push(step);
+    }
+
+    /// Forget every change, once they no longer apply to the file.
+    fn forget_history(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    /// Put back what `step` replaced, returning what it in turn replaced.
+    fn restore(&mut self, step: Step) -> Result<Step> {
+        let mut inverse = Step::default();
+        for (row, fields) in step.rows.into_iter().rev() {
+            let current = match fields {
+                Some(fields) => self.edits.insert(row, fields),
+                None => self.edits.remove(&row),
+            };
+            inverse.rows.push((row, current));
+        }
+        if let Some(order) = step.order {
+            inverse.order = Some(std::mem::replace(&mut self.order, order));
+        }
+        if let Some(columns) = step.columns {
+            inverse.columns = Some(std::mem::replace(&mut self.columns, columns));
+            #[cfg(feature = "schema")]
+            self.rebind_schema()?;
+        }
+        self.reset_journal()?;
+        Ok(inverse)
+    }
+
+    /// Path of the edit journal for this editor's file.
+    pub fn journal_path(&self) -> PathBuf {
+        Journal::path_for(self.reader.path())
+    }
+
+    /// Whether a journal left behind by an earlier session exists and has not
+    /// been recovered or discarded. A journal with no edits to the file as it
+    /// is now has nothing to recover.
+    pub fn has_recoverable_journal(&self) -> bool {
+        self.journal.is_none() && self.journal_path().is_file() && !self.journal_is_empty()
+    }
+
+    /// Whether the journal on disk is for the file as it is now and records
+    /// no edits, as one left by a session that ended right after saving.
+    fn journal_is_empty(&self) -> bool {
+        let fingerprint = self.reader.fingerprint();
+        matches!(
+            read_journal(&self.journal_path()),
+            Ok(((len, hash), entries))
+                if entries.is_empty() && len == fingerprint.len && hash == fingerprint.sample_hash
+        )
+    }
+
+    /// Start recording edits to `<file>.journal` as they are made.
+    ///
+    /// Fails if a journal from an earlier session exists; call
+    /// [`CsvEditor::recover`] or [`CsvEditor::discard_journal`] first.
+    pub fn enable_journal(&mut self) -> Result<()> {
+        if self.journal.is_some() {
+            return Ok(());
+        }
+        let path = self.journal_path();
+        if path.exists() && !self.journal_is_empty() {
+            return Err(MassiveCsvError::JournalExists(path.display().to_string()));
+        }
+        self.journal = Some(Journal::create(
+            path,
+            self.reader.fingerprint(),
+            self.column_changes(),
+            self.order.as_ref(),
+            &self.edits,
+        )?);
+        Ok(())
+    }
+
+    /// Replay the edits recorded in a leftover journal and keep journaling.
+    ///
+    /// Fails with [`MassiveCsvError::ExternallyModified`] if the file changed
+    /// since the journal was started. Returns the number of pending edits.
+    pub fn recover(&mut self) -> Result<usize> {
+        let path = self.journal_path();
+        let ((len, hash), entries) = read_journal(&path)?;
+        let fingerprint = self.reader.fingerprint();
+        if fingerprint.len != len || fingerprint.sample_hash != hash {
+            return Err(MassiveCsvError::ExternallyModified(
+                self.reader.path().display().to_string(),
+            ));
+        }
+
+        // Apply without journaling, then continue appending to the same file
+        self.journal = None;
+        let count = self.reader.row_count();
+        for entry in entries {
+            match entry {
+                JournalEntry::SetRow(row, fields) => {
+                    self.edits.insert(row, fields);
+                }
+                JournalEntry::RevertRow(row) => {
+                    self.edits.remove(&row);
+                }
+                JournalEntry::RevertAll => self.revert_all(),
+                JournalEntry::Columns(change) => self.change_columns(change)?,
+                JournalEntry::Order(runs) => {
+                    let order = RowOrder::from_runs(runs);
+                    self.order = (!order.is_identity(count)).then_some(order);
+                }
+            }
+        }
+
+        // Inserted rows need their fields; edits to rows no longer in the
+        // order were deleted
+        let order = self.order.clone().unwrap_or_else(|| RowOrder::new(count));
+        let inserted_missing = order
+            .runs()
+            .iter()
+            .any(|run| (run.start.max(count)..run.end).any(|row| !self.edits.contains_key(&row)));
+        if inserted_missing {
+            return Err(MassiveCsvError::Parse(format!(
+                "invalid row order in journal {}",
+                path.display()
+            )));
+        }
+        self.edits
+            .retain(|row, _| order.runs().iter().any(|run| run.contains(row)));
+        self.forget_history();
+        self.journal = Some(Journal::reopen(path)?);
+
+        Ok(self.edits.len())
+    }
+
+    /// Delete the journal, leftover or active, and stop journaling.
+    pub fn discard_journal(&mut self) -> Result<()> {
+        match self.journal.take() {
+            Some(journal) => journal.remove(),
+            None => remove_if_exists(&self.journal_path()),
+        }
+    }
+
+    fn log(&mut self, entry: JournalEntry) -> Result<()> {
+        match &mut self.journal {
+            Some(journal) => journal.append(&entry),
+            None => Ok(()),
+        }
+    }
+
+    /// Restart the active journal so it matches the current file and edits.
+    fn reset_journal(&mut self) -> Result<()> {
+        if self.journal.is_some() {
+            self.journal = Some(Journal::create(
+                self.journal_path(),
+                self.reader.fingerprint(),
+                self.column_changes(),
+                self.order.as_ref(),
+                &self.edits,
+            )?);
+        }
+        Ok(())
+    }
+
+    /// Save all changes atomically.
+    ///
+    /// Strategy: write all rows to a temp file in the same directory,
+    /// then atomically rename it over the original file.
+    /// After save, re-opens the reader to reflect the new file contents.
+    ///
+    /// Fails with [`MassiveCsvError::ExternallyModified`] if another process
+    /// changed the file since it was opened; see [`CsvEditor::save_force`] and
+    /// [`CsvEditor::reload_and_reapply`].
+    pub fn save(&mut self) -> Result<()> {
+        self.save_with_options(&SaveOptions::default())
+    }
+
+    /// Save all changes atomically, as [`CsvEditor::save`], with extra options.
+    pub fn save_with_options(&mut self, options: &SaveOptions) -> Result<()> {
+        if !self.has_changes() {
+            return Ok(());
+        }
+
+        if self.reader.is_modified_on_disk()? {
+            return Err(MassiveCsvError::ExternallyModified(
+                self.reader.path().display().to_string(),
+            ));
+        }
+
+        // Compute the patch against the original rows before they are replaced
+        let patch = self.write_pending_patch(options)?;
+
+        if options.backup {
+            let path = self.reader.path();
+            fs::copy(path, options.backup_path(path))?;
+        }
+        if options.snapshot {
+            snapshot(self.reader.path())?;
+        }
+
+        self.write_and_reopen(options)?;
+
+        if let (Some(patch), Some(out)) = (patch, &options.patch) {
+            persist_atomic(patch, &out.path, options.durability)?;
+        }
+
+        Ok(())
+    }
+
+    /// Describe the pending edits as a key-based [`Patch`].
+    ///
+    /// Edited cells become column-level updates. If an edit changes a key
+    /// column, the row is expressed as a delete of the old row plus an insert.
+    /// Column changes and moved rows can't be described this way and are an
+    /// error.
+    pub fn pending_patch(&self, key_columns: &[String]) -> Result<Patch> {
+        if !self.columns.is_empty() {
+            return Err(MassiveCsvError::InvalidPatch(
+                "a patch can't describe column changes".to_string(),
+            ));
+        }
+        if self.order.is_some() {
+            return Err(MassiveCsvError::InvalidPatch(
+                "a patch can't describe moved, inserted or deleted rows".to_string(),
+            ));
+        }
+        let headers = self.reader.headers().to_vec();
+        if key_columns.is_empty() {
+            return Err(MassiveCsvError::ColumnNotFound(
+                "no key columns given".to_string(),
+            ));
+        }
+        let key_idx = resolve_columns(&headers, key_columns)?;
+
+        let mut rows: Vec<&usize> = self.edits.keys().collect();
+        rows.sort();
+
+        let mut ops = Vec::new();
+        for &row in rows {
+            let original = self.reader.get_row(row)?;
+            let edited = &self.edits[&row];
+            let old_key = key_of(&original, &key_idx);
+
+            if key_of(edited, &key_idx) != old_key {
+                ops.push(PatchOp::Delete { fields: original });
+                ops.push(PatchOp::Insert {
+                    fields: edited.clone(),
+                });
+                continue;
+            }
+
+            for (c, name) in headers.iter().enumerate() {
+                let before = original.get(c).map(|s| s.as_str()).unwrap_or("");
+                let after = edited.get(c).map(|s| s.as_str()).unwrap_or("");
+                if before != after {
+                    ops.push(PatchOp::Update {
+                        key: old_key.clone(),
+                        column: name.clone(),
+                        old: before.to_string(),
+                        new: after.to_string(),
+                    });
+                }
+            }
+        }
+
+        Ok(Patch {
+            key_columns: key_columns.to_vec(),
+            headers,
+            ops,
+        })
+    }
+
+    /// Save all changes, overwriting the file even if it was modified externally.
+    ///
+    /// Unedited rows are copied from the file as it was opened, so a version
+    /// another process saved to the same path is overwritten. If that process
+    /// rewrote the file in place instead, the original rows are gone and this
+    /// fails with [`MassiveCsvError::ExternallyModified`]; see
+    /// [`CsvEditor::reload_and_reapply`].
+    pub fn save_force(&mut self) -> Result<()> {
+        if !self.has_changes() {
+            return Ok(());
+        }
+        if self.reader.is_changed_in_place()? {
+            return Err(MassiveCsvError::ExternallyModified(format!(
+                "{} was rewritten in place; its original rows can't be copied",
+                self.reader.path().display()
+            )));
+        }
+        self.write_and_reopen(&SaveOptions::default())
+    }
+
+    /// Re-open the file from disk and re-apply pending edits on top of it.
+    ///
+    /// Edits are keyed by row number, so they land on whatever rows now occupy
+    /// those positions. Edits to rows that no longer exist are dropped.
+    /// Column changes are looked up again in the new headers, and fail if
+    /// their columns are gone; moved, inserted or deleted rows fail if the
+    /// row count changed.
+    /// Returns the number of edits kept.
+    pub fn reload_and_reapply(&mut self) -> Result<usize> {
+        self.reload_and_reapply_with_progress(&Progress::default())
+    }
+
+    /// [`reload_and_reapply`](Self::reload_and_reapply), reporting the bytes
+    /// indexed so far to `progress` while the file is re-opened.
+    pub fn reload_and_reapply_with_progress(&mut self, progress: &Progress) -> Result<usize> {
+        let reader = self.reader.reopen_with_progress(progress)?;
+        if self.order.is_some() && reader.row_count() != self.reader.row_count() {
+            return Err(MassiveCsvError::ExternallyModified(format!(
+                "{} now has {} rows; moved, inserted or deleted rows can't be reapplied",
+                self.reader.path().display(),
+                reader.row_count()
+            )));
+        }
+        let mut headers = reader.headers().to_vec();
+        let mut columns = Vec::with_capacity(self.columns.len());
+        for change in &self.columns {
+            let bound = change.change.clone().bind(&headers)?;
+            headers.clone_from(&bound.headers);
+            columns.push(bound);
+        }
+        self.reader = reader;
+        self.columns = columns;
+        if self.order.is_none() {
+            let count = self.reader.row_count();
+            self.edits.retain(|&row, _| row < count);
+        }
+        self.forget_history();
+        self.reset_journal()?;
+        Ok(self.edits.len())
+    }
+
+    /// Write the current state (original + edits) to a different file.
+    ///
+    /// The source file and pending edits are left untouched. `backup` and
+    /// `snapshot` apply to an existing file at `path`. Saving to the editor's
+    /// own path is the same as [`CsvEditor::save_with_options`].
+    pub fn save_as(&mut self, path: &Path, options: &SaveOptions) -> Result<()> {
+        if is_same_file(path, self.reader.path()) {
+            return self.save_with_options(options);
+        }
+
+        let patch = self.write_pending_patch(options)?;
+
+        if path.exists() {
+            if options.backup {
+                fs::copy(path, options.backup_path(path))?;
+            }
+            if options.snapshot {
+                snapshot(path)?;
+            }
+        }
+
+        self.write_merged(path, options)?;
+
+        if let (Some(patch), Some(out)) = (patch, &options.patch) {
+            persist_atomic(patch, &out.path, options.durability)?;
+        }
+
+        Ok(())
+    }
+
+    /// Write the patch [`SaveOptions::patch`] asks for to a temp file, before
+    /// the save replaces anything, so a patch that can't be written fails the
+    /// save with the file untouched. The caller renames it into place after.
+    fn write_pending_patch(&self, options: &SaveOptions) -> Result<Option<NamedTempFile>> {
+        let Some(out) = &options.patch else {
+            return Ok(None);
+        };
+        let patch = self.pending_patch(&out.key_columns)?;
+        let temp = write_temp_with(&out.path, options.durability, |writer| {
+            patch.write_to(writer)
+        })?;
+        Ok(Some(temp))
+    }
+
+    fn write_and_reopen(&mut self, options: &SaveOptions) -> Result<()> {
+        let path = self.reader.path().to_path_buf();
+        self.write_merged(&path, options)?;
+
+        // Re-open reader with new file contents; a converted file needs its dialect re-detected
+        self.reader = if options.delimiter.is_some() || options.quote_style.is_some() {
+            let open = OpenOptions {
+                no_headers: self.reader.options().no_headers,
+                ..Default::default()
+            };
+            CsvReader::open_with(&path, &open)?
+        } else if let Some(encoding) = options.encoding {
+            let open = OpenOptions {
+                encoding: Some(encoding),
+                ..self.reader.options().clone()
+            };
+            CsvReader::open_with(&path, &open)?
+        } else {
+            self.reader.reopen()?
+        };
+        self.edits.clear();
+        self.order = None;
+        self.forget_history();
+        if !self.columns.is_empty() {
+            self.columns.clear();
+            #[cfg(feature = "schema")]
+            self.rebind_schema()?;
+        }
+        self.reset_journal()?;
+
+        Ok(())
+    }
+
+    /// Atomically write all rows, substituting edits, to `path`.
+    fn write_merged(&self, path: &Path, options: &SaveOptions) -> Result<()> {
+        let source = self.reader.dialect();
+        let delimiter = options.delimiter.unwrap_or(source.delimiter);
+        // Converting the dialect or changing the columns means every row has
+        // to be re-serialized, with standard quoting and escaping
+        let convert = delimiter != source.delimiter
+            || options.quote_style.is_some()
+            || !self.columns.is_empty();
+        let dialect = if convert {
+            Dialect::from(delimiter)
+        } else {
+            source
+        };
+        let quote_style = options.quote_style.unwrap_or_default();
+        let line_ending = options.line_ending.unwrap_or(self.reader.line_ending());
+        let trailing_newline = options
+            .trailing_newline
+            .unwrap_or(self.reader.has_trailing_newline());
+
+        // Fixed-width files stay fixed-width unless converted to a delimiter
+        let layout = self.reader.fixed_width().filter(|_| !convert);
+        let encoding = options.encoding.unwrap_or(self.reader.encoding());
+
+        write_atomic_with(path, options.durability, |writer| {
+            let compression = options.compression.unwrap_or(self.reader.compression());
+            let mut compressor = Compressor::new(writer, compression)?;
+            let mut lines = LineWriter::with_encoding(&mut compressor, line_ending, encoding);
+            match layout {
+                _ if self.reader.options().no_headers => {}
+                Some(layout) => lines.write_line(
+                    &layout
+                        .serialize_preserving(self.reader.headers(), self.reader.header_raw()?)?,
+                )?,
+                None => {
+                    lines.write_line(&serialize_row_with(self.headers(), dialect, quote_style))?
+                }
+            }
+
+            // Write all rows in their current order, substituting edits
+            let file_rows = self.reader.row_count();
+            let order = match &self.order {
+                Some(order) => order.clone(),
+                None => RowOrder::new(file_rows),
+            };
+            let total = order.len();
+            for (written, i) in order.iter().enumerate() {
+                if written.is_multiple_of(SAVE_PROGRESS_ROWS) {
+                    options.progress.update(written, total)?;
+                }
+                if convert {
+                    let fields = self.current_row(i)?;
+                    lines.write_line(&serialize_row_with(&fields, dialect, quote_style))?;
+                    continue;
+                }
+                // Inserted rows have no original text to keep
+                let raw = if i < file_rows {
+                    self.reader.get_row_raw(i)?
+                } else {
+                    ""
+                };
+                match (self.edits.get(&i), layout) {
+                    (Some(edited), Some(layout)) => {
+                        lines.write_line(&layout.serialize_preserving(edited, raw)?)?
+                    }
+                    (Some(edited), None) => {
+                        lines.write_line(&serialize_row_preserving(edited, raw, dialect))?
+                    }
+                    (None, _) => lines.write_line(raw)?,
+                }
+            }
+            options.progress.update(total, total)?;
+            lines.finish(trailing_newline)?;
+            compressor.finish()
+        })
+    }
+}
+
+impl Drop for CsvEditor {
+    /// Remove the journal if nothing is left unsaved, so the next session
+    /// doesn't find it.
+    fn drop(&mut self) {
+        if !self.has_changes() {
+            if let Some(journal) = self.journal.take() {
+                let _ = journal.remove();
+            }
+        }
+    }
+}
+
+/// Store a compressed copy of the file at `path` in its version history.
+#[cfg(feature = "versions")]
+fn snapshot(path: &Path) -> Result<()> {
+    VersionStore::for_file(path).snapshot().map(drop)
+}
+
+#[cfg(not(feature = "versions"))]
+fn snapshot(_: &Path) -> Result<()> {
+    Err(MassiveCsvError::FeatureDisabled(
+        "Saving with a snapshot",
+        "versions",
+    ))
+}
+
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// Writes lines separated by a fixed terminator, with an optional final one.
+pub(crate) struct LineWriter<'a> {
+    out: &'a mut dyn Write,
+    line_ending: LineEnding,
+    encoding: Encoding,
+    encoded: Vec<u8>,
+    started: bool,
+}
+
+impl<'a> LineWriter<'a> {
+    pub fn new(out: &'a mut dyn Write, line_ending: LineEnding) -> Self {
+        Self::with_encoding(out, line_ending, Encoding::Utf8)
+    }
+
+    /// A writer that encodes lines as `encoding`, starting with its byte
+    /// order mark if it has one.
+    pub fn with_encoding(
+        out: &'a mut dyn Write,
+        line_ending: LineEnding,
+        encoding: Encoding,
+    ) -> Self {
+        Self {
+            out,
+            line_ending,
+            encoding,
+            encoded: Vec::new(),
+            started: false,
+        }
+    }
+
+    /// Write one line (without terminator).
+    pub fn write_line(&mut self, line: &str) -> Result<()> {
+        if self.started {
+            self.write_str(self.line_ending.as_str())?;
+        } else if self.encoding != Encoding::Utf8 {
+            self.out.write_all(self.encoding.bom())?;
+            // A UTF-8 byte order mark kept in the header has no place here
+            let line = line.strip_prefix('\u{feff}').unwrap_or(line);
+            self.started = true;
+            return self.write_str(line);
+        }
+        self.write_str(line)?;
+        self.started = true;
+        Ok(())
     }
 
-    /// Revert all pending edits.
-    pub fn revert_all(&mut self) {
-        self.edits.clear();
+    /// Finish the output, ending it with a terminator if `trailing_newline`.
+    pub fn finish(mut self, trailing_newline: bool) -> Result<()> {
+        if self.started && trailing_newline {
+            self.write_str(self.line_ending.as_str())?;
+        }
+        Ok(())
     }
 
-    /// Save all changes atomically.
-    ///
-    /// Strategy: write all rows to a temp file in the same directory,
-    /// then atomically rename it over the original file.
-    /// After save, re-opens the reader to reflect the new file contents.
-    pub fn save(&mut self) -> Result<()> {
-        if self.edits.is_empty() {
-            return Ok(());
+    fn write_str(&mut self, text: &str) -> Result<()> {
+        if self.encoding == Encoding::Utf8 {
+            self.out.write_all(text.as_bytes())?;
+        } else {
+            self.encoded.clear();
+            self.encoding.encode(text, &mut self.encoded)?;
+            self.out.write_all(&self.encoded)?;
         }
+        Ok(())
+    }
+}
 
-        let path = self.reader.path().to_path_buf();
-        let parent = path.parent().unwrap_or(std::path::Path::new("."));
-        let delimiter = self.reader.delimiter();
-
-        // Create temp file in the same directory (required for atomic rename)
-        let temp = NamedTempFile::new_in(parent)?;
-        let mut writer = BufWriter::new(&temp);
-
-        // Write header
-        let header_line = serialize_row(self.reader.headers(), delimiter);
-        writer.write_all(header_line.as_bytes())?;
-        writer.write_all(b"\n")?;
-
-        // Write all rows, substituting edits
-        let row_count = self.reader.row_count();
-        for i in 0..row_count {
-            if let Some(edited_fields) = self.edits.get(&i) {
-                let line = serialize_row(edited_fields, delimiter);
-                writer.write_all(line.as_bytes())?;
-            } else {
-                let raw = self.reader.get_row_raw(i)?;
-                writer.write_all(raw.as_bytes())?;
-            }
-            writer.write_all(b"\n")?;
-        }
+/// Write a file atomically with the default [`Durability`].
+pub(crate) fn write_atomic<F>(path: &Path, write: F) -> Result<()>
+where
+    F: FnOnce(&mut dyn Write) -> Result<()>,
+{
+    write_atomic_with(path, Durability::None, write)
+}
 
+/// Write a file atomically.
+///
+/// Strategy: `write` fills a temp file in the same directory as `path`,
+/// which is synced according to `durability` and then atomically renamed over `path`.
+/// Something other than a regular file, such as `/dev/stdout` or a named
+/// pipe, can't be replaced that way and is written to directly.
+pub(crate) fn write_atomic_with<F>(path: &Path, durability: Durability, write: F) -> Result<()>
+where
+    F: FnOnce(&mut dyn Write) -> Result<()>,
+{
+    if fs::metadata(path).is_ok_and(|meta| !meta.is_file()) {
+        let mut writer = BufWriter::new(fs::OpenOptions::new().write(true).open(path)?);
+        write(&mut writer)?;
         writer.flush()?;
-        drop(writer);
+        return Ok(());
+    }
+    let temp = write_temp_with(path, durability, write)?;
+    persist_atomic(temp, path, durability)
+}
 
-        // Atomic rename
-        // On Unix, persist does rename(2). On Windows, it falls back to copy+delete.
-        temp.persist(&path).map_err(|e| e.error)?;
+/// The first half of [`write_atomic_with`]: a temp file beside `path`,
+/// filled by `write` and synced, for [`persist_atomic`] to rename over
+/// `path` once everything else it depends on has been written.
+fn write_temp_with<F>(path: &Path, durability: Durability, write: F) -> Result<NamedTempFile>
+where
+    F: FnOnce(&mut dyn Write) -> Result<()>,
+{
+    // Create temp file in the same directory (required for atomic rename)
+    let temp = NamedTempFile::new_in(parent_dir(path))?;
+    let mut writer = BufWriter::new(&temp);
+    write(&mut writer)?;
+    writer.flush()?;
+    drop(writer);
 
-        // Ensure filesystem has flushed the directory entry
-        if let Ok(dir) = fs::File::open(parent) {
-            let _ = dir.sync_all();
-        }
+    // Data must be on disk before the rename makes it visible
+    match durability {
+        Durability::None => {}
+        Durability::DataSync => temp.as_file().sync_data()?,
+        Durability::Full => temp.as_file().sync_all()?,
+    }
+    Ok(temp)
+}
 
-        // Re-open reader with new file contents
-        self.reader = CsvReader::open(&path)?;
-        self.edits.clear();
+/// The second half of [`write_atomic_with`]: rename `temp` over `path`.
+fn persist_atomic(temp: NamedTempFile, path: &Path, durability: Durability) -> Result<()> {
+    // Atomic rename
+    // On Unix, persist does rename(2). On Windows, it falls back to copy+delete.
+    temp.persist(path).map_err(|e| e.error)?;
 
-        Ok(())
+    // Ensure filesystem has flushed the directory entry
+    let synced = fs::File::open(parent_dir(path)).and_then(|dir| dir.sync_all());
+    // Directories can't be opened for syncing on Windows
+    if durability == Durability::Full && cfg!(unix) {
+        synced?;
+    }
+
+    Ok(())
+}
+
+fn parent_dir(path: &Path) -> &Path {
+    match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::parser::FixedWidth;
     use std::io::Write as IoWrite;
 
     fn make_csv(content: &str) -> tempfile::NamedTempFile {
@@ -179,6 +1578,41 @@ mod tests {
         assert_eq!(row, vec!["Bob", "25"]);
     }
 
+    #[test]
+    fn cancelled_save_keeps_file_and_edits() {
+        let f = make_csv("name,age\nAlice,30\n");
+        let mut editor = CsvEditor::open(f.path()).unwrap();
+        editor.set_cell(0, 1, "31".to_string()).unwrap();
+
+        let cancel = Arc::new(std::sync::atomic::AtomicBool::new(true));
+        let options = SaveOptions {
+            progress: Progress::default().with_cancel(cancel),
+            ..Default::default()
+        };
+        assert!(matches!(
+            editor.save_with_options(&options),
+            Err(MassiveCsvError::Cancelled)
+        ));
+        assert_eq!(
+            std::fs::read_to_string(f.path()).unwrap(),
+            "name,age\nAlice,30\n"
+        );
+        assert_eq!(editor.get_row(0).unwrap(), vec!["Alice", "31"]);
+    }
+
+    #[test]
+    fn saves_a_file_without_headers() {
+        let f = make_csv("1,Ann\n2,Bo\n");
+        let mut editor = CsvEditor::builder(f.path())
+            .no_headers(true)
+            .open()
+            .unwrap();
+        editor.set_cell(0, 1, "Al".to_string()).unwrap();
+        editor.save().unwrap();
+        assert_eq!(std::fs::read_to_string(f.path()).unwrap(), "1,Al\n2,Bo\n");
+        assert_eq!(editor.row_count(), 2);
+    }
+
     #[test]
     fn set_row_and_revert() {
         let f = make_csv("a,b\n1,2\n3,4\n");
@@ -215,6 +1649,555 @@ mod tests {
         editor.save().unwrap(); // should be a no-op
     }
 
+    #[test]
+    fn save_detects_external_modification() {
+        let f = make_csv("a,b\n1,2\n3,4\n");
+        let path = f.path().to_path_buf();
+
+        let mut editor = CsvEditor::open(&path).unwrap();
+        editor.set_cell(0, 0, "x".to_string()).unwrap();
+
+        // Saved by another program through a rename, as most editors do
+        let other = path.with_extension("other");
+        std::fs::write(&other, "a,b\n1,2\n5,6\n").unwrap();
+        std::fs::rename(&other, &path).unwrap();
+
+        let result = editor.save();
+        assert!(matches!(
+            result,
+            Err(MassiveCsvError::ExternallyModified(_))
+        ));
+        assert!(editor.has_changes());
+
+        editor.save_force().unwrap();
+        assert!(!editor.has_changes());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "a,b\nx,2\n3,4\n");
+    }
+
+    #[test]
+    fn save_force_refuses_a_file_rewritten_in_place() {
+        let rows: String = (0..20_000).map(|i| format!("{i},row {i}\n")).collect();
+        let f = make_csv(&format!("id,name\n{rows}"));
+        let path = f.path().to_path_buf();
+
+        let mut editor = CsvEditor::open(&path).unwrap();
+        editor.set_cell(0, 1, "edited".to_string()).unwrap();
+
+        // Truncated and rewritten through the same inode the editor has mapped
+        let shorter: String = (0..100).map(|i| format!("{i},new {i}\n")).collect();
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .truncate(true)
+            .open(&path)
+            .unwrap();
+        file.write_all(format!("id,name\n{shorter}").as_bytes())
+            .unwrap();
+        drop(file);
+
+        assert!(matches!(
+            editor.save_force(),
+            Err(MassiveCsvError::ExternallyModified(_))
+        ));
+        assert!(editor.has_changes());
+        let reader = CsvReader::open(&path).unwrap();
+        assert_eq!(reader.row_count(), 100);
+        for i in 0..100 {
+            assert_eq!(
+                reader.get_row(i).unwrap(),
+                vec![i.to_string(), format!("new {i}")]
+            );
+        }
+    }
+
+    #[test]
+    fn reload_and_reapply_keeps_edits_in_range() {
+        let f = make_csv("a\n1\n2\n3\n");
+        let path = f.path().to_path_buf();
+
+        let mut editor = CsvEditor::open(&path).unwrap();
+        editor.set_row(0, vec!["x".to_string()]).unwrap();
+        editor.set_row(2, vec!["z".to_string()]).unwrap();
+
+        std::fs::write(&path, "a\n10\n20\n").unwrap();
+
+        assert_eq!(editor.reload_and_reapply().unwrap(), 1);
+        editor.save().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "a\nx\n20\n");
+    }
+
+    #[test]
+    fn save_emits_patch() {
+        let f = make_csv("id,v\n1,a\n2,b\n");
+        let path = f.path().to_path_buf();
+        let dir = tempfile::tempdir().unwrap();
+        let patch_path = dir.path().join("changes.mcpatch");
+
+        let mut editor = CsvEditor::open(&path).unwrap();
+        editor.set_cell(1, 1, "B".to_string()).unwrap();
+
+        let options = SaveOptions {
+            patch: Some(PatchOutput {
+                path: patch_path.clone(),
+                key_columns: vec!["id".to_string()],
+            }),
+            ..Default::default()
+        };
+        editor.save_with_options(&options).unwrap();
+
+        let patch = Patch::load(&patch_path).unwrap();
+        assert_eq!(
+            patch.ops,
+            vec![PatchOp::Update {
+                key: vec!["2".to_string()],
+                column: "v".to_string(),
+                old: "b".to_string(),
+                new: "B".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn save_leaves_file_alone_when_patch_fails() {
+        let f = make_csv("id,v\n1,a\n2,b\n");
+        let path = f.path().to_path_buf();
+        let dir = tempfile::tempdir().unwrap();
+
+        let mut editor = CsvEditor::open(&path).unwrap();
+        editor.set_cell(1, 1, "B".to_string()).unwrap();
+
+        let options = SaveOptions {
+            patch: Some(PatchOutput {
+                path: dir.path().join("missing").join("changes.mcpatch"),
+                key_columns: vec!["id".to_string()],
+            }),
+            ..Default::default()
+        };
+        assert!(editor.save_with_options(&options).is_err());
+        assert!(editor
+            .save_as(&dir.path().join("out.csv"), &options)
+            .is_err());
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "id,v\n1,a\n2,b\n");
+        assert!(!dir.path().join("out.csv").exists());
+        assert!(editor.has_changes());
+    }
+
+    #[test]
+    fn save_writes_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.csv");
+        std::fs::write(&path, "a\n1\n").unwrap();
+
+        let mut editor = CsvEditor::open(&path).unwrap();
+        editor.set_row(0, vec!["2".to_string()]).unwrap();
+        let options = SaveOptions {
+            backup: true,
+            ..Default::default()
+        };
+        editor.save_with_options(&options).unwrap();
+
+        let backup = dir.path().join("data.csv.bak");
+        assert_eq!(std::fs::read_to_string(backup).unwrap(), "a\n1\n");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "a\n2\n");
+    }
+
+    #[test]
+    fn journal_recovers_unsaved_edits() {
+        let f = make_csv("a,b\n1,2\n3,4\n");
+        let path = f.path().to_path_buf();
+
+        {
+            let mut editor = CsvEditor::open(&path).unwrap();
+            editor.enable_journal().unwrap();
+            editor.set_cell(0, 1, "x, y".to_string()).unwrap();
+            editor.set_cell(1, 0, "z".to_string()).unwrap();
+            editor.revert_row(1);
+            // Dropped without saving, as in a crash
+        }
+
+        let mut editor = CsvEditor::open(&path).unwrap();
+        assert!(editor.has_recoverable_journal());
+        assert!(editor.enable_journal().is_err());
+
+        assert_eq!(editor.recover().unwrap(), 1);
+        assert_eq!(editor.get_row(0).unwrap(), vec!["1", "x, y"]);
+        assert!(!editor.has_recoverable_journal());
+
+        editor.save().unwrap();
+        editor.discard_journal().unwrap();
+        assert!(!editor.journal_path().exists());
+    }
+
+    #[test]
+    fn saved_journal_is_not_left_behind() {
+        let f = make_csv("a,b\n1,2\n3,4\n");
+        let path = f.path().to_path_buf();
+
+        {
+            let mut editor = CsvEditor::open(&path).unwrap();
+            editor.enable_journal().unwrap();
+            editor.set_cell(0, 1, "x".to_string()).unwrap();
+            editor.save().unwrap();
+        }
+        let mut editor = CsvEditor::open(&path).unwrap();
+        assert!(!editor.journal_path().exists());
+        assert!(!editor.has_recoverable_journal());
+        editor.enable_journal().unwrap();
+
+        // A crash right after saving leaves a journal with nothing to replay
+        editor.set_cell(1, 1, "y".to_string()).unwrap();
+        editor.save().unwrap();
+        std::mem::forget(editor);
+        let mut editor = CsvEditor::open(&path).unwrap();
+        assert!(editor.journal_path().exists());
+        assert!(!editor.has_recoverable_journal());
+        editor.enable_journal().unwrap();
+        assert_eq!(editor.get_row(1).unwrap(), vec!["3", "y"]);
+    }
+
+    #[test]
+    fn journal_ignores_truncated_entry() {
+        let f = make_csv("a\n1\n2\n");
+        let path = f.path().to_path_buf();
+
+        {
+            let mut editor = CsvEditor::open(&path).unwrap();
+            editor.enable_journal().unwrap();
+            editor.set_row(0, vec!["x".to_string()]).unwrap();
+            editor.set_row(1, vec!["yyy".to_string()]).unwrap();
+        }
+
+        let journal = Journal::path_for(&path);
+        let content = std::fs::read_to_string(&journal).unwrap();
+        std::fs::write(&journal, &content[..content.len() - 4]).unwrap();
+
+        let mut editor = CsvEditor::open(&path).unwrap();
+        assert_eq!(editor.recover().unwrap(), 1);
+        assert_eq!(editor.get_row(1).unwrap(), vec!["2"]);
+        editor.discard_journal().unwrap();
+    }
+
+    #[test]
+    fn splits_and_merges_columns_on_save() {
+        let f = make_csv("id,place,first,last\n1,\"Austin, TX\",Ada,Lovelace\n2,Paris,Bo,\n");
+        let mut editor = CsvEditor::open(f.path()).unwrap();
+        editor.set_cell(1, 1, "Lyon".to_string()).unwrap();
+
+        editor
+            .split_column("place", ",", &["city", "state"])
+            .unwrap();
+        editor
+            .merge_columns(&["first", "last"], " ", "name")
+            .unwrap();
+        assert!(editor.merge_columns(&["nope"], " ", "x").is_err());
+        assert!(editor.split_column("name", " ", &["id"]).is_err());
+        assert_eq!(editor.headers(), &["id", "city", "state", "name"]);
+        assert_eq!(
+            editor.get_row(0).unwrap(),
+            vec!["1", "Austin", "TX", "Ada Lovelace"]
+        );
+        // Edited before the changes, reshaped with them
+        assert_eq!(editor.get_row(1).unwrap(), vec!["2", "Lyon", "", "Bo"]);
+        assert_eq!(editor.get_record(0).unwrap().get("state"), Some("TX"));
+        assert!(editor.pending_patch(&["id".to_string()]).is_err());
+
+        editor.set_cell(1, 2, "FR".to_string()).unwrap();
+        editor.save().unwrap();
+        assert!(!editor.has_changes());
+        assert_eq!(
+            std::fs::read_to_string(f.path()).unwrap(),
+            "id,city,state,name\n1,Austin,TX,Ada Lovelace\n2,Lyon,FR,Bo\n"
+        );
+    }
+
+    #[test]
+    fn journal_recovers_column_changes() {
+        let f = make_csv("id,first,last\n1,Ada,Lovelace\n");
+        let path = f.path().to_path_buf();
+        {
+            let mut editor = CsvEditor::open(&path).unwrap();
+            editor.enable_journal().unwrap();
+            editor
+                .merge_columns(&["first", "last"], " ", "name")
+                .unwrap();
+            editor.set_cell(0, 1, "Grace Hopper".to_string()).unwrap();
+            editor.add_column("note", "-").unwrap();
+            editor.rename_column("id", "key").unwrap();
+            editor.drop_column("name").unwrap();
+        }
+
+        let mut editor = CsvEditor::open(&path).unwrap();
+        editor.recover().unwrap();
+        assert_eq!(editor.headers(), &["key", "note"]);
+        assert_eq!(editor.get_row(0).unwrap(), vec!["1", "-"]);
+
+        editor.revert_all();
+        assert!(!editor.has_changes());
+        assert_eq!(editor.headers(), &["id", "first", "last"]);
+        editor.discard_journal().unwrap();
+    }
+
+    #[test]
+    fn moves_rows_with_their_edits() {
+        let f = make_csv("n\na\nb\nc\nd\ne\n");
+        let path = f.path().to_path_buf();
+        {
+            let mut editor = CsvEditor::open(&path).unwrap();
+            editor.enable_journal().unwrap();
+            editor.set_cell(3, 0, "D".to_string()).unwrap();
+            // d, e to the top
+            editor.move_rows(3..5, 0).unwrap();
+            assert_eq!(editor.get_row(0).unwrap(), vec!["D"]);
+            assert_eq!(editor.file_row(2).unwrap(), 0);
+            editor.set_cell(4, 0, "C".to_string()).unwrap();
+            assert!(editor.move_rows(4..6, 0).is_err());
+            assert!(editor.move_rows(0..2, 4).is_err());
+        }
+
+        let mut editor = CsvEditor::open(&path).unwrap();
+        assert_eq!(editor.recover().unwrap(), 2);
+        assert!(editor.has_moved_rows());
+        let rows: Vec<String> = (0..5)
+            .map(|i| editor.get_row(i).unwrap()[0].clone())
+            .collect();
+        assert_eq!(rows, vec!["D", "e", "a", "b", "C"]);
+        assert!(editor.pending_patch(&["n".to_string()]).is_err());
+
+        editor.save().unwrap();
+        assert!(!editor.has_changes());
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "n\nD\ne\na\nb\nC\n"
+        );
+        editor.discard_journal().unwrap();
+
+        editor.move_rows(0..1, 4).unwrap();
+        editor.revert_all();
+        assert!(!editor.has_moved_rows());
+        assert_eq!(editor.get_row(0).unwrap(), vec!["D"]);
+    }
+
+    #[test]
+    fn inserts_and_deletes_rows() {
+        let f = make_csv("n,v\na,1\nb,2\nc,3\nd,4\n");
+        let path = f.path().to_path_buf();
+        let row = |s: &str| s.split(',').map(String::from).collect::<Vec<String>>();
+        {
+            let mut editor = CsvEditor::open(&path).unwrap();
+            editor.enable_journal().unwrap();
+            editor.insert_rows(0, vec![row("top,0")]).unwrap();
+            editor
+                .insert_rows(5, vec![row("end"), row("last,9,extra")])
+                .unwrap();
+            assert_eq!(editor.row_count(), 7);
+            assert_eq!(editor.get_row(6).unwrap(), vec!["last", "9"]);
+            assert_eq!(editor.get_row(5).unwrap(), vec!["end", ""]);
+            assert!(editor.insert_rows(8, vec![row("x,1")]).is_err());
+
+            editor.set_cell(2, 1, "B".to_string()).unwrap();
+            editor.delete_rows(3..5).unwrap();
+            assert_eq!(editor.row_count(), 5);
+            assert_eq!(editor.get_row(3).unwrap(), vec!["end", ""]);
+            assert!(editor.delete_rows(4..6).is_err());
+            assert!(editor.pending_patch(&["n".to_string()]).is_err());
+        }
+
+        let mut editor = CsvEditor::open(&path).unwrap();
+        editor.recover().unwrap();
+        let rows: Vec<String> = (0..editor.row_count())
+            .map(|i| editor.get_row(i).unwrap().join(","))
+            .collect();
+        assert_eq!(rows, vec!["top,0", "a,1", "b,B", "end,", "last,9"]);
+
+        // Reverting an inserted row removes it
+        editor.revert_row(3);
+        editor.delete_rows_at(&[3, 0]).unwrap();
+        assert!(editor.delete_rows_at(&[2]).is_err());
+        editor.save().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "n,v\na,1\nb,B\n");
+        editor.discard_journal().unwrap();
+
+        editor.delete_rows(0..2).unwrap();
+        assert_eq!(editor.row_count(), 0);
+        editor.insert_rows(0, vec![row("z,26")]).unwrap();
+        editor.save().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "n,v\nz,26\n");
+    }
+
+    #[test]
+    fn save_as_leaves_source_untouched() {
+        let f = make_csv("a,b\n1,2\n3,4\n");
+        let path = f.path().to_path_buf();
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("out.csv");
+
+        let mut editor = CsvEditor::open(&path).unwrap();
+        editor.set_cell(1, 1, "x".to_string()).unwrap();
+        editor.save_as(&target, &SaveOptions::default()).unwrap();
+
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "a,b\n1,2\n3,x\n");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "a,b\n1,2\n3,4\n");
+        assert!(editor.has_changes());
+    }
+
+    #[test]
+    fn save_preserves_line_endings() {
+        let f = make_csv("a,b\r\n1,2\r\n3,4");
+        let path = f.path().to_path_buf();
+
+        let mut editor = CsvEditor::open(&path).unwrap();
+        editor.set_cell(0, 0, "x".to_string()).unwrap();
+        editor.save().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "a,b\r\nx,2\r\n3,4");
+
+        editor.set_cell(0, 0, "y".to_string()).unwrap();
+        let options = SaveOptions {
+            line_ending: Some(LineEnding::Lf),
+            trailing_newline: Some(true),
+            ..Default::default()
+        };
+        editor.save_with_options(&options).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "a,b\ny,2\n3,4\n");
+    }
+
+    #[test]
+    fn save_keeps_quoting_of_untouched_fields() {
+        let f = make_csv("id,code,note\n1,\"007\",\"ok\"\n");
+        let path = f.path().to_path_buf();
+
+        let mut editor = CsvEditor::open(&path).unwrap();
+        editor.set_cell(0, 2, "done, really".to_string()).unwrap();
+        editor.save().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "id,code,note\n1,\"007\",\"done, really\"\n"
+        );
+    }
+
+    #[test]
+    fn save_as_converts_dialect() {
+        let f = make_csv("name;price\nTaschenlampe;\"3,50\"\nSeil;2\n");
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out.csv");
+
+        let mut editor = CsvEditor::open(f.path()).unwrap();
+        editor.set_cell(1, 1, "2,10".to_string()).unwrap();
+        let options = SaveOptions {
+            delimiter: Some(b','),
+            line_ending: Some(LineEnding::CrLf),
+            ..Default::default()
+        };
+        editor.save_as(&out, &options).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&out).unwrap(),
+            "name,price\r\nTaschenlampe,\"3,50\"\r\nSeil,\"2,10\"\r\n"
+        );
+    }
+
+    #[test]
+    fn edit_fixed_width_file() {
+        let f = make_csv("ID  NAME      AMT\n   7Ann         12\n  42Bob          3\n");
+        let options = OpenOptions {
+            fixed_width: Some(FixedWidth::new(vec![4, 10, 4])),
+            ..Default::default()
+        };
+        let mut editor = CsvEditor::open_with(f.path(), &options).unwrap();
+        assert_eq!(editor.reader().headers(), &["ID", "NAME", "AMT"]);
+        assert_eq!(editor.get_row(0).unwrap(), vec!["7", "Ann", "12"]);
+
+        editor.set_cell(1, 1, "Roberta".to_string()).unwrap();
+        editor.save().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(f.path()).unwrap(),
+            "ID  NAME      AMT\n   7Ann         12\n  42Roberta      3\n"
+        );
+        assert_eq!(editor.get_row(1).unwrap(), vec!["42", "Roberta", "3"]);
+
+        editor
+            .set_cell(0, 1, "Far too long a name".to_string())
+            .unwrap();
+        assert!(matches!(editor.save(), Err(MassiveCsvError::Parse(_))));
+    }
+
+    #[cfg(feature = "encodings")]
+    #[test]
+    fn save_keeps_or_converts_encoding() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("latin1.csv");
+        std::fs::write(&path, b"name\ncaf\xe9\nna\xefve\n").unwrap();
+
+        let mut editor = CsvEditor::open(&path).unwrap();
+        editor.set_cell(1, 0, "déjà vu".to_string()).unwrap();
+        editor.save().unwrap();
+        assert_eq!(
+            std::fs::read(&path).unwrap(),
+            b"name\ncaf\xe9\nd\xe9j\xe0 vu\n"
+        );
+
+        editor.set_cell(0, 0, "€ ✓".to_string()).unwrap();
+        assert!(matches!(
+            editor.save(),
+            Err(MassiveCsvError::Unencodable('✓', _))
+        ));
+        editor.set_cell(0, 0, "café".to_string()).unwrap();
+
+        let options = SaveOptions {
+            encoding: Some(Encoding::Utf16Be),
+            ..Default::default()
+        };
+        editor.save_with_options(&options).unwrap();
+        let expected: Vec<u8> = [0xFE, 0xFF]
+            .into_iter()
+            .chain(
+                "name\ncafé\ndéjà vu\n"
+                    .encode_utf16()
+                    .flat_map(u16::to_be_bytes),
+            )
+            .collect();
+        assert_eq!(std::fs::read(&path).unwrap(), expected);
+        assert_eq!(editor.reader().encoding(), Encoding::Utf16Be);
+        assert_eq!(editor.get_row(1).unwrap(), vec!["déjà vu"]);
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn edit_and_save_compressed_file() {
+        use std::io::Read;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.csv.gz");
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(b"a,b\n1,2\n3,4\n").unwrap();
+        std::fs::write(&path, encoder.finish().unwrap()).unwrap();
+
+        let mut editor = CsvEditor::open(&path).unwrap();
+        assert_eq!(editor.reader().compression(), Compression::Gzip);
+        editor.set_cell(1, 1, "x".to_string()).unwrap();
+        editor.save().unwrap();
+
+        let mut saved = String::new();
+        flate2::read::GzDecoder::new(std::fs::File::open(&path).unwrap())
+            .read_to_string(&mut saved)
+            .unwrap();
+        assert_eq!(saved, "a,b\n1,2\n3,x\n");
+        assert_eq!(editor.get_row(1).unwrap(), vec!["3", "x"]);
+    }
+
+    #[test]
+    fn save_with_full_durability() {
+        let f = make_csv("a\n1\n");
+        let path = f.path().to_path_buf();
+
+        let mut editor = CsvEditor::open(&path).unwrap();
+        editor.set_cell(0, 0, "2".to_string()).unwrap();
+        let options = SaveOptions {
+            durability: Durability::Full,
+            ..Default::default()
+        };
+        editor.save_with_options(&options).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "a\n2\n");
+    }
+
     #[test]
     fn multiple_edits_save() {
         let f = make_csv("x\na\nb\nc\nd\n");
@@ -233,4 +2216,107 @@ mod tests {
         assert_eq!(editor.get_row(2).unwrap(), vec!["C"]);
         assert_eq!(editor.get_row(3).unwrap(), vec!["D"]);
     }
+
+    #[test]
+    fn undo_and_redo_changes() {
+        let f = make_csv("id,name\n1,a\n2,b\n3,c\n");
+        let mut editor = CsvEditor::open(f.path()).unwrap();
+        let row = |s: &str| s.split(',').map(String::from).collect::<Vec<String>>();
+        assert!(!editor.can_undo());
+        assert!(!editor.undo().unwrap());
+
+        editor.set_cell(0, 1, "A".to_string()).unwrap();
+        editor.set_cell(0, 1, "AA".to_string()).unwrap();
+        editor.insert_rows(1, vec![row("9,z")]).unwrap();
+        editor.add_column("note", "-").unwrap();
+        editor.delete_rows(2..3).unwrap();
+        assert_eq!(editor.row_count(), 3);
+
+        assert!(editor.undo().unwrap());
+        assert_eq!(editor.get_row(2).unwrap(), row("2,b,-"));
+        assert!(editor.undo().unwrap());
+        assert_eq!(editor.headers(), row("id,name"));
+        assert_eq!(editor.get_row(0).unwrap(), row("1,AA"));
+        assert!(editor.undo().unwrap());
+        assert_eq!(editor.row_count(), 3);
+        assert_eq!(editor.get_row(1).unwrap(), row("2,b"));
+        assert!(editor.undo().unwrap());
+        assert_eq!(editor.get_row(0).unwrap(), row("1,A"));
+        assert!(editor.can_redo());
+
+        assert!(editor.redo().unwrap());
+        assert!(editor.redo().unwrap());
+        assert!(editor.redo().unwrap());
+        assert_eq!(editor.get_row(1).unwrap(), row("9,z,-"));
+        assert_eq!(editor.get_row(0).unwrap(), row("1,AA,-"));
+
+        // A new change drops what was undone
+        editor.set_cell(3, 0, "3!".to_string()).unwrap();
+        assert!(!editor.can_redo());
+
+        editor.revert_all();
+        assert!(!editor.has_changes());
+        assert!(editor.undo().unwrap());
+        assert_eq!(editor.get_row(3).unwrap(), row("3!,c,-"));
+
+        editor.save().unwrap();
+        assert!(!editor.can_undo());
+        assert_eq!(editor.get_row(0).unwrap(), row("1,AA,-"));
+    }
+
+    #[test]
+    fn undo_a_batch_of_cell_edits_at_once() {
+        let f = make_csv("id,name\n1,a\n2,b\n");
+        let mut editor = CsvEditor::open(f.path()).unwrap();
+        let edits = [CellEdit::new(0, "name", "x"), CellEdit::new(1, "name", "y")];
+        assert_eq!(editor.apply_cell_edits(&edits).unwrap(), 2);
+        assert!(editor.undo().unwrap());
+        assert!(!editor.has_changes());
+        assert!(!editor.can_undo());
+    }
+
+    #[test]
+    fn lists_edited_rows_and_cells() {
+        let f = make_csv("id,name\n1,a\n2,b\n3,c\n");
+        let mut editor = CsvEditor::open(f.path()).unwrap();
+        let row = |s: &str| s.split(',').map(String::from).collect::<Vec<String>>();
+        editor.set_cell(2, 1, "C".to_string()).unwrap();
+        editor.set_cell(0, 1, "a".to_string()).unwrap();
+        assert_eq!(editor.edited_rows(), vec![0, 2]);
+
+        editor.insert_rows(0, vec![row("0,new")]).unwrap();
+        assert_eq!(editor.edited_rows(), vec![0, 1, 3]);
+
+        let diff = editor.row_diff(3).unwrap();
+        assert_eq!(diff.original, Some(row("3,c")));
+        assert_eq!(diff.current, row("3,C"));
+        assert_eq!(diff.changed, vec![1]);
+        assert!(editor.is_cell_edited(3, 1).unwrap());
+        assert!(!editor.is_cell_edited(3, 0).unwrap());
+        // Set to the value it had
+        assert!(!editor.is_cell_edited(1, 1).unwrap());
+        assert!(!editor.is_cell_edited(2, 1).unwrap());
+
+        let inserted = editor.row_diff(0).unwrap();
+        assert_eq!((inserted.original, inserted.changed), (None, vec![0, 1]));
+        assert!(editor.row_diff(2).unwrap().changed.is_empty());
+        assert!(editor.row_diff(9).is_err());
+    }
+
+    #[test]
+    fn reports_memory_usage() {
+        let f = make_csv("id,name\n1,a\n2,b\n");
+        let mut editor = CsvEditor::open(f.path()).unwrap();
+        let usage = editor.memory_usage();
+        assert_eq!(usage.mapped, 16);
+        assert!(usage.index >= 2 * 8);
+        assert_eq!(usage.edits, 0);
+
+        editor.set_cell(0, 1, "a longer name".to_string()).unwrap();
+        let edited = editor.memory_usage().edits;
+        assert!(edited >= "1".len() + "a longer name".len());
+        editor.set_cell(0, 1, "again".to_string()).unwrap();
+        // The replaced edit is kept for undo
+        assert!(editor.memory_usage().edits > edited);
+    }
 }
@@ -0,0 +1,116 @@
+//! Batches of cell edits listed in a file, for scripted corrections applied
+//! with [`CsvEditor::apply_cell_edits`](crate::CsvEditor::apply_cell_edits).
+
+use std::path::Path;
+
+use crate::error::{MassiveCsvError, Result};
+use crate::parser::column_position;
+use crate::reader::CsvReader;
+
+/// Set the cell at `row`, `column` to `value`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+pub struct CellEdit {
+    pub row: usize,
+    /// A column name, or a 0-based index if no column has that name.
+    pub column: String,
+    pub value: String,
+}
+
+impl CellEdit {
+    pub fn new(row: usize, column: impl Into<String>, value: impl Into<String>) -> Self {
+        Self {
+            row,
+            column: column.into(),
+            value: value.into(),
+        }
+    }
+
+    /// Position of the edit's column in `headers`.
+    pub fn column_index(&self, headers: &[String]) -> Result<usize> {
+        column_position(headers, &self.column)
+            .or_else(|| {
+                self.column
+                    .trim()
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|&i| i < headers.len())
+            })
+            .ok_or_else(|| MassiveCsvError::ColumnNotFound(self.column.clone()))
+    }
+}
+
+/// Read cell edits from a CSV file with `row`, `column` and `value`
+/// columns (in any order, alongside any others), one edit per row.
+pub fn read_cell_edits(path: &Path) -> Result<Vec<CellEdit>> {
+    let reader = CsvReader::open(path)?;
+    let find = |name: &str| {
+        reader.column_index(name).ok_or_else(|| {
+            MassiveCsvError::Parse(format!(
+                "edit file has no '{name}' column (expected row,column,value)"
+            ))
+        })
+    };
+    let (row_col, column_col, value_col) = (find("row")?, find("column")?, find("value")?);
+    (0..reader.row_count())
+        .map(|i| {
+            let fields = reader.get_row(i)?;
+            let field = |col: usize| fields.get(col).cloned().unwrap_or_default();
+            let row = field(row_col);
+            let row = row.trim().parse().map_err(|_| {
+                MassiveCsvError::Parse(format!("edit file row {i}: '{row}' is not a row number"))
+            })?;
+            Ok(CellEdit::new(row, field(column_col), field(value_col)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CsvEditor;
+    use std::io::Write;
+
+    fn make_csv(content: &str) -> tempfile::NamedTempFile {
+        let mut f = tempfile::NamedTempFile::new().unwrap();
+        f.write_all(content.as_bytes()).unwrap();
+        f.flush().unwrap();
+        f
+    }
+
+    #[test]
+    fn reads_and_applies_an_edit_file() {
+        let edits = make_csv("value,row,column\nfixed,1,status\n\"a, b\",0,1\nok,1,status\n");
+        let edits = read_cell_edits(edits.path()).unwrap();
+        assert_eq!(edits[1], CellEdit::new(0, "1", "a, b"));
+
+        let f = make_csv("id,note,status\n1,x,ok\n2,y,bad\n3,z,ok\n");
+        let mut editor = CsvEditor::open(f.path()).unwrap();
+        // The later edit to row 1's status wins
+        assert_eq!(editor.apply_cell_edits(&edits).unwrap(), 2);
+        assert_eq!(editor.get_row(0).unwrap(), vec!["1", "a, b", "ok"]);
+        assert_eq!(editor.get_row(1).unwrap(), vec!["2", "y", "ok"]);
+
+        // One bad entry and nothing changes
+        let mut editor = CsvEditor::open(f.path()).unwrap();
+        let bad = vec![
+            CellEdit::new(0, "note", "new"),
+            CellEdit::new(9, "note", "x"),
+        ];
+        assert!(matches!(
+            editor.apply_cell_edits(&bad),
+            Err(MassiveCsvError::RowOutOfRange(9, 3))
+        ));
+        let bad = vec![
+            CellEdit::new(0, "note", "new"),
+            CellEdit::new(1, "nope", "x"),
+        ];
+        assert!(editor.apply_cell_edits(&bad).is_err());
+        assert!(!editor.has_changes());
+
+        let missing = make_csv("row,value\n0,x\n");
+        assert!(read_cell_edits(missing.path()).is_err());
+        let not_a_row = make_csv("row,column,value\nfirst,id,x\n");
+        assert!(read_cell_edits(not_a_row.path()).is_err());
+    }
+}
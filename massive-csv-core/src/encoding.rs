@@ -0,0 +1,238 @@
+use std::fmt;
+use std::fs::File;
+
+use crate::error::{MassiveCsvError, Result};
+
+/// Bytes examined by [`Encoding::detect`] when there is no byte order mark.
+const DETECT_SAMPLE: usize = 64 * 1024;
+
+/// Text encoding of a file.
+///
+/// Files that are not UTF-8 are transcoded to UTF-8 when opened, so rows,
+/// search and filters always see UTF-8 text; saving encodes back to the
+/// file's encoding unless [`SaveOptions::encoding`](crate::SaveOptions::encoding)
+/// asks for another. Decoding and Windows-1252 output need the `encodings`
+/// feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    /// Windows-1252, the superset of Latin-1 (ISO-8859-1) used by Excel and
+    /// most Western European exports.
+    Windows1252,
+    Utf16Le,
+    Utf16Be,
+}
+
+impl Encoding {
+    pub fn name(self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Windows1252 => "Windows-1252",
+            Encoding::Utf16Le => "UTF-16LE",
+            Encoding::Utf16Be => "UTF-16BE",
+        }
+    }
+
+    /// Parse an encoding name such as `utf-8`, `latin1`, `cp1252` or `utf-16le`.
+    /// Plain `utf-16` means little-endian.
+    pub fn from_label(label: &str) -> Option<Self> {
+        match label.to_ascii_lowercase().replace('_', "-").as_str() {
+            "utf-8" | "utf8" => Some(Encoding::Utf8),
+            "windows-1252" | "cp1252" | "latin1" | "latin-1" | "iso-8859-1" => {
+                Some(Encoding::Windows1252)
+            }
+            "utf-16" | "utf16" | "utf-16le" | "utf16le" => Some(Encoding::Utf16Le),
+            "utf-16be" | "utf16be" => Some(Encoding::Utf16Be),
+            _ => None,
+        }
+    }
+
+    /// Guess the encoding of `data`.
+    ///
+    /// A byte order mark decides. Without one, text where every other byte is
+    /// zero is taken as UTF-16. Otherwise the first 64 KB is Windows-1252 if
+    /// it has more accented letters that are invalid UTF-8 than valid
+    /// multi-byte characters, so UTF-8 files with a few corrupt bytes stay
+    /// UTF-8 and those bytes are still reported by validation.
+    pub fn detect(data: &[u8]) -> Self {
+        match data {
+            [0xFF, 0xFE, ..] => return Encoding::Utf16Le,
+            [0xFE, 0xFF, ..] => return Encoding::Utf16Be,
+            [0xEF, 0xBB, 0xBF, ..] => return Encoding::Utf8,
+            _ => {}
+        }
+
+        let sample = &data[..data.len().min(DETECT_SAMPLE)];
+        let pairs = sample.len() / 2;
+        if pairs >= 2 {
+            let zero_even = sample.iter().step_by(2).filter(|&&b| b == 0).count();
+            let zero_odd = sample
+                .iter()
+                .skip(1)
+                .step_by(2)
+                .filter(|&&b| b == 0)
+                .count();
+            if zero_odd * 2 > pairs && zero_even * 10 < pairs {
+                return Encoding::Utf16Le;
+            }
+            if zero_even * 2 > pairs && zero_odd * 10 < pairs {
+                return Encoding::Utf16Be;
+            }
+        }
+
+        // Latin-1 text shows up as invalid UTF-8 bytes inside words, like the
+        // 0xE9 in "caf\xE9"; a lone bad byte between delimiters is corruption
+        let mut multibyte = 0;
+        let mut accented = 0;
+        let mut offset = 0;
+        for chunk in sample.utf8_chunks() {
+            multibyte += chunk.valid().chars().filter(|c| !c.is_ascii()).count();
+            offset += chunk.valid().len();
+            for (i, &b) in chunk.invalid().iter().enumerate() {
+                let at = offset + i;
+                let letter_before = at > 0 && sample[at - 1].is_ascii_alphabetic();
+                let letter_after = sample.get(at + 1).is_some_and(u8::is_ascii_alphabetic);
+                if b >= 0xC0 && (letter_before || letter_after) {
+                    accented += 1;
+                }
+            }
+            offset += chunk.invalid().len();
+        }
+        if accented > multibyte {
+            Encoding::Windows1252
+        } else {
+            Encoding::Utf8
+        }
+    }
+
+    /// Byte order mark written at the start of a saved file.
+    pub(crate) fn bom(self) -> &'static [u8] {
+        match self {
+            Encoding::Utf16Le => &[0xFF, 0xFE],
+            Encoding::Utf16Be => &[0xFE, 0xFF],
+            Encoding::Utf8 | Encoding::Windows1252 => &[],
+        }
+    }
+
+    /// Append `text` to `out` in this encoding.
+    pub(crate) fn encode(self, text: &str, out: &mut Vec<u8>) -> Result<()> {
+        match self {
+            Encoding::Utf8 => out.extend_from_slice(text.as_bytes()),
+            Encoding::Utf16Le => out.extend(text.encode_utf16().flat_map(u16::to_le_bytes)),
+            Encoding::Utf16Be => out.extend(text.encode_utf16().flat_map(u16::to_be_bytes)),
+            Encoding::Windows1252 => encode_windows_1252(text, out)?,
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[cfg(feature = "encodings")]
+impl Encoding {
+    fn to_encoding_rs(self) -> &'static encoding_rs::Encoding {
+        match self {
+            Encoding::Utf8 => encoding_rs::UTF_8,
+            Encoding::Windows1252 => encoding_rs::WINDOWS_1252,
+            Encoding::Utf16Le => encoding_rs::UTF_16LE,
+            Encoding::Utf16Be => encoding_rs::UTF_16BE,
+        }
+    }
+}
+
+/// Decode `data` into an anonymous temporary file of UTF-8, dropping any byte
+/// order mark. Malformed input becomes U+FFFD.
+#[cfg(feature = "encodings")]
+pub(crate) fn decode_to_tempfile(data: &[u8], encoding: Encoding) -> Result<File> {
+    use std::io::Write;
+
+    let mut decoder = encoding.to_encoding_rs().new_decoder_with_bom_removal();
+    let mut out = std::io::BufWriter::new(tempfile::tempfile()?);
+    let mut buf = String::with_capacity(1024 * 1024);
+    let mut input = data;
+    loop {
+        buf.clear();
+        let (result, read, _) = decoder.decode_to_string(input, &mut buf, true);
+        out.write_all(buf.as_bytes())?;
+        input = &input[read..];
+        if let encoding_rs::CoderResult::InputEmpty = result {
+            break;
+        }
+    }
+    out.into_inner().map_err(|e| e.into_error().into())
+}
+
+#[cfg(not(feature = "encodings"))]
+pub(crate) fn decode_to_tempfile(_data: &[u8], encoding: Encoding) -> Result<File> {
+    Err(MassiveCsvError::FeatureDisabled(
+        match encoding {
+            Encoding::Windows1252 => "Reading Windows-1252",
+            _ => "Reading UTF-16",
+        },
+        "encodings",
+    ))
+}
+
+#[cfg(feature = "encodings")]
+fn encode_windows_1252(text: &str, out: &mut Vec<u8>) -> Result<()> {
+    let (bytes, _, unmappable) = encoding_rs::WINDOWS_1252.encode(text);
+    if unmappable {
+        let c = text
+            .chars()
+            .find(|c| {
+                encoding_rs::WINDOWS_1252
+                    .encode(c.encode_utf8(&mut [0; 4]))
+                    .2
+            })
+            .unwrap_or(char::REPLACEMENT_CHARACTER);
+        return Err(MassiveCsvError::Unencodable(
+            c,
+            Encoding::Windows1252.name(),
+        ));
+    }
+    out.extend_from_slice(&bytes);
+    Ok(())
+}
+
+#[cfg(not(feature = "encodings"))]
+fn encode_windows_1252(_text: &str, _out: &mut Vec<u8>) -> Result<()> {
+    Err(MassiveCsvError::FeatureDisabled(
+        "Writing Windows-1252",
+        "encodings",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_bom_utf16_and_latin1() {
+        assert_eq!(Encoding::detect(b"\xFF\xFEa\0,\0"), Encoding::Utf16Le);
+        assert_eq!(Encoding::detect(b"\xFE\xFF\0a\0,"), Encoding::Utf16Be);
+        assert_eq!(Encoding::detect(b"\xEF\xBB\xBFa,b\n"), Encoding::Utf8);
+        assert_eq!(Encoding::detect(b"n\0a\0m\0e\0\n\0"), Encoding::Utf16Le);
+        assert_eq!(Encoding::detect(b"\0n\0a\0m\0e\0\n"), Encoding::Utf16Be);
+        assert_eq!(Encoding::detect(b"name\ncaf\xe9\n"), Encoding::Windows1252);
+        assert_eq!(Encoding::detect("name\ncafé\n".as_bytes()), Encoding::Utf8);
+        // Mostly valid UTF-8 with one broken byte stays UTF-8
+        assert_eq!(
+            Encoding::detect(b"caf\xc3\xa9,na\xc3\xafve,\xe9t\xe9\n"),
+            Encoding::Utf8
+        );
+        assert_eq!(Encoding::detect(b"a,b\n\xff,2\n"), Encoding::Utf8);
+    }
+
+    #[test]
+    fn labels() {
+        assert_eq!(Encoding::from_label("Latin1"), Some(Encoding::Windows1252));
+        assert_eq!(Encoding::from_label("UTF_16"), Some(Encoding::Utf16Le));
+        assert_eq!(Encoding::from_label("utf-16be"), Some(Encoding::Utf16Be));
+        assert_eq!(Encoding::from_label("ebcdic"), None);
+    }
+}
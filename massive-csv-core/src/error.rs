@@ -10,6 +10,10 @@ pub enum MassiveCsvError {
     #[error("CSV parse error: {0}")]
     Csv(#[from] csv::Error),
 
+    #[cfg(any(feature = "pipelines", feature = "views"))]
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+
     #[error("Parse error: {0}")]
     Parse(String),
 
@@ -24,4 +28,80 @@ pub enum MassiveCsvError {
 
     #[error("Invalid UTF-8 at byte position {0}")]
     InvalidUtf8(usize),
+
+    #[error("File was modified on disk since it was opened: {0}")]
+    ExternallyModified(String),
+
+    #[error("Unrecovered edit journal exists: {0}")]
+    JournalExists(String),
+
+    #[error("Version not found: {0}")]
+    VersionNotFound(String),
+
+    #[error("Header mismatch: {0}")]
+    HeaderMismatch(String),
+
+    #[error("Duplicate key: {0}")]
+    DuplicateKey(String),
+
+    #[error("Invalid patch: {0}")]
+    InvalidPatch(String),
+
+    #[error("Patch conflict: {0}")]
+    PatchConflict(String),
+
+    #[error("Invalid pipeline spec: {0}")]
+    InvalidSpec(String),
+
+    #[error("Script error: {0}")]
+    Script(String),
+
+    #[error("Plugin error: {0}")]
+    Plugin(String),
+
+    #[error("Invalid filter expression: {0}")]
+    InvalidFilter(String),
+
+    #[error("Invalid schema: {0}")]
+    InvalidSchema(String),
+
+    #[error("Schema violation: {0}")]
+    SchemaViolation(String),
+
+    #[error("Invalid query: {0}")]
+    InvalidQuery(String),
+
+    #[error("Background build failed: {0}")]
+    Background(String),
+
+    #[error("Invalid open options: {0}")]
+    InvalidOptions(String),
+
+    #[error("Remote file error: {0}")]
+    Remote(String),
+
+    #[error("Excel export error: {0}")]
+    Xlsx(String),
+
+    #[error("Character {0:?} cannot be written as {1}")]
+    Unencodable(char, &'static str),
+
+    #[error("{0} requires the `{1}` feature")]
+    FeatureDisabled(&'static str, &'static str),
+
+    #[error("Cancelled")]
+    Cancelled,
+}
+
+impl From<massive_csv_parser::Error> for MassiveCsvError {
+    fn from(e: massive_csv_parser::Error) -> Self {
+        match e {
+            massive_csv_parser::Error::Csv(e) => MassiveCsvError::Csv(e),
+            massive_csv_parser::Error::Empty => MassiveCsvError::EmptyFile,
+            massive_csv_parser::Error::InvalidUtf8(pos) => MassiveCsvError::InvalidUtf8(pos),
+            e @ massive_csv_parser::Error::FieldTooWide { .. } => {
+                MassiveCsvError::Parse(e.to_string())
+            }
+        }
+    }
 }
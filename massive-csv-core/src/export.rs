@@ -0,0 +1,880 @@
+use std::borrow::Cow;
+use std::io::Write;
+use std::ops::Range;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rayon::prelude::*;
+
+use crate::background::Progress;
+use crate::compression::{Compression, Compressor};
+use crate::editor::{write_atomic, LineWriter};
+use crate::error::{MassiveCsvError, Result};
+use crate::filter::{Expr, Filter};
+use crate::infer::{sample_row_numbers, TypeInference, DEFAULT_SAMPLE_ROWS};
+#[cfg(feature = "scripting")]
+use crate::parser::serialize_row_preserving;
+use crate::parser::{serialize_row, LineEnding};
+use crate::reader::CsvReader;
+#[cfg(feature = "scripting")]
+use crate::script::RowScript;
+use crate::sqlite::SqliteWriter;
+use crate::view::select_columns;
+
+/// Rows processed per parallel batch when a script is run.
+const CHUNK_ROWS: usize = 64 * 1024;
+
+/// Options controlling [`export`].
+#[derive(Debug, Clone, Default)]
+pub struct ExportOptions {
+    /// Append columns recording where each row came from.
+    pub provenance: Option<Provenance>,
+    /// Export only rows matching this filter, checked against the source row
+    /// before any script runs.
+    pub filter: Option<Expr>,
+    /// Rhai row script to transform and filter rows (see [`crate::script::RowScript`]).
+    pub script: Option<String>,
+    /// Compress the output (default: plain text).
+    pub compression: Compression,
+    /// Output format (default: CSV).
+    pub format: ExportFormat,
+    /// Export only these rows (default: all). Clamped to the file.
+    pub rows: Option<Range<usize>>,
+    /// Export only these columns, in this order (default: all): names,
+    /// indices or ranges, as in [`select_columns`]. They refer to the
+    /// script's output columns when there is a script; provenance columns
+    /// are always added.
+    pub columns: Option<Vec<String>>,
+    /// Table name for [`ExportFormat::Sqlite`] (default: the output file's
+    /// name up to its first `.`).
+    pub table: Option<String>,
+    /// Told how many rows have been processed as they are exported.
+    pub progress: Progress,
+}
+
+/// What [`export`] writes.
+///
+/// The JSON formats write each row as an object keyed by column name, with
+/// every value a string. JSON and SQLite name duplicate or empty headers by
+/// their canonical names (see [`CsvReader::canonical_headers`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExportFormat {
+    /// The source's dialect; unchanged rows are copied verbatim.
+    #[default]
+    Csv,
+    /// One JSON array of row objects.
+    Json,
+    /// JSON Lines: one row object per line.
+    Ndjson,
+    /// A SQLite database holding one table. Column types are inferred from
+    /// a sample of the exported rows (see [`crate::infer`]).
+    Sqlite,
+    /// An Excel workbook, split across sheets past Excel's row limit. Needs
+    /// the `xlsx` feature.
+    Xlsx,
+}
+
+impl ExportFormat {
+    /// The format called `name`: `csv`, `json`, `ndjson`, `sqlite` or `xlsx`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "csv" => Some(ExportFormat::Csv),
+            "json" => Some(ExportFormat::Json),
+            "ndjson" | "jsonl" => Some(ExportFormat::Ndjson),
+            "sqlite" => Some(ExportFormat::Sqlite),
+            "xlsx" => Some(ExportFormat::Xlsx),
+            _ => None,
+        }
+    }
+
+    /// The format a file name asks for: `.json`, `.ndjson` or `.jsonl`
+    /// (before any `.gz` or `.zst`), `.sqlite`, `.sqlite3` or `.db`, `.xlsx`,
+    /// else CSV.
+    pub fn for_path(path: &Path) -> Self {
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_ascii_lowercase())
+            .unwrap_or_default();
+        if crate::ndjson::is_ndjson_path(path) {
+            ExportFormat::Ndjson
+        } else if [".json", ".json.gz", ".json.zst"]
+            .iter()
+            .any(|ext| name.ends_with(ext))
+        {
+            ExportFormat::Json
+        } else if [".sqlite", ".sqlite3", ".db"]
+            .iter()
+            .any(|ext| name.ends_with(ext))
+        {
+            ExportFormat::Sqlite
+        } else if name.ends_with(".xlsx") {
+            ExportFormat::Xlsx
+        } else {
+            ExportFormat::Csv
+        }
+    }
+}
+
+/// Provenance columns appended to every exported row.
+///
+/// Each column is only added if its name is set.
+#[derive(Debug, Clone)]
+pub struct Provenance {
+    /// Column holding the source file name.
+    pub source_column: Option<String>,
+    /// Column holding the 0-indexed data row number in the source file.
+    pub row_column: Option<String>,
+    /// Column holding the export time (RFC 3339, UTC).
+    pub timestamp_column: Option<String>,
+    /// Value written to `source_column` (default: the source file name).
+    pub source_name: Option<String>,
+}
+
+impl Default for Provenance {
+    fn default() -> Self {
+        Self {
+            source_column: Some("_source_file".to_string()),
+            row_column: Some("_source_row".to_string()),
+            timestamp_column: Some("_ingested_at".to_string()),
+            source_name: None,
+        }
+    }
+}
+
+/// Counts of what [`export`] wrote.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExportSummary {
+    pub rows_written: usize,
+    /// Rows dropped by the filter or the script.
+    pub rows_filtered: usize,
+}
+
+/// Export the file behind `reader` to `output`, in any [`ExportFormat`].
+///
+/// Rows are streamed from the memory map; when exporting every column as
+/// CSV, unchanged rows are copied verbatim.
+pub fn export(reader: &CsvReader, output: &Path, options: &ExportOptions) -> Result<ExportSummary> {
+    export_into(reader, Target::File(output), options)
+}
+
+/// [`export`] to `writer` instead of a file, e.g. stdout. SQLite tables
+/// are named `data` unless [`ExportOptions::table`] is set.
+pub fn export_to_writer(
+    reader: &CsvReader,
+    writer: &mut dyn Write,
+    options: &ExportOptions,
+) -> Result<ExportSummary> {
+    export_into(reader, Target::Writer(writer), options)
+}
+
+/// Where [`export_into`] writes.
+enum Target<'a> {
+    /// A file, replaced atomically.
+    File(&'a Path),
+    Writer(&'a mut dyn Write),
+}
+
+impl Target<'_> {
+    fn path(&self) -> Option<&Path> {
+        match self {
+            Target::File(path) => Some(path),
+            Target::Writer(_) => None,
+        }
+    }
+
+    fn write(self, write: impl FnOnce(&mut dyn Write) -> Result<()>) -> Result<()> {
+        match self {
+            Target::File(path) => write_atomic(path, write),
+            Target::Writer(writer) => {
+                write(writer)?;
+                writer.flush()?;
+                Ok(())
+            }
+        }
+    }
+}
+
+fn export_into(
+    reader: &CsvReader,
+    output: Target,
+    options: &ExportOptions,
+) -> Result<ExportSummary> {
+    let dialect = reader.dialect();
+    let delimiter_str = (dialect.delimiter as char).to_string();
+
+    // Provenance values that are the same for every row
+    let (extra_headers, source, timestamp) = match &options.provenance {
+        Some(p) => {
+            let source = p.source_column.as_ref().map(|_| {
+                p.source_name.clone().unwrap_or_else(|| {
+                    reader
+                        .path()
+                        .file_name()
+                        .map(|n| n.to_string_lossy().into_owned())
+                        .unwrap_or_default()
+                })
+            });
+            let timestamp = p
+                .timestamp_column
+                .as_ref()
+                .map(|_| format_rfc3339(SystemTime::now()));
+            let headers: Vec<String> = [&p.source_column, &p.row_column, &p.timestamp_column]
+                .into_iter()
+                .flatten()
+                .cloned()
+                .collect();
+            (headers, source, timestamp)
+        }
+        None => (vec![], None, None),
+    };
+    let with_row = options
+        .provenance
+        .as_ref()
+        .is_some_and(|p| p.row_column.is_some());
+
+    let filter = match &options.filter {
+        Some(expr) => Some(Filter::new(expr.clone(), reader.headers())?),
+        None => None,
+    };
+    let script = match &options.script {
+        Some(source) => Some(compile_script(source, reader.headers())?),
+        None => None,
+    };
+    let mut headers = match &script {
+        Some(script) => script_columns(script),
+        None => reader.headers().to_vec(),
+    };
+    // Positions of the selected columns in `headers`
+    let selected = match &options.columns {
+        Some(columns) => Some(select_columns(&headers, columns)?),
+        None => None,
+    };
+    if script.is_none() && options.format != ExportFormat::Csv {
+        headers = reader.canonical_headers().to_vec();
+    }
+    if let Some(selected) = &selected {
+        headers = selected.iter().map(|&i| headers[i].clone()).collect();
+    }
+    headers.extend(extra_headers.iter().cloned());
+
+    // Copying raw lines only works when every column is kept as CSV
+    let by_field = options.format != ExportFormat::Csv || selected.is_some();
+    let rows = options.rows.clone().unwrap_or(0..usize::MAX);
+    let (first_row, row_count) = (
+        rows.start.min(reader.row_count()),
+        rows.end.min(reader.row_count()),
+    );
+    let mut summary = ExportSummary::default();
+
+    // A row's output fields, or None if the filter or script dropped it
+    let fields_for = |i: usize| -> Result<Option<Vec<String>>> {
+        let row = reader.get_row(i)?;
+        if filter.as_ref().is_some_and(|filter| !filter.matches(&row)) {
+            return Ok(None);
+        }
+        let fields = match &script {
+            Some(script) => run_script_fields(script, row)?,
+            None => Some(row),
+        };
+        Ok(fields.map(|fields| {
+            let mut fields = match &selected {
+                Some(selected) => selected
+                    .iter()
+                    .map(|&c| fields.get(c).cloned().unwrap_or_default())
+                    .collect(),
+                None => fields,
+            };
+            fields.extend(source.iter().cloned());
+            if with_row {
+                fields.push(i.to_string());
+            }
+            fields.extend(timestamp.iter().cloned());
+            fields
+        }))
+    };
+
+    if options.format == ExportFormat::Sqlite {
+        let table = match &options.table {
+            Some(table) => table.clone(),
+            None => output
+                .path()
+                .and_then(Path::file_name)
+                .map(|n| n.to_string_lossy())
+                .and_then(|n| n.split('.').next().map(str::to_string))
+                .filter(|n| !n.is_empty())
+                .unwrap_or_else(|| "data".to_string()),
+        };
+        let inference = sample_row_numbers(row_count - first_row, DEFAULT_SAMPLE_ROWS)
+            .into_par_iter()
+            .try_fold(TypeInference::new, |mut inference, i| {
+                if let Some(fields) = fields_for(first_row + i)? {
+                    inference.observe(&fields);
+                }
+                Ok::<_, MassiveCsvError>(inference)
+            })
+            .try_reduce(TypeInference::new, |a, b| Ok(a.merge(b)))?;
+        let columns: Vec<_> = inference
+            .finish(&headers)
+            .into_iter()
+            .map(|c| (c.name, c.column_type))
+            .collect();
+
+        let mut database = SqliteWriter::new(&table, &columns)?;
+        summary.rows_filtered = for_each_row(
+            first_row..row_count,
+            &fields_for,
+            &options.progress,
+            &mut |fields| database.insert(&fields),
+        )?;
+        summary.rows_written = database.row_count() as usize;
+        output.write(|writer| {
+            let mut compressor = Compressor::new(writer, options.compression)?;
+            database.finish(&mut compressor)?;
+            compressor.finish()
+        })?;
+        return Ok(summary);
+    }
+    if options.format == ExportFormat::Xlsx {
+        return export_xlsx(output, options, &headers, first_row..row_count, &fields_for);
+    }
+
+    output.write(|writer| {
+        let mut compressor = Compressor::new(writer, options.compression)?;
+        let line_ending = match options.format {
+            ExportFormat::Csv => reader.line_ending(),
+            _ => LineEnding::Lf,
+        };
+        let mut lines = LineWriter::new(&mut compressor, line_ending);
+        match options.format {
+            ExportFormat::Csv => lines.write_line(&serialize_row(&headers, dialect))?,
+            ExportFormat::Json => lines.write_line("[")?,
+            ExportFormat::Ndjson => {}
+            ExportFormat::Sqlite | ExportFormat::Xlsx => unreachable!("written above"),
+        }
+
+        let mut pending: Option<String> = None;
+        let mut start = first_row;
+        while start < row_count {
+            options
+                .progress
+                .update(start - first_row, row_count - first_row)?;
+            let end = (start + CHUNK_ROWS).min(row_count);
+            if by_field {
+                let rows: Vec<Option<Vec<String>>> = (start..end)
+                    .into_par_iter()
+                    .map(fields_for)
+                    .collect::<Result<_>>()?;
+                for fields in rows {
+                    let Some(fields) = fields else {
+                        summary.rows_filtered += 1;
+                        continue;
+                    };
+                    match options.format {
+                        ExportFormat::Csv => lines.write_line(&serialize_row(&fields, dialect))?,
+                        ExportFormat::Ndjson => {
+                            lines.write_line(&json_object(&headers, &fields))?
+                        }
+                        // Held back a row to know whether it needs a comma
+                        ExportFormat::Json => {
+                            if let Some(previous) = pending.replace(json_object(&headers, &fields))
+                            {
+                                lines.write_line(&format!("{previous},"))?;
+                            }
+                        }
+                        ExportFormat::Sqlite | ExportFormat::Xlsx => {
+                            unreachable!("written above")
+                        }
+                    }
+                    summary.rows_written += 1;
+                }
+                start = end;
+                continue;
+            }
+
+            // None = dropped by the filter or script
+            let rows: Vec<Option<Cow<str>>> = if script.is_none() && filter.is_none() {
+                (start..end)
+                    .map(|i| Ok(Some(Cow::Borrowed(reader.get_row_raw(i)?))))
+                    .collect::<Result<_>>()?
+            } else {
+                (start..end)
+                    .into_par_iter()
+                    .map(|i| {
+                        if let Some(filter) = &filter {
+                            if !filter.matches(&reader.get_row(i)?) {
+                                return Ok(None);
+                            }
+                        }
+                        match &script {
+                            Some(script) => run_script(script, reader, i),
+                            None => Ok(Some(Cow::Borrowed(reader.get_row_raw(i)?))),
+                        }
+                    })
+                    .collect::<Result<_>>()?
+            };
+
+            for (i, raw) in (start..end).zip(rows) {
+                let Some(raw) = raw else {
+                    summary.rows_filtered += 1;
+                    continue;
+                };
+                if extra_headers.is_empty() {
+                    lines.write_line(&raw)?;
+                } else {
+                    let mut extra = Vec::with_capacity(3);
+                    extra.extend(source.iter().cloned());
+                    if with_row {
+                        extra.push(i.to_string());
+                    }
+                    extra.extend(timestamp.iter().cloned());
+
+                    let mut line = String::with_capacity(raw.len() + 64);
+                    line.push_str(&raw);
+                    line.push_str(&delimiter_str);
+                    line.push_str(&serialize_row(&extra, dialect));
+                    lines.write_line(&line)?;
+                }
+                summary.rows_written += 1;
+            }
+            start = end;
+        }
+        options
+            .progress
+            .update(row_count - first_row, row_count - first_row)?;
+
+        if options.format == ExportFormat::Json {
+            if let Some(last) = pending {
+                lines.write_line(&last)?;
+            }
+            lines.write_line("]")?;
+        }
+        lines.finish(true)?;
+        compressor.finish()
+    })?;
+
+    Ok(summary)
+}
+
+/// Pass the output fields of each row in `rows` to `sink`, in order,
+/// returning the number of rows the script dropped.
+fn for_each_row(
+    rows: Range<usize>,
+    fields_for: &(dyn Fn(usize) -> Result<Option<Vec<String>>> + Sync),
+    progress: &Progress,
+    sink: &mut dyn FnMut(Vec<String>) -> Result<()>,
+) -> Result<usize> {
+    let mut filtered = 0;
+    let mut start = rows.start;
+    while start < rows.end {
+        let end = (start + CHUNK_ROWS).min(rows.end);
+        let chunk: Vec<Option<Vec<String>>> = (start..end)
+            .into_par_iter()
+            .map(fields_for)
+            .collect::<Result<_>>()?;
+        for fields in chunk {
+            match fields {
+                Some(fields) => sink(fields)?,
+                None => filtered += 1,
+            }
+        }
+        progress.update(end - rows.start, rows.len())?;
+        start = end;
+    }
+    Ok(filtered)
+}
+
+#[cfg(feature = "xlsx")]
+fn export_xlsx(
+    output: Target,
+    options: &ExportOptions,
+    headers: &[String],
+    rows: Range<usize>,
+    fields_for: &(dyn Fn(usize) -> Result<Option<Vec<String>>> + Sync),
+) -> Result<ExportSummary> {
+    let mut workbook = crate::xlsx::XlsxWriter::new(headers)?;
+    let rows_filtered = for_each_row(rows, fields_for, &options.progress, &mut |fields| {
+        workbook.write_row(&fields)
+    })?;
+    let rows_written = workbook.row_count() as usize;
+    output.write(|writer| {
+        let mut compressor = Compressor::new(writer, options.compression)?;
+        workbook.finish(&mut compressor)?;
+        compressor.finish()
+    })?;
+    Ok(ExportSummary {
+        rows_written,
+        rows_filtered,
+    })
+}
+
+#[cfg(not(feature = "xlsx"))]
+fn export_xlsx(
+    _: Target,
+    _: &ExportOptions,
+    _: &[String],
+    _: Range<usize>,
+    _: &(dyn Fn(usize) -> Result<Option<Vec<String>>> + Sync),
+) -> Result<ExportSummary> {
+    Err(MassiveCsvError::FeatureDisabled(
+        "Exporting to Excel",
+        "xlsx",
+    ))
+}
+
+/// A JSON object mapping each of `keys` to the field at the same position.
+fn json_object(keys: &[String], fields: &[String]) -> String {
+    let mut out = String::with_capacity(256);
+    out.push('{');
+    for (i, key) in keys.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        push_json_string(&mut out, key);
+        out.push(':');
+        push_json_string(&mut out, fields.get(i).map_or("", String::as_str));
+    }
+    out.push('}');
+    out
+}
+
+fn push_json_string(out: &mut String, s: &str) {
+    use std::fmt::Write;
+
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if u32::from(c) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", u32::from(c));
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(feature = "scripting")]
+type Script = RowScript;
+
+/// Stand-in when scripting is compiled out; never constructed.
+#[cfg(not(feature = "scripting"))]
+enum Script {}
+
+#[cfg(feature = "scripting")]
+fn compile_script(source: &str, headers: &[String]) -> Result<Script> {
+    RowScript::compile(source, headers)
+}
+
+#[cfg(not(feature = "scripting"))]
+fn compile_script(_: &str, _: &[String]) -> Result<Script> {
+    Err(MassiveCsvError::Script(
+        "scripting support is not enabled".to_string(),
+    ))
+}
+
+#[cfg(feature = "scripting")]
+fn script_columns(script: &Script) -> Vec<String> {
+    script.columns().to_vec()
+}
+
+#[cfg(not(feature = "scripting"))]
+fn script_columns(script: &Script) -> Vec<String> {
+    match *script {}
+}
+
+/// Run the script on row `i`, returning its output line (the raw line if unchanged).
+#[cfg(feature = "scripting")]
+fn run_script<'a>(
+    script: &Script,
+    reader: &'a CsvReader,
+    i: usize,
+) -> Result<Option<Cow<'a, str>>> {
+    let raw = reader.get_row_raw(i)?;
+    let fields = reader.get_row(i)?;
+    Ok(script
+        .apply(fields)?
+        .map(|out| Cow::Owned(serialize_row_preserving(&out, raw, reader.dialect()))))
+}
+
+#[cfg(not(feature = "scripting"))]
+fn run_script<'a>(script: &Script, _: &'a CsvReader, _: usize) -> Result<Option<Cow<'a, str>>> {
+    match *script {}
+}
+
+/// Run the script on a row's fields, returning its output fields.
+#[cfg(feature = "scripting")]
+fn run_script_fields(script: &Script, fields: Vec<String>) -> Result<Option<Vec<String>>> {
+    script.apply(fields)
+}
+
+#[cfg(not(feature = "scripting"))]
+fn run_script_fields(script: &Script, _: Vec<String>) -> Result<Option<Vec<String>>> {
+    match *script {}
+}
+
+/// Format a time as RFC 3339 in UTC with second precision (e.g. `2024-05-01T12:00:00Z`).
+pub fn format_rfc3339(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let (days, rem) = (secs / 86_400, secs % 86_400);
+    let (y, m, d) = civil_from_days(days as i64);
+    format!(
+        "{y:04}-{m:02}-{d:02}T{:02}:{:02}:{:02}Z",
+        rem / 3600,
+        (rem % 3600) / 60,
+        rem % 60
+    )
+}
+
+/// Convert days since 1970-01-01 to a (year, month, day) civil date.
+pub(crate) fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    // Howard Hinnant's days-to-civil algorithm
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let y = yoe + era * 400 + i64::from(m <= 2);
+    (y, m, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::time::Duration;
+
+    fn make_csv(content: &str) -> tempfile::NamedTempFile {
+        let mut f = tempfile::NamedTempFile::new().unwrap();
+        f.write_all(content.as_bytes()).unwrap();
+        f.flush().unwrap();
+        f
+    }
+
+    #[test]
+    fn export_with_provenance() {
+        let f = make_csv("id,name\n1,\"a, b\"\n2,c\n");
+        let reader = CsvReader::open(f.path()).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out.csv");
+
+        let options = ExportOptions {
+            provenance: Some(Provenance {
+                timestamp_column: None,
+                source_name: Some("shard-1.csv".to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let summary = export(&reader, &out, &options).unwrap();
+        assert_eq!(summary.rows_written, 2);
+
+        let written = std::fs::read_to_string(&out).unwrap();
+        assert_eq!(
+            written,
+            "id,name,_source_file,_source_row\n1,\"a, b\",shard-1.csv,0\n2,c,shard-1.csv,1\n"
+        );
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn export_compressed() {
+        let f = make_csv("id,name\n1,a\n2,b\n");
+        let reader = CsvReader::open(f.path()).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out.csv.zst");
+
+        let options = ExportOptions {
+            compression: Compression::Zstd,
+            ..Default::default()
+        };
+        export(&reader, &out, &options).unwrap();
+
+        let data = std::fs::read(&out).unwrap();
+        assert_eq!(zstd::decode_all(&data[..]).unwrap(), b"id,name\n1,a\n2,b\n");
+        let exported = CsvReader::open(&out).unwrap();
+        assert_eq!(exported.compression(), Compression::Zstd);
+        assert_eq!(exported.get_row(1).unwrap(), vec!["2", "b"]);
+    }
+
+    #[cfg(feature = "scripting")]
+    #[test]
+    fn export_with_script() {
+        let f = make_csv("sku,price,qty\n\"A1\",2,3\nB2,5,0\n");
+        let reader = CsvReader::open(f.path()).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out.csv");
+
+        let options = ExportOptions {
+            script: Some("row.total = row.price * row.qty; row.qty > 0".to_string()),
+            ..Default::default()
+        };
+        let summary = export(&reader, &out, &options).unwrap();
+        assert_eq!(summary.rows_written, 1);
+        assert_eq!(summary.rows_filtered, 1);
+        assert_eq!(
+            std::fs::read_to_string(&out).unwrap(),
+            "sku,price,qty,total\n\"A1\",2,3,6\n"
+        );
+    }
+
+    #[test]
+    fn export_to_a_writer() {
+        let f = make_csv("id,name\n1,a\n2,b\n");
+        let reader = CsvReader::open(f.path()).unwrap();
+
+        let options = ExportOptions {
+            format: ExportFormat::Ndjson,
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        let summary = export_to_writer(&reader, &mut out, &options).unwrap();
+        assert_eq!(summary.rows_written, 2);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "{\"id\":\"1\",\"name\":\"a\"}\n{\"id\":\"2\",\"name\":\"b\"}\n"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn export_writes_through_a_device() {
+        let f = make_csv("id\n1\n");
+        let reader = CsvReader::open(f.path()).unwrap();
+
+        export(&reader, Path::new("/dev/null"), &ExportOptions::default()).unwrap();
+        assert!(!std::fs::metadata("/dev/null").unwrap().is_file());
+    }
+
+    #[test]
+    fn export_json_formats() {
+        let f = make_csv("id,name,\n1,\"say \"\"hi\"\"\",x\n2,b,y\n3,c,z\n");
+        let reader = CsvReader::open(f.path()).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out.ndjson");
+
+        let options = ExportOptions {
+            format: ExportFormat::Ndjson,
+            rows: Some(0..2),
+            ..Default::default()
+        };
+        let summary = export(&reader, &out, &options).unwrap();
+        assert_eq!(summary.rows_written, 2);
+        assert_eq!(
+            std::fs::read_to_string(&out).unwrap(),
+            "{\"id\":\"1\",\"name\":\"say \\\"hi\\\"\",\"column_3\":\"x\"}\n\
+             {\"id\":\"2\",\"name\":\"b\",\"column_3\":\"y\"}\n"
+        );
+
+        let out = dir.path().join("out.json");
+        let options = ExportOptions {
+            format: ExportFormat::for_path(&out),
+            rows: Some(1..10),
+            columns: Some(vec!["name".to_string(), "id".to_string()]),
+            ..Default::default()
+        };
+        export(&reader, &out, &options).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&out).unwrap(),
+            "[\n{\"name\":\"b\",\"id\":\"2\"},\n{\"name\":\"c\",\"id\":\"3\"}\n]\n"
+        );
+
+        let options = ExportOptions {
+            columns: Some(vec!["missing".to_string()]),
+            ..Default::default()
+        };
+        assert!(matches!(
+            export(&reader, &out, &options),
+            Err(MassiveCsvError::ColumnNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn export_selected_columns_as_csv() {
+        let f = make_csv("a;b;c\n1;2;3\n4;5;6\n");
+        let reader = CsvReader::open(f.path()).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out.csv");
+        let options = ExportOptions {
+            columns: Some(vec!["c".to_string(), "a".to_string()]),
+            rows: Some(1..2),
+            ..Default::default()
+        };
+        export(&reader, &out, &options).unwrap();
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), "c;a\n6;4\n");
+    }
+
+    #[test]
+    fn export_filtered_rows() {
+        let f = make_csv("id,name,status\n1,a,active\n2,b,closed\n3,\"c, d\",active\n");
+        let reader = CsvReader::open(f.path()).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out.csv");
+
+        let mut options = ExportOptions {
+            filter: Some(Expr::parse("status=active").unwrap()),
+            ..Default::default()
+        };
+        let summary = export(&reader, &out, &options).unwrap();
+        assert_eq!((summary.rows_written, summary.rows_filtered), (2, 1));
+        assert_eq!(
+            std::fs::read_to_string(&out).unwrap(),
+            "id,name,status\n1,a,active\n3,\"c, d\",active\n"
+        );
+
+        options.columns = Some(vec!["id".to_string(), "name".to_string()]);
+        export(&reader, &out, &options).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&out).unwrap(),
+            "id,name\n1,a\n3,\"c, d\"\n"
+        );
+        options.columns = Some(vec!["2".to_string(), "1-0".to_string()]);
+        export(&reader, &out, &options).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&out).unwrap(),
+            "status,name,id\nactive,a,1\nactive,\"c, d\",3\n"
+        );
+
+        options.filter = Some(Expr::parse("missing = 1").unwrap());
+        assert!(matches!(
+            export(&reader, &out, &options),
+            Err(MassiveCsvError::ColumnNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn export_sqlite() {
+        let f = make_csv("id,price,name\n1,2.5,a\n2,,b\n");
+        let reader = CsvReader::open(f.path()).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("orders.db");
+        let options = ExportOptions {
+            format: ExportFormat::for_path(&out),
+            ..Default::default()
+        };
+        assert_eq!(options.format, ExportFormat::Sqlite);
+        let summary = export(&reader, &out, &options).unwrap();
+        assert_eq!(summary.rows_written, 2);
+
+        let data = std::fs::read(&out).unwrap();
+        assert!(data.starts_with(b"SQLite format 3\0"));
+        let sql = b"CREATE TABLE \"orders\" (\"id\" INTEGER, \"price\" REAL, \"name\" TEXT)";
+        assert!(data.windows(sql.len()).any(|w| w == sql));
+    }
+
+    #[test]
+    fn rfc3339_formatting() {
+        assert_eq!(format_rfc3339(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+        let t = UNIX_EPOCH + Duration::from_secs(1_709_251_199);
+        assert_eq!(format_rfc3339(t), "2024-02-29T23:59:59Z");
+    }
+}
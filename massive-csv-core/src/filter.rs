@@ -0,0 +1,1241 @@
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::fmt;
+use std::ops::Range;
+use std::sync::Arc;
+
+use memchr::memmem::Finder;
+use rayon::prelude::*;
+
+use crate::bloom::BloomFilter;
+use crate::error::{MassiveCsvError, Result};
+use crate::index::IndexStore;
+use crate::parser::{self, Dialect, EscapeStyle};
+use crate::reader::CsvReader;
+
+/// A parsed filter expression, e.g.
+/// `status == "active" && amount > 100 || region in ("eu", "us")`.
+///
+/// The left side of a comparison is a column name (bare, or quoted if it has
+/// spaces); the right side is a quoted string, a number or a bare word.
+/// Ordering comparisons are numeric when both sides parse as numbers and
+/// textual otherwise.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+    Not(Box<Expr>),
+    Compare(Comparison),
+}
+
+/// A single `column <op> value` predicate.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    pub column: String,
+    /// Column position, once resolved against a file's headers.
+    pub index: Option<usize>,
+    pub op: CmpOp,
+    /// One value, or the list for `in`.
+    pub values: Vec<Value>,
+    /// Hashed lookup for long `in` lists.
+    members: Option<Arc<Members>>,
+}
+
+/// `in` lists at least this long are probed through a [`Members`] set.
+const MEMBER_SET_MIN: usize = 16;
+
+/// `in` lists longer than this don't yield literal prefilters; probing the
+/// member set is cheaper than searching the line for every value.
+const MAX_PREFILTER_LITERALS: usize = 8;
+
+/// The values of a long `in` list, split by how [`Comparison::matches`]
+/// compares them, behind a Bloom filter that rejects most non-members
+/// before the hash lookup.
+#[derive(Debug, PartialEq)]
+struct Members {
+    texts: HashSet<String>,
+    numbers: HashSet<u64>,
+    bloom: BloomFilter,
+}
+
+impl Members {
+    fn new(values: &[Value]) -> Self {
+        let mut bloom = BloomFilter::new(values.len(), 0.01);
+        let mut texts = HashSet::new();
+        let mut numbers = HashSet::new();
+        for value in values {
+            match value.number {
+                Some(n) => {
+                    let bits = number_key(n);
+                    bloom.insert(&bits.to_le_bytes());
+                    numbers.insert(bits);
+                }
+                None => {
+                    bloom.insert(value.text.as_bytes());
+                    texts.insert(value.text.clone());
+                }
+            }
+        }
+        Self {
+            texts,
+            numbers,
+            bloom,
+        }
+    }
+
+    /// A numeric field can only equal a numeric value, and a textual field
+    /// only a textual one.
+    fn contains(&self, field: &str) -> bool {
+        match field_number(field) {
+            Some(n) => {
+                let bits = number_key(n);
+                self.bloom.might_contain(&bits.to_le_bytes()) && self.numbers.contains(&bits)
+            }
+            None => self.bloom.might_contain(field.as_bytes()) && self.texts.contains(field),
+        }
+    }
+}
+
+/// Hash key for a number, with `-0.0` folded into `0.0`.
+fn number_key(n: f64) -> u64 {
+    if n == 0.0 {
+        0
+    } else {
+        n.to_bits()
+    }
+}
+
+/// Comparison operators.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains,
+    StartsWith,
+    EndsWith,
+    In,
+}
+
+impl CmpOp {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CmpOp::Eq => "==",
+            CmpOp::Ne => "!=",
+            CmpOp::Lt => "<",
+            CmpOp::Le => "<=",
+            CmpOp::Gt => ">",
+            CmpOp::Ge => ">=",
+            CmpOp::Contains => "contains",
+            CmpOp::StartsWith => "startswith",
+            CmpOp::EndsWith => "endswith",
+            CmpOp::In => "in",
+        }
+    }
+}
+
+/// A literal on the right side of a comparison.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Value {
+    pub text: String,
+    /// The literal as a number, if it is one.
+    pub number: Option<f64>,
+}
+
+impl Value {
+    pub fn new(text: String) -> Self {
+        let number = text.trim().parse::<f64>().ok().filter(|n| n.is_finite());
+        Self { text, number }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.number {
+            Some(_) => write!(f, "{}", self.text),
+            None => write!(f, "\"{}\"", self.text.replace('"', "\\\"")),
+        }
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let simple = self.column.chars().all(|c| c.is_alphanumeric() || c == '_');
+        if simple {
+            write!(f, "{} {} ", self.column, self.op.as_str())?;
+        } else {
+            write!(f, "`{}` {} ", self.column, self.op.as_str())?;
+        }
+        if self.op == CmpOp::In {
+            const SHOWN: usize = 10;
+            let mut values: Vec<String> = self
+                .values
+                .iter()
+                .take(SHOWN)
+                .map(|v| v.to_string())
+                .collect();
+            if self.values.len() > SHOWN {
+                let more = self.values.len() - SHOWN;
+                values.push(format!("... {more} more"));
+            }
+            write!(f, "({})", values.join(", "))
+        } else {
+            write!(f, "{}", self.values[0])
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let join = |f: &mut fmt::Formatter<'_>, parts: &[Expr], sep: &str| {
+            for (i, part) in parts.iter().enumerate() {
+                if i > 0 {
+                    write!(f, " {sep} ")?;
+                }
+                match part {
+                    Expr::And(_) | Expr::Or(_) => write!(f, "({part})")?,
+                    _ => write!(f, "{part}")?,
+                }
+            }
+            Ok(())
+        };
+        match self {
+            Expr::And(parts) => join(f, parts, "&&"),
+            Expr::Or(parts) => join(f, parts, "||"),
+            Expr::Not(inner) => match **inner {
+                Expr::Compare(_) => write!(f, "!{inner}"),
+                _ => write!(f, "!({inner})"),
+            },
+            Expr::Compare(c) => write!(f, "{c}"),
+        }
+    }
+}
+
+impl Expr {
+    /// Parse an expression without resolving column names.
+    pub fn parse(input: &str) -> Result<Self> {
+        let tokens = tokenize(input)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.or()?;
+        if let Some(token) = parser.tokens.get(parser.pos) {
+            return Err(invalid(format!(
+                "unexpected {token} after a complete expression"
+            )));
+        }
+        Ok(expr)
+    }
+
+    /// Names of the columns the expression refers to, in order of first use.
+    pub fn columns(&self) -> Vec<&str> {
+        let mut columns = Vec::new();
+        self.visit(&mut |c| {
+            if !columns.contains(&c.column.as_str()) {
+                columns.push(c.column.as_str());
+            }
+        });
+        columns
+    }
+
+    /// All comparisons in the expression, depth-first.
+    pub fn comparisons(&self) -> Vec<&Comparison> {
+        let mut out = Vec::new();
+        self.visit(&mut |c| out.push(c));
+        out
+    }
+
+    fn visit<'a>(&'a self, f: &mut dyn FnMut(&'a Comparison)) {
+        match self {
+            Expr::And(parts) | Expr::Or(parts) => parts.iter().for_each(|p| p.visit(f)),
+            Expr::Not(inner) => inner.visit(f),
+            Expr::Compare(c) => f(c),
+        }
+    }
+
+    fn resolve(&mut self, headers: &[String]) -> Result<()> {
+        match self {
+            Expr::And(parts) | Expr::Or(parts) => {
+                parts.iter_mut().try_for_each(|p| p.resolve(headers))
+            }
+            Expr::Not(inner) => inner.resolve(headers),
+            Expr::Compare(c) => {
+                let index = parser::column_position(headers, &c.column)
+                    .ok_or_else(|| MassiveCsvError::ColumnNotFound(c.column.clone()))?;
+                c.index = Some(index);
+                Ok(())
+            }
+        }
+    }
+
+    fn eval(&self, fields: &[String]) -> bool {
+        match self {
+            Expr::And(parts) => parts.iter().all(|p| p.eval(fields)),
+            Expr::Or(parts) => parts.iter().any(|p| p.eval(fields)),
+            Expr::Not(inner) => !inner.eval(fields),
+            Expr::Compare(c) => {
+                let field = c
+                    .index
+                    .and_then(|i| fields.get(i))
+                    .map(|s| s.as_str())
+                    .unwrap_or("");
+                c.matches(field)
+            }
+        }
+    }
+}
+
+impl Comparison {
+    /// A comparison of `column` against literal values (several only for `in`).
+    pub fn new(column: impl Into<String>, op: CmpOp, values: Vec<String>) -> Self {
+        Self::with_values(
+            column.into(),
+            op,
+            values.into_iter().map(Value::new).collect(),
+        )
+    }
+
+    fn with_values(column: String, op: CmpOp, values: Vec<Value>) -> Self {
+        let members = (op == CmpOp::In && values.len() >= MEMBER_SET_MIN)
+            .then(|| Arc::new(Members::new(&values)));
+        Self {
+            column,
+            index: None,
+            op,
+            values,
+            members,
+        }
+    }
+
+    /// Whether a field value satisfies this comparison.
+    pub fn matches(&self, field: &str) -> bool {
+        let value = &self.values[0];
+        match self.op {
+            CmpOp::Eq => equals(field, value),
+            CmpOp::Ne => !equals(field, value),
+            CmpOp::Lt => compare(field, value) == Ordering::Less,
+            CmpOp::Le => compare(field, value) != Ordering::Greater,
+            CmpOp::Gt => compare(field, value) == Ordering::Greater,
+            CmpOp::Ge => compare(field, value) != Ordering::Less,
+            CmpOp::Contains => field.contains(value.text.as_str()),
+            CmpOp::StartsWith => field.starts_with(value.text.as_str()),
+            CmpOp::EndsWith => field.ends_with(value.text.as_str()),
+            CmpOp::In => match &self.members {
+                Some(members) => members.contains(field),
+                None => self.values.iter().any(|v| equals(field, v)),
+            },
+        }
+    }
+
+    /// Whether the comparison is evaluated numerically (the literal is a number).
+    pub fn is_numeric(&self) -> bool {
+        !matches!(
+            self.op,
+            CmpOp::Contains | CmpOp::StartsWith | CmpOp::EndsWith
+        ) && self.values.iter().all(|v| v.number.is_some())
+    }
+}
+
+fn field_number(field: &str) -> Option<f64> {
+    field.trim().parse::<f64>().ok().filter(|n| n.is_finite())
+}
+
+fn equals(field: &str, value: &Value) -> bool {
+    match (field_number(field), value.number) {
+        (Some(a), Some(b)) => a == b,
+        _ => field == value.text,
+    }
+}
+
+fn compare(field: &str, value: &Value) -> Ordering {
+    match (field_number(field), value.number) {
+        (Some(a), Some(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+        _ => field.cmp(value.text.as_str()),
+    }
+}
+
+/// A filter expression resolved against a file's columns, ready to evaluate.
+#[derive(Debug, Clone)]
+pub struct Filter {
+    expr: Expr,
+}
+
+impl Filter {
+    /// Parse `input` and resolve its columns against `headers`.
+    pub fn parse(input: &str, headers: &[String]) -> Result<Self> {
+        Self::new(Expr::parse(input)?, headers)
+    }
+
+    /// Resolve the columns of an already built expression against `headers`.
+    pub fn new(mut expr: Expr, headers: &[String]) -> Result<Self> {
+        expr.resolve(headers)?;
+        Ok(Self { expr })
+    }
+
+    /// The resolved expression tree.
+    pub fn expr(&self) -> &Expr {
+        &self.expr
+    }
+
+    /// Whether a parsed row satisfies the filter.
+    pub fn matches(&self, fields: &[String]) -> bool {
+        self.expr.eval(fields)
+    }
+
+    /// Describe how the filter would be executed against `reader`.
+    ///
+    /// Checking whether a column is sorted reads that column the first time
+    /// `reader` is asked; an unsorted column is usually rejected within the
+    /// first few rows sampled.
+    pub fn plan(&self, reader: &CsvReader) -> Plan {
+        let columns = self
+            .expr
+            .comparisons()
+            .into_iter()
+            .fold(Vec::new(), |mut acc, c| {
+                let entry = (c.column.clone(), c.index.unwrap_or(0));
+                if !acc.contains(&entry) {
+                    acc.push(entry);
+                }
+                acc
+            });
+
+        let prefilters = required_literals(&self.expr, reader.dialect());
+
+        // A fresh on-disk index narrows the candidates directly.
+        let store = IndexStore::for_file(reader.path());
+        let mut indexed: Vec<String> = Vec::new();
+        let mut candidates: Option<Vec<usize>> = None;
+        for c in conjuncts(&self.expr) {
+            let Some(rows) = index_rows(&store, reader, c) else {
+                continue;
+            };
+            if !indexed.contains(&c.column) {
+                indexed.push(c.column.clone());
+            }
+            candidates = Some(match candidates {
+                None => rows,
+                Some(prev) => intersect(&prev, &rows),
+            });
+        }
+        if let Some(rows) = candidates {
+            return Plan {
+                columns,
+                rows: reader.row_count(),
+                prefilters,
+                access: Access::Index {
+                    columns: indexed,
+                    rows,
+                },
+            };
+        }
+
+        let mut access = Access::FullScan;
+        for c in conjuncts(&self.expr) {
+            let Some(index) = c.index else { continue };
+            if !matches!(
+                c.op,
+                CmpOp::Eq | CmpOp::Lt | CmpOp::Le | CmpOp::Gt | CmpOp::Ge
+            ) {
+                continue;
+            }
+            let numeric = c.is_numeric();
+            if !reader.column_sorted(index, numeric, || column_is_sorted(reader, index, numeric)) {
+                continue;
+            }
+
+            let range = sorted_range(reader, c);
+            access = match access {
+                Access::FullScan => Access::SortedRange {
+                    columns: vec![c.column.clone()],
+                    rows: range,
+                },
+                Access::Index { .. } => unreachable!("index plans return early"),
+                Access::SortedRange { mut columns, rows } => {
+                    if !columns.contains(&c.column) {
+                        columns.push(c.column.clone());
+                    }
+                    let start = rows.start.max(range.start);
+                    Access::SortedRange {
+                        columns,
+                        rows: start..rows.end.min(range.end).max(start),
+                    }
+                }
+            };
+        }
+
+        Plan {
+            columns,
+            rows: reader.row_count(),
+            prefilters,
+            access,
+        }
+    }
+
+    /// Row numbers (0-indexed, ascending) of the rows matching the filter.
+    ///
+    /// Stops collecting after `max_results` matches (0 = unlimited).
+    pub fn matching_rows(&self, reader: &CsvReader, max_results: usize) -> Result<Vec<usize>> {
+        let mut matches = Vec::new();
+        self.scan(reader, &mut |batch| {
+            matches.extend(batch);
+            max_results == 0 || matches.len() < max_results
+        })?;
+        if max_results > 0 {
+            matches.truncate(max_results);
+        }
+        Ok(matches)
+    }
+
+    /// Number of rows matching the filter, by the same plan as
+    /// [`matching_rows`](Self::matching_rows) but without keeping them.
+    pub fn count_matching(&self, reader: &CsvReader) -> Result<usize> {
+        let mut count = 0;
+        self.scan(reader, &mut |batch| {
+            count += batch.len();
+            true
+        })?;
+        Ok(count)
+    }
+
+    /// Test the rows the plan can't rule out in parallel batches, passing
+    /// each batch's matches (ascending) to `visit` until it returns false.
+    fn scan(&self, reader: &CsvReader, visit: &mut dyn FnMut(Vec<usize>) -> bool) -> Result<()> {
+        let plan = self.plan(reader);
+        // Scans walk their row range; only an index's rows are listed
+        let (range, listed) = match plan.access {
+            Access::FullScan => (0..reader.row_count(), None),
+            Access::SortedRange { rows, .. } => (rows, None),
+            Access::Index { rows, .. } => (0..rows.len(), Some(rows)),
+        };
+        let finders: Vec<Vec<Finder<'_>>> = plan
+            .prefilters
+            .iter()
+            .map(|group| group.iter().map(Finder::new).collect())
+            .collect();
+        let test = |row: usize| -> Result<Option<usize>> {
+            let raw = reader.get_row_text(row)?;
+            let candidate = finders
+                .iter()
+                .all(|group| group.iter().any(|f| f.find(raw.as_bytes()).is_some()));
+            if !candidate {
+                return Ok(None);
+            }
+            let fields = reader.parse_raw(&raw)?;
+            Ok(self.matches(&fields).then_some(row))
+        };
+
+        let mut start = range.start;
+        while start < range.end {
+            let end = (start + SCAN_CHUNK).min(range.end);
+            let chunk = match &listed {
+                Some(rows) => rows[start..end]
+                    .par_iter()
+                    .map(|&row| test(row))
+                    .collect::<Result<Vec<_>>>()?,
+                None => (start..end)
+                    .into_par_iter()
+                    .map(test)
+                    .collect::<Result<Vec<_>>>()?,
+            };
+            if !visit(chunk.into_iter().flatten().collect()) {
+                break;
+            }
+            start = end;
+        }
+        Ok(())
+    }
+}
+
+/// Rows tested per parallel batch by [`Filter::matching_rows`] and
+/// [`Filter::count_matching`], so a result limit can stop the scan early.
+const SCAN_CHUNK: usize = 64 * 1024;
+
+/// Rows sampled, evenly spaced, before a full check that a column is sorted.
+const SORT_SAMPLE: usize = 1024;
+
+/// How a filter is executed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Access {
+    /// Every row is tested, in parallel.
+    FullScan,
+    /// The filter constrains ascending-sorted columns, so only this row range
+    /// (found by binary search) can match.
+    SortedRange {
+        columns: Vec<String>,
+        rows: Range<usize>,
+    },
+    /// On-disk indexes of these columns give the only rows that can match.
+    Index {
+        columns: Vec<String>,
+        rows: Vec<usize>,
+    },
+}
+
+/// The execution plan for a [`Filter`] over a specific file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Plan {
+    /// Columns touched, with their positions.
+    pub columns: Vec<(String, usize)>,
+    /// Rows in the file.
+    pub rows: usize,
+    /// Literal prefilters: a matching row's raw text contains at least one
+    /// string from every group, so rows failing this are skipped unparsed.
+    pub prefilters: Vec<Vec<String>>,
+    pub access: Access,
+}
+
+/// Comparisons that every matching row must satisfy (the top-level conjunction).
+fn conjuncts(expr: &Expr) -> Vec<&Comparison> {
+    match expr {
+        Expr::Compare(c) => vec![c],
+        Expr::And(parts) => parts.iter().flat_map(conjuncts).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Candidate rows for `c` from a fresh on-disk index of its column, if usable.
+///
+/// Index values are ordered as text, so only comparisons against text literals
+/// (which [`Comparison::matches`] also compares as text) can use one.
+fn index_rows(store: &IndexStore, reader: &CsvReader, c: &Comparison) -> Option<Vec<usize>> {
+    let text = c.values.iter().all(|v| v.number.is_none());
+    let usable = match c.op {
+        CmpOp::StartsWith => true,
+        CmpOp::Eq | CmpOp::In | CmpOp::Lt | CmpOp::Le | CmpOp::Gt | CmpOp::Ge => text,
+        _ => false,
+    };
+    if !usable {
+        return None;
+    }
+    let index = store.open(reader, c.index?).ok()??;
+
+    let value = c.values[0].text.as_str();
+    Some(match c.op {
+        CmpOp::Eq => index.lookup(value),
+        CmpOp::In => {
+            let mut rows: Vec<usize> = c
+                .values
+                .iter()
+                .flat_map(|v| index.lookup(&v.text))
+                .collect();
+            rows.sort_unstable();
+            rows.dedup();
+            rows
+        }
+        CmpOp::StartsWith => index.prefix(value),
+        CmpOp::Lt => index.range(None, Some((value, false))),
+        CmpOp::Le => index.range(None, Some((value, true))),
+        CmpOp::Gt => index.range(Some((value, false)), None),
+        _ => index.range(Some((value, true)), None),
+    })
+}
+
+/// Intersection of two ascending row lists.
+fn intersect(a: &[usize], b: &[usize]) -> Vec<usize> {
+    let mut out = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            Ordering::Less => i += 1,
+            Ordering::Greater => j += 1,
+            Ordering::Equal => {
+                out.push(a[i]);
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    out
+}
+
+/// Groups of literals of which a matching row's raw line must contain at
+/// least one per group.
+///
+/// Only comparisons whose match implies the literal appears verbatim in the
+/// line qualify: text equality, `in` lists and substring operators. Numeric
+/// equality does not (`100` equals `1e2`), nor does a literal that would be
+/// escaped when quoted.
+fn required_literals(expr: &Expr, dialect: Dialect) -> Vec<Vec<String>> {
+    let usable = |text: &str| {
+        !text.is_empty()
+            && !text.contains(dialect.quote as char)
+            && !text.contains(['\n', '\r'])
+            && (dialect.escape != EscapeStyle::Backslash || !text.contains('\\'))
+    };
+
+    match expr {
+        Expr::Compare(c) => {
+            let literals: Vec<&Value> = match c.op {
+                CmpOp::Eq | CmpOp::In
+                    if c.values.len() <= MAX_PREFILTER_LITERALS
+                        && c.values.iter().all(|v| v.number.is_none()) =>
+                {
+                    c.values.iter().collect()
+                }
+                CmpOp::Contains | CmpOp::StartsWith | CmpOp::EndsWith => vec![&c.values[0]],
+                _ => return Vec::new(),
+            };
+            if literals.iter().all(|v| usable(&v.text)) {
+                vec![literals.iter().map(|v| v.text.clone()).collect()]
+            } else {
+                Vec::new()
+            }
+        }
+        Expr::And(parts) => parts
+            .iter()
+            .flat_map(|p| required_literals(p, dialect))
+            .collect(),
+        Expr::Or(parts) => {
+            // Each branch contributes its most selective group; one of them must hold.
+            let mut group = Vec::new();
+            for part in parts {
+                let best = required_literals(part, dialect)
+                    .into_iter()
+                    .min_by_key(|g| (g.len(), std::cmp::Reverse(g.iter().map(String::len).min())));
+                match best {
+                    Some(g) => group.extend(g),
+                    None => return Vec::new(),
+                }
+            }
+            group.dedup();
+            vec![group]
+        }
+        Expr::Not(_) => Vec::new(),
+    }
+}
+
+/// Order a field against a comparison's literal, numerically or textually.
+fn key_order(field: &str, value: &Value, numeric: bool) -> Ordering {
+    if numeric {
+        let field = field_number(field).unwrap_or(f64::NAN);
+        field
+            .partial_cmp(&value.number.unwrap_or(f64::NAN))
+            .unwrap_or(Ordering::Equal)
+    } else {
+        field.cmp(value.text.as_str())
+    }
+}
+
+fn field_at(reader: &CsvReader, row: usize, index: usize) -> Option<String> {
+    let raw = reader.get_row_raw(row).ok()?;
+    reader.field_of(raw, index).map(|f| f.into_owned())
+}
+
+/// Whether column `index` is in ascending order over the whole file.
+///
+/// For `numeric`, every value must also parse as a number, so that numeric
+/// and textual comparisons can't disagree.
+fn column_is_sorted(reader: &CsvReader, index: usize, numeric: bool) -> bool {
+    let rows = reader.row_count();
+    if rows < 2 {
+        return false;
+    }
+
+    let in_order = |a: &str, b: &str| {
+        if numeric {
+            match (field_number(a), field_number(b)) {
+                (Some(a), Some(b)) => a <= b,
+                _ => false,
+            }
+        } else {
+            a <= b
+        }
+    };
+    let ordered = |rows: &[usize]| {
+        let mut prev: Option<String> = None;
+        for &row in rows {
+            let Some(field) = field_at(reader, row, index) else {
+                return false;
+            };
+            if let Some(prev) = &prev {
+                if !in_order(prev, &field) {
+                    return false;
+                }
+            }
+            prev = Some(field);
+        }
+        true
+    };
+
+    let step = (rows / SORT_SAMPLE).max(1);
+    let sample: Vec<usize> = (0..rows).step_by(step).chain([rows - 1]).collect();
+    if !ordered(&sample) {
+        return false;
+    }
+
+    // Chunks overlap by one row so every adjacent pair is checked.
+    (0..rows.div_ceil(SCAN_CHUNK)).into_par_iter().all(|chunk| {
+        let start = chunk * SCAN_CHUNK;
+        let end = (start + SCAN_CHUNK + 1).min(rows);
+        ordered(&(start..end).collect::<Vec<_>>())
+    })
+}
+
+/// Rows of a column sorted ascending that can satisfy `c`, by binary search.
+fn sorted_range(reader: &CsvReader, c: &Comparison) -> Range<usize> {
+    let index = c.index.unwrap_or(0);
+    let value = &c.values[0];
+    let numeric = c.is_numeric();
+    let order = |row: usize| {
+        let field = field_at(reader, row, index).unwrap_or_default();
+        key_order(&field, value, numeric)
+    };
+    let partition = |pred: &dyn Fn(Ordering) -> bool| {
+        let (mut lo, mut hi) = (0, reader.row_count());
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            if pred(order(mid)) {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        lo
+    };
+
+    let first_not_less = || partition(&|o| o == Ordering::Less);
+    let first_greater = || partition(&|o| o != Ordering::Greater);
+    match c.op {
+        CmpOp::Eq => first_not_less()..first_greater(),
+        CmpOp::Lt => 0..first_not_less(),
+        CmpOp::Le => 0..first_greater(),
+        CmpOp::Gt => first_greater()..reader.row_count(),
+        CmpOp::Ge => first_not_less()..reader.row_count(),
+        _ => 0..reader.row_count(),
+    }
+}
+
+fn invalid(message: String) -> MassiveCsvError {
+    MassiveCsvError::InvalidFilter(message)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Str(String),
+    Op(CmpOp),
+    And,
+    Or,
+    Not,
+    LParen,
+    RParen,
+    Comma,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Word(w) => write!(f, "'{w}'"),
+            Token::Str(s) => write!(f, "\"{s}\""),
+            Token::Op(op) => write!(f, "'{}'", op.as_str()),
+            Token::And => write!(f, "'&&'"),
+            Token::Or => write!(f, "'||'"),
+            Token::Not => write!(f, "'!'"),
+            Token::LParen => write!(f, "'('"),
+            Token::RParen => write!(f, "')'"),
+            Token::Comma => write!(f, "','"),
+        }
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let two = |a: char, b: char| c == a && next == Some(b);
+
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        let (token, len) = if two('&', '&') {
+            (Token::And, 2)
+        } else if two('|', '|') {
+            (Token::Or, 2)
+        } else if two('=', '=') {
+            (Token::Op(CmpOp::Eq), 2)
+        } else if two('!', '=') || two('<', '>') {
+            (Token::Op(CmpOp::Ne), 2)
+        } else if two('<', '=') {
+            (Token::Op(CmpOp::Le), 2)
+        } else if two('>', '=') {
+            (Token::Op(CmpOp::Ge), 2)
+        } else {
+            match c {
+                '=' => (Token::Op(CmpOp::Eq), 1),
+                '<' => (Token::Op(CmpOp::Lt), 1),
+                '>' => (Token::Op(CmpOp::Gt), 1),
+                '!' => (Token::Not, 1),
+                '(' => (Token::LParen, 1),
+                ')' => (Token::RParen, 1),
+                ',' => (Token::Comma, 1),
+                '"' | '\'' | '`' => {
+                    let (text, len) = quoted(&chars[i..])?;
+                    (Token::Str(text), len)
+                }
+                _ => {
+                    let len = chars[i..]
+                        .iter()
+                        .take_while(|c| !c.is_whitespace() && !"()!=<>&|,\"'`".contains(**c))
+                        .count();
+                    if len == 0 {
+                        return Err(invalid(format!("unexpected character '{c}'")));
+                    }
+                    let word: String = chars[i..i + len].iter().collect();
+                    let token = match word.to_ascii_lowercase().as_str() {
+                        "and" => Token::And,
+                        "or" => Token::Or,
+                        "not" => Token::Not,
+                        "in" => Token::Op(CmpOp::In),
+                        "contains" => Token::Op(CmpOp::Contains),
+                        "startswith" | "starts_with" => Token::Op(CmpOp::StartsWith),
+                        "endswith" | "ends_with" => Token::Op(CmpOp::EndsWith),
+                        _ => Token::Word(word),
+                    };
+                    (token, len)
+                }
+            }
+        };
+        tokens.push(token);
+        i += len;
+    }
+
+    Ok(tokens)
+}
+
+/// Read a quoted string starting at `chars[0]`; returns its text and length in chars.
+///
+/// The quote is escaped by doubling it or with a backslash.
+fn quoted(chars: &[char]) -> Result<(String, usize)> {
+    let quote = chars[0];
+    let mut text = String::new();
+    let mut i = 1;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '\\' && i + 1 < chars.len() {
+            text.push(chars[i + 1]);
+            i += 2;
+        } else if c == quote && chars.get(i + 1) == Some(&quote) {
+            text.push(quote);
+            i += 2;
+        } else if c == quote {
+            return Ok((text, i + 1));
+        } else {
+            text.push(c);
+            i += 1;
+        }
+    }
+    Err(invalid(format!(
+        "unterminated string starting with {quote}"
+    )))
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn or(&mut self) -> Result<Expr> {
+        let mut parts = vec![self.and()?];
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            parts.push(self.and()?);
+        }
+        Ok(if parts.len() == 1 {
+            parts.pop().expect("one part")
+        } else {
+            Expr::Or(parts)
+        })
+    }
+
+    fn and(&mut self) -> Result<Expr> {
+        let mut parts = vec![self.unary()?];
+        while self.peek() == Some(&Token::And) {
+            self.pos += 1;
+            parts.push(self.unary()?);
+        }
+        Ok(if parts.len() == 1 {
+            parts.pop().expect("one part")
+        } else {
+            Expr::And(parts)
+        })
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        match self.peek() {
+            Some(Token::Not) => {
+                self.pos += 1;
+                Ok(Expr::Not(Box::new(self.unary()?)))
+            }
+            Some(Token::LParen) => {
+                self.pos += 1;
+                let expr = self.or()?;
+                match self.next() {
+                    Some(Token::RParen) => Ok(expr),
+                    Some(t) => Err(invalid(format!("expected ')', found {t}"))),
+                    None => Err(invalid("missing ')'".to_string())),
+                }
+            }
+            _ => self.comparison(),
+        }
+    }
+
+    fn comparison(&mut self) -> Result<Expr> {
+        let column = match self.next() {
+            Some(Token::Word(w)) | Some(Token::Str(w)) => w,
+            Some(t) => return Err(invalid(format!("expected a column name, found {t}"))),
+            None => return Err(invalid("expected a column name".to_string())),
+        };
+        let op = match self.next() {
+            Some(Token::Op(op)) => op,
+            Some(t) => {
+                return Err(invalid(format!(
+                    "expected an operator after '{column}', found {t}"
+                )))
+            }
+            None => return Err(invalid(format!("expected an operator after '{column}'"))),
+        };
+
+        let values = if op == CmpOp::In {
+            if self.next() != Some(Token::LParen) {
+                return Err(invalid("expected '(' after 'in'".to_string()));
+            }
+            let mut values = vec![self.value()?];
+            loop {
+                match self.next() {
+                    Some(Token::Comma) => values.push(self.value()?),
+                    Some(Token::RParen) => break,
+                    _ => return Err(invalid("expected ',' or ')' in 'in' list".to_string())),
+                }
+            }
+            values
+        } else {
+            vec![self.value()?]
+        };
+
+        Ok(Expr::Compare(Comparison::with_values(column, op, values)))
+    }
+
+    fn value(&mut self) -> Result<Value> {
+        match self.next() {
+            Some(Token::Word(w)) | Some(Token::Str(w)) => Ok(Value::new(w)),
+            Some(t) => Err(invalid(format!("expected a value, found {t}"))),
+            None => Err(invalid("expected a value".to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers() -> Vec<String> {
+        ["id", "status", "amount", "region", "full name"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect()
+    }
+
+    fn row(values: &[&str]) -> Vec<String> {
+        values.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_and_evaluate() {
+        let filter = Filter::parse(
+            r#"status == "active" && amount > 100 || region in ("eu", 'us')"#,
+            &headers(),
+        )
+        .unwrap();
+
+        assert!(filter.matches(&row(&["1", "active", "150.5", "apac", ""])));
+        assert!(!filter.matches(&row(&["2", "active", "99", "apac", ""])));
+        assert!(filter.matches(&row(&["3", "closed", "0", "us", ""])));
+
+        assert_eq!(filter.expr().columns(), vec!["status", "amount", "region"]);
+        assert_eq!(
+            filter.expr().to_string(),
+            r#"(status == "active" && amount > 100) || region in ("eu", "us")"#
+        );
+    }
+
+    #[test]
+    fn quoted_columns_and_keywords() {
+        let filter =
+            Filter::parse(r#"not (`full name` startswith "A") and id=7"#, &headers()).unwrap();
+        assert!(filter.matches(&row(&["7.0", "", "", "", "Bob"])));
+        assert!(!filter.matches(&row(&["7", "", "", "", "Alice"])));
+    }
+
+    #[test]
+    fn canonical_column_names() {
+        let headers = row(&["amount", "amount", ""]);
+        let filter = Filter::parse("amount_2 > 5 && column_3 == x", &headers).unwrap();
+        assert!(filter.matches(&row(&["1", "10", "x"])));
+        assert!(!filter.matches(&row(&["10", "1", "x"])));
+    }
+
+    fn make_csv(content: &str) -> tempfile::NamedTempFile {
+        use std::io::Write;
+        let mut f = tempfile::NamedTempFile::new().unwrap();
+        f.write_all(content.as_bytes()).unwrap();
+        f.flush().unwrap();
+        f
+    }
+
+    #[test]
+    fn prefilters_from_literals() {
+        let f = make_csv("id,status,amount,region,full name\n1,active,5,eu,Ann\n");
+        let reader = CsvReader::open(f.path()).unwrap();
+        let plan = |expr: &str| Filter::parse(expr, &headers()).unwrap().plan(&reader);
+
+        let p = plan(r#"status == "active" && amount > 100 && region in (eu, us)"#);
+        assert_eq!(p.prefilters, vec![vec!["active"], vec!["eu", "us"]]);
+
+        let p = plan(r#"`full name` contains Ann || status == "closed""#);
+        assert_eq!(p.prefilters, vec![vec!["Ann", "closed"]]);
+
+        // Numeric equality, negation, or a branch without a literal can't be prefiltered.
+        assert!(plan("amount == 5").prefilters.is_empty());
+        assert!(plan(r#"!(status == "x")"#).prefilters.is_empty());
+        assert!(plan(r#"status == "x" || amount > 1"#).prefilters.is_empty());
+    }
+
+    #[test]
+    fn sorted_column_binary_search() {
+        let mut csv = String::from("id,status,amount,region,full name\n");
+        for i in 0..200 {
+            csv.push_str(&format!("{},s{},{},eu,x\n", i * 2, i % 3, 200 - i));
+        }
+        let f = make_csv(&csv);
+        let reader = CsvReader::open(f.path()).unwrap();
+
+        let filter = Filter::parse("id >= 100 && id < 110 && status == s1", &headers()).unwrap();
+        let plan = filter.plan(&reader);
+        assert_eq!(
+            plan.access,
+            Access::SortedRange {
+                columns: vec!["id".to_string()],
+                rows: 50..55,
+            }
+        );
+        assert_eq!(filter.matching_rows(&reader, 0).unwrap(), vec![52]);
+        assert_eq!(filter.count_matching(&reader).unwrap(), 1);
+
+        // The check is remembered by the reader, so later plans don't read the column again
+        assert!(reader.column_sorted(0, true, || unreachable!("already checked")));
+
+        // `amount` descends, so it is scanned.
+        let filter = Filter::parse("amount <= 3", &headers()).unwrap();
+        assert_eq!(filter.plan(&reader).access, Access::FullScan);
+        assert_eq!(filter.matching_rows(&reader, 2).unwrap(), vec![197, 198]);
+        assert_eq!(filter.count_matching(&reader).unwrap(), 3);
+    }
+
+    #[test]
+    fn full_scan_walks_rows_in_chunks() {
+        let mut csv = String::from("id,status,amount,region,full name\n");
+        for i in 0..SCAN_CHUNK + 10 {
+            let region = if i % 10_000 == 1 || i == SCAN_CHUNK + 5 {
+                "us"
+            } else {
+                "eu"
+            };
+            csv.push_str(&format!("{i},s,1,{region},x\n"));
+        }
+        let f = make_csv(&csv);
+        let reader = CsvReader::open(f.path()).unwrap();
+
+        let filter = Filter::parse("region == us", &headers()).unwrap();
+        assert_eq!(filter.plan(&reader).access, Access::FullScan);
+        assert_eq!(
+            filter.matching_rows(&reader, 3).unwrap(),
+            vec![1, 10_001, 20_001]
+        );
+        assert_eq!(filter.count_matching(&reader).unwrap(), 8);
+        let all = filter.matching_rows(&reader, 0).unwrap();
+        assert_eq!(all.last(), Some(&(SCAN_CHUNK + 5)));
+    }
+
+    #[test]
+    fn uses_fresh_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.csv");
+        std::fs::write(
+            &path,
+            "id,status,amount,region,full name\n1,b,5,eu,x\n2,a,6,us,y\n3,b,7,eu,z\n",
+        )
+        .unwrap();
+        let reader = CsvReader::open(&path).unwrap();
+        IndexStore::for_file(&path)
+            .build(&reader, "status")
+            .unwrap();
+
+        let filter = Filter::parse("status == b && region == eu", &headers()).unwrap();
+        assert_eq!(
+            filter.plan(&reader).access,
+            Access::Index {
+                columns: vec!["status".to_string()],
+                rows: vec![0, 2],
+            }
+        );
+        assert_eq!(filter.matching_rows(&reader, 0).unwrap(), vec![0, 2]);
+
+        // Numeric literals are compared as numbers, which the index can't answer.
+        let filter = Filter::parse("status == 1", &headers()).unwrap();
+        assert_eq!(filter.plan(&reader).access, Access::FullScan);
+    }
+
+    #[test]
+    fn long_in_lists_use_member_set() {
+        let mut values: Vec<String> = (0..100).map(|i| format!("k{i}")).collect();
+        values.extend(["7".to_string(), "-0".to_string()]);
+        let expr = Expr::Compare(Comparison::new("status", CmpOp::In, values));
+        let filter = Filter::new(expr, &headers()).unwrap();
+
+        assert!(filter.matches(&row(&["", "k42", "", "", ""])));
+        assert!(filter.matches(&row(&["", "7.0", "", "", ""])));
+        assert!(filter.matches(&row(&["", "0", "", "", ""])));
+        assert!(!filter.matches(&row(&["", "k100", "", "", ""])));
+        assert!(!filter.matches(&row(&["", "8", "", "", ""])));
+        assert!(filter.expr().to_string().ends_with(r#""k9", ... 92 more)"#));
+
+        let f = make_csv("id,status,amount,region,full name\n1,k1,5,eu,x\n");
+        let reader = CsvReader::open(f.path()).unwrap();
+        assert!(filter.plan(&reader).prefilters.is_empty());
+    }
+
+    #[test]
+    fn errors() {
+        assert!(matches!(
+            Expr::parse("status =="),
+            Err(MassiveCsvError::InvalidFilter(_))
+        ));
+        assert!(matches!(
+            Expr::parse("(a == 1"),
+            Err(MassiveCsvError::InvalidFilter(_))
+        ));
+        assert!(matches!(
+            Filter::parse("missing == 1", &headers()),
+            Err(MassiveCsvError::ColumnNotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,121 @@
+//! Range requests against HTTP(S) servers, for [`RemoteReader`].
+
+use std::io::Read;
+use std::time::Duration;
+
+use crate::error::{MassiveCsvError, Result};
+use crate::reader::OpenOptions;
+use crate::remote::{RangeSource, RemoteReader};
+
+/// A file served over HTTP(S) by a server that supports `Range` requests.
+pub struct HttpSource {
+    url: String,
+    len: u64,
+    agent: ureq::Agent,
+}
+
+impl HttpSource {
+    /// Probe `url` with a `HEAD` request for its size and range support.
+    pub fn open(url: &str) -> Result<Self> {
+        let agent = agent();
+        let response = agent.head(url).call().map_err(|e| request_error(url, e))?;
+        let len = content_length(url, &response)?;
+        Ok(Self {
+            url: url.to_string(),
+            len,
+            agent,
+        })
+    }
+}
+
+impl RangeSource for HttpSource {
+    fn name(&self) -> &str {
+        &self.url
+    }
+
+    fn len(&self) -> u64 {
+        self.len
+    }
+
+    fn read_range(&self, offset: u64, len: usize) -> Result<Vec<u8>> {
+        let end = (offset + len as u64).min(self.len);
+        if offset >= end {
+            return Ok(Vec::new());
+        }
+        fetch_range(&self.url, self.agent.get(&self.url), offset, end)
+    }
+}
+
+impl RemoteReader {
+    /// Open an `http://` or `https://` URL, or an `s3://bucket/key` URL
+    /// with the `s3` feature.
+    pub fn open_url(url: &str) -> Result<Self> {
+        Self::open_url_with(url, &OpenOptions::default())
+    }
+
+    /// Open a URL with dialect overrides.
+    pub fn open_url_with(url: &str, options: &OpenOptions) -> Result<Self> {
+        if url.starts_with("s3://") {
+            #[cfg(feature = "s3")]
+            return Self::open_with(crate::s3::S3Source::open(url)?, options);
+            #[cfg(not(feature = "s3"))]
+            return Err(MassiveCsvError::FeatureDisabled("Opening s3:// URLs", "s3"));
+        }
+        Self::open_with(HttpSource::open(url)?, options)
+    }
+}
+
+pub(crate) fn agent() -> ureq::Agent {
+    ureq::AgentBuilder::new()
+        .timeout_connect(Duration::from_secs(10))
+        .timeout_read(Duration::from_secs(60))
+        .build()
+}
+
+/// The size reported by a `HEAD` response, which must allow range requests.
+pub(crate) fn content_length(name: &str, response: &ureq::Response) -> Result<u64> {
+    if response.header("Accept-Ranges") == Some("none") {
+        return Err(MassiveCsvError::Remote(format!(
+            "{name} does not support range requests"
+        )));
+    }
+    response
+        .header("Content-Length")
+        .and_then(|len| len.parse().ok())
+        .ok_or_else(|| MassiveCsvError::Remote(format!("{name} did not report its size")))
+}
+
+/// Send `request` for bytes `offset..end`.
+pub(crate) fn fetch_range(
+    name: &str,
+    request: ureq::Request,
+    offset: u64,
+    end: u64,
+) -> Result<Vec<u8>> {
+    let response = request
+        .set("Range", &format!("bytes={offset}-{}", end - 1))
+        .call()
+        .map_err(|e| request_error(name, e))?;
+    // A 200 would be the whole file, which is what range requests avoid
+    if response.status() != 206 {
+        return Err(MassiveCsvError::Remote(format!(
+            "{name} ignored a range request (status {})",
+            response.status()
+        )));
+    }
+    let mut data = Vec::with_capacity((end - offset) as usize);
+    response
+        .into_reader()
+        .take(end - offset)
+        .read_to_end(&mut data)?;
+    Ok(data)
+}
+
+pub(crate) fn request_error(name: &str, error: ureq::Error) -> MassiveCsvError {
+    match error {
+        ureq::Error::Status(status, _) => {
+            MassiveCsvError::Remote(format!("{name} returned status {status}"))
+        }
+        ureq::Error::Transport(e) => MassiveCsvError::Remote(format!("{name}: {e}")),
+    }
+}
@@ -0,0 +1,538 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+
+use memmap2::Mmap;
+use rayon::prelude::*;
+
+use crate::background::{BuildHandle, Progress};
+use crate::editor::write_atomic;
+use crate::error::{MassiveCsvError, Result};
+use crate::reader::{CsvReader, FileFingerprint};
+use crate::META_DIR;
+
+const MAGIC: &[u8; 8] = b"MCSVIDX\x01";
+const INDEX_EXT: &str = "idx";
+
+/// Rows read per parallel batch while building an index.
+const BUILD_CHUNK: usize = 64 * 1024;
+
+/// Persistent per-column indexes for a single file.
+///
+/// Each index is stored under `.massive-csv/indexes/<file name>/` next to the
+/// file and records the fingerprint of the data it was built from, so an index
+/// is only used while the file is unchanged.
+#[derive(Debug, Clone)]
+pub struct IndexStore {
+    dir: PathBuf,
+}
+
+/// Summary of a stored column index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexInfo {
+    pub column: String,
+    pub position: usize,
+    /// Number of distinct values.
+    pub values: usize,
+    /// Number of rows indexed.
+    pub rows: usize,
+    /// Size of the index file in bytes.
+    pub size: u64,
+    /// Whether the index still matches the file on disk.
+    pub fresh: bool,
+    pub path: PathBuf,
+}
+
+impl IndexStore {
+    /// Index store for the file at `path`. Nothing is created until an index is built.
+    pub fn for_file(path: &Path) -> Self {
+        let parent = match path.parent() {
+            Some(p) if !p.as_os_str().is_empty() => p,
+            _ => Path::new("."),
+        };
+        let name = path.file_name().unwrap_or(path.as_os_str());
+        Self {
+            dir: parent.join(META_DIR).join("indexes").join(name),
+        }
+    }
+
+    /// Directory the indexes are stored in.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn index_path(&self, position: usize) -> PathBuf {
+        self.dir.join(format!("{position}.{INDEX_EXT}"))
+    }
+
+    /// Build (or rebuild) the index for `column` from the reader's current data.
+    pub fn build(&self, reader: &CsvReader, column: &str) -> Result<IndexInfo> {
+        self.build_with_progress(reader, column, &Progress::default())
+    }
+
+    /// Build the index for `column` on a background thread.
+    ///
+    /// Filters keep scanning until the build is ready, then pick up the fresh
+    /// index automatically.
+    pub fn build_in_background(
+        &self,
+        reader: Arc<CsvReader>,
+        column: &str,
+    ) -> BuildHandle<IndexInfo> {
+        let store = self.clone();
+        let column = column.to_string();
+        BuildHandle::spawn(move |progress| store.build_with_progress(&reader, &column, progress))
+    }
+
+    /// [`build`](Self::build), reporting rows read to `progress`.
+    pub fn build_with_progress(
+        &self,
+        reader: &CsvReader,
+        column: &str,
+        progress: &Progress,
+    ) -> Result<IndexInfo> {
+        let position = reader
+            .column_index(column)
+            .ok_or_else(|| MassiveCsvError::ColumnNotFound(column.to_string()))?;
+        let rows = reader.row_count();
+
+        let mut entries: Vec<(String, u64)> = Vec::with_capacity(rows);
+        let mut start = 0;
+        while start < rows {
+            let end = (start + BUILD_CHUNK).min(rows);
+            let chunk = (start..end)
+                .into_par_iter()
+                .map(|row| {
+                    let raw = reader.get_row_raw(row)?;
+                    let value = reader.field_of(raw, position).unwrap_or_default();
+                    Ok((value.into_owned(), row as u64))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            entries.extend(chunk);
+            progress.update(end, rows)?;
+            start = end;
+        }
+        entries.par_sort_unstable();
+
+        fs::create_dir_all(&self.dir)?;
+        let path = self.index_path(position);
+        write_atomic(&path, |writer| {
+            let mut out = BufWriter::new(writer);
+            write_index(&mut out, reader, position, &entries)?;
+            out.flush()?;
+            Ok(())
+        })?;
+
+        let index = ColumnIndex::open(&path)?;
+        Ok(index.info(reader.fingerprint(), &path))
+    }
+
+    /// The index for column `position`, if one exists and matches the reader's file.
+    pub fn open(&self, reader: &CsvReader, position: usize) -> Result<Option<ColumnIndex>> {
+        let path = self.index_path(position);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let index = ColumnIndex::open(&path)?;
+        let fresh = index.is_fresh(reader.fingerprint())
+            && index.rows == reader.row_count()
+            && reader.headers().get(position) == Some(&index.column);
+        Ok(fresh.then_some(index))
+    }
+
+    /// All stored indexes, ordered by column position.
+    pub fn list(&self, reader: &CsvReader) -> Result<Vec<IndexInfo>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut infos = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(INDEX_EXT) {
+                continue;
+            }
+            let index = ColumnIndex::open(&path)?;
+            let mut info = index.info(reader.fingerprint(), &path);
+            info.fresh &= reader.headers().get(info.position) == Some(&info.column);
+            infos.push(info);
+        }
+        infos.sort_by_key(|info| info.position);
+        Ok(infos)
+    }
+
+    /// Delete the index for `column`, if any. Returns whether one was removed.
+    pub fn remove(&self, reader: &CsvReader, column: &str) -> Result<bool> {
+        let position = reader
+            .column_index(column)
+            .ok_or_else(|| MassiveCsvError::ColumnNotFound(column.to_string()))?;
+        match fs::remove_file(self.index_path(position)) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// A memory-mapped index of one column: its distinct values in sorted order,
+/// each with the ascending list of rows holding it.
+///
+/// File layout (all integers little-endian u64 unless noted):
+///
+/// ```text
+/// magic "MCSVIDX\x01"
+/// fingerprint: len, has_mtime (u8), mtime secs, mtime nanos (u32), sample_hash
+/// column position, column name length, column name bytes
+/// row count, distinct value count N
+/// value offsets [N + 1]   into the value bytes
+/// row offsets   [N + 1]   into the row list
+/// value bytes
+/// row list
+/// ```
+pub struct ColumnIndex {
+    mmap: Mmap,
+    fingerprint: FileFingerprint,
+    column: String,
+    position: usize,
+    rows: usize,
+    values: usize,
+    value_offsets: usize,
+    row_offsets: usize,
+    value_bytes: usize,
+    row_list: usize,
+}
+
+impl ColumnIndex {
+    fn open(path: &Path) -> Result<Self> {
+        let file = File::open(path)?;
+        // SAFETY: Index files are only replaced atomically, never modified in place.
+        let mmap = unsafe { Mmap::map(&file)? };
+        let invalid = || MassiveCsvError::Parse(format!("invalid index file {}", path.display()));
+
+        let mut cursor = Cursor {
+            data: &mmap,
+            pos: 0,
+        };
+        if cursor.bytes(MAGIC.len()).ok_or_else(invalid)? != MAGIC {
+            return Err(invalid());
+        }
+        let fingerprint = cursor.fingerprint().ok_or_else(invalid)?;
+        let position = cursor.u64().ok_or_else(invalid)? as usize;
+        let name_len = cursor.u64().ok_or_else(invalid)? as usize;
+        let column = std::str::from_utf8(cursor.bytes(name_len).ok_or_else(invalid)?)
+            .map_err(|_| invalid())?
+            .to_string();
+        let rows = cursor.u64().ok_or_else(invalid)? as usize;
+        let values = cursor.u64().ok_or_else(invalid)? as usize;
+
+        let value_offsets = cursor.pos;
+        let row_offsets = value_offsets + (values + 1) * 8;
+        let value_bytes = row_offsets + (values + 1) * 8;
+        let mut index = Self {
+            fingerprint,
+            column,
+            position,
+            rows,
+            values,
+            value_offsets,
+            row_offsets,
+            value_bytes,
+            row_list: 0,
+            mmap,
+        };
+        if value_bytes > index.mmap.len() {
+            return Err(invalid());
+        }
+        index.row_list = value_bytes + index.offset(value_offsets, values) as usize;
+        let end = index.row_list + index.offset(row_offsets, values) as usize * 8;
+        if end != index.mmap.len() || !index.data_is_valid() {
+            return Err(invalid());
+        }
+        Ok(index)
+    }
+
+    /// Column name the index was built for.
+    pub fn column(&self) -> &str {
+        &self.column
+    }
+
+    /// Column position the index was built for.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Number of distinct values.
+    pub fn distinct_values(&self) -> usize {
+        self.values
+    }
+
+    /// Whether the index was built from a file with this fingerprint.
+    pub fn is_fresh(&self, fingerprint: &FileFingerprint) -> bool {
+        &self.fingerprint == fingerprint
+    }
+
+    /// Rows whose value is exactly `value`, ascending.
+    pub fn lookup(&self, value: &str) -> Vec<usize> {
+        let start = self.lower_bound(value);
+        let end = if start < self.values && self.value(start) == value {
+            start + 1
+        } else {
+            start
+        };
+        self.rows_for(start..end)
+    }
+
+    /// Rows whose value starts with `prefix`, ascending.
+    pub fn prefix(&self, prefix: &str) -> Vec<usize> {
+        let start = self.lower_bound(prefix);
+        let len = self.values - start;
+        let end = start + partition_point(len, |i| self.value(start + i).starts_with(prefix));
+        self.rows_for(start..end)
+    }
+
+    /// Rows whose value lies between the bounds, compared as text.
+    ///
+    /// `lower` and `upper` are `(value, inclusive)` pairs; `None` is unbounded.
+    pub fn range(&self, lower: Option<(&str, bool)>, upper: Option<(&str, bool)>) -> Vec<usize> {
+        let start = match lower {
+            None => 0,
+            Some((v, true)) => self.lower_bound(v),
+            Some((v, false)) => self.upper_bound(v),
+        };
+        let end = match upper {
+            None => self.values,
+            Some((v, true)) => self.upper_bound(v),
+            Some((v, false)) => self.lower_bound(v),
+        };
+        self.rows_for(start..end.max(start))
+    }
+
+    fn info(&self, fingerprint: &FileFingerprint, path: &Path) -> IndexInfo {
+        IndexInfo {
+            column: self.column.clone(),
+            position: self.position,
+            values: self.values,
+            rows: self.rows,
+            size: self.mmap.len() as u64,
+            fresh: self.is_fresh(fingerprint),
+            path: path.to_path_buf(),
+        }
+    }
+
+    fn offset(&self, table: usize, i: usize) -> u64 {
+        let at = table + i * 8;
+        u64::from_le_bytes(self.mmap[at..at + 8].try_into().expect("8 bytes"))
+    }
+
+    fn value(&self, i: usize) -> &str {
+        let start = self.value_bytes + self.offset(self.value_offsets, i) as usize;
+        let end = self.value_bytes + self.offset(self.value_offsets, i + 1) as usize;
+        // Validated as UTF-8 when the index was opened.
+        std::str::from_utf8(&self.mmap[start..end]).unwrap_or_default()
+    }
+
+    /// First value position not less than `value`.
+    fn lower_bound(&self, value: &str) -> usize {
+        partition_point(self.values, |i| self.value(i) < value)
+    }
+
+    /// First value position greater than `value`.
+    fn upper_bound(&self, value: &str) -> usize {
+        partition_point(self.values, |i| self.value(i) <= value)
+    }
+
+    /// Rows for a range of value positions, ascending.
+    fn rows_for(&self, values: Range<usize>) -> Vec<usize> {
+        if values.is_empty() {
+            return Vec::new();
+        }
+        let first = self.offset(self.row_offsets, values.start) as usize;
+        let last = self.offset(self.row_offsets, values.end) as usize;
+        let mut rows: Vec<usize> = (first..last)
+            .map(|i| self.offset(self.row_list, i) as usize)
+            .collect();
+        if values.len() > 1 {
+            rows.sort_unstable();
+        }
+        rows
+    }
+
+    /// Check that offset tables are monotonic and in bounds, and values are UTF-8.
+    fn data_is_valid(&self) -> bool {
+        let value_len = self.row_list - self.value_bytes;
+        let row_len = (self.mmap.len() - self.row_list) / 8;
+        let mut prev = (0, 0);
+        for i in 0..=self.values {
+            let offsets = (
+                self.offset(self.value_offsets, i) as usize,
+                self.offset(self.row_offsets, i) as usize,
+            );
+            if offsets.0 < prev.0 || offsets.1 < prev.1 || offsets.0 > value_len {
+                return false;
+            }
+            prev = offsets;
+        }
+        prev.1 == row_len
+            && std::str::from_utf8(&self.mmap[self.value_bytes..self.row_list]).is_ok()
+    }
+}
+
+/// Number of leading positions in `0..len` for which `pred` holds (`pred` must be monotonic).
+fn partition_point(len: usize, pred: impl Fn(usize) -> bool) -> usize {
+    let (mut lo, mut hi) = (0, len);
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        if pred(mid) {
+            lo = mid + 1;
+        } else {
+            hi = mid;
+        }
+    }
+    lo
+}
+
+fn write_index(
+    out: &mut impl Write,
+    reader: &CsvReader,
+    position: usize,
+    entries: &[(String, u64)],
+) -> Result<()> {
+    let fingerprint = reader.fingerprint();
+    let column = &reader.headers()[position];
+    let mtime = fingerprint
+        .modified
+        .and_then(|m| m.duration_since(UNIX_EPOCH).ok());
+
+    out.write_all(MAGIC)?;
+    out.write_all(&fingerprint.len.to_le_bytes())?;
+    out.write_all(&[mtime.is_some() as u8])?;
+    let mtime = mtime.unwrap_or_default();
+    out.write_all(&mtime.as_secs().to_le_bytes())?;
+    out.write_all(&mtime.subsec_nanos().to_le_bytes())?;
+    out.write_all(&fingerprint.sample_hash.to_le_bytes())?;
+    out.write_all(&(position as u64).to_le_bytes())?;
+    out.write_all(&(column.len() as u64).to_le_bytes())?;
+    out.write_all(column.as_bytes())?;
+    out.write_all(&(entries.len() as u64).to_le_bytes())?;
+
+    // Distinct values start where the value changes
+    let starts: Vec<usize> = (0..entries.len())
+        .filter(|&i| i == 0 || entries[i].0 != entries[i - 1].0)
+        .collect();
+    out.write_all(&(starts.len() as u64).to_le_bytes())?;
+
+    let mut value_offset = 0u64;
+    out.write_all(&value_offset.to_le_bytes())?;
+    for &i in &starts {
+        value_offset += entries[i].0.len() as u64;
+        out.write_all(&value_offset.to_le_bytes())?;
+    }
+    for &i in &starts {
+        out.write_all(&(i as u64).to_le_bytes())?;
+    }
+    out.write_all(&(entries.len() as u64).to_le_bytes())?;
+    for &i in &starts {
+        out.write_all(entries[i].0.as_bytes())?;
+    }
+    for (_, row) in entries {
+        out.write_all(&row.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+struct Cursor<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let slice = self.data.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len;
+        Some(slice)
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.bytes(8)?.try_into().ok()?))
+    }
+
+    fn fingerprint(&mut self) -> Option<FileFingerprint> {
+        let len = self.u64()?;
+        let has_mtime = self.bytes(1)?[0] == 1;
+        let secs = self.u64()?;
+        let nanos = u32::from_le_bytes(self.bytes(4)?.try_into().ok()?);
+        let sample_hash = self.u64()?;
+        Some(FileFingerprint {
+            len,
+            modified: has_mtime.then(|| UNIX_EPOCH + Duration::new(secs, nanos)),
+            sample_hash,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_and_query() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.csv");
+        fs::write(&path, "id,email\n3,c@x\n1,\"a,b@x\"\n2,c@x\n4,b@y\n").unwrap();
+        let reader = CsvReader::open(&path).unwrap();
+        let store = IndexStore::for_file(&path);
+
+        let info = store.build(&reader, "email").unwrap();
+        assert_eq!((info.values, info.rows, info.fresh), (3, 4, true));
+
+        let index = store.open(&reader, 1).unwrap().unwrap();
+        assert_eq!(index.lookup("c@x"), vec![0, 2]);
+        assert_eq!(index.lookup("a,b@x"), vec![1]);
+        assert!(index.lookup("zzz").is_empty());
+        assert_eq!(index.prefix("b"), vec![3]);
+        assert_eq!(index.range(Some(("b", true)), None), vec![0, 2, 3]);
+        assert_eq!(index.range(None, Some(("c@x", false))), vec![1, 3]);
+
+        assert_eq!(store.list(&reader).unwrap(), vec![info]);
+    }
+
+    #[test]
+    fn background_build() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.csv");
+        fs::write(&path, "id\n2\n1\n").unwrap();
+        let reader = Arc::new(CsvReader::open(&path).unwrap());
+        let store = IndexStore::for_file(&path);
+
+        let handle = store.build_in_background(Arc::clone(&reader), "id");
+        let info = handle.wait().unwrap();
+        assert_eq!(info.rows, 2);
+        assert_eq!(
+            store.open(&reader, 0).unwrap().unwrap().lookup("1"),
+            vec![1]
+        );
+    }
+
+    #[test]
+    fn stale_index_is_ignored() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.csv");
+        fs::write(&path, "id\n1\n2\n").unwrap();
+        let reader = CsvReader::open(&path).unwrap();
+        let store = IndexStore::for_file(&path);
+        store.build(&reader, "id").unwrap();
+
+        fs::write(&path, "id\n1\n2\n3\n").unwrap();
+        let reader = reader.reopen().unwrap();
+        assert!(store.open(&reader, 0).unwrap().is_none());
+        assert!(!store.list(&reader).unwrap()[0].fresh);
+
+        assert!(store.remove(&reader, "id").unwrap());
+        assert!(store.list(&reader).unwrap().is_empty());
+    }
+}
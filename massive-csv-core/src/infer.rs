@@ -0,0 +1,316 @@
+//! Column type inference from field values.
+//!
+//! Every non-empty value is classified on its own ([`ColumnType::of`]), and a
+//! column's type is the narrowest type all of its values fit
+//! ([`ColumnType::merge`]): integers widen to floats, dates to datetimes,
+//! and anything else mixed becomes text. Empty fields don't count against a
+//! type but make the column nullable.
+
+use std::fmt;
+
+use rayon::prelude::*;
+
+use crate::error::{MassiveCsvError, Result};
+use crate::reader::CsvReader;
+
+/// Rows read by [`infer_schema`] unless told otherwise.
+pub const DEFAULT_SAMPLE_ROWS: usize = 100_000;
+
+/// The type of a column's values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum ColumnType {
+    /// `true` or `false`, in any case.
+    Boolean,
+    /// A 64-bit signed integer without leading zeros (`007` is text, as
+    /// codes like ZIP codes must keep their zeros).
+    Integer,
+    /// A decimal number, optionally with an exponent.
+    Float,
+    /// `YYYY-MM-DD`.
+    Date,
+    /// `YYYY-MM-DD` then `T` or a space, `HH:MM:SS`, optional fractional
+    /// seconds and an optional `Z` or `±HH:MM` offset.
+    DateTime,
+    Text,
+}
+
+impl ColumnType {
+    pub fn name(self) -> &'static str {
+        match self {
+            ColumnType::Boolean => "boolean",
+            ColumnType::Integer => "integer",
+            ColumnType::Float => "float",
+            ColumnType::Date => "date",
+            ColumnType::DateTime => "datetime",
+            ColumnType::Text => "text",
+        }
+    }
+
+    /// The type of a single value, or `None` if it is empty.
+    pub fn of(value: &str) -> Option<Self> {
+        if value.is_empty() {
+            return None;
+        }
+        Some(if is_integer(value) {
+            ColumnType::Integer
+        } else if is_float(value) {
+            ColumnType::Float
+        } else if value.eq_ignore_ascii_case("true") || value.eq_ignore_ascii_case("false") {
+            ColumnType::Boolean
+        } else if is_date(value.as_bytes()) {
+            ColumnType::Date
+        } else if is_datetime(value.as_bytes()) {
+            ColumnType::DateTime
+        } else {
+            ColumnType::Text
+        })
+    }
+
+    /// The narrowest type covering values of both types.
+    pub fn merge(self, other: Self) -> Self {
+        use ColumnType::*;
+        match (self, other) {
+            (a, b) if a == b => a,
+            (Integer, Float) | (Float, Integer) => Float,
+            (Date, DateTime) | (DateTime, Date) => DateTime,
+            _ => Text,
+        }
+    }
+}
+
+impl fmt::Display for ColumnType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A column's inferred type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InferredColumn {
+    pub name: String,
+    /// [`ColumnType::Text`] if every sampled value was empty.
+    pub column_type: ColumnType,
+    /// Whether any sampled value was empty.
+    pub nullable: bool,
+}
+
+/// Accumulates the types of rows' fields, column by column.
+#[derive(Debug, Clone, Default)]
+pub struct TypeInference {
+    /// Merged type (None until a non-empty value) and whether empties were seen.
+    columns: Vec<(Option<ColumnType>, bool)>,
+}
+
+impl TypeInference {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn observe(&mut self, fields: &[String]) {
+        if self.columns.len() < fields.len() {
+            self.columns.resize(fields.len(), (None, false));
+        }
+        for (column, value) in self.columns.iter_mut().zip(fields) {
+            match ColumnType::of(value) {
+                Some(t) => column.0 = Some(column.0.map_or(t, |c| c.merge(t))),
+                None => column.1 = true,
+            }
+        }
+    }
+
+    /// Combine with inference over other rows.
+    pub fn merge(mut self, other: Self) -> Self {
+        if self.columns.len() < other.columns.len() {
+            self.columns.resize(other.columns.len(), (None, false));
+        }
+        for (column, (t, empty)) in self.columns.iter_mut().zip(other.columns) {
+            column.0 = match (column.0, t) {
+                (Some(a), Some(b)) => Some(a.merge(b)),
+                (a, b) => a.or(b),
+            };
+            column.1 |= empty;
+        }
+        self
+    }
+
+    /// Name the columns. Columns never observed are nullable text.
+    pub fn finish(&self, names: &[String]) -> Vec<InferredColumn> {
+        names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let (column_type, empty) = self.columns.get(i).copied().unwrap_or((None, true));
+                InferredColumn {
+                    name: name.clone(),
+                    column_type: column_type.unwrap_or(ColumnType::Text),
+                    nullable: empty || column_type.is_none(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Infer column types from up to `sample_rows` rows spread evenly over
+/// the file (all rows if it has fewer).
+pub fn infer_schema(reader: &CsvReader, sample_rows: usize) -> Result<Vec<InferredColumn>> {
+    let rows = sample_row_numbers(reader.row_count(), sample_rows);
+    let inference = rows
+        .into_par_iter()
+        .try_fold(TypeInference::new, |mut inference, row| {
+            inference.observe(&reader.get_row(row)?);
+            Ok::<_, MassiveCsvError>(inference)
+        })
+        .try_reduce(TypeInference::new, |a, b| Ok(a.merge(b)))?;
+    Ok(inference.finish(reader.canonical_headers()))
+}
+
+/// Up to `sample` row numbers spread evenly over `0..row_count`.
+pub(crate) fn sample_row_numbers(row_count: usize, sample: usize) -> Vec<usize> {
+    if row_count <= sample {
+        return (0..row_count).collect();
+    }
+    (0..sample).map(|i| i * row_count / sample).collect()
+}
+
+/// Parse `value` as an integer of type [`ColumnType::Integer`].
+pub(crate) fn parse_integer(value: &str) -> Option<i64> {
+    is_integer(value).then(|| value.parse().ok()).flatten()
+}
+
+fn is_integer(value: &str) -> bool {
+    let digits = value.strip_prefix('-').unwrap_or(value);
+    !digits.is_empty()
+        && digits.bytes().all(|b| b.is_ascii_digit())
+        && (digits == "0" || !digits.starts_with('0'))
+        && value.parse::<i64>().is_ok()
+}
+
+fn is_float(value: &str) -> bool {
+    let unsigned = value.strip_prefix('-').unwrap_or(value);
+    let (mantissa, exponent) = match unsigned.find(['e', 'E']) {
+        Some(i) => (&unsigned[..i], Some(&unsigned[i + 1..])),
+        None => (unsigned, None),
+    };
+    let (int, frac) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let all_digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+    let exponent_ok = exponent.is_none_or(|e| {
+        let e = e.strip_prefix(['-', '+']).unwrap_or(e);
+        !e.is_empty() && all_digits(e)
+    });
+    // Integers too large for i64 stay text rather than lose precision
+    (mantissa.contains('.') || exponent.is_some())
+        && (!int.is_empty() || !frac.is_empty())
+        && all_digits(int)
+        && all_digits(frac)
+        && (int.len() <= 1 || !int.starts_with('0'))
+        && exponent_ok
+}
+
+fn is_date(b: &[u8]) -> bool {
+    let digits = |range: std::ops::Range<usize>| b[range].iter().all(u8::is_ascii_digit);
+    if b.len() != 10 || b[4] != b'-' || b[7] != b'-' || !digits(0..4) {
+        return false;
+    }
+    if !digits(5..7) || !digits(8..10) {
+        return false;
+    }
+    let month = (b[5] - b'0') * 10 + (b[6] - b'0');
+    let day = (b[8] - b'0') * 10 + (b[9] - b'0');
+    (1..=12).contains(&month) && (1..=31).contains(&day)
+}
+
+fn is_datetime(b: &[u8]) -> bool {
+    if b.len() < 19 || !is_date(&b[..10]) || !matches!(b[10], b'T' | b' ') {
+        return false;
+    }
+    let time = &b[11..19];
+    let two = |i: usize| time[i].is_ascii_digit() && time[i + 1].is_ascii_digit();
+    if !(two(0) && time[2] == b':' && two(3) && time[5] == b':' && two(6)) {
+        return false;
+    }
+    let mut rest = &b[19..];
+    if let Some(frac) = rest.strip_prefix(b".") {
+        let n = frac.iter().take_while(|c| c.is_ascii_digit()).count();
+        if n == 0 {
+            return false;
+        }
+        rest = &frac[n..];
+    }
+    match rest {
+        [] | [b'Z'] => true,
+        [b'+' | b'-', h1, h2, b':', m1, m2] => [h1, h2, m1, m2].iter().all(|c| c.is_ascii_digit()),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn make_csv(content: &str) -> tempfile::NamedTempFile {
+        let mut f = tempfile::NamedTempFile::new().unwrap();
+        f.write_all(content.as_bytes()).unwrap();
+        f.flush().unwrap();
+        f
+    }
+
+    #[test]
+    fn classifies_values() {
+        use ColumnType::*;
+        let cases = [
+            ("42", Some(Integer)),
+            ("-7", Some(Integer)),
+            ("0", Some(Integer)),
+            ("007", Some(Text)),
+            ("99999999999999999999", Some(Text)),
+            ("3.14", Some(Float)),
+            ("-0.5", Some(Float)),
+            (".5", Some(Float)),
+            ("1e-3", Some(Float)),
+            ("1e", Some(Text)),
+            ("inf", Some(Text)),
+            ("TRUE", Some(Boolean)),
+            ("2024-02-29", Some(Date)),
+            ("2024-13-01", Some(Text)),
+            ("2024-02-29T12:30:00Z", Some(DateTime)),
+            ("2024-02-29 12:30:00.125+02:00", Some(DateTime)),
+            ("2024-02-29 12:30", Some(Text)),
+            ("", None),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(ColumnType::of(value), expected, "{value:?}");
+        }
+        assert_eq!(Integer.merge(Float), Float);
+        assert_eq!(Date.merge(DateTime), DateTime);
+        assert_eq!(Integer.merge(Boolean), Text);
+    }
+
+    #[test]
+    fn infers_file_schema() {
+        let f = make_csv("id,price,when,flag,note,blank\n1,2,2024-01-01,true,a,\n2,2.5,2024-01-02 10:00:00,,7,\n");
+        let reader = CsvReader::open(f.path()).unwrap();
+        let schema = infer_schema(&reader, DEFAULT_SAMPLE_ROWS).unwrap();
+        let types: Vec<(ColumnType, bool)> =
+            schema.iter().map(|c| (c.column_type, c.nullable)).collect();
+        use ColumnType::*;
+        assert_eq!(
+            types,
+            [
+                (Integer, false),
+                (Float, false),
+                (DateTime, false),
+                (Boolean, true),
+                (Text, false),
+                (Text, true),
+            ]
+        );
+        assert_eq!(schema[5].name, "blank");
+        assert_eq!(sample_row_numbers(10, 4), [0, 2, 5, 7]);
+    }
+}
@@ -0,0 +1,540 @@
+//! Joining two files on key columns.
+//!
+//! The right file is the build side. A fresh [`IndexStore`] index on its
+//! (first) key column is used as is; otherwise its rows are indexed as
+//! `(key hash, row)` pairs sorted by hash, which costs 16 bytes per row
+//! whatever the row width. Past [`JoinOptions::memory_limit`] entries that
+//! index is sorted in runs on disk, merged into one temporary file and
+//! memory-mapped, so the operating system pages it as needed. Left rows are
+//! then probed in parallel batches, and a candidate match is confirmed by
+//! comparing the actual key values, so hash collisions never join unrelated
+//! rows.
+
+use std::cmp::Reverse;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BinaryHeap;
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use memmap2::Mmap;
+use rayon::prelude::*;
+
+use crate::compression::{Compression, Compressor};
+use crate::editor::{write_atomic, LineWriter};
+use crate::error::{MassiveCsvError, Result};
+use crate::index::{ColumnIndex, IndexStore};
+use crate::parser::{canonical_headers, serialize_row};
+use crate::reader::CsvReader;
+
+/// Left rows probed per parallel batch.
+const CHUNK_ROWS: usize = 64 * 1024;
+
+/// Bytes per index entry: a hash and a row number, little-endian.
+const ENTRY_BYTES: usize = 16;
+
+/// A left row's fields with the right rows (and their fields) it matched.
+type Probe = (Vec<String>, Vec<(usize, Vec<String>)>);
+
+/// Which rows a join keeps besides matching pairs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum JoinType {
+    /// Only rows with a match on both sides.
+    #[default]
+    Inner,
+    /// Every left row; right columns are empty where nothing matches.
+    Left,
+    /// Every right row; left columns other than the keys are empty where
+    /// nothing matches.
+    Right,
+    /// Every row of both files.
+    Full,
+}
+
+impl JoinType {
+    /// Parse `inner`, `left`, `right` or `full`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "inner" => Some(JoinType::Inner),
+            "left" => Some(JoinType::Left),
+            "right" => Some(JoinType::Right),
+            "full" | "outer" => Some(JoinType::Full),
+            _ => None,
+        }
+    }
+}
+
+/// Options controlling [`join`].
+#[derive(Debug, Clone)]
+pub struct JoinOptions {
+    pub join_type: JoinType,
+    /// Key columns of the left file.
+    pub left_on: Vec<String>,
+    /// Key columns of the right file, paired with `left_on` (default: the
+    /// same names).
+    pub right_on: Vec<String>,
+    /// Compress the output (default: plain text).
+    pub compression: Compression,
+    /// Index entries kept in memory before the index spills to disk.
+    pub memory_limit: usize,
+}
+
+impl Default for JoinOptions {
+    fn default() -> Self {
+        Self {
+            join_type: JoinType::Inner,
+            left_on: Vec::new(),
+            right_on: Vec::new(),
+            compression: Compression::None,
+            // 256 MB of entries
+            memory_limit: 16 * 1024 * 1024,
+        }
+    }
+}
+
+/// What [`join`] wrote.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct JoinSummary {
+    pub rows_written: usize,
+    /// Left rows with at least one match.
+    pub left_matched: usize,
+    /// Left rows without a match (written only by left and full joins).
+    pub left_unmatched: usize,
+    /// Right rows no left row matched (written only by right and full joins).
+    pub right_unmatched: usize,
+    /// Whether the right file's index spilled to disk.
+    pub spilled: bool,
+    /// Whether a stored index on the right key column was used instead of
+    /// building one.
+    pub indexed: bool,
+}
+
+/// Join `left` and `right` on key columns into a CSV at `output`, in the
+/// left file's dialect.
+///
+/// Output columns are the left file's, then the right file's without its key
+/// columns; a right column named like a left one gets a `_right` suffix.
+/// Rows come in left file order, each left row followed by all its matches;
+/// right and full joins then add the unmatched right rows in their order.
+pub fn join(
+    left: &CsvReader,
+    right: &CsvReader,
+    output: &Path,
+    options: &JoinOptions,
+) -> Result<JoinSummary> {
+    if options.left_on.is_empty() {
+        return Err(MassiveCsvError::Parse("no join key columns".to_string()));
+    }
+    let right_on = if options.right_on.is_empty() {
+        &options.left_on
+    } else {
+        &options.right_on
+    };
+    if right_on.len() != options.left_on.len() {
+        return Err(MassiveCsvError::Parse(format!(
+            "{} left key column(s) but {} right",
+            options.left_on.len(),
+            right_on.len()
+        )));
+    }
+    let left_keys = key_positions(left, &options.left_on)?;
+    let right_keys = key_positions(right, right_on)?;
+    let right_rest: Vec<usize> = (0..right.headers().len())
+        .filter(|c| !right_keys.contains(c))
+        .collect();
+
+    let mut headers = left.canonical_headers().to_vec();
+    let right_headers = right.canonical_headers();
+    headers.extend(right_rest.iter().map(|&c| {
+        let name = &right_headers[c];
+        if left.canonical_headers().contains(name) {
+            format!("{name}_right")
+        } else {
+            name.clone()
+        }
+    }));
+    let headers = canonical_headers(&headers);
+    let left_width = left.headers().len();
+
+    let index = RightIndex::build(right, &right_keys, options.memory_limit)?;
+    let keep_left = matches!(options.join_type, JoinType::Left | JoinType::Full);
+    let keep_right = matches!(options.join_type, JoinType::Right | JoinType::Full);
+    let mut right_matched = vec![false; right.row_count()];
+    let mut summary = JoinSummary {
+        spilled: index.is_spilled(),
+        indexed: matches!(index, RightIndex::Stored(_)),
+        ..JoinSummary::default()
+    };
+    let dialect = left.dialect();
+
+    write_atomic(output, |writer| {
+        let mut compressor = Compressor::new(writer, options.compression)?;
+        let mut lines = LineWriter::new(&mut compressor, left.line_ending());
+        lines.write_line(&serialize_row(&headers, dialect))?;
+
+        let mut start = 0;
+        while start < left.row_count() {
+            let end = (start + CHUNK_ROWS).min(left.row_count());
+            let batch: Vec<Probe> = (start..end)
+                .into_par_iter()
+                .map(|row| {
+                    let mut fields = left.get_row(row)?;
+                    fields.resize(left_width, String::new());
+                    let key = key_of(&fields, &left_keys);
+                    let mut matches = Vec::new();
+                    for candidate in index.candidates(&key) {
+                        let right_fields = right.get_row(candidate)?;
+                        if key_of(&right_fields, &right_keys) == key {
+                            matches.push((candidate, right_fields));
+                        }
+                    }
+                    Ok((fields, matches))
+                })
+                .collect::<Result<_>>()?;
+
+            for (fields, matches) in batch {
+                if matches.is_empty() {
+                    summary.left_unmatched += 1;
+                    if keep_left {
+                        let mut out = fields;
+                        out.resize(headers.len(), String::new());
+                        lines.write_line(&serialize_row(&out, dialect))?;
+                        summary.rows_written += 1;
+                    }
+                    continue;
+                }
+                summary.left_matched += 1;
+                for (right_row, right_fields) in matches {
+                    right_matched[right_row] = true;
+                    let mut out = fields.clone();
+                    out.extend(
+                        right_rest
+                            .iter()
+                            .map(|&c| right_fields.get(c).cloned().unwrap_or_default()),
+                    );
+                    lines.write_line(&serialize_row(&out, dialect))?;
+                    summary.rows_written += 1;
+                }
+            }
+            start = end;
+        }
+
+        summary.right_unmatched = right_matched.iter().filter(|&&m| !m).count();
+        if keep_right {
+            let unmatched = right_matched.iter().enumerate().filter(|(_, &m)| !m);
+            for (row, _) in unmatched {
+                let right_fields = right.get_row(row)?;
+                // The key goes in the left key columns
+                let mut out = vec![String::new(); left_width];
+                for (&l, &r) in left_keys.iter().zip(&right_keys) {
+                    out[l] = right_fields.get(r).cloned().unwrap_or_default();
+                }
+                out.extend(
+                    right_rest
+                        .iter()
+                        .map(|&c| right_fields.get(c).cloned().unwrap_or_default()),
+                );
+                lines.write_line(&serialize_row(&out, dialect))?;
+                summary.rows_written += 1;
+            }
+        }
+
+        lines.finish(true)?;
+        compressor.finish()
+    })?;
+
+    Ok(summary)
+}
+
+fn key_positions(reader: &CsvReader, names: &[String]) -> Result<Vec<usize>> {
+    names
+        .iter()
+        .map(|name| {
+            reader
+                .column_index(name)
+                .ok_or_else(|| MassiveCsvError::ColumnNotFound(name.clone()))
+        })
+        .collect()
+}
+
+fn key_of<'a>(fields: &'a [String], positions: &[usize]) -> Vec<&'a str> {
+    positions
+        .iter()
+        .map(|&c| fields.get(c).map_or("", String::as_str))
+        .collect()
+}
+
+fn hash_key(key: &[&str]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// The right file's rows by key: a stored index of the first key column,
+/// or `(hash, row)` pairs sorted by hash.
+enum RightIndex {
+    Stored(ColumnIndex),
+    Memory(Vec<(u64, u64)>),
+    Disk(Mmap),
+}
+
+impl RightIndex {
+    fn build(reader: &CsvReader, keys: &[usize], memory_limit: usize) -> Result<Self> {
+        let store = IndexStore::for_file(reader.path());
+        if let Ok(Some(index)) = store.open(reader, keys[0]) {
+            return Ok(RightIndex::Stored(index));
+        }
+
+        let memory_limit = memory_limit.max(1);
+        let entries = |range: std::ops::Range<usize>| -> Result<Vec<(u64, u64)>> {
+            let mut entries: Vec<(u64, u64)> = range
+                .into_par_iter()
+                .map(|row| {
+                    let fields = reader.get_row(row)?;
+                    Ok((hash_key(&key_of(&fields, keys)), row as u64))
+                })
+                .collect::<Result<_>>()?;
+            entries.par_sort_unstable();
+            Ok(entries)
+        };
+
+        let rows = reader.row_count();
+        if rows <= memory_limit {
+            return Ok(RightIndex::Memory(entries(0..rows)?));
+        }
+
+        // Sorted runs on disk, then a k-way merge into one file
+        let mut runs = Vec::new();
+        let mut start = 0;
+        while start < rows {
+            let end = (start + memory_limit).min(rows);
+            let mut run = BufWriter::new(tempfile::tempfile()?);
+            for (hash, row) in entries(start..end)? {
+                write_entry(&mut run, hash, row)?;
+            }
+            let mut run = run.into_inner().map_err(|e| e.into_error())?;
+            std::io::Seek::rewind(&mut run)?;
+            runs.push(BufReader::new(run));
+            start = end;
+        }
+
+        let mut heap = BinaryHeap::new();
+        for (i, run) in runs.iter_mut().enumerate() {
+            if let Some(entry) = read_entry(run)? {
+                heap.push(Reverse((entry, i)));
+            }
+        }
+        let mut merged = BufWriter::new(tempfile::tempfile()?);
+        while let Some(Reverse(((hash, row), i))) = heap.pop() {
+            write_entry(&mut merged, hash, row)?;
+            if let Some(entry) = read_entry(&mut runs[i])? {
+                heap.push(Reverse((entry, i)));
+            }
+        }
+        let merged: File = merged.into_inner().map_err(|e| e.into_error())?;
+        // SAFETY: the temporary file is unnamed, so nothing else can modify it.
+        let map = unsafe { Mmap::map(&merged)? };
+        Ok(RightIndex::Disk(map))
+    }
+
+    fn is_spilled(&self) -> bool {
+        matches!(self, RightIndex::Disk(_))
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            RightIndex::Stored(_) => 0,
+            RightIndex::Memory(entries) => entries.len(),
+            RightIndex::Disk(map) => map.len() / ENTRY_BYTES,
+        }
+    }
+
+    fn entry(&self, i: usize) -> (u64, u64) {
+        match self {
+            RightIndex::Stored(_) => unreachable!("a stored index has no hash entries"),
+            RightIndex::Memory(entries) => entries[i],
+            RightIndex::Disk(map) => {
+                let bytes = &map[i * ENTRY_BYTES..(i + 1) * ENTRY_BYTES];
+                let word = |at: usize| u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap());
+                (word(0), word(8))
+            }
+        }
+    }
+
+    /// Right rows that may have `key`, in row order: those whose first key
+    /// value matches, or whose key hashes the same.
+    fn candidates(&self, key: &[&str]) -> Vec<usize> {
+        if let RightIndex::Stored(index) = self {
+            return index.lookup(key[0]);
+        }
+        let hash = hash_key(key);
+        // First entry with a hash of at least `hash`
+        let (mut low, mut high) = (0, self.len());
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if self.entry(mid).0 < hash {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        (low..self.len())
+            .map(|i| self.entry(i))
+            .take_while(|&(h, _)| h == hash)
+            .map(|(_, row)| row as usize)
+            .collect()
+    }
+}
+
+fn write_entry(out: &mut impl Write, hash: u64, row: u64) -> Result<()> {
+    out.write_all(&hash.to_le_bytes())?;
+    out.write_all(&row.to_le_bytes())?;
+    Ok(())
+}
+
+fn read_entry(input: &mut impl Read) -> Result<Option<(u64, u64)>> {
+    let mut bytes = [0u8; ENTRY_BYTES];
+    match input.read_exact(&mut bytes) {
+        Ok(()) => Ok(Some((
+            u64::from_le_bytes(bytes[..8].try_into().unwrap()),
+            u64::from_le_bytes(bytes[8..].try_into().unwrap()),
+        ))),
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn make_csv(content: &str) -> tempfile::NamedTempFile {
+        let mut f = tempfile::NamedTempFile::new().unwrap();
+        f.write_all(content.as_bytes()).unwrap();
+        f.flush().unwrap();
+        f
+    }
+
+    fn run(join_type: JoinType, memory_limit: usize) -> (String, JoinSummary) {
+        let left = make_csv("order,customer_id,total\n1,c1,10\n2,c2,20\n3,c9,30\n4,c1,40\n");
+        let right =
+            make_csv("customer_id,name,total\nc1,Ann,100\nc2,Bo,200\nc3,Cy,300\nc2,Bob,250\n");
+        let left = CsvReader::open(left.path()).unwrap();
+        let right = CsvReader::open(right.path()).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("joined.csv");
+        let options = JoinOptions {
+            join_type,
+            left_on: vec!["customer_id".to_string()],
+            memory_limit,
+            ..JoinOptions::default()
+        };
+        let summary = join(&left, &right, &output, &options).unwrap();
+        (std::fs::read_to_string(&output).unwrap(), summary)
+    }
+
+    #[test]
+    fn inner_and_left_joins() {
+        let (csv, summary) = run(JoinType::Inner, usize::MAX);
+        assert_eq!(
+            csv,
+            "order,customer_id,total,name,total_right\n\
+             1,c1,10,Ann,100\n\
+             2,c2,20,Bo,200\n\
+             2,c2,20,Bob,250\n\
+             4,c1,40,Ann,100\n"
+        );
+        assert_eq!(
+            summary,
+            JoinSummary {
+                rows_written: 4,
+                left_matched: 3,
+                left_unmatched: 1,
+                right_unmatched: 1,
+                spilled: false,
+                indexed: false,
+            }
+        );
+
+        let (csv, _) = run(JoinType::Left, usize::MAX);
+        assert!(csv.contains("\n3,c9,30,,\n"));
+        assert!(!csv.contains("c3"));
+    }
+
+    #[test]
+    fn right_and_full_joins() {
+        let (csv, _) = run(JoinType::Right, usize::MAX);
+        assert!(!csv.contains("c9"));
+        assert!(csv.ends_with("4,c1,40,Ann,100\n,c3,,Cy,300\n"));
+
+        let (csv, summary) = run(JoinType::Full, usize::MAX);
+        assert!(csv.contains("\n3,c9,30,,\n"));
+        assert!(csv.ends_with(",c3,,Cy,300\n"));
+        assert_eq!(summary.rows_written, 6);
+    }
+
+    #[test]
+    fn spilled_index_gives_the_same_result() {
+        let (in_memory, _) = run(JoinType::Full, usize::MAX);
+        let (spilled, summary) = run(JoinType::Full, 1);
+        assert!(summary.spilled);
+        assert_eq!(spilled, in_memory);
+    }
+
+    #[test]
+    fn uses_a_stored_index_on_the_right_key() {
+        let left = make_csv("id,v\n1,a\n2,b\n2,c\n");
+        let right = make_csv("w,ref\nx,2\ny,1\nz,2\n");
+        let left = CsvReader::open(left.path()).unwrap();
+        let right = CsvReader::open(right.path()).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("joined.csv");
+        let options = JoinOptions {
+            left_on: vec!["id".to_string()],
+            right_on: vec!["ref".to_string()],
+            ..JoinOptions::default()
+        };
+        let hashed = join(&left, &right, &output, &options).unwrap();
+        let expected = std::fs::read_to_string(&output).unwrap();
+        assert!(!hashed.indexed);
+
+        let store = IndexStore::for_file(right.path());
+        store.build(&right, "ref").unwrap();
+        let indexed = join(&left, &right, &output, &options).unwrap();
+        store.remove(&right, "ref").unwrap();
+        assert!(indexed.indexed);
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), expected);
+        assert_eq!(
+            JoinSummary {
+                indexed: false,
+                ..indexed
+            },
+            hashed
+        );
+    }
+
+    #[test]
+    fn joins_on_differently_named_keys() {
+        let left = make_csv("id,v\n1,a\n2,b\n");
+        let right = make_csv("ref,w\n2,x\n");
+        let left = CsvReader::open(left.path()).unwrap();
+        let right = CsvReader::open(right.path()).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("joined.csv");
+        let mut options = JoinOptions {
+            left_on: vec!["id".to_string()],
+            right_on: vec!["ref".to_string()],
+            ..JoinOptions::default()
+        };
+        join(&left, &right, &output, &options).unwrap();
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "id,v,w\n2,b,x\n");
+
+        options.right_on = vec!["nope".to_string()];
+        assert!(matches!(
+            join(&left, &right, &output, &options),
+            Err(MassiveCsvError::ColumnNotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,249 @@
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use crate::error::{MassiveCsvError, Result};
+use crate::order::RowOrder;
+use crate::parser::serialize_row;
+use crate::reader::FileFingerprint;
+use crate::reshape::ColumnChange;
+
+/// First field of the first record of every journal file.
+const JOURNAL_MAGIC: &str = "massive-csv-journal";
+/// Journal format version written by this crate.
+const JOURNAL_VERSION: &str = "1";
+
+/// Last field of every entry; an entry without it was cut short by a crash.
+const END_MARKER: &str = "$";
+
+/// A single recorded editor operation. Rows are file rows, not positions
+/// in the editor's current row order; inserted rows are numbered from the
+/// file's row count up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum JournalEntry {
+    SetRow(usize, Vec<String>),
+    RevertRow(usize),
+    RevertAll,
+    Columns(ColumnChange),
+    /// The whole row order after a move, insert or delete, as runs of file
+    /// rows.
+    Order(Vec<Range<usize>>),
+}
+
+/// Append-only write-ahead log of unsaved edits, stored as `<file>.journal`.
+///
+/// Each entry is written straight to the OS as it is made, so edits survive a
+/// crash of the process (not of the machine). The first record holds the
+/// fingerprint of the file the edits apply to.
+pub(crate) struct Journal {
+    file: File,
+    path: PathBuf,
+}
+
+impl Journal {
+    /// Journal location for the data file at `path`.
+    pub fn path_for(path: &Path) -> PathBuf {
+        let mut name = path.as_os_str().to_os_string();
+        name.push(".journal");
+        PathBuf::from(name)
+    }
+
+    /// Start a fresh journal for `fingerprint`, replacing any existing one,
+    /// and record `changes`, `order` and `edits` as its initial entries.
+    pub fn create<'a>(
+        path: PathBuf,
+        fingerprint: &FileFingerprint,
+        changes: impl IntoIterator<Item = &'a ColumnChange>,
+        order: Option<&RowOrder>,
+        edits: &HashMap<usize, Vec<String>>,
+    ) -> Result<Self> {
+        let file = File::create(&path)?;
+        let mut journal = Self { file, path };
+
+        let header = [
+            JOURNAL_MAGIC.to_string(),
+            JOURNAL_VERSION.to_string(),
+            fingerprint.len.to_string(),
+            fingerprint.sample_hash.to_string(),
+        ];
+        journal.write_record(&header)?;
+
+        for change in changes {
+            journal.append(&JournalEntry::Columns(change.clone()))?;
+        }
+        if let Some(order) = order {
+            journal.append(&JournalEntry::Order(order.runs().to_vec()))?;
+        }
+        let mut rows: Vec<&usize> = edits.keys().collect();
+        rows.sort();
+        for row in rows {
+            journal.append(&JournalEntry::SetRow(*row, edits[row].clone()))?;
+        }
+        Ok(journal)
+    }
+
+    /// Continue appending to an existing journal.
+    pub fn reopen(path: PathBuf) -> Result<Self> {
+        let file = OpenOptions::new().append(true).open(&path)?;
+        Ok(Self { file, path })
+    }
+
+    /// Record one operation.
+    pub fn append(&mut self, entry: &JournalEntry) -> Result<()> {
+        let mut record = match entry {
+            JournalEntry::SetRow(row, fields) => {
+                let mut record = vec!["set".to_string(), row.to_string(), fields.len().to_string()];
+                record.extend(fields.iter().cloned());
+                record
+            }
+            JournalEntry::RevertRow(row) => vec!["revert".to_string(), row.to_string()],
+            JournalEntry::RevertAll => vec!["revert_all".to_string()],
+            JournalEntry::Columns(ColumnChange::Split {
+                column,
+                delimiter,
+                names,
+            }) => {
+                let mut record = vec!["split".to_string(), column.clone(), delimiter.clone()];
+                record.extend(names.iter().cloned());
+                record
+            }
+            JournalEntry::Columns(ColumnChange::Merge {
+                columns,
+                separator,
+                name,
+            }) => {
+                let mut record = vec!["merge".to_string(), name.clone(), separator.clone()];
+                record.extend(columns.iter().cloned());
+                record
+            }
+            JournalEntry::Columns(ColumnChange::Add { name, default }) => {
+                vec!["add".to_string(), name.clone(), default.clone()]
+            }
+            JournalEntry::Columns(ColumnChange::Drop { column }) => {
+                vec!["drop".to_string(), column.clone()]
+            }
+            JournalEntry::Columns(ColumnChange::Rename { from, to }) => {
+                vec!["rename".to_string(), from.clone(), to.clone()]
+            }
+            JournalEntry::Order(runs) => {
+                let mut record = vec!["order".to_string()];
+                for run in runs {
+                    record.push(run.start.to_string());
+                    record.push(run.end.to_string());
+                }
+                record
+            }
+        };
+        record.push(END_MARKER.to_string());
+        self.write_record(&record)
+    }
+
+    /// Delete the journal file.
+    pub fn remove(self) -> Result<()> {
+        drop(self.file);
+        remove_if_exists(&self.path)
+    }
+
+    fn write_record(&mut self, fields: &[String]) -> Result<()> {
+        let mut line = serialize_row(fields, b',');
+        line.push('\n');
+        // One write per record keeps entries whole except for a crash mid-write
+        self.file.write_all(line.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// Read a journal, returning the recorded (len, sample hash) and its entries.
+///
+/// A truncated final entry (left by a crash mid-write) is ignored.
+pub(crate) fn read_journal(path: &Path) -> Result<((u64, u64), Vec<JournalEntry>)> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(BufReader::new(File::open(path)?));
+    let mut records = reader.records();
+
+    let invalid = || MassiveCsvError::Parse(format!("invalid journal file {}", path.display()));
+    let header = records.next().ok_or_else(invalid)??;
+    if header.get(0) != Some(JOURNAL_MAGIC) || header.get(1) != Some(JOURNAL_VERSION) {
+        return Err(invalid());
+    }
+    let len: u64 = header
+        .get(2)
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(invalid)?;
+    let hash: u64 = header
+        .get(3)
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(invalid)?;
+
+    let mut entries = Vec::new();
+    for record in records {
+        let Ok(record) = record else { break };
+        let mut record: Vec<&str> = record.iter().collect();
+        if record.pop() != Some(END_MARKER) {
+            break;
+        }
+
+        let row = record.get(1).and_then(|s| s.parse::<usize>().ok());
+        let entry = match (record.first().copied(), row) {
+            (Some("set"), Some(row)) => {
+                let count = record.get(2).and_then(|s| s.parse::<usize>().ok());
+                let fields: Vec<String> = record.iter().skip(3).map(|f| f.to_string()).collect();
+                if count != Some(fields.len()) {
+                    break;
+                }
+                JournalEntry::SetRow(row, fields)
+            }
+            (Some("revert"), Some(row)) => JournalEntry::RevertRow(row),
+            (Some("revert_all"), _) => JournalEntry::RevertAll,
+            (Some("split"), _) if record.len() > 3 => JournalEntry::Columns(ColumnChange::Split {
+                column: record[1].to_string(),
+                delimiter: record[2].to_string(),
+                names: record[3..].iter().map(|s| s.to_string()).collect(),
+            }),
+            (Some("order"), _) => {
+                let bounds: Option<Vec<usize>> =
+                    record[1..].iter().map(|s| s.parse().ok()).collect();
+                match bounds {
+                    Some(bounds) if bounds.len() % 2 == 0 => {
+                        JournalEntry::Order(bounds.chunks(2).map(|b| b[0]..b[1]).collect())
+                    }
+                    _ => break,
+                }
+            }
+            (Some("merge"), _) if record.len() > 3 => JournalEntry::Columns(ColumnChange::Merge {
+                name: record[1].to_string(),
+                separator: record[2].to_string(),
+                columns: record[3..].iter().map(|s| s.to_string()).collect(),
+            }),
+            (Some("add"), _) if record.len() == 3 => JournalEntry::Columns(ColumnChange::Add {
+                name: record[1].to_string(),
+                default: record[2].to_string(),
+            }),
+            (Some("drop"), _) if record.len() == 2 => JournalEntry::Columns(ColumnChange::Drop {
+                column: record[1].to_string(),
+            }),
+            (Some("rename"), _) if record.len() == 3 => {
+                JournalEntry::Columns(ColumnChange::Rename {
+                    from: record[1].to_string(),
+                    to: record[2].to_string(),
+                })
+            }
+            _ => break,
+        };
+        entries.push(entry);
+    }
+
+    Ok(((len, hash), entries))
+}
+
+pub(crate) fn remove_if_exists(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
@@ -1,14 +1,135 @@
+//! Memory-mapped reading, searching and editing of massive CSV files.
+//!
+//! Work over rows runs in parallel on rayon, but output never depends on
+//! scheduling: search results, filter matches, validation issues, repairs,
+//! exports, pipeline output and patches all come back in row order, and
+//! limits such as `max_results` keep the first rows that qualify. The same
+//! input gives the same output on every run and thread count.
+
+#[cfg(feature = "anonymize")]
+pub mod anonymize;
+pub mod arrow;
+pub mod background;
+pub mod bloom;
+#[cfg(feature = "checksum")]
+pub mod checksum;
+pub mod compression;
+pub mod computed;
+pub mod concat;
+pub mod dates;
+pub mod dedupe;
+pub mod diff;
 pub mod editor;
+pub mod edits;
+pub mod encoding;
 pub mod error;
+pub mod export;
+pub mod filter;
+#[cfg(feature = "http")]
+pub mod http;
+pub mod index;
+pub mod infer;
+pub mod join;
+mod journal;
+pub mod ndjson;
+mod order;
 pub mod parser;
+#[cfg(feature = "pipelines")]
+pub mod pipeline;
+#[cfg(feature = "plugins")]
+pub mod plugin;
+pub mod query;
 pub mod reader;
+pub mod record;
+pub mod remote;
+pub mod repair;
+pub mod reshape;
+#[cfg(feature = "s3")]
+pub mod s3;
+pub mod sample;
+#[cfg(feature = "schema")]
+pub mod schema;
+#[cfg(feature = "scripting")]
+pub mod script;
 pub mod searcher;
+pub mod sort;
+pub mod split;
+pub mod sqlite;
+pub mod stats;
+pub mod validate;
+#[cfg(feature = "versions")]
+pub mod versions;
+pub mod view;
+#[cfg(feature = "views")]
+pub mod view_store;
+#[cfg(feature = "xlsx")]
+pub mod xlsx;
 
-pub use editor::CsvEditor;
+#[cfg(feature = "anonymize")]
+pub use anonymize::{anonymize, map_columns, Mask};
+pub use arrow::IpcWriter;
+pub use background::{BuildHandle, BuildStatus, Progress};
+pub use bloom::BloomFilter;
+#[cfg(feature = "checksum")]
+pub use checksum::{checksum, Checksum, ChecksumOptions};
+pub use compression::Compression;
+pub use computed::{materialize, ComputedColumn, ComputedColumns};
+pub use concat::{concat, ConcatOptions, ConcatSummary};
+pub use dates::{
+    detect_date_format, normalize_dates, DateError, DateFormat, DateTarget, DateValue,
+    NormalizeDatesOptions, NormalizeDatesReport,
+};
+pub use dedupe::{dedupe, find_duplicates, DedupeSummary, DuplicateGroup, Keep};
+pub use diff::{Patch, PatchOp, PatchSummary};
+pub use editor::{CsvEditor, Durability, MemoryUsage, PatchOutput, RowDiff, SaveOptions};
+pub use edits::{read_cell_edits, CellEdit};
+pub use encoding::Encoding;
 pub use error::{MassiveCsvError, Result};
-pub use parser::Delimiter;
-pub use reader::CsvReader;
-pub use searcher::{SearchOptions, SearchResult};
+pub use export::{ExportFormat, ExportOptions, ExportSummary, Provenance};
+pub use filter::{Expr, Filter};
+#[cfg(feature = "http")]
+pub use http::HttpSource;
+pub use index::{ColumnIndex, IndexInfo, IndexStore};
+pub use infer::{infer_schema, ColumnType, InferredColumn, TypeInference};
+pub use join::{join, JoinOptions, JoinSummary, JoinType};
+pub use parser::{
+    Delimiter, DetectOptions, Detection, Dialect, EscapeStyle, FixedWidth, LineEnding, QuoteStyle,
+};
+#[cfg(feature = "pipelines")]
+pub use pipeline::{LineageManifest, PipelineSpec, Step};
+#[cfg(feature = "plugins")]
+pub use plugin::Plugin;
+pub use query::{query, Query, QueryResult, RowExpr};
+#[cfg(feature = "serde")]
+pub use reader::DeserializeRows;
+pub use reader::{CsvReader, FileFingerprint, OpenBuilder, OpenOptions};
+pub use record::{HeaderMap, Record};
+pub use remote::{RangeSource, RemoteReader};
+pub use repair::{CsvRepair, LongRows, RepairAction, RepairReport, RowRepair};
+pub use reshape::ColumnChange;
+#[cfg(feature = "s3")]
+pub use s3::{S3Config, S3Credentials, S3Source};
+pub use sample::{sample, SampleMethod};
+#[cfg(feature = "schema")]
+pub use schema::{validate_schema, ColumnSchema, Schema, SchemaReport, Violation, ViolationKind};
+#[cfg(feature = "scripting")]
+pub use script::RowScript;
+pub use searcher::{FieldMatch, SearchOptions, SearchPage, SearchResult};
+pub use sort::{sort, KeyKind, SortKey, SortOptions, SortSummary};
+pub use split::{split_by_column, split_rows, SplitPart};
+pub use sqlite::SqliteWriter;
+pub use stats::{column_stats, value_counts, ColumnStats, NumericStats, ValueCounts};
+pub use validate::{validate, Issue, IssueKind, ValidateOptions, ValidationReport};
+#[cfg(feature = "versions")]
+pub use versions::{Version, VersionStore};
+pub use view::{select_columns, CsvView};
+#[cfg(feature = "views")]
+pub use view_store::{Bookmark, SavedView, ViewStore};
+#[cfg(feature = "xlsx")]
+pub use xlsx::XlsxWriter;
+
+/// Directory (next to the data file) holding massive-csv metadata.
+pub const META_DIR: &str = ".massive-csv";
 
 /// Search convenience function re-exported at crate root.
 pub fn search(
@@ -18,3 +139,12 @@ pub fn search(
 ) -> Result<Vec<SearchResult>> {
     searcher::search(reader, query, options)
 }
+
+/// Search returning [`Record`]s, re-exported at crate root.
+pub fn search_records(
+    reader: &CsvReader,
+    query: &str,
+    options: &SearchOptions,
+) -> Result<Vec<Record>> {
+    searcher::search_records(reader, query, options)
+}
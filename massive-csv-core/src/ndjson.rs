@@ -0,0 +1,266 @@
+//! Reading JSON Lines (NDJSON) as a table.
+//!
+//! Each non-blank line must be a JSON object. The columns are every key
+//! seen, in order of first appearance; nested objects are flattened into
+//! dotted names (`{"user": {"id": 1}}` gives a `user.id` column). Strings
+//! are taken as they are, numbers and booleans as their JSON text, arrays
+//! as compact JSON, and `null` or a missing key as an empty field.
+//!
+//! The data is converted to CSV in a temporary file, so everything that
+//! works on a [`CsvReader`](crate::CsvReader) works on the result; see
+//! [`CsvReader::from_ndjson`](crate::CsvReader::from_ndjson). Needs the
+//! `ndjson` feature.
+
+use std::io::Write;
+use std::path::Path;
+
+use crate::error::Result;
+
+/// Whether `path` is named like a JSON Lines file: `.ndjson`, `.jsonl` or
+/// `.json-lines`, optionally followed by `.gz` or `.zst`.
+pub fn is_ndjson_path(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+        return false;
+    };
+    let name = name.to_ascii_lowercase();
+    let name = name
+        .strip_suffix(".gz")
+        .or_else(|| name.strip_suffix(".zst"))
+        .unwrap_or(&name);
+    [".ndjson", ".jsonl", ".json-lines"]
+        .iter()
+        .any(|ext| name.ends_with(ext))
+}
+
+/// Rows and columns found by [`ndjson_to_csv`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NdjsonSummary {
+    pub rows: usize,
+    pub columns: usize,
+}
+
+/// Convert NDJSON `data` to comma-separated CSV with a header line. Reads
+/// `data` twice: once to collect the columns, once to write the rows.
+#[cfg(feature = "ndjson")]
+pub fn ndjson_to_csv(data: &[u8], out: &mut dyn Write) -> Result<NdjsonSummary> {
+    use std::collections::HashMap;
+
+    use crate::parser::serialize_row;
+
+    let mut columns: Vec<String> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    for object in objects(data) {
+        flatten(object?, "", &mut |key, _| {
+            if !positions.contains_key(&key) {
+                positions.insert(key.clone(), columns.len());
+                columns.push(key);
+            }
+        });
+    }
+    if columns.is_empty() {
+        return Ok(NdjsonSummary {
+            rows: 0,
+            columns: 0,
+        });
+    }
+
+    out.write_all(serialize_row(&columns, b',').as_bytes())?;
+    out.write_all(b"\n")?;
+    let mut rows = 0;
+    let mut fields = vec![String::new(); columns.len()];
+    for object in objects(data) {
+        fields.iter_mut().for_each(String::clear);
+        flatten(object?, "", &mut |key, value| {
+            fields[positions[&key]] = value
+        });
+        out.write_all(serialize_row(&fields, b',').as_bytes())?;
+        out.write_all(b"\n")?;
+        rows += 1;
+    }
+    Ok(NdjsonSummary {
+        rows,
+        columns: columns.len(),
+    })
+}
+
+#[cfg(not(feature = "ndjson"))]
+pub fn ndjson_to_csv(_data: &[u8], _out: &mut dyn Write) -> Result<NdjsonSummary> {
+    Err(crate::error::MassiveCsvError::FeatureDisabled(
+        "Reading NDJSON",
+        "ndjson",
+    ))
+}
+
+/// The object on each non-blank line.
+#[cfg(feature = "ndjson")]
+fn objects(data: &[u8]) -> impl Iterator<Item = Result<Vec<(String, Json)>>> + '_ {
+    use crate::error::MassiveCsvError;
+
+    data.split(|&b| b == b'\n')
+        .enumerate()
+        .filter(|(_, line)| !line.trim_ascii().is_empty())
+        .map(|(i, line)| {
+            let line_number = i + 1;
+            match serde_json::from_slice::<Json>(line) {
+                Ok(Json::Object(fields)) => Ok(fields),
+                Ok(Json::Value(_)) => Err(MassiveCsvError::Parse(format!(
+                    "line {line_number}: expected a JSON object"
+                ))),
+                Err(e) => Err(MassiveCsvError::Parse(format!("line {line_number}: {e}"))),
+            }
+        })
+}
+
+/// Call `visit` with each leaf of `object` as a column name and field.
+#[cfg(feature = "ndjson")]
+fn flatten(object: Vec<(String, Json)>, prefix: &str, visit: &mut dyn FnMut(String, String)) {
+    for (key, value) in object {
+        let key = if prefix.is_empty() {
+            key
+        } else {
+            format!("{prefix}.{key}")
+        };
+        match value {
+            Json::Object(fields) => flatten(fields, &key, visit),
+            Json::Value(serde_json::Value::Null) => visit(key, String::new()),
+            Json::Value(serde_json::Value::String(s)) => visit(key, s),
+            Json::Value(other) => visit(key, other.to_string()),
+        }
+    }
+}
+
+/// A JSON value whose objects keep their keys in file order, which
+/// `serde_json::Value` only does with its `preserve_order` feature.
+#[cfg(feature = "ndjson")]
+enum Json {
+    Object(Vec<(String, Json)>),
+    Value(serde_json::Value),
+}
+
+#[cfg(feature = "ndjson")]
+impl<'de> serde::Deserialize<'de> for Json {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        use serde::de::{MapAccess, SeqAccess, Visitor};
+        use serde_json::Value;
+
+        struct JsonVisitor;
+
+        impl<'de> Visitor<'de> for JsonVisitor {
+            type Value = Json;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a JSON value")
+            }
+
+            fn visit_bool<E>(self, v: bool) -> std::result::Result<Json, E> {
+                Ok(Json::Value(Value::Bool(v)))
+            }
+
+            fn visit_i64<E>(self, v: i64) -> std::result::Result<Json, E> {
+                Ok(Json::Value(Value::from(v)))
+            }
+
+            fn visit_u64<E>(self, v: u64) -> std::result::Result<Json, E> {
+                Ok(Json::Value(Value::from(v)))
+            }
+
+            fn visit_f64<E>(self, v: f64) -> std::result::Result<Json, E> {
+                Ok(Json::Value(Value::from(v)))
+            }
+
+            fn visit_str<E>(self, v: &str) -> std::result::Result<Json, E> {
+                Ok(Json::Value(Value::String(v.to_string())))
+            }
+
+            fn visit_string<E>(self, v: String) -> std::result::Result<Json, E> {
+                Ok(Json::Value(Value::String(v)))
+            }
+
+            fn visit_unit<E>(self) -> std::result::Result<Json, E> {
+                Ok(Json::Value(Value::Null))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(
+                self,
+                mut seq: A,
+            ) -> std::result::Result<Json, A::Error> {
+                let mut items = Vec::new();
+                while let Some(item) = seq.next_element::<Value>()? {
+                    items.push(item);
+                }
+                Ok(Json::Value(Value::Array(items)))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(
+                self,
+                mut map: A,
+            ) -> std::result::Result<Json, A::Error> {
+                let mut fields = Vec::new();
+                while let Some(entry) = map.next_entry::<String, Json>()? {
+                    fields.push(entry);
+                }
+                Ok(Json::Object(fields))
+            }
+        }
+
+        deserializer.deserialize_any(JsonVisitor)
+    }
+}
+
+#[cfg(all(test, feature = "ndjson"))]
+mod tests {
+    use super::*;
+    use crate::error::MassiveCsvError;
+
+    fn convert(data: &str) -> Result<(String, NdjsonSummary)> {
+        let mut out = Vec::new();
+        let summary = ndjson_to_csv(data.as_bytes(), &mut out)?;
+        Ok((String::from_utf8(out).unwrap(), summary))
+    }
+
+    #[test]
+    fn infers_columns_in_first_seen_order() {
+        let (csv, summary) = convert(
+            "{\"id\": 1, \"name\": \"Ann, Jr.\", \"tags\": [\"a\", \"b\"]}\n\
+             \n\
+             {\"name\": \"Bob\", \"user\": {\"age\": 30, \"admin\": true}, \"id\": 2.5}\n\
+             {\"id\": null}\n",
+        )
+        .unwrap();
+        assert_eq!(
+            csv,
+            "id,name,tags,user.age,user.admin\n\
+             1,\"Ann, Jr.\",\"[\"\"a\"\",\"\"b\"\"]\",,\n\
+             2.5,Bob,,30,true\n\
+             ,,,,\n"
+        );
+        assert_eq!(
+            summary,
+            NdjsonSummary {
+                rows: 3,
+                columns: 5
+            }
+        );
+    }
+
+    #[test]
+    fn reports_bad_lines() {
+        let err = convert("{\"a\": 1}\n[1, 2]\n").unwrap_err();
+        assert!(
+            matches!(&err, MassiveCsvError::Parse(msg) if msg.contains("line 2")),
+            "{err}"
+        );
+        let err = convert("{\"a\": 1}\n{\"a\": \n").unwrap_err();
+        assert!(matches!(&err, MassiveCsvError::Parse(msg) if msg.starts_with("line 2:")));
+    }
+
+    #[test]
+    fn recognises_file_names() {
+        assert!(is_ndjson_path(Path::new("events.ndjson")));
+        assert!(is_ndjson_path(Path::new("dir/EVENTS.JSONL.gz")));
+        assert!(!is_ndjson_path(Path::new("events.json")));
+        assert!(!is_ndjson_path(Path::new("events.csv.gz")));
+    }
+}
@@ -0,0 +1,190 @@
+use std::ops::Range;
+
+/// The order of a file's rows after pending moves, inserts and deletes, as
+/// runs of consecutive file rows. Rows inserted since the file was read are
+/// numbered from its row count up. Moving a block of rows splits at most
+/// three runs, so the list stays short however large the file is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct RowOrder {
+    runs: Vec<Range<usize>>,
+}
+
+impl RowOrder {
+    /// File order for a file of `rows` rows.
+    pub fn new(rows: usize) -> Self {
+        Self::from_runs(std::iter::once(0..rows).collect())
+    }
+
+    pub fn from_runs(runs: Vec<Range<usize>>) -> Self {
+        let mut order = Self { runs };
+        order.coalesce();
+        order
+    }
+
+    /// The runs of file rows, in order.
+    pub fn runs(&self) -> &[Range<usize>] {
+        &self.runs
+    }
+
+    pub fn len(&self) -> usize {
+        self.runs.iter().map(|r| r.len()).sum()
+    }
+
+    /// Whether this is the order of a file of `rows` rows, unchanged.
+    pub fn is_identity(&self, rows: usize) -> bool {
+        match self.runs.as_slice() {
+            [] => rows == 0,
+            [run] => *run == (0..rows),
+            _ => false,
+        }
+    }
+
+    /// The file row at position `row`.
+    pub fn file_row(&self, row: usize) -> Option<usize> {
+        let mut start = 0;
+        for run in &self.runs {
+            if row < start + run.len() {
+                return Some(run.start + row - start);
+            }
+            start += run.len();
+        }
+        None
+    }
+
+    /// File rows in order.
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.runs.iter().flat_map(|r| r.clone())
+    }
+
+    /// Move the rows at positions `rows` so the first of them ends up at
+    /// position `dest`. Both must be in range.
+    pub fn move_rows(&mut self, rows: Range<usize>, dest: usize) {
+        let first = self.split_at(rows.start);
+        let end = self.split_at(rows.end);
+        let moved: Vec<Range<usize>> = self.runs.drain(first..end).collect();
+        let at = self.split_at(dest);
+        self.runs.splice(at..at, moved);
+        self.coalesce();
+    }
+
+    /// Insert the rows `ids` at position `at`, which must be in range or
+    /// just past the end.
+    pub fn insert(&mut self, at: usize, ids: Range<usize>) {
+        let at = self.split_at(at);
+        self.runs.insert(at, ids);
+        self.coalesce();
+    }
+
+    /// Remove the rows at positions `rows`, returning them.
+    pub fn delete(&mut self, rows: Range<usize>) -> Vec<Range<usize>> {
+        let first = self.split_at(rows.start);
+        let end = self.split_at(rows.end);
+        let removed = self.runs.drain(first..end).collect();
+        self.coalesce();
+        removed
+    }
+
+    /// Remove the rows at `positions`, which must be sorted and in range,
+    /// in one pass. Returns the rows removed.
+    pub fn delete_positions(&mut self, positions: &[usize]) -> Vec<usize> {
+        let mut removed = Vec::with_capacity(positions.len());
+        let mut kept = Vec::with_capacity(self.runs.len() + positions.len());
+        let mut positions = positions.iter().copied().peekable();
+        let mut start = 0;
+        for run in self.runs.drain(..) {
+            let end = start + run.len();
+            let mut from = run.start;
+            while let Some(position) = positions.next_if(|&p| p < end) {
+                let row = run.start + position - start;
+                kept.push(from..row);
+                removed.push(row);
+                from = row + 1;
+            }
+            kept.push(from..run.end);
+            start = end;
+        }
+        self.runs = kept;
+        self.coalesce();
+        removed
+    }
+
+    /// Split the run containing position `row` so a run starts there, and
+    /// return that run's index.
+    fn split_at(&mut self, row: usize) -> usize {
+        let mut start = 0;
+        for i in 0..self.runs.len() {
+            let run = self.runs[i].clone();
+            if row == start {
+                return i;
+            }
+            if row < start + run.len() {
+                let mid = run.start + row - start;
+                self.runs[i] = run.start..mid;
+                self.runs.insert(i + 1, mid..run.end);
+                return i + 1;
+            }
+            start += run.len();
+        }
+        self.runs.len()
+    }
+
+    fn coalesce(&mut self) {
+        self.runs.retain(|r| !r.is_empty());
+        let mut merged: Vec<Range<usize>> = Vec::with_capacity(self.runs.len());
+        for run in self.runs.drain(..) {
+            match merged.last_mut() {
+                Some(last) if last.end == run.start => last.end = run.end,
+                _ => merged.push(run),
+            }
+        }
+        self.runs = merged;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moves_blocks_of_rows() {
+        let mut order = RowOrder::new(6);
+        order.move_rows(4..6, 1);
+        assert_eq!(order.iter().collect::<Vec<_>>(), vec![0, 4, 5, 1, 2, 3]);
+        assert_eq!(order.file_row(2), Some(5));
+        assert_eq!(order.file_row(6), None);
+
+        order.move_rows(0..1, 5);
+        assert_eq!(order.iter().collect::<Vec<_>>(), vec![4, 5, 1, 2, 3, 0]);
+        assert_eq!(order.runs(), &[4..6, 1..4, 0..1]);
+
+        // Moving everything back restores file order
+        order.move_rows(5..6, 0);
+        order.move_rows(3..6, 1);
+        assert_eq!(order.iter().collect::<Vec<_>>(), vec![0, 1, 2, 3, 4, 5]);
+        assert!(order.is_identity(6));
+        assert_eq!(order.len(), 6);
+    }
+
+    #[test]
+    fn inserts_and_deletes_rows() {
+        let mut order = RowOrder::new(6);
+        order.insert(2, 6..8);
+        assert_eq!(
+            order.iter().collect::<Vec<_>>(),
+            vec![0, 1, 6, 7, 2, 3, 4, 5]
+        );
+        order.insert(8, 8..9);
+        assert_eq!(order.file_row(8), Some(8));
+
+        assert_eq!(order.delete(1..3), vec![1..2, 6..7]);
+        assert_eq!(order.iter().collect::<Vec<_>>(), vec![0, 7, 2, 3, 4, 5, 8]);
+        assert_eq!(order.delete_positions(&[0, 3, 4, 6]), vec![0, 3, 4, 8]);
+        assert_eq!(order.runs(), &[7..8, 2..3, 5..6]);
+        assert!(!order.is_identity(3));
+
+        let mut order = RowOrder::new(4);
+        order.delete(3..4);
+        assert!(order.is_identity(3));
+        assert!(!order.is_identity(4));
+    }
+}
@@ -0,0 +1,688 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::editor::{write_atomic, LineWriter};
+use crate::error::{MassiveCsvError, Result};
+use crate::parser::serialize_row;
+#[cfg(feature = "plugins")]
+use crate::plugin::Plugin;
+use crate::reader::CsvReader;
+#[cfg(feature = "scripting")]
+use crate::script::RowScript;
+
+/// Rows processed per parallel batch.
+const CHUNK_ROWS: usize = 64 * 1024;
+
+/// A declarative, repeatable data-prep pipeline: read `input`, apply `steps`
+/// to every row in order, write `output`.
+///
+/// Stored as JSON, e.g.
+/// `{"input": "vendor.csv", "output": "clean.csv", "steps": [{"op": "trim"}]}`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PipelineSpec {
+    pub input: Option<PathBuf>,
+    pub output: Option<PathBuf>,
+    #[serde(default)]
+    pub steps: Vec<Step>,
+}
+
+/// One pipeline transform.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Step {
+    /// Keep only these columns, in this order.
+    Select { columns: Vec<String> },
+    /// Remove these columns.
+    Drop { columns: Vec<String> },
+    /// Rename a column.
+    Rename { from: String, to: String },
+    /// Keep rows whose `column` equals and/or contains the given text.
+    Filter {
+        column: String,
+        #[serde(default)]
+        equals: Option<String>,
+        #[serde(default)]
+        contains: Option<String>,
+        #[serde(default)]
+        case_insensitive: bool,
+    },
+    /// Set `column` to a constant, adding it at the end if it does not exist.
+    Set { column: String, value: String },
+    /// Trim surrounding whitespace (all columns if none are given).
+    Trim {
+        #[serde(default)]
+        columns: Vec<String>,
+    },
+    /// Convert to upper case (all columns if none are given).
+    Uppercase {
+        #[serde(default)]
+        columns: Vec<String>,
+    },
+    /// Convert to lower case (all columns if none are given).
+    Lowercase {
+        #[serde(default)]
+        columns: Vec<String>,
+    },
+    /// Replace every occurrence of `from` with `to` in a column.
+    Replace {
+        column: String,
+        from: String,
+        to: String,
+    },
+    /// Run a Rhai row script (see [`crate::script::RowScript`]); rows for which
+    /// it evaluates to `false` are dropped. Requires the `scripting` feature.
+    Script { script: String },
+    /// Run a row-transform plugin (see [`crate::plugin`]), given by name
+    /// (searched for in `$MASSIVE_CSV_PLUGIN_PATH`) or path; rows it filters
+    /// out are dropped. Requires the `plugins` feature.
+    Plugin { plugin: String },
+}
+
+impl Step {
+    /// Short name of the operation, as written in the spec.
+    pub fn op_name(&self) -> &'static str {
+        match self {
+            Step::Select { .. } => "select",
+            Step::Drop { .. } => "drop",
+            Step::Rename { .. } => "rename",
+            Step::Filter { .. } => "filter",
+            Step::Set { .. } => "set",
+            Step::Trim { .. } => "trim",
+            Step::Uppercase { .. } => "uppercase",
+            Step::Lowercase { .. } => "lowercase",
+            Step::Replace { .. } => "replace",
+            Step::Script { .. } => "script",
+            Step::Plugin { .. } => "plugin",
+        }
+    }
+}
+
+/// Where an output column's values come from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ColumnLineage {
+    /// Output column name.
+    pub name: String,
+    /// Input columns the values derive from (empty for constants).
+    pub sources: Vec<String>,
+    /// Indices of the steps that transformed this column's values or name.
+    pub transforms: Vec<usize>,
+}
+
+/// Row counts through one step.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StepStats {
+    pub index: usize,
+    pub op: String,
+    pub rows_in: usize,
+    pub rows_out: usize,
+}
+
+/// Machine-readable record of a pipeline run, for data-governance documentation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LineageManifest {
+    pub input: PathBuf,
+    pub output: PathBuf,
+    pub input_columns: Vec<String>,
+    pub rows_in: usize,
+    pub rows_out: usize,
+    pub steps: Vec<StepStats>,
+    pub columns: Vec<ColumnLineage>,
+}
+
+impl LineageManifest {
+    /// Conventional manifest location for an output file: `<output>.lineage.json`.
+    pub fn path_for(output: &Path) -> PathBuf {
+        let mut name = output.as_os_str().to_os_string();
+        name.push(".lineage.json");
+        PathBuf::from(name)
+    }
+
+    /// Write the manifest as pretty-printed JSON.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        write_atomic(path, |writer| {
+            serde_json::to_writer_pretty(&mut *writer, self)?;
+            writer.write_all(b"\n")?;
+            Ok(())
+        })
+    }
+}
+
+impl PipelineSpec {
+    /// Parse a spec from JSON text.
+    pub fn from_json(text: &str) -> Result<Self> {
+        Ok(serde_json::from_str(text)?)
+    }
+
+    /// Load a spec from a JSON file.
+    ///
+    /// Relative `input`, `output` and plugin paths are resolved against the
+    /// spec's directory.
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut spec = Self::from_json(&fs::read_to_string(path)?)?;
+        if let Some(dir) = path.parent() {
+            for p in [&mut spec.input, &mut spec.output].into_iter().flatten() {
+                if p.is_relative() {
+                    *p = dir.join(&*p);
+                }
+            }
+            #[cfg(feature = "plugins")]
+            for step in &mut spec.steps {
+                if let Step::Plugin { plugin } = step {
+                    if crate::plugin::is_path(plugin) && Path::new(plugin.as_str()).is_relative() {
+                        *plugin = dir.join(&*plugin).to_string_lossy().into_owned();
+                    }
+                }
+            }
+        }
+        Ok(spec)
+    }
+}
+
+/// A step with its columns resolved against the schema at that point.
+enum Compiled {
+    Project(Vec<usize>),
+    Filter {
+        col: usize,
+        equals: Option<String>,
+        contains: Option<String>,
+        case_insensitive: bool,
+    },
+    Set {
+        col: usize,
+        value: String,
+        append: bool,
+    },
+    Map {
+        cols: Vec<usize>,
+        f: fn(&str) -> String,
+    },
+    Replace {
+        col: usize,
+        from: String,
+        to: String,
+    },
+    #[cfg(feature = "scripting")]
+    Script(Box<RowScript>),
+    #[cfg(feature = "plugins")]
+    Plugin(Box<Plugin>),
+    /// Renames only change the header.
+    Nothing,
+}
+
+/// What happened to a row.
+enum Outcome {
+    Keep(Vec<String>),
+    /// Dropped by the step at this index.
+    Dropped(usize),
+}
+
+/// Resolve every step against the evolving schema, returning the compiled
+/// steps and the lineage of the final columns.
+fn compile(
+    input_columns: &[String],
+    steps: &[Step],
+) -> Result<(Vec<Compiled>, Vec<ColumnLineage>)> {
+    let mut schema: Vec<ColumnLineage> = input_columns
+        .iter()
+        .map(|c| ColumnLineage {
+            name: c.clone(),
+            sources: vec![c.clone()],
+            transforms: vec![],
+        })
+        .collect();
+
+    let find = |schema: &[ColumnLineage], name: &str, step: usize| -> Result<usize> {
+        schema.iter().position(|c| c.name == name).ok_or_else(|| {
+            MassiveCsvError::ColumnNotFound(format!("{name} (pipeline step {step})"))
+        })
+    };
+    let find_all =
+        |schema: &[ColumnLineage], names: &[String], step: usize| -> Result<Vec<usize>> {
+            if names.is_empty() {
+                return Ok((0..schema.len()).collect());
+            }
+            names.iter().map(|n| find(schema, n, step)).collect()
+        };
+
+    let mut compiled = Vec::with_capacity(steps.len());
+    for (i, step) in steps.iter().enumerate() {
+        let c = match step {
+            Step::Select { columns } => {
+                let idx: Vec<usize> = columns
+                    .iter()
+                    .map(|n| find(&schema, n, i))
+                    .collect::<Result<_>>()?;
+                schema = idx.iter().map(|&j| schema[j].clone()).collect();
+                Compiled::Project(idx)
+            }
+            Step::Drop { columns } => {
+                let dropped: Vec<usize> = columns
+                    .iter()
+                    .map(|n| find(&schema, n, i))
+                    .collect::<Result<_>>()?;
+                let idx: Vec<usize> = (0..schema.len()).filter(|j| !dropped.contains(j)).collect();
+                schema = idx.iter().map(|&j| schema[j].clone()).collect();
+                Compiled::Project(idx)
+            }
+            Step::Rename { from, to } => {
+                let j = find(&schema, from, i)?;
+                schema[j].name = to.clone();
+                schema[j].transforms.push(i);
+                Compiled::Nothing
+            }
+            Step::Filter {
+                column,
+                equals,
+                contains,
+                case_insensitive,
+            } => {
+                if equals.is_none() && contains.is_none() {
+                    return Err(MassiveCsvError::InvalidSpec(format!(
+                        "filter step {i} needs `equals` or `contains`"
+                    )));
+                }
+                let col = find(&schema, column, i)?;
+                let fold = |s: &Option<String>| {
+                    s.as_ref().map(|v| {
+                        if *case_insensitive {
+                            v.to_lowercase()
+                        } else {
+                            v.clone()
+                        }
+                    })
+                };
+                Compiled::Filter {
+                    col,
+                    equals: fold(equals),
+                    contains: fold(contains),
+                    case_insensitive: *case_insensitive,
+                }
+            }
+            Step::Set { column, value } => {
+                let existing = schema.iter().position(|c| &c.name == column);
+                let col = match existing {
+                    Some(j) => {
+                        schema[j].sources.clear();
+                        schema[j].transforms.push(i);
+                        j
+                    }
+                    None => {
+                        schema.push(ColumnLineage {
+                            name: column.clone(),
+                            sources: vec![],
+                            transforms: vec![i],
+                        });
+                        schema.len() - 1
+                    }
+                };
+                Compiled::Set {
+                    col,
+                    value: value.clone(),
+                    append: existing.is_none(),
+                }
+            }
+            Step::Trim { columns } | Step::Uppercase { columns } | Step::Lowercase { columns } => {
+                let cols = find_all(&schema, columns, i)?;
+                for &j in &cols {
+                    schema[j].transforms.push(i);
+                }
+                let f: fn(&str) -> String = match step {
+                    Step::Trim { .. } => |s| s.trim().to_string(),
+                    Step::Uppercase { .. } => |s| s.to_uppercase(),
+                    _ => |s| s.to_lowercase(),
+                };
+                Compiled::Map { cols, f }
+            }
+            Step::Replace { column, from, to } => {
+                let col = find(&schema, column, i)?;
+                schema[col].transforms.push(i);
+                Compiled::Replace {
+                    col,
+                    from: from.clone(),
+                    to: to.clone(),
+                }
+            }
+            Step::Script { script } => compile_script(&mut schema, script, i)?,
+            Step::Plugin { plugin } => compile_plugin(&mut schema, plugin, i)?,
+        };
+        compiled.push(c);
+    }
+
+    Ok((compiled, schema))
+}
+
+#[cfg(feature = "scripting")]
+fn compile_script(schema: &mut Vec<ColumnLineage>, source: &str, step: usize) -> Result<Compiled> {
+    let names: Vec<String> = schema.iter().map(|c| c.name.clone()).collect();
+    let script = RowScript::compile(source, &names)?;
+
+    // Assigned columns derive from everything else the script reads
+    let read: Vec<String> = script
+        .referenced_columns()
+        .iter()
+        .filter(|c| !script.assigned_columns().contains(c))
+        .filter_map(|c| schema.iter().find(|l| &l.name == c))
+        .flat_map(|l| l.sources.clone())
+        .collect();
+
+    for name in script.assigned_columns() {
+        let lineage = match schema.iter().position(|c| &c.name == name) {
+            Some(j) => &mut schema[j],
+            None => {
+                schema.push(ColumnLineage {
+                    name: name.clone(),
+                    sources: vec![],
+                    transforms: vec![],
+                });
+                schema.last_mut().expect("just pushed")
+            }
+        };
+        for source in &read {
+            if !lineage.sources.contains(source) {
+                lineage.sources.push(source.clone());
+            }
+        }
+        lineage.transforms.push(step);
+    }
+
+    Ok(Compiled::Script(Box::new(script)))
+}
+
+#[cfg(not(feature = "scripting"))]
+fn compile_script(_: &mut Vec<ColumnLineage>, _: &str, step: usize) -> Result<Compiled> {
+    Err(MassiveCsvError::InvalidSpec(format!(
+        "step {step} is a script, but scripting support is not enabled"
+    )))
+}
+
+#[cfg(feature = "plugins")]
+fn compile_plugin(schema: &mut Vec<ColumnLineage>, name: &str, step: usize) -> Result<Compiled> {
+    let plugin = Plugin::find(name)?;
+    let names: Vec<String> = schema.iter().map(|c| c.name.clone()).collect();
+
+    // A plugin is opaque: columns it keeps by name keep their sources, and
+    // new ones may derive from any input column
+    let all: Vec<String> = schema.iter().flat_map(|c| c.sources.clone()).collect();
+    *schema = plugin
+        .headers(&names)?
+        .into_iter()
+        .map(|name| {
+            let mut lineage = schema
+                .iter()
+                .find(|c| c.name == name)
+                .cloned()
+                .unwrap_or_else(|| ColumnLineage {
+                    name,
+                    sources: all.clone(),
+                    transforms: vec![],
+                });
+            lineage.transforms.push(step);
+            lineage
+        })
+        .collect();
+
+    Ok(Compiled::Plugin(Box::new(plugin)))
+}
+
+#[cfg(not(feature = "plugins"))]
+fn compile_plugin(_: &mut Vec<ColumnLineage>, _: &str, step: usize) -> Result<Compiled> {
+    Err(MassiveCsvError::InvalidSpec(format!(
+        "step {step} is a plugin, but plugin support is not enabled"
+    )))
+}
+
+/// Run one row through the compiled steps.
+fn run_row(mut fields: Vec<String>, steps: &[Compiled]) -> Result<Outcome> {
+    for (i, step) in steps.iter().enumerate() {
+        match step {
+            Compiled::Project(idx) => {
+                fields = idx
+                    .iter()
+                    .map(|&j| fields.get(j).cloned().unwrap_or_default())
+                    .collect();
+            }
+            Compiled::Filter {
+                col,
+                equals,
+                contains,
+                case_insensitive,
+            } => {
+                let raw = fields.get(*col).map(|s| s.as_str()).unwrap_or("");
+                let value = if *case_insensitive {
+                    raw.to_lowercase()
+                } else {
+                    raw.to_string()
+                };
+                let keep = equals.as_ref().is_none_or(|e| &value == e)
+                    && contains.as_ref().is_none_or(|c| value.contains(c.as_str()));
+                if !keep {
+                    return Ok(Outcome::Dropped(i));
+                }
+            }
+            Compiled::Set { col, value, append } => {
+                if *append {
+                    fields.push(value.clone());
+                } else if let Some(f) = fields.get_mut(*col) {
+                    *f = value.clone();
+                }
+            }
+            Compiled::Map { cols, f } => {
+                for &j in cols {
+                    if let Some(v) = fields.get_mut(j) {
+                        *v = f(v);
+                    }
+                }
+            }
+            Compiled::Replace { col, from, to } => {
+                if let Some(v) = fields.get_mut(*col) {
+                    *v = v.replace(from.as_str(), to);
+                }
+            }
+            #[cfg(feature = "scripting")]
+            Compiled::Script(script) => match script.apply(fields)? {
+                Some(out) => fields = out,
+                None => return Ok(Outcome::Dropped(i)),
+            },
+            #[cfg(feature = "plugins")]
+            Compiled::Plugin(plugin) => match plugin.apply(fields)? {
+                Some(out) => fields = out,
+                None => return Ok(Outcome::Dropped(i)),
+            },
+            Compiled::Nothing => {}
+        }
+    }
+    Ok(Outcome::Keep(fields))
+}
+
+/// Run `spec` over the file behind `reader`, writing the result atomically to `output`.
+///
+/// Returns the lineage manifest describing the run; it is not written to disk.
+pub fn run(reader: &CsvReader, spec: &PipelineSpec, output: &Path) -> Result<LineageManifest> {
+    let (steps, columns) = compile(reader.headers(), &spec.steps)?;
+    let dialect = reader.dialect();
+    let row_count = reader.row_count();
+
+    // dropped_at[i] = rows removed by step i
+    let mut dropped_at = vec![0usize; steps.len()];
+    let mut rows_out = 0usize;
+
+    write_atomic(output, |writer| {
+        let mut lines = LineWriter::new(writer, reader.line_ending());
+        let headers: Vec<String> = columns.iter().map(|c| c.name.clone()).collect();
+        lines.write_line(&serialize_row(&headers, dialect))?;
+
+        let mut start = 0;
+        while start < row_count {
+            let end = (start + CHUNK_ROWS).min(row_count);
+            let results: Vec<std::result::Result<String, usize>> = (start..end)
+                .into_par_iter()
+                .map(|i| {
+                    Ok(match run_row(reader.get_row(i)?, &steps)? {
+                        Outcome::Keep(fields) => Ok(serialize_row(&fields, dialect)),
+                        Outcome::Dropped(step) => Err(step),
+                    })
+                })
+                .collect::<Result<_>>()?;
+
+            for result in results {
+                match result {
+                    Ok(line) => {
+                        lines.write_line(&line)?;
+                        rows_out += 1;
+                    }
+                    Err(step) => dropped_at[step] += 1,
+                }
+            }
+            start = end;
+        }
+
+        lines.finish(true)
+    })?;
+
+    let mut remaining = row_count;
+    let stats = spec
+        .steps
+        .iter()
+        .enumerate()
+        .map(|(i, step)| {
+            let rows_in = remaining;
+            remaining -= dropped_at[i];
+            StepStats {
+                index: i,
+                op: step.op_name().to_string(),
+                rows_in,
+                rows_out: remaining,
+            }
+        })
+        .collect();
+
+    Ok(LineageManifest {
+        input: reader.path().to_path_buf(),
+        output: output.to_path_buf(),
+        input_columns: reader.headers().to_vec(),
+        rows_in: row_count,
+        rows_out,
+        steps: stats,
+        columns,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn make_csv(content: &str) -> tempfile::NamedTempFile {
+        let mut f = tempfile::NamedTempFile::new().unwrap();
+        f.write_all(content.as_bytes()).unwrap();
+        f.flush().unwrap();
+        f
+    }
+
+    #[test]
+    fn run_pipeline_with_lineage() {
+        let f = make_csv("id,amt,status,notes\n1, 10 ,active,x\n2,20,closed,y\n3,30,active,z\n");
+        let reader = CsvReader::open(f.path()).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out.csv");
+
+        let spec = PipelineSpec::from_json(
+            r#"{"steps": [
+                {"op": "filter", "column": "status", "equals": "ACTIVE", "case_insensitive": true},
+                {"op": "rename", "from": "amt", "to": "amount"},
+                {"op": "trim", "columns": ["amount"]},
+                {"op": "drop", "columns": ["notes"]},
+                {"op": "set", "column": "source", "value": "vendor"}
+            ]}"#,
+        )
+        .unwrap();
+
+        let manifest = run(&reader, &spec, &out).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&out).unwrap(),
+            "id,amount,status,source\n1,10,active,vendor\n3,30,active,vendor\n"
+        );
+
+        assert_eq!(manifest.rows_in, 3);
+        assert_eq!(manifest.rows_out, 2);
+        assert_eq!(manifest.steps[0].rows_out, 2);
+        assert_eq!(manifest.steps[4].rows_in, 2);
+
+        let amount = &manifest.columns[1];
+        assert_eq!(amount.name, "amount");
+        assert_eq!(amount.sources, vec!["amt"]);
+        assert_eq!(amount.transforms, vec![1, 2]);
+        assert!(manifest.columns[3].sources.is_empty());
+    }
+
+    #[cfg(feature = "plugins")]
+    #[test]
+    fn plugin_steps_resolve_paths_against_the_spec() {
+        let dir = tempfile::tempdir().unwrap();
+        let spec_path = dir.path().join("spec.json");
+        std::fs::write(
+            &spec_path,
+            r#"{"steps": [{"op": "plugin", "plugin": "plugins/scrub.so"}, {"op": "plugin", "plugin": "scrub"}]}"#,
+        )
+        .unwrap();
+        let spec = PipelineSpec::load(&spec_path).unwrap();
+        let expected = dir
+            .path()
+            .join("plugins/scrub.so")
+            .to_string_lossy()
+            .into_owned();
+        assert_eq!(spec.steps[0], Step::Plugin { plugin: expected });
+        assert_eq!(
+            spec.steps[1],
+            Step::Plugin {
+                plugin: "scrub".into()
+            }
+        );
+
+        let f = make_csv("id\n1\n");
+        let reader = CsvReader::open(f.path()).unwrap();
+        let err = run(&reader, &spec, &dir.path().join("out.csv")).unwrap_err();
+        assert!(matches!(err, MassiveCsvError::Plugin(_)));
+    }
+
+    #[cfg(feature = "scripting")]
+    #[test]
+    fn script_step_lineage() {
+        let f = make_csv("price,qty\n2,3\n4,0\n");
+        let reader = CsvReader::open(f.path()).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out.csv");
+
+        let spec = PipelineSpec::from_json(
+            r#"{"steps": [{"op": "script", "script": "row.total = row.price * row.qty; row.qty > 0"}]}"#,
+        )
+        .unwrap();
+        let manifest = run(&reader, &spec, &out).unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&out).unwrap(),
+            "price,qty,total\n2,3,6\n"
+        );
+        assert_eq!(manifest.steps[0].rows_out, 1);
+        assert_eq!(manifest.columns[2].sources, vec!["price", "qty"]);
+    }
+
+    #[test]
+    fn unknown_column_is_reported() {
+        let f = make_csv("a\n1\n");
+        let reader = CsvReader::open(f.path()).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let spec = PipelineSpec {
+            input: None,
+            output: None,
+            steps: vec![Step::Drop {
+                columns: vec!["b".to_string()],
+            }],
+        };
+        let result = run(&reader, &spec, &dir.path().join("out.csv"));
+        assert!(matches!(result, Err(MassiveCsvError::ColumnNotFound(_))));
+    }
+}
@@ -0,0 +1,421 @@
+//! Row transforms loaded from shared libraries at run time, so cleaning
+//! logic can ship separately from this crate.
+//!
+//! A plugin is a `cdylib` exporting, with C linkage:
+//!
+//! ```text
+//! uint32_t massive_csv_plugin_abi(void);
+//! int32_t  massive_csv_transform(const McField *fields, size_t len, void *out, McEmit emit);
+//! int32_t  massive_csv_headers(const McField *headers, size_t len, void *out, McEmit emit);
+//!
+//! typedef struct { const uint8_t *ptr; size_t len; } McField;
+//! typedef void (*McEmit)(void *out, const uint8_t *ptr, size_t len);
+//! ```
+//!
+//! `massive_csv_plugin_abi` returns [`PLUGIN_ABI_VERSION`].
+//! `massive_csv_transform` is given a row's UTF-8 fields and calls `emit`
+//! with `out` once per output field, in order, then returns
+//! [`PLUGIN_KEEP`], or [`PLUGIN_DROP`] to filter the row out; anything else
+//! is an error. It is called from several threads at once. The optional
+//! `massive_csv_headers` is called the same way once, with the input
+//! headers, to name the output columns; without it they are unchanged.
+
+use std::ffi::c_void;
+use std::path::{Path, PathBuf};
+
+use rayon::prelude::*;
+
+use crate::edits::CellEdit;
+use crate::error::{MassiveCsvError, Result};
+use crate::reader::CsvReader;
+
+/// Version of the ABI described above.
+pub const PLUGIN_ABI_VERSION: u32 = 1;
+
+/// Returned by a plugin function to keep the row it emitted.
+pub const PLUGIN_KEEP: i32 = 0;
+
+/// Returned by `massive_csv_transform` to drop the row.
+pub const PLUGIN_DROP: i32 = 1;
+
+/// Environment variable listing directories searched for plugins by name,
+/// separated like `PATH`.
+pub const PLUGIN_PATH_VAR: &str = "MASSIVE_CSV_PLUGIN_PATH";
+
+/// A field handed to a plugin: UTF-8 bytes, not NUL-terminated.
+#[repr(C)]
+pub struct PluginField {
+    pub ptr: *const u8,
+    pub len: usize,
+}
+
+/// Called by a plugin with each output field.
+pub type EmitFn = unsafe extern "C" fn(out: *mut c_void, ptr: *const u8, len: usize);
+
+/// `massive_csv_transform` and `massive_csv_headers`.
+pub type TransformFn = unsafe extern "C" fn(
+    fields: *const PluginField,
+    len: usize,
+    out: *mut c_void,
+    emit: EmitFn,
+) -> i32;
+
+type AbiFn = unsafe extern "C" fn() -> u32;
+
+/// A loaded plugin.
+pub struct Plugin {
+    name: String,
+    transform: TransformFn,
+    headers: Option<TransformFn>,
+    /// Keeps the functions above loaded.
+    _library: Option<libloading::Library>,
+}
+
+impl Plugin {
+    /// Load the plugin at `path`.
+    ///
+    /// Loading a library runs its initialization code, so only load
+    /// plugins you trust.
+    pub fn load(path: &Path) -> Result<Self> {
+        let fail =
+            |e: libloading::Error| MassiveCsvError::Plugin(format!("{}: {e}", path.display()));
+        // SAFETY: the library is trusted to follow the ABI above; the
+        // symbols are only used while `_library` keeps it loaded.
+        unsafe {
+            let library = libloading::Library::new(path).map_err(fail)?;
+            let abi = library
+                .get::<AbiFn>(b"massive_csv_plugin_abi\0")
+                .map_err(fail)?;
+            let version = abi();
+            if version != PLUGIN_ABI_VERSION {
+                return Err(MassiveCsvError::Plugin(format!(
+                    "{}: built for plugin ABI {version}, expected {PLUGIN_ABI_VERSION}",
+                    path.display()
+                )));
+            }
+            let transform = *library
+                .get::<TransformFn>(b"massive_csv_transform\0")
+                .map_err(fail)?;
+            let headers = library
+                .get::<TransformFn>(b"massive_csv_headers\0")
+                .ok()
+                .map(|f| *f);
+            Ok(Self {
+                name: plugin_name(path),
+                transform,
+                headers,
+                _library: Some(library),
+            })
+        }
+    }
+
+    /// Load a plugin by name from the [`plugin_dirs`], or from a path if
+    /// `name` is one.
+    pub fn find(name: &str) -> Result<Self> {
+        if is_path(name) {
+            return Self::load(Path::new(name));
+        }
+        let file = libloading::library_filename(name);
+        plugin_dirs()
+            .into_iter()
+            .map(|dir| dir.join(&file))
+            .find(|path| path.is_file())
+            .map_or_else(
+                || {
+                    Err(MassiveCsvError::Plugin(format!(
+                        "no plugin '{name}' in ${PLUGIN_PATH_VAR}"
+                    )))
+                },
+                |path| Self::load(&path),
+            )
+    }
+
+    /// The plugin's file name without its `lib` prefix and extension.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Output column names for rows with `headers`.
+    pub fn headers(&self, headers: &[String]) -> Result<Vec<String>> {
+        match self.headers {
+            Some(f) => self
+                .call(f, headers, "massive_csv_headers")
+                .map(|out| out.unwrap_or_default()),
+            None => Ok(headers.to_vec()),
+        }
+    }
+
+    /// Run the plugin on one row.
+    ///
+    /// Returns the output fields, or `None` if the plugin filtered the row out.
+    pub fn apply(&self, fields: Vec<String>) -> Result<Option<Vec<String>>> {
+        self.call(self.transform, &fields, "massive_csv_transform")
+    }
+
+    /// The cell edits running the plugin over every row of `reader` makes,
+    /// for [`CsvEditor::apply_cell_edits`](crate::CsvEditor::apply_cell_edits).
+    ///
+    /// Rows the plugin filters out are left as they are. A plugin that
+    /// changes the columns can only run in a pipeline, and is an error here.
+    pub fn cell_edits(&self, reader: &CsvReader) -> Result<Vec<CellEdit>> {
+        let headers = reader.headers();
+        if self.headers(headers)? != headers {
+            return Err(MassiveCsvError::Plugin(format!(
+                "{} changes the columns, so it can only run in a pipeline",
+                self.name
+            )));
+        }
+        let edits: Vec<Vec<CellEdit>> = (0..reader.row_count())
+            .into_par_iter()
+            .map(|row| {
+                let fields = reader.get_row(row)?;
+                let Some(out) = self.apply(fields.clone())? else {
+                    return Ok(Vec::new());
+                };
+                Ok(headers
+                    .iter()
+                    .zip(fields.iter().zip(out))
+                    .filter(|(_, (old, new))| *old != new)
+                    .map(|(column, (_, new))| CellEdit::new(row, column, new))
+                    .collect())
+            })
+            .collect::<Result<_>>()?;
+        Ok(edits.into_iter().flatten().collect())
+    }
+
+    fn call(&self, f: TransformFn, fields: &[String], what: &str) -> Result<Option<Vec<String>>> {
+        let input: Vec<PluginField> = fields
+            .iter()
+            .map(|v| PluginField {
+                ptr: v.as_ptr(),
+                len: v.len(),
+            })
+            .collect();
+        let mut output: Vec<String> = Vec::with_capacity(fields.len());
+        // SAFETY: `input` borrows `fields` for the call, and `emit` is only
+        // handed `output`, which outlives it.
+        let status = unsafe {
+            f(
+                input.as_ptr(),
+                input.len(),
+                &mut output as *mut Vec<String> as *mut c_void,
+                emit,
+            )
+        };
+        match status {
+            PLUGIN_KEEP => Ok(Some(output)),
+            PLUGIN_DROP => Ok(None),
+            code => Err(MassiveCsvError::Plugin(format!(
+                "{}: {what} failed with code {code}",
+                self.name
+            ))),
+        }
+    }
+}
+
+/// Directories searched for plugins by name, from [`PLUGIN_PATH_VAR`].
+pub fn plugin_dirs() -> Vec<PathBuf> {
+    std::env::var_os(PLUGIN_PATH_VAR)
+        .map(|dirs| std::env::split_paths(&dirs).collect())
+        .unwrap_or_default()
+}
+
+/// The plugins in the [`plugin_dirs`]: (name, path), sorted by name. A name
+/// found in more than one directory is listed from the first.
+pub fn discover() -> Vec<(String, PathBuf)> {
+    let mut found: Vec<(String, PathBuf)> = Vec::new();
+    for dir in plugin_dirs() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| {
+                p.extension()
+                    .is_some_and(|e| e == std::env::consts::DLL_EXTENSION)
+            })
+            .collect();
+        paths.sort();
+        for path in paths {
+            let name = plugin_name(&path);
+            if !found.iter().any(|(n, _)| *n == name) {
+                found.push((name, path));
+            }
+        }
+    }
+    found.sort_by(|a, b| a.0.cmp(&b.0));
+    found
+}
+
+/// Whether a pipeline's plugin reference is a path rather than a name.
+pub(crate) fn is_path(name: &str) -> bool {
+    name.contains(['/', '\\'])
+        || Path::new(name)
+            .extension()
+            .is_some_and(|e| e == std::env::consts::DLL_EXTENSION)
+}
+
+fn plugin_name(path: &Path) -> String {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy())
+        .unwrap_or_default();
+    let prefix = std::env::consts::DLL_PREFIX;
+    stem.strip_prefix(prefix).unwrap_or(&stem).to_string()
+}
+
+/// The [`EmitFn`] handed to plugins: appends a field to the `Vec<String>`
+/// behind `out`.
+unsafe extern "C" fn emit(out: *mut c_void, ptr: *const u8, len: usize) {
+    let bytes = if len == 0 {
+        &[][..]
+    } else {
+        std::slice::from_raw_parts(ptr, len)
+    };
+    let output = &mut *(out as *mut Vec<String>);
+    output.push(String::from_utf8_lossy(bytes).into_owned());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields<'a>(ptr: *const PluginField, len: usize) -> Vec<&'a str> {
+        unsafe { std::slice::from_raw_parts(ptr, len) }
+            .iter()
+            .map(|f| unsafe {
+                std::str::from_utf8(std::slice::from_raw_parts(f.ptr, f.len)).unwrap()
+            })
+            .collect()
+    }
+
+    /// Upper-cases the first column, appends its length, and drops rows
+    /// where it is empty.
+    unsafe extern "C" fn shout(
+        ptr: *const PluginField,
+        len: usize,
+        out: *mut c_void,
+        emit: EmitFn,
+    ) -> i32 {
+        let row = fields(ptr, len);
+        match row.first() {
+            Some(&"") => PLUGIN_DROP,
+            Some(&"fail") => -1,
+            _ => {
+                let first = row[0].to_uppercase();
+                emit(out, first.as_ptr(), first.len());
+                for value in &row[1..] {
+                    emit(out, value.as_ptr(), value.len());
+                }
+                let length = row[0].len().to_string();
+                emit(out, length.as_ptr(), length.len());
+                PLUGIN_KEEP
+            }
+        }
+    }
+
+    unsafe extern "C" fn shout_headers(
+        ptr: *const PluginField,
+        len: usize,
+        out: *mut c_void,
+        emit: EmitFn,
+    ) -> i32 {
+        for name in fields(ptr, len).into_iter().chain(["length"]) {
+            emit(out, name.as_ptr(), name.len());
+        }
+        PLUGIN_KEEP
+    }
+
+    fn plugin(headers: Option<TransformFn>) -> Plugin {
+        Plugin {
+            name: "shout".to_string(),
+            transform: shout,
+            headers,
+            _library: None,
+        }
+    }
+
+    fn row(values: &[&str]) -> Vec<String> {
+        values.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn runs_rows_through_a_plugin() {
+        let plugin = plugin(Some(shout_headers));
+        assert_eq!(
+            plugin.headers(&row(&["name", "city"])).unwrap(),
+            row(&["name", "city", "length"])
+        );
+        assert_eq!(
+            plugin.apply(row(&["ann", "Paris"])).unwrap(),
+            Some(row(&["ANN", "Paris", "3"]))
+        );
+        assert_eq!(plugin.apply(row(&["", "Lyon"])).unwrap(), None);
+        let err = plugin.apply(row(&["fail", "Nice"])).unwrap_err();
+        assert!(err.to_string().contains("failed with code -1"));
+
+        let unnamed = self::plugin(None);
+        assert_eq!(unnamed.headers(&row(&["a"])).unwrap(), row(&["a"]));
+    }
+
+    #[test]
+    fn lists_the_cells_a_plugin_changes() {
+        let mut f = tempfile::NamedTempFile::new().unwrap();
+        std::io::Write::write_all(&mut f, b"name,city\nann,Paris\n,Lyon\nBOB,Nice\n").unwrap();
+        let reader = CsvReader::open(f.path()).unwrap();
+
+        // Keeps the columns: upper-cases names, leaves empty ones alone
+        unsafe extern "C" fn upper(
+            ptr: *const PluginField,
+            len: usize,
+            out: *mut c_void,
+            emit: EmitFn,
+        ) -> i32 {
+            let row = fields(ptr, len);
+            if row[0].is_empty() {
+                return PLUGIN_DROP;
+            }
+            let name = row[0].to_uppercase();
+            emit(out, name.as_ptr(), name.len());
+            emit(out, row[1].as_ptr(), row[1].len());
+            PLUGIN_KEEP
+        }
+        let plugin = Plugin {
+            name: "upper".to_string(),
+            transform: upper,
+            headers: None,
+            _library: None,
+        };
+        assert_eq!(
+            plugin.cell_edits(&reader).unwrap(),
+            vec![CellEdit::new(0, "name", "ANN")]
+        );
+
+        let err = self::plugin(Some(shout_headers))
+            .cell_edits(&reader)
+            .unwrap_err();
+        assert!(err.to_string().contains("only run in a pipeline"));
+    }
+
+    #[test]
+    fn reports_missing_plugins() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join(libloading::library_filename("nope"));
+        assert!(matches!(
+            Plugin::load(&missing),
+            Err(MassiveCsvError::Plugin(_))
+        ));
+        assert!(Plugin::find("surely-not-installed").is_err());
+
+        assert!(is_path("./clean.so") || is_path(".\\clean.dll"));
+        assert!(is_path(&format!(
+            "clean.{}",
+            std::env::consts::DLL_EXTENSION
+        )));
+        assert!(!is_path("clean"));
+        assert_eq!(
+            plugin_name(&dir.path().join(libloading::library_filename("clean"))),
+            "clean"
+        );
+    }
+}
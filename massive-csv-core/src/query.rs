@@ -0,0 +1,1921 @@
+//! A SQL subset over CSV files, e.g.
+//!
+//! ```sql
+//! SELECT r.name, count(*) AS orders, sum(amount)
+//! FROM 'sales.csv' s
+//! LEFT JOIN 'regions.csv' r ON s.region = r.code
+//! WHERE amount > 100 AND status IN ('paid', 'shipped')
+//! GROUP BY r.name
+//! HAVING count(*) > 10
+//! ORDER BY 3 DESC
+//! LIMIT 20
+//! ```
+//!
+//! Tables are file paths in single quotes; identifiers with spaces go in
+//! double quotes or backticks. Every field is text and an empty field is
+//! NULL. As in filters, comparisons are numeric when both sides parse as
+//! numbers and textual otherwise, and arithmetic treats non-numbers as NULL.
+//!
+//! Rows are scanned in parallel straight from the memory map. Only the
+//! selected rows, one entry per group and the join's key table are held in
+//! memory; a `LIMIT` without `ORDER BY` stops the scan early.
+
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::Path;
+
+use rayon::prelude::*;
+
+use crate::error::{MassiveCsvError, Result};
+use crate::reader::CsvReader;
+
+/// Rows scanned per parallel batch, so a `LIMIT` can stop the scan early.
+const SCAN_CHUNK: usize = 64 * 1024;
+
+/// Words that can't be used as bare column names or aliases.
+const KEYWORDS: &[&str] = &[
+    "select", "distinct", "from", "join", "inner", "left", "outer", "on", "where", "group", "by",
+    "having", "order", "asc", "desc", "limit", "offset", "and", "or", "not", "in", "like", "is",
+    "null", "as", "between",
+];
+
+/// A parsed query, not yet bound to any files.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    distinct: bool,
+    items: Vec<SelectItem>,
+    from: TableRef,
+    join: Option<JoinClause>,
+    filter: Option<Node>,
+    group_by: Vec<Node>,
+    having: Option<Node>,
+    order_by: Vec<(Node, bool)>,
+    limit: Option<usize>,
+    offset: usize,
+}
+
+/// The result of [`Query::execute`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QueryResult {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+    /// Rows of the `FROM` file read before the scan finished.
+    pub rows_scanned: usize,
+}
+
+/// Parse and run `sql`, opening its files with default options.
+pub fn query(sql: &str) -> Result<QueryResult> {
+    let query = Query::parse(sql)?;
+    let readers = query
+        .tables()
+        .into_iter()
+        .map(|path| CsvReader::open(Path::new(path)))
+        .collect::<Result<Vec<_>>>()?;
+    query.execute(&readers)
+}
+
+/// A scalar expression over one file's columns, written as in a query's
+/// `SELECT` list (e.g. `price * qty` or `first || ' ' || last`) and
+/// evaluated row by row. Aggregates are not allowed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowExpr {
+    node: Node,
+}
+
+impl RowExpr {
+    /// Parse `text` and resolve its columns against `headers`.
+    pub fn parse(text: &str, headers: &[String]) -> Result<Self> {
+        let tokens = tokenize(text)?;
+        let mut parser = Parser {
+            sql: text,
+            tokens,
+            pos: 0,
+        };
+        let mut node = parser.expr()?;
+        if let Some(token) = parser.peek() {
+            return Err(invalid(format!("unexpected {token} after the expression")));
+        }
+        let table = TableRef {
+            path: String::new(),
+            alias: None,
+        };
+        let tables = [(&table, headers.to_vec(), 0)];
+        node.walk(&mut |n| {
+            match n {
+                Node::Column { table: None, name } => {
+                    *n = Node::Field(resolve_column(&tables, None, name)?);
+                }
+                Node::Column {
+                    table: Some(table),
+                    name,
+                } => {
+                    return Err(invalid(format!("unknown table in {table}.{name}")));
+                }
+                Node::Call(name, _) if AggFunc::from_name(name).is_some() => {
+                    return Err(invalid(format!("{name}() is an aggregate")));
+                }
+                Node::Call(name, args) => {
+                    let (min, max) = scalar_arity(name)
+                        .ok_or_else(|| invalid(format!("unknown function {name}()")))?;
+                    if args.len() < min || args.len() > max {
+                        return Err(invalid(format!("wrong number of arguments to {name}()")));
+                    }
+                }
+                _ => {}
+            }
+            Ok(())
+        })?;
+        Ok(Self { node })
+    }
+
+    /// The value for a row with `fields`: numbers in their shortest form,
+    /// comparisons as 1 or 0, and NULL as an empty string.
+    pub fn eval(&self, fields: &[String]) -> String {
+        let scope = Scope { fields, aggs: &[] };
+        self.node.eval(&scope).text().into_owned()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum SelectItem {
+    /// `*`, or `t.*` for one table.
+    Star(Option<String>),
+    Expr {
+        node: Node,
+        /// The alias, or the expression as written.
+        name: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct TableRef {
+    path: String,
+    alias: Option<String>,
+}
+
+impl TableRef {
+    /// Whether `t.column` refers to this table: by alias, or by file stem
+    /// when there is none.
+    fn is_named(&self, name: &str) -> bool {
+        match &self.alias {
+            Some(alias) => alias.eq_ignore_ascii_case(name),
+            None => Path::new(&self.path)
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(|s| s.split('.').next())
+                .is_some_and(|stem| stem.eq_ignore_ascii_case(name)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct JoinClause {
+    left_outer: bool,
+    table: TableRef,
+    on: Node,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Concat,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AggFunc {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+impl AggFunc {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "count" => Some(AggFunc::Count),
+            "sum" => Some(AggFunc::Sum),
+            "avg" => Some(AggFunc::Avg),
+            "min" => Some(AggFunc::Min),
+            "max" => Some(AggFunc::Max),
+            _ => None,
+        }
+    }
+}
+
+/// An expression. `Column` and aggregate `Call`s are replaced by `Field`
+/// and `Agg` when the query is bound to its files.
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Column {
+        table: Option<String>,
+        name: String,
+    },
+    Field(usize),
+    Agg(usize),
+    Literal(Value<'static>),
+    Neg(Box<Node>),
+    Not(Box<Node>),
+    Binary(BinOp, Box<Node>, Box<Node>),
+    IsNull(Box<Node>, bool),
+    In(Box<Node>, Vec<Node>, bool),
+    Like(Box<Node>, Box<Node>, bool),
+    /// A function call; `count(*)` has no arguments.
+    Call(String, Vec<Node>),
+}
+
+impl Node {
+    fn children(&self) -> Vec<&Node> {
+        match self {
+            Node::Column { .. } | Node::Field(_) | Node::Agg(_) | Node::Literal(_) => Vec::new(),
+            Node::Neg(a) | Node::Not(a) | Node::IsNull(a, _) => vec![a],
+            Node::Binary(_, a, b) | Node::Like(a, b, _) => vec![a, b],
+            Node::In(a, list, _) => std::iter::once(&**a).chain(list).collect(),
+            Node::Call(_, args) => args.iter().collect(),
+        }
+    }
+
+    fn children_mut(&mut self) -> Vec<&mut Node> {
+        match self {
+            Node::Column { .. } | Node::Field(_) | Node::Agg(_) | Node::Literal(_) => Vec::new(),
+            Node::Neg(a) | Node::Not(a) | Node::IsNull(a, _) => vec![a],
+            Node::Binary(_, a, b) | Node::Like(a, b, _) => vec![a, b],
+            Node::In(a, list, _) => std::iter::once(&mut **a).chain(list.iter_mut()).collect(),
+            Node::Call(_, args) => args.iter_mut().collect(),
+        }
+    }
+
+    fn has_aggregate(&self) -> bool {
+        match self {
+            Node::Agg(_) => true,
+            Node::Call(name, _) if AggFunc::from_name(name).is_some() => true,
+            _ => self.children().into_iter().any(Node::has_aggregate),
+        }
+    }
+
+    /// Visit every node, parents before children.
+    fn walk(&mut self, f: &mut dyn FnMut(&mut Node) -> Result<()>) -> Result<()> {
+        f(self)?;
+        for child in self.children_mut() {
+            child.walk(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// A value during evaluation.
+#[derive(Debug, Clone, PartialEq)]
+enum Value<'a> {
+    Null,
+    Number(f64),
+    Text(Cow<'a, str>),
+}
+
+impl Value<'_> {
+    fn number(&self) -> Option<f64> {
+        match self {
+            Value::Null => None,
+            Value::Number(n) => Some(*n),
+            Value::Text(t) => t.trim().parse::<f64>().ok().filter(|n| n.is_finite()),
+        }
+    }
+
+    fn truthy(&self) -> bool {
+        self.number().is_some_and(|n| n != 0.0)
+    }
+
+    fn text(&self) -> Cow<'_, str> {
+        match self {
+            Value::Null => Cow::Borrowed(""),
+            Value::Number(n) => Cow::Owned(format_number(*n)),
+            Value::Text(t) => Cow::Borrowed(t),
+        }
+    }
+
+    fn into_owned(self) -> Value<'static> {
+        match self {
+            Value::Null => Value::Null,
+            Value::Number(n) => Value::Number(n),
+            Value::Text(t) => Value::Text(Cow::Owned(t.into_owned())),
+        }
+    }
+
+    fn boolean(b: bool) -> Value<'static> {
+        Value::Number(if b { 1.0 } else { 0.0 })
+    }
+}
+
+/// Order of two non-NULL values: numeric if both are numbers, else textual.
+fn compare(a: &Value<'_>, b: &Value<'_>) -> Option<Ordering> {
+    if matches!(a, Value::Null) || matches!(b, Value::Null) {
+        return None;
+    }
+    match (a.number(), b.number()) {
+        (Some(x), Some(y)) => x.partial_cmp(&y),
+        _ => Some(a.text().cmp(&b.text())),
+    }
+}
+
+/// Order for `ORDER BY`: NULLs last in either direction.
+fn sort_order(a: &Value<'_>, b: &Value<'_>, descending: bool) -> Ordering {
+    match (a, b) {
+        (Value::Null, Value::Null) => Ordering::Equal,
+        (Value::Null, _) => Ordering::Greater,
+        (_, Value::Null) => Ordering::Less,
+        _ => {
+            let order = compare(a, b).unwrap_or(Ordering::Equal);
+            if descending {
+                order.reverse()
+            } else {
+                order
+            }
+        }
+    }
+}
+
+/// Integers without a fraction, other numbers in their shortest form.
+fn format_number(n: f64) -> String {
+    if n.fract() == 0.0 && n.abs() < 1e15 {
+        format!("{}", n as i64)
+    } else {
+        format!("{n}")
+    }
+}
+
+/// SQL `LIKE`: `%` matches any run of characters, `_` any one character.
+fn like(text: &str, pattern: &str) -> bool {
+    let text: Vec<char> = text.chars().collect();
+    let pattern: Vec<char> = pattern.chars().collect();
+    let (mut t, mut p) = (0, 0);
+    // Position after the last `%` seen, and the text position it matched up to
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('%') => {
+                p += 1;
+                backtrack = Some((p, t));
+            }
+            Some(&c) if c == '_' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((bp, bt)) => {
+                    p = bp;
+                    t = bt + 1;
+                    backtrack = Some((bp, bt + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '%')
+}
+
+/// What an expression is evaluated against: a combined row (the `FROM`
+/// file's fields followed by the joined file's) and, for groups, the
+/// aggregate values.
+struct Scope<'a> {
+    fields: &'a [String],
+    aggs: &'a [Value<'static>],
+}
+
+impl Node {
+    fn eval<'a>(&self, scope: &Scope<'a>) -> Value<'a> {
+        match self {
+            Node::Field(i) => match scope.fields.get(*i) {
+                Some(field) if !field.is_empty() => Value::Text(Cow::Borrowed(field)),
+                _ => Value::Null,
+            },
+            Node::Agg(i) => scope.aggs[*i].clone(),
+            Node::Literal(value) => value.clone(),
+            Node::Column { .. } => unreachable!("columns are resolved before evaluation"),
+            Node::Neg(a) => match a.eval(scope).number() {
+                Some(n) => Value::Number(-n),
+                None => Value::Null,
+            },
+            Node::Not(a) => match a.eval(scope) {
+                Value::Null => Value::Null,
+                value => Value::boolean(!value.truthy()),
+            },
+            Node::Binary(BinOp::And, a, b) => {
+                let (a, b) = (a.eval(scope), b.eval(scope));
+                let is_false = |v: &Value<'_>| !matches!(v, Value::Null) && !v.truthy();
+                if is_false(&a) || is_false(&b) {
+                    Value::boolean(false)
+                } else if matches!(a, Value::Null) || matches!(b, Value::Null) {
+                    Value::Null
+                } else {
+                    Value::boolean(true)
+                }
+            }
+            Node::Binary(BinOp::Or, a, b) => {
+                let (a, b) = (a.eval(scope), b.eval(scope));
+                if a.truthy() || b.truthy() {
+                    Value::boolean(true)
+                } else if matches!(a, Value::Null) || matches!(b, Value::Null) {
+                    Value::Null
+                } else {
+                    Value::boolean(false)
+                }
+            }
+            Node::Binary(op, a, b) => binary(*op, a.eval(scope), b.eval(scope)),
+            Node::IsNull(a, negated) => {
+                Value::boolean(matches!(a.eval(scope), Value::Null) != *negated)
+            }
+            Node::In(a, list, negated) => {
+                let value = a.eval(scope);
+                if matches!(value, Value::Null) {
+                    return Value::Null;
+                }
+                let found = list
+                    .iter()
+                    .any(|item| compare(&value, &item.eval(scope)) == Some(Ordering::Equal));
+                Value::boolean(found != *negated)
+            }
+            Node::Like(a, pattern, negated) => match (a.eval(scope), pattern.eval(scope)) {
+                (Value::Null, _) | (_, Value::Null) => Value::Null,
+                (value, pattern) => {
+                    Value::boolean(like(&value.text(), &pattern.text()) != *negated)
+                }
+            },
+            Node::Call(name, args) => call(name, args.iter().map(|a| a.eval(scope)).collect()),
+        }
+    }
+}
+
+fn binary<'a>(op: BinOp, a: Value<'a>, b: Value<'a>) -> Value<'a> {
+    if matches!(a, Value::Null) || matches!(b, Value::Null) {
+        return Value::Null;
+    }
+    let arithmetic = |f: fn(f64, f64) -> f64| match (a.number(), b.number()) {
+        (Some(x), Some(y)) => {
+            let n = f(x, y);
+            if n.is_finite() {
+                Value::Number(n)
+            } else {
+                Value::Null
+            }
+        }
+        _ => Value::Null,
+    };
+    let ordering = || compare(&a, &b).unwrap_or(Ordering::Equal);
+    match op {
+        BinOp::Add => arithmetic(|x, y| x + y),
+        BinOp::Sub => arithmetic(|x, y| x - y),
+        BinOp::Mul => arithmetic(|x, y| x * y),
+        BinOp::Div => arithmetic(|x, y| x / y),
+        BinOp::Rem => arithmetic(|x, y| x % y),
+        BinOp::Concat => Value::Text(Cow::Owned(format!("{}{}", a.text(), b.text()))),
+        BinOp::Eq => Value::boolean(ordering() == Ordering::Equal),
+        BinOp::Ne => Value::boolean(ordering() != Ordering::Equal),
+        BinOp::Lt => Value::boolean(ordering() == Ordering::Less),
+        BinOp::Le => Value::boolean(ordering() != Ordering::Greater),
+        BinOp::Gt => Value::boolean(ordering() == Ordering::Greater),
+        BinOp::Ge => Value::boolean(ordering() != Ordering::Less),
+        BinOp::And | BinOp::Or => unreachable!("handled with NULL logic in eval"),
+    }
+}
+
+/// Scalar functions; arities are checked when the query is bound.
+fn call<'a>(name: &str, args: Vec<Value<'a>>) -> Value<'a> {
+    let mut args = args.into_iter();
+    if name == "coalesce" {
+        return args
+            .find(|v| !matches!(v, Value::Null))
+            .unwrap_or(Value::Null);
+    }
+    let first = args.next().unwrap_or(Value::Null);
+    if matches!(first, Value::Null) {
+        return Value::Null;
+    }
+    match name {
+        "lower" => Value::Text(Cow::Owned(first.text().to_lowercase())),
+        "upper" => Value::Text(Cow::Owned(first.text().to_uppercase())),
+        "length" => Value::Number(first.text().chars().count() as f64),
+        "trim" => Value::Text(Cow::Owned(first.text().trim().to_string())),
+        "abs" => first
+            .number()
+            .map_or(Value::Null, |n| Value::Number(n.abs())),
+        "round" => {
+            let digits = args.next().and_then(|d| d.number()).unwrap_or(0.0) as i32;
+            let scale = 10f64.powi(digits);
+            first
+                .number()
+                .map_or(Value::Null, |n| Value::Number((n * scale).round() / scale))
+        }
+        _ => unreachable!("unknown functions are rejected when binding"),
+    }
+}
+
+/// Argument counts of the scalar functions (min, max).
+fn scalar_arity(name: &str) -> Option<(usize, usize)> {
+    match name {
+        "lower" | "upper" | "length" | "trim" | "abs" => Some((1, 1)),
+        "round" => Some((1, 2)),
+        "coalesce" => Some((1, usize::MAX)),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Aggregate {
+    func: AggFunc,
+    /// `None` for `count(*)`.
+    arg: Option<Node>,
+}
+
+#[derive(Debug, Clone)]
+enum AggState {
+    Count(u64),
+    Sum(Option<f64>),
+    Avg(f64, u64),
+    Min(Option<Value<'static>>),
+    Max(Option<Value<'static>>),
+}
+
+impl Aggregate {
+    fn start(&self) -> AggState {
+        match self.func {
+            AggFunc::Count => AggState::Count(0),
+            AggFunc::Sum => AggState::Sum(None),
+            AggFunc::Avg => AggState::Avg(0.0, 0),
+            AggFunc::Min => AggState::Min(None),
+            AggFunc::Max => AggState::Max(None),
+        }
+    }
+
+    /// Fold one row in. NULLs are skipped, as are non-numbers by `sum`
+    /// and `avg`.
+    fn add(&self, state: &mut AggState, scope: &Scope<'_>) {
+        let value = match &self.arg {
+            Some(arg) => arg.eval(scope),
+            None => Value::Number(1.0),
+        };
+        if matches!(value, Value::Null) {
+            return;
+        }
+        match state {
+            AggState::Count(n) => *n += 1,
+            AggState::Sum(sum) => {
+                if let Some(x) = value.number() {
+                    *sum = Some(sum.unwrap_or(0.0) + x);
+                }
+            }
+            AggState::Avg(sum, n) => {
+                if let Some(x) = value.number() {
+                    *sum += x;
+                    *n += 1;
+                }
+            }
+            AggState::Min(min) => keep(min, value, Ordering::Less),
+            AggState::Max(max) => keep(max, value, Ordering::Greater),
+        }
+    }
+}
+
+/// Replace `current` with `value` if it orders `wanted` against it.
+fn keep(current: &mut Option<Value<'static>>, value: Value<'_>, wanted: Ordering) {
+    let replace = match current {
+        Some(current) => compare(&value, current) == Some(wanted),
+        None => true,
+    };
+    if replace {
+        *current = Some(value.into_owned());
+    }
+}
+
+impl AggState {
+    fn merge(&mut self, other: AggState) {
+        match (self, other) {
+            (AggState::Count(a), AggState::Count(b)) => *a += b,
+            (AggState::Sum(a), AggState::Sum(Some(b))) => *a = Some(a.unwrap_or(0.0) + b),
+            (AggState::Avg(sa, na), AggState::Avg(sb, nb)) => {
+                *sa += sb;
+                *na += nb;
+            }
+            (AggState::Min(a), AggState::Min(Some(b))) => keep(a, b, Ordering::Less),
+            (AggState::Max(a), AggState::Max(Some(b))) => keep(a, b, Ordering::Greater),
+            _ => {}
+        }
+    }
+
+    fn finish(self) -> Value<'static> {
+        match self {
+            AggState::Count(n) => Value::Number(n as f64),
+            AggState::Sum(sum) => sum.map_or(Value::Null, Value::Number),
+            AggState::Avg(_, 0) => Value::Null,
+            AggState::Avg(sum, n) => Value::Number(sum / n as f64),
+            AggState::Min(v) | AggState::Max(v) => v.unwrap_or(Value::Null),
+        }
+    }
+}
+
+/// One group while aggregating: its first row, for the non-aggregate
+/// expressions, and the aggregate states.
+#[derive(Debug, Clone)]
+struct Group {
+    /// Row of the `FROM` file and, with a join, which of its matches.
+    first: (usize, usize),
+    fields: Vec<String>,
+    states: Vec<AggState>,
+}
+
+impl Group {
+    fn merge(&mut self, other: Group) {
+        if other.first < self.first {
+            self.first = other.first;
+            self.fields = other.fields;
+        }
+        for (a, b) in self.states.iter_mut().zip(other.states) {
+            a.merge(b);
+        }
+    }
+}
+
+/// An `ORDER BY` term: an output column (by position or alias) or an
+/// expression.
+#[derive(Debug, Clone)]
+enum OrderKey {
+    Output(usize),
+    Expr(Node),
+}
+
+/// A query bound to its files.
+struct Plan<'r> {
+    left: &'r CsvReader,
+    left_width: usize,
+    join: Option<JoinPlan<'r>>,
+    filter: Option<Node>,
+    outputs: Vec<Node>,
+    group_by: Vec<Node>,
+    having: Option<Node>,
+    aggregates: Vec<Aggregate>,
+    order_by: Vec<(OrderKey, bool)>,
+}
+
+struct JoinPlan<'r> {
+    reader: &'r CsvReader,
+    width: usize,
+    left_outer: bool,
+    left_keys: Vec<usize>,
+    /// Right rows by their key values; empty keys are left out, as NULLs
+    /// never match.
+    rows: HashMap<Vec<String>, Vec<usize>>,
+}
+
+/// A selected row with its `ORDER BY` values.
+type Output = (Vec<String>, Vec<Value<'static>>);
+
+impl Query {
+    /// Parse a `SELECT` statement.
+    pub fn parse(sql: &str) -> Result<Self> {
+        let tokens = tokenize(sql)?;
+        let mut parser = Parser {
+            sql,
+            tokens,
+            pos: 0,
+        };
+        let query = parser.query()?;
+        parser.eat_symbol(";");
+        if let Some(token) = parser.peek() {
+            return Err(invalid(format!("unexpected {token} after the query")));
+        }
+        Ok(query)
+    }
+
+    /// Paths of the files the query reads: the `FROM` file, then the
+    /// joined file if any.
+    pub fn tables(&self) -> Vec<&str> {
+        std::iter::once(&self.from)
+            .chain(self.join.as_ref().map(|j| &j.table))
+            .map(|t| t.path.as_str())
+            .collect()
+    }
+
+    /// Run the query over `readers`, opened from [`Query::tables`] in order.
+    pub fn execute(&self, readers: &[CsvReader]) -> Result<QueryResult> {
+        if readers.len() != self.tables().len() {
+            return Err(invalid(format!(
+                "the query reads {} files but {} were given",
+                self.tables().len(),
+                readers.len()
+            )));
+        }
+        let (plan, columns) = self.bind(readers)?;
+        let (mut outputs, rows_scanned) = if plan.group_by.is_empty() && plan.aggregates.is_empty()
+        {
+            let stop_after = match (self.order_by.is_empty() && !self.distinct, self.limit) {
+                (true, Some(limit)) => Some(self.offset + limit),
+                _ => None,
+            };
+            plan.select(stop_after)?
+        } else {
+            (plan.aggregate()?, plan.left.row_count())
+        };
+
+        if self.distinct {
+            let mut seen = HashSet::new();
+            outputs.retain(|(row, _)| seen.insert(row.clone()));
+        }
+        if !plan.order_by.is_empty() {
+            outputs.par_sort_by(|a, b| {
+                plan.order_by
+                    .iter()
+                    .zip(a.1.iter().zip(&b.1))
+                    .map(|((_, descending), (x, y))| sort_order(x, y, *descending))
+                    .find(|o| o.is_ne())
+                    .unwrap_or(Ordering::Equal)
+            });
+        }
+        let rows = outputs
+            .into_iter()
+            .skip(self.offset)
+            .take(self.limit.unwrap_or(usize::MAX))
+            .map(|(row, _)| row)
+            .collect();
+
+        Ok(QueryResult {
+            columns,
+            rows,
+            rows_scanned,
+        })
+    }
+
+    /// Resolve columns and aggregates against the files' headers.
+    fn bind<'r>(&self, readers: &'r [CsvReader]) -> Result<(Plan<'r>, Vec<String>)> {
+        let left = &readers[0];
+        let mut tables = vec![(&self.from, left.canonical_headers().to_vec(), 0)];
+        let left_width = left.canonical_headers().len();
+        if let Some(join) = &self.join {
+            let headers = readers[1].canonical_headers().to_vec();
+            tables.push((&join.table, headers, left_width));
+        }
+        let resolve = |node: &mut Node| -> Result<()> {
+            node.walk(&mut |n| {
+                if let Node::Column { table, name } = n {
+                    *n = Node::Field(resolve_column(&tables, table.as_deref(), name)?);
+                }
+                Ok(())
+            })
+        };
+        let no_aggregate = |node: &Node, clause: &str| {
+            if node.has_aggregate() {
+                Err(invalid(format!("aggregates are not allowed in {clause}")))
+            } else {
+                Ok(())
+            }
+        };
+
+        // Run after `resolve`, so aggregate arguments are already bound
+        let mut aggregates = Vec::new();
+        let mut bind_aggregates = |node: &mut Node| -> Result<()> {
+            node.walk(&mut |n| {
+                let Node::Call(name, args) = n else {
+                    return Ok(());
+                };
+                let arity = match AggFunc::from_name(name) {
+                    Some(AggFunc::Count) => Some((0, 1)),
+                    Some(_) => Some((1, 1)),
+                    None => scalar_arity(name),
+                };
+                let (min, max) =
+                    arity.ok_or_else(|| invalid(format!("unknown function {name}()")))?;
+                if args.len() < min || args.len() > max {
+                    return Err(invalid(format!("wrong number of arguments to {name}()")));
+                }
+                if args.iter().any(Node::has_aggregate) && AggFunc::from_name(name).is_some() {
+                    return Err(invalid("aggregates can't be nested".to_string()));
+                }
+                Ok(())
+            })?;
+            node.walk(&mut |n| {
+                if let Node::Call(name, args) = n {
+                    if let Some(func) = AggFunc::from_name(name) {
+                        let arg = args.pop();
+                        *n = Node::Agg(aggregates.len());
+                        aggregates.push(Aggregate { func, arg });
+                    }
+                }
+                Ok(())
+            })
+        };
+
+        let mut outputs = Vec::new();
+        let mut columns = Vec::new();
+        for item in &self.items {
+            match item {
+                SelectItem::Star(table) => {
+                    let mut any = false;
+                    for (table_ref, headers, offset) in &tables {
+                        if table.as_ref().is_some_and(|t| !table_ref.is_named(t)) {
+                            continue;
+                        }
+                        any = true;
+                        for (i, header) in headers.iter().enumerate() {
+                            outputs.push(Node::Field(offset + i));
+                            columns.push(header.clone());
+                        }
+                    }
+                    if !any {
+                        return Err(invalid(format!(
+                            "no table named {}",
+                            table.as_deref().unwrap_or_default()
+                        )));
+                    }
+                }
+                SelectItem::Expr { node, name } => {
+                    let mut node = node.clone();
+                    resolve(&mut node)?;
+                    bind_aggregates(&mut node)?;
+                    outputs.push(node);
+                    columns.push(name.clone());
+                }
+            }
+        }
+
+        let mut order_by = Vec::new();
+        for (node, descending) in &self.order_by {
+            let key = match node {
+                Node::Literal(Value::Number(n)) => {
+                    let position = *n as usize;
+                    if n.fract() != 0.0 || position == 0 || position > outputs.len() {
+                        return Err(invalid(format!("ORDER BY {n} is not a selected column")));
+                    }
+                    OrderKey::Output(position - 1)
+                }
+                Node::Column { table: None, name } if self.alias(name).is_some() => {
+                    OrderKey::Output(columns.iter().position(|c| c == name).expect("alias"))
+                }
+                _ => {
+                    let mut node = node.clone();
+                    resolve(&mut node)?;
+                    bind_aggregates(&mut node)?;
+                    OrderKey::Expr(node)
+                }
+            };
+            order_by.push((key, *descending));
+        }
+
+        let having = match &self.having {
+            Some(node) => {
+                let mut node = node.clone();
+                resolve(&mut node)?;
+                bind_aggregates(&mut node)?;
+                Some(node)
+            }
+            None => None,
+        };
+
+        let filter = match &self.filter {
+            Some(node) => {
+                no_aggregate(node, "WHERE")?;
+                let mut node = node.clone();
+                resolve(&mut node)?;
+                bind_aggregates(&mut node)?;
+                Some(node)
+            }
+            None => None,
+        };
+        let mut group_by = Vec::new();
+        for node in &self.group_by {
+            // A select alias, unless a column has the same name
+            let node = match node {
+                Node::Column { table: None, name }
+                    if resolve_column(&tables, None, name).is_err() =>
+                {
+                    self.alias(name).unwrap_or(node)
+                }
+                _ => node,
+            };
+            no_aggregate(node, "GROUP BY")?;
+            let mut node = node.clone();
+            resolve(&mut node)?;
+            bind_aggregates(&mut node)?;
+            group_by.push(node);
+        }
+
+        if having.is_some() && self.group_by.is_empty() && aggregates.is_empty() {
+            return Err(invalid("HAVING needs GROUP BY or an aggregate".to_string()));
+        }
+
+        let join = match &self.join {
+            Some(join) => {
+                let mut on = join.on.clone();
+                no_aggregate(&on, "ON")?;
+                resolve(&mut on)?;
+                Some(self.bind_join(&readers[1], join, &on, left_width)?)
+            }
+            None => None,
+        };
+
+        let plan = Plan {
+            left,
+            left_width,
+            join,
+            filter,
+            outputs,
+            group_by,
+            having,
+            aggregates,
+            order_by,
+        };
+        Ok((plan, columns))
+    }
+
+    /// The expression selected under `alias`.
+    fn alias(&self, alias: &str) -> Option<&Node> {
+        self.items.iter().find_map(|item| match item {
+            SelectItem::Expr { node, name } if name == alias => Some(node),
+            _ => None,
+        })
+    }
+
+    /// Split the `ON` condition into key column pairs and index the right file.
+    fn bind_join<'r>(
+        &self,
+        reader: &'r CsvReader,
+        join: &JoinClause,
+        on: &Node,
+        left_width: usize,
+    ) -> Result<JoinPlan<'r>> {
+        let mut pairs = Vec::new();
+        let mut pending = vec![on];
+        while let Some(node) = pending.pop() {
+            match node {
+                Node::Binary(BinOp::And, a, b) => pending.extend([&**b, &**a]),
+                Node::Binary(BinOp::Eq, a, b) => match (&**a, &**b) {
+                    (&Node::Field(x), &Node::Field(y)) if (x < left_width) != (y < left_width) => {
+                        pairs.push((x.min(y), x.max(y) - left_width));
+                    }
+                    _ => {
+                        return Err(invalid(
+                            "JOIN ... ON must compare a column of each file".to_string(),
+                        ))
+                    }
+                },
+                _ => {
+                    return Err(invalid(
+                        "JOIN ... ON supports only column equalities joined by AND".to_string(),
+                    ))
+                }
+            }
+        }
+        let (left_keys, right_keys): (Vec<usize>, Vec<usize>) = pairs.into_iter().unzip();
+
+        let rows = (0..reader.row_count())
+            .into_par_iter()
+            .with_min_len(4096)
+            .try_fold(
+                HashMap::new,
+                |mut rows: HashMap<Vec<String>, Vec<usize>>, row| {
+                    let fields = reader.get_row(row)?;
+                    let key: Vec<String> = right_keys
+                        .iter()
+                        .map(|&c| fields.get(c).cloned().unwrap_or_default())
+                        .collect();
+                    if key.iter().all(|k| !k.is_empty()) {
+                        rows.entry(key).or_default().push(row);
+                    }
+                    Ok::<_, MassiveCsvError>(rows)
+                },
+            )
+            .try_reduce(HashMap::new, |a, b| {
+                let (mut into, from) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+                for (key, rows) in from {
+                    into.entry(key).or_default().extend(rows);
+                }
+                Ok(into)
+            })?
+            .into_iter()
+            .map(|(key, mut rows)| {
+                rows.sort_unstable();
+                (key, rows)
+            })
+            .collect();
+
+        Ok(JoinPlan {
+            reader,
+            width: reader.canonical_headers().len(),
+            left_outer: join.left_outer,
+            left_keys,
+            rows,
+        })
+    }
+}
+
+/// Position of a column in the combined row.
+fn resolve_column(
+    tables: &[(&TableRef, Vec<String>, usize)],
+    table: Option<&str>,
+    name: &str,
+) -> Result<usize> {
+    let mut found = Vec::new();
+    for (table_ref, headers, offset) in tables {
+        if table.is_some_and(|t| !table_ref.is_named(t)) {
+            continue;
+        }
+        let position = crate::parser::column_position(headers, name).or_else(|| {
+            let mut matches = headers
+                .iter()
+                .enumerate()
+                .filter(|(_, h)| h.eq_ignore_ascii_case(name));
+            match (matches.next(), matches.next()) {
+                (Some((i, _)), None) => Some(i),
+                _ => None,
+            }
+        });
+        if let Some(position) = position {
+            found.push(offset + position);
+        }
+    }
+    let qualified = || match table {
+        Some(table) => format!("{table}.{name}"),
+        None => name.to_string(),
+    };
+    match found[..] {
+        [index] => Ok(index),
+        [] => Err(MassiveCsvError::ColumnNotFound(qualified())),
+        _ => Err(invalid(format!(
+            "column {name} is in both files; qualify it with a table name"
+        ))),
+    }
+}
+
+impl Plan<'_> {
+    /// Combined rows for one row of the `FROM` file that pass `WHERE`.
+    fn rows_for(&self, row: usize) -> Result<Vec<Vec<String>>> {
+        let mut fields = self.left.get_row(row)?;
+        fields.resize(self.left_width, String::new());
+        let combined = match &self.join {
+            None => vec![fields],
+            Some(join) => {
+                let key: Vec<String> = join.left_keys.iter().map(|&c| fields[c].clone()).collect();
+                let matches = join.rows.get(&key).map_or(&[][..], Vec::as_slice);
+                let mut combined = Vec::with_capacity(matches.len().max(1));
+                for &right in matches {
+                    let mut row = fields.clone();
+                    let mut right = join.reader.get_row(right)?;
+                    right.resize(join.width, String::new());
+                    row.extend(right);
+                    combined.push(row);
+                }
+                if combined.is_empty() && join.left_outer {
+                    fields.resize(self.left_width + join.width, String::new());
+                    combined.push(fields);
+                }
+                combined
+            }
+        };
+        Ok(combined
+            .into_iter()
+            .filter(|fields| {
+                self.filter
+                    .as_ref()
+                    .is_none_or(|filter| filter.eval(&Scope { fields, aggs: &[] }).truthy())
+            })
+            .collect())
+    }
+
+    fn output(&self, scope: &Scope<'_>) -> Output {
+        let row: Vec<String> = self
+            .outputs
+            .iter()
+            .map(|node| node.eval(scope).text().into_owned())
+            .collect();
+        let keys = self
+            .order_by
+            .iter()
+            .map(|(key, _)| match key {
+                OrderKey::Output(i) => self.outputs[*i].eval(scope).into_owned(),
+                OrderKey::Expr(node) => node.eval(scope).into_owned(),
+            })
+            .collect();
+        (row, keys)
+    }
+
+    /// Selected rows in file order, scanning in parallel batches until
+    /// `stop_after` rows are found. Also returns the rows scanned.
+    fn select(&self, stop_after: Option<usize>) -> Result<(Vec<Output>, usize)> {
+        let rows = self.left.row_count();
+        let mut outputs = Vec::new();
+        let mut start = 0;
+        while start < rows {
+            let end = (start + SCAN_CHUNK).min(rows);
+            let batch = (start..end)
+                .into_par_iter()
+                .map(|row| {
+                    Ok(self
+                        .rows_for(row)?
+                        .iter()
+                        .map(|fields| self.output(&Scope { fields, aggs: &[] }))
+                        .collect::<Vec<_>>())
+                })
+                .collect::<Result<Vec<_>>>()?;
+            outputs.extend(batch.into_iter().flatten());
+            start = end;
+            if stop_after.is_some_and(|n| outputs.len() >= n) {
+                break;
+            }
+        }
+        Ok((outputs, start))
+    }
+
+    /// One output row per group, groups in order of their first row. Each
+    /// thread aggregates into its own map; the maps are merged at the end.
+    fn aggregate(&self) -> Result<Vec<Output>> {
+        let groups = (0..self.left.row_count())
+            .into_par_iter()
+            .with_min_len(4096)
+            .try_fold(
+                HashMap::new,
+                |mut groups: HashMap<Vec<String>, Group>, row| {
+                    for (i, fields) in self.rows_for(row)?.into_iter().enumerate() {
+                        let scope = Scope {
+                            fields: &fields,
+                            aggs: &[],
+                        };
+                        let key: Vec<String> = self
+                            .group_by
+                            .iter()
+                            .map(|node| match node.eval(&scope) {
+                                // Keeps NULL apart from every text value
+                                Value::Null => "\0".to_string(),
+                                value => value.text().into_owned(),
+                            })
+                            .collect();
+                        let group = groups.entry(key).or_insert_with(|| Group {
+                            first: (row, i),
+                            fields: fields.clone(),
+                            states: self.aggregates.iter().map(Aggregate::start).collect(),
+                        });
+                        for (aggregate, state) in self.aggregates.iter().zip(&mut group.states) {
+                            aggregate.add(state, &scope);
+                        }
+                    }
+                    Ok::<_, MassiveCsvError>(groups)
+                },
+            )
+            .try_reduce(HashMap::new, |a, b| {
+                let (mut into, from) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+                for (key, group) in from {
+                    match into.get_mut(&key) {
+                        Some(existing) => existing.merge(group),
+                        None => {
+                            into.insert(key, group);
+                        }
+                    }
+                }
+                Ok(into)
+            })?;
+
+        let mut groups: Vec<Group> = groups.into_values().collect();
+        if groups.is_empty() && self.group_by.is_empty() {
+            // Aggregates over no rows still give one row
+            groups.push(Group {
+                first: (0, 0),
+                fields: Vec::new(),
+                states: self.aggregates.iter().map(Aggregate::start).collect(),
+            });
+        }
+        groups.sort_unstable_by_key(|group| group.first);
+
+        let mut outputs = Vec::with_capacity(groups.len());
+        for group in groups {
+            let aggs: Vec<Value<'static>> =
+                group.states.into_iter().map(AggState::finish).collect();
+            let scope = Scope {
+                fields: &group.fields,
+                aggs: &aggs,
+            };
+            if self
+                .having
+                .as_ref()
+                .is_none_or(|having| having.eval(&scope).truthy())
+            {
+                outputs.push(self.output(&scope));
+            }
+        }
+        Ok(outputs)
+    }
+}
+
+fn invalid(message: String) -> MassiveCsvError {
+    MassiveCsvError::InvalidQuery(message)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// A bare word: keyword, column, alias or function name.
+    Word(String),
+    /// A `"quoted"` or `` `quoted` `` identifier.
+    Ident(String),
+    /// A `'quoted'` string.
+    Str(String),
+    Number(f64),
+    Symbol(&'static str),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Word(w) => write!(f, "'{w}'"),
+            Token::Ident(i) => write!(f, "\"{i}\""),
+            Token::Str(s) => write!(f, "string '{s}'"),
+            Token::Number(n) => write!(f, "{n}"),
+            Token::Symbol(s) => write!(f, "'{s}'"),
+        }
+    }
+}
+
+const SYMBOLS: &[&str] = &[
+    "<=", ">=", "<>", "!=", "==", "||", "=", "<", ">", "+", "-", "*", "/", "%", ",", "(", ")", ".",
+    ";",
+];
+
+/// Tokens with their byte ranges in the input.
+fn tokenize(input: &str) -> Result<Vec<(Token, usize, usize)>> {
+    let mut tokens = Vec::new();
+    let bytes = input.as_bytes();
+    let mut i = 0;
+    while i < input.len() {
+        let rest = &input[i..];
+        let c = rest.chars().next().expect("non-empty");
+        if c.is_whitespace() {
+            i += c.len_utf8();
+            continue;
+        }
+        if rest.starts_with("--") {
+            i += rest.find('\n').unwrap_or(rest.len());
+            continue;
+        }
+
+        let (token, len) = if matches!(c, '\'' | '"' | '`') {
+            let (text, len) = quoted(rest)?;
+            let token = if c == '\'' {
+                Token::Str(text)
+            } else {
+                Token::Ident(text)
+            };
+            (token, len)
+        } else if c.is_ascii_digit()
+            || (c == '.' && bytes.get(i + 1).is_some_and(u8::is_ascii_digit))
+        {
+            let mut len = rest
+                .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                .unwrap_or(rest.len());
+            if rest[len..].starts_with(['e', 'E']) {
+                let exponent = rest[len + 1..].trim_start_matches(['+', '-']);
+                let digits = exponent
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(exponent.len());
+                if digits > 0 {
+                    len = rest.len() - exponent.len() + digits;
+                }
+            }
+            let number = rest[..len]
+                .parse()
+                .map_err(|_| invalid(format!("invalid number {}", &rest[..len])))?;
+            (Token::Number(number), len)
+        } else if c.is_alphabetic() || c == '_' {
+            let len = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            (Token::Word(rest[..len].to_string()), len)
+        } else {
+            let symbol = SYMBOLS
+                .iter()
+                .find(|s| rest.starts_with(**s))
+                .copied()
+                .ok_or_else(|| invalid(format!("unexpected character '{c}'")))?;
+            (Token::Symbol(symbol), symbol.len())
+        };
+        tokens.push((token, i, i + len));
+        i += len;
+    }
+    Ok(tokens)
+}
+
+/// Read a quoted string or identifier at the start of `input`; returns its
+/// text and length in bytes. The quote is escaped by doubling it.
+fn quoted(input: &str) -> Result<(String, usize)> {
+    let quote = input.chars().next().expect("non-empty");
+    let mut text = String::new();
+    let mut chars = input.char_indices().skip(1).peekable();
+    while let Some((i, c)) = chars.next() {
+        if c != quote {
+            text.push(c);
+        } else if chars.next_if(|&(_, next)| next == quote).is_some() {
+            text.push(quote);
+        } else {
+            return Ok((text, i + 1));
+        }
+    }
+    Err(invalid(format!(
+        "unterminated string starting with {quote}"
+    )))
+}
+
+struct Parser<'s> {
+    sql: &'s str,
+    tokens: Vec<(Token, usize, usize)>,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _, _)| token)
+    }
+
+    fn next(&mut self) -> Result<Token> {
+        let token = self
+            .peek()
+            .cloned()
+            .ok_or_else(|| invalid("unexpected end of query".to_string()))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(w)) if w.eq_ignore_ascii_case(keyword))
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let found = self.is_keyword(keyword);
+        self.pos += found as usize;
+        found
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<()> {
+        if self.eat_keyword(keyword) {
+            return Ok(());
+        }
+        Err(invalid(match self.peek() {
+            Some(token) => format!("expected {} but found {token}", keyword.to_uppercase()),
+            None => format!("expected {}", keyword.to_uppercase()),
+        }))
+    }
+
+    fn eat_symbol(&mut self, symbol: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Symbol(s)) if *s == symbol);
+        self.pos += found as usize;
+        found
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> Result<()> {
+        if self.eat_symbol(symbol) {
+            return Ok(());
+        }
+        Err(invalid(match self.peek() {
+            Some(token) => format!("expected '{symbol}' but found {token}"),
+            None => format!("expected '{symbol}'"),
+        }))
+    }
+
+    /// A bare word that isn't a keyword, or a quoted identifier.
+    fn identifier(&mut self) -> Option<String> {
+        match self.peek()? {
+            Token::Ident(name) => {
+                let name = name.clone();
+                self.pos += 1;
+                Some(name)
+            }
+            Token::Word(word) if !is_keyword(word) => {
+                let word = word.clone();
+                self.pos += 1;
+                Some(word)
+            }
+            _ => None,
+        }
+    }
+
+    fn count(&mut self) -> Result<usize> {
+        match self.next()? {
+            Token::Number(n) if n >= 0.0 && n.fract() == 0.0 => Ok(n as usize),
+            token => Err(invalid(format!("expected a row count but found {token}"))),
+        }
+    }
+
+    fn query(&mut self) -> Result<Query> {
+        self.expect_keyword("select")?;
+        let distinct = self.eat_keyword("distinct");
+        let mut items = vec![self.select_item()?];
+        while self.eat_symbol(",") {
+            items.push(self.select_item()?);
+        }
+
+        self.expect_keyword("from")?;
+        let from = self.table()?;
+        let join = if self.is_keyword("join") || self.is_keyword("inner") || self.is_keyword("left")
+        {
+            let left_outer = self.eat_keyword("left");
+            if left_outer {
+                self.eat_keyword("outer");
+            } else {
+                self.eat_keyword("inner");
+            }
+            self.expect_keyword("join")?;
+            let table = self.table()?;
+            self.expect_keyword("on")?;
+            Some(JoinClause {
+                left_outer,
+                table,
+                on: self.expr()?,
+            })
+        } else {
+            None
+        };
+
+        let filter = if self.eat_keyword("where") {
+            Some(self.expr()?)
+        } else {
+            None
+        };
+        let mut group_by = Vec::new();
+        if self.eat_keyword("group") {
+            self.expect_keyword("by")?;
+            group_by.push(self.expr()?);
+            while self.eat_symbol(",") {
+                group_by.push(self.expr()?);
+            }
+        }
+        let having = if self.eat_keyword("having") {
+            Some(self.expr()?)
+        } else {
+            None
+        };
+        let mut order_by = Vec::new();
+        if self.eat_keyword("order") {
+            self.expect_keyword("by")?;
+            loop {
+                let node = self.expr()?;
+                let descending = self.eat_keyword("desc");
+                if !descending {
+                    self.eat_keyword("asc");
+                }
+                order_by.push((node, descending));
+                if !self.eat_symbol(",") {
+                    break;
+                }
+            }
+        }
+        let mut limit = None;
+        let mut offset = 0;
+        if self.eat_keyword("limit") {
+            limit = Some(self.count()?);
+            if self.eat_keyword("offset") {
+                offset = self.count()?;
+            }
+        }
+
+        Ok(Query {
+            distinct,
+            items,
+            from,
+            join,
+            filter,
+            group_by,
+            having,
+            order_by,
+            limit,
+            offset,
+        })
+    }
+
+    fn select_item(&mut self) -> Result<SelectItem> {
+        if self.eat_symbol("*") {
+            return Ok(SelectItem::Star(None));
+        }
+        // `t.*`
+        if let (Some((Token::Word(table), _, _)), Some((Token::Symbol("."), _, _))) =
+            (self.tokens.get(self.pos), self.tokens.get(self.pos + 1))
+        {
+            if self.tokens.get(self.pos + 2).map(|t| &t.0) == Some(&Token::Symbol("*")) {
+                let table = table.clone();
+                self.pos += 3;
+                return Ok(SelectItem::Star(Some(table)));
+            }
+        }
+
+        let start = self.tokens[self.pos.min(self.tokens.len() - 1)].1;
+        let node = self.expr()?;
+        let end = self.tokens[self.pos - 1].2;
+        let name = if self.eat_keyword("as") {
+            self.identifier()
+                .ok_or_else(|| invalid("expected an alias after AS".to_string()))?
+        } else if let Some(alias) = self.identifier() {
+            alias
+        } else if let Node::Column { name, .. } = &node {
+            name.clone()
+        } else {
+            self.sql[start..end].to_string()
+        };
+        Ok(SelectItem::Expr { node, name })
+    }
+
+    fn table(&mut self) -> Result<TableRef> {
+        let path = match self.next()? {
+            Token::Str(path) | Token::Ident(path) => path,
+            token => {
+                return Err(invalid(format!(
+                    "expected a quoted file path but found {token}"
+                )))
+            }
+        };
+        let alias = if self.eat_keyword("as") {
+            Some(
+                self.identifier()
+                    .ok_or_else(|| invalid("expected an alias after AS".to_string()))?,
+            )
+        } else {
+            self.identifier()
+        };
+        Ok(TableRef { path, alias })
+    }
+
+    fn expr(&mut self) -> Result<Node> {
+        let mut node = self.and()?;
+        while self.eat_keyword("or") {
+            node = Node::Binary(BinOp::Or, Box::new(node), Box::new(self.and()?));
+        }
+        Ok(node)
+    }
+
+    fn and(&mut self) -> Result<Node> {
+        let mut node = self.not()?;
+        while self.eat_keyword("and") {
+            node = Node::Binary(BinOp::And, Box::new(node), Box::new(self.not()?));
+        }
+        Ok(node)
+    }
+
+    fn not(&mut self) -> Result<Node> {
+        if self.eat_keyword("not") {
+            return Ok(Node::Not(Box::new(self.not()?)));
+        }
+        self.predicate()
+    }
+
+    fn predicate(&mut self) -> Result<Node> {
+        let left = self.additive()?;
+        if self.eat_keyword("is") {
+            let negated = self.eat_keyword("not");
+            self.expect_keyword("null")?;
+            return Ok(Node::IsNull(Box::new(left), negated));
+        }
+        let negated = self.eat_keyword("not");
+        if self.eat_keyword("in") {
+            self.expect_symbol("(")?;
+            let mut list = vec![self.expr()?];
+            while self.eat_symbol(",") {
+                list.push(self.expr()?);
+            }
+            self.expect_symbol(")")?;
+            return Ok(Node::In(Box::new(left), list, negated));
+        }
+        if self.eat_keyword("like") {
+            return Ok(Node::Like(
+                Box::new(left),
+                Box::new(self.additive()?),
+                negated,
+            ));
+        }
+        if self.eat_keyword("between") {
+            let low = self.additive()?;
+            self.expect_keyword("and")?;
+            let high = self.additive()?;
+            let between = Node::Binary(
+                BinOp::And,
+                Box::new(Node::Binary(
+                    BinOp::Ge,
+                    Box::new(left.clone()),
+                    Box::new(low),
+                )),
+                Box::new(Node::Binary(BinOp::Le, Box::new(left), Box::new(high))),
+            );
+            return Ok(if negated {
+                Node::Not(Box::new(between))
+            } else {
+                between
+            });
+        }
+        if negated {
+            return Err(invalid(
+                "expected IN, LIKE or BETWEEN after NOT".to_string(),
+            ));
+        }
+
+        let op = match self.peek() {
+            Some(Token::Symbol("=" | "==")) => BinOp::Eq,
+            Some(Token::Symbol("!=" | "<>")) => BinOp::Ne,
+            Some(Token::Symbol("<")) => BinOp::Lt,
+            Some(Token::Symbol("<=")) => BinOp::Le,
+            Some(Token::Symbol(">")) => BinOp::Gt,
+            Some(Token::Symbol(">=")) => BinOp::Ge,
+            _ => return Ok(left),
+        };
+        self.pos += 1;
+        Ok(Node::Binary(op, Box::new(left), Box::new(self.additive()?)))
+    }
+
+    fn additive(&mut self) -> Result<Node> {
+        let mut node = self.multiplicative()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Symbol("+")) => BinOp::Add,
+                Some(Token::Symbol("-")) => BinOp::Sub,
+                Some(Token::Symbol("||")) => BinOp::Concat,
+                _ => return Ok(node),
+            };
+            self.pos += 1;
+            node = Node::Binary(op, Box::new(node), Box::new(self.multiplicative()?));
+        }
+    }
+
+    fn multiplicative(&mut self) -> Result<Node> {
+        let mut node = self.unary()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Symbol("*")) => BinOp::Mul,
+                Some(Token::Symbol("/")) => BinOp::Div,
+                Some(Token::Symbol("%")) => BinOp::Rem,
+                _ => return Ok(node),
+            };
+            self.pos += 1;
+            node = Node::Binary(op, Box::new(node), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Node> {
+        if self.eat_symbol("-") {
+            return Ok(Node::Neg(Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Node> {
+        match self.next()? {
+            Token::Number(n) => Ok(Node::Literal(Value::Number(n))),
+            Token::Str(s) => Ok(Node::Literal(Value::Text(Cow::Owned(s)))),
+            Token::Symbol("(") => {
+                let node = self.expr()?;
+                self.expect_symbol(")")?;
+                Ok(node)
+            }
+            Token::Word(word) if word.eq_ignore_ascii_case("null") => {
+                Ok(Node::Literal(Value::Null))
+            }
+            Token::Word(word) if !is_keyword(&word) && self.eat_symbol("(") => {
+                let name = word.to_ascii_lowercase();
+                let mut args = Vec::new();
+                if name == "count" && self.eat_symbol("*") {
+                    // count(*) has no arguments
+                } else if self.peek() != Some(&Token::Symbol(")")) {
+                    args.push(self.expr()?);
+                    while self.eat_symbol(",") {
+                        args.push(self.expr()?);
+                    }
+                }
+                self.expect_symbol(")")?;
+                Ok(Node::Call(name, args))
+            }
+            Token::Word(word) if is_keyword(&word) => Err(invalid(format!(
+                "unexpected {}; quote column names that are keywords",
+                word.to_uppercase()
+            ))),
+            Token::Word(name) | Token::Ident(name) => {
+                if self.eat_symbol(".") {
+                    let column = self
+                        .identifier()
+                        .ok_or_else(|| invalid(format!("expected a column after {name}.")))?;
+                    return Ok(Node::Column {
+                        table: Some(name),
+                        name: column,
+                    });
+                }
+                Ok(Node::Column { table: None, name })
+            }
+            token => Err(invalid(format!("unexpected {token}"))),
+        }
+    }
+}
+
+fn is_keyword(word: &str) -> bool {
+    KEYWORDS.iter().any(|k| k.eq_ignore_ascii_case(word))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn make_csv(content: &str) -> tempfile::NamedTempFile {
+        let mut f = tempfile::NamedTempFile::new().unwrap();
+        f.write_all(content.as_bytes()).unwrap();
+        f.flush().unwrap();
+        f
+    }
+
+    const SALES: &str = "id,region,amount,status\n\
+        1,eu,100,paid\n\
+        2,us,250,paid\n\
+        3,eu,40,void\n\
+        4,apac,,paid\n\
+        5,us,10,paid\n\
+        6,eu,60,paid\n";
+
+    fn run(sql: &str, files: &[&tempfile::NamedTempFile]) -> QueryResult {
+        let query = Query::parse(sql).unwrap();
+        let readers: Vec<CsvReader> = files
+            .iter()
+            .map(|f| CsvReader::open(f.path()).unwrap())
+            .collect();
+        query.execute(&readers).unwrap()
+    }
+
+    #[test]
+    fn selects_filters_orders_and_limits() {
+        let f = make_csv(SALES);
+        let result = run(
+            "SELECT id, amount * 2 AS double FROM 'sales.csv' \
+             WHERE status = 'paid' AND amount >= 60 ORDER BY amount DESC",
+            &[&f],
+        );
+        assert_eq!(result.columns, vec!["id", "double"]);
+        assert_eq!(
+            result.rows,
+            vec![vec!["2", "500"], vec!["1", "200"], vec!["6", "120"]]
+        );
+
+        let result = run(
+            "select * from 'x.csv' where region in ('eu', 'apac') and amount is null",
+            &[&f],
+        );
+        assert_eq!(result.columns, vec!["id", "region", "amount", "status"]);
+        assert_eq!(result.rows, vec![vec!["4", "apac", "", "paid"]]);
+
+        let result = run(
+            "SELECT id FROM 'x.csv' WHERE region LIKE 'e%' LIMIT 2 OFFSET 1",
+            &[&f],
+        );
+        assert_eq!(result.rows, vec![vec!["3"], vec!["6"]]);
+
+        // Empty amounts sort last either way
+        let result = run("SELECT id FROM 'x.csv' ORDER BY amount, 1 DESC", &[&f]);
+        assert_eq!(result.rows.concat(), vec!["5", "3", "6", "1", "2", "4"]);
+
+        let result = run(
+            "SELECT DISTINCT upper(region) r FROM 'x.csv' ORDER BY r",
+            &[&f],
+        );
+        assert_eq!(result.rows.concat(), vec!["APAC", "EU", "US"]);
+    }
+
+    #[test]
+    fn groups_and_aggregates() {
+        let f = make_csv(SALES);
+        let result = run(
+            "SELECT region, count(*), sum(amount), avg(amount), max(amount) \
+             FROM 'sales.csv' GROUP BY region",
+            &[&f],
+        );
+        assert_eq!(
+            result.columns,
+            vec![
+                "region",
+                "count(*)",
+                "sum(amount)",
+                "avg(amount)",
+                "max(amount)"
+            ]
+        );
+        assert_eq!(
+            result.rows,
+            vec![
+                vec!["eu", "3", "200", "66.66666666666667", "100"],
+                vec!["us", "2", "260", "130", "250"],
+                vec!["apac", "1", "", "", ""],
+            ]
+        );
+
+        let result = run(
+            "SELECT region, count(amount) n FROM 'x.csv' WHERE status = 'paid' \
+             GROUP BY region HAVING count(*) > 1 ORDER BY n DESC, region",
+            &[&f],
+        );
+        assert_eq!(result.rows, vec![vec!["eu", "2"], vec!["us", "2"]]);
+
+        let result = run("SELECT count(*), min(region) FROM 'x.csv'", &[&f]);
+        assert_eq!(result.rows, vec![vec!["6", "apac"]]);
+        let result = run("SELECT count(*) FROM 'x.csv' WHERE id > 100", &[&f]);
+        assert_eq!(result.rows, vec![vec!["0"]]);
+    }
+
+    #[test]
+    fn joins_a_second_file() {
+        let sales = make_csv(SALES);
+        let regions = make_csv("code,name\neu,Europe\nus,United States\n");
+        let result = run(
+            "SELECT s.id, r.name FROM 'sales.csv' s JOIN 'regions.csv' r ON s.region = r.code \
+             WHERE s.amount > 50",
+            &[&sales, &regions],
+        );
+        assert_eq!(
+            result.rows,
+            vec![
+                vec!["1", "Europe"],
+                vec!["2", "United States"],
+                vec!["6", "Europe"]
+            ]
+        );
+
+        let result = run(
+            "SELECT coalesce(name, 'unknown') AS area, sum(amount) FROM 's.csv' \
+             LEFT JOIN 'r.csv' ON region = code GROUP BY area ORDER BY 2",
+            &[&sales, &regions],
+        );
+        assert_eq!(
+            result.rows,
+            vec![
+                vec!["Europe", "200"],
+                vec!["United States", "260"],
+                vec!["unknown", ""]
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_invalid_queries() {
+        let f = make_csv(SALES);
+        let error = |sql: &str| {
+            let reader = CsvReader::open(f.path()).unwrap();
+            match Query::parse(sql).and_then(|q| q.execute(&[reader])) {
+                Err(e) => e,
+                Ok(result) => panic!("{sql} gave {result:?}"),
+            }
+        };
+        assert!(matches!(
+            error("SELECT price FROM 'x.csv'"),
+            MassiveCsvError::ColumnNotFound(_)
+        ));
+        for sql in [
+            "SELECT id FROM sales",
+            "SELECT id FROM 'x.csv' WHERE",
+            "SELECT id FROM 'x.csv' WHERE sum(amount) > 1",
+            "SELECT frobnicate(id) FROM 'x.csv'",
+            "SELECT id FROM 'x.csv' ORDER BY 3",
+            "SELECT 'unterminated FROM 'x.csv'",
+            "SELECT id FROM 'x.csv' extra tokens",
+        ] {
+            assert!(
+                matches!(error(sql), MassiveCsvError::InvalidQuery(_)),
+                "{sql}"
+            );
+        }
+    }
+
+    #[test]
+    fn evaluates_row_expressions() {
+        let headers: Vec<String> = ["price", "qty", "first", "last"]
+            .iter()
+            .map(|h| h.to_string())
+            .collect();
+        let row: Vec<String> = ["2.5", "4", "Ada", "Lovelace"]
+            .iter()
+            .map(|f| f.to_string())
+            .collect();
+        let eval = |text: &str| RowExpr::parse(text, &headers).unwrap().eval(&row);
+        assert_eq!(eval("price * qty"), "10");
+        assert_eq!(eval("first || ' ' || upper(last)"), "Ada LOVELACE");
+        assert_eq!(eval("qty > 3"), "1");
+        assert_eq!(eval("coalesce(NULL, round(price))"), "3");
+
+        for bad in ["sum(price)", "price +", "nope * 2", "t.price", "lower()"] {
+            assert!(RowExpr::parse(bad, &headers).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn matches_like_patterns() {
+        assert!(like("europe", "eu%"));
+        assert!(like("europe", "%rop_"));
+        assert!(like("a%b", "a%b"));
+        assert!(!like("europe", "eu"));
+        assert!(!like("us", "_"));
+        assert!(like("", "%"));
+    }
+}
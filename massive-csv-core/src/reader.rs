@@ -1,23 +1,292 @@
 use memmap2::Mmap;
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::fs::File;
+use std::hash::{DefaultHasher, Hasher};
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::marker::PhantomData;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
 
+use crate::background::Progress;
+use crate::compression::{decompress_to_tempfile, Compression};
+use crate::editor::CsvEditor;
+use crate::encoding::{decode_to_tempfile, Encoding};
 use crate::error::{MassiveCsvError, Result};
-use crate::parser::{detect_delimiter, parse_headers, parse_row};
+use crate::ndjson::ndjson_to_csv;
+use crate::parser::{
+    detect_delimiter_with, detect_dialect, detect_line_ending, field_at, field_count,
+    parse_headers, parse_row, DetectOptions, Dialect, EscapeStyle, FixedWidth, LineEnding,
+};
+#[cfg(feature = "serde")]
+use crate::record::deserialize_fields;
+use crate::record::{HeaderMap, Record};
+use crate::validate::IssueKind;
 
 /// A memory-mapped CSV reader with O(1) row access via line indexing.
 pub struct CsvReader {
     mmap: Mmap,
     /// Byte offset of the start of each data row (row 0 = first row after header).
     line_index: Vec<u64>,
-    headers: Vec<String>,
-    delimiter: u8,
+    headers: Arc<HeaderMap>,
+    dialect: Dialect,
+    delimiter_confidence: f64,
+    encoding: Encoding,
+    compression: Compression,
+    options: OpenOptions,
+    line_ending: LineEnding,
+    trailing_newline: bool,
     path: PathBuf,
+    fingerprint: FileFingerprint,
+    /// The file `mmap` maps, when it is mapped directly rather than through
+    /// a decompressed or decoded copy; see [`CsvReader::is_changed_in_place`].
+    mapped: Option<File>,
+    /// Temporary file holding a stream read by [`CsvReader::from_reader`],
+    /// deleted when the last reader using it is dropped.
+    spill: Option<Arc<tempfile::TempPath>>,
+    /// Whether columns checked by filter plans are in ascending order, by
+    /// (column, numeric), so each is read once for the life of the mapping.
+    sorted_columns: Mutex<HashMap<(usize, bool), bool>>,
+}
+
+/// Overrides for the dialect [`CsvReader::open`] would otherwise detect.
+#[derive(Debug, Clone, Default)]
+pub struct OpenOptions {
+    /// Field delimiter.
+    pub delimiter: Option<u8>,
+    /// Quote character, e.g. `'` for some ERP exports.
+    pub quote_char: Option<u8>,
+    /// How quotes inside quoted fields are escaped.
+    pub escape: Option<EscapeStyle>,
+    /// Recover malformed rows instead of failing; see [`CsvReader::get_row_checked`].
+    pub lenient: bool,
+    /// Sample size and candidates for delimiter detection when `delimiter` is unset.
+    pub detect: DetectOptions,
+    /// Read the file as fixed-width columns instead of delimited fields. The
+    /// first line is the header, split with the same widths.
+    pub fixed_width: Option<FixedWidth>,
+    /// Text encoding of the file. When unset it is detected with the
+    /// `encodings` feature and assumed to be UTF-8 without it.
+    pub encoding: Option<Encoding>,
+    /// The first line is data, not headers. Columns are named `column_1`,
+    /// `column_2`, ... after the fields of the first line, and an editor
+    /// saves the file without a header line.
+    pub no_headers: bool,
+}
+
+impl OpenOptions {
+    /// Check that the options can be used together. Called by every `open`.
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |msg: &str| Err(MassiveCsvError::InvalidOptions(msg.to_string()));
+        let quote = self.quote_char.unwrap_or(b'"');
+        if let Some(delimiter) = self.delimiter {
+            if matches!(delimiter, b'\n' | b'\r') {
+                return invalid("the delimiter cannot be a line break");
+            }
+            if delimiter == quote {
+                return invalid("the delimiter and quote character must differ");
+            }
+        }
+        if matches!(quote, b'\n' | b'\r') {
+            return invalid("the quote character cannot be a line break");
+        }
+        if let Some(layout) = &self.fixed_width {
+            if self.delimiter.is_some() {
+                return invalid("a fixed-width layout has no delimiter");
+            }
+            if layout.widths.is_empty() || layout.widths.contains(&0) {
+                return invalid("fixed-width columns need a width of at least 1");
+            }
+        }
+        if self.delimiter.is_none() && self.fixed_width.is_none() {
+            let detect = &self.detect;
+            if detect.sample_lines == 0 || detect.sample_points == 0 {
+                return invalid("delimiter detection needs at least one sample line");
+            }
+            if detect.candidates.is_empty() {
+                return invalid("delimiter detection needs at least one candidate");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Chained setters for [`OpenOptions`], from [`CsvReader::builder`] or
+/// [`CsvEditor::builder`](crate::CsvEditor::builder):
+///
+/// ```no_run
+/// # use massive_csv_core::{CsvReader, Encoding};
+/// # fn main() -> massive_csv_core::Result<()> {
+/// let reader = CsvReader::builder("export.csv")
+///     .delimiter(b';')
+///     .encoding(Encoding::Windows1252)
+///     .lenient(true)
+///     .open()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct OpenBuilder<T> {
+    path: PathBuf,
+    options: OpenOptions,
+    progress: Progress,
+    _target: PhantomData<fn() -> T>,
+}
+
+impl<T> OpenBuilder<T> {
+    pub(crate) fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            options: OpenOptions::default(),
+            progress: Progress::default(),
+            _target: PhantomData,
+        }
+    }
+
+    /// Start from a full set of options; later setters override them.
+    pub fn options(mut self, options: OpenOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn delimiter(mut self, delimiter: u8) -> Self {
+        self.options.delimiter = Some(delimiter);
+        self
+    }
+
+    pub fn quote_char(mut self, quote: u8) -> Self {
+        self.options.quote_char = Some(quote);
+        self
+    }
+
+    pub fn escape(mut self, escape: EscapeStyle) -> Self {
+        self.options.escape = Some(escape);
+        self
+    }
+
+    pub fn lenient(mut self, lenient: bool) -> Self {
+        self.options.lenient = lenient;
+        self
+    }
+
+    pub fn detect(mut self, detect: DetectOptions) -> Self {
+        self.options.detect = detect;
+        self
+    }
+
+    pub fn fixed_width(mut self, widths: Vec<usize>) -> Self {
+        self.options.fixed_width = Some(FixedWidth::new(widths));
+        self
+    }
+
+    pub fn encoding(mut self, encoding: Encoding) -> Self {
+        self.options.encoding = Some(encoding);
+        self
+    }
+
+    /// Report the bytes indexed to `progress` while opening, as
+    /// [`CsvReader::open_with_progress`]; it can also cancel the open.
+    pub fn no_headers(mut self, no_headers: bool) -> Self {
+        self.options.no_headers = no_headers;
+        self
+    }
+
+    pub fn progress(mut self, progress: Progress) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// The options set so far.
+    pub fn open_options(&self) -> &OpenOptions {
+        &self.options
+    }
+}
+
+impl OpenBuilder<CsvReader> {
+    pub fn open(self) -> Result<CsvReader> {
+        CsvReader::open_with_progress(&self.path, &self.options, &self.progress)
+    }
+}
+
+impl OpenBuilder<CsvEditor> {
+    pub fn open(self) -> Result<CsvEditor> {
+        let reader = CsvReader::open_with_progress(&self.path, &self.options, &self.progress)?;
+        Ok(CsvEditor::new(reader))
+    }
+}
+
+/// Bytes hashed from each end of the file by [`FileFingerprint`].
+const FINGERPRINT_SAMPLE: u64 = 64 * 1024;
+
+/// Snapshot of a file's on-disk state, used to detect external modification.
+///
+/// Hashing the whole file would defeat the point of memory-mapping it, so
+/// only the first and last 64 KB are hashed alongside size and mtime.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileFingerprint {
+    pub len: u64,
+    pub modified: Option<SystemTime>,
+    pub sample_hash: u64,
+}
+
+impl FileFingerprint {
+    /// Fingerprint the file currently at `path`.
+    pub fn of(path: &Path) -> Result<Self> {
+        Self::of_file(&File::open(path)?)
+    }
+
+    /// Fingerprint an open file, wherever it is now linked.
+    fn of_file(mut file: &File) -> Result<Self> {
+        let metadata = file.metadata()?;
+        file.seek(SeekFrom::Start(0))?;
+        let len = metadata.len();
+
+        let mut hasher = DefaultHasher::new();
+        let mut buf = Vec::with_capacity(FINGERPRINT_SAMPLE as usize);
+        (&mut file).take(FINGERPRINT_SAMPLE).read_to_end(&mut buf)?;
+        hasher.write(&buf);
+
+        if len > FINGERPRINT_SAMPLE {
+            buf.clear();
+            let tail_start = (len - FINGERPRINT_SAMPLE).max(FINGERPRINT_SAMPLE);
+            file.seek(SeekFrom::Start(tail_start))?;
+            file.read_to_end(&mut buf)?;
+            hasher.write(&buf);
+        }
+
+        Ok(Self {
+            len,
+            modified: metadata.modified().ok(),
+            sample_hash: hasher.finish(),
+        })
+    }
 }
 
 impl CsvReader {
     /// Open a CSV file, build the line index, and detect delimiter/headers.
     pub fn open(path: &Path) -> Result<Self> {
+        Self::open_with(path, &OpenOptions::default())
+    }
+
+    /// Set open options one at a time, then [`open`](OpenBuilder::open).
+    pub fn builder(path: impl Into<PathBuf>) -> OpenBuilder<Self> {
+        OpenBuilder::new(path)
+    }
+
+    /// Open a CSV file, detecting only the parts of the dialect `options` leaves unset.
+    pub fn open_with(path: &Path, options: &OpenOptions) -> Result<Self> {
+        Self::open_with_progress(path, options, &Progress::default())
+    }
+
+    /// [`open_with`](Self::open_with), reporting the bytes indexed so far
+    /// to `progress` while the line index is built.
+    pub fn open_with_progress(
+        path: &Path,
+        options: &OpenOptions,
+        progress: &Progress,
+    ) -> Result<Self> {
+        options.validate()?;
         let file = File::open(path)?;
         let metadata = file.metadata()?;
 
@@ -25,12 +294,55 @@ impl CsvReader {
             return Err(MassiveCsvError::EmptyFile);
         }
 
+        let fingerprint = FileFingerprint::of_file(&file)?;
+
         // SAFETY: We only read from the mmap. The file should not be modified externally
         // while we hold this mapping (standard mmap caveat).
-        let mmap = unsafe { Mmap::map(&file)? };
+        let mut mmap = unsafe { Mmap::map(&file)? };
 
-        let delimiter = detect_delimiter(&mmap).as_byte();
-        let headers = parse_headers(&mmap, delimiter)?;
+        let compression = Compression::detect(&mmap);
+        if compression != Compression::None {
+            let decompressed = decompress_to_tempfile(&mmap, compression)?;
+            if decompressed.metadata()?.len() == 0 {
+                return Err(MassiveCsvError::EmptyFile);
+            }
+            // SAFETY: the temporary file is unnamed, so nothing else can modify it.
+            mmap = unsafe { Mmap::map(&decompressed)? };
+        }
+
+        // Other encodings are decoded once into a temporary UTF-8 copy, so
+        // every row access below stays a zero-copy slice of a mapping
+        let encoding = match options.encoding {
+            Some(encoding) => encoding,
+            None if cfg!(feature = "encodings") => Encoding::detect(&mmap),
+            None => Encoding::Utf8,
+        };
+        if encoding != Encoding::Utf8 {
+            let decoded = decode_to_tempfile(&mmap, encoding)?;
+            if decoded.metadata()?.len() == 0 {
+                return Err(MassiveCsvError::EmptyFile);
+            }
+            // SAFETY: the temporary file is unnamed, so nothing else can modify it.
+            mmap = unsafe { Mmap::map(&decoded)? };
+        }
+        let mapped =
+            (compression == Compression::None && encoding == Encoding::Utf8).then_some(file);
+
+        let (delimiter, delimiter_confidence) = match options.delimiter {
+            Some(delimiter) => (delimiter, 1.0),
+            None if options.fixed_width.is_some() => (Dialect::default().delimiter, 1.0),
+            None => {
+                let detection = detect_delimiter_with(&mmap, &options.detect);
+                (detection.delimiter, detection.confidence)
+            }
+        };
+        let mut dialect = detect_dialect(&mmap, delimiter);
+        if let Some(quote) = options.quote_char {
+            dialect.quote = quote;
+        }
+        if let Some(escape) = options.escape {
+            dialect.escape = escape;
+        }
 
         // Find where the header line ends
         let header_end = mmap
@@ -39,14 +351,41 @@ impl CsvReader {
             .map(|pos| pos + 1)
             .unwrap_or(mmap.len());
 
-        let line_index = build_index(&mmap, header_end);
+        let mut headers = match &options.fixed_width {
+            Some(layout) => {
+                let line = std::str::from_utf8(strip_line_ending(&mmap[..header_end]))
+                    .map_err(|_| MassiveCsvError::InvalidUtf8(0))?;
+                layout.parse(line)
+            }
+            None => parse_headers(&mmap, dialect)?,
+        };
+        let data_start = if options.no_headers {
+            headers = (1..=headers.len()).map(|i| format!("column_{i}")).collect();
+            0
+        } else {
+            header_end
+        };
+
+        let line_index = build_index(&mmap, data_start, progress)?;
+        let line_ending = detect_line_ending(&mmap);
+        let trailing_newline = mmap.last() == Some(&b'\n');
 
         Ok(Self {
             mmap,
             line_index,
-            headers,
-            delimiter,
+            headers: Arc::new(HeaderMap::new(headers)),
+            dialect,
+            delimiter_confidence,
+            encoding,
+            compression,
+            options: options.clone(),
+            line_ending,
+            trailing_newline,
             path: path.to_path_buf(),
+            fingerprint,
+            mapped,
+            spill: None,
+            sorted_columns: Mutex::new(HashMap::new()),
         })
     }
 
@@ -55,14 +394,79 @@ impl CsvReader {
         self.line_index.len()
     }
 
-    /// Column headers.
+    /// Column headers, as written in the file.
     pub fn headers(&self) -> &[String] {
-        &self.headers
+        self.headers.raw()
+    }
+
+    /// Column names with empty headers filled in and duplicates suffixed
+    /// (`amount`, `amount_2`). Equal to [`headers`](Self::headers) when every
+    /// header is present and unique.
+    pub fn canonical_headers(&self) -> &[String] {
+        self.headers.canonical()
+    }
+
+    /// Whether any header is empty or repeated.
+    pub fn has_irregular_headers(&self) -> bool {
+        self.headers.raw() != self.headers.canonical()
+    }
+
+    /// Index of the column with the given name. Canonical names are matched
+    /// first, then raw header text, so `amount_2` finds the second `amount`.
+    pub fn column_index(&self, name: &str) -> Option<usize> {
+        self.headers.position(name)
+    }
+
+    /// Text encoding of the file on disk. Rows are always returned as UTF-8.
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    /// Compression of the file on disk.
+    pub fn compression(&self) -> Compression {
+        self.compression
+    }
+
+    /// Size in bytes of the text being read: after decompression and
+    /// transcoding, so it can differ from the file's size on disk.
+    pub fn data_len(&self) -> u64 {
+        self.mmap.len() as u64
+    }
+
+    /// Bytes the row index takes in memory.
+    pub fn index_bytes(&self) -> usize {
+        self.line_index.capacity() * std::mem::size_of::<u64>()
+    }
+
+    /// The options the file was opened with.
+    pub fn options(&self) -> &OpenOptions {
+        &self.options
     }
 
     /// The detected delimiter byte.
     pub fn delimiter(&self) -> u8 {
-        self.delimiter
+        self.dialect.delimiter
+    }
+
+    /// How sure delimiter detection was, from 0 to 1 (1 when the delimiter was
+    /// given in [`OpenOptions`]).
+    pub fn delimiter_confidence(&self) -> f64 {
+        self.delimiter_confidence
+    }
+
+    /// The delimiter, quote character and escape style rows are parsed with.
+    pub fn dialect(&self) -> Dialect {
+        self.dialect
+    }
+
+    /// The dominant line ending of the file.
+    pub fn line_ending(&self) -> LineEnding {
+        self.line_ending
+    }
+
+    /// Whether the file ends with a line terminator.
+    pub fn has_trailing_newline(&self) -> bool {
+        self.trailing_newline
     }
 
     /// File path this reader was opened from.
@@ -70,8 +474,84 @@ impl CsvReader {
         &self.path
     }
 
+    /// Fingerprint of the file as it was when this reader opened it.
+    pub fn fingerprint(&self) -> &FileFingerprint {
+        &self.fingerprint
+    }
+
+    /// Whether column `index` is sorted, compared as numbers if `numeric`:
+    /// from an earlier call, or from `check`, remembered for later calls.
+    pub(crate) fn column_sorted(
+        &self,
+        index: usize,
+        numeric: bool,
+        check: impl FnOnce() -> bool,
+    ) -> bool {
+        let key = (index, numeric);
+        if let Some(&sorted) = self
+            .sorted_columns
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&key)
+        {
+            return sorted;
+        }
+        // Checked unlocked, as it reads the whole column in parallel
+        let sorted = check();
+        self.sorted_columns
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(key, sorted);
+        sorted
+    }
+
+    /// Whether the file on disk no longer matches the fingerprint taken at open.
+    pub fn is_modified_on_disk(&self) -> Result<bool> {
+        Ok(FileFingerprint::of(&self.path)? != self.fingerprint)
+    }
+
+    /// Whether the mapped file itself was rewritten since it was opened, as
+    /// opposed to replaced by a new file at the same path.
+    ///
+    /// Rows are read straight from the mapping, so after an in-place rewrite
+    /// they no longer match the line index, and reading past a truncated end
+    /// crashes the process. Compressed and transcoded files are read from a
+    /// private copy and are never changed in place.
+    pub fn is_changed_in_place(&self) -> Result<bool> {
+        match &self.mapped {
+            Some(file) => Ok(FileFingerprint::of_file(file)? != self.fingerprint),
+            None => Ok(false),
+        }
+    }
+
+    /// The header line as &str, without its line ending.
+    pub(crate) fn header_raw(&self) -> Result<&str> {
+        let end = self
+            .line_index
+            .first()
+            .map_or(self.mmap.len(), |&offset| offset as usize);
+        std::str::from_utf8(strip_line_ending(&self.mmap[..end]))
+            .map_err(|_| MassiveCsvError::InvalidUtf8(0))
+    }
+
     /// Get a raw line as &str (zero-copy from mmap). Does not include the trailing newline.
     pub fn get_row_raw(&self, row: usize) -> Result<&str> {
+        let slice = self.get_row_bytes(row)?;
+        std::str::from_utf8(slice)
+            .map_err(|_| MassiveCsvError::InvalidUtf8(self.line_index[row] as usize))
+    }
+
+    /// The raw line, as [`get_row_raw`](Self::get_row_raw) in strict mode; in
+    /// lenient mode invalid UTF-8 is replaced rather than failing.
+    pub fn get_row_text(&self, row: usize) -> Result<Cow<'_, str>> {
+        if self.options.lenient {
+            return Ok(String::from_utf8_lossy(self.get_row_bytes(row)?));
+        }
+        self.get_row_raw(row).map(Cow::Borrowed)
+    }
+
+    /// Get a raw line as bytes, whether or not it is valid UTF-8.
+    pub fn get_row_bytes(&self, row: usize) -> Result<&[u8]> {
         let count = self.row_count();
         if row >= count {
             return Err(MassiveCsvError::RowOutOfRange(row, count));
@@ -87,15 +567,140 @@ impl CsvReader {
         let slice = &self.mmap[start..end];
 
         // Trim trailing \n and \r\n
-        let slice = strip_line_ending(slice);
-
-        std::str::from_utf8(slice).map_err(|_| MassiveCsvError::InvalidUtf8(start))
+        Ok(strip_line_ending(slice))
     }
 
     /// Get a row parsed into fields.
+    ///
+    /// In lenient mode, malformed rows are recovered as by
+    /// [`get_row_checked`](Self::get_row_checked) rather than failing.
     pub fn get_row(&self, row: usize) -> Result<Vec<String>> {
+        if self.options.lenient {
+            return self.get_row_checked(row).map(|(fields, _)| fields);
+        }
         let raw = self.get_row_raw(row)?;
-        parse_row(raw, self.delimiter)
+        self.parse_raw(raw)
+    }
+
+    /// Get a row as a [`Record`], with access to its fields by column name.
+    pub fn get_record(&self, row: usize) -> Result<Record> {
+        Ok(self.record(row, self.get_row(row)?))
+    }
+
+    /// Deserialize a row into `T`, matching struct fields to canonical column
+    /// names:
+    ///
+    /// ```no_run
+    /// # fn main() -> massive_csv_core::Result<()> {
+    /// #[derive(serde::Deserialize)]
+    /// struct Order {
+    ///     id: u64,
+    ///     price: f64,
+    ///     note: Option<String>,
+    /// }
+    ///
+    /// let reader = massive_csv_core::CsvReader::open("orders.csv".as_ref())?;
+    /// let order: Order = reader.deserialize_row(1_500_000)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "serde")]
+    pub fn deserialize_row<T: serde::de::DeserializeOwned>(&self, row: usize) -> Result<T> {
+        self.get_record(row)?.deserialize()
+    }
+
+    /// Deserialize rows `start..end` lazily, one row per item.
+    #[cfg(feature = "serde")]
+    pub fn deserialize_rows<T: serde::de::DeserializeOwned>(
+        &self,
+        start: usize,
+        end: usize,
+    ) -> DeserializeRows<'_, T> {
+        DeserializeRows {
+            reader: self,
+            headers: csv::StringRecord::from(self.canonical_headers()),
+            rows: start..end.min(self.row_count()),
+            _target: std::marker::PhantomData,
+        }
+    }
+
+    /// Get a range of rows as [`Record`]s.
+    pub fn get_records(&self, start: usize, end: usize) -> Result<Vec<Record>> {
+        let end = end.min(self.row_count());
+        (start..end).map(|row| self.get_record(row)).collect()
+    }
+
+    /// Wrap fields read from `row` of this file in a [`Record`].
+    pub fn record(&self, row: usize, fields: Vec<String>) -> Record {
+        Record::new(row, fields, Arc::clone(&self.headers))
+    }
+
+    /// Parse a raw line from this file, honoring lenient mode.
+    pub fn parse_raw(&self, raw: &str) -> Result<Vec<String>> {
+        if let Some(layout) = &self.options.fixed_width {
+            return Ok(layout.parse(raw));
+        }
+        if self.options.lenient {
+            return Ok(self.recover(raw).0);
+        }
+        Ok(parse_row(raw, self.dialect)?)
+    }
+
+    /// Field `index` of a raw line from this file, without parsing the others.
+    pub(crate) fn field_of<'a>(&self, raw: &'a str, index: usize) -> Option<Cow<'a, str>> {
+        match &self.options.fixed_width {
+            Some(layout) => layout.field(raw, index).map(Cow::Borrowed),
+            None => field_at(raw, index, &self.dialect),
+        }
+    }
+
+    /// The column layout, if the file was opened as fixed-width.
+    pub fn fixed_width(&self) -> Option<&FixedWidth> {
+        self.options.fixed_width.as_ref()
+    }
+
+    /// Whether malformed rows are recovered instead of failing.
+    pub fn is_lenient(&self) -> bool {
+        self.options.lenient
+    }
+
+    /// Get a row, recovering it if malformed, along with what was wrong with it.
+    ///
+    /// Invalid UTF-8 is decoded lossily. A row with unbalanced quotes is split
+    /// on the delimiter with its quotes kept as literal text. The fields are
+    /// then padded or truncated to the header count.
+    pub fn get_row_checked(&self, row: usize) -> Result<(Vec<String>, Option<IssueKind>)> {
+        let bytes = self.get_row_bytes(row)?;
+        match std::str::from_utf8(bytes) {
+            Ok(raw) => Ok(self.recover(raw)),
+            Err(_) => {
+                let (fields, _) = self.recover(&String::from_utf8_lossy(bytes));
+                Ok((fields, Some(IssueKind::InvalidUtf8)))
+            }
+        }
+    }
+
+    fn recover(&self, raw: &str) -> (Vec<String>, Option<IssueKind>) {
+        if let Some(layout) = &self.options.fixed_width {
+            return (layout.parse(raw), None);
+        }
+        let expected = self.headers.len();
+        let (found, balanced) = field_count(raw, &self.dialect);
+
+        let (mut fields, mut issue) = match parse_row(raw, self.dialect) {
+            Ok(fields) if balanced => (fields, None),
+            _ => (
+                raw.split(self.dialect.delimiter as char)
+                    .map(str::to_string)
+                    .collect(),
+                Some(IssueKind::UnbalancedQuotes),
+            ),
+        };
+        if issue.is_none() && found != expected {
+            issue = Some(IssueKind::FieldCount { expected, found });
+        }
+        fields.resize(expected, String::new());
+        (fields, issue)
     }
 
     /// Get a range of rows parsed into fields.
@@ -108,24 +713,192 @@ impl CsvReader {
         Ok(rows)
     }
 
-    /// Re-open the file (e.g., after save). Returns a new CsvReader.
+    /// Read CSV from a stream such as stdin.
+    ///
+    /// A stream can't be memory-mapped, so it is first copied to a temporary
+    /// file, which then behaves like any opened file; [`path`](Self::path)
+    /// points at it and it is deleted when the reader (and every reader
+    /// reopened from it) is dropped. Compressed streams are decompressed.
+    pub fn from_reader(source: impl Read) -> Result<Self> {
+        Self::from_reader_with(source, &OpenOptions::default())
+    }
+
+    /// Like [`from_reader`](Self::from_reader), with dialect overrides.
+    pub fn from_reader_with(mut source: impl Read, options: &OpenOptions) -> Result<Self> {
+        let spill = tempfile::NamedTempFile::new()?;
+        let mut writer = BufWriter::with_capacity(1024 * 1024, spill.as_file());
+        std::io::copy(&mut source, &mut writer)?;
+        writer.flush()?;
+        drop(writer);
+
+        let mut reader = Self::open_with(spill.path(), options)?;
+        reader.spill = Some(Arc::new(spill.into_temp_path()));
+        Ok(reader)
+    }
+
+    /// Read a JSON Lines (NDJSON) file as a table; see [`crate::ndjson`] for
+    /// how objects become rows. Like [`from_reader`](Self::from_reader), the
+    /// converted CSV lives in a temporary file, so save edits with
+    /// [`CsvEditor::save_as`](crate::CsvEditor::save_as). Compressed files
+    /// are decompressed.
+    pub fn from_ndjson(path: &Path) -> Result<Self> {
+        let file = File::open(path)?;
+        if file.metadata()?.len() == 0 {
+            return Err(MassiveCsvError::EmptyFile);
+        }
+        // SAFETY: We only read from the mmap (standard mmap caveat).
+        let mut data = unsafe { Mmap::map(&file)? };
+        let compression = Compression::detect(&data);
+        if compression != Compression::None {
+            // SAFETY: the temporary file is unnamed, so nothing else can modify it.
+            data = unsafe { Mmap::map(&decompress_to_tempfile(&data, compression)?)? };
+        }
+
+        let spill = tempfile::NamedTempFile::new()?;
+        let mut writer = BufWriter::with_capacity(1024 * 1024, spill.as_file());
+        ndjson_to_csv(&data, &mut writer)?;
+        writer.flush()?;
+        drop(writer);
+
+        let options = OpenOptions {
+            delimiter: Some(b','),
+            encoding: Some(Encoding::Utf8),
+            ..OpenOptions::default()
+        };
+        let mut reader = Self::open_with(spill.path(), &options)?;
+        reader.spill = Some(Arc::new(spill.into_temp_path()));
+        Ok(reader)
+    }
+
+    /// Whether the data came from a stream rather than a file the caller named.
+    pub fn is_spilled(&self) -> bool {
+        self.spill.is_some()
+    }
+
+    /// Index rows appended to the file since it was read, e.g. by a job
+    /// still writing it, and return how many rows were added. Only the new
+    /// bytes are scanned. An unterminated last row is re-read, as it may
+    /// have been cut mid-write.
+    ///
+    /// Fails with [`MassiveCsvError::ExternallyModified`] if the file
+    /// shrank or its start changed, which means it was replaced rather
+    /// than appended to.
+    pub fn refresh_appended(&mut self) -> Result<usize> {
+        if self.compression != Compression::None
+            || self.encoding != Encoding::Utf8
+            || self.spill.is_some()
+        {
+            return Err(MassiveCsvError::InvalidOptions(
+                "only uncompressed UTF-8 files can be followed".to_string(),
+            ));
+        }
+        let file = File::open(&self.path)?;
+        let len = file.metadata()?.len();
+        let old_len = self.mmap.len() as u64;
+        if len == old_len {
+            return Ok(0);
+        }
+        let replaced = || {
+            MassiveCsvError::ExternallyModified(format!(
+                "{} was replaced, not appended to",
+                self.path.display()
+            ))
+        };
+        if len < old_len {
+            return Err(replaced());
+        }
+        // SAFETY: as in `open_with`; appending leaves mapped bytes unchanged.
+        let mmap = unsafe { Mmap::map(&file)? };
+        let sample = (FINGERPRINT_SAMPLE as usize).min(self.mmap.len());
+        if mmap[..sample] != self.mmap[..sample] {
+            return Err(replaced());
+        }
+
+        let before = self.row_count();
+        let start = match self.line_index.last() {
+            Some(&last) if !self.trailing_newline => {
+                self.line_index.pop();
+                last as usize
+            }
+            Some(_) => self.mmap.len(),
+            None => match mmap.iter().position(|&b| b == b'\n') {
+                Some(pos) => pos + 1,
+                None => mmap.len(),
+            },
+        };
+        self.line_index
+            .extend(build_index(&mmap, start, &Progress::default())?);
+        self.trailing_newline = mmap.last() == Some(&b'\n');
+        self.mmap = mmap;
+        self.fingerprint = FileFingerprint::of_file(&file)?;
+        self.mapped = Some(file);
+        Ok(self.row_count().saturating_sub(before))
+    }
+
+    /// Re-open the file (e.g., after save) with the same options. Returns a new CsvReader.
     pub fn reopen(&self) -> Result<Self> {
-        Self::open(&self.path)
+        self.reopen_with_progress(&Progress::default())
+    }
+
+    /// [`reopen`](Self::reopen), reporting the bytes indexed so far to `progress`.
+    pub fn reopen_with_progress(&self, progress: &Progress) -> Result<Self> {
+        let mut reader = Self::open_with_progress(&self.path, &self.options, progress)?;
+        reader.spill = self.spill.clone();
+        Ok(reader)
     }
 }
 
+/// Iterator over rows deserialized into `T`, from [`CsvReader::deserialize_rows`].
+#[cfg(feature = "serde")]
+pub struct DeserializeRows<'a, T> {
+    reader: &'a CsvReader,
+    headers: csv::StringRecord,
+    rows: std::ops::Range<usize>,
+    _target: std::marker::PhantomData<fn() -> T>,
+}
+
+#[cfg(feature = "serde")]
+impl<T: serde::de::DeserializeOwned> Iterator for DeserializeRows<'_, T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let row = self.rows.next()?;
+        Some(
+            self.reader
+                .get_row(row)
+                .and_then(|fields| deserialize_fields(row, fields.into(), &self.headers)),
+        )
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.rows.size_hint()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T: serde::de::DeserializeOwned> ExactSizeIterator for DeserializeRows<'_, T> {}
+
+/// Bytes scanned between progress reports while building the line index.
+const INDEX_PROGRESS_BYTES: usize = 16 * 1024 * 1024;
+
 /// Build a line index starting from `data_start` (byte position after the header line).
-fn build_index(data: &[u8], data_start: usize) -> Vec<u64> {
+fn build_index(data: &[u8], data_start: usize, progress: &Progress) -> Result<Vec<u64>> {
     if data_start >= data.len() {
-        return vec![];
+        return Ok(vec![]);
     }
 
     let mut index = vec![data_start as u64];
 
-    for pos in data_start..data.len() {
-        if data[pos] == b'\n' && pos + 1 < data.len() {
-            index.push((pos + 1) as u64);
+    let mut start = data_start;
+    while start < data.len() {
+        let end = (start + INDEX_PROGRESS_BYTES).min(data.len());
+        for pos in start..end {
+            if data[pos] == b'\n' && pos + 1 < data.len() {
+                index.push((pos + 1) as u64);
+            }
         }
+        progress.update(end, data.len())?;
+        start = end;
     }
 
     // If the last "row" is empty (file ends with \n), remove it
@@ -140,7 +913,7 @@ fn build_index(data: &[u8], data_start: usize) -> Vec<u64> {
         }
     }
 
-    index
+    Ok(index)
 }
 
 fn strip_line_ending(data: &[u8]) -> &[u8] {
@@ -159,13 +932,253 @@ mod tests {
     use super::*;
     use std::io::Write;
 
-    fn make_csv(content: &str) -> tempfile::NamedTempFile {
+    fn row(values: &[&str]) -> Vec<String> {
+        values.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn make_csv(content: impl AsRef<[u8]>) -> tempfile::NamedTempFile {
         let mut f = tempfile::NamedTempFile::new().unwrap();
-        f.write_all(content.as_bytes()).unwrap();
+        f.write_all(content.as_ref()).unwrap();
         f.flush().unwrap();
         f
     }
 
+    #[test]
+    fn duplicate_and_empty_headers() {
+        let f = make_csv("id,amount,amount,\n1,10,20,x\n");
+        let reader = CsvReader::open(f.path()).unwrap();
+        assert_eq!(reader.headers(), &["id", "amount", "amount", ""]);
+        assert_eq!(
+            reader.canonical_headers(),
+            &["id", "amount", "amount_2", "column_4"]
+        );
+        assert!(reader.has_irregular_headers());
+        assert_eq!(reader.column_index("amount"), Some(1));
+        assert_eq!(reader.column_index("amount_2"), Some(2));
+        assert_eq!(reader.column_index("column_4"), Some(3));
+        assert_eq!(reader.column_index(""), Some(3));
+    }
+
+    #[test]
+    fn records_share_headers() {
+        let f = make_csv("name,amount\nAlice,10.5\nBob,3\n");
+        let reader = CsvReader::open(f.path()).unwrap();
+        let records = reader.get_records(0, 10).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].row(), 1);
+        assert_eq!(records[1].get("name"), Some("Bob"));
+        assert_eq!(records[0].get_f64("amount").unwrap(), 10.5);
+        assert_eq!(records[0].fields(), reader.get_row(0).unwrap());
+    }
+
+    #[cfg(feature = "encodings")]
+    #[test]
+    fn transcodes_latin1_and_utf16() {
+        let f = make_csv(b"name,city\nJos\xe9,M\xe1laga\n");
+        let reader = CsvReader::open(f.path()).unwrap();
+        assert_eq!(reader.encoding(), Encoding::Windows1252);
+        assert_eq!(reader.get_row(0).unwrap(), row(&["José", "Málaga"]));
+
+        let utf16: Vec<u8> = [0xFF, 0xFE]
+            .into_iter()
+            .chain(
+                "id;naïve\r\n1;ü\r\n"
+                    .encode_utf16()
+                    .flat_map(u16::to_le_bytes),
+            )
+            .collect();
+        let f = make_csv(utf16);
+        let reader = CsvReader::open(f.path()).unwrap();
+        assert_eq!(reader.encoding(), Encoding::Utf16Le);
+        assert_eq!(reader.delimiter(), b';');
+        assert_eq!(reader.headers(), &["id", "naïve"]);
+        assert_eq!(reader.get_row(0).unwrap(), row(&["1", "ü"]));
+        assert_eq!(reader.line_ending(), LineEnding::CrLf);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn deserialize_rows_into_structs() {
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Order {
+            id: u32,
+            price: f64,
+            note: Option<String>,
+            price_2: String,
+        }
+
+        let f = make_csv("id,price,note,price\n1,9.5,,a\n2,3,\"x, y\",b\n3,oops,,c\n");
+        let reader = CsvReader::open(f.path()).unwrap();
+
+        let order: Order = reader.deserialize_row(1).unwrap();
+        assert_eq!(
+            order,
+            Order {
+                id: 2,
+                price: 3.0,
+                note: Some("x, y".into()),
+                price_2: "b".into(),
+            }
+        );
+
+        let orders: Vec<Result<Order>> = reader.deserialize_rows(0, 10).collect();
+        assert_eq!(orders.len(), 3);
+        assert_eq!(orders[0].as_ref().unwrap().note, None);
+        assert!(matches!(&orders[2], Err(MassiveCsvError::Parse(m)) if m.starts_with("row 2")));
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn reads_gzip_and_zstd() {
+        let csv = b"id,name\n1,Ann\n2,Bob\n";
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        gzip.write_all(csv).unwrap();
+        let zstd = zstd::encode_all(&csv[..], 0).unwrap();
+
+        for (data, compression) in [
+            (gzip.finish().unwrap(), Compression::Gzip),
+            (zstd, Compression::Zstd),
+        ] {
+            let f = make_csv(&data);
+            let reader = CsvReader::open(f.path()).unwrap();
+            assert_eq!(reader.compression(), compression);
+            assert_eq!(reader.data_len(), csv.len() as u64);
+            assert_eq!(reader.headers(), &["id", "name"]);
+            assert_eq!(reader.get_row(1).unwrap(), row(&["2", "Bob"]));
+        }
+    }
+
+    #[test]
+    fn read_from_stream() {
+        let reader = CsvReader::from_reader(&b"id;name\n1;Ann\n2;Bob\n"[..]).unwrap();
+        assert_eq!(reader.delimiter(), b';');
+        assert_eq!(reader.row_count(), 2);
+        assert_eq!(reader.get_row(1).unwrap(), row(&["2", "Bob"]));
+        assert!(reader.is_spilled());
+
+        let spill = reader.path().to_path_buf();
+        let reopened = reader.reopen().unwrap();
+        drop(reader);
+        assert!(spill.exists());
+        drop(reopened);
+        assert!(!spill.exists());
+
+        assert!(matches!(
+            CsvReader::from_reader(std::io::empty()),
+            Err(MassiveCsvError::EmptyFile)
+        ));
+    }
+
+    #[cfg(feature = "ndjson")]
+    #[test]
+    fn read_ndjson_as_table() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.ndjson");
+        std::fs::write(
+            &path,
+            "{\"id\": 1, \"name\": \"Ann\"}\n{\"id\": 2, \"tags\": [1]}\n",
+        )
+        .unwrap();
+        let reader = CsvReader::from_ndjson(&path).unwrap();
+        assert_eq!(reader.headers(), &["id", "name", "tags"]);
+        assert_eq!(reader.get_row(1).unwrap(), row(&["2", "", "[1]"]));
+        assert!(reader.is_spilled());
+
+        let mut editor = CsvEditor::new(reader);
+        editor.set_cell(0, 1, "Anna".to_string()).unwrap();
+        let out = dir.path().join("events.csv");
+        editor.save_as(&out, &Default::default()).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&out).unwrap(),
+            "id,name,tags\n1,Anna,\n2,,[1]\n"
+        );
+    }
+
+    #[test]
+    fn builder_opens_and_validates() {
+        let f = make_csv("a|b\n'x|y'|2\n");
+        let reader = CsvReader::builder(f.path())
+            .delimiter(b'|')
+            .quote_char(b'\'')
+            .open()
+            .unwrap();
+        assert_eq!(reader.get_row(0).unwrap(), row(&["x|y", "2"]));
+
+        let editor = CsvEditor::builder(f.path()).lenient(true).open().unwrap();
+        assert!(editor.reader().is_lenient());
+
+        let invalid = |builder: OpenBuilder<CsvReader>| {
+            matches!(builder.open(), Err(MassiveCsvError::InvalidOptions(_)))
+        };
+        assert!(invalid(CsvReader::builder(f.path()).delimiter(b'"')));
+        assert!(invalid(CsvReader::builder(f.path()).delimiter(b'\n')));
+        assert!(invalid(
+            CsvReader::builder(f.path()).fixed_width(vec![3, 0])
+        ));
+        assert!(invalid(
+            CsvReader::builder(f.path())
+                .fixed_width(vec![3])
+                .delimiter(b',')
+        ));
+        assert!(invalid(CsvReader::builder(f.path()).detect(
+            DetectOptions {
+                candidates: vec![],
+                ..Default::default()
+            }
+        )));
+    }
+
+    #[test]
+    fn open_with_quote_override() {
+        let f = make_csv("a;b\n|x;y|;2\n");
+        let options = OpenOptions {
+            quote_char: Some(b'|'),
+            ..Default::default()
+        };
+        let reader = CsvReader::open_with(f.path(), &options).unwrap();
+        assert_eq!(reader.delimiter(), b';');
+        assert_eq!(reader.get_row(0).unwrap(), vec!["x;y", "2"]);
+    }
+
+    #[test]
+    fn lenient_mode_recovers_malformed_rows() {
+        let f = make_csv(b"a,b,c\n1,2,3\n\"x,2,3\n1,2\n1,2,3,4\n\xff,2,3\n");
+        let options = OpenOptions {
+            lenient: true,
+            ..Default::default()
+        };
+        let reader = CsvReader::open_with(f.path(), &options).unwrap();
+
+        assert_eq!(
+            reader.get_row_checked(0).unwrap(),
+            (row(&["1", "2", "3"]), None)
+        );
+        assert_eq!(
+            reader.get_row_checked(1).unwrap(),
+            (row(&["\"x", "2", "3"]), Some(IssueKind::UnbalancedQuotes))
+        );
+        assert_eq!(
+            reader.get_row_checked(2).unwrap(),
+            (
+                row(&["1", "2", ""]),
+                Some(IssueKind::FieldCount {
+                    expected: 3,
+                    found: 2
+                })
+            )
+        );
+        assert_eq!(reader.get_row(3).unwrap(), row(&["1", "2", "3"]));
+        assert_eq!(
+            reader.get_row_checked(4).unwrap(),
+            (row(&["\u{FFFD}", "2", "3"]), Some(IssueKind::InvalidUtf8))
+        );
+        assert_eq!(reader.get_row(4).unwrap(), row(&["\u{FFFD}", "2", "3"]));
+
+        // Strict mode still fails on invalid UTF-8.
+        let reader = CsvReader::open(f.path()).unwrap();
+        assert!(reader.get_row(4).is_err());
+    }
+
     #[test]
     fn basic_read() {
         let f = make_csv("name,age\nAlice,30\nBob,25\n");
@@ -215,5 +1228,65 @@ mod tests {
         let reader = CsvReader::open(f.path()).unwrap();
         assert_eq!(reader.row_count(), 2);
         assert_eq!(reader.get_row(0).unwrap(), vec!["Alice", "30"]);
+        assert_eq!(reader.line_ending(), LineEnding::CrLf);
+        assert!(reader.has_trailing_newline());
+    }
+
+    #[test]
+    fn refreshes_appended_rows() {
+        let mut f = make_csv("id,name\n1,a\n2,b");
+        let mut reader = CsvReader::open(f.path()).unwrap();
+        assert_eq!(reader.refresh_appended().unwrap(), 0);
+
+        // The unterminated row is finished, then more follow
+        f.write_all(b"ob\n3,c\n4,").unwrap();
+        f.flush().unwrap();
+        assert_eq!(reader.refresh_appended().unwrap(), 2);
+        assert_eq!(reader.get_row(1).unwrap(), vec!["2", "bob"]);
+        assert_eq!(reader.get_row(3).unwrap(), vec!["4", ""]);
+        f.write_all(b"d\n").unwrap();
+        f.flush().unwrap();
+        assert_eq!(reader.refresh_appended().unwrap(), 0);
+        assert_eq!(reader.get_row(3).unwrap(), vec!["4", "d"]);
+        assert!(!reader.is_modified_on_disk().unwrap());
+
+        std::fs::write(f.path(), "id,name\n").unwrap();
+        assert!(matches!(
+            reader.refresh_appended(),
+            Err(MassiveCsvError::ExternallyModified(_))
+        ));
+    }
+
+    #[test]
+    fn reports_indexing_progress() {
+        let f = make_csv("id,name\n1,a\n2,b\n");
+        let reported = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let progress = {
+            let reported = Arc::clone(&reported);
+            Progress::new(move |done, total| reported.lock().unwrap().push((done, total)))
+        };
+        let reader =
+            CsvReader::open_with_progress(f.path(), &OpenOptions::default(), &progress).unwrap();
+        assert_eq!(reader.row_count(), 2);
+        assert_eq!(*reported.lock().unwrap(), vec![(16, 16)]);
+
+        let cancel = Arc::new(std::sync::atomic::AtomicBool::new(true));
+        let progress = Progress::default().with_cancel(cancel);
+        assert!(matches!(
+            CsvReader::open_with_progress(f.path(), &OpenOptions::default(), &progress),
+            Err(MassiveCsvError::Cancelled)
+        ));
+    }
+    #[test]
+    fn reads_a_file_without_headers() {
+        let f = make_csv("1,Ann\n2,Bo\n");
+        let reader = CsvReader::builder(f.path())
+            .no_headers(true)
+            .open()
+            .unwrap();
+        assert_eq!(reader.headers(), &["column_1", "column_2"]);
+        assert_eq!(reader.row_count(), 2);
+        assert_eq!(reader.get_row(0).unwrap(), vec!["1", "Ann"]);
+        assert_eq!(reader.reopen().unwrap().row_count(), 2);
     }
 }
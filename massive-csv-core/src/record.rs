@@ -0,0 +1,183 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::error::{MassiveCsvError, Result};
+use crate::parser::canonical_headers;
+
+/// The column names of a file, shared by every [`Record`] read from it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeaderMap {
+    raw: Vec<String>,
+    canonical: Vec<String>,
+}
+
+impl HeaderMap {
+    pub fn new(raw: Vec<String>) -> Self {
+        let canonical = canonical_headers(&raw);
+        Self { raw, canonical }
+    }
+
+    /// Headers as written in the file.
+    pub fn raw(&self) -> &[String] {
+        &self.raw
+    }
+
+    /// Headers with empty names filled in and duplicates suffixed.
+    pub fn canonical(&self) -> &[String] {
+        &self.canonical
+    }
+
+    pub fn len(&self) -> usize {
+        self.raw.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.raw.is_empty()
+    }
+
+    /// Position of the column called `name`, by canonical name first and then
+    /// by raw header text.
+    pub fn position(&self, name: &str) -> Option<usize> {
+        self.canonical
+            .iter()
+            .position(|h| h == name)
+            .or_else(|| self.raw.iter().position(|h| h == name))
+    }
+}
+
+/// One row of a file: its fields, its row number and its file's headers.
+///
+/// Fields can be read by position or by column name, as text or parsed:
+///
+/// ```no_run
+/// # fn main() -> massive_csv_core::Result<()> {
+/// let reader = massive_csv_core::CsvReader::open("orders.csv".as_ref())?;
+/// let record = reader.get_record(0)?;
+/// let total = record.get_f64("price")? * record.get_f64("qty")?;
+/// println!("row {}: {} = {total}", record.row(), record.get("id").unwrap_or(""));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    row: usize,
+    fields: Vec<String>,
+    headers: Arc<HeaderMap>,
+}
+
+impl Record {
+    pub fn new(row: usize, fields: Vec<String>, headers: Arc<HeaderMap>) -> Self {
+        Self {
+            row,
+            fields,
+            headers,
+        }
+    }
+
+    /// Row number (0-indexed, excluding the header).
+    pub fn row(&self) -> usize {
+        self.row
+    }
+
+    pub fn fields(&self) -> &[String] {
+        &self.fields
+    }
+
+    pub fn into_fields(self) -> Vec<String> {
+        self.fields
+    }
+
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// The field at `index`.
+    pub fn at(&self, index: usize) -> Option<&str> {
+        self.fields.get(index).map(String::as_str)
+    }
+
+    /// The field in column `name` (canonical or raw header).
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.headers.position(name).and_then(|i| self.at(i))
+    }
+
+    /// The field in column `name`, parsed as a number. Surrounding whitespace
+    /// is ignored.
+    pub fn get_f64(&self, name: &str) -> Result<f64> {
+        self.parse(name)
+    }
+
+    /// The field in column `name`, parsed as an integer. Surrounding whitespace
+    /// is ignored.
+    pub fn get_i64(&self, name: &str) -> Result<i64> {
+        self.parse(name)
+    }
+
+    /// The field in column `name`, parsed with [`FromStr`].
+    pub fn parse<T>(&self, name: &str) -> Result<T>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        let value = self
+            .get(name)
+            .ok_or_else(|| MassiveCsvError::ColumnNotFound(name.to_string()))?;
+        value.trim().parse().map_err(|e| {
+            MassiveCsvError::Parse(format!(
+                "row {}, column {name}: cannot parse {value:?}: {e}",
+                self.row
+            ))
+        })
+    }
+
+    /// Deserialize the record into `T` with the csv crate's deserializer.
+    /// Struct fields are matched to canonical column names.
+    #[cfg(feature = "serde")]
+    pub fn deserialize<T: serde::de::DeserializeOwned>(&self) -> Result<T> {
+        let headers = csv::StringRecord::from(self.headers.canonical());
+        deserialize_fields(self.row, self.fields.as_slice().into(), &headers)
+    }
+}
+
+#[cfg(feature = "serde")]
+pub(crate) fn deserialize_fields<T: serde::de::DeserializeOwned>(
+    row: usize,
+    fields: csv::StringRecord,
+    headers: &csv::StringRecord,
+) -> Result<T> {
+    fields
+        .deserialize(Some(headers))
+        .map_err(|e| MassiveCsvError::Parse(format!("row {row}: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(fields: &[&str]) -> Record {
+        let headers = HeaderMap::new(vec!["id".into(), "amount".into(), "amount".into()]);
+        let fields = fields.iter().map(|s| s.to_string()).collect();
+        Record::new(4, fields, Arc::new(headers))
+    }
+
+    #[test]
+    fn access_by_name_and_type() {
+        let r = record(&["7", " 12.5", "x"]);
+        assert_eq!(r.row(), 4);
+        assert_eq!(r.get("id"), Some("7"));
+        assert_eq!(r.get("amount_2"), Some("x"));
+        assert_eq!(r.get("missing"), None);
+        assert_eq!(r.get_i64("id").unwrap(), 7);
+        assert_eq!(r.get_f64("amount").unwrap(), 12.5);
+
+        assert!(matches!(
+            r.get_f64("amount_2"),
+            Err(MassiveCsvError::Parse(_))
+        ));
+        assert!(matches!(
+            r.get_f64("missing"),
+            Err(MassiveCsvError::ColumnNotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,474 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::compression::Compression;
+use crate::encoding::Encoding;
+use crate::error::{MassiveCsvError, Result};
+use crate::parser::{
+    detect_delimiter_with, detect_dialect, parse_headers, parse_row, DetectOptions, Dialect,
+    FixedWidth,
+};
+use crate::reader::OpenOptions;
+use crate::record::HeaderMap;
+
+/// Random access to the bytes of a file that is not on local disk.
+pub trait RangeSource: Send + Sync {
+    /// Where the data comes from, e.g. its URL.
+    fn name(&self) -> &str;
+
+    /// Total size in bytes.
+    fn len(&self) -> u64;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Read bytes `offset..offset + len`, cut short at the end of the data.
+    fn read_range(&self, offset: u64, len: usize) -> Result<Vec<u8>>;
+}
+
+/// Bytes fetched per range request.
+const BLOCK_SIZE: usize = 256 * 1024;
+
+/// Blocks kept in memory (16 MB).
+const CACHE_BLOCKS: usize = 64;
+
+/// Reads rows of a remote CSV file on demand.
+///
+/// Unlike [`CsvReader`](crate::CsvReader), nothing is downloaded up front:
+/// the header comes from the first block, and rows are located by scanning
+/// blocks for line breaks only as far as the rows asked for. Fetched blocks
+/// are kept in a small least-recently-used cache, so paging through nearby
+/// rows doesn't fetch them again. The total row count is only known once
+/// the whole file has been scanned ([`count_rows`](Self::count_rows)).
+///
+/// Remote files must be uncompressed UTF-8, one row per line.
+pub struct RemoteReader {
+    source: Box<dyn RangeSource>,
+    headers: Arc<HeaderMap>,
+    dialect: Dialect,
+    fixed_width: Option<FixedWidth>,
+    block_size: usize,
+    state: Mutex<ScanState>,
+}
+
+struct ScanState {
+    cache: BlockCache,
+    /// Byte offset of the start of each data row found so far.
+    line_index: Vec<u64>,
+    /// Bytes before this offset have been scanned for line breaks.
+    scanned_to: u64,
+}
+
+impl RemoteReader {
+    pub fn open(source: impl RangeSource + 'static) -> Result<Self> {
+        Self::open_with(source, &OpenOptions::default())
+    }
+
+    /// Open with dialect overrides. Lenient parsing and non-UTF-8 encodings
+    /// are not supported remotely.
+    pub fn open_with(source: impl RangeSource + 'static, options: &OpenOptions) -> Result<Self> {
+        Self::with_block_size(Box::new(source), options, BLOCK_SIZE)
+    }
+
+    fn with_block_size(
+        source: Box<dyn RangeSource>,
+        options: &OpenOptions,
+        block_size: usize,
+    ) -> Result<Self> {
+        options.validate()?;
+        if options.lenient || options.encoding.is_some_and(|e| e != Encoding::Utf8) {
+            return Err(MassiveCsvError::InvalidOptions(
+                "remote files are read strictly, as UTF-8".to_string(),
+            ));
+        }
+        if source.is_empty() {
+            return Err(MassiveCsvError::EmptyFile);
+        }
+
+        let mut state = ScanState {
+            cache: BlockCache::new(CACHE_BLOCKS),
+            line_index: Vec::new(),
+            scanned_to: 0,
+        };
+        let first = state.block(source.as_ref(), block_size, 0)?;
+        let compression = Compression::detect(&first);
+        if compression != Compression::None {
+            return Err(MassiveCsvError::Remote(format!(
+                "{} is {compression}-compressed; download it to open it",
+                source.name()
+            )));
+        }
+        let encoding = Encoding::detect(&first);
+        if encoding != Encoding::Utf8 {
+            return Err(MassiveCsvError::Remote(format!(
+                "{} looks like {encoding}; only UTF-8 can be read remotely",
+                source.name()
+            )));
+        }
+
+        let delimiter = match options.delimiter {
+            Some(delimiter) => delimiter,
+            None if options.fixed_width.is_some() => Dialect::default().delimiter,
+            None => {
+                // Only the first block is at hand, so sample just its start
+                let detect = DetectOptions {
+                    sample_points: 1,
+                    ..options.detect.clone()
+                };
+                detect_delimiter_with(&first, &detect).delimiter
+            }
+        };
+        let mut dialect = detect_dialect(&first, delimiter);
+        if let Some(quote) = options.quote_char {
+            dialect.quote = quote;
+        }
+        if let Some(escape) = options.escape {
+            dialect.escape = escape;
+        }
+
+        // The header line may run past the first block
+        let header_end = loop {
+            if let Some(end) = state.next_line_start(source.as_ref(), block_size)? {
+                break end;
+            }
+            if state.scanned_to >= source.len() {
+                break source.len();
+            }
+        };
+        let header = state.read(source.as_ref(), block_size, 0, header_end)?;
+        let headers = match &options.fixed_width {
+            Some(layout) => {
+                let line = std::str::from_utf8(strip_line_ending(&header))
+                    .map_err(|_| MassiveCsvError::InvalidUtf8(0))?;
+                layout.parse(line)
+            }
+            None => parse_headers(&header, dialect)?,
+        };
+        if header_end < source.len() {
+            state.line_index.push(header_end);
+        }
+
+        Ok(Self {
+            source,
+            headers: Arc::new(HeaderMap::new(headers)),
+            dialect,
+            fixed_width: options.fixed_width.clone(),
+            block_size,
+            state: Mutex::new(state),
+        })
+    }
+
+    /// Where the data comes from, e.g. its URL.
+    pub fn name(&self) -> &str {
+        self.source.name()
+    }
+
+    /// Size of the remote file in bytes.
+    pub fn len(&self) -> u64 {
+        self.source.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.source.is_empty()
+    }
+
+    /// Column headers, as written in the file.
+    pub fn headers(&self) -> &[String] {
+        self.headers.raw()
+    }
+
+    /// Column names with empty headers filled in and duplicates suffixed.
+    pub fn canonical_headers(&self) -> &[String] {
+        self.headers.canonical()
+    }
+
+    pub fn column_index(&self, name: &str) -> Option<usize> {
+        self.headers.position(name)
+    }
+
+    pub fn dialect(&self) -> Dialect {
+        self.dialect
+    }
+
+    pub fn delimiter(&self) -> u8 {
+        self.dialect.delimiter
+    }
+
+    /// The fixed-width layout, if the file is read as one.
+    pub fn fixed_width(&self) -> Option<&FixedWidth> {
+        self.fixed_width.as_ref()
+    }
+
+    /// Rows located so far. The file has at least this many.
+    pub fn rows_indexed(&self) -> usize {
+        self.lock().line_index.len()
+    }
+
+    /// The number of rows, if the whole file has been scanned.
+    pub fn row_count(&self) -> Option<usize> {
+        let state = self.lock();
+        (state.scanned_to >= self.source.len()).then_some(state.line_index.len())
+    }
+
+    /// Scan the rest of the file to count its rows. This downloads everything
+    /// not yet scanned, though only the cache's worth is kept.
+    pub fn count_rows(&self) -> Result<usize> {
+        let mut state = self.lock();
+        self.index_through(&mut state, usize::MAX)?;
+        Ok(state.line_index.len())
+    }
+
+    /// A row's text, without its line ending.
+    pub fn get_row_raw(&self, row: usize) -> Result<String> {
+        let mut state = self.lock();
+        self.index_through(&mut state, row + 1)?;
+        let Some(&start) = state.line_index.get(row) else {
+            return Err(MassiveCsvError::RowOutOfRange(row, state.line_index.len()));
+        };
+        let end = state
+            .line_index
+            .get(row + 1)
+            .copied()
+            .unwrap_or(self.source.len());
+        let bytes = state.read(self.source.as_ref(), self.block_size, start, end)?;
+        String::from_utf8(strip_line_ending(&bytes).to_vec()).map_err(|e| {
+            MassiveCsvError::InvalidUtf8(start as usize + e.utf8_error().valid_up_to())
+        })
+    }
+
+    /// A row parsed into fields.
+    pub fn get_row(&self, row: usize) -> Result<Vec<String>> {
+        let raw = self.get_row_raw(row)?;
+        match &self.fixed_width {
+            Some(layout) => Ok(layout.parse(&raw)),
+            None => Ok(parse_row(&raw, self.dialect)?),
+        }
+    }
+
+    /// Rows `start..end`, stopping early at the end of the file.
+    pub fn get_rows(&self, start: usize, end: usize) -> Result<Vec<Vec<String>>> {
+        let mut rows = Vec::with_capacity(end.saturating_sub(start).min(1024));
+        for row in start..end {
+            match self.get_row(row) {
+                Ok(fields) => rows.push(fields),
+                Err(MassiveCsvError::RowOutOfRange(..)) => break,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(rows)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, ScanState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Scan until the start of row `row` is known or the file ends.
+    fn index_through(&self, state: &mut ScanState, row: usize) -> Result<()> {
+        let len = self.source.len();
+        while state.line_index.len() <= row && state.scanned_to < len {
+            match state.next_line_start(self.source.as_ref(), self.block_size)? {
+                Some(start) if start < len => state.line_index.push(start),
+                _ => {}
+            }
+        }
+        if state.scanned_to >= len {
+            // Like the local reader, ignore a blank line at the very end
+            if let Some(&last) = state.line_index.last() {
+                let tail = state.read(self.source.as_ref(), self.block_size, last, len)?;
+                if strip_line_ending(&tail).iter().all(u8::is_ascii_whitespace) {
+                    state.line_index.pop();
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ScanState {
+    /// Block `index`, from the cache or the source.
+    fn block(
+        &mut self,
+        source: &dyn RangeSource,
+        block_size: usize,
+        index: u64,
+    ) -> Result<Arc<Vec<u8>>> {
+        if let Some(block) = self.cache.get(index) {
+            return Ok(block);
+        }
+        let block = Arc::new(source.read_range(index * block_size as u64, block_size)?);
+        self.cache.insert(index, Arc::clone(&block));
+        Ok(block)
+    }
+
+    /// Bytes `start..end`, which may span blocks.
+    fn read(
+        &mut self,
+        source: &dyn RangeSource,
+        block_size: usize,
+        start: u64,
+        end: u64,
+    ) -> Result<Vec<u8>> {
+        let size = block_size as u64;
+        let mut out = Vec::with_capacity((end - start) as usize);
+        let mut pos = start;
+        while pos < end {
+            let block = self.block(source, block_size, pos / size)?;
+            let from = (pos % size) as usize;
+            let to = ((end - pos) as usize + from).min(block.len());
+            if from >= to {
+                break;
+            }
+            out.extend_from_slice(&block[from..to]);
+            pos += (to - from) as u64;
+        }
+        Ok(out)
+    }
+
+    /// Scan forward for the next line break, at most to the end of the
+    /// current block, returning the offset just past it if found.
+    fn next_line_start(
+        &mut self,
+        source: &dyn RangeSource,
+        block_size: usize,
+    ) -> Result<Option<u64>> {
+        let size = block_size as u64;
+        let index = self.scanned_to / size;
+        let block = self.block(source, block_size, index)?;
+        let from = (self.scanned_to % size) as usize;
+        match memchr::memchr(b'\n', &block[from.min(block.len())..]) {
+            Some(i) => {
+                self.scanned_to += i as u64 + 1;
+                Ok(Some(self.scanned_to))
+            }
+            None => {
+                self.scanned_to = (index * size + block.len() as u64).max(self.scanned_to + 1);
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// A fixed number of blocks, evicting the least recently used.
+struct BlockCache {
+    blocks: HashMap<u64, (Arc<Vec<u8>>, u64)>,
+    capacity: usize,
+    clock: u64,
+}
+
+impl BlockCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            blocks: HashMap::with_capacity(capacity),
+            capacity,
+            clock: 0,
+        }
+    }
+
+    fn get(&mut self, index: u64) -> Option<Arc<Vec<u8>>> {
+        self.clock += 1;
+        let (block, used) = self.blocks.get_mut(&index)?;
+        *used = self.clock;
+        Some(Arc::clone(block))
+    }
+
+    fn insert(&mut self, index: u64, block: Arc<Vec<u8>>) {
+        if self.blocks.len() >= self.capacity {
+            let oldest = self
+                .blocks
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(&i, _)| i);
+            if let Some(oldest) = oldest {
+                self.blocks.remove(&oldest);
+            }
+        }
+        self.clock += 1;
+        self.blocks.insert(index, (block, self.clock));
+    }
+}
+
+fn strip_line_ending(data: &[u8]) -> &[u8] {
+    let data = data.strip_suffix(b"\n").unwrap_or(data);
+    data.strip_suffix(b"\r").unwrap_or(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// In-memory source counting its range requests.
+    struct MemorySource {
+        data: Vec<u8>,
+        requests: Arc<AtomicUsize>,
+    }
+
+    impl RangeSource for MemorySource {
+        fn name(&self) -> &str {
+            "memory"
+        }
+
+        fn len(&self) -> u64 {
+            self.data.len() as u64
+        }
+
+        fn read_range(&self, offset: u64, len: usize) -> Result<Vec<u8>> {
+            self.requests.fetch_add(1, Ordering::SeqCst);
+            let start = (offset as usize).min(self.data.len());
+            let end = (start + len).min(self.data.len());
+            Ok(self.data[start..end].to_vec())
+        }
+    }
+
+    fn open(data: &str, block_size: usize) -> (RemoteReader, Arc<AtomicUsize>) {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let source = MemorySource {
+            data: data.as_bytes().to_vec(),
+            requests: Arc::clone(&requests),
+        };
+        let reader =
+            RemoteReader::with_block_size(Box::new(source), &OpenOptions::default(), block_size)
+                .unwrap();
+        (reader, requests)
+    }
+
+    #[test]
+    fn reads_rows_lazily() {
+        let mut data = String::from("id;name\n");
+        for i in 0..1000 {
+            data.push_str(&format!("{i};\"row {i}\"\n"));
+        }
+        let (reader, requests) = open(&data, 64);
+
+        assert_eq!(reader.headers(), &["id", "name"]);
+        assert_eq!(reader.delimiter(), b';');
+        assert_eq!(reader.get_row(3).unwrap(), vec!["3", "row 3"]);
+        assert_eq!(reader.row_count(), None);
+        let fetched = requests.load(Ordering::SeqCst);
+        assert!(fetched <= 3, "fetched {fetched} blocks for row 3");
+
+        // Cached blocks are not fetched again
+        reader.get_row(2).unwrap();
+        assert_eq!(requests.load(Ordering::SeqCst), fetched);
+
+        assert_eq!(reader.count_rows().unwrap(), 1000);
+        assert_eq!(reader.row_count(), Some(1000));
+        assert_eq!(reader.get_row(999).unwrap(), vec!["999", "row 999"]);
+        assert!(matches!(
+            reader.get_row(1000),
+            Err(MassiveCsvError::RowOutOfRange(1000, 1000))
+        ));
+        assert_eq!(reader.get_rows(998, 1005).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn long_header_and_no_trailing_newline() {
+        let header: Vec<String> = (0..40).map(|i| format!("column_{i}")).collect();
+        let data = format!("{}\n{}", header.join(","), vec!["x"; 40].join(","));
+        let (reader, _) = open(&data, 16);
+        assert_eq!(reader.headers().len(), 40);
+        assert_eq!(reader.get_row(0).unwrap(), vec!["x"; 40]);
+        assert_eq!(reader.count_rows().unwrap(), 1);
+    }
+}
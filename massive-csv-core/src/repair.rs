@@ -0,0 +1,261 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::path::Path;
+
+use rayon::prelude::*;
+
+use crate::editor::{write_atomic, LineWriter};
+use crate::error::Result;
+use crate::parser::{field_count, parse_row, serialize_row};
+use crate::reader::CsvReader;
+
+/// Rows repaired per parallel batch.
+const CHUNK: usize = 64 * 1024;
+
+/// What to do with fields beyond the header count.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LongRows {
+    /// Drop the extra fields.
+    #[default]
+    Truncate,
+    /// Keep them in `_overflow_1`, `_overflow_2`, ... columns appended to the header.
+    Overflow,
+}
+
+/// A fix applied to one row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RepairAction {
+    /// NUL bytes removed.
+    StrippedNul { count: usize },
+    /// Invalid UTF-8 replaced with U+FFFD.
+    ReplacedInvalidUtf8,
+    /// An unclosed quote was kept as literal text and the row re-quoted.
+    BalancedQuotes,
+    /// Empty fields added to reach the header count.
+    Padded { from: usize, to: usize },
+    /// Fields beyond the header count dropped.
+    Truncated { from: usize, to: usize },
+    /// Fields beyond the header count moved to overflow columns.
+    Overflowed { fields: usize },
+}
+
+impl fmt::Display for RepairAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RepairAction::StrippedNul { count } => write!(
+                f,
+                "stripped {count} NUL byte{}",
+                if *count == 1 { "" } else { "s" }
+            ),
+            RepairAction::ReplacedInvalidUtf8 => write!(f, "replaced invalid UTF-8"),
+            RepairAction::BalancedQuotes => write!(f, "balanced quotes"),
+            RepairAction::Padded { from, to } => write!(f, "padded {from} -> {to} fields"),
+            RepairAction::Truncated { from, to } => write!(f, "truncated {from} -> {to} fields"),
+            RepairAction::Overflowed { fields } => {
+                write!(f, "moved {fields} extra field(s) to overflow columns")
+            }
+        }
+    }
+}
+
+/// The fixes applied to a row (0-indexed, excluding the header).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RowRepair {
+    pub row: usize,
+    pub actions: Vec<RepairAction>,
+}
+
+/// Result of [`CsvRepair::run`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RepairReport {
+    pub rows_written: usize,
+    /// Overflow columns added to the header.
+    pub overflow_columns: usize,
+    /// Every row that was changed, in row order.
+    pub repairs: Vec<RowRepair>,
+}
+
+/// Rewrites a CSV file so every row is well-formed.
+///
+/// Rows that need no repair are copied byte-for-byte; repaired rows are
+/// re-serialized in the file's dialect.
+#[derive(Debug, Clone, Default)]
+pub struct CsvRepair {
+    pub long_rows: LongRows,
+}
+
+impl CsvRepair {
+    /// Repair the reader's file into `output`, which may be the same file.
+    pub fn run(&self, reader: &CsvReader, output: &Path) -> Result<RepairReport> {
+        let expected = reader.headers().len();
+        let rows = reader.row_count();
+
+        let mut fixed: Vec<(usize, Vec<String>, Vec<RepairAction>)> = Vec::new();
+        let mut start = 0;
+        while start < rows {
+            let end = (start + CHUNK).min(rows);
+            let chunk: Vec<_> = (start..end)
+                .into_par_iter()
+                .map(|row| {
+                    Ok(self
+                        .repair_row(reader, row, expected)?
+                        .map(|r| (row, r.0, r.1)))
+                })
+                .collect::<Result<_>>()?;
+            fixed.extend(chunk.into_iter().flatten());
+            start = end;
+        }
+
+        let overflow_columns = fixed
+            .iter()
+            .map(|(_, fields, _)| fields.len().saturating_sub(expected))
+            .max()
+            .unwrap_or(0);
+        let dialect = reader.dialect();
+        let padding = (dialect.delimiter as char)
+            .to_string()
+            .repeat(overflow_columns);
+
+        write_atomic(output, |writer| {
+            let mut lines = LineWriter::new(writer, reader.line_ending());
+            let mut headers = reader.headers().to_vec();
+            headers.extend((1..=overflow_columns).map(|i| format!("_overflow_{i}")));
+            lines.write_line(&serialize_row(&headers, dialect))?;
+
+            let mut fixed = fixed.iter().peekable();
+            for row in 0..rows {
+                match fixed.next_if(|(r, _, _)| *r == row) {
+                    Some((_, fields, _)) => {
+                        let mut fields = fields.clone();
+                        fields.resize(expected + overflow_columns, String::new());
+                        lines.write_line(&serialize_row(&fields, dialect))?;
+                    }
+                    None => {
+                        let raw = reader.get_row_raw(row)?;
+                        lines.write_line(&format!("{raw}{padding}"))?;
+                    }
+                }
+            }
+            lines.finish(reader.has_trailing_newline())
+        })?;
+
+        Ok(RepairReport {
+            rows_written: rows,
+            overflow_columns,
+            repairs: fixed
+                .into_iter()
+                .map(|(row, _, actions)| RowRepair { row, actions })
+                .collect(),
+        })
+    }
+
+    /// The repaired fields and the fixes made, or `None` if the row is fine.
+    fn repair_row(
+        &self,
+        reader: &CsvReader,
+        row: usize,
+        expected: usize,
+    ) -> Result<Option<(Vec<String>, Vec<RepairAction>)>> {
+        let dialect = reader.dialect();
+        let bytes = reader.get_row_bytes(row)?;
+        let mut actions = Vec::new();
+
+        let nuls = bytes.iter().filter(|&&b| b == 0).count();
+        let bytes: Cow<[u8]> = if nuls > 0 {
+            actions.push(RepairAction::StrippedNul { count: nuls });
+            Cow::Owned(bytes.iter().copied().filter(|&b| b != 0).collect())
+        } else {
+            Cow::Borrowed(bytes)
+        };
+        let line = match String::from_utf8_lossy(&bytes) {
+            Cow::Borrowed(line) => Cow::Borrowed(line),
+            Cow::Owned(line) => {
+                actions.push(RepairAction::ReplacedInvalidUtf8);
+                Cow::Owned(line)
+            }
+        };
+
+        let (_, balanced) = field_count(&line, &dialect);
+        let mut fields = if balanced {
+            parse_row(&line, dialect)?
+        } else {
+            actions.push(RepairAction::BalancedQuotes);
+            line.split(dialect.delimiter as char)
+                .map(str::to_string)
+                .collect()
+        };
+
+        let found = fields.len();
+        if found < expected {
+            actions.push(RepairAction::Padded {
+                from: found,
+                to: expected,
+            });
+            fields.resize(expected, String::new());
+        } else if found > expected {
+            match self.long_rows {
+                LongRows::Truncate => {
+                    actions.push(RepairAction::Truncated {
+                        from: found,
+                        to: expected,
+                    });
+                    fields.truncate(expected);
+                }
+                LongRows::Overflow => actions.push(RepairAction::Overflowed {
+                    fields: found - expected,
+                }),
+            }
+        }
+
+        Ok((!actions.is_empty()).then_some((fields, actions)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn repair(input: &[u8], long_rows: LongRows) -> (String, RepairReport) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("in.csv");
+        fs::write(&path, input).unwrap();
+        let reader = CsvReader::open(&path).unwrap();
+        let output = dir.path().join("out.csv");
+        let report = CsvRepair { long_rows }.run(&reader, &output).unwrap();
+        (fs::read_to_string(&output).unwrap(), report)
+    }
+
+    #[test]
+    fn repairs_broken_rows() {
+        let (out, report) = repair(
+            b"a,b,c\n1,\"x\",3\n1,2\n\"x,2,3\n1,2,3,4\n1\x00,\xff,3\n",
+            LongRows::Truncate,
+        );
+        assert_eq!(
+            out,
+            "a,b,c\n1,\"x\",3\n1,2,\n\"\"\"x\",2,3\n1,2,3\n1,\u{FFFD},3\n"
+        );
+
+        let rows: Vec<usize> = report.repairs.iter().map(|r| r.row).collect();
+        assert_eq!(rows, vec![1, 2, 3, 4]);
+        assert_eq!(
+            report.repairs[3].actions,
+            vec![
+                RepairAction::StrippedNul { count: 1 },
+                RepairAction::ReplacedInvalidUtf8
+            ]
+        );
+    }
+
+    #[test]
+    fn overflow_columns() {
+        let (out, report) = repair(b"a,b\n1,2,3,4\n5,6\n", LongRows::Overflow);
+        assert_eq!(out, "a,b,_overflow_1,_overflow_2\n1,2,3,4\n5,6,,\n");
+        assert_eq!(report.overflow_columns, 2);
+        assert_eq!(
+            report.repairs[0].actions,
+            vec![RepairAction::Overflowed { fields: 2 }]
+        );
+    }
+}
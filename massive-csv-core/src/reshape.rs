@@ -0,0 +1,292 @@
+//! Pending changes to a file's columns, kept by [`CsvEditor`](crate::CsvEditor)
+//! and applied to every row when it is read or saved.
+
+use crate::error::{MassiveCsvError, Result};
+
+/// A change to the columns of a file being edited.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ColumnChange {
+    /// Replace `column` with the columns `names`, filled from its value
+    /// split at `delimiter`. Parts are trimmed; the last column takes the
+    /// rest of the value, and columns past the last part are empty.
+    Split {
+        column: String,
+        delimiter: String,
+        names: Vec<String>,
+    },
+    /// Replace `columns` with one column `name`, at the position of the
+    /// first of them, holding their non-empty values joined by `separator`.
+    Merge {
+        columns: Vec<String>,
+        separator: String,
+        name: String,
+    },
+    /// Append a column `name`, holding `default` in every row.
+    Add { name: String, default: String },
+    /// Remove `column`.
+    Drop { column: String },
+    /// Rename `from` to `to`, keeping its values.
+    Rename { from: String, to: String },
+}
+
+/// A [`ColumnChange`] with its columns looked up in the headers it applies
+/// to.
+#[derive(Debug, Clone)]
+pub(crate) struct BoundChange {
+    pub change: ColumnChange,
+    /// Width of rows before the change.
+    width: usize,
+    /// Columns the change reads, in its order.
+    columns: Vec<usize>,
+    /// Headers after the change.
+    pub headers: Vec<String>,
+}
+
+impl ColumnChange {
+    /// Look the change's columns up in `headers`.
+    pub(crate) fn bind(self, headers: &[String]) -> Result<BoundChange> {
+        let find = |name: &String| {
+            headers
+                .iter()
+                .position(|h| h == name)
+                .ok_or_else(|| MassiveCsvError::ColumnNotFound(name.clone()))
+        };
+        let (columns, added, new_headers) = match &self {
+            ColumnChange::Split {
+                column,
+                delimiter,
+                names,
+            } => {
+                if delimiter.is_empty() {
+                    return Err(MassiveCsvError::Parse(
+                        "split delimiter is empty".to_string(),
+                    ));
+                }
+                if names.is_empty() {
+                    return Err(MassiveCsvError::Parse(format!(
+                        "no names given for the parts of '{column}'"
+                    )));
+                }
+                let col = find(column)?;
+                let mut new_headers = headers.to_vec();
+                new_headers.splice(col..=col, names.iter().cloned());
+                (vec![col], names.clone(), new_headers)
+            }
+            ColumnChange::Merge { columns, name, .. } => {
+                if columns.is_empty() {
+                    return Err(MassiveCsvError::Parse("no columns to merge".to_string()));
+                }
+                let cols = columns.iter().map(find).collect::<Result<Vec<_>>>()?;
+                let first = *cols.iter().min().expect("not empty");
+                let mut new_headers: Vec<String> = headers
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| *i == first || !cols.contains(i))
+                    .map(|(_, h)| h.clone())
+                    .collect();
+                let position = first - cols.iter().filter(|&&c| c < first).count();
+                new_headers[position] = name.clone();
+                (cols, vec![name.clone()], new_headers)
+            }
+            ColumnChange::Add { name, .. } => {
+                let mut new_headers = headers.to_vec();
+                new_headers.push(name.clone());
+                (Vec::new(), vec![name.clone()], new_headers)
+            }
+            ColumnChange::Drop { column } => {
+                let col = find(column)?;
+                if headers.len() == 1 {
+                    return Err(MassiveCsvError::Parse(format!(
+                        "can't drop '{column}', the only column"
+                    )));
+                }
+                let mut new_headers = headers.to_vec();
+                new_headers.remove(col);
+                (vec![col], Vec::new(), new_headers)
+            }
+            ColumnChange::Rename { from, to } => {
+                let col = find(from)?;
+                let mut new_headers = headers.to_vec();
+                new_headers[col] = to.clone();
+                (vec![col], vec![to.clone()], new_headers)
+            }
+        };
+        for name in &added {
+            if name.is_empty() {
+                return Err(MassiveCsvError::Parse("column name is empty".to_string()));
+            }
+            if new_headers.iter().filter(|h| *h == name).count() > 1 {
+                return Err(MassiveCsvError::Parse(format!(
+                    "column '{name}' already exists"
+                )));
+            }
+        }
+        Ok(BoundChange {
+            change: self,
+            width: headers.len(),
+            columns,
+            headers: new_headers,
+        })
+    }
+}
+
+impl BoundChange {
+    /// Apply the change to a row. A row with more or fewer fields than the
+    /// headers before the change is first cut or padded to their width.
+    pub fn apply(&self, fields: &mut Vec<String>) {
+        fields.resize(self.width, String::new());
+        match &self.change {
+            ColumnChange::Split {
+                delimiter, names, ..
+            } => {
+                let col = self.columns[0];
+                let value = std::mem::take(&mut fields[col]);
+                let mut parts: Vec<String> = value
+                    .splitn(names.len(), delimiter.as_str())
+                    .map(|p| p.trim().to_string())
+                    .collect();
+                parts.resize(names.len(), String::new());
+                fields.splice(col..=col, parts);
+            }
+            ColumnChange::Merge { separator, .. } => {
+                let merged = self
+                    .columns
+                    .iter()
+                    .map(|&c| fields[c].as_str())
+                    .filter(|v| !v.is_empty())
+                    .collect::<Vec<_>>()
+                    .join(separator);
+                let first = *self.columns.iter().min().expect("not empty");
+                fields[first] = merged;
+                let mut i = 0;
+                fields.retain(|_| {
+                    let keep = i == first || !self.columns.contains(&i);
+                    i += 1;
+                    keep
+                });
+            }
+            ColumnChange::Add { default, .. } => fields.push(default.clone()),
+            ColumnChange::Drop { .. } => {
+                fields.remove(self.columns[0]);
+            }
+            ColumnChange::Rename { .. } => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(names: &[&str]) -> Vec<String> {
+        names.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn row(values: &[&str]) -> Vec<String> {
+        headers(values)
+    }
+
+    #[test]
+    fn splits_a_column() {
+        let change = ColumnChange::Split {
+            column: "place".into(),
+            delimiter: ",".into(),
+            names: headers(&["city", "state"]),
+        }
+        .bind(&headers(&["id", "place", "zip"]))
+        .unwrap();
+        assert_eq!(change.headers, headers(&["id", "city", "state", "zip"]));
+
+        let mut fields = row(&["1", "Austin, TX", "78701"]);
+        change.apply(&mut fields);
+        assert_eq!(fields, row(&["1", "Austin", "TX", "78701"]));
+        let mut fields = row(&["2", "Nowhere"]);
+        change.apply(&mut fields);
+        assert_eq!(fields, row(&["2", "Nowhere", "", ""]));
+        let mut fields = row(&["3", "a, b, c", "1"]);
+        change.apply(&mut fields);
+        assert_eq!(fields, row(&["3", "a", "b, c", "1"]));
+
+        assert!(ColumnChange::Split {
+            column: "place".into(),
+            delimiter: ",".into(),
+            names: headers(&["id", "state"]),
+        }
+        .bind(&headers(&["id", "place"]))
+        .is_err());
+    }
+
+    #[test]
+    fn merges_columns() {
+        let change = ColumnChange::Merge {
+            columns: headers(&["last", "first"]),
+            separator: ", ".into(),
+            name: "name".into(),
+        }
+        .bind(&headers(&["id", "first", "last", "age"]))
+        .unwrap();
+        assert_eq!(change.headers, headers(&["id", "name", "age"]));
+
+        let mut fields = row(&["1", "Ada", "Lovelace", "36"]);
+        change.apply(&mut fields);
+        assert_eq!(fields, row(&["1", "Lovelace, Ada", "36"]));
+        let mut fields = row(&["2", "Plato", "", "80"]);
+        change.apply(&mut fields);
+        assert_eq!(fields, row(&["2", "Plato", "80"]));
+
+        assert!(ColumnChange::Merge {
+            columns: headers(&["first", "nope"]),
+            separator: " ".into(),
+            name: "name".into(),
+        }
+        .bind(&headers(&["first", "last"]))
+        .is_err());
+    }
+
+    #[test]
+    fn adds_drops_and_renames_columns() {
+        let change = ColumnChange::Add {
+            name: "created".into(),
+            default: "today".into(),
+        }
+        .bind(&headers(&["id", "amt"]))
+        .unwrap();
+        assert_eq!(change.headers, headers(&["id", "amt", "created"]));
+        let mut fields = row(&["1"]);
+        change.apply(&mut fields);
+        assert_eq!(fields, row(&["1", "", "today"]));
+
+        let change = ColumnChange::Drop {
+            column: "amt".into(),
+        }
+        .bind(&headers(&["id", "amt", "note"]))
+        .unwrap();
+        assert_eq!(change.headers, headers(&["id", "note"]));
+        let mut fields = row(&["1", "5", "x"]);
+        change.apply(&mut fields);
+        assert_eq!(fields, row(&["1", "x"]));
+
+        let change = ColumnChange::Rename {
+            from: "amt".into(),
+            to: "amount".into(),
+        }
+        .bind(&headers(&["id", "amt"]))
+        .unwrap();
+        assert_eq!(change.headers, headers(&["id", "amount"]));
+
+        let existing = ColumnChange::Add {
+            name: "id".into(),
+            default: String::new(),
+        };
+        assert!(existing.bind(&headers(&["id"])).is_err());
+        let existing = ColumnChange::Rename {
+            from: "amt".into(),
+            to: "id".into(),
+        };
+        assert!(existing.bind(&headers(&["id", "amt"])).is_err());
+        let missing = ColumnChange::Drop {
+            column: "nope".into(),
+        };
+        assert!(missing.bind(&headers(&["id"])).is_err());
+    }
+}
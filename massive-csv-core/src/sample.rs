@@ -0,0 +1,187 @@
+//! Reproducible row samples.
+
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::editor::{write_atomic, LineWriter};
+use crate::error::Result;
+use crate::reader::CsvReader;
+use crate::stats::mix;
+
+/// How [`sample`] picks rows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SampleMethod {
+    /// `n` rows chosen uniformly at random (Floyd's algorithm; memory grows
+    /// with `n`, not with the file).
+    #[default]
+    Random,
+    /// Reservoir sampling (Li's algorithm L) over the rows in order, as a
+    /// single streaming pass would; uniform like `Random`, picking
+    /// different rows for the same seed.
+    Reservoir,
+    /// Every k-th row from a random start, k being the row count over `n`.
+    EveryKth,
+}
+
+impl SampleMethod {
+    /// Parse `random`, `reservoir` or `every-kth`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "random" => Some(SampleMethod::Random),
+            "reservoir" => Some(SampleMethod::Reservoir),
+            "every-kth" | "systematic" => Some(SampleMethod::EveryKth),
+            _ => None,
+        }
+    }
+}
+
+/// Pick `n` rows of the reader's file (all of them if it has fewer),
+/// returned in row order. The same seed always gives the same rows.
+pub fn sample(reader: &CsvReader, n: usize, method: SampleMethod, seed: u64) -> Result<Vec<usize>> {
+    Ok(sample_rows(reader.row_count(), n, method, seed))
+}
+
+fn sample_rows(rows: usize, n: usize, method: SampleMethod, seed: u64) -> Vec<usize> {
+    if n >= rows {
+        return (0..rows).collect();
+    }
+    if n == 0 {
+        return Vec::new();
+    }
+    let mut rng = SplitMix64(seed);
+    let mut picked = match method {
+        SampleMethod::Random => {
+            let mut chosen = HashSet::with_capacity(n);
+            for j in rows - n..rows {
+                let t = rng.below(j + 1);
+                if !chosen.insert(t) {
+                    chosen.insert(j);
+                }
+            }
+            chosen.into_iter().collect()
+        }
+        SampleMethod::Reservoir => {
+            let mut reservoir: Vec<usize> = (0..n).collect();
+            let mut w = (rng.unit().ln() / n as f64).exp();
+            let mut i = n - 1;
+            loop {
+                let skip = (rng.unit().ln() / (1.0 - w).ln()).floor();
+                if !skip.is_finite() || skip >= (rows - i) as f64 {
+                    break;
+                }
+                i += skip as usize + 1;
+                if i >= rows {
+                    break;
+                }
+                reservoir[rng.below(n)] = i;
+                w *= (rng.unit().ln() / n as f64).exp();
+            }
+            reservoir
+        }
+        SampleMethod::EveryKth => {
+            let k = rows / n;
+            let start = rng.below(k);
+            (0..n).map(|i| start + i * k).collect()
+        }
+    };
+    picked.sort_unstable();
+    picked
+}
+
+/// Write the header and the given rows (in row order) of the reader's file
+/// to `output`, copying each line byte-for-byte.
+pub fn write_rows(reader: &CsvReader, rows: &[usize], output: &Path) -> Result<()> {
+    write_atomic(output, |writer| {
+        let mut lines = LineWriter::new(writer, reader.line_ending());
+        lines.write_line(reader.header_raw()?)?;
+        for &row in rows {
+            lines.write_line(reader.get_row_raw(row)?)?;
+        }
+        lines.finish(reader.has_trailing_newline())
+    })
+}
+
+/// A small seeded generator; samples need reproducibility, not security.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        mix(self.0)
+    }
+
+    /// Uniform in `0..bound` (bound > 0).
+    fn below(&mut self, bound: usize) -> usize {
+        ((self.next() as u128 * bound as u128) >> 64) as usize
+    }
+
+    /// Uniform in the open interval (0, 1).
+    fn unit(&mut self) -> f64 {
+        ((self.next() >> 11) as f64 + 0.5) / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn make_csv(content: &str) -> tempfile::NamedTempFile {
+        let mut f = tempfile::NamedTempFile::new().unwrap();
+        f.write_all(content.as_bytes()).unwrap();
+        f.flush().unwrap();
+        f
+    }
+
+    #[test]
+    fn samples_are_reproducible_and_distinct() {
+        for method in [
+            SampleMethod::Random,
+            SampleMethod::Reservoir,
+            SampleMethod::EveryKth,
+        ] {
+            let rows = sample_rows(1_000_000, 1000, method, 42);
+            assert_eq!(rows.len(), 1000, "{method:?}");
+            assert!(rows.windows(2).all(|w| w[0] < w[1]), "{method:?}");
+            assert!(*rows.last().unwrap() < 1_000_000);
+            assert_eq!(rows, sample_rows(1_000_000, 1000, method, 42));
+            assert_ne!(rows, sample_rows(1_000_000, 1000, method, 43));
+        }
+        assert_eq!(
+            sample_rows(5, 10, SampleMethod::Random, 1),
+            vec![0, 1, 2, 3, 4]
+        );
+
+        let every = sample_rows(100, 10, SampleMethod::EveryKth, 7);
+        assert!(every.windows(2).all(|w| w[1] - w[0] == 10));
+    }
+
+    #[test]
+    fn samples_cover_the_whole_file() {
+        // Uniform samples land in each tenth of the file about equally
+        for method in [SampleMethod::Random, SampleMethod::Reservoir] {
+            let mut buckets = [0usize; 10];
+            for row in sample_rows(100_000, 10_000, method, 7) {
+                buckets[row / 10_000] += 1;
+            }
+            assert!(
+                buckets.iter().all(|&b| (900..1100).contains(&b)),
+                "{method:?} {buckets:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn writes_sampled_rows() {
+        let f = make_csv("id\n0\n1\n2\n3\n");
+        let reader = CsvReader::open(f.path()).unwrap();
+        let rows = sample(&reader, 2, SampleMethod::Random, 3).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("sample.csv");
+        write_rows(&reader, &rows, &output).unwrap();
+        let expected: String = std::iter::once("id\n".to_string())
+            .chain(rows.iter().map(|r| format!("{r}\n")))
+            .collect();
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), expected);
+    }
+}
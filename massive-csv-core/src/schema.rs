@@ -0,0 +1,401 @@
+//! User-defined column schemas: a type, nullability and value constraints
+//! per column, checked as cells are edited or over a whole file.
+
+use std::fmt;
+use std::path::Path;
+
+use rayon::prelude::*;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{MassiveCsvError, Result};
+use crate::infer::ColumnType;
+use crate::reader::CsvReader;
+use crate::validate::ValidateOptions;
+
+/// Rows checked per parallel batch, so the violation limit can stop the
+/// scan early.
+const CHUNK: usize = 64 * 1024;
+
+/// Rules for some of a file's columns. Columns it doesn't name are
+/// unconstrained.
+///
+/// As JSON: `{"columns": [{"name": "age", "type": "integer", "nullable":
+/// false, "min": 0}, {"name": "email", "pattern": "[^@]+@[^@]+"}]}`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Schema {
+    pub columns: Vec<ColumnSchema>,
+}
+
+/// Rules for one column.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ColumnSchema {
+    pub name: String,
+    /// Type every non-empty value must have, as [`ColumnType::of`] sees
+    /// it; integers also count as floats and dates as datetimes.
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub column_type: Option<ColumnType>,
+    /// Whether the value may be empty (default: true). Empty values skip
+    /// the other checks.
+    #[serde(default = "nullable_default")]
+    pub nullable: bool,
+    /// Regular expression the whole value must match.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+    /// Smallest allowed number; values must then be numeric.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,
+    /// Largest allowed number; values must then be numeric.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
+}
+
+fn nullable_default() -> bool {
+    true
+}
+
+impl ColumnSchema {
+    /// A nullable column of any type, without constraints.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            column_type: None,
+            nullable: true,
+            pattern: None,
+            min: None,
+            max: None,
+        }
+    }
+}
+
+impl Schema {
+    /// Parse a schema from JSON text.
+    pub fn from_json(text: &str) -> Result<Self> {
+        serde_json::from_str(text).map_err(|e| MassiveCsvError::InvalidSchema(e.to_string()))
+    }
+
+    /// Load a schema from a JSON file.
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+
+    /// Resolve the schema's columns against `headers` and compile its
+    /// patterns.
+    pub(crate) fn bind(&self, headers: &[String]) -> Result<BoundSchema> {
+        let columns = self
+            .columns
+            .iter()
+            .map(|rule| {
+                let index = headers
+                    .iter()
+                    .position(|h| *h == rule.name)
+                    .ok_or_else(|| MassiveCsvError::ColumnNotFound(rule.name.clone()))?;
+                let pattern = rule
+                    .pattern
+                    .as_deref()
+                    .map(|p| Regex::new(&format!("^(?:{p})$")))
+                    .transpose()
+                    .map_err(|e| {
+                        MassiveCsvError::InvalidSchema(format!("column '{}': {e}", rule.name))
+                    })?;
+                Ok(BoundColumn {
+                    index,
+                    rule: rule.clone(),
+                    pattern,
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(BoundSchema { columns })
+    }
+}
+
+/// Why a value broke its column's rules.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ViolationKind {
+    /// The value is empty but the column is not nullable.
+    Required,
+    /// The value is not of the column's type.
+    Type(ColumnType),
+    /// The column has a range but the value is not a number.
+    NotNumeric,
+    /// The value doesn't match the column's pattern.
+    Pattern(String),
+    /// The value is below the column's minimum.
+    BelowMin(f64),
+    /// The value is above the column's maximum.
+    AboveMax(f64),
+}
+
+/// A value breaking its column's rules.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    /// Row number (0-indexed, excluding the header).
+    pub row: usize,
+    pub column: String,
+    pub value: String,
+    pub kind: ViolationKind,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "row {}, column '{}': ", self.row, self.column)?;
+        let value = &self.value;
+        match &self.kind {
+            ViolationKind::Required => write!(f, "a value is required"),
+            ViolationKind::Type(t) => write!(f, "expected {t}, got {value:?}"),
+            ViolationKind::NotNumeric => write!(f, "expected a number, got {value:?}"),
+            ViolationKind::Pattern(p) => write!(f, "{value:?} does not match `{p}`"),
+            ViolationKind::BelowMin(min) => write!(f, "{value} is below the minimum {min}"),
+            ViolationKind::AboveMax(max) => write!(f, "{value} is above the maximum {max}"),
+        }
+    }
+}
+
+/// Result of [`validate_schema`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SchemaReport {
+    /// Rows checked before the scan finished or hit the violation limit.
+    pub rows_checked: usize,
+    /// Violations found, in row order.
+    pub violations: Vec<Violation>,
+    /// Whether the scan stopped early at `max_issues`.
+    pub truncated: bool,
+}
+
+impl SchemaReport {
+    pub fn is_valid(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+/// Check every row of `reader` against `schema`. `max_issues` in `options`
+/// limits the violations reported.
+pub fn validate_schema(
+    reader: &CsvReader,
+    schema: &Schema,
+    options: &ValidateOptions,
+) -> Result<SchemaReport> {
+    let bound = schema.bind(reader.canonical_headers())?;
+    bound.scan(reader.row_count(), |row| reader.get_row(row), options)
+}
+
+/// A schema resolved against a file's headers.
+#[derive(Debug, Clone)]
+pub(crate) struct BoundSchema {
+    columns: Vec<BoundColumn>,
+}
+
+#[derive(Debug, Clone)]
+struct BoundColumn {
+    index: usize,
+    rule: ColumnSchema,
+    pattern: Option<Regex>,
+}
+
+impl BoundColumn {
+    fn check(&self, row: usize, value: &str) -> Option<Violation> {
+        let rule = &self.rule;
+        let kind = if value.is_empty() {
+            (!rule.nullable).then_some(ViolationKind::Required)?
+        } else if let Some(t) = rule
+            .column_type
+            .filter(|&t| ColumnType::of(value).is_some_and(|v| t.merge(v) != t))
+        {
+            ViolationKind::Type(t)
+        } else if self.pattern.as_ref().is_some_and(|p| !p.is_match(value)) {
+            ViolationKind::Pattern(rule.pattern.clone().unwrap_or_default())
+        } else if rule.min.is_some() || rule.max.is_some() {
+            match value.trim().parse::<f64>() {
+                Err(_) => ViolationKind::NotNumeric,
+                Ok(n) => match (rule.min, rule.max) {
+                    (Some(min), _) if n < min => ViolationKind::BelowMin(min),
+                    (_, Some(max)) if n > max => ViolationKind::AboveMax(max),
+                    _ => return None,
+                },
+            }
+        } else {
+            return None;
+        };
+        Some(Violation {
+            row,
+            column: rule.name.clone(),
+            value: value.to_string(),
+            kind,
+        })
+    }
+}
+
+impl BoundSchema {
+    /// The first violation in `fields`, the fields of `row`.
+    pub(crate) fn check_row(&self, row: usize, fields: &[String]) -> Option<Violation> {
+        self.columns.iter().find_map(|column| {
+            let value = fields.get(column.index).map_or("", String::as_str);
+            column.check(row, value)
+        })
+    }
+
+    /// The violation `value` would cause in column `col` of `row`.
+    pub(crate) fn check_cell(&self, row: usize, col: usize, value: &str) -> Option<Violation> {
+        self.columns
+            .iter()
+            .filter(|column| column.index == col)
+            .find_map(|column| column.check(row, value))
+    }
+
+    /// Check rows `0..rows`, fetched with `get_row`, in parallel batches.
+    pub(crate) fn scan(
+        &self,
+        rows: usize,
+        get_row: impl Fn(usize) -> Result<Vec<String>> + Sync,
+        options: &ValidateOptions,
+    ) -> Result<SchemaReport> {
+        let mut report = SchemaReport::default();
+        let mut start = 0;
+        while start < rows {
+            let end = (start + CHUNK).min(rows);
+            let chunk: Vec<Vec<Violation>> = (start..end)
+                .into_par_iter()
+                .map(|row| {
+                    let fields = get_row(row)?;
+                    Ok(self
+                        .columns
+                        .iter()
+                        .filter_map(|column| {
+                            let value = fields.get(column.index).map_or("", String::as_str);
+                            column.check(row, value)
+                        })
+                        .collect())
+                })
+                .collect::<Result<_>>()?;
+
+            report.rows_checked = end;
+            report.violations.extend(chunk.into_iter().flatten());
+            if options.max_issues > 0 && report.violations.len() >= options.max_issues {
+                report.truncated = report.violations.len() > options.max_issues || end < rows;
+                report.violations.truncate(options.max_issues);
+                if let Some(last) = report.violations.last() {
+                    report.rows_checked = last.row + 1;
+                }
+                break;
+            }
+            start = end;
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::CsvEditor;
+    use std::io::Write;
+
+    fn make_csv(content: &str) -> tempfile::NamedTempFile {
+        let mut f = tempfile::NamedTempFile::new().unwrap();
+        f.write_all(content.as_bytes()).unwrap();
+        f.flush().unwrap();
+        f
+    }
+
+    const SCHEMA: &str = r#"{"columns": [
+        {"name": "id", "type": "integer", "nullable": false},
+        {"name": "age", "type": "integer", "min": 0, "max": 150},
+        {"name": "email", "pattern": "[^@]+@[^@]+"}
+    ]}"#;
+
+    #[test]
+    fn reports_violations_in_row_order() {
+        let f = make_csv("id,age,email\n1,30,a@x.io\n,200,b@x.io\nx,-1,nope\n4,,\n");
+        let reader = CsvReader::open(f.path()).unwrap();
+        let schema = Schema::from_json(SCHEMA).unwrap();
+
+        let report = validate_schema(&reader, &schema, &ValidateOptions::default()).unwrap();
+        let found: Vec<(usize, &str, &ViolationKind)> = report
+            .violations
+            .iter()
+            .map(|v| (v.row, v.column.as_str(), &v.kind))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (1, "id", &ViolationKind::Required),
+                (1, "age", &ViolationKind::AboveMax(150.0)),
+                (2, "id", &ViolationKind::Type(ColumnType::Integer)),
+                (2, "age", &ViolationKind::BelowMin(0.0)),
+                (
+                    2,
+                    "email",
+                    &ViolationKind::Pattern("[^@]+@[^@]+".to_string())
+                ),
+            ]
+        );
+        assert_eq!(
+            report.violations[2].to_string(),
+            "row 2, column 'id': expected integer, got \"x\""
+        );
+
+        let options = ValidateOptions { max_issues: 2 };
+        let report = validate_schema(&reader, &schema, &options).unwrap();
+        assert_eq!(report.violations.len(), 2);
+        assert_eq!(report.rows_checked, 2);
+        assert!(report.truncated);
+    }
+
+    #[test]
+    fn rejects_bad_schemas() {
+        let f = make_csv("id\n1\n");
+        let reader = CsvReader::open(f.path()).unwrap();
+        let bad_pattern = Schema {
+            columns: vec![ColumnSchema {
+                pattern: Some("(".to_string()),
+                ..ColumnSchema::new("id")
+            }],
+        };
+        assert!(matches!(
+            validate_schema(&reader, &bad_pattern, &ValidateOptions::default()),
+            Err(MassiveCsvError::InvalidSchema(_))
+        ));
+        let missing = Schema {
+            columns: vec![ColumnSchema::new("name")],
+        };
+        assert!(matches!(
+            validate_schema(&reader, &missing, &ValidateOptions::default()),
+            Err(MassiveCsvError::ColumnNotFound(_))
+        ));
+        assert!(Schema::from_json(r#"{"columns": [{"name": "id", "type": "money"}]}"#).is_err());
+    }
+
+    #[test]
+    fn editor_rejects_invalid_edits() {
+        let f = make_csv("id,age,email\n1,30,a@x.io\n2,40,b@x.io\n");
+        let mut editor = CsvEditor::open(f.path()).unwrap();
+        editor
+            .set_schema(Some(Schema::from_json(SCHEMA).unwrap()))
+            .unwrap();
+
+        let err = editor.set_cell(0, 1, "old".to_string()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Schema violation: row 0, column 'age': expected integer, got \"old\""
+        );
+        assert!(matches!(
+            editor.set_cell(0, 2, "nobody".to_string()),
+            Err(MassiveCsvError::SchemaViolation(_))
+        ));
+        assert!(editor
+            .set_row(1, vec!["".into(), "1".into(), "c@x.io".into()])
+            .is_err());
+        assert_eq!(editor.edit_count(), 0);
+
+        editor.set_cell(0, 1, "31".to_string()).unwrap();
+        // Pending edits are checked along with the file
+        assert!(editor
+            .validate_against_schema(&ValidateOptions::default())
+            .unwrap()
+            .is_valid());
+
+        editor.set_schema(None).unwrap();
+        editor.set_cell(0, 1, "old".to_string()).unwrap();
+    }
+}
@@ -0,0 +1,202 @@
+use rhai::{Dynamic, Engine, Map, Scope, AST};
+
+use crate::error::{MassiveCsvError, Result};
+
+/// A compiled [Rhai](https://rhai.rs) script run once per row.
+///
+/// The row is exposed as the object map `row`, keyed by column name; fields
+/// that look like numbers are passed as numbers so arithmetic works directly.
+/// Assigning `row.<column>` changes a field (or adds a column), and a script
+/// whose last expression is `false` drops the row:
+///
+/// ```text
+/// row.total = row.price * row.qty; row.status == "active"
+/// ```
+pub struct RowScript {
+    engine: Engine,
+    ast: AST,
+    input_columns: Vec<String>,
+    columns: Vec<String>,
+    assigned: Vec<String>,
+    referenced: Vec<String>,
+}
+
+impl RowScript {
+    /// Compile `source` for rows with the given headers.
+    pub fn compile(source: &str, headers: &[String]) -> Result<Self> {
+        let engine = Engine::new();
+        let ast = engine
+            .compile(source)
+            .map_err(|e| MassiveCsvError::Script(e.to_string()))?;
+
+        let (assigned, referenced) = scan_row_properties(source);
+        let mut columns = headers.to_vec();
+        for name in &assigned {
+            if !columns.contains(name) {
+                columns.push(name.clone());
+            }
+        }
+
+        Ok(Self {
+            engine,
+            ast,
+            input_columns: headers.to_vec(),
+            columns,
+            assigned,
+            referenced,
+        })
+    }
+
+    /// Output columns: the input headers followed by any columns the script adds.
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    /// Columns the script assigns to, as found by scanning its source.
+    pub fn assigned_columns(&self) -> &[String] {
+        &self.assigned
+    }
+
+    /// Columns the script reads or writes, as found by scanning its source.
+    pub fn referenced_columns(&self) -> &[String] {
+        &self.referenced
+    }
+
+    /// Run the script on one row.
+    ///
+    /// Returns the output fields, or `None` if the script filtered the row out.
+    /// Fields the script leaves alone are returned exactly as they were.
+    pub fn apply(&self, fields: Vec<String>) -> Result<Option<Vec<String>>> {
+        let mut map = Map::new();
+        let mut originals = Vec::with_capacity(self.input_columns.len());
+        for (name, value) in self.input_columns.iter().zip(&fields) {
+            let value = to_dynamic(value);
+            originals.push(value.to_string());
+            map.insert(name.as_str().into(), value);
+        }
+
+        let mut scope = Scope::new();
+        scope.push("row", map);
+        let result: Dynamic = self
+            .engine
+            .eval_ast_with_scope(&mut scope, &self.ast)
+            .map_err(|e| MassiveCsvError::Script(e.to_string()))?;
+        if result.as_bool() == Ok(false) {
+            return Ok(None);
+        }
+
+        let map = scope
+            .get_value::<Map>("row")
+            .ok_or_else(|| MassiveCsvError::Script("`row` is no longer a map".to_string()))?;
+
+        let mut output = fields;
+        output.resize(self.columns.len(), String::new());
+        for (i, name) in self.columns.iter().enumerate() {
+            let Some(value) = map.get(name.as_str()) else {
+                continue;
+            };
+            let text = if value.is_unit() {
+                String::new()
+            } else {
+                value.to_string()
+            };
+            if originals.get(i) != Some(&text) {
+                output[i] = text;
+            }
+        }
+        Ok(Some(output))
+    }
+}
+
+/// Expose a field as an integer or float if it parses as one, otherwise as a string.
+fn to_dynamic(value: &str) -> Dynamic {
+    if let Ok(n) = value.parse::<i64>() {
+        return Dynamic::from_int(n);
+    }
+    match value.parse::<f64>() {
+        Ok(f) if f.is_finite() && !value.trim().is_empty() => Dynamic::from_float(f),
+        _ => Dynamic::from(value.to_string()),
+    }
+}
+
+/// Find `row.<name>` accesses in a script: (assigned names, all names), in order of appearance.
+fn scan_row_properties(source: &str) -> (Vec<String>, Vec<String>) {
+    let mut assigned = Vec::new();
+    let mut referenced = Vec::new();
+    let bytes = source.as_bytes();
+
+    let mut pos = 0;
+    while let Some(found) = source[pos..].find("row.") {
+        let start = pos + found;
+        pos = start + 4;
+        let preceded_by_ident =
+            start > 0 && (bytes[start - 1].is_ascii_alphanumeric() || bytes[start - 1] == b'_');
+        if preceded_by_ident {
+            continue;
+        }
+
+        let end = source[pos..]
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .map_or(source.len(), |e| pos + e);
+        if end == pos {
+            continue;
+        }
+        let name = source[pos..end].to_string();
+
+        let rest = source[end..].trim_start();
+        let is_assignment = rest.starts_with('=') && !rest.starts_with("==");
+        if is_assignment && !assigned.contains(&name) {
+            assigned.push(name.clone());
+        }
+        if !referenced.contains(&name) {
+            referenced.push(name);
+        }
+        pos = end;
+    }
+
+    (assigned, referenced)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(names: &[&str]) -> Vec<String> {
+        names.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn row(values: &[&str]) -> Vec<String> {
+        values.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn transform_and_filter() {
+        let script = RowScript::compile(
+            r#"row.total = row.price * row.qty; row.status == "active""#,
+            &headers(&["id", "price", "qty", "status"]),
+        )
+        .unwrap();
+        assert_eq!(
+            script.columns(),
+            headers(&["id", "price", "qty", "status", "total"])
+        );
+
+        let out = script.apply(row(&["007", "2.5", "4", "active"])).unwrap();
+        assert_eq!(out, Some(row(&["007", "2.5", "4", "active", "10.0"])));
+
+        let out = script.apply(row(&["8", "1", "1", "closed"])).unwrap();
+        assert_eq!(out, None);
+    }
+
+    #[test]
+    fn script_errors_are_reported() {
+        let result = RowScript::compile("row.a = ", &headers(&["a"]));
+        assert!(matches!(result, Err(MassiveCsvError::Script(_))));
+
+        let script = RowScript::compile(r#"row.a = row.a / 0"#, &headers(&["a"])).unwrap();
+        assert!(matches!(
+            script.apply(row(&["1"])),
+            Err(MassiveCsvError::Script(_))
+        ));
+    }
+}
@@ -1,14 +1,45 @@
+use std::ops::Range;
+
 use rayon::prelude::*;
 
+use crate::background::Progress;
 use crate::error::Result;
-use crate::parser::parse_row;
+use crate::filter::{Expr, Filter};
 use crate::reader::CsvReader;
+use crate::record::Record;
+
+/// Rows searched per parallel batch, so `max_results` can stop the scan early.
+const CHUNK: usize = 64 * 1024;
 
 /// A single search result.
 #[derive(Debug, Clone)]
 pub struct SearchResult {
     pub row_num: usize,
     pub fields: Vec<String>,
+    /// Where the query occurs in `fields`, in column order. Empty for an
+    /// empty query, or when the query only occurs across a delimiter.
+    pub matches: Vec<FieldMatch>,
+}
+
+/// One occurrence of the query in a field of a [`SearchResult`], for
+/// highlighting it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldMatch {
+    /// Index of the field.
+    pub column: usize,
+    /// Character (not byte) offsets of the occurrence in the field's value.
+    pub start: usize,
+    pub end: usize,
+}
+
+/// A page of [`search`] results, with the number of matches in the whole
+/// file, as returned by [`search_page`].
+#[derive(Debug, Clone)]
+pub struct SearchPage {
+    /// The matches `offset` and `max_results` select.
+    pub results: Vec<SearchResult>,
+    /// Every matching row, however many were returned.
+    pub total: usize,
 }
 
 /// Options controlling how search is performed.
@@ -20,10 +51,25 @@ pub struct SearchOptions {
     pub case_insensitive: bool,
     /// Stop after finding this many results (0 = unlimited).
     pub max_results: usize,
+    /// Skip this many matches first, to page through them `max_results`
+    /// at a time.
+    pub offset: usize,
+    /// Only return rows that also match this filter expression, evaluated
+    /// as by [`Filter`]. An empty query with a filter returns every row
+    /// the filter matches.
+    pub filter: Option<Expr>,
+    /// Only search these rows (default: all), so a caller can scan a file
+    /// a part at a time. [`find_next`] ignores it.
+    pub rows: Option<Range<usize>>,
+    /// Told how many rows [`search_each`] has scanned as it goes.
+    pub progress: Progress,
 }
 
 /// Search the CSV for rows matching the query string.
 ///
+/// Results are in row order, and with `max_results` they are always the first
+/// matching rows, however the parallel scan is scheduled.
+///
 /// Strategy: pre-filter on raw text (fast) before parsing fields (slow).
 /// For column-specific searches, we still pre-filter on raw text, then
 /// verify the match is in the target column after parsing.
@@ -32,70 +78,379 @@ pub fn search(
     query: &str,
     options: &SearchOptions,
 ) -> Result<Vec<SearchResult>> {
-    let column_index = if let Some(ref col_name) = options.column {
-        let idx = reader
-            .headers()
-            .iter()
-            .position(|h| h == col_name)
-            .ok_or_else(|| crate::error::MassiveCsvError::ColumnNotFound(col_name.clone()))?;
-        Some(idx)
-    } else {
-        None
-    };
-
-    let query_lower = if options.case_insensitive {
-        query.to_lowercase()
-    } else {
-        query.to_string()
-    };
+    let mut results = Vec::new();
+    search_each(reader, query, options, &mut |result| {
+        results.push(result);
+        Ok(())
+    })?;
+    Ok(results)
+}
+
+/// [`search`], passing each match to `visit` as soon as the batch of rows
+/// it is in has been scanned, rather than collecting them all. Matches
+/// arrive in row order; an error from `visit` stops the scan. Returns the
+/// number of matches passed to `visit`.
+pub fn search_each(
+    reader: &CsvReader,
+    query: &str,
+    options: &SearchOptions,
+    visit: &mut dyn FnMut(SearchResult) -> Result<()>,
+) -> Result<usize> {
+    search_batches(reader, query, options, &mut |batch| {
+        batch.into_iter().try_for_each(&mut *visit)
+    })
+}
+
+/// [`search_each`], passing the matches of each batch of rows scanned to
+/// `visit` at once, for sending them across a thread or process boundary in
+/// as few messages as possible. Batches are never empty.
+pub fn search_batches(
+    reader: &CsvReader,
+    query: &str,
+    options: &SearchOptions,
+    visit: &mut dyn FnMut(Vec<SearchResult>) -> Result<()>,
+) -> Result<usize> {
+    scan(reader, query, options, false, visit).map(|(found, _)| found)
+}
+
+/// [`search`], also counting the matches past the page it returns, so a
+/// UI can show "1,234 matches" while listing the first few.
+pub fn search_page(reader: &CsvReader, query: &str, options: &SearchOptions) -> Result<SearchPage> {
+    let mut results = Vec::new();
+    let (_, total) = scan(reader, query, options, true, &mut |batch| {
+        results.extend(batch);
+        Ok(())
+    })?;
+    Ok(SearchPage { results, total })
+}
+
+/// Pass the matches `options` select to `visit`, a batch per chunk with any,
+/// returning how many were passed and how many rows matched in all. Unless
+/// `count_all`, the scan stops once `max_results` have been passed, and the
+/// total is only of the rows scanned.
+fn scan(
+    reader: &CsvReader,
+    query: &str,
+    options: &SearchOptions,
+    count_all: bool,
+    visit: &mut dyn FnMut(Vec<SearchResult>) -> Result<()>,
+) -> Result<(usize, usize)> {
+    let matcher = Matcher::new(reader, query, options)?;
+    let rows = options.rows.clone().unwrap_or(0..reader.row_count());
+    let mut tally = Tally::default();
+    scan_rows(&matcher, rows, options, count_all, &mut tally, visit)?;
+    Ok((tally.found, tally.skipped + tally.found + tally.extra))
+}
+
+/// Matches counted so far by [`scan_rows`], carried across calls.
+#[derive(Debug, Default)]
+struct Tally {
+    /// Skipped for `offset`.
+    skipped: usize,
+    /// Passed to `visit`.
+    found: usize,
+    /// Past `max_results`, counted only with `count_all`.
+    extra: usize,
+}
 
+/// The chunk loop of [`scan`] over `rows`, cut to the file. Returns whether
+/// it stopped because `max_results` have been passed.
+fn scan_rows(
+    matcher: &Matcher,
+    rows: Range<usize>,
+    options: &SearchOptions,
+    count_all: bool,
+    tally: &mut Tally,
+    visit: &mut dyn FnMut(Vec<SearchResult>) -> Result<()>,
+) -> Result<bool> {
+    let row_count = matcher.reader.row_count();
+    let last = rows.end.min(row_count);
+
+    // Scan in chunks so a result limit can stop early; rayon's indexed collect
+    // keeps each chunk in row order
+    let mut start = rows.start;
+    while start < last {
+        let end = (start + CHUNK).min(last);
+        let chunk: Vec<SearchResult> = (start..end)
+            .into_par_iter()
+            .filter_map(|row_num| matcher.check(row_num))
+            .collect();
+
+        let mut batch = Vec::new();
+        let mut full = false;
+        for result in chunk {
+            if tally.skipped < options.offset {
+                tally.skipped += 1;
+                continue;
+            }
+            if options.max_results > 0 && tally.found == options.max_results {
+                if !count_all {
+                    full = true;
+                    break;
+                }
+                tally.extra += 1;
+                continue;
+            }
+            batch.push(result);
+            tally.found += 1;
+        }
+        if !batch.is_empty() {
+            visit(batch)?;
+        }
+        if full {
+            return Ok(true);
+        }
+        options.progress.update(end, row_count)?;
+        start = end;
+    }
+    Ok(false)
+}
+
+/// [`search_batches`] run a part of the file at a time, for a caller that
+/// has to let go of the reader between parts, e.g. so other work on the
+/// same document can run. `offset` and `max_results` apply across parts,
+/// and the progress reporter is told after each chunk, as by [`search`].
+#[derive(Debug)]
+pub struct SearchScan {
+    query: String,
+    options: SearchOptions,
+    /// Next row to scan.
+    next: usize,
+    tally: Tally,
+}
+
+impl SearchScan {
+    pub fn new(query: &str, options: &SearchOptions) -> Self {
+        Self {
+            query: query.to_string(),
+            next: options.rows.as_ref().map_or(0, |rows| rows.start),
+            options: options.clone(),
+            tally: Tally::default(),
+        }
+    }
+
+    /// Scan up to `rows` more rows of `reader`, passing the matches of each
+    /// chunk to `visit`. Returns whether there is more to scan.
+    pub fn step(
+        &mut self,
+        reader: &CsvReader,
+        rows: usize,
+        visit: &mut dyn FnMut(Vec<SearchResult>) -> Result<()>,
+    ) -> Result<bool> {
+        let last = self
+            .options
+            .rows
+            .as_ref()
+            .map_or(usize::MAX, |rows| rows.end)
+            .min(reader.row_count());
+        let end = self.next.saturating_add(rows).min(last);
+        let matcher = Matcher::new(reader, &self.query, &self.options)?;
+        let full = scan_rows(
+            &matcher,
+            self.next..end,
+            &self.options,
+            false,
+            &mut self.tally,
+            visit,
+        )?;
+        self.next = end;
+        let limit = self.options.max_results;
+        Ok(!full && end < last && (limit == 0 || self.tally.found < limit))
+    }
+
+    /// Matches passed to `visit` so far.
+    pub fn found(&self) -> usize {
+        self.tally.found
+    }
+}
+
+/// The first match at or after row `from`, wrapping around to the start of
+/// the file, for "find next". `max_results` and `offset` are ignored.
+pub fn find_next(
+    reader: &CsvReader,
+    query: &str,
+    options: &SearchOptions,
+    from: usize,
+) -> Result<Option<SearchResult>> {
+    let matcher = Matcher::new(reader, query, options)?;
     let row_count = reader.row_count();
+    let from = from.min(row_count);
+    for (first, last) in [(from, row_count), (0, from)] {
+        let mut start = first;
+        while start < last {
+            let end = (start + CHUNK).min(last);
+            let result = (start..end)
+                .into_par_iter()
+                .find_map_first(|row_num| matcher.check(row_num));
+            if result.is_some() {
+                return Ok(result);
+            }
+            start = end;
+        }
+    }
+    Ok(None)
+}
+
+/// The rows to show for matches at `rows` (in row order) with `before`
+/// and `after` rows of context each, as grep's `-B`/`-A` do: one range per
+/// group of overlapping or adjacent windows, cut to `row_count`.
+pub fn context_windows(
+    rows: &[usize],
+    before: usize,
+    after: usize,
+    row_count: usize,
+) -> Vec<Range<usize>> {
+    let mut windows: Vec<Range<usize>> = Vec::new();
+    for &row in rows {
+        let window = row.saturating_sub(before)..row.saturating_add(after + 1).min(row_count);
+        match windows.last_mut() {
+            Some(last) if window.start <= last.end => last.end = last.end.max(window.end),
+            _ => windows.push(window),
+        }
+    }
+    windows
+}
+
+/// A query and [`SearchOptions`] resolved against a file's headers.
+struct Matcher<'a> {
+    reader: &'a CsvReader,
+    query: &'a str,
+    query_lower: String,
+    case_insensitive: bool,
+    column_index: Option<usize>,
+    filter: Option<Filter>,
+}
 
-    // Collect raw rows with indices so we can use rayon
-    // For very large files, we process in chunks to allow early termination
-    let results: Vec<SearchResult> = (0..row_count)
-        .into_par_iter()
-        .filter_map(|row_num| {
-            let raw = reader.get_row_raw(row_num).ok()?;
+impl<'a> Matcher<'a> {
+    fn new(reader: &'a CsvReader, query: &'a str, options: &SearchOptions) -> Result<Self> {
+        let column_index = if let Some(ref col_name) = options.column {
+            let idx = reader
+                .column_index(col_name)
+                .ok_or_else(|| crate::error::MassiveCsvError::ColumnNotFound(col_name.clone()))?;
+            Some(idx)
+        } else {
+            None
+        };
+
+        let filter = match &options.filter {
+            Some(expr) => Some(Filter::new(expr.clone(), reader.headers())?),
+            None => None,
+        };
+
+        let query_lower = if options.case_insensitive {
+            query.to_lowercase()
+        } else {
+            query.to_string()
+        };
+
+        Ok(Self {
+            reader,
+            query,
+            query_lower,
+            case_insensitive: options.case_insensitive,
+            column_index,
+            filter,
+        })
+    }
+
+    /// Row `row_num`, if it matches.
+    fn check(&self, row_num: usize) -> Option<SearchResult> {
+        let raw = self.reader.get_row_text(row_num).ok()?;
+
+        // Pre-filter: quick check if query appears in the raw line at all
+        let matches_raw = if self.case_insensitive {
+            raw.to_lowercase().contains(&self.query_lower)
+        } else {
+            raw.contains(self.query)
+        };
 
-            // Pre-filter: quick check if query appears in the raw line at all
-            let matches_raw = if options.case_insensitive {
-                raw.to_lowercase().contains(&query_lower)
+        if !matches_raw {
+            return None;
+        }
+
+        // Parse fields for column-specific check or to return
+        let fields = self.reader.parse_raw(&raw).ok()?;
+
+        if let Some(col_idx) = self.column_index {
+            let field = fields.get(col_idx)?;
+            let matches_field = if self.case_insensitive {
+                field.to_lowercase().contains(&self.query_lower)
             } else {
-                raw.contains(query)
+                field.contains(self.query)
             };
-
-            if !matches_raw {
+            if !matches_field {
                 return None;
             }
+        }
+        if self
+            .filter
+            .as_ref()
+            .is_some_and(|filter| !filter.matches(&fields))
+        {
+            return None;
+        }
 
-            // Parse fields for column-specific check or to return
-            let fields = parse_row(raw, reader.delimiter()).ok()?;
-
-            if let Some(col_idx) = column_index {
-                let field = fields.get(col_idx)?;
-                let matches_field = if options.case_insensitive {
-                    field.to_lowercase().contains(&query_lower)
-                } else {
-                    field.contains(query)
-                };
-                if !matches_field {
-                    return None;
-                }
-            }
-
-            Some(SearchResult { row_num, fields })
+        let matches = match self.column_index {
+            Some(col_idx) => self.find_in(col_idx, &fields[col_idx]),
+            None => fields
+                .iter()
+                .enumerate()
+                .flat_map(|(col, field)| self.find_in(col, field))
+                .collect(),
+        };
+        Some(SearchResult {
+            row_num,
+            fields,
+            matches,
         })
-        .collect();
+    }
 
-    // Apply max_results after parallel collection (rayon doesn't support early exit cleanly)
-    if options.max_results > 0 && results.len() > options.max_results {
-        Ok(results.into_iter().take(options.max_results).collect())
-    } else {
-        Ok(results)
+    /// The non-overlapping occurrences of the query in `value`, field
+    /// `column` of a row.
+    fn find_in(&self, column: usize, value: &str) -> Vec<FieldMatch> {
+        if self.query.is_empty() {
+            return Vec::new();
+        }
+        // Search the value as compared (lowercased, which can change its
+        // length) and map each byte of that back to a character of `value`
+        let mut compared = String::with_capacity(value.len());
+        let mut char_of = Vec::with_capacity(value.len());
+        for (i, c) in value.chars().enumerate() {
+            if self.case_insensitive {
+                compared.extend(c.to_lowercase());
+            } else {
+                compared.push(c);
+            }
+            char_of.resize(compared.len(), i);
+        }
+        let query = if self.case_insensitive {
+            self.query_lower.as_str()
+        } else {
+            self.query
+        };
+        compared
+            .match_indices(query)
+            .map(|(at, found)| FieldMatch {
+                column,
+                start: char_of[at],
+                end: char_of[at + found.len() - 1] + 1,
+            })
+            .collect()
     }
 }
 
+/// [`search`], returning each match as a [`Record`].
+pub fn search_records(
+    reader: &CsvReader,
+    query: &str,
+    options: &SearchOptions,
+) -> Result<Vec<Record>> {
+    Ok(search(reader, query, options)?
+        .into_iter()
+        .map(|r| reader.record(r.row_num, r.fields))
+        .collect())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -160,6 +515,237 @@ mod tests {
         assert_eq!(results.len(), 2);
     }
 
+    #[test]
+    fn search_pages_with_offset() {
+        let f = make_csv("v\na\nb\na\na\nb\na\n");
+        let reader = CsvReader::open(f.path()).unwrap();
+        let page = |offset| {
+            let opts = SearchOptions {
+                max_results: 2,
+                offset,
+                ..Default::default()
+            };
+            search(&reader, "a", &opts)
+                .unwrap()
+                .iter()
+                .map(|r| r.row_num)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(page(0), vec![0, 2]);
+        assert_eq!(page(2), vec![3, 5]);
+        assert!(page(4).is_empty());
+    }
+
+    #[test]
+    fn search_each_streams_matches() {
+        let f = make_csv("v\na\nb\na\na\n");
+        let reader = CsvReader::open(f.path()).unwrap();
+
+        let mut rows = Vec::new();
+        let found = search_each(&reader, "a", &SearchOptions::default(), &mut |r| {
+            rows.push(r.row_num);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!((found, rows), (3, vec![0, 2, 3]));
+
+        let mut seen = 0;
+        let result = search_each(&reader, "a", &SearchOptions::default(), &mut |_| {
+            seen += 1;
+            Err(crate::error::MassiveCsvError::Parse("stop".to_string()))
+        });
+        assert!(result.is_err());
+        assert_eq!(seen, 1);
+    }
+
+    #[test]
+    fn search_reports_where_the_query_matched() {
+        let f = make_csv("name,city\nAnna,Savannah\nBob,LA\nÉtienne,Nantes\n");
+        let reader = CsvReader::open(f.path()).unwrap();
+        let at = |m: &FieldMatch| (m.column, m.start, m.end);
+
+        let results = search(&reader, "an", &SearchOptions::default()).unwrap();
+        assert_eq!(
+            results[0].matches.iter().map(at).collect::<Vec<_>>(),
+            vec![(1, 3, 5)]
+        );
+        assert_eq!(
+            results[1].matches.iter().map(at).collect::<Vec<_>>(),
+            vec![(1, 1, 3)]
+        );
+
+        let opts = SearchOptions {
+            case_insensitive: true,
+            ..Default::default()
+        };
+        let results = search(&reader, "AN", &opts).unwrap();
+        let found: Vec<_> = results[0].matches.iter().map(at).collect();
+        assert_eq!(found, vec![(0, 0, 2), (1, 3, 5)]);
+        // Offsets count characters, not bytes
+        let results = search(&reader, "ienne", &opts).unwrap();
+        assert_eq!(
+            results[0].matches.iter().map(at).collect::<Vec<_>>(),
+            vec![(0, 2, 7)]
+        );
+
+        let opts = SearchOptions {
+            column: Some("city".to_string()),
+            case_insensitive: true,
+            ..Default::default()
+        };
+        let results = search(&reader, "an", &opts).unwrap();
+        assert!(results
+            .iter()
+            .all(|r| r.matches.iter().all(|m| m.column == 1)));
+    }
+
+    #[test]
+    fn search_batches_sends_a_batch_per_chunk() {
+        let rows = CHUNK + 100;
+        let f = make_csv(&format!("v\n{}", "a\nb\n".repeat(rows / 2)));
+        let reader = CsvReader::open(f.path()).unwrap();
+
+        let mut sizes = Vec::new();
+        let found = search_batches(&reader, "a", &SearchOptions::default(), &mut |batch| {
+            sizes.push(batch.len());
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(found, rows / 2);
+        assert_eq!(sizes, vec![CHUNK / 2, 50]);
+
+        let opts = SearchOptions {
+            max_results: 10,
+            ..Default::default()
+        };
+        let mut sizes = Vec::new();
+        search_batches(&reader, "a", &opts, &mut |batch| {
+            sizes.push(batch.len());
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(sizes, vec![10]);
+    }
+
+    #[test]
+    fn search_scan_in_parts_matches_one_search() {
+        let f = make_csv("v\na\nb\na\na\nb\na\na\n");
+        let reader = CsvReader::open(f.path()).unwrap();
+        let opts = SearchOptions {
+            max_results: 3,
+            offset: 1,
+            ..Default::default()
+        };
+        let whole: Vec<usize> = search(&reader, "a", &opts)
+            .unwrap()
+            .iter()
+            .map(|r| r.row_num)
+            .collect();
+
+        let mut scan = SearchScan::new("a", &opts);
+        let mut rows = Vec::new();
+        let mut steps = 0;
+        loop {
+            steps += 1;
+            let more = scan
+                .step(&reader, 2, &mut |batch| {
+                    rows.extend(batch.iter().map(|r| r.row_num));
+                    Ok(())
+                })
+                .unwrap();
+            if !more {
+                break;
+            }
+        }
+        assert_eq!(rows, whole);
+        assert_eq!(rows, vec![2, 3, 5]);
+        assert_eq!(scan.found(), 3);
+        assert_eq!(steps, 3);
+    }
+
+    #[test]
+    fn search_within_rows() {
+        let f = make_csv("v\na\nb\na\na\nb\na\n");
+        let reader = CsvReader::open(f.path()).unwrap();
+        let rows = |range| {
+            let opts = SearchOptions {
+                rows: Some(range),
+                ..Default::default()
+            };
+            search(&reader, "a", &opts)
+                .unwrap()
+                .iter()
+                .map(|r| r.row_num)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(rows(1..4), vec![2, 3]);
+        assert_eq!(rows(4..100), vec![5]);
+        assert!(rows(7..9).is_empty());
+    }
+
+    #[test]
+    fn search_page_counts_every_match() {
+        let f = make_csv("v\na\nb\na\na\nb\na\n");
+        let reader = CsvReader::open(f.path()).unwrap();
+
+        let opts = SearchOptions {
+            max_results: 2,
+            offset: 1,
+            ..Default::default()
+        };
+        let page = search_page(&reader, "a", &opts).unwrap();
+        assert_eq!(page.total, 4);
+        let rows: Vec<usize> = page.results.iter().map(|r| r.row_num).collect();
+        assert_eq!(rows, vec![2, 3]);
+        assert_eq!(search_page(&reader, "z", &opts).unwrap().total, 0);
+    }
+
+    #[test]
+    fn find_next_wraps_around() {
+        let f = make_csv("v\na\nb\na\nc\n");
+        let reader = CsvReader::open(f.path()).unwrap();
+        let options = SearchOptions::default();
+
+        let next = |from| find_next(&reader, "a", &options, from).unwrap();
+        assert_eq!(next(0).map(|r| r.row_num), Some(0));
+        assert_eq!(next(1).map(|r| r.row_num), Some(2));
+        assert_eq!(next(3).map(|r| r.row_num), Some(0));
+        assert!(find_next(&reader, "z", &options, 2).unwrap().is_none());
+    }
+
+    #[test]
+    fn context_windows_merge() {
+        assert_eq!(context_windows(&[1, 3, 9], 1, 1, 10), vec![0..5, 8..10]);
+        assert_eq!(context_windows(&[0, 2], 0, 0, 5), vec![0..1, 2..3]);
+        assert_eq!(context_windows(&[0, 1], 0, 0, 5), vec![0..2]);
+        assert_eq!(context_windows(&[4], 2, 3, 5), vec![2..5]);
+        assert!(context_windows(&[], 1, 1, 5).is_empty());
+    }
+
+    #[test]
+    fn search_with_filter() {
+        let f = make_csv("name,status,amount\nann,open,5\nbob,open,500\nann,closed,700\n");
+        let reader = CsvReader::open(f.path()).unwrap();
+
+        let opts = SearchOptions {
+            filter: Some(Expr::parse("status == \"open\" && amount > 100").unwrap()),
+            ..Default::default()
+        };
+        let rows: Vec<usize> = search(&reader, "", &opts)
+            .unwrap()
+            .iter()
+            .map(|r| r.row_num)
+            .collect();
+        assert_eq!(rows, vec![1]);
+        assert!(search(&reader, "ann", &opts).unwrap().is_empty());
+
+        let opts = SearchOptions {
+            filter: Some(Expr::parse("missing == 1").unwrap()),
+            ..Default::default()
+        };
+        assert!(search(&reader, "ann", &opts).is_err());
+    }
+
     #[test]
     fn search_column_not_found() {
         let f = make_csv("name\nAlice\n");
@@ -172,4 +758,36 @@ mod tests {
         let result = search(&reader, "x", &opts);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn results_are_in_row_order_on_any_pool() {
+        let mut content = String::from("id,tag\n");
+        for i in 0..150_000 {
+            let tag = if i % 997 == 0 { "hit" } else { "miss" };
+            content.push_str(&format!("{i},{tag}\n"));
+        }
+        let f = make_csv(&content);
+        let reader = CsvReader::open(f.path()).unwrap();
+
+        let run = |threads: usize, max_results: usize| {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            let opts = SearchOptions {
+                max_results,
+                ..Default::default()
+            };
+            pool.install(|| search(&reader, "hit", &opts).unwrap())
+                .into_iter()
+                .map(|r| r.row_num)
+                .collect::<Vec<_>>()
+        };
+
+        let all = run(1, 0);
+        assert_eq!(all, (0..150_000).step_by(997).collect::<Vec<_>>());
+        assert_eq!(run(4, 0), all);
+        assert_eq!(run(4, 3), vec![0, 997, 1994]);
+        assert_eq!(run(4, 100), all[..100]);
+    }
 }
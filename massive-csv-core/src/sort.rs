@@ -0,0 +1,418 @@
+//! Sorting a file by one or more columns.
+//!
+//! Rows are sorted in runs of at most [`SortOptions::memory_limit`] rows,
+//! holding only their keys in memory and reading lines from the mapped file.
+//! A file that fits in one run is written straight from the map; otherwise
+//! each sorted run is written to a temporary file and the runs are merged.
+
+use std::cmp::Ordering;
+use std::io::{BufReader, BufWriter, Read, Seek, Write};
+use std::path::Path;
+
+use rayon::prelude::*;
+
+use crate::editor::{write_atomic, LineWriter};
+use crate::error::{MassiveCsvError, Result};
+use crate::infer::{infer_schema, ColumnType, DEFAULT_SAMPLE_ROWS};
+use crate::reader::CsvReader;
+
+/// How a sort key's values compare.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeyKind {
+    /// Numeric if a sample of the column is all numbers, text otherwise.
+    #[default]
+    Auto,
+    /// As numbers; values that aren't sort after those that are.
+    Numeric,
+    /// Byte by byte. ISO dates sort correctly this way.
+    Text,
+}
+
+/// One column to sort by.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortKey {
+    pub column: String,
+    pub descending: bool,
+    pub kind: KeyKind,
+}
+
+impl SortKey {
+    /// Ascending, compared as [`KeyKind::Auto`].
+    pub fn new(column: impl Into<String>) -> Self {
+        Self {
+            column: column.into(),
+            descending: false,
+            kind: KeyKind::Auto,
+        }
+    }
+
+    /// Parse `column[:asc|:desc][:num|:text]`, e.g. `date:desc` or
+    /// `amount:num`.
+    pub fn parse(spec: &str) -> Result<Self> {
+        let mut parts = spec.split(':');
+        let mut key = SortKey::new(parts.next().unwrap_or_default());
+        if key.column.is_empty() {
+            return Err(MassiveCsvError::Parse(format!(
+                "empty sort key in '{spec}'"
+            )));
+        }
+        for modifier in parts {
+            match modifier.to_ascii_lowercase().as_str() {
+                "asc" => key.descending = false,
+                "desc" => key.descending = true,
+                "num" | "numeric" => key.kind = KeyKind::Numeric,
+                "text" => key.kind = KeyKind::Text,
+                other => {
+                    return Err(MassiveCsvError::Parse(format!(
+                        "unknown sort modifier '{other}' in '{spec}'"
+                    )))
+                }
+            }
+        }
+        Ok(key)
+    }
+}
+
+/// Options controlling [`sort`].
+#[derive(Debug, Clone)]
+pub struct SortOptions {
+    /// Columns to sort by, most significant first.
+    pub keys: Vec<SortKey>,
+    /// Rows sorted in memory at a time; longer files are sorted in runs
+    /// spilled to disk and merged.
+    pub memory_limit: usize,
+}
+
+impl Default for SortOptions {
+    fn default() -> Self {
+        Self {
+            keys: Vec::new(),
+            memory_limit: 8 * 1024 * 1024,
+        }
+    }
+}
+
+/// What [`sort`] did.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SortSummary {
+    pub rows_written: usize,
+    /// Sorted runs written to disk (0 when the file fit in memory).
+    pub runs: usize,
+}
+
+/// Write the reader's rows to `output` (which may be the same file) sorted
+/// by `options.keys`. The sort is stable and empty values sort last in
+/// either direction; rows are copied byte-for-byte.
+pub fn sort(reader: &CsvReader, output: &Path, options: &SortOptions) -> Result<SortSummary> {
+    let sorter = Sorter::new(reader, &options.keys)?;
+
+    let rows = reader.row_count();
+    let run_rows = options.memory_limit.max(1);
+    let mut summary = SortSummary {
+        rows_written: rows,
+        runs: 0,
+    };
+
+    if rows <= run_rows {
+        let order = sorter.sorted_rows(reader, (0..rows).collect())?;
+        write_atomic(output, |writer| {
+            let mut lines = Lines::new(writer, reader)?;
+            for (_, row) in order {
+                lines.write(reader.get_row_bytes(row)?)?;
+            }
+            lines.finish()
+        })?;
+        return Ok(summary);
+    }
+
+    // Sorted runs of length-prefixed lines
+    let mut runs = Vec::new();
+    let mut start = 0;
+    while start < rows {
+        let end = (start + run_rows).min(rows);
+        let mut run = BufWriter::new(tempfile::tempfile()?);
+        for (_, row) in sorter.sorted_rows(reader, (start..end).collect())? {
+            let line = reader.get_row_bytes(row)?;
+            run.write_all(&(line.len() as u64).to_le_bytes())?;
+            run.write_all(line)?;
+        }
+        let mut run = run.into_inner().map_err(|e| e.into_error())?;
+        run.rewind()?;
+        runs.push(BufReader::new(run));
+        start = end;
+    }
+    summary.runs = runs.len();
+
+    write_atomic(output, |writer| {
+        let mut lines = Lines::new(writer, reader)?;
+        // The next line of each run with its key; ties go to the earlier run
+        let mut heads: Vec<Option<(Vec<Value>, Vec<u8>)>> = runs
+            .iter_mut()
+            .map(|run| sorter.next_line(reader, run))
+            .collect::<Result<_>>()?;
+        loop {
+            let mut best: Option<usize> = None;
+            for (i, head) in heads.iter().enumerate() {
+                let Some((key, _)) = head else { continue };
+                let better = match best.and_then(|b| heads[b].as_ref()) {
+                    Some((best_key, _)) => sorter.compare(key, best_key) == Ordering::Less,
+                    None => true,
+                };
+                if better {
+                    best = Some(i);
+                }
+            }
+            let Some(i) = best else { break };
+            let (_, line) = heads[i].take().expect("chosen run has a line");
+            lines.write(&line)?;
+            heads[i] = sorter.next_line(reader, &mut runs[i])?;
+        }
+        lines.finish()
+    })?;
+    Ok(summary)
+}
+
+/// `rows` of the reader's file ordered by `keys`, as [`sort`] would
+/// order them.
+pub(crate) fn sort_rows(
+    reader: &CsvReader,
+    rows: Vec<usize>,
+    keys: &[SortKey],
+) -> Result<Vec<usize>> {
+    let sorter = Sorter::new(reader, keys)?;
+    Ok(sorter
+        .sorted_rows(reader, rows)?
+        .into_iter()
+        .map(|(_, row)| row)
+        .collect())
+}
+
+/// A sort key value.
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Number(f64),
+    Text(String),
+    Empty,
+}
+
+struct Sorter {
+    /// (column, numeric, descending)
+    keys: Vec<(usize, bool, bool)>,
+}
+
+impl Sorter {
+    /// Resolve `keys` against the reader's columns, inferring the kind of
+    /// [`KeyKind::Auto`] keys from a sample.
+    fn new(reader: &CsvReader, keys: &[SortKey]) -> Result<Self> {
+        if keys.is_empty() {
+            return Err(MassiveCsvError::Parse("no sort keys".to_string()));
+        }
+        let schema = if keys.iter().any(|k| k.kind == KeyKind::Auto) {
+            infer_schema(reader, DEFAULT_SAMPLE_ROWS)?
+        } else {
+            Vec::new()
+        };
+        let keys = keys
+            .iter()
+            .map(|key| {
+                let column = reader
+                    .column_index(&key.column)
+                    .ok_or_else(|| MassiveCsvError::ColumnNotFound(key.column.clone()))?;
+                let numeric = match key.kind {
+                    KeyKind::Numeric => true,
+                    KeyKind::Text => false,
+                    KeyKind::Auto => matches!(
+                        schema.get(column).map(|c| c.column_type),
+                        Some(ColumnType::Integer | ColumnType::Float)
+                    ),
+                };
+                Ok((column, numeric, key.descending))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { keys })
+    }
+
+    fn key(&self, fields: &[String]) -> Vec<Value> {
+        self.keys
+            .iter()
+            .map(|&(column, numeric, _)| {
+                let field = fields.get(column).map_or("", String::as_str);
+                if field.is_empty() {
+                    return Value::Empty;
+                }
+                match field.trim().parse::<f64>() {
+                    Ok(number) if numeric && !number.is_nan() => Value::Number(number),
+                    _ => Value::Text(field.to_string()),
+                }
+            })
+            .collect()
+    }
+
+    fn compare(&self, a: &[Value], b: &[Value]) -> Ordering {
+        for ((a, b), &(_, _, descending)) in a.iter().zip(b).zip(&self.keys) {
+            let ordering = match (a, b) {
+                (Value::Empty, Value::Empty) => Ordering::Equal,
+                (Value::Empty, _) => Ordering::Greater,
+                (_, Value::Empty) => Ordering::Less,
+                (a, b) => {
+                    let ordering = match (a, b) {
+                        (Value::Number(a), Value::Number(b)) => a.total_cmp(b),
+                        (Value::Number(_), _) => Ordering::Less,
+                        (_, Value::Number(_)) => Ordering::Greater,
+                        (Value::Text(a), Value::Text(b)) => a.cmp(b),
+                        _ => Ordering::Equal,
+                    };
+                    if descending {
+                        ordering.reverse()
+                    } else {
+                        ordering
+                    }
+                }
+            };
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+        Ordering::Equal
+    }
+
+    /// `rows` with their keys, sorted stably.
+    fn sorted_rows(
+        &self,
+        reader: &CsvReader,
+        rows: Vec<usize>,
+    ) -> Result<Vec<(Vec<Value>, usize)>> {
+        let mut rows: Vec<(Vec<Value>, usize)> = rows
+            .into_par_iter()
+            .map(|row| Ok((self.key(&reader.get_row(row)?), row)))
+            .collect::<Result<_>>()?;
+        // Stable, so equal keys keep the order of `rows`
+        rows.par_sort_by(|a, b| self.compare(&a.0, &b.0));
+        Ok(rows)
+    }
+
+    fn next_line(
+        &self,
+        reader: &CsvReader,
+        run: &mut impl Read,
+    ) -> Result<Option<(Vec<Value>, Vec<u8>)>> {
+        let mut len = [0u8; 8];
+        match run.read_exact(&mut len) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+        let mut line = vec![0u8; u64::from_le_bytes(len) as usize];
+        run.read_exact(&mut line)?;
+        let fields = reader.parse_raw(&String::from_utf8_lossy(&line))?;
+        Ok(Some((self.key(&fields), line)))
+    }
+}
+
+/// Writes the header and then raw lines in the reader's line ending.
+struct Lines<'a> {
+    lines: LineWriter<'a>,
+    trailing_newline: bool,
+}
+
+impl<'a> Lines<'a> {
+    fn new(writer: &'a mut dyn Write, reader: &CsvReader) -> Result<Self> {
+        let mut lines = LineWriter::new(writer, reader.line_ending());
+        lines.write_line(reader.header_raw()?)?;
+        Ok(Self {
+            lines,
+            trailing_newline: reader.has_trailing_newline(),
+        })
+    }
+
+    fn write(&mut self, line: &[u8]) -> Result<()> {
+        self.lines.write_line(&String::from_utf8_lossy(line))
+    }
+
+    fn finish(self) -> Result<()> {
+        self.lines.finish(self.trailing_newline)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn make_csv(content: &str) -> tempfile::NamedTempFile {
+        let mut f = tempfile::NamedTempFile::new().unwrap();
+        f.write_all(content.as_bytes()).unwrap();
+        f.flush().unwrap();
+        f
+    }
+
+    const SALES: &str = "date,amount,note\n\
+        2024-02-01,10,a\n\
+        2024-01-15,9,b\n\
+        2024-02-01,100,c\n\
+        ,5,d\n\
+        2024-01-15,9,e\n";
+
+    fn sorted(csv: &str, keys: &[&str], memory_limit: usize) -> (String, SortSummary) {
+        let f = make_csv(csv);
+        let reader = CsvReader::open(f.path()).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("sorted.csv");
+        let options = SortOptions {
+            keys: keys.iter().map(|k| SortKey::parse(k).unwrap()).collect(),
+            memory_limit,
+        };
+        let summary = sort(&reader, &output, &options).unwrap();
+        (std::fs::read_to_string(&output).unwrap(), summary)
+    }
+
+    fn notes(csv: &str) -> String {
+        csv.lines()
+            .skip(1)
+            .map(|l| l.rsplit(',').next().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn sorts_by_several_keys() {
+        let (csv, summary) = sorted(SALES, &["date:desc", "amount:asc"], usize::MAX);
+        assert!(csv.starts_with("date,amount,note\n"));
+        // Empty dates last; amounts numerically; ties stable
+        assert_eq!(notes(&csv), "acbed");
+        assert_eq!(
+            summary,
+            SortSummary {
+                rows_written: 5,
+                runs: 0,
+            }
+        );
+
+        // Auto-detected numbers: 9 < 10 < 100, unlike text order
+        let (csv, _) = sorted(SALES, &["amount"], usize::MAX);
+        assert_eq!(notes(&csv), "dbeac");
+        let (csv, _) = sorted(SALES, &["amount:text"], usize::MAX);
+        assert_eq!(notes(&csv), "acdbe");
+    }
+
+    #[test]
+    fn merges_runs_from_disk() {
+        let (in_memory, _) = sorted(SALES, &["date:desc", "amount"], usize::MAX);
+        let (merged, summary) = sorted(SALES, &["date:desc", "amount"], 2);
+        assert_eq!(summary.runs, 3);
+        assert_eq!(merged, in_memory);
+    }
+
+    #[test]
+    fn parses_sort_keys() {
+        assert_eq!(
+            SortKey::parse("amount:num:desc").unwrap(),
+            SortKey {
+                column: "amount".to_string(),
+                descending: true,
+                kind: KeyKind::Numeric,
+            }
+        );
+        assert!(SortKey::parse("amount:sideways").is_err());
+        assert!(SortKey::parse(":desc").is_err());
+    }
+}
@@ -0,0 +1,312 @@
+//! Splitting a file into smaller ones, by row count or by a column's value.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use rayon::prelude::*;
+use tempfile::{NamedTempFile, TempPath};
+
+use crate::error::{MassiveCsvError, Result};
+use crate::reader::CsvReader;
+
+/// Most output files written at once when splitting by column; columns with
+/// more distinct values take one pass over the file per batch.
+const MAX_OPEN_PARTS: usize = 256;
+
+/// Rows whose values are parsed per parallel batch.
+const SCAN_CHUNK: usize = 64 * 1024;
+
+/// One file written by [`split_rows`] or [`split_by_column`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplitPart {
+    pub path: PathBuf,
+    pub rows: usize,
+    /// The column value of the part's rows, when splitting by column.
+    pub value: Option<String>,
+}
+
+/// Write the reader's rows to `output_dir` in files of `rows_per_file` rows
+/// each, named `<stem>-0001.<ext>` and up after the input file. Every file
+/// starts with the header; rows are copied byte-for-byte.
+pub fn split_rows(
+    reader: &CsvReader,
+    rows_per_file: usize,
+    output_dir: &Path,
+) -> Result<Vec<SplitPart>> {
+    if rows_per_file == 0 {
+        return Err(MassiveCsvError::Parse(
+            "rows per file must be at least 1".to_string(),
+        ));
+    }
+    std::fs::create_dir_all(output_dir)?;
+    let (stem, extension) = name_parts(reader.path());
+    let total = reader.row_count();
+    let width = total.div_ceil(rows_per_file).to_string().len().max(4);
+
+    let mut parts = Vec::new();
+    for (i, start) in (0..total).step_by(rows_per_file).enumerate() {
+        let end = (start + rows_per_file).min(total);
+        let path = output_dir.join(format!("{stem}-{:0width$}.{extension}", i + 1));
+        let mut part = Part::create(reader, output_dir)?;
+        for row in start..end {
+            part.write_row(reader.get_row_bytes(row)?)?;
+        }
+        part.persist(reader, &path)?;
+        parts.push(SplitPart {
+            path,
+            rows: end - start,
+            value: None,
+        });
+    }
+    Ok(parts)
+}
+
+/// Write the reader's rows to `output_dir` with one file per distinct value
+/// of `column`, named `<stem>-<value>.<ext>` (characters unsafe in file
+/// names replaced by `_`, empty values as `empty`), in order of each value's
+/// first row. Every file starts with the header; rows keep their order and
+/// are copied byte-for-byte.
+pub fn split_by_column(
+    reader: &CsvReader,
+    column: &str,
+    output_dir: &Path,
+) -> Result<Vec<SplitPart>> {
+    let index = reader
+        .column_index(column)
+        .ok_or_else(|| MassiveCsvError::ColumnNotFound(column.to_string()))?;
+    std::fs::create_dir_all(output_dir)?;
+    let (stem, extension) = name_parts(reader.path());
+    let value_of = |row: usize| -> Result<String> {
+        let mut fields = reader.get_row(row)?;
+        Ok(if index < fields.len() {
+            fields.swap_remove(index)
+        } else {
+            String::new()
+        })
+    };
+
+    // Distinct values in order of first appearance, each with its row count
+    let mut values: Vec<(String, usize)> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    let total = reader.row_count();
+    for start in (0..total).step_by(SCAN_CHUNK) {
+        let end = (start + SCAN_CHUNK).min(total);
+        let batch = (start..end)
+            .into_par_iter()
+            .map(value_of)
+            .collect::<Result<Vec<_>>>()?;
+        for value in batch {
+            match positions.get(&value) {
+                Some(&i) => values[i].1 += 1,
+                None => {
+                    positions.insert(value.clone(), values.len());
+                    values.push((value, 1));
+                }
+            }
+        }
+    }
+
+    let mut used: HashMap<String, usize> = HashMap::new();
+    let paths: Vec<PathBuf> = values
+        .iter()
+        .map(|(value, _)| {
+            let name = file_name_for(value);
+            let seen = used.entry(name.clone()).or_insert(0);
+            *seen += 1;
+            let name = match *seen {
+                1 => name,
+                n => format!("{name}-{n}"),
+            };
+            output_dir.join(format!("{stem}-{name}.{extension}"))
+        })
+        .collect();
+
+    for batch in (0..values.len()).collect::<Vec<_>>().chunks(MAX_OPEN_PARTS) {
+        let first = batch[0];
+        let mut parts = batch
+            .iter()
+            .map(|_| Part::create(reader, output_dir))
+            .collect::<Result<Vec<_>>>()?;
+        for start in (0..total).step_by(SCAN_CHUNK) {
+            let end = (start + SCAN_CHUNK).min(total);
+            let targets = (start..end)
+                .into_par_iter()
+                .map(|row| Ok(positions[&value_of(row)?]))
+                .collect::<Result<Vec<usize>>>()?;
+            for (row, target) in (start..end).zip(targets) {
+                if let Some(part) = target.checked_sub(first).and_then(|i| parts.get_mut(i)) {
+                    part.write_row(reader.get_row_bytes(row)?)?;
+                }
+            }
+        }
+        for (part, &i) in parts.drain(..).zip(batch) {
+            part.persist(reader, &paths[i])?;
+        }
+    }
+
+    Ok(values
+        .into_iter()
+        .zip(paths)
+        .map(|((value, rows), path)| SplitPart {
+            path,
+            rows,
+            value: Some(value),
+        })
+        .collect())
+}
+
+/// The input's file stem and extension (`csv` if it has none), with a
+/// compression suffix such as `.gz` dropped.
+fn name_parts(path: &Path) -> (String, String) {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "split".to_string());
+    let name = [".gz", ".zst"]
+        .iter()
+        .find_map(|suffix| name.strip_suffix(suffix))
+        .unwrap_or(&name);
+    match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => (stem.to_string(), extension.to_string()),
+        _ => (name.to_string(), "csv".to_string()),
+    }
+}
+
+/// A column value as part of a file name.
+fn file_name_for(value: &str) -> String {
+    if value.is_empty() {
+        return "empty".to_string();
+    }
+    value
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// An output file being written: a temporary file in the output directory,
+/// renamed into place when complete.
+struct Part {
+    temp: TempPath,
+    writer: BufWriter<File>,
+    line_ending: &'static str,
+}
+
+impl Part {
+    fn create(reader: &CsvReader, dir: &Path) -> Result<Self> {
+        let (file, temp) = NamedTempFile::new_in(dir)?.into_parts();
+        let mut writer = BufWriter::new(file);
+        writer.write_all(reader.header_raw()?.as_bytes())?;
+        Ok(Self {
+            temp,
+            writer,
+            line_ending: reader.line_ending().as_str(),
+        })
+    }
+
+    fn write_row(&mut self, line: &[u8]) -> Result<()> {
+        self.writer.write_all(self.line_ending.as_bytes())?;
+        self.writer.write_all(line)?;
+        Ok(())
+    }
+
+    fn persist(mut self, reader: &CsvReader, path: &Path) -> Result<()> {
+        if reader.has_trailing_newline() {
+            self.writer.write_all(self.line_ending.as_bytes())?;
+        }
+        self.writer.flush()?;
+        drop(self.writer);
+        self.temp.persist(path).map_err(|e| e.error)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(path: &Path) -> String {
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn splits_by_row_count() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("events.csv");
+        std::fs::write(&input, "id,kind\r\n1,a\r\n2,b\r\n3,a\r\n4,c\r\n5,b\r\n").unwrap();
+        let reader = CsvReader::open(&input).unwrap();
+        let out = dir.path().join("parts");
+
+        let parts = split_rows(&reader, 2, &out).unwrap();
+        assert_eq!(
+            parts.iter().map(|p| p.rows).collect::<Vec<_>>(),
+            vec![2, 2, 1]
+        );
+        assert_eq!(parts[0].path, out.join("events-0001.csv"));
+        assert_eq!(read(&parts[0].path), "id,kind\r\n1,a\r\n2,b\r\n");
+        assert_eq!(read(&parts[2].path), "id,kind\r\n5,b\r\n");
+        assert!(split_rows(&reader, 0, &out).is_err());
+    }
+
+    #[test]
+    fn splits_by_column_value() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("sales.csv");
+        std::fs::write(&input, "id,region\n1,eu\n2,us/ca\n3,eu\n4\n5,us_ca").unwrap();
+        let reader = CsvReader::open(&input).unwrap();
+        let out = dir.path().join("by-region");
+
+        let parts = split_by_column(&reader, "region", &out).unwrap();
+        let summary: Vec<(String, usize, Option<String>)> = parts
+            .iter()
+            .map(|p| {
+                let name = p.path.file_name().unwrap().to_string_lossy().into_owned();
+                (name, p.rows, p.value.clone())
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("sales-eu.csv".to_string(), 2, Some("eu".to_string())),
+                ("sales-us_ca.csv".to_string(), 1, Some("us/ca".to_string())),
+                ("sales-empty.csv".to_string(), 1, Some(String::new())),
+                (
+                    "sales-us_ca-2.csv".to_string(),
+                    1,
+                    Some("us_ca".to_string())
+                ),
+            ]
+        );
+        // No trailing newline in the input, so none in the parts
+        assert_eq!(read(&parts[0].path), "id,region\n1,eu\n3,eu");
+        assert_eq!(read(&parts[3].path), "id,region\n5,us_ca");
+        assert!(matches!(
+            split_by_column(&reader, "country", &out),
+            Err(MassiveCsvError::ColumnNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn splits_many_values_in_batches() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("ids.csv");
+        let mut csv = String::from("id\n");
+        for i in 0..MAX_OPEN_PARTS + 10 {
+            csv.push_str(&format!("{i}\n"));
+        }
+        std::fs::write(&input, csv).unwrap();
+        let reader = CsvReader::open(&input).unwrap();
+
+        let parts = split_by_column(&reader, "id", dir.path()).unwrap();
+        assert_eq!(parts.len(), MAX_OPEN_PARTS + 10);
+        let last = parts.last().unwrap();
+        assert_eq!(read(&last.path), format!("id\n{}\n", MAX_OPEN_PARTS + 9));
+    }
+}
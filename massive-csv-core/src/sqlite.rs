@@ -0,0 +1,519 @@
+//! Writing rows into a new SQLite database file.
+//!
+//! The database is written directly in the SQLite file format (one rowid
+//! table plus `sqlite_schema`), without linking SQLite. Rows fill leaf pages
+//! in order as they arrive, with long values spilling into overflow pages,
+//! and the interior pages of the table's b-tree are added once all rows are
+//! in; memory use doesn't grow with the number of rows.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, Write};
+
+use crate::error::{MassiveCsvError, Result};
+use crate::infer::{parse_integer, ColumnType};
+
+/// Most children an interior page is given; fewer than fit on the smallest page.
+const INTERIOR_FANOUT: usize = 200;
+
+/// `SQLITE_VERSION_NUMBER` recorded as the last writer.
+const SQLITE_VERSION: u32 = 3_045_000;
+
+/// Streams rows into a single-table SQLite database.
+pub struct SqliteWriter {
+    columns: Vec<ColumnType>,
+    /// The `CREATE TABLE` statement.
+    sql: String,
+    table: String,
+    page_size: usize,
+    /// Pages 2 onwards, in order.
+    pages: BufWriter<File>,
+    /// Number of the next page to be written.
+    next_page: u32,
+    leaf: Leaf,
+    /// Each finished leaf's page number and largest rowid.
+    leaves: Vec<(u32, i64)>,
+    rows: i64,
+}
+
+/// The leaf page being filled; its overflow pages follow it in the file.
+struct Leaf {
+    page: u32,
+    cells: Vec<Vec<u8>>,
+    used: usize,
+    overflow: Vec<Vec<u8>>,
+    last_rowid: i64,
+}
+
+impl SqliteWriter {
+    /// Start a database with one table, `table`, of the named columns.
+    /// Integer and boolean columns are declared `INTEGER`, floats `REAL`
+    /// and everything else `TEXT`.
+    pub fn new(table: &str, columns: &[(String, ColumnType)]) -> Result<Self> {
+        let definitions: Vec<String> = columns
+            .iter()
+            .map(|(name, column_type)| format!("{} {}", quote(name), declared_type(*column_type)))
+            .collect();
+        let sql = format!("CREATE TABLE {} ({})", quote(table), definitions.join(", "));
+
+        // The schema row must fit on page 1; a big enough page always holds it
+        let schema_cell = 40 + 2 * table.len() + sql.len();
+        let page_size = [4096, 8192, 16384, 32768, 65536]
+            .into_iter()
+            .find(|&size| schema_cell + 110 <= size)
+            .ok_or_else(|| {
+                MassiveCsvError::Parse("too many columns for a SQLite table".to_string())
+            })?;
+
+        Ok(Self {
+            columns: columns.iter().map(|(_, t)| *t).collect(),
+            sql,
+            table: table.to_string(),
+            page_size,
+            pages: BufWriter::with_capacity(1024 * 1024, tempfile::tempfile()?),
+            next_page: 2,
+            leaf: Leaf {
+                page: 2,
+                cells: Vec::new(),
+                used: 0,
+                overflow: Vec::new(),
+                last_rowid: 0,
+            },
+            leaves: Vec::new(),
+            rows: 0,
+        })
+    }
+
+    /// Append a row. Empty fields are stored as NULL; values that don't fit
+    /// their column's type are stored as text, as SQLite itself would.
+    pub fn insert(&mut self, fields: &[String]) -> Result<()> {
+        self.rows += 1;
+        let record = encode_record(&self.columns, fields);
+        self.add_cell(self.rows, &record)
+    }
+
+    /// Number of rows inserted.
+    pub fn row_count(&self) -> u64 {
+        self.rows as u64
+    }
+
+    /// Write the finished database to `out`.
+    pub fn finish(mut self, out: &mut dyn Write) -> Result<()> {
+        self.flush_leaf()?;
+        let root = self.build_tree()?;
+        let mut pages = self.pages.into_inner().map_err(|e| e.into_error())?;
+
+        // sqlite_schema: (type, name, tbl_name, rootpage, sql)
+        let mut record = Vec::new();
+        let values = [
+            Value::Text("table"),
+            Value::Text(&self.table),
+            Value::Text(&self.table),
+            Value::Int32(root as i32),
+            Value::Text(&self.sql),
+        ];
+        write_record(&mut record, &values);
+        let mut schema = Leaf {
+            page: 1,
+            cells: Vec::new(),
+            used: 0,
+            overflow: Vec::new(),
+            last_rowid: 0,
+        };
+        let first_overflow = self.next_page;
+        let (cell, overflow) = leaf_cell(self.page_size, 1, &record, first_overflow);
+        schema.cells.push(cell);
+        schema.overflow = overflow;
+        let page_count = self.next_page - 1 + schema.overflow.len() as u32;
+
+        let mut page = leaf_page(self.page_size, &schema.cells, 100);
+        page[..100].copy_from_slice(&database_header(self.page_size, page_count));
+        out.write_all(&page)?;
+        pages.rewind()?;
+        io::copy(&mut pages, out)?;
+        for page in &schema.overflow {
+            out.write_all(page)?;
+        }
+        Ok(())
+    }
+
+    fn add_cell(&mut self, rowid: i64, payload: &[u8]) -> Result<()> {
+        let size = leaf_cell_size(self.page_size, rowid, payload.len());
+        // Leaf header, then a 2-byte pointer per cell
+        if !self.leaf.cells.is_empty()
+            && 8 + 2 * (self.leaf.cells.len() + 1) + self.leaf.used + size > self.page_size
+        {
+            self.flush_leaf()?;
+        }
+        let first_overflow = self.leaf.page + 1 + self.leaf.overflow.len() as u32;
+        let (cell, overflow) = leaf_cell(self.page_size, rowid, payload, first_overflow);
+        self.leaf.used += cell.len();
+        self.leaf.cells.push(cell);
+        self.leaf.overflow.extend(overflow);
+        self.leaf.last_rowid = rowid;
+        Ok(())
+    }
+
+    /// Write the current leaf and its overflow pages, and start the next.
+    fn flush_leaf(&mut self) -> Result<()> {
+        if self.leaf.cells.is_empty() && !self.leaves.is_empty() {
+            return Ok(());
+        }
+        self.pages
+            .write_all(&leaf_page(self.page_size, &self.leaf.cells, 0))?;
+        for page in &self.leaf.overflow {
+            self.pages.write_all(page)?;
+        }
+        self.leaves.push((self.leaf.page, self.leaf.last_rowid));
+        self.next_page = self.leaf.page + 1 + self.leaf.overflow.len() as u32;
+        self.leaf = Leaf {
+            page: self.next_page,
+            cells: Vec::new(),
+            used: 0,
+            overflow: Vec::new(),
+            last_rowid: 0,
+        };
+        Ok(())
+    }
+
+    /// Add interior pages over the leaves, returning the root page.
+    fn build_tree(&mut self) -> Result<u32> {
+        let mut level = std::mem::take(&mut self.leaves);
+        while level.len() > 1 {
+            let mut parents = Vec::with_capacity(level.len() / INTERIOR_FANOUT + 1);
+            for children in level.chunks(INTERIOR_FANOUT) {
+                let (right, max_key) = children[children.len() - 1];
+                let cells: Vec<Vec<u8>> = children[..children.len() - 1]
+                    .iter()
+                    .map(|&(page, key)| {
+                        let mut cell = page.to_be_bytes().to_vec();
+                        put_varint(&mut cell, key as u64);
+                        cell
+                    })
+                    .collect();
+                self.pages
+                    .write_all(&interior_page(self.page_size, &cells, right))?;
+                parents.push((self.next_page, max_key));
+                self.next_page += 1;
+            }
+            level = parents;
+        }
+        Ok(level[0].0)
+    }
+}
+
+fn declared_type(column_type: ColumnType) -> &'static str {
+    match column_type {
+        ColumnType::Integer | ColumnType::Boolean => "INTEGER",
+        ColumnType::Float => "REAL",
+        _ => "TEXT",
+    }
+}
+
+/// Quote an SQL identifier.
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+enum Value<'a> {
+    Null,
+    Int(i64),
+    /// Always 4 bytes, so the schema row's size is known in advance.
+    Int32(i32),
+    Real(f64),
+    Text(&'a str),
+}
+
+fn encode_record(columns: &[ColumnType], fields: &[String]) -> Vec<u8> {
+    let values: Vec<Value> = columns
+        .iter()
+        .enumerate()
+        .map(|(i, column_type)| {
+            let field = fields.get(i).map_or("", String::as_str);
+            if field.is_empty() {
+                return Value::Null;
+            }
+            let typed = match column_type {
+                ColumnType::Integer => parse_integer(field).map(Value::Int),
+                ColumnType::Boolean if field.eq_ignore_ascii_case("true") => Some(Value::Int(1)),
+                ColumnType::Boolean if field.eq_ignore_ascii_case("false") => Some(Value::Int(0)),
+                ColumnType::Float => match ColumnType::of(field) {
+                    Some(ColumnType::Integer | ColumnType::Float) => {
+                        field.parse().ok().map(Value::Real)
+                    }
+                    _ => None,
+                },
+                _ => None,
+            };
+            typed.unwrap_or(Value::Text(field))
+        })
+        .collect();
+    let mut record = Vec::new();
+    write_record(&mut record, &values);
+    record
+}
+
+/// Encode a record: a header of serial types, then the values.
+fn write_record(out: &mut Vec<u8>, values: &[Value]) {
+    let mut types = Vec::with_capacity(values.len() * 2);
+    let mut body = Vec::new();
+    for value in values {
+        let serial = match value {
+            Value::Null => 0,
+            Value::Int(0) => 8,
+            Value::Int(1) => 9,
+            Value::Int(v) => {
+                let (serial, width) = match *v {
+                    v if i8::try_from(v).is_ok() => (1, 1),
+                    v if i16::try_from(v).is_ok() => (2, 2),
+                    v if (-(1 << 23)..1 << 23).contains(&v) => (3, 3),
+                    v if i32::try_from(v).is_ok() => (4, 4),
+                    v if (-(1 << 47)..1 << 47).contains(&v) => (5, 6),
+                    _ => (6, 8),
+                };
+                body.extend_from_slice(&v.to_be_bytes()[8 - width..]);
+                serial
+            }
+            Value::Int32(v) => {
+                body.extend_from_slice(&v.to_be_bytes());
+                4
+            }
+            Value::Real(v) => {
+                body.extend_from_slice(&v.to_be_bytes());
+                7
+            }
+            Value::Text(s) => {
+                body.extend_from_slice(s.as_bytes());
+                13 + 2 * s.len() as u64
+            }
+        };
+        put_varint(&mut types, serial);
+    }
+    // The header size counts its own varint
+    let mut header_size = types.len() + 1;
+    while varint_len((header_size) as u64) + types.len() != header_size {
+        header_size += 1;
+    }
+    put_varint(out, header_size as u64);
+    out.extend_from_slice(&types);
+    out.extend_from_slice(&body);
+}
+
+/// Bytes of a table leaf cell's payload kept on the page.
+fn local_payload(page_size: usize, payload: usize) -> usize {
+    let usable = page_size;
+    let max_local = usable - 35;
+    if payload <= max_local {
+        return payload;
+    }
+    let min_local = (usable - 12) * 32 / 255 - 23;
+    let local = min_local + (payload - min_local) % (usable - 4);
+    if local <= max_local {
+        local
+    } else {
+        min_local
+    }
+}
+
+fn leaf_cell_size(page_size: usize, rowid: i64, payload: usize) -> usize {
+    let local = local_payload(page_size, payload);
+    let pointer = if local < payload { 4 } else { 0 };
+    varint_len(payload as u64) + varint_len(rowid as u64) + local + pointer
+}
+
+/// A table leaf cell and the overflow pages for the rest of its payload,
+/// to be numbered from `first_overflow`.
+fn leaf_cell(
+    page_size: usize,
+    rowid: i64,
+    payload: &[u8],
+    first_overflow: u32,
+) -> (Vec<u8>, Vec<Vec<u8>>) {
+    let local = local_payload(page_size, payload.len());
+    let mut cell = Vec::with_capacity(local + 18);
+    put_varint(&mut cell, payload.len() as u64);
+    put_varint(&mut cell, rowid as u64);
+    cell.extend_from_slice(&payload[..local]);
+    if local == payload.len() {
+        return (cell, Vec::new());
+    }
+    cell.extend_from_slice(&first_overflow.to_be_bytes());
+
+    let chunks: Vec<&[u8]> = payload[local..].chunks(page_size - 4).collect();
+    let pages = chunks
+        .iter()
+        .enumerate()
+        .map(|(i, chunk)| {
+            let next = if i + 1 < chunks.len() {
+                first_overflow + i as u32 + 1
+            } else {
+                0
+            };
+            let mut page = vec![0; page_size];
+            page[..4].copy_from_slice(&next.to_be_bytes());
+            page[4..4 + chunk.len()].copy_from_slice(chunk);
+            page
+        })
+        .collect();
+    (cell, pages)
+}
+
+/// A b-tree page whose header starts at `offset` (100 on page 1).
+fn btree_page(
+    page_size: usize,
+    kind: u8,
+    cells: &[Vec<u8>],
+    offset: usize,
+    right: Option<u32>,
+) -> Vec<u8> {
+    let mut page = vec![0; page_size];
+    let header = if right.is_some() { 12 } else { 8 };
+    let mut content = page_size;
+    for (i, cell) in cells.iter().enumerate() {
+        content -= cell.len();
+        page[content..content + cell.len()].copy_from_slice(cell);
+        let pointer = offset + header + 2 * i;
+        page[pointer..pointer + 2].copy_from_slice(&(content as u16).to_be_bytes());
+    }
+    page[offset] = kind;
+    page[offset + 3..offset + 5].copy_from_slice(&(cells.len() as u16).to_be_bytes());
+    // 65536 is written as 0
+    page[offset + 5..offset + 7].copy_from_slice(&(content as u16).to_be_bytes());
+    if let Some(right) = right {
+        page[offset + 8..offset + 12].copy_from_slice(&right.to_be_bytes());
+    }
+    page
+}
+
+fn leaf_page(page_size: usize, cells: &[Vec<u8>], offset: usize) -> Vec<u8> {
+    btree_page(page_size, 0x0D, cells, offset, None)
+}
+
+fn interior_page(page_size: usize, cells: &[Vec<u8>], right: u32) -> Vec<u8> {
+    btree_page(page_size, 0x05, cells, 0, Some(right))
+}
+
+/// The 100-byte header at the start of the file.
+fn database_header(page_size: usize, page_count: u32) -> [u8; 100] {
+    let mut header = [0u8; 100];
+    header[..16].copy_from_slice(b"SQLite format 3\0");
+    // 65536 is written as 1
+    let size_field = if page_size == 65536 {
+        1
+    } else {
+        page_size as u16
+    };
+    header[16..18].copy_from_slice(&size_field.to_be_bytes());
+    header[18] = 1; // write version: rollback journal
+    header[19] = 1; // read version
+    header[21] = 64; // payload fractions, fixed by the format
+    header[22] = 32;
+    header[23] = 32;
+    header[24..28].copy_from_slice(&1u32.to_be_bytes()); // change counter
+    header[28..32].copy_from_slice(&page_count.to_be_bytes());
+    header[40..44].copy_from_slice(&1u32.to_be_bytes()); // schema cookie
+    header[44..48].copy_from_slice(&4u32.to_be_bytes()); // schema format
+    header[56..60].copy_from_slice(&1u32.to_be_bytes()); // UTF-8
+    header[92..96].copy_from_slice(&1u32.to_be_bytes()); // version-valid-for
+    header[96..100].copy_from_slice(&SQLITE_VERSION.to_be_bytes());
+    header
+}
+
+/// Append SQLite's big-endian variable-length integer.
+fn put_varint(out: &mut Vec<u8>, value: u64) {
+    if value >> 56 != 0 {
+        // Nine bytes: eight of 7 bits, then a full byte
+        let mut bytes = [0u8; 9];
+        bytes[8] = value as u8;
+        let mut rest = value >> 8;
+        for byte in bytes[..8].iter_mut().rev() {
+            *byte = (rest & 0x7F) as u8 | 0x80;
+            rest >>= 7;
+        }
+        out.extend_from_slice(&bytes);
+        return;
+    }
+    let start = out.len();
+    let mut rest = value;
+    loop {
+        out.push((rest & 0x7F) as u8 | 0x80);
+        rest >>= 7;
+        if rest == 0 {
+            break;
+        }
+    }
+    out[start] &= 0x7F;
+    out[start..].reverse();
+}
+
+fn varint_len(value: u64) -> usize {
+    if value >> 56 != 0 {
+        return 9;
+    }
+    (64 - value.leading_zeros() as usize).max(1).div_ceil(7)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn varints() {
+        for (value, bytes) in [
+            (0u64, vec![0x00]),
+            (127, vec![0x7F]),
+            (128, vec![0x81, 0x00]),
+            (300, vec![0x82, 0x2C]),
+            (u64::MAX, vec![0xFF; 9]),
+        ] {
+            let mut out = Vec::new();
+            put_varint(&mut out, value);
+            assert_eq!(out, bytes, "{value}");
+            assert_eq!(varint_len(value), bytes.len());
+        }
+    }
+
+    #[test]
+    fn records() {
+        let mut out = Vec::new();
+        write_record(
+            &mut out,
+            &[
+                Value::Null,
+                Value::Int(1),
+                Value::Int(-200),
+                Value::Text("hi"),
+            ],
+        );
+        // header size 5; NULL, one, i16, text of 2
+        assert_eq!(out, [5, 0, 9, 2, 17, 0xFF, 0x38, b'h', b'i']);
+    }
+
+    #[test]
+    fn writes_a_database_file() {
+        let columns = [
+            ("id".to_string(), ColumnType::Integer),
+            ("note".to_string(), ColumnType::Text),
+        ];
+        let mut writer = SqliteWriter::new("t", &columns).unwrap();
+        let long = "x".repeat(10_000);
+        for i in 0..5000 {
+            let note = if i == 7 {
+                long.clone()
+            } else {
+                format!("row {i}")
+            };
+            writer.insert(&[i.to_string(), note]).unwrap();
+        }
+        assert_eq!(writer.row_count(), 5000);
+        let mut out = Vec::new();
+        writer.finish(&mut out).unwrap();
+
+        assert_eq!(&out[..16], b"SQLite format 3\0");
+        assert_eq!(out.len() % 4096, 0);
+        let pages = u32::from_be_bytes(out[28..32].try_into().unwrap());
+        assert_eq!(pages as usize, out.len() / 4096);
+        // Page 1 holds the schema row, page 2 starts the table's leaves
+        assert_eq!(out[100], 0x0D);
+        assert_eq!(out[4096], 0x0D);
+    }
+}
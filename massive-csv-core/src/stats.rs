@@ -0,0 +1,557 @@
+//! Per-column summary statistics and value frequencies, each computed in
+//! one parallel pass.
+
+use std::collections::HashMap;
+
+use rayon::prelude::*;
+
+use crate::bloom::fnv1a;
+use crate::error::{MassiveCsvError, Result};
+use crate::infer::ColumnType;
+use crate::reader::CsvReader;
+
+/// HyperLogLog precision: 2^12 registers, about 1.6% standard error.
+const HLL_BITS: u32 = 12;
+const HLL_REGISTERS: usize = 1 << HLL_BITS;
+
+/// Share of distinct values from which a column looks like a key. Leaves
+/// room for the estimate's error.
+const NEAR_UNIQUE_RATIO: f64 = 0.95;
+
+/// Most distinct values a column can have to look like an enum.
+const LOW_CARDINALITY: u64 = 100;
+
+/// t-digest compression: bounds a digest to roughly twice this many
+/// centroids, for percentiles within a fraction of a percent.
+const DIGEST_COMPRESSION: f64 = 100.0;
+
+/// Values buffered before they are folded into a digest's centroids.
+const DIGEST_BUFFER: usize = 512;
+
+/// Summary of one column.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnStats {
+    pub name: String,
+    /// Non-empty values.
+    pub count: usize,
+    /// Empty cells, counting fields missing from short rows.
+    pub empty: usize,
+    /// Present when there is at least one value and every value is a
+    /// number (see [`ColumnType::of`]).
+    pub numeric: Option<NumericStats>,
+    /// Shortest non-empty value, in characters (0 without values).
+    pub min_length: usize,
+    /// Longest value, in characters.
+    pub max_length: usize,
+    /// Estimated number of distinct non-empty values (HyperLogLog, within a
+    /// few percent; exact for small counts). Never more than `count`.
+    pub distinct: u64,
+}
+
+impl ColumnStats {
+    /// Estimated share of values that are distinct, from 0 to 1.
+    pub fn distinct_ratio(&self) -> f64 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        self.distinct as f64 / self.count as f64
+    }
+
+    /// Whether (nearly) every value is different, as in a key column.
+    pub fn is_near_unique(&self) -> bool {
+        self.count > 1 && self.distinct_ratio() >= NEAR_UNIQUE_RATIO
+    }
+
+    /// Whether a few values repeat throughout, as in an enum or flag column.
+    pub fn is_low_cardinality(&self) -> bool {
+        self.count > 0 && self.distinct <= LOW_CARDINALITY && self.distinct_ratio() <= 0.5
+    }
+}
+
+/// Statistics of a numeric column.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NumericStats {
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    /// Sample standard deviation (0 for a single value).
+    pub stddev: f64,
+    /// Approximate median, 95th and 99th percentiles (t-digest; exact for
+    /// small columns).
+    pub p50: f64,
+    pub p95: f64,
+    pub p99: f64,
+}
+
+/// Compute statistics for the named columns, or every column if `columns`
+/// is empty.
+pub fn column_stats(reader: &CsvReader, columns: &[String]) -> Result<Vec<ColumnStats>> {
+    let headers = reader.canonical_headers();
+    let selected: Vec<usize> = if columns.is_empty() {
+        (0..headers.len()).collect()
+    } else {
+        columns
+            .iter()
+            .map(|name| {
+                reader
+                    .column_index(name)
+                    .ok_or_else(|| MassiveCsvError::ColumnNotFound(name.clone()))
+            })
+            .collect::<Result<_>>()?
+    };
+
+    let empty = || vec![Accumulator::default(); selected.len()];
+    let accumulators = (0..reader.row_count())
+        .into_par_iter()
+        .with_min_len(4096)
+        .try_fold(empty, |mut accumulators, row| {
+            let fields = reader.get_row(row)?;
+            for (accumulator, &column) in accumulators.iter_mut().zip(&selected) {
+                accumulator.add(fields.get(column).map_or("", String::as_str));
+            }
+            Ok::<_, MassiveCsvError>(accumulators)
+        })
+        .try_reduce(empty, |mut a, b| {
+            for (a, b) in a.iter_mut().zip(b) {
+                a.merge(b);
+            }
+            Ok(a)
+        })?;
+
+    Ok(selected
+        .iter()
+        .zip(accumulators)
+        .map(|(&column, accumulator)| accumulator.finish(headers[column].clone()))
+        .collect())
+}
+
+/// The most frequent values of a column.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValueCounts {
+    /// Values with their counts, most frequent first; ties in value order.
+    /// Empty cells count as the value `""`.
+    pub values: Vec<(String, usize)>,
+    /// Number of different values, including ones cut by `top_n`.
+    pub distinct: usize,
+    /// Rows counted.
+    pub rows: usize,
+}
+
+/// Count how often each value of `column` occurs and keep the `top_n` most
+/// frequent (all of them if `top_n` is 0). Each thread aggregates its rows
+/// into its own map; the maps are merged at the end.
+pub fn value_counts(reader: &CsvReader, column: &str, top_n: usize) -> Result<ValueCounts> {
+    let index = reader
+        .column_index(column)
+        .ok_or_else(|| MassiveCsvError::ColumnNotFound(column.to_string()))?;
+
+    let counts = (0..reader.row_count())
+        .into_par_iter()
+        .with_min_len(4096)
+        .try_fold(HashMap::new, |mut counts, row| {
+            let mut fields = reader.get_row(row)?;
+            let value = if index < fields.len() {
+                fields.swap_remove(index)
+            } else {
+                String::new()
+            };
+            *counts.entry(value).or_insert(0usize) += 1;
+            Ok::<_, MassiveCsvError>(counts)
+        })
+        .try_reduce(HashMap::new, |a, b| {
+            let (mut into, from) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+            for (value, count) in from {
+                *into.entry(value).or_insert(0) += count;
+            }
+            Ok(into)
+        })?;
+
+    let distinct = counts.len();
+    let mut values: Vec<(String, usize)> = counts.into_iter().collect();
+    let order =
+        |a: &(String, usize), b: &(String, usize)| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0));
+    if top_n > 0 && top_n < values.len() {
+        values.select_nth_unstable_by(top_n - 1, order);
+        values.truncate(top_n);
+    }
+    values.sort_unstable_by(order);
+
+    Ok(ValueCounts {
+        values,
+        distinct,
+        rows: reader.row_count(),
+    })
+}
+
+#[derive(Debug, Clone)]
+struct Accumulator {
+    count: usize,
+    empty: usize,
+    /// Cleared by the first value that isn't a number.
+    numeric: bool,
+    min: f64,
+    max: f64,
+    /// Running mean and sum of squared deviations (Welford).
+    mean: f64,
+    m2: f64,
+    digest: TDigest,
+    min_length: usize,
+    max_length: usize,
+    distinct: HyperLogLog,
+}
+
+impl Default for Accumulator {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            empty: 0,
+            numeric: true,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            mean: 0.0,
+            m2: 0.0,
+            digest: TDigest::default(),
+            min_length: usize::MAX,
+            max_length: 0,
+            distinct: HyperLogLog::default(),
+        }
+    }
+}
+
+impl Accumulator {
+    fn add(&mut self, value: &str) {
+        if value.is_empty() {
+            self.empty += 1;
+            return;
+        }
+        self.count += 1;
+        let length = value.chars().count();
+        self.min_length = self.min_length.min(length);
+        self.max_length = self.max_length.max(length);
+        self.distinct.insert(value.as_bytes());
+
+        if !self.numeric {
+            return;
+        }
+        let number = match ColumnType::of(value) {
+            Some(ColumnType::Integer | ColumnType::Float) => value.parse::<f64>().ok(),
+            _ => None,
+        };
+        let Some(x) = number else {
+            self.numeric = false;
+            self.digest = TDigest::default();
+            return;
+        };
+        self.min = self.min.min(x);
+        self.max = self.max.max(x);
+        let delta = x - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (x - self.mean);
+        self.digest.add(x);
+    }
+
+    fn merge(&mut self, other: Self) {
+        let (n_a, n_b) = (self.count as f64, other.count as f64);
+        self.numeric &= other.numeric;
+        if self.numeric && other.count > 0 {
+            // Chan et al.'s pairwise update
+            let delta = other.mean - self.mean;
+            let n = n_a + n_b;
+            self.mean += delta * n_b / n;
+            self.m2 += other.m2 + delta * delta * n_a * n_b / n;
+            self.min = self.min.min(other.min);
+            self.max = self.max.max(other.max);
+            self.digest.merge(other.digest);
+        } else if !self.numeric {
+            self.digest = TDigest::default();
+        }
+        self.count += other.count;
+        self.empty += other.empty;
+        self.min_length = self.min_length.min(other.min_length);
+        self.max_length = self.max_length.max(other.max_length);
+        self.distinct.merge(&other.distinct);
+    }
+
+    fn finish(mut self, name: String) -> ColumnStats {
+        self.digest.compress();
+        let numeric = (self.numeric && self.count > 0).then(|| {
+            let percentile = |q| self.digest.quantile(q, self.min, self.max);
+            NumericStats {
+                min: self.min,
+                max: self.max,
+                mean: self.mean,
+                stddev: if self.count > 1 {
+                    (self.m2 / (self.count - 1) as f64).sqrt()
+                } else {
+                    0.0
+                },
+                p50: percentile(0.5),
+                p95: percentile(0.95),
+                p99: percentile(0.99),
+            }
+        });
+        ColumnStats {
+            name,
+            count: self.count,
+            empty: self.empty,
+            numeric,
+            min_length: if self.count > 0 { self.min_length } else { 0 },
+            max_length: self.max_length,
+            distinct: self.distinct.estimate().min(self.count as u64),
+        }
+    }
+}
+
+/// A merging t-digest (Dunning and Ertl) for approximate quantiles in
+/// bounded memory. Centroids are kept small near the tails, where the high
+/// percentiles need the detail, and large around the median.
+#[derive(Debug, Clone, Default)]
+struct TDigest {
+    /// (mean, weight), sorted by mean after [`TDigest::compress`].
+    centroids: Vec<(f64, f64)>,
+    buffer: Vec<f64>,
+}
+
+impl TDigest {
+    fn add(&mut self, x: f64) {
+        self.buffer.push(x);
+        if self.buffer.len() >= DIGEST_BUFFER {
+            self.compress();
+        }
+    }
+
+    fn merge(&mut self, other: Self) {
+        self.centroids.extend(other.centroids);
+        self.buffer.extend(other.buffer);
+        self.compress();
+    }
+
+    /// Fold the buffer into the centroids, merging neighbours while the
+    /// result stays under the size limit for its quantile.
+    fn compress(&mut self) {
+        let mut points = std::mem::take(&mut self.centroids);
+        points.extend(self.buffer.drain(..).map(|x| (x, 1.0)));
+        points.sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+        let total: f64 = points.iter().map(|c| c.1).sum();
+
+        let mut points = points.into_iter();
+        let Some(mut current) = points.next() else {
+            return;
+        };
+        let mut before = 0.0;
+        for next in points {
+            let q0 = before / total;
+            let q2 = (before + current.1 + next.1) / total;
+            let limit = 4.0 * total * (q0 * (1.0 - q0)).min(q2 * (1.0 - q2)) / DIGEST_COMPRESSION;
+            if current.1 + next.1 <= limit {
+                let weight = current.1 + next.1;
+                current.0 += (next.0 - current.0) * next.1 / weight;
+                current.1 = weight;
+            } else {
+                before += current.1;
+                self.centroids.push(current);
+                current = next;
+            }
+        }
+        self.centroids.push(current);
+    }
+
+    /// The value at quantile `q`, interpolating between centroid centres
+    /// and out to `min` and `max` at the ends. Call after `compress`.
+    fn quantile(&self, q: f64, min: f64, max: f64) -> f64 {
+        let (Some(first), Some(last)) = (self.centroids.first(), self.centroids.last()) else {
+            return f64::NAN;
+        };
+        let total: f64 = self.centroids.iter().map(|c| c.1).sum();
+        let index = q * total;
+
+        let mut centre = first.1 / 2.0;
+        if index < centre {
+            return min + (first.0 - min) * index / centre;
+        }
+        for pair in self.centroids.windows(2) {
+            let gap = (pair[0].1 + pair[1].1) / 2.0;
+            if index < centre + gap {
+                return pair[0].0 + (pair[1].0 - pair[0].0) * (index - centre) / gap;
+            }
+            centre += gap;
+        }
+        last.0 + (max - last.0) * ((index - centre) / (last.1 / 2.0)).min(1.0)
+    }
+}
+
+/// A HyperLogLog sketch; the registers are allocated on first insert.
+#[derive(Debug, Clone, Default)]
+struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+impl HyperLogLog {
+    fn insert(&mut self, value: &[u8]) {
+        if self.registers.is_empty() {
+            self.registers = vec![0; HLL_REGISTERS];
+        }
+        let hash = mix(fnv1a(value));
+        let index = (hash >> (64 - HLL_BITS)) as usize;
+        // Leading zeros of the remaining bits, plus one
+        let rank = ((hash << HLL_BITS) | (1 << (HLL_BITS - 1))).leading_zeros() as u8 + 1;
+        self.registers[index] = self.registers[index].max(rank);
+    }
+
+    fn merge(&mut self, other: &Self) {
+        if self.registers.is_empty() {
+            self.registers = other.registers.clone();
+        } else if !other.registers.is_empty() {
+            for (a, &b) in self.registers.iter_mut().zip(&other.registers) {
+                *a = (*a).max(b);
+            }
+        }
+    }
+
+    fn estimate(&self) -> u64 {
+        if self.registers.is_empty() {
+            return 0;
+        }
+        let m = HLL_REGISTERS as f64;
+        let sum: f64 = self.registers.iter().map(|&r| 2f64.powi(-(r as i32))).sum();
+        let raw = 0.7213 / (1.0 + 1.079 / m) * m * m / sum;
+        let zeros = self.registers.iter().filter(|&&r| r == 0).count();
+        // Linear counting is more accurate while many registers are unset
+        let estimate = if raw <= 2.5 * m && zeros > 0 {
+            m * (m / zeros as f64).ln()
+        } else {
+            raw
+        };
+        estimate.round() as u64
+    }
+}
+
+/// Spread FNV's bits over the whole word (the splitmix64 finalizer).
+pub(crate) fn mix(mut x: u64) -> u64 {
+    x ^= x >> 30;
+    x = x.wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x ^= x >> 27;
+    x = x.wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn make_csv(content: &str) -> tempfile::NamedTempFile {
+        let mut f = tempfile::NamedTempFile::new().unwrap();
+        f.write_all(content.as_bytes()).unwrap();
+        f.flush().unwrap();
+        f
+    }
+
+    #[test]
+    fn computes_column_stats() {
+        let f = make_csv("id,amount,name\n1,2,ann\n2,4,bo\n3,,ann\n4,6\n5,8,Zoë\n");
+        let reader = CsvReader::open(f.path()).unwrap();
+        let stats = column_stats(&reader, &[]).unwrap();
+        assert_eq!(stats.len(), 3);
+
+        let amount = &stats[1];
+        assert_eq!((amount.count, amount.empty), (4, 1));
+        let numeric = amount.numeric.unwrap();
+        assert_eq!((numeric.min, numeric.max, numeric.mean), (2.0, 8.0, 5.0));
+        assert!((numeric.stddev - (20.0f64 / 3.0).sqrt()).abs() < 1e-9);
+
+        let name = &stats[2];
+        assert_eq!((name.count, name.empty), (4, 1));
+        assert_eq!(name.numeric, None);
+        assert_eq!((name.min_length, name.max_length), (2, 3));
+        assert_eq!(name.distinct, 3);
+
+        let only = column_stats(&reader, &["name".to_string()]).unwrap();
+        assert_eq!(only, vec![name.clone()]);
+        assert!(matches!(
+            column_stats(&reader, &["nope".to_string()]),
+            Err(MassiveCsvError::ColumnNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn counts_values() {
+        let f = make_csv("id,country\n1,ES\n2,FR\n3,ES\n4\n5,DE\n6,FR\n7,ES\n");
+        let reader = CsvReader::open(f.path()).unwrap();
+
+        let counts = value_counts(&reader, "country", 2).unwrap();
+        assert_eq!(
+            counts.values,
+            vec![("ES".to_string(), 3), ("FR".to_string(), 2)]
+        );
+        assert_eq!((counts.distinct, counts.rows), (4, 7));
+
+        let all = value_counts(&reader, "country", 0).unwrap();
+        assert_eq!(all.values.len(), 4);
+        // Ties in value order
+        assert_eq!(all.values[2..], [(String::new(), 1), ("DE".to_string(), 1)]);
+        assert!(matches!(
+            value_counts(&reader, "city", 10),
+            Err(MassiveCsvError::ColumnNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn estimates_percentiles() {
+        let mut csv = String::from("latency_ms\n");
+        // Shuffled 1..=20_000
+        for i in 0..20_000u64 {
+            csv.push_str(&format!("{}\n", i * 7_919 % 20_000 + 1));
+        }
+        let f = make_csv(&csv);
+        let reader = CsvReader::open(f.path()).unwrap();
+        let numeric = column_stats(&reader, &[]).unwrap()[0].numeric.unwrap();
+        for (estimate, exact) in [
+            (numeric.p50, 10_000.0),
+            (numeric.p95, 19_000.0),
+            (numeric.p99, 19_800.0),
+        ] {
+            assert!(
+                (estimate / exact - 1.0).abs() < 0.005,
+                "{estimate} vs {exact}"
+            );
+        }
+
+        let f = make_csv("n\n1\n2\n3\n4\n");
+        let reader = CsvReader::open(f.path()).unwrap();
+        let numeric = column_stats(&reader, &[]).unwrap()[0].numeric.unwrap();
+        assert_eq!(numeric.p50, 2.5);
+        assert!(numeric.p99 > 3.9 && numeric.p99 <= 4.0);
+    }
+
+    #[test]
+    fn classifies_cardinality() {
+        let mut csv = String::from("id,status\n");
+        for i in 0..1000 {
+            csv.push_str(&format!("{i},{}\n", ["new", "paid", "void"][i % 3]));
+        }
+        let f = make_csv(&csv);
+        let reader = CsvReader::open(f.path()).unwrap();
+        let stats = column_stats(&reader, &[]).unwrap();
+
+        assert!(stats[0].is_near_unique());
+        assert!(!stats[0].is_low_cardinality());
+        assert!(stats[0].distinct <= 1000);
+        assert_eq!(stats[1].distinct, 3);
+        assert!(stats[1].is_low_cardinality());
+        assert!(!stats[1].is_near_unique());
+    }
+
+    #[test]
+    fn estimates_distinct_values() {
+        let mut a = HyperLogLog::default();
+        let mut b = HyperLogLog::default();
+        for i in 0..100_000 {
+            a.insert(format!("value-{i}").as_bytes());
+            b.insert(format!("value-{}", i + 50_000).as_bytes());
+        }
+        a.merge(&b);
+        let estimate = a.estimate() as f64;
+        assert!((estimate / 150_000.0 - 1.0).abs() < 0.05, "{estimate}");
+    }
+}
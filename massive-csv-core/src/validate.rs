@@ -0,0 +1,202 @@
+use std::fmt;
+
+use rayon::prelude::*;
+
+use crate::error::{MassiveCsvError, Result};
+use crate::parser::field_count;
+use crate::reader::CsvReader;
+
+/// Rows checked per parallel batch, so the issue limit can stop the scan early.
+const CHUNK: usize = 64 * 1024;
+
+/// A structural problem found in one row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IssueKind {
+    /// The row has a different number of fields than the header.
+    FieldCount { expected: usize, found: usize },
+    /// A quoted field is never closed on this line.
+    UnbalancedQuotes,
+    /// A control character other than tab, at this byte offset in the line.
+    ControlCharacter { byte: u8, offset: usize },
+    /// The row is not valid UTF-8.
+    InvalidUtf8,
+}
+
+impl fmt::Display for IssueKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IssueKind::FieldCount { expected, found } => {
+                write!(f, "expected {expected} fields, found {found}")
+            }
+            IssueKind::UnbalancedQuotes => write!(f, "unbalanced quotes"),
+            IssueKind::ControlCharacter { byte, offset } => {
+                write!(f, "control character 0x{byte:02X} at byte {offset}")
+            }
+            IssueKind::InvalidUtf8 => write!(f, "invalid UTF-8"),
+        }
+    }
+}
+
+/// A problem in a specific row (0-indexed, excluding the header).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    pub row: usize,
+    pub kind: IssueKind,
+}
+
+/// Options controlling validation.
+#[derive(Debug, Clone, Default)]
+pub struct ValidateOptions {
+    /// Stop after recording this many issues (0 = unlimited).
+    pub max_issues: usize,
+}
+
+/// Result of [`validate`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    /// Rows checked before the scan finished or hit the issue limit.
+    pub rows_checked: usize,
+    /// Issues found, in row order.
+    pub issues: Vec<Issue>,
+    /// Whether the scan stopped early at `max_issues`.
+    pub truncated: bool,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// Number of distinct rows with at least one issue.
+    pub fn rows_with_issues(&self) -> usize {
+        let mut rows: Vec<usize> = self.issues.iter().map(|i| i.row).collect();
+        rows.dedup();
+        rows.len()
+    }
+}
+
+/// Scan every row for structural problems: field counts that differ from the
+/// header, unbalanced quotes, stray control characters and invalid UTF-8.
+pub fn validate(reader: &CsvReader, options: &ValidateOptions) -> Result<ValidationReport> {
+    let expected = reader.headers().len();
+    let dialect = reader.dialect();
+    let rows = reader.row_count();
+    let mut report = ValidationReport::default();
+
+    let mut start = 0;
+    while start < rows {
+        let end = (start + CHUNK).min(rows);
+        let chunk: Vec<Vec<Issue>> = (start..end)
+            .into_par_iter()
+            .map(|row| {
+                let raw = match reader.get_row_raw(row) {
+                    Ok(raw) => raw,
+                    Err(MassiveCsvError::InvalidUtf8(_)) => {
+                        return Ok(vec![Issue {
+                            row,
+                            kind: IssueKind::InvalidUtf8,
+                        }])
+                    }
+                    Err(e) => return Err(e),
+                };
+
+                let mut issues = Vec::new();
+                // Fixed-width rows have no delimiters or quotes to check
+                if reader.fixed_width().is_none() {
+                    let (found, balanced) = field_count(raw, &dialect);
+                    if !balanced {
+                        issues.push(IssueKind::UnbalancedQuotes);
+                    } else if found != expected {
+                        issues.push(IssueKind::FieldCount { expected, found });
+                    }
+                }
+                if let Some(offset) = raw.bytes().position(is_stray_control) {
+                    let byte = raw.as_bytes()[offset];
+                    issues.push(IssueKind::ControlCharacter { byte, offset });
+                }
+                Ok(issues.into_iter().map(|kind| Issue { row, kind }).collect())
+            })
+            .collect::<Result<_>>()?;
+
+        report.rows_checked = end;
+        report.issues.extend(chunk.into_iter().flatten());
+        if options.max_issues > 0 && report.issues.len() >= options.max_issues {
+            report.truncated = report.issues.len() > options.max_issues || end < rows;
+            report.issues.truncate(options.max_issues);
+            if let Some(last) = report.issues.last() {
+                report.rows_checked = last.row + 1;
+            }
+            break;
+        }
+        start = end;
+    }
+
+    Ok(report)
+}
+
+/// Control characters that don't belong in CSV text; tab is allowed.
+fn is_stray_control(b: u8) -> bool {
+    (b < 0x20 && b != b'\t') || b == 0x7F
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn make_csv(content: &[u8]) -> tempfile::NamedTempFile {
+        let mut f = tempfile::NamedTempFile::new().unwrap();
+        f.write_all(content).unwrap();
+        f.flush().unwrap();
+        f
+    }
+
+    #[test]
+    fn reports_structural_problems() {
+        let f = make_csv(b"a,b,c\n1,2,3\n1,2\n\"x,2,3\n1,2\x00,3\n1,\"y,z\",3,4\n\xff,2,3\n");
+        let reader = CsvReader::open(f.path()).unwrap();
+        let report = validate(&reader, &ValidateOptions::default()).unwrap();
+
+        let kinds: Vec<(usize, IssueKind)> =
+            report.issues.into_iter().map(|i| (i.row, i.kind)).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (
+                    1,
+                    IssueKind::FieldCount {
+                        expected: 3,
+                        found: 2
+                    }
+                ),
+                (2, IssueKind::UnbalancedQuotes),
+                (3, IssueKind::ControlCharacter { byte: 0, offset: 3 }),
+                (
+                    4,
+                    IssueKind::FieldCount {
+                        expected: 3,
+                        found: 4
+                    }
+                ),
+                (5, IssueKind::InvalidUtf8),
+            ]
+        );
+        assert_eq!(report.rows_checked, 6);
+        assert!(!report.truncated);
+    }
+
+    #[test]
+    fn stops_at_issue_limit() {
+        let f = make_csv(b"a,b\n1\n2\n3,4\n5\n");
+        let reader = CsvReader::open(f.path()).unwrap();
+        let options = ValidateOptions { max_issues: 2 };
+        let report = validate(&reader, &options).unwrap();
+        assert_eq!(report.issues.len(), 2);
+        assert_eq!(report.rows_with_issues(), 2);
+        assert!(report.truncated);
+
+        let f = make_csv(b"a,b\n1,2\n");
+        let reader = CsvReader::open(f.path()).unwrap();
+        assert!(validate(&reader, &options).unwrap().is_valid());
+    }
+}
@@ -0,0 +1,174 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use crate::editor::write_atomic;
+use crate::error::{MassiveCsvError, Result};
+use crate::META_DIR;
+
+const SNAPSHOT_EXT: &str = ".csv.gz";
+
+/// A stored snapshot of a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Version {
+    /// Identifier used to restore this version (milliseconds since the Unix epoch).
+    pub id: String,
+    /// When the snapshot was taken.
+    pub created: SystemTime,
+    /// Size of the compressed snapshot in bytes.
+    pub compressed_size: u64,
+    /// Location of the snapshot on disk.
+    pub path: PathBuf,
+}
+
+/// Simple built-in version history for a single file.
+///
+/// Snapshots are gzip-compressed copies stored under
+/// `.massive-csv/versions/<file name>/` next to the file.
+pub struct VersionStore {
+    file: PathBuf,
+    dir: PathBuf,
+}
+
+impl VersionStore {
+    /// Version store for the file at `path`. Nothing is created until the first snapshot.
+    pub fn for_file(path: &Path) -> Self {
+        let parent = match path.parent() {
+            Some(p) if !p.as_os_str().is_empty() => p,
+            _ => Path::new("."),
+        };
+        let name = path.file_name().unwrap_or(path.as_os_str());
+        Self {
+            file: path.to_path_buf(),
+            dir: parent.join(META_DIR).join("versions").join(name),
+        }
+    }
+
+    /// Directory the snapshots are stored in.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Store a compressed copy of the file's current contents.
+    pub fn snapshot(&self) -> Result<Version> {
+        fs::create_dir_all(&self.dir)?;
+
+        let mut millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or(0);
+        // Two saves in the same millisecond must not overwrite each other
+        while self.snapshot_path(&millis.to_string()).exists() {
+            millis += 1;
+        }
+        let id = millis.to_string();
+        let path = self.snapshot_path(&id);
+
+        let mut source = BufReader::new(File::open(&self.file)?);
+        write_atomic(&path, |writer| {
+            let mut encoder = GzEncoder::new(writer, Compression::fast());
+            io::copy(&mut source, &mut encoder)?;
+            encoder.finish()?.flush()?;
+            Ok(())
+        })?;
+
+        self.version_at(id, path)
+    }
+
+    /// All stored versions, oldest first.
+    pub fn list(&self) -> Result<Vec<Version>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut versions = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if let Some(id) = name.strip_suffix(SNAPSHOT_EXT) {
+                if id.parse::<u128>().is_ok() {
+                    versions.push(self.version_at(id.to_string(), entry.path())?);
+                }
+            }
+        }
+
+        versions.sort_by_key(|v| v.id.parse::<u128>().unwrap_or(0));
+        Ok(versions)
+    }
+
+    /// Replace the file with the version `id`.
+    ///
+    /// The current contents are snapshotted first so a restore can itself be
+    /// undone; that snapshot is returned.
+    pub fn restore(&self, id: &str) -> Result<Version> {
+        let path = self.snapshot_path(id);
+        if !path.is_file() {
+            return Err(MassiveCsvError::VersionNotFound(id.to_string()));
+        }
+
+        let previous = self.snapshot()?;
+
+        let mut decoder = GzDecoder::new(BufReader::new(File::open(&path)?));
+        write_atomic(&self.file, |writer| {
+            io::copy(&mut decoder, writer)?;
+            Ok(())
+        })?;
+
+        Ok(previous)
+    }
+
+    fn snapshot_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{id}{SNAPSHOT_EXT}"))
+    }
+
+    fn version_at(&self, id: String, path: PathBuf) -> Result<Version> {
+        let millis: u64 = id.parse().unwrap_or(0);
+        Ok(Version {
+            created: UNIX_EPOCH + std::time::Duration::from_millis(millis),
+            compressed_size: fs::metadata(&path)?.len(),
+            id,
+            path,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_list_restore() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.csv");
+        fs::write(&path, "a\n1\n").unwrap();
+
+        let store = VersionStore::for_file(&path);
+        assert!(store.list().unwrap().is_empty());
+
+        let v1 = store.snapshot().unwrap();
+        fs::write(&path, "a\n2\n").unwrap();
+
+        let previous = store.restore(&v1.id).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a\n1\n");
+
+        let ids: Vec<String> = store.list().unwrap().into_iter().map(|v| v.id).collect();
+        assert_eq!(ids, vec![v1.id, previous.id]);
+    }
+
+    #[test]
+    fn restore_unknown_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.csv");
+        fs::write(&path, "a\n1\n").unwrap();
+
+        let result = VersionStore::for_file(&path).restore("123");
+        assert!(matches!(result, Err(MassiveCsvError::VersionNotFound(_))));
+    }
+}
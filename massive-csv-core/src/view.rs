@@ -0,0 +1,254 @@
+//! Filtered and sorted projections of a file, read through from the
+//! mapped file without writing a copy.
+
+use std::sync::Arc;
+
+use crate::error::{MassiveCsvError, Result};
+use crate::filter::Filter;
+use crate::parser::column_position;
+use crate::reader::CsvReader;
+use crate::sort::{sort_rows, SortKey};
+
+/// Some of a file's rows in some order, e.g. "rows where status is error,
+/// by timestamp". Row numbers given to a view are positions in it; the
+/// file row behind each is [`file_row`](Self::file_row).
+///
+/// Only the row numbers are held in memory. They are shared, so the list
+/// can outlive the borrow of the reader, e.g. to edit through
+/// [`CsvEditor::set_cell_in_view`](crate::CsvEditor::set_cell_in_view).
+#[derive(Clone)]
+pub struct CsvView<'a> {
+    reader: &'a CsvReader,
+    rows: Arc<[usize]>,
+}
+
+impl<'a> CsvView<'a> {
+    /// Every row, in file order.
+    pub fn all(reader: &'a CsvReader) -> Self {
+        Self {
+            reader,
+            rows: (0..reader.row_count()).collect(),
+        }
+    }
+
+    /// The given file rows, in the given order.
+    pub fn new(reader: &'a CsvReader, rows: impl Into<Arc<[usize]>>) -> Result<Self> {
+        let rows = rows.into();
+        let count = reader.row_count();
+        if let Some(&row) = rows.iter().find(|&&row| row >= count) {
+            return Err(MassiveCsvError::RowOutOfRange(row, count));
+        }
+        Ok(Self { reader, rows })
+    }
+
+    /// The rows matching `filter`, in file order.
+    pub fn filtered(reader: &'a CsvReader, filter: &Filter) -> Result<Self> {
+        Ok(Self {
+            reader,
+            rows: filter.matching_rows(reader, 0)?.into(),
+        })
+    }
+
+    /// This view's rows ordered by `keys`, as `sort` would order them; rows
+    /// with equal keys keep their order in this view.
+    pub fn sorted(&self, keys: &[SortKey]) -> Result<Self> {
+        Ok(Self {
+            reader: self.reader,
+            rows: sort_rows(self.reader, self.rows.to_vec(), keys)?.into(),
+        })
+    }
+
+    /// The reader the view reads from.
+    pub fn reader(&self) -> &'a CsvReader {
+        self.reader
+    }
+
+    /// The headers of the file.
+    pub fn headers(&self) -> &'a [String] {
+        self.reader.headers()
+    }
+
+    /// Number of rows in the view.
+    pub fn row_count(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// The file rows in view order.
+    pub fn rows(&self) -> &Arc<[usize]> {
+        &self.rows
+    }
+
+    /// The file row shown at `row` of the view.
+    pub fn file_row(&self, row: usize) -> Result<usize> {
+        resolve(&self.rows, row)
+    }
+
+    /// Where file row `file_row` appears in the view, if it does.
+    pub fn position_of(&self, file_row: usize) -> Option<usize> {
+        self.rows.iter().position(|&r| r == file_row)
+    }
+
+    /// Get row `row` of the view parsed into fields.
+    pub fn get_row(&self, row: usize) -> Result<Vec<String>> {
+        self.reader.get_row(self.file_row(row)?)
+    }
+
+    /// Get rows `start..end` of the view (clamped to its length).
+    pub fn get_rows(&self, start: usize, end: usize) -> Result<Vec<Vec<String>>> {
+        let end = end.min(self.rows.len());
+        let start = start.min(end);
+        self.rows[start..end]
+            .iter()
+            .map(|&row| self.reader.get_row(row))
+            .collect()
+    }
+}
+
+/// Resolve a selection of columns against `headers`, in the order given.
+/// Each item is a column name, a 0-based index, or an inclusive range of
+/// indices such as `3-7`; names are tried first, so a column called `2` is
+/// found by name.
+pub fn select_columns<S: AsRef<str>>(headers: &[String], items: &[S]) -> Result<Vec<usize>> {
+    let index = |s: &str| -> Result<Option<usize>> {
+        let Ok(i) = s.trim().parse::<usize>() else {
+            return Ok(None);
+        };
+        if i >= headers.len() {
+            return Err(MassiveCsvError::ColumnNotFound(format!("index {i}")));
+        }
+        Ok(Some(i))
+    };
+    let mut columns = Vec::new();
+    for item in items {
+        let item = item.as_ref().trim();
+        if let Some(col) = column_position(headers, item) {
+            columns.push(col);
+        } else if let Some(col) = index(item)? {
+            columns.push(col);
+        } else {
+            let range = match item.split_once('-') {
+                Some((first, last)) => index(first)?.zip(index(last)?),
+                None => None,
+            };
+            let (first, last) =
+                range.ok_or_else(|| MassiveCsvError::ColumnNotFound(item.to_string()))?;
+            if first <= last {
+                columns.extend(first..=last);
+            } else {
+                columns.extend((last..=first).rev());
+            }
+        }
+    }
+    Ok(columns)
+}
+
+/// The fields at `columns`, in that order; missing fields are empty.
+pub fn project(fields: &[String], columns: &[usize]) -> Vec<String> {
+    columns
+        .iter()
+        .map(|&c| fields.get(c).cloned().unwrap_or_default())
+        .collect()
+}
+
+/// The file row at position `row` of a view's `rows`.
+pub(crate) fn resolve(rows: &[usize], row: usize) -> Result<usize> {
+    rows.get(row)
+        .copied()
+        .ok_or(MassiveCsvError::RowOutOfRange(row, rows.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::CsvEditor;
+    use std::io::Write;
+
+    fn make_csv(content: &str) -> tempfile::NamedTempFile {
+        let mut f = tempfile::NamedTempFile::new().unwrap();
+        f.write_all(content.as_bytes()).unwrap();
+        f.flush().unwrap();
+        f
+    }
+
+    const LOG: &str = "ts,status\n5,ok\n3,error\n9,error\n1,ok\n7,error\n";
+
+    #[test]
+    fn filters_and_sorts_without_copying() {
+        let f = make_csv(LOG);
+        let reader = CsvReader::open(f.path()).unwrap();
+        let errors = Filter::parse("status == error", reader.headers()).unwrap();
+
+        let view = CsvView::filtered(&reader, &errors).unwrap();
+        assert_eq!(view.row_count(), 3);
+        assert_eq!(&view.rows()[..], &[1, 2, 4]);
+
+        let view = view.sorted(&[SortKey::new("ts")]).unwrap();
+        assert_eq!(&view.rows()[..], &[1, 4, 2]);
+        assert_eq!(view.get_row(1).unwrap(), vec!["7", "error"]);
+        assert_eq!(
+            view.get_rows(1, 10).unwrap(),
+            vec![vec!["7", "error"], vec!["9", "error"]]
+        );
+        assert_eq!(view.file_row(2).unwrap(), 2);
+        assert_eq!(view.position_of(4), Some(1));
+        assert_eq!(view.position_of(0), None);
+        assert!(matches!(
+            view.get_row(3),
+            Err(MassiveCsvError::RowOutOfRange(3, 3))
+        ));
+
+        assert!(CsvView::new(&reader, vec![0, 5]).is_err());
+        assert_eq!(CsvView::all(&reader).row_count(), 5);
+    }
+
+    #[test]
+    fn edits_through_a_view() {
+        let f = make_csv(LOG);
+        let mut editor = CsvEditor::open(f.path()).unwrap();
+        let rows = {
+            let reader = editor.reader();
+            let errors = Filter::parse("status == error", reader.headers()).unwrap();
+            let view = CsvView::filtered(reader, &errors).unwrap();
+            view.sorted(&[SortKey::parse("ts:desc").unwrap()])
+                .unwrap()
+                .rows()
+                .clone()
+        };
+
+        // The latest error is file row 2
+        assert_eq!(
+            editor
+                .set_cell_in_view(&rows, 0, 1, "fixed".into())
+                .unwrap(),
+            2
+        );
+        assert_eq!(editor.get_row(2).unwrap(), vec!["9", "fixed"]);
+        assert!(editor.set_cell_in_view(&rows, 3, 1, "x".into()).is_err());
+    }
+
+    #[test]
+    fn selects_columns_by_name_index_and_range() {
+        let headers: Vec<String> = ["id", "name", "2", "a", "b", "c"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(
+            select_columns(&headers, &["name", "0", "3-5"]).unwrap(),
+            vec![1, 0, 3, 4, 5]
+        );
+        // Names win over indices; reversed ranges run backwards
+        assert_eq!(
+            select_columns(&headers, &["2", "4-3"]).unwrap(),
+            vec![2, 4, 3]
+        );
+        assert!(matches!(
+            select_columns(&headers, &["9"]),
+            Err(MassiveCsvError::ColumnNotFound(_))
+        ));
+        assert!(select_columns(&headers, &["status"]).is_err());
+        assert!(select_columns(&headers, &["1-x"]).is_err());
+
+        let fields: Vec<String> = vec!["1".into(), "Ada".into()];
+        assert_eq!(project(&fields, &[1, 0, 4]), vec!["Ada", "1", ""]);
+    }
+}
@@ -0,0 +1,280 @@
+//! Named views and row bookmarks saved next to a file, to be restored the
+//! next time it is opened.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::editor::write_atomic;
+use crate::error::{MassiveCsvError, Result};
+use crate::filter::{Expr, Filter};
+use crate::reader::CsvReader;
+use crate::sort::SortKey;
+use crate::view::CsvView;
+use crate::META_DIR;
+
+/// A named way of looking at a file: which rows, in which order, showing
+/// which columns.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedView {
+    pub name: String,
+    /// Filter expression the rows must match, as in `filter`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filter: Option<String>,
+    /// Sort keys as `column[:asc|:desc][:num|:text]`, most significant first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sort: Vec<String>,
+    /// Columns to show, in order; empty shows all of them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub columns: Vec<String>,
+}
+
+impl SavedView {
+    /// A view of every row and column.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            ..Self::default()
+        }
+    }
+
+    /// The view's sort keys.
+    pub fn sort_keys(&self) -> Result<Vec<SortKey>> {
+        self.sort.iter().map(|spec| SortKey::parse(spec)).collect()
+    }
+
+    /// The rows of `reader` this view shows, in its order.
+    pub fn apply<'a>(&self, reader: &'a CsvReader) -> Result<CsvView<'a>> {
+        let view = match &self.filter {
+            Some(expr) => CsvView::filtered(reader, &Filter::parse(expr, reader.headers())?)?,
+            None => CsvView::all(reader),
+        };
+        let keys = self.sort_keys()?;
+        if keys.is_empty() {
+            return Ok(view);
+        }
+        view.sorted(&keys)
+    }
+
+    /// Indices of the columns this view shows, in its order.
+    pub fn column_indices(&self, reader: &CsvReader) -> Result<Vec<usize>> {
+        if self.columns.is_empty() {
+            return Ok((0..reader.headers().len()).collect());
+        }
+        self.columns
+            .iter()
+            .map(|name| {
+                reader
+                    .column_index(name)
+                    .ok_or_else(|| MassiveCsvError::ColumnNotFound(name.clone()))
+            })
+            .collect()
+    }
+
+    fn check(&self) -> Result<()> {
+        if self.name.is_empty() {
+            return Err(MassiveCsvError::Parse("view name is empty".to_string()));
+        }
+        if let Some(expr) = &self.filter {
+            Expr::parse(expr)?;
+        }
+        self.sort_keys()?;
+        Ok(())
+    }
+}
+
+/// A bookmarked row, with an optional note.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bookmark {
+    pub row: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Sidecar {
+    #[serde(default)]
+    views: Vec<SavedView>,
+    #[serde(default)]
+    bookmarks: Vec<Bookmark>,
+}
+
+/// Saved views and bookmarks for a single file.
+///
+/// They are kept as JSON in `.massive-csv/views/<file name>.json` next to
+/// the file, rewritten on every change.
+pub struct ViewStore {
+    path: PathBuf,
+    data: Sidecar,
+}
+
+impl ViewStore {
+    /// Views and bookmarks saved for the file at `path`. Nothing is created
+    /// until the first change.
+    pub fn for_file(path: &Path) -> Result<Self> {
+        let parent = match path.parent() {
+            Some(p) if !p.as_os_str().is_empty() => p,
+            _ => Path::new("."),
+        };
+        let mut name = path.file_name().unwrap_or(path.as_os_str()).to_owned();
+        name.push(".json");
+        let path = parent.join(META_DIR).join("views").join(name);
+        let data = match fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Sidecar::default(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self { path, data })
+    }
+
+    /// Location of the sidecar file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Saved views, in the order they were first saved.
+    pub fn views(&self) -> &[SavedView] {
+        &self.data.views
+    }
+
+    /// The view called `name`.
+    pub fn view(&self, name: &str) -> Option<&SavedView> {
+        self.data.views.iter().find(|v| v.name == name)
+    }
+
+    /// Save `view`, replacing any view with the same name.
+    ///
+    /// The filter and sort keys are checked for syntax; columns are only
+    /// resolved when the view is applied, since the file may change.
+    pub fn save_view(&mut self, view: SavedView) -> Result<()> {
+        view.check()?;
+        match self.data.views.iter_mut().find(|v| v.name == view.name) {
+            Some(existing) => *existing = view,
+            None => self.data.views.push(view),
+        }
+        self.write()
+    }
+
+    /// Remove the view called `name`. Returns whether there was one.
+    pub fn remove_view(&mut self, name: &str) -> Result<bool> {
+        let before = self.data.views.len();
+        self.data.views.retain(|v| v.name != name);
+        if self.data.views.len() == before {
+            return Ok(false);
+        }
+        self.write()?;
+        Ok(true)
+    }
+
+    /// Bookmarked rows, in row order.
+    pub fn bookmarks(&self) -> &[Bookmark] {
+        &self.data.bookmarks
+    }
+
+    /// Bookmark `row`, replacing the label of an existing bookmark on it.
+    pub fn add_bookmark(&mut self, row: usize, label: Option<String>) -> Result<()> {
+        let bookmarks = &mut self.data.bookmarks;
+        match bookmarks.binary_search_by_key(&row, |b| b.row) {
+            Ok(i) => bookmarks[i].label = label,
+            Err(i) => bookmarks.insert(i, Bookmark { row, label }),
+        }
+        self.write()
+    }
+
+    /// Remove the bookmark on `row`. Returns whether there was one.
+    pub fn remove_bookmark(&mut self, row: usize) -> Result<bool> {
+        match self.data.bookmarks.binary_search_by_key(&row, |b| b.row) {
+            Ok(i) => {
+                self.data.bookmarks.remove(i);
+                self.write()?;
+                Ok(true)
+            }
+            Err(_) => Ok(false),
+        }
+    }
+
+    fn write(&self) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        write_atomic(&self.path, |writer| {
+            serde_json::to_writer_pretty(&mut *writer, &self.data)?;
+            writer.write_all(b"\n")?;
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saves_and_restores_views() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("log.csv");
+        fs::write(
+            &file,
+            "ts,status,msg\n5,ok,a\n3,error,b\n9,error,c\n1,ok,d\n",
+        )
+        .unwrap();
+
+        let mut store = ViewStore::for_file(&file).unwrap();
+        assert!(store.views().is_empty());
+        store
+            .save_view(SavedView {
+                filter: Some("status == error".to_string()),
+                sort: vec!["ts:desc".to_string()],
+                columns: vec!["msg".to_string(), "ts".to_string()],
+                ..SavedView::new("errors_only")
+            })
+            .unwrap();
+        store.add_bookmark(3, Some("odd".to_string())).unwrap();
+        store.add_bookmark(1, None).unwrap();
+        assert_eq!(
+            store.path(),
+            dir.path().join(".massive-csv/views/log.csv.json")
+        );
+
+        let store = ViewStore::for_file(&file).unwrap();
+        let saved = store.view("errors_only").unwrap();
+        let reader = CsvReader::open(&file).unwrap();
+        assert_eq!(&saved.apply(&reader).unwrap().rows()[..], &[2, 1]);
+        assert_eq!(saved.column_indices(&reader).unwrap(), vec![2, 0]);
+        assert_eq!(
+            store.bookmarks().iter().map(|b| b.row).collect::<Vec<_>>(),
+            vec![1, 3]
+        );
+    }
+
+    #[test]
+    fn replaces_and_removes() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("a.csv");
+        let mut store = ViewStore::for_file(&file).unwrap();
+
+        store.save_view(SavedView::new("v")).unwrap();
+        store
+            .save_view(SavedView {
+                sort: vec!["x".to_string()],
+                ..SavedView::new("v")
+            })
+            .unwrap();
+        assert_eq!(store.views().len(), 1);
+        assert_eq!(store.views()[0].sort, vec!["x"]);
+        assert!(store
+            .save_view(SavedView {
+                filter: Some("x ==".to_string()),
+                ..SavedView::new("bad")
+            })
+            .is_err());
+
+        assert!(store.remove_view("v").unwrap());
+        assert!(!store.remove_view("v").unwrap());
+        store.add_bookmark(4, None).unwrap();
+        assert!(store.remove_bookmark(4).unwrap());
+        assert!(!store.remove_bookmark(4).unwrap());
+        assert!(ViewStore::for_file(&file).unwrap().views().is_empty());
+    }
+}
@@ -0,0 +1,156 @@
+//! Writing rows to an Excel workbook (`.xlsx`). Needs the `xlsx` feature.
+//!
+//! Sheets are written in constant-memory mode, so only the current row is
+//! held in memory. A sheet holds at most [`MAX_SHEET_ROWS`] rows including
+//! its header; longer tables continue on `Sheet2`, `Sheet3` and so on, each
+//! starting with the header row again.
+
+use std::io::{self, Seek, Write};
+
+use rust_xlsxwriter::{Format, Workbook, XlsxError};
+
+use crate::error::{MassiveCsvError, Result};
+use crate::infer::{parse_integer, ColumnType};
+
+/// Rows in an Excel worksheet, the header included.
+pub const MAX_SHEET_ROWS: u32 = 1_048_576;
+
+/// Largest integer Excel stores exactly (it keeps numbers as doubles).
+const MAX_EXACT_INTEGER: i64 = 1 << 53;
+
+/// Streams rows into a workbook.
+pub struct XlsxWriter {
+    workbook: Workbook,
+    headers: Vec<String>,
+    bold: Format,
+    sheets: usize,
+    /// Next row in the current sheet.
+    next_row: u32,
+    rows: u64,
+}
+
+impl XlsxWriter {
+    pub fn new(headers: &[String]) -> Result<Self> {
+        let mut writer = Self {
+            workbook: Workbook::new(),
+            headers: headers.to_vec(),
+            bold: Format::new().set_bold(),
+            sheets: 0,
+            next_row: 0,
+            rows: 0,
+        };
+        writer.add_sheet()?;
+        Ok(writer)
+    }
+
+    /// Append a row. Fields that look like numbers (see
+    /// [`ColumnType::of`]) are written as numbers, everything else as text;
+    /// integers beyond 2^53, which Excel would round, stay text.
+    pub fn write_row(&mut self, fields: &[String]) -> Result<()> {
+        if self.next_row == MAX_SHEET_ROWS {
+            self.add_sheet()?;
+        }
+        let row = self.next_row;
+        let sheet = self
+            .workbook
+            .worksheet_from_index(self.sheets - 1)
+            .map_err(xlsx_error)?;
+        for (col, field) in fields.iter().enumerate() {
+            let col = column_number(col)?;
+            let number = match ColumnType::of(field) {
+                Some(ColumnType::Integer) => parse_integer(field)
+                    .filter(|v| v.unsigned_abs() <= MAX_EXACT_INTEGER as u64)
+                    .map(|v| v as f64),
+                Some(ColumnType::Float) => field.parse::<f64>().ok().filter(|v| v.is_finite()),
+                _ => None,
+            };
+            match number {
+                Some(number) => sheet.write_number(row, col, number),
+                None if field.is_empty() => continue,
+                None => sheet.write_string(row, col, field),
+            }
+            .map_err(xlsx_error)?;
+        }
+        self.next_row += 1;
+        self.rows += 1;
+        Ok(())
+    }
+
+    /// Number of rows written, not counting headers.
+    pub fn row_count(&self) -> u64 {
+        self.rows
+    }
+
+    /// Number of worksheets used.
+    pub fn sheet_count(&self) -> usize {
+        self.sheets
+    }
+
+    /// Write the finished workbook to `out`.
+    pub fn finish(mut self, out: &mut dyn Write) -> Result<()> {
+        // The workbook wants a `Send` writer; assemble it in a temporary file
+        let mut file = tempfile::tempfile()?;
+        self.workbook
+            .save_to_writer(&mut file)
+            .map_err(xlsx_error)?;
+        file.rewind()?;
+        io::copy(&mut file, out)?;
+        Ok(())
+    }
+
+    /// Start a new sheet with a bold, frozen header row.
+    fn add_sheet(&mut self) -> Result<()> {
+        self.sheets += 1;
+        let sheet = self.workbook.add_worksheet_with_constant_memory();
+        for (col, header) in self.headers.iter().enumerate() {
+            sheet
+                .write_string_with_format(0, column_number(col)?, header, &self.bold)
+                .map_err(xlsx_error)?;
+        }
+        sheet.set_freeze_panes(1, 0).map_err(xlsx_error)?;
+        self.next_row = 1;
+        Ok(())
+    }
+}
+
+fn column_number(col: usize) -> Result<u16> {
+    // Excel's last column is XFD, the 16,384th
+    u16::try_from(col)
+        .ok()
+        .filter(|&c| c < 16_384)
+        .ok_or_else(|| MassiveCsvError::Xlsx("more than 16,384 columns".to_string()))
+}
+
+fn xlsx_error(e: XlsxError) -> MassiveCsvError {
+    MassiveCsvError::Xlsx(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_a_workbook() {
+        let mut writer = XlsxWriter::new(&["id".to_string(), "note".to_string()]).unwrap();
+        writer
+            .write_row(&["1".to_string(), "007".to_string()])
+            .unwrap();
+        writer
+            .write_row(&["2.5".to_string(), String::new()])
+            .unwrap();
+        assert_eq!(writer.row_count(), 2);
+        assert_eq!(writer.sheet_count(), 1);
+
+        let mut out = Vec::new();
+        writer.finish(&mut out).unwrap();
+        // A zip archive
+        assert!(out.starts_with(b"PK\x03\x04"));
+
+        let long = vec!["x".repeat(40_000)];
+        let mut writer = XlsxWriter::new(&["a".to_string()]).unwrap();
+        assert!(matches!(
+            writer.write_row(&long),
+            Err(MassiveCsvError::Xlsx(_))
+        ));
+    }
+}
@@ -5,40 +5,548 @@
  *
  * Wraps CsvEditor which itself wraps CsvReader, providing
  * memory-mapped reading, parallel search, edit tracking, and atomic save.
+ *
+ * Opening, reading pages of rows, searching and saving return Promises and
+ * run on the libuv thread pool, so a large file doesn't block the event
+ * loop. Other calls on the document wait for them to finish. They report
+ * progress to listeners added with `on`, and take an AbortSignal that
+ * stops them at their next progress update and rejects with "Cancelled".
+ *
+ * Call `close()` when done: the file stays mapped, and on Windows can't be
+ * deleted or replaced, until then or until the object is garbage collected.
  */
 export declare class CsvDocument {
-  /** Open a CSV file and return a CsvDocument. */
-  static open(path: string): CsvDocument
+  /**
+   * Open a CSV file, resolving to a CsvDocument once its rows are indexed.
+   * `onProgress` is called with the bytes indexed so far, and stays an
+   * "openProgress" listener of the document, for `reload`.
+   */
+  static open(path: string, options?: JsOpenOptions | undefined | null, onProgress?: ((progress: JsProgress) => void) | undefined | null, signal?: AbortSignal | undefined | null): Promise<CsvDocument>
+  /**
+   * Open a CSV file as `open` does, with `options` such as
+   * `{ delimiter, hasHeaders, encoding, readOnly }`.
+   */
+  static openWithOptions(path: string, options: JsOpenOptions, onProgress?: ((progress: JsProgress) => void) | undefined | null, signal?: AbortSignal | undefined | null): Promise<CsvDocument>
+  /**
+   * Release the file: drop the editor and its memory map, discarding
+   * pending edits, and stop index builds. Waits for a running open,
+   * read, search or save to finish first. Every later call fails,
+   * except `close` and `dispose`, which do nothing.
+   */
+  close(): void
+  /** Same as `close`, for `using` declarations and disposal helpers. */
+  dispose(): void
+  /**
+   * Call `callback` with a `{ done, total }` progress object as a long operation runs:
+   * "openProgress" for reloads (bytes indexed), "saveProgress" for saves (rows written)
+   * and "searchProgress" for searches (rows searched).
+   */
+  on(event: 'openProgress' | 'saveProgress' | 'searchProgress', callback: (progress: JsProgress) => void): void
+  /** Remove every listener for `event`. */
+  off(event: string): void
   /** Get file metadata. */
   getInfo(): CsvInfo
   /** Get a single row (returns edited version if modified). */
   getRow(row: number): Array<string>
+  /** Describe what is wrong with a row as stored on disk, or null if it is well-formed. */
+  getRowIssue(row: number): string | null
   /** Get a range of rows [start, end). Returns edited versions where applicable. */
-  getRows(start: number, end: number): Array<Array<string>>
-  /** Search for rows matching a query. */
-  search(query: string, options?: JsSearchOptions | undefined | null): Array<JsSearchResult>
+  getRows(start: number, end: number): Promise<Array<Array<string>>>
+  /**
+   * Get a range of rows [start, end) as one Buffer, so a large window
+   * crosses into JS as a single object rather than a string per value:
+   * a u32 row count, then for each row a u32 field count and each field
+   * as a u32 byte length and its UTF-8 bytes, all little-endian. Rows are
+   * as from getRows.
+   */
+  getRowsBuffer(start: number, end: number): Promise<Buffer>
+  /**
+   * Get `limit` rows from `offset`, with the document's row count, for a
+   * virtually scrolled grid. Only `columns` (indices) are returned if given.
+   * The next page in the direction of scrolling is read ahead into a small
+   * cache, so scrolling on rarely waits for the file.
+   */
+  getPage(offset: number, limit: number, columns?: Array<number> | undefined | null): Promise<JsPage>
+  /**
+   * Get a range of rows [start, end) as an Arrow IPC stream of string
+   * columns, for `tableFromIPC` in arrow-js or DuckDB-Wasm. Returns
+   * edited versions where applicable.
+   */
+  getRowsArrow(start: number, end: number): Buffer
+  /**
+   * Search for rows matching a query. Resolves to the matches selected by offset and
+   * maxResults, each with where the query occurs in it, and the number of matching rows.
+   */
+  search(query: string, options?: JsSearchOptions | undefined | null, signal?: AbortSignal | undefined | null): Promise<JsSearchResults>
+  /**
+   * Search like `search`, but call onBatch with the matches of each batch of rows as soon as
+   * it has been scanned, so they can be listed while the rest of the file is searched. Resolves
+   * to the number of matches passed to onBatch, after its last call. The document can be used
+   * meanwhile, but saving or reloading it fails the search.
+   */
+  searchStream(query: string, options: JsSearchOptions | undefined | null, onBatch: (results: Array<JsSearchResult>) => void, signal?: AbortSignal | undefined | null): Promise<number>
+  /**
+   * Row numbers matching a filter expression, in order, for showing a
+   * filtered view (0 or no maxResults = all matches).
+   */
+  filterRows(expr: string, maxResults?: number | undefined | null): Array<number>
+  /**
+   * Summary statistics of a column of the file as saved: counts, lengths, an
+   * estimate of distinct values and, for numeric columns, min, max, mean and
+   * percentiles. Reads every row.
+   */
+  getColumnStats(column: string): Promise<JsColumnStats>
+  /**
+   * The type of each column of the file as saved, from up to `sampleRows` rows
+   * spread over it (default 100,000).
+   */
+  inferSchema(sampleRows?: number | undefined | null): Promise<Array<JsInferredColumn>>
+  /**
+   * The `topN` most frequent values of a column of the file as saved, most
+   * frequent first (0 or no topN = all of them). Empty cells count as "".
+   */
+  getValueCounts(column: string, topN?: number | undefined | null): Promise<JsValueCounts>
+  /**
+   * Add a computed column, e.g. "total = price * qty", shown after the
+   * file's columns (and earlier computed ones, which it may use) by
+   * getRow, getRows and search.
+   */
+  addComputedColumn(definition: string): void
+  /**
+   * Remove a computed column and every later one using it. Returns
+   * whether there was one.
+   */
+  removeComputedColumn(name: string): boolean
+  /**
+   * Write the computed columns into the file as real columns, to `output`
+   * or over the file itself (which needs no pending edits; the document
+   * then reloads it). Returns the number of rows written.
+   */
+  materializeComputedColumns(output?: string | undefined | null): number
+  /** Export the file as saved on disk (pending edits are not included). */
+  export(output: string, options?: JsExportOptions | undefined | null): JsExportSummary
   /** Edit a single cell. */
   setCell(row: number, col: number, value: string): void
   /** Replace an entire row. */
   setRow(row: number, fields: Array<string>): void
+  /**
+   * Move rows `start..end` so the first ends up at `dest`, e.g. after a
+   * drag and drop. Row numbers afterwards follow the new order; it is
+   * written on save.
+   */
+  moveRows(start: number, end: number, dest: number): void
+  /**
+   * Insert a row at `row` (or at rowCount to append), empty unless `fields`
+   * are given; rows from `row` on move down one. Written on save.
+   */
+  insertRow(row: number, fields?: Array<string> | undefined | null): JsStructureChange
+  /** Delete rows [start, end); the rows after them move up. Written on save. */
+  deleteRows(start: number, end: number): JsStructureChange
+  /**
+   * Add a column `name` after the file's columns, holding `defaultValue`
+   * (default empty) in every row. Written on save.
+   */
+  addColumn(name: string, defaultValue?: string | undefined | null): JsStructureChange
+  /** Rename the column `from` to `to`. Written on save. */
+  renameColumn(from: string, to: string): JsStructureChange
+  /**
+   * Drop the column `column`; the columns after it move left. Computed
+   * columns using it are removed too. Written on save.
+   */
+  dropColumn(column: string): JsStructureChange
   /** Revert a single row to its original state. */
   revertRow(row: number): void
   /** Revert all pending edits. */
   revertAll(): void
+  /**
+   * Undo the latest change: an edit, an insert, delete or move of rows, a column
+   * change or a revert. Returns false if there was nothing to undo. Rows and
+   * columns may have changed, so re-read them with getInfo and getPage.
+   */
+  undo(): boolean
+  /** Redo the latest change undone. Returns false if there was nothing to redo. */
+  redo(): boolean
+  /** Whether undo has a change to undo. Saving or reloading clears the history. */
+  get canUndo(): boolean
+  /** Whether redo has a change to redo. */
+  get canRedo(): boolean
+  /** Rows with pending edits, inserted rows included, in order. */
+  getEditedRows(): Array<number>
+  /** A row before and after its pending edits, with the columns that changed. */
+  getRowDiff(row: number): JsRowDiff
+  /** Whether a cell has a pending edit that changed its value, for marking it in the grid. */
+  isCellEdited(row: number, col: number): boolean
   /** Save all pending edits atomically. */
-  save(): void
+  save(signal?: AbortSignal | undefined | null): Promise<void>
+  /** Save all pending edits atomically, with extra options. */
+  saveWithOptions(options: JsSaveOptions, signal?: AbortSignal | undefined | null): Promise<void>
+  /** Write the current state to a different file, leaving the source and pending edits untouched. */
+  saveAs(path: string, options?: JsSaveOptions | undefined | null, signal?: AbortSignal | undefined | null): Promise<void>
+  /**
+   * Save all pending edits, overwriting the file even if it changed on disk.
+   * Reports no progress and can only be aborted before it starts.
+   */
+  saveForce(signal?: AbortSignal | undefined | null): Promise<void>
+  /**
+   * Call `onChanged` (the `changed` event) whenever something other than
+   * this document modifies or removes the file, e.g. to offer a reload.
+   * Replaces any earlier watch.
+   */
+  watch(onChanged: (change: JsFileChange) => void): void
+  /** Stop watching the file. */
+  unwatch(): void
+  /**
+   * Re-open the file from disk, e.g. after a `changed` event, keeping
+   * pending edits that still apply: edits to rows past the new end are
+   * dropped, and it fails if a column added, renamed or dropped here is
+   * gone, or if rows were moved, inserted or deleted and the row count
+   * changed.
+   * Resolves to the number of edits kept.
+   */
+  reload(): Promise<number>
+  /** Re-open the file from disk and re-apply pending edits. Returns the number of edits kept. */
+  reloadAndReapply(): number
+  /** Start recording edits to `<file>.journal` as they are made, for crash recovery. */
+  enableJournal(): void
+  /** Replay edits from a journal left by an earlier session. Returns the number of pending edits. */
+  recover(): number
+  /** Delete the edit journal and stop journaling. */
+  discardJournal(): void
+  /** Whether a journal from an earlier session is waiting to be recovered. */
+  get hasRecoverableJournal(): boolean
+  /**
+   * Start building an index on a column in the background.
+   * Poll `indexStatus` to see when it is ready.
+   */
+  buildIndex(column: string): void
+  /**
+   * Status of a background index build: "pending", "building:<percent>",
+   * "ready", "failed: <reason>", or "none" if no build was started.
+   */
+  indexStatus(column: string): string
+  /**
+   * Attach a schema (JSON, as for `massive-csv validate --schema`) that
+   * `setCell` and `setRow` check edits against; null detaches it.
+   */
+  setSchema(schema?: string | undefined | null): void
+  /**
+   * Check every row, with pending edits, against the attached schema
+   * (0 or no maxIssues = all violations).
+   */
+  validateAgainstSchema(maxIssues?: number | undefined | null): Array<JsSchemaViolation>
+  /** Views saved for this file, in the order they were first saved. */
+  listViews(): Array<JsSavedView>
+  /** Save a view, replacing any view with the same name. */
+  saveView(view: JsSavedView): void
+  /** Remove a saved view. Returns whether there was one. */
+  removeView(name: string): boolean
+  /** Rows and columns of a saved view, against the file as saved on disk. */
+  applyView(name: string): JsAppliedView
+  /** Bookmarked rows, in row order. */
+  listBookmarks(): Array<JsBookmark>
+  /** Bookmark a row, replacing the label of an existing bookmark on it. */
+  addBookmark(row: number, label?: string | undefined | null): void
+  /** Remove the bookmark on a row. Returns whether there was one. */
+  removeBookmark(row: number): boolean
   /** Number of pending edits. */
   get editCount(): number
   /** Whether there are unsaved changes. */
   get hasChanges(): boolean
 }
 
+/**
+ * Several documents open at once, by id, for an app with a tab per file.
+ *
+ * Each file can be open for writing as one document at a time; any number
+ * of `readOnly` documents can read it alongside. `get` returns another
+ * handle on an open document: handles share its edits and closing, but
+ * each has its own progress listeners and watcher.
+ */
+export declare class CsvWorkspace {
+  constructor()
+  /**
+   * Open a file as document `id`, resolving to a handle on it once its rows are
+   * indexed. Fails if `id` is taken, or if the file is already open for writing
+   * in this workspace and `readOnly` isn't set.
+   */
+  open(id: string, path: string, options?: JsOpenOptions | undefined | null, signal?: AbortSignal | undefined | null): Promise<CsvDocument>
+  /** Another handle on the open document `id`. */
+  get(id: string): CsvDocument
+  /** The open documents, in no particular order. */
+  list(): Array<JsWorkspaceDocument>
+  /**
+   * Close document `id`, discarding its pending edits, as `close` on any of its
+   * handles would. Returns whether it was open.
+   */
+  close(id: string): boolean
+  /** Close every document. */
+  closeAll(): void
+  /** Memory held by all open documents. */
+  memoryUsage(): JsMemoryUsage
+  /**
+   * Copy rows [start, end) of document `from` into document `to`, inserted at
+   * `at` (default: appended). Columns are matched by name; target columns the
+   * source lacks are left empty. Resolves to the number of rows copied, and
+   * rejects a range that is reversed or runs past the source's last row.
+   */
+  copyRows(from: string, start: number, end: number, to: string, at?: number | undefined | null): Promise<number>
+  /**
+   * Compare documents `oldId` and `newId` as saved on disk, matching rows by
+   * `keyColumns`, and optionally write the changes as a .mcpatch file.
+   */
+  diff(oldId: string, newId: string, keyColumns: Array<string>, patchPath?: string | undefined | null): Promise<JsDiffSummary>
+  /**
+   * Join documents `leftId` and `rightId`, as saved on disk, on key columns
+   * into a CSV file at `output`.
+   */
+  join(leftId: string, rightId: string, output: string, options: JsJoinOptions): Promise<JsJoinSummary>
+}
+
 /** Info about an opened CSV file. */
 export interface CsvInfo {
   rowCount: number
   headers: Array<string>
+  /** Headers with empty names filled in and duplicates suffixed (amount, amount_2). */
+  canonicalHeaders: Array<string>
   delimiter: string
+  /** How sure delimiter detection was, from 0 to 1. */
+  delimiterConfidence: number
+  /** Text encoding of the file: "UTF-8", "Windows-1252", "UTF-16LE" or "UTF-16BE". */
+  encoding: string
   filePath: string
+  /**
+   * Computed columns, shown after the file's own columns by getRow,
+   * getRows and search.
+   */
+  computedColumns: Array<string>
+}
+
+/** The rows and columns a saved view shows. */
+export interface JsAppliedView {
+  /** File rows in view order. */
+  rows: Array<number>
+  /** Column indices in view order. */
+  columns: Array<number>
+}
+
+/** A bookmarked row. */
+export interface JsBookmark {
+  row: number
+  label?: string
+}
+
+/** What `diff` found between two documents. */
+export interface JsDiffSummary {
+  /** Rows present in both with at least one changed cell. */
+  updatedRows: number
+  updatedCells: number
+  inserted: number
+  deleted: number
+}
+
+/** Options for exporting. */
+export interface JsExportOptions {
+  /** "csv", "json", "ndjson", "sqlite" or "xlsx" (default: from the output extension). */
+  format?: string
+  /** Export only rows matching this filter expression, e.g. 'status == "active"'. */
+  filter?: string
+  /** Export only these columns, in this order. */
+  columns?: Array<string>
+  /** First row to export (default 0). */
+  start?: number
+  /** Row to stop before (default: the end of the file). */
+  end?: number
+  /** Table name when exporting to SQLite (default: the output file name). */
+  table?: string
+}
+
+/** Counts of what `export` wrote. */
+export interface JsExportSummary {
+  rowsWritten: number
+  /** Rows dropped by the filter. */
+  rowsFiltered: number
+}
+
+/** One occurrence of the query in a field, as character offsets into its value. */
+export interface JsFieldMatch {
+  column: number
+  start: number
+  end: number
+}
+
+/** A change made to a watched file by something other than the document. */
+export interface JsFileChange {
+  path: string
+  /** "modified", or "removed" if the file is gone. */
+  kind: string
+}
+
+/** A row before and after its pending edits. */
+export interface JsRowDiff {
+  /** The row as in the file, with pending column changes; null for an inserted row. */
+  original?: Array<string>
+  current: Array<string>
+  /** Columns whose value was edited; every column of an inserted row. */
+  changedColumns: Array<number>
+}
+
+/** Summary statistics of a column, from `getColumnStats`. */
+export interface JsColumnStats {
+  name: string
+  /** Non-empty values. */
+  count: number
+  /** Empty cells, counting fields missing from short rows. */
+  empty: number
+  /** Present when there is at least one value and every value is a number. */
+  numeric?: JsNumericStats
+  /** Shortest non-empty value, in characters (0 without values). */
+  minLength: number
+  /** Longest value, in characters. */
+  maxLength: number
+  /** Estimated number of distinct non-empty values, within a few percent. */
+  distinct: number
+  /** Whether (nearly) every value is different, as in a key column. */
+  nearUnique: boolean
+  /** Whether a few values repeat throughout, as in an enum or flag column. */
+  lowCardinality: boolean
+}
+
+/** Statistics of a numeric column. */
+export interface JsNumericStats {
+  min: number
+  max: number
+  mean: number
+  /** Sample standard deviation (0 for a single value). */
+  stddev: number
+  /** Approximate median, 95th and 99th percentiles. */
+  p50: number
+  p95: number
+  p99: number
+}
+
+/** A column's inferred type, from `inferSchema`. */
+export interface JsInferredColumn {
+  name: string
+  /**
+   * "boolean", "integer", "float", "date", "datetime" or "text" (also when
+   * every sampled value was empty).
+   */
+  columnType: string
+  /** Whether any sampled value was empty. */
+  nullable: boolean
+}
+
+/** The most frequent values of a column, from `getValueCounts`. */
+export interface JsValueCounts {
+  /** Most frequent first; ties in value order. */
+  values: Array<JsValueCount>
+  /** Number of different values, including ones cut by topN. */
+  distinct: number
+  /** Rows counted. */
+  rows: number
+}
+
+/** A value and how many rows hold it. */
+export interface JsValueCount {
+  value: string
+  count: number
+}
+
+/** Options for `join`. */
+export interface JsJoinOptions {
+  /** "inner" (default), "left", "right" or "full". */
+  joinType?: string
+  /** Key columns of the left document. */
+  leftOn: Array<string>
+  /** Key columns of the right document, paired with leftOn (default: the same names). */
+  rightOn?: Array<string>
+}
+
+/** What `join` wrote. */
+export interface JsJoinSummary {
+  rowsWritten: number
+  /** Left rows with at least one match. */
+  leftMatched: number
+  /** Left rows without a match (written only by left and full joins). */
+  leftUnmatched: number
+  /** Right rows no left row matched (written only by right and full joins). */
+  rightUnmatched: number
+}
+
+/** Memory held by the documents of a workspace, in bytes. */
+export interface JsMemoryUsage {
+  documents: number
+  /**
+   * Bytes of files mapped into memory; the OS pages them in as rows are
+   * read, so only part of them may be resident.
+   */
+  mapped: number
+  /** Bytes of row indexes. */
+  index: number
+  /** Bytes of pending edits and undo history, roughly. */
+  edits: number
+}
+
+/** Options for opening a file. Anything left unset is detected. */
+export interface JsOpenOptions {
+  /** Field delimiter, e.g. ";" or "\t". */
+  delimiter?: string
+  /** Quote character, e.g. "'". */
+  quoteChar?: string
+  /** How quotes inside quoted fields are escaped: "doubled" or "backslash". */
+  escape?: string
+  /** Recover malformed rows (bad quoting, ragged rows, invalid UTF-8) instead of failing. */
+  lenient?: boolean
+  /** Read a fixed-width file with these column widths instead of a delimited one. */
+  widths?: Array<number>
+  /** Text encoding, e.g. "utf-8", "latin1" or "utf-16le" (default: detect). */
+  encoding?: string
+  /**
+   * Whether the first line holds column names (default true). Without
+   * them columns are named column_1, column_2, ... and saving writes none.
+   */
+  hasHeaders?: boolean
+  /**
+   * Refuse edits and saving over the file; reading, searching, exporting
+   * and saveAs still work.
+   */
+  readOnly?: boolean
+}
+
+/** A page of rows returned by `getPage`. */
+export interface JsPage {
+  /** Row number of the first row in `rows`. */
+  offset: number
+  rows: Array<Array<string>>
+  /** Rows in the document, for sizing the scrollbar. */
+  rowCount: number
+}
+
+/** How far a long operation has got, passed to progress listeners. */
+export interface JsProgress {
+  /** Units of work done: bytes indexed when opening, rows searched or saved. */
+  done: number
+  total: number
+}
+
+/**
+ * How a structural edit renumbered the document's rows and columns. Rows
+ * (or columns) numbered from `firstShiftedRow` (or `firstShiftedColumn`)
+ * before the edit are now numbered `rowShift` (or `columnShift`) higher;
+ * those before it keep their numbers.
+ */
+export interface JsStructureChange {
+  /** Rows in the document after the edit. */
+  rowCount: number
+  /** Column names after the edit, computed columns last. */
+  headers: Array<string>
+  firstShiftedRow: number
+  /** +n after inserting n rows, -n after deleting n, 0 after a column change. */
+  rowShift: number
+  /** Computed columns count too, so adding a column shifts them. */
+  firstShiftedColumn: number
+  /** +1 after adding a column, -1 after dropping one, 0 otherwise. */
+  columnShift: number
 }
 
 /** Options for searching. */
@@ -46,10 +554,77 @@ export interface JsSearchOptions {
   column?: string
   caseInsensitive?: boolean
   maxResults?: number
+  /** Skip this many matches first, to page through them. */
+  offset?: number
+  /** Only rows also matching this filter expression, e.g. `status == "open"`. */
+  filter?: string
+}
+
+/** Options for saving. */
+export interface JsSaveOptions {
+  /** Also write the saved edits as a .mcpatch file at this path. */
+  patchPath?: string
+  /** Key column(s) identifying rows in the patch. */
+  patchKey?: Array<string>
+  /** Copy the original file aside before replacing it. */
+  backup?: boolean
+  /** Suffix for the backup file name (default ".bak"; "{timestamp}" is expanded). */
+  backupSuffix?: string
+  /** Keep a compressed copy of the original in the file's version history. */
+  snapshot?: boolean
+  /** Line ending to write: "lf" or "crlf" (default: as in the source file). */
+  lineEnding?: string
+  /** Whether to end the file with a line ending (default: as in the source file). */
+  trailingNewline?: boolean
+  /** Field delimiter to write, e.g. "," or "\t" (default: as in the source file). */
+  delimiter?: string
+  /** Quote fields: "necessary", "always", "non-numeric" or "never" (default: keep the source's quoting). */
+  quoteStyle?: string
+  /** Text encoding to write, e.g. "utf-8" or "latin1" (default: as in the source file). */
+  encoding?: string
+  /** Sync policy before replacing the file: "none", "data" or "full" (default "none"). */
+  durability?: string
+}
+
+/** A named view saved next to the file: a filter, sort keys and visible columns. */
+export interface JsSavedView {
+  name: string
+  /** Filter expression, as in `filterRows`. */
+  filter?: string
+  /** Sort keys as "column[:asc|:desc][:num|:text]", most significant first. */
+  sort?: Array<string>
+  /** Columns to show, in order (default: all). */
+  columns?: Array<string>
+}
+
+/** A value breaking a column's rules in the attached schema. */
+export interface JsSchemaViolation {
+  row: number
+  column: string
+  value: string
+  /** Description, e.g. "row 3, column 'age': expected integer, got \"abc\"". */
+  message: string
 }
 
 /** A single search result returned to JS. */
 export interface JsSearchResult {
   rowNum: number
   fields: Array<string>
+  /** Where the query occurs in `fields`, for highlighting. */
+  matches: Array<JsFieldMatch>
+}
+
+/** The results of a search, and how many rows matched in all. */
+export interface JsSearchResults {
+  /** The matches selected by offset and maxResults. */
+  results: Array<JsSearchResult>
+  /** Every matching row in the file, for showing "1,234 matches". */
+  totalMatches: number
+}
+
+/** A document open in a workspace. */
+export interface JsWorkspaceDocument {
+  id: string
+  path: string
+  readOnly: boolean
 }
@@ -577,3 +577,4 @@ if (!nativeBinding) {
 
 module.exports = nativeBinding
 module.exports.CsvDocument = nativeBinding.CsvDocument
+module.exports.CsvWorkspace = nativeBinding.CsvWorkspace
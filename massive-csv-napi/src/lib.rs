@@ -1,18 +1,40 @@
-use std::path::Path;
-use std::sync::Mutex;
+mod workspace;
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex, MutexGuard, OnceLock};
 
 use napi::bindgen_prelude::*;
+use napi::threadsafe_function::{ThreadsafeFunction, ThreadsafeFunctionCallMode};
+use napi::{Env, Task};
 use napi_derive::napi;
 
-use massive_csv_core::{CsvEditor, SearchOptions};
+use massive_csv_core::infer::DEFAULT_SAMPLE_ROWS;
+use massive_csv_core::searcher::SearchScan;
+use massive_csv_core::{
+    column_stats, infer_schema, searcher, value_counts, BuildHandle, BuildStatus, ComputedColumns,
+    CsvEditor, Durability, Encoding, EscapeStyle, ExportFormat, ExportOptions, Expr, FileFingerprint,
+    Filter, IndexInfo, IndexStore, LineEnding, MassiveCsvError, OpenBuilder, PatchOutput, Progress,
+    QuoteStyle, SaveOptions, SavedView, Schema, SearchOptions, SearchResult, ValidateOptions, ViewStore,
+};
 
 /// Info about an opened CSV file.
 #[napi(object)]
 pub struct CsvInfo {
     pub row_count: u32,
     pub headers: Vec<String>,
+    /// Headers with empty names filled in and duplicates suffixed (amount, amount_2).
+    pub canonical_headers: Vec<String>,
     pub delimiter: String,
+    /// How sure delimiter detection was, from 0 to 1.
+    pub delimiter_confidence: f64,
+    /// Text encoding of the file: "UTF-8", "Windows-1252", "UTF-16LE" or "UTF-16BE".
+    pub encoding: String,
     pub file_path: String,
+    /// Computed columns, shown after the file's own columns by getRow,
+    /// getRows and search.
+    pub computed_columns: Vec<String>,
 }
 
 /// A single search result returned to JS.
@@ -20,6 +42,58 @@ pub struct CsvInfo {
 pub struct JsSearchResult {
     pub row_num: u32,
     pub fields: Vec<String>,
+    /// Where the query occurs in `fields`, for highlighting.
+    pub matches: Vec<JsFieldMatch>,
+}
+
+/// One occurrence of the query in a field, as character offsets into its value.
+#[napi(object)]
+pub struct JsFieldMatch {
+    pub column: u32,
+    pub start: u32,
+    pub end: u32,
+}
+
+/// The results of a search, and how many rows matched in all.
+#[napi(object)]
+pub struct JsSearchResults {
+    /// The matches selected by offset and maxResults.
+    pub results: Vec<JsSearchResult>,
+    /// Every matching row in the file, for showing "1,234 matches".
+    pub total_matches: u32,
+}
+
+/// A row before and after its pending edits.
+#[napi(object)]
+pub struct JsRowDiff {
+    /// The row as in the file, with pending column changes; null for an inserted row.
+    pub original: Option<Vec<String>>,
+    pub current: Vec<String>,
+    /// Columns whose value was edited; every column of an inserted row.
+    pub changed_columns: Vec<u32>,
+}
+
+/// Options for opening a file. Anything left unset is detected.
+#[napi(object)]
+pub struct JsOpenOptions {
+    /// Field delimiter, e.g. ";" or "\t".
+    pub delimiter: Option<String>,
+    /// Quote character, e.g. "'".
+    pub quote_char: Option<String>,
+    /// How quotes inside quoted fields are escaped: "doubled" or "backslash".
+    pub escape: Option<String>,
+    /// Recover malformed rows (bad quoting, ragged rows, invalid UTF-8) instead of failing.
+    pub lenient: Option<bool>,
+    /// Read a fixed-width file with these column widths instead of a delimited one.
+    pub widths: Option<Vec<u32>>,
+    /// Text encoding, e.g. "utf-8", "latin1" or "utf-16le" (default: detect).
+    pub encoding: Option<String>,
+    /// Whether the first line holds column names (default true). Without
+    /// them columns are named column_1, column_2, ... and saving writes none.
+    pub has_headers: Option<bool>,
+    /// Refuse edits and saving over the file; reading, searching, exporting
+    /// and saveAs still work.
+    pub read_only: Option<bool>,
 }
 
 /// Options for searching.
@@ -28,149 +102,2034 @@ pub struct JsSearchOptions {
     pub column: Option<String>,
     pub case_insensitive: Option<bool>,
     pub max_results: Option<u32>,
+    /// Skip this many matches first, to page through them.
+    pub offset: Option<u32>,
+    /// Only rows also matching this filter expression, e.g. `status == "open"`.
+    pub filter: Option<String>,
+}
+
+/// Options for saving.
+#[napi(object)]
+pub struct JsSaveOptions {
+    /// Also write the saved edits as a .mcpatch file at this path.
+    pub patch_path: Option<String>,
+    /// Key column(s) identifying rows in the patch.
+    pub patch_key: Option<Vec<String>>,
+    /// Copy the original file aside before replacing it.
+    pub backup: Option<bool>,
+    /// Suffix for the backup file name (default ".bak"; "{timestamp}" is expanded).
+    pub backup_suffix: Option<String>,
+    /// Keep a compressed copy of the original in the file's version history.
+    pub snapshot: Option<bool>,
+    /// Line ending to write: "lf" or "crlf" (default: as in the source file).
+    pub line_ending: Option<String>,
+    /// Whether to end the file with a line ending (default: as in the source file).
+    pub trailing_newline: Option<bool>,
+    /// Field delimiter to write, e.g. "," or "\t" (default: as in the source file).
+    pub delimiter: Option<String>,
+    /// Quote fields: "necessary", "always", "non-numeric" or "never" (default: keep the source's quoting).
+    pub quote_style: Option<String>,
+    /// Text encoding to write, e.g. "utf-8" or "latin1" (default: as in the source file).
+    pub encoding: Option<String>,
+    /// Sync policy before replacing the file: "none", "data" or "full" (default "none").
+    pub durability: Option<String>,
+}
+
+/// Options for exporting.
+#[napi(object)]
+pub struct JsExportOptions {
+    /// "csv", "json", "ndjson", "sqlite" or "xlsx" (default: from the output extension).
+    pub format: Option<String>,
+    /// Export only rows matching this filter expression, e.g. 'status == "active"'.
+    pub filter: Option<String>,
+    /// Export only these columns, in this order.
+    pub columns: Option<Vec<String>>,
+    /// First row to export (default 0).
+    pub start: Option<u32>,
+    /// Row to stop before (default: the end of the file).
+    pub end: Option<u32>,
+    /// Table name when exporting to SQLite (default: the output file name).
+    pub table: Option<String>,
+}
+
+/// Counts of what `export` wrote.
+#[napi(object)]
+pub struct JsExportSummary {
+    pub rows_written: u32,
+    /// Rows dropped by the filter.
+    pub rows_filtered: u32,
+}
+
+/// A value breaking a column's rules in the attached schema.
+#[napi(object)]
+pub struct JsSchemaViolation {
+    pub row: u32,
+    pub column: String,
+    pub value: String,
+    /// Description, e.g. "row 3, column 'age': expected integer, got \"abc\"".
+    pub message: String,
+}
+
+/// A named view saved next to the file: a filter, sort keys and visible columns.
+#[napi(object)]
+pub struct JsSavedView {
+    pub name: String,
+    /// Filter expression, as in `filterRows`.
+    pub filter: Option<String>,
+    /// Sort keys as "column[:asc|:desc][:num|:text]", most significant first.
+    pub sort: Option<Vec<String>>,
+    /// Columns to show, in order (default: all).
+    pub columns: Option<Vec<String>>,
+}
+
+/// The rows and columns a saved view shows.
+#[napi(object)]
+pub struct JsAppliedView {
+    /// File rows in view order.
+    pub rows: Vec<u32>,
+    /// Column indices in view order.
+    pub columns: Vec<u32>,
+}
+
+/// How a structural edit renumbered the document's rows and columns. Rows
+/// (or columns) numbered from `firstShiftedRow` (or `firstShiftedColumn`)
+/// before the edit are now numbered `rowShift` (or `columnShift`) higher;
+/// those before it keep their numbers.
+#[napi(object)]
+pub struct JsStructureChange {
+    /// Rows in the document after the edit.
+    pub row_count: u32,
+    /// Column names after the edit, computed columns last.
+    pub headers: Vec<String>,
+    pub first_shifted_row: u32,
+    /// +n after inserting n rows, -n after deleting n, 0 after a column change.
+    pub row_shift: i32,
+    /// Computed columns count too, so adding a column shifts them.
+    pub first_shifted_column: u32,
+    /// +1 after adding a column, -1 after dropping one, 0 otherwise.
+    pub column_shift: i32,
+}
+
+/// A page of rows returned by `getPage`.
+#[napi(object)]
+pub struct JsPage {
+    /// Row number of the first row in `rows`.
+    pub offset: u32,
+    pub rows: Vec<Vec<String>>,
+    /// Rows in the document, for sizing the scrollbar.
+    pub row_count: u32,
+}
+
+/// How far a long operation has got, passed to progress listeners.
+#[napi(object)]
+pub struct JsProgress {
+    /// Units of work done: bytes indexed when opening, rows searched or saved.
+    pub done: f64,
+    pub total: f64,
+}
+
+/// A listener for progress events, called on the event loop without keeping the process alive.
+type ProgressListener = ThreadsafeFunction<JsProgress, (), JsProgress, Status, false, true>;
+
+/// A `searchStream` callback, called with each batch of matches.
+type BatchListener = ThreadsafeFunction<Vec<JsSearchResult>, (), Vec<JsSearchResult>, Status, false, true>;
+
+/// A change made to a watched file by something other than the document.
+#[napi(object)]
+pub struct JsFileChange {
+    pub path: String,
+    /// "modified", or "removed" if the file is gone.
+    pub kind: String,
+}
+
+/// Events `on` accepts.
+const PROGRESS_EVENTS: [&str; 3] = ["openProgress", "saveProgress", "searchProgress"];
+
+/// Summary statistics of a column, from `getColumnStats`.
+#[napi(object)]
+pub struct JsColumnStats {
+    pub name: String,
+    /// Non-empty values.
+    pub count: u32,
+    /// Empty cells, counting fields missing from short rows.
+    pub empty: u32,
+    /// Present when there is at least one value and every value is a number.
+    pub numeric: Option<JsNumericStats>,
+    /// Shortest non-empty value, in characters (0 without values).
+    pub min_length: u32,
+    /// Longest value, in characters.
+    pub max_length: u32,
+    /// Estimated number of distinct non-empty values, within a few percent.
+    pub distinct: u32,
+    /// Whether (nearly) every value is different, as in a key column.
+    pub near_unique: bool,
+    /// Whether a few values repeat throughout, as in an enum or flag column.
+    pub low_cardinality: bool,
+}
+
+/// Statistics of a numeric column.
+#[napi(object)]
+pub struct JsNumericStats {
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    /// Sample standard deviation (0 for a single value).
+    pub stddev: f64,
+    /// Approximate median, 95th and 99th percentiles.
+    pub p50: f64,
+    pub p95: f64,
+    pub p99: f64,
+}
+
+/// A column's inferred type, from `inferSchema`.
+#[napi(object)]
+pub struct JsInferredColumn {
+    pub name: String,
+    /// "boolean", "integer", "float", "date", "datetime" or "text" (also when
+    /// every sampled value was empty).
+    pub column_type: String,
+    /// Whether any sampled value was empty.
+    pub nullable: bool,
+}
+
+/// The most frequent values of a column, from `getValueCounts`.
+#[napi(object)]
+pub struct JsValueCounts {
+    /// Most frequent first; ties in value order.
+    pub values: Vec<JsValueCount>,
+    /// Number of different values, including ones cut by topN.
+    pub distinct: u32,
+    /// Rows counted.
+    pub rows: u32,
+}
+
+/// A value and how many rows hold it.
+#[napi(object)]
+pub struct JsValueCount {
+    pub value: String,
+    pub count: u32,
+}
+
+/// A bookmarked row.
+#[napi(object)]
+pub struct JsBookmark {
+    pub row: u32,
+    pub label: Option<String>,
 }
 
 /// A CSV document backed by the massive-csv-core engine.
 ///
 /// Wraps CsvEditor which itself wraps CsvReader, providing
 /// memory-mapped reading, parallel search, edit tracking, and atomic save.
+///
+/// Opening, reading pages of rows, searching and saving return Promises and
+/// run on the libuv thread pool, so a large file doesn't block the event
+/// loop. Other calls on the document wait for them to finish. They report
+/// progress to listeners added with `on`, and take an AbortSignal that
+/// stops them at their next progress update and rejects with "Cancelled".
+///
+/// Call `close()` when done: the file stays mapped, and on Windows can't be
+/// deleted or replaced, until then or until the object is garbage collected.
 #[napi]
 pub struct CsvDocument {
-    editor: Mutex<CsvEditor>,
+    /// `None` once closed.
+    editor: SharedEditor,
+    /// Opened with `readOnly`: edits and saves over the file fail.
+    read_only: bool,
+    index_builds: Mutex<HashMap<String, BuildHandle<IndexInfo>>>,
+    computed: Arc<Mutex<ComputedColumns>>,
+    listeners: Mutex<HashMap<String, Vec<Arc<ProgressListener>>>>,
+    /// Pages recently read by `getPage`, dropped on every edit.
+    pages: Arc<Mutex<PageCache>>,
+    /// Watches the file for outside changes while `watch` is on.
+    watcher: Mutex<Option<notify::RecommendedWatcher>>,
+}
+
+/// Opens a file off the event loop, for `CsvDocument.open`.
+pub struct OpenTask {
+    builder: Option<OpenBuilder<CsvEditor>>,
+    read_only: bool,
+    /// Called with "openProgress" events, and kept as listeners for them.
+    listeners: Vec<Arc<ProgressListener>>,
+    cancel: Arc<AtomicBool>,
 }
 
 #[napi]
-impl CsvDocument {
-    /// Open a CSV file and return a CsvDocument.
-    #[napi(factory)]
-    pub fn open(path: String) -> Result<CsvDocument> {
-        let editor = CsvEditor::open(Path::new(&path))
+impl Task for OpenTask {
+    type Output = CsvEditor;
+    type JsValue = CsvDocument;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        let builder = self.builder.take().expect("an open task runs once");
+        check_cancelled(&self.cancel)?;
+        builder
+            .progress(report_to(self.listeners.clone(), self.cancel.clone()))
+            .open()
+            .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
+    fn resolve(&mut self, _env: Env, editor: Self::Output) -> Result<Self::JsValue> {
+        let document = CsvDocument::new(editor, self.read_only);
+        if !self.listeners.is_empty() {
+            let mut listeners = document.listeners.lock().map_err(|e| Error::from_reason(e.to_string()))?;
+            listeners.insert("openProgress".to_string(), std::mem::take(&mut self.listeners));
+        }
+        Ok(document)
+    }
+}
+
+/// Re-opens the file off the event loop, for `reload`.
+pub struct ReloadTask {
+    editor: SharedEditor,
+    computed: Arc<Mutex<ComputedColumns>>,
+    pages: Arc<Mutex<PageCache>>,
+    progress: Progress,
+}
+
+#[napi]
+impl Task for ReloadTask {
+    type Output = u32;
+    type JsValue = u32;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        let mut editor = lock_editor(&self.editor)?;
+        let kept = editor
+            .reload_and_reapply_with_progress(&self.progress)
             .map_err(|e| Error::from_reason(e.to_string()))?;
-        Ok(CsvDocument {
-            editor: Mutex::new(editor),
-        })
+        self.pages.lock().map_err(|e| Error::from_reason(e.to_string()))?.clear();
+        let mut computed = self.computed.lock().map_err(|e| Error::from_reason(e.to_string()))?;
+        rebind_computed(&mut computed, editor.headers());
+        Ok(kept as u32)
     }
 
-    /// Get file metadata.
-    #[napi]
-    pub fn get_info(&self) -> Result<CsvInfo> {
-        let editor = self.editor.lock().map_err(|e| Error::from_reason(e.to_string()))?;
-        let reader = editor.reader();
-        Ok(CsvInfo {
-            row_count: reader.row_count() as u32,
-            headers: reader.headers().to_vec(),
-            delimiter: String::from(reader.delimiter() as char),
-            file_path: reader.path().to_string_lossy().into_owned(),
+    fn resolve(&mut self, _env: Env, kept: Self::Output) -> Result<Self::JsValue> {
+        Ok(kept)
+    }
+}
+
+/// Reads rows off the event loop, for `getRows`.
+pub struct GetRowsTask {
+    editor: SharedEditor,
+    computed: Arc<Mutex<ComputedColumns>>,
+    start: usize,
+    end: usize,
+}
+
+#[napi]
+impl Task for GetRowsTask {
+    type Output = Vec<Vec<String>>;
+    type JsValue = Vec<Vec<String>>;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        let editor = lock_editor(&self.editor)?;
+        let computed = self.computed.lock().map_err(|e| Error::from_reason(e.to_string()))?;
+        let end = self.end.min(editor.row_count());
+        let mut rows = Vec::with_capacity(end.saturating_sub(self.start));
+        for i in self.start..end {
+            let mut fields = editor
+                .get_row(i)
+                .map_err(|e| Error::from_reason(e.to_string()))?;
+            computed.extend(&mut fields);
+            rows.push(fields);
+        }
+        Ok(rows)
+    }
+
+    fn resolve(&mut self, _env: Env, rows: Self::Output) -> Result<Self::JsValue> {
+        Ok(rows)
+    }
+}
+
+/// Reads rows into one buffer off the event loop, for `getRowsBuffer`.
+pub struct RowsBufferTask {
+    rows: GetRowsTask,
+}
+
+#[napi]
+impl Task for RowsBufferTask {
+    type Output = Vec<u8>;
+    type JsValue = Buffer;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        Ok(encode_rows(&self.rows.compute()?))
+    }
+
+    fn resolve(&mut self, _env: Env, bytes: Self::Output) -> Result<Self::JsValue> {
+        Ok(bytes.into())
+    }
+}
+
+/// Reads a page off the event loop, for `getPage`, then prefetches the next
+/// page in the direction of scrolling.
+pub struct PageTask {
+    editor: SharedEditor,
+    computed: Arc<Mutex<ComputedColumns>>,
+    pages: Arc<Mutex<PageCache>>,
+    offset: usize,
+    limit: usize,
+    columns: Option<Vec<usize>>,
+}
+
+#[napi]
+impl Task for PageTask {
+    type Output = JsPage;
+    type JsValue = JsPage;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        let (page, row_count) =
+            read_page(&self.editor, &self.computed, &self.pages, self.offset, self.limit)?;
+        let backwards = {
+            let mut pages = self.pages.lock().map_err(|e| Error::from_reason(e.to_string()))?;
+            let backwards = pages.last_offset.is_some_and(|last| self.offset < last);
+            pages.last_offset = Some(self.offset);
+            backwards
+        };
+        let next = if self.limit == 0 {
+            None
+        } else if backwards {
+            self.offset.checked_sub(self.limit)
+        } else {
+            Some(self.offset + self.limit).filter(|&next| next < row_count)
+        };
+        if let Some(next) = next {
+            let key = (next, self.limit);
+            let mut pages = self.pages.lock().map_err(|e| Error::from_reason(e.to_string()))?;
+            // One prefetch in flight per document: fast scrolling skips pages rather than queueing them
+            if pages.pending.is_none() && !pages.contains(key) {
+                pages.pending = Some(key);
+                prefetch(Prefetch {
+                    editor: self.editor.clone(),
+                    computed: self.computed.clone(),
+                    pages: self.pages.clone(),
+                    key,
+                });
+            }
+        }
+        let rows = match &self.columns {
+            Some(columns) => page
+                .iter()
+                .map(|fields| {
+                    columns
+                        .iter()
+                        .map(|&c| fields.get(c).cloned().unwrap_or_default())
+                        .collect()
+                })
+                .collect(),
+            None => page.to_vec(),
+        };
+        Ok(JsPage {
+            offset: self.offset as u32,
+            rows,
+            row_count: row_count as u32,
         })
     }
 
-    /// Get a single row (returns edited version if modified).
-    #[napi]
-    pub fn get_row(&self, row: u32) -> Result<Vec<String>> {
-        let editor = self.editor.lock().map_err(|e| Error::from_reason(e.to_string()))?;
-        editor
-            .get_row(row as usize)
-            .map_err(|e| Error::from_reason(e.to_string()))
+    fn resolve(&mut self, _env: Env, page: Self::Output) -> Result<Self::JsValue> {
+        Ok(page)
     }
+}
 
-    /// Get a range of rows [start, end). Returns edited versions where applicable.
-    #[napi]
-    pub fn get_rows(&self, start: u32, end: u32) -> Result<Vec<Vec<String>>> {
-        let editor = self.editor.lock().map_err(|e| Error::from_reason(e.to_string()))?;
-        let end = (end as usize).min(editor.reader().row_count());
-        let mut rows = Vec::with_capacity(end.saturating_sub(start as usize));
-        for i in (start as usize)..end {
-            rows.push(
-                editor
-                    .get_row(i)
-                    .map_err(|e| Error::from_reason(e.to_string()))?,
+/// Searches off the event loop, for `search`.
+pub struct SearchTask {
+    editor: SharedEditor,
+    computed: Arc<Mutex<ComputedColumns>>,
+    query: String,
+    options: SearchOptions,
+    cancel: Arc<AtomicBool>,
+}
+
+#[napi]
+impl Task for SearchTask {
+    type Output = JsSearchResults;
+    type JsValue = JsSearchResults;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        let editor = lock_editor(&self.editor)?;
+        check_cancelled(&self.cancel)?;
+        let page = searcher::search_page(editor.reader(), &self.query, &self.options)
+            .map_err(|e| Error::from_reason(e.to_string()))?;
+        let computed = self.computed.lock().map_err(|e| Error::from_reason(e.to_string()))?;
+        let results = page.results.into_iter().map(|r| js_search_result(r, &computed)).collect();
+        Ok(JsSearchResults { results, total_matches: page.total as u32 })
+    }
+
+    fn resolve(&mut self, _env: Env, results: Self::Output) -> Result<Self::JsValue> {
+        Ok(results)
+    }
+}
+
+/// Searches off the event loop, handing matches to JS a batch at a time, for `searchStream`.
+pub struct SearchStreamTask {
+    editor: SharedEditor,
+    computed: Arc<Mutex<ComputedColumns>>,
+    query: String,
+    options: SearchOptions,
+    on_batch: BatchListener,
+    cancel: Arc<AtomicBool>,
+}
+
+#[napi]
+impl Task for SearchStreamTask {
+    type Output = u32;
+    type JsValue = u32;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        let mut scan = SearchScan::new(&self.query, &self.options);
+        let mut fingerprint = None;
+        // Each batch is sent once the next is found, so the last can be sent after the scan
+        let mut last = None;
+        loop {
+            check_cancelled(&self.cancel)?;
+            // The document is locked a part of the file at a time, so other calls on it can run
+            // between them, and unlocked while batches go to JS
+            let mut batches: Vec<Vec<JsSearchResult>> = Vec::new();
+            let more = {
+                let editor = lock_editor(&self.editor)?;
+                let reader = editor.reader();
+                if fingerprint.get_or_insert_with(|| reader.fingerprint().clone()) != reader.fingerprint() {
+                    return Err(Error::from_reason("the file was saved or reloaded during the search"));
+                }
+                let computed = self.computed.lock().map_err(|e| Error::from_reason(e.to_string()))?;
+                scan.step(reader, STREAM_ROWS, &mut |results| {
+                    batches.push(results.into_iter().map(|r| js_search_result(r, &computed)).collect());
+                    Ok(())
+                })
+                .map_err(|e| Error::from_reason(e.to_string()))?
+            };
+            for batch in batches {
+                if let Some(previous) = last.replace(batch) {
+                    self.on_batch.call(previous, ThreadsafeFunctionCallMode::NonBlocking);
+                }
+            }
+            if !more {
+                break;
+            }
+        }
+        // Wait for the last batch to be taken. Calls are made in order, so by then every batch has
+        // been, before the promise resolves.
+        if let Some(batch) = last {
+            let (taken, wait) = mpsc::channel();
+            let status = self.on_batch.call_with_return_value(
+                batch,
+                ThreadsafeFunctionCallMode::Blocking,
+                move |result: Result<()>, _env| {
+                    let _ = taken.send(result);
+                    Ok(())
+                },
             );
+            if status != Status::Ok {
+                return Err(Error::from_status(status));
+            }
+            wait.recv().map_err(|e| Error::from_reason(e.to_string()))??;
         }
-        Ok(rows)
+        Ok(scan.found() as u32)
     }
 
-    /// Search for rows matching a query.
-    #[napi]
-    pub fn search(
-        &self,
-        query: String,
-        options: Option<JsSearchOptions>,
-    ) -> Result<Vec<JsSearchResult>> {
-        let editor = self.editor.lock().map_err(|e| Error::from_reason(e.to_string()))?;
-        let opts = match options {
-            Some(o) => SearchOptions {
-                column: o.column,
-                case_insensitive: o.case_insensitive.unwrap_or(false),
-                max_results: o.max_results.unwrap_or(0) as usize,
-            },
-            None => SearchOptions::default(),
-        };
-        let results = massive_csv_core::search(editor.reader(), &query, &opts)
+    fn resolve(&mut self, _env: Env, found: Self::Output) -> Result<Self::JsValue> {
+        Ok(found)
+    }
+}
+
+/// Profiles a column off the event loop, for `getColumnStats`.
+pub struct ColumnStatsTask {
+    editor: SharedEditor,
+    column: String,
+}
+
+#[napi]
+impl Task for ColumnStatsTask {
+    type Output = JsColumnStats;
+    type JsValue = JsColumnStats;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        let editor = lock_editor(&self.editor)?;
+        let mut stats = column_stats(editor.reader(), std::slice::from_ref(&self.column))
             .map_err(|e| Error::from_reason(e.to_string()))?;
+        let stats = stats.pop().expect("one column asked for");
+        Ok(JsColumnStats {
+            name: stats.name.clone(),
+            count: stats.count as u32,
+            empty: stats.empty as u32,
+            numeric: stats.numeric.map(|n| JsNumericStats {
+                min: n.min,
+                max: n.max,
+                mean: n.mean,
+                stddev: n.stddev,
+                p50: n.p50,
+                p95: n.p95,
+                p99: n.p99,
+            }),
+            min_length: stats.min_length as u32,
+            max_length: stats.max_length as u32,
+            distinct: stats.distinct as u32,
+            near_unique: stats.is_near_unique(),
+            low_cardinality: stats.is_low_cardinality(),
+        })
+    }
+
+    fn resolve(&mut self, _env: Env, stats: Self::Output) -> Result<Self::JsValue> {
+        Ok(stats)
+    }
+}
 
-        Ok(results
+/// Infers column types off the event loop, for `inferSchema`.
+pub struct InferSchemaTask {
+    editor: SharedEditor,
+    sample_rows: usize,
+}
+
+#[napi]
+impl Task for InferSchemaTask {
+    type Output = Vec<JsInferredColumn>;
+    type JsValue = Vec<JsInferredColumn>;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        let editor = lock_editor(&self.editor)?;
+        let columns = infer_schema(editor.reader(), self.sample_rows)
+            .map_err(|e| Error::from_reason(e.to_string()))?;
+        Ok(columns
             .into_iter()
-            .map(|r| JsSearchResult {
-                row_num: r.row_num as u32,
-                fields: r.fields,
+            .map(|c| JsInferredColumn {
+                name: c.name,
+                column_type: c.column_type.name().to_string(),
+                nullable: c.nullable,
             })
             .collect())
     }
 
-    /// Edit a single cell.
-    #[napi]
-    pub fn set_cell(&self, row: u32, col: u32, value: String) -> Result<()> {
-        let mut editor = self.editor.lock().map_err(|e| Error::from_reason(e.to_string()))?;
-        editor
-            .set_cell(row as usize, col as usize, value)
-            .map_err(|e| Error::from_reason(e.to_string()))
+    fn resolve(&mut self, _env: Env, columns: Self::Output) -> Result<Self::JsValue> {
+        Ok(columns)
     }
+}
 
-    /// Replace an entire row.
+/// Counts values off the event loop, for `getValueCounts`.
+pub struct ValueCountsTask {
+    editor: SharedEditor,
+    column: String,
+    top_n: usize,
+}
+
+#[napi]
+impl Task for ValueCountsTask {
+    type Output = JsValueCounts;
+    type JsValue = JsValueCounts;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        let editor = lock_editor(&self.editor)?;
+        let counts = value_counts(editor.reader(), &self.column, self.top_n)
+            .map_err(|e| Error::from_reason(e.to_string()))?;
+        Ok(JsValueCounts {
+            values: counts
+                .values
+                .into_iter()
+                .map(|(value, count)| JsValueCount { value, count: count as u32 })
+                .collect(),
+            distinct: counts.distinct as u32,
+            rows: counts.rows as u32,
+        })
+    }
+
+    fn resolve(&mut self, _env: Env, counts: Self::Output) -> Result<Self::JsValue> {
+        Ok(counts)
+    }
+}
+
+/// Saves off the event loop, for `save`, `saveWithOptions`, `saveAs` and
+/// `saveForce`.
+pub struct SaveTask {
+    editor: SharedEditor,
+    options: SaveOptions,
+    /// Write here instead, leaving the source and pending edits untouched.
+    target: Option<PathBuf>,
+    /// Overwrite the file even if it changed on disk.
+    force: bool,
+    cancel: Arc<AtomicBool>,
+}
+
+#[napi]
+impl Task for SaveTask {
+    type Output = ();
+    type JsValue = ();
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        let mut editor = lock_editor(&self.editor)?;
+        check_cancelled(&self.cancel)?;
+        let saved = match &self.target {
+            Some(path) => editor.save_as(path, &self.options),
+            None if self.force => editor.save_force(),
+            None => editor.save_with_options(&self.options),
+        };
+        saved.map_err(|e| Error::from_reason(e.to_string()))
+    }
+
+    fn resolve(&mut self, _env: Env, _output: Self::Output) -> Result<Self::JsValue> {
+        Ok(())
+    }
+}
+
+#[napi]
+impl CsvDocument {
+    /// Open a CSV file, resolving to a CsvDocument once its rows are indexed.
+    /// `onProgress` is called with the bytes indexed so far, and stays an
+    /// "openProgress" listener of the document, for `reload`.
+    #[napi(ts_args_type = "path: string, options?: JsOpenOptions | undefined | null, \
+                           onProgress?: ((progress: JsProgress) => void) | undefined | null, \
+                           signal?: AbortSignal | undefined | null")]
+    pub fn open(
+        env: Env,
+        path: String,
+        options: Option<JsOpenOptions>,
+        on_progress: Option<ThreadsafeFunction<JsProgress, (), JsProgress, Status, false, true>>,
+        signal: Option<Object>,
+    ) -> Result<AsyncTask<OpenTask>> {
+        let cancel = cancel_flag(&env, signal)?;
+        let listeners = on_progress.map(Arc::new).into_iter().collect();
+        let read_only = options.as_ref().and_then(|o| o.read_only).unwrap_or(false);
+        Ok(AsyncTask::new(OpenTask {
+            builder: Some(open_builder(path, options)?),
+            read_only,
+            listeners,
+            cancel,
+        }))
+    }
+
+    /// Open a CSV file as `open` does, with `options` such as
+    /// `{ delimiter, hasHeaders, encoding, readOnly }`.
+    #[napi(ts_args_type = "path: string, options: JsOpenOptions, \
+                           onProgress?: ((progress: JsProgress) => void) | undefined | null, \
+                           signal?: AbortSignal | undefined | null")]
+    pub fn open_with_options(
+        env: Env,
+        path: String,
+        options: JsOpenOptions,
+        on_progress: Option<ThreadsafeFunction<JsProgress, (), JsProgress, Status, false, true>>,
+        signal: Option<Object>,
+    ) -> Result<AsyncTask<OpenTask>> {
+        Self::open(env, path, Some(options), on_progress, signal)
+    }
+
+    /// Release the file: drop the editor and its memory map, discarding
+    /// pending edits, and stop index builds. Waits for a running open,
+    /// read, search or save to finish first. Every later call fails,
+    /// except `close` and `dispose`, which do nothing.
     #[napi]
-    pub fn set_row(&self, row: u32, fields: Vec<String>) -> Result<()> {
+    pub fn close(&self) -> Result<()> {
+        self.watcher.lock().map_err(|e| Error::from_reason(e.to_string()))?.take();
         let mut editor = self.editor.lock().map_err(|e| Error::from_reason(e.to_string()))?;
-        editor
-            .set_row(row as usize, fields)
-            .map_err(|e| Error::from_reason(e.to_string()))
+        editor.take();
+        self.pages.lock().map_err(|e| Error::from_reason(e.to_string()))?.clear();
+        let mut builds = self.index_builds.lock().map_err(|e| Error::from_reason(e.to_string()))?;
+        for (_, build) in builds.drain() {
+            build.cancel();
+        }
+        self.listeners.lock().map_err(|e| Error::from_reason(e.to_string()))?.clear();
+        Ok(())
     }
 
-    /// Revert a single row to its original state.
+    /// Same as `close`, for `using` declarations and disposal helpers.
     #[napi]
-    pub fn revert_row(&self, row: u32) -> Result<()> {
-        let mut editor = self.editor.lock().map_err(|e| Error::from_reason(e.to_string()))?;
-        editor.revert_row(row as usize);
+    pub fn dispose(&self) -> Result<()> {
+        self.close()
+    }
+
+    /// Call `callback` with a `{ done, total }` progress object as a long operation runs:
+    /// "openProgress" for reloads (bytes indexed), "saveProgress" for saves (rows written)
+    /// and "searchProgress" for searches (rows searched).
+    #[napi(ts_args_type = "event: 'openProgress' | 'saveProgress' | 'searchProgress', \
+                           callback: (progress: JsProgress) => void")]
+    pub fn on(
+        &self,
+        event: String,
+        callback: ThreadsafeFunction<JsProgress, (), JsProgress, Status, false, true>,
+    ) -> Result<()> {
+        if !PROGRESS_EVENTS.contains(&event.as_str()) {
+            return Err(Error::from_reason(format!(
+                "unknown event '{event}', expected one of: {}",
+                PROGRESS_EVENTS.join(", ")
+            )));
+        }
+        let mut listeners = self.listeners.lock().map_err(|e| Error::from_reason(e.to_string()))?;
+        listeners.entry(event).or_default().push(Arc::new(callback));
         Ok(())
     }
 
-    /// Revert all pending edits.
+    /// Remove every listener for `event`.
     #[napi]
-    pub fn revert_all(&self) -> Result<()> {
-        let mut editor = self.editor.lock().map_err(|e| Error::from_reason(e.to_string()))?;
-        editor.revert_all();
+    pub fn off(&self, event: String) -> Result<()> {
+        let mut listeners = self.listeners.lock().map_err(|e| Error::from_reason(e.to_string()))?;
+        listeners.remove(&event);
         Ok(())
     }
 
-    /// Save all pending edits atomically.
+    /// Get file metadata.
     #[napi]
-    pub fn save(&self) -> Result<()> {
-        let mut editor = self.editor.lock().map_err(|e| Error::from_reason(e.to_string()))?;
-        editor
-            .save()
-            .map_err(|e| Error::from_reason(e.to_string()))
+    pub fn get_info(&self) -> Result<CsvInfo> {
+        let editor = lock_editor(&self.editor)?;
+        let computed = self.computed.lock().map_err(|e| Error::from_reason(e.to_string()))?;
+        let reader = editor.reader();
+        Ok(CsvInfo {
+            row_count: editor.row_count() as u32,
+            headers: editor.headers().to_vec(),
+            canonical_headers: reader.canonical_headers().to_vec(),
+            delimiter: String::from(reader.delimiter() as char),
+            delimiter_confidence: reader.delimiter_confidence(),
+            encoding: reader.encoding().to_string(),
+            file_path: reader.path().to_string_lossy().into_owned(),
+            computed_columns: computed.columns().iter().map(|c| c.name.clone()).collect(),
+        })
     }
 
-    /// Number of pending edits.
-    #[napi(getter)]
-    pub fn edit_count(&self) -> Result<u32> {
-        let editor = self.editor.lock().map_err(|e| Error::from_reason(e.to_string()))?;
-        Ok(editor.edit_count() as u32)
+    /// Get a single row (returns edited version if modified).
+    #[napi]
+    pub fn get_row(&self, row: u32) -> Result<Vec<String>> {
+        let editor = lock_editor(&self.editor)?;
+        let computed = self.computed.lock().map_err(|e| Error::from_reason(e.to_string()))?;
+        let mut fields = editor
+            .get_row(row as usize)
+            .map_err(|e| Error::from_reason(e.to_string()))?;
+        computed.extend(&mut fields);
+        Ok(fields)
     }
 
-    /// Whether there are unsaved changes.
-    #[napi(getter)]
-    pub fn has_changes(&self) -> Result<bool> {
-        let editor = self.editor.lock().map_err(|e| Error::from_reason(e.to_string()))?;
-        Ok(editor.has_changes())
+    /// Describe what is wrong with a row as stored on disk, or null if it is well-formed.
+    #[napi]
+    pub fn get_row_issue(&self, row: u32) -> Result<Option<String>> {
+        let editor = lock_editor(&self.editor)?;
+        let (_, issue) = editor
+            .reader()
+            .get_row_checked(row as usize)
+            .map_err(|e| Error::from_reason(e.to_string()))?;
+        Ok(issue.map(|i| i.to_string()))
+    }
+
+    /// Get a range of rows [start, end). Returns edited versions where applicable.
+    #[napi]
+    pub fn get_rows(&self, start: u32, end: u32) -> AsyncTask<GetRowsTask> {
+        AsyncTask::new(GetRowsTask {
+            editor: self.editor.clone(),
+            computed: self.computed.clone(),
+            start: start as usize,
+            end: end as usize,
+        })
+    }
+
+    /// Get a range of rows [start, end) as one Buffer, so a large window
+    /// crosses into JS as a single object rather than a string per value:
+    /// a u32 row count, then for each row a u32 field count and each field
+    /// as a u32 byte length and its UTF-8 bytes, all little-endian. Rows are
+    /// as from getRows.
+    #[napi]
+    pub fn get_rows_buffer(&self, start: u32, end: u32) -> AsyncTask<RowsBufferTask> {
+        AsyncTask::new(RowsBufferTask {
+            rows: GetRowsTask {
+                editor: self.editor.clone(),
+                computed: self.computed.clone(),
+                start: start as usize,
+                end: end as usize,
+            },
+        })
+    }
+
+    /// Get `limit` rows from `offset`, with the document's row count, for a
+    /// virtually scrolled grid. Only `columns` (indices) are returned if given.
+    /// The next page in the direction of scrolling is read ahead into a small
+    /// cache, so scrolling on rarely waits for the file.
+    #[napi]
+    pub fn get_page(
+        &self,
+        offset: u32,
+        limit: u32,
+        columns: Option<Vec<u32>>,
+    ) -> AsyncTask<PageTask> {
+        AsyncTask::new(PageTask {
+            editor: self.editor.clone(),
+            computed: self.computed.clone(),
+            pages: self.pages.clone(),
+            offset: offset as usize,
+            limit: limit as usize,
+            columns: columns.map(|columns| columns.into_iter().map(|c| c as usize).collect()),
+        })
+    }
+
+    /// Get a range of rows [start, end) as an Arrow IPC stream of string
+    /// columns, for `tableFromIPC` in arrow-js or DuckDB-Wasm. Returns
+    /// edited versions where applicable.
+    #[napi]
+    pub fn get_rows_arrow(&self, start: u32, end: u32) -> Result<Buffer> {
+        let editor = lock_editor(&self.editor)?;
+        let stream = editor
+            .arrow_ipc(start as usize, end as usize)
+            .map_err(|e| Error::from_reason(e.to_string()))?;
+        Ok(stream.into())
+    }
+
+    /// Search for rows matching a query. Resolves to the matches selected by offset and
+    /// maxResults, each with where the query occurs in it, and the number of matching rows.
+    #[napi(ts_args_type = "query: string, options?: JsSearchOptions | undefined | null, \
+                           signal?: AbortSignal | undefined | null")]
+    pub fn search(
+        &self,
+        env: Env,
+        query: String,
+        options: Option<JsSearchOptions>,
+        signal: Option<Object>,
+    ) -> Result<AsyncTask<SearchTask>> {
+        let cancel = cancel_flag(&env, signal)?;
+        let progress = self.progress("searchProgress", &cancel)?;
+        Ok(AsyncTask::new(SearchTask {
+            editor: self.editor.clone(),
+            computed: self.computed.clone(),
+            query,
+            options: search_options(options, progress)?,
+            cancel,
+        }))
+    }
+
+    /// Search like `search`, but call onBatch with the matches of each batch of rows as soon as
+    /// it has been scanned, so they can be listed while the rest of the file is searched. Resolves
+    /// to the number of matches passed to onBatch, after its last call. The document can be used
+    /// meanwhile, but saving or reloading it fails the search.
+    #[napi(ts_args_type = "query: string, options: JsSearchOptions | undefined | null, \
+                           onBatch: (results: Array<JsSearchResult>) => void, \
+                           signal?: AbortSignal | undefined | null")]
+    pub fn search_stream(
+        &self,
+        env: Env,
+        query: String,
+        options: Option<JsSearchOptions>,
+        on_batch: BatchListener,
+        signal: Option<Object>,
+    ) -> Result<AsyncTask<SearchStreamTask>> {
+        let cancel = cancel_flag(&env, signal)?;
+        let progress = self.progress("searchProgress", &cancel)?;
+        Ok(AsyncTask::new(SearchStreamTask {
+            editor: self.editor.clone(),
+            computed: self.computed.clone(),
+            query,
+            options: search_options(options, progress)?,
+            on_batch,
+            cancel,
+        }))
+    }
+
+    /// Row numbers matching a filter expression, in order, for showing a
+    /// filtered view (0 or no maxResults = all matches).
+    #[napi]
+    pub fn filter_rows(&self, expr: String, max_results: Option<u32>) -> Result<Vec<u32>> {
+        let editor = lock_editor(&self.editor)?;
+        let filter = Filter::parse(&expr, editor.reader().headers())
+            .map_err(|e| Error::from_reason(e.to_string()))?;
+        let rows = filter
+            .matching_rows(editor.reader(), max_results.unwrap_or(0) as usize)
+            .map_err(|e| Error::from_reason(e.to_string()))?;
+        Ok(rows.into_iter().map(|row| row as u32).collect())
+    }
+
+    /// Summary statistics of a column of the file as saved: counts, lengths, an
+    /// estimate of distinct values and, for numeric columns, min, max, mean and
+    /// percentiles. Reads every row.
+    #[napi]
+    pub fn get_column_stats(&self, column: String) -> AsyncTask<ColumnStatsTask> {
+        AsyncTask::new(ColumnStatsTask { editor: self.editor.clone(), column })
+    }
+
+    /// The type of each column of the file as saved, from up to `sampleRows` rows
+    /// spread over it (default 100,000).
+    #[napi]
+    pub fn infer_schema(&self, sample_rows: Option<u32>) -> AsyncTask<InferSchemaTask> {
+        AsyncTask::new(InferSchemaTask {
+            editor: self.editor.clone(),
+            sample_rows: sample_rows.map_or(DEFAULT_SAMPLE_ROWS, |n| n as usize),
+        })
+    }
+
+    /// The `topN` most frequent values of a column of the file as saved, most
+    /// frequent first (0 or no topN = all of them). Empty cells count as "".
+    #[napi]
+    pub fn get_value_counts(&self, column: String, top_n: Option<u32>) -> AsyncTask<ValueCountsTask> {
+        AsyncTask::new(ValueCountsTask {
+            editor: self.editor.clone(),
+            column,
+            top_n: top_n.unwrap_or(0) as usize,
+        })
+    }
+
+    /// Add a computed column, e.g. "total = price * qty", shown after the
+    /// file's columns (and earlier computed ones, which it may use) by
+    /// getRow, getRows and search.
+    #[napi]
+    pub fn add_computed_column(&self, definition: String) -> Result<()> {
+        let editor = self.editor_mut()?;
+        let mut computed = self.computed.lock().map_err(|e| Error::from_reason(e.to_string()))?;
+        computed
+            .add(&definition, editor.reader().canonical_headers())
+            .map_err(|e| Error::from_reason(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Remove a computed column and every later one using it. Returns
+    /// whether there was one.
+    #[napi]
+    pub fn remove_computed_column(&self, name: String) -> Result<bool> {
+        let editor = self.editor_mut()?;
+        let mut computed = self.computed.lock().map_err(|e| Error::from_reason(e.to_string()))?;
+        Ok(computed.remove(&name, editor.reader().canonical_headers()))
+    }
+
+    /// Write the computed columns into the file as real columns, to `output`
+    /// or over the file itself (which needs no pending edits; the document
+    /// then reloads it). Returns the number of rows written.
+    #[napi]
+    pub fn materialize_computed_columns(&self, output: Option<String>) -> Result<u32> {
+        let mut editor = self.editor_mut()?;
+        let mut computed = self.computed.lock().map_err(|e| Error::from_reason(e.to_string()))?;
+        let in_place = output.is_none();
+        if in_place {
+            self.check_writable()?;
+        }
+        if in_place && editor.has_changes() {
+            return Err(Error::from_reason(
+                "save or revert pending edits before adding columns to the file".to_string(),
+            ));
+        }
+        let target = match output {
+            Some(path) => path.into(),
+            None => editor.reader().path().to_path_buf(),
+        };
+        let rows = massive_csv_core::materialize(editor.reader(), &computed, &target)
+            .map_err(|e| Error::from_reason(e.to_string()))?;
+        if in_place {
+            editor
+                .reload_and_reapply()
+                .map_err(|e| Error::from_reason(e.to_string()))?;
+            *computed = ComputedColumns::new(editor.reader().canonical_headers());
+        }
+        Ok(rows as u32)
+    }
+
+    /// Export the file as saved on disk (pending edits are not included).
+    #[napi]
+    pub fn export(
+        &self,
+        output: String,
+        options: Option<JsExportOptions>,
+    ) -> Result<JsExportSummary> {
+        let editor = lock_editor(&self.editor)?;
+        let output = Path::new(&output);
+        let mut opts = ExportOptions {
+            format: ExportFormat::for_path(output),
+            ..Default::default()
+        };
+        if let Some(o) = options {
+            if let Some(name) = o.format {
+                opts.format = ExportFormat::from_name(&name)
+                    .ok_or_else(|| Error::from_reason(format!("unknown format '{name}'")))?;
+            }
+            if let Some(filter) = o.filter {
+                opts.filter =
+                    Some(Expr::parse(&filter).map_err(|e| Error::from_reason(e.to_string()))?);
+            }
+            if o.start.is_some() || o.end.is_some() {
+                opts.rows = Some(
+                    o.start.unwrap_or(0) as usize..o.end.map_or(usize::MAX, |end| end as usize),
+                );
+            }
+            opts.columns = o.columns;
+            opts.table = o.table;
+        }
+        let summary = massive_csv_core::export::export(editor.reader(), output, &opts)
+            .map_err(|e| Error::from_reason(e.to_string()))?;
+        Ok(JsExportSummary {
+            rows_written: summary.rows_written as u32,
+            rows_filtered: summary.rows_filtered as u32,
+        })
+    }
+
+    /// Edit a single cell.
+    #[napi]
+    pub fn set_cell(&self, row: u32, col: u32, value: String) -> Result<()> {
+        self.check_writable()?;
+        let mut editor = self.editor_mut()?;
+        editor
+            .set_cell(row as usize, col as usize, value)
+            .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
+    /// Replace an entire row.
+    #[napi]
+    pub fn set_row(&self, row: u32, fields: Vec<String>) -> Result<()> {
+        self.check_writable()?;
+        let mut editor = self.editor_mut()?;
+        editor
+            .set_row(row as usize, fields)
+            .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
+    /// Move rows `start..end` so the first ends up at `dest`, e.g. after a
+    /// drag and drop. Row numbers afterwards follow the new order; it is
+    /// written on save.
+    #[napi]
+    pub fn move_rows(&self, start: u32, end: u32, dest: u32) -> Result<()> {
+        self.check_writable()?;
+        let mut editor = self.editor_mut()?;
+        editor
+            .move_rows(start as usize..end as usize, dest as usize)
+            .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
+    /// Insert a row at `row` (or at rowCount to append), empty unless `fields`
+    /// are given; rows from `row` on move down one. Written on save.
+    #[napi]
+    pub fn insert_row(&self, row: u32, fields: Option<Vec<String>>) -> Result<JsStructureChange> {
+        self.check_writable()?;
+        let mut editor = self.editor_mut()?;
+        editor
+            .insert_rows(row as usize, vec![fields.unwrap_or_default()])
+            .map_err(|e| Error::from_reason(e.to_string()))?;
+        self.structure_change(&editor, (row, 1), None)
+    }
+
+    /// Delete rows [start, end); the rows after them move up. Written on save.
+    #[napi]
+    pub fn delete_rows(&self, start: u32, end: u32) -> Result<JsStructureChange> {
+        self.check_writable()?;
+        let mut editor = self.editor_mut()?;
+        editor
+            .delete_rows(start as usize..end as usize)
+            .map_err(|e| Error::from_reason(e.to_string()))?;
+        let deleted = end.saturating_sub(start) as i32;
+        self.structure_change(&editor, (end, -deleted), None)
+    }
+
+    /// Add a column `name` after the file's columns, holding `defaultValue`
+    /// (default empty) in every row. Written on save.
+    #[napi]
+    pub fn add_column(&self, name: String, default_value: Option<String>) -> Result<JsStructureChange> {
+        self.check_writable()?;
+        let mut editor = self.editor_mut()?;
+        let width = editor.headers().len() as u32;
+        editor
+            .add_column(&name, default_value.as_deref().unwrap_or(""))
+            .map_err(|e| Error::from_reason(e.to_string()))?;
+        self.structure_change(&editor, (0, 0), Some((width, 1)))
+    }
+
+    /// Rename the column `from` to `to`. Written on save.
+    #[napi]
+    pub fn rename_column(&self, from: String, to: String) -> Result<JsStructureChange> {
+        self.check_writable()?;
+        let mut editor = self.editor_mut()?;
+        editor
+            .rename_column(&from, &to)
+            .map_err(|e| Error::from_reason(e.to_string()))?;
+        self.structure_change(&editor, (0, 0), None)
+    }
+
+    /// Drop the column `column`; the columns after it move left. Computed
+    /// columns using it are removed too. Written on save.
+    #[napi]
+    pub fn drop_column(&self, column: String) -> Result<JsStructureChange> {
+        self.check_writable()?;
+        let mut editor = self.editor_mut()?;
+        let position = editor.headers().iter().position(|h| *h == column).unwrap_or(0) as u32;
+        editor
+            .drop_column(&column)
+            .map_err(|e| Error::from_reason(e.to_string()))?;
+        self.structure_change(&editor, (0, 0), Some((position + 1, -1)))
+    }
+
+    /// Revert a single row to its original state.
+    #[napi]
+    pub fn revert_row(&self, row: u32) -> Result<()> {
+        let mut editor = self.editor_mut()?;
+        editor.revert_row(row as usize);
+        Ok(())
+    }
+
+    /// Revert all pending edits.
+    #[napi]
+    pub fn revert_all(&self) -> Result<()> {
+        let mut editor = self.editor_mut()?;
+        editor.revert_all();
+        Ok(())
+    }
+
+    /// Undo the latest change: an edit, an insert, delete or move of rows, a column
+    /// change or a revert. Returns false if there was nothing to undo. Rows and
+    /// columns may have changed, so re-read them with getInfo and getPage.
+    #[napi]
+    pub fn undo(&self) -> Result<bool> {
+        self.check_writable()?;
+        let mut editor = self.editor_mut()?;
+        let undone = editor.undo().map_err(|e| Error::from_reason(e.to_string()))?;
+        self.rebind(&editor)?;
+        Ok(undone)
+    }
+
+    /// Redo the latest change undone. Returns false if there was nothing to redo.
+    #[napi]
+    pub fn redo(&self) -> Result<bool> {
+        self.check_writable()?;
+        let mut editor = self.editor_mut()?;
+        let redone = editor.redo().map_err(|e| Error::from_reason(e.to_string()))?;
+        self.rebind(&editor)?;
+        Ok(redone)
+    }
+
+    /// Whether undo has a change to undo. Saving or reloading clears the history.
+    #[napi(getter)]
+    pub fn can_undo(&self) -> Result<bool> {
+        let editor = lock_editor(&self.editor)?;
+        Ok(editor.can_undo())
+    }
+
+    /// Whether redo has a change to redo.
+    #[napi(getter)]
+    pub fn can_redo(&self) -> Result<bool> {
+        let editor = lock_editor(&self.editor)?;
+        Ok(editor.can_redo())
+    }
+
+    /// Rows with pending edits, inserted rows included, in order.
+    #[napi]
+    pub fn get_edited_rows(&self) -> Result<Vec<u32>> {
+        let editor = lock_editor(&self.editor)?;
+        Ok(editor.edited_rows().into_iter().map(|r| r as u32).collect())
+    }
+
+    /// A row before and after its pending edits, with the columns that changed.
+    #[napi]
+    pub fn get_row_diff(&self, row: u32) -> Result<JsRowDiff> {
+        let editor = lock_editor(&self.editor)?;
+        let diff = editor
+            .row_diff(row as usize)
+            .map_err(|e| Error::from_reason(e.to_string()))?;
+        Ok(JsRowDiff {
+            original: diff.original,
+            current: diff.current,
+            changed_columns: diff.changed.into_iter().map(|c| c as u32).collect(),
+        })
+    }
+
+    /// Whether a cell has a pending edit that changed its value, for marking it in the grid.
+    #[napi]
+    pub fn is_cell_edited(&self, row: u32, col: u32) -> Result<bool> {
+        let editor = lock_editor(&self.editor)?;
+        editor
+            .is_cell_edited(row as usize, col as usize)
+            .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
+    /// Save all pending edits atomically.
+    #[napi(ts_args_type = "signal?: AbortSignal | undefined | null")]
+    pub fn save(&self, env: Env, signal: Option<Object>) -> Result<AsyncTask<SaveTask>> {
+        self.save_task(&env, SaveOptions::default(), None, signal)
+    }
+
+    /// Save all pending edits atomically, with extra options.
+    #[napi(ts_args_type = "options: JsSaveOptions, signal?: AbortSignal | undefined | null")]
+    pub fn save_with_options(
+        &self,
+        env: Env,
+        options: JsSaveOptions,
+        signal: Option<Object>,
+    ) -> Result<AsyncTask<SaveTask>> {
+        self.save_task(&env, save_options_from_js(options), None, signal)
+    }
+
+    /// Write the current state to a different file, leaving the source and pending edits untouched.
+    #[napi(ts_args_type = "path: string, options?: JsSaveOptions | undefined | null, \
+                           signal?: AbortSignal | undefined | null")]
+    pub fn save_as(
+        &self,
+        env: Env,
+        path: String,
+        options: Option<JsSaveOptions>,
+        signal: Option<Object>,
+    ) -> Result<AsyncTask<SaveTask>> {
+        let opts = options.map(save_options_from_js).unwrap_or_default();
+        self.save_task(&env, opts, Some(path.into()), signal)
+    }
+
+    /// Save all pending edits, overwriting the file even if it changed on disk.
+    /// Reports no progress and can only be aborted before it starts.
+    #[napi(ts_args_type = "signal?: AbortSignal | undefined | null")]
+    pub fn save_force(&self, env: Env, signal: Option<Object>) -> Result<AsyncTask<SaveTask>> {
+        self.check_writable()?;
+        let cancel = cancel_flag(&env, signal)?;
+        Ok(AsyncTask::new(SaveTask {
+            editor: self.editor.clone(),
+            options: SaveOptions::default(),
+            target: None,
+            force: true,
+            cancel,
+        }))
+    }
+
+    /// Call `onChanged` (the `changed` event) whenever something other than
+    /// this document modifies or removes the file, e.g. to offer a reload.
+    /// Replaces any earlier watch.
+    #[napi(ts_args_type = "onChanged: (change: JsFileChange) => void")]
+    pub fn watch(
+        &self,
+        on_changed: ThreadsafeFunction<JsFileChange, (), JsFileChange, Status, false, true>,
+    ) -> Result<()> {
+        use notify::Watcher;
+
+        let path = lock_editor(&self.editor)?.reader().path().to_path_buf();
+        let name = path.file_name().map(|n| n.to_os_string());
+        let editor = self.editor.clone();
+        // Fingerprint of the file as last reported, so one change is reported once
+        let mut reported: Option<Option<FileFingerprint>> = None;
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            let Ok(event) = event else {
+                return;
+            };
+            if !event.paths.iter().any(|p| p.file_name() == name.as_deref()) {
+                return;
+            }
+            // The editor is locked while this document saves, so its own
+            // writes are seen only once it has re-read the file
+            let Ok(editor) = lock_editor(&editor) else {
+                return;
+            };
+            let current = FileFingerprint::of(editor.reader().path()).ok();
+            let unchanged = current.as_ref() == Some(editor.reader().fingerprint());
+            if unchanged || reported.as_ref() == Some(&current) {
+                return;
+            }
+            let change = JsFileChange {
+                path: editor.reader().path().to_string_lossy().into_owned(),
+                kind: if current.is_some() { "modified" } else { "removed" }.to_string(),
+            };
+            reported = Some(current);
+            on_changed.call(change, ThreadsafeFunctionCallMode::NonBlocking);
+        })
+        .map_err(|e| Error::from_reason(e.to_string()))?;
+        // Watch the directory, so a file replaced by a rename is still seen
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        watcher
+            .watch(dir, notify::RecursiveMode::NonRecursive)
+            .map_err(|e| Error::from_reason(e.to_string()))?;
+        *self.watcher.lock().map_err(|e| Error::from_reason(e.to_string()))? = Some(watcher);
+        Ok(())
+    }
+
+    /// Stop watching the file.
+    #[napi]
+    pub fn unwatch(&self) -> Result<()> {
+        self.watcher.lock().map_err(|e| Error::from_reason(e.to_string()))?.take();
+        Ok(())
+    }
+
+    /// Re-open the file from disk, e.g. after a `changed` event, keeping
+    /// pending edits that still apply: edits to rows past the new end are
+    /// dropped, and it fails if a column added, renamed or dropped here is
+    /// gone, or if rows were moved, inserted or deleted and the row count
+    /// changed.
+    /// Resolves to the number of edits kept.
+    #[napi]
+    pub fn reload(&self) -> Result<AsyncTask<ReloadTask>> {
+        Ok(AsyncTask::new(ReloadTask {
+            editor: self.editor.clone(),
+            computed: self.computed.clone(),
+            pages: self.pages.clone(),
+            progress: self.progress("openProgress", &Arc::new(AtomicBool::new(false)))?,
+        }))
+    }
+
+    /// Re-open the file from disk and re-apply pending edits. Returns the number of edits kept.
+    #[napi]
+    pub fn reload_and_reapply(&self) -> Result<u32> {
+        let mut editor = self.editor_mut()?;
+        editor
+            .reload_and_reapply()
+            .map(|kept| kept as u32)
+            .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
+    /// Start recording edits to `<file>.journal` as they are made, for crash recovery.
+    #[napi]
+    pub fn enable_journal(&self) -> Result<()> {
+        self.check_writable()?;
+        let mut editor = lock_editor(&self.editor)?;
+        editor
+            .enable_journal()
+            .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
+    /// Replay edits from a journal left by an earlier session. Returns the number of pending edits.
+    #[napi]
+    pub fn recover(&self) -> Result<u32> {
+        self.check_writable()?;
+        let mut editor = self.editor_mut()?;
+        editor
+            .recover()
+            .map(|n| n as u32)
+            .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
+    /// Delete the edit journal and stop journaling.
+    #[napi]
+    pub fn discard_journal(&self) -> Result<()> {
+        let mut editor = lock_editor(&self.editor)?;
+        editor
+            .discard_journal()
+            .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
+    /// Whether a journal from an earlier session is waiting to be recovered.
+    #[napi(getter)]
+    pub fn has_recoverable_journal(&self) -> Result<bool> {
+        let editor = lock_editor(&self.editor)?;
+        Ok(editor.has_recoverable_journal())
+    }
+
+    /// Start building an index on a column in the background.
+    /// Poll `indexStatus` to see when it is ready.
+    #[napi]
+    pub fn build_index(&self, column: String) -> Result<()> {
+        let reader = {
+            let editor = lock_editor(&self.editor)?;
+            editor
+                .reader()
+                .reopen()
+                .map_err(|e| Error::from_reason(e.to_string()))?
+        };
+        let store = IndexStore::for_file(reader.path());
+        let handle = store.build_in_background(Arc::new(reader), &column);
+        let mut builds = self.index_builds.lock().map_err(|e| Error::from_reason(e.to_string()))?;
+        builds.insert(column, handle);
+        Ok(())
+    }
+
+    /// Status of a background index build: "pending", "building:<percent>",
+    /// "ready", "failed: <reason>", or "none" if no build was started.
+    #[napi]
+    pub fn index_status(&self, column: String) -> Result<String> {
+        let builds = self.index_builds.lock().map_err(|e| Error::from_reason(e.to_string()))?;
+        Ok(match builds.get(&column).map(|handle| handle.status()) {
+            None => "none".to_string(),
+            Some(BuildStatus::Pending) => "pending".to_string(),
+            Some(BuildStatus::Building(percent)) => format!("building:{percent}"),
+            Some(BuildStatus::Ready) => "ready".to_string(),
+            Some(BuildStatus::Failed(reason)) => format!("failed: {reason}"),
+        })
+    }
+
+    /// Attach a schema (JSON, as for `massive-csv validate --schema`) that
+    /// `setCell` and `setRow` check edits against; null detaches it.
+    #[napi]
+    pub fn set_schema(&self, schema: Option<String>) -> Result<()> {
+        let schema = schema
+            .as_deref()
+            .map(Schema::from_json)
+            .transpose()
+            .map_err(|e| Error::from_reason(e.to_string()))?;
+        let mut editor = lock_editor(&self.editor)?;
+        editor
+            .set_schema(schema)
+            .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
+    /// Check every row, with pending edits, against the attached schema
+    /// (0 or no maxIssues = all violations).
+    #[napi]
+    pub fn validate_against_schema(&self, max_issues: Option<u32>) -> Result<Vec<JsSchemaViolation>> {
+        let editor = lock_editor(&self.editor)?;
+        let options = ValidateOptions {
+            max_issues: max_issues.unwrap_or(0) as usize,
+        };
+        let report = editor
+            .validate_against_schema(&options)
+            .map_err(|e| Error::from_reason(e.to_string()))?;
+        Ok(report
+            .violations
+            .into_iter()
+            .map(|v| JsSchemaViolation {
+                row: v.row as u32,
+                message: v.to_string(),
+                column: v.column,
+                value: v.value,
+            })
+            .collect())
+    }
+
+    /// Views saved for this file, in the order they were first saved.
+    #[napi]
+    pub fn list_views(&self) -> Result<Vec<JsSavedView>> {
+        let store = self.view_store()?;
+        Ok(store
+            .views()
+            .iter()
+            .map(|v| JsSavedView {
+                name: v.name.clone(),
+                filter: v.filter.clone(),
+                sort: Some(v.sort.clone()),
+                columns: Some(v.columns.clone()),
+            })
+            .collect())
+    }
+
+    /// Save a view, replacing any view with the same name.
+    #[napi]
+    pub fn save_view(&self, view: JsSavedView) -> Result<()> {
+        let mut store = self.view_store()?;
+        store
+            .save_view(SavedView {
+                name: view.name,
+                filter: view.filter,
+                sort: view.sort.unwrap_or_default(),
+                columns: view.columns.unwrap_or_default(),
+            })
+            .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
+    /// Remove a saved view. Returns whether there was one.
+    #[napi]
+    pub fn remove_view(&self, name: String) -> Result<bool> {
+        let mut store = self.view_store()?;
+        store
+            .remove_view(&name)
+            .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
+    /// Rows and columns of a saved view, against the file as saved on disk.
+    #[napi]
+    pub fn apply_view(&self, name: String) -> Result<JsAppliedView> {
+        let store = self.view_store()?;
+        let saved = store
+            .view(&name)
+            .ok_or_else(|| Error::from_reason(format!("no view named '{name}'")))?;
+        let editor = lock_editor(&self.editor)?;
+        let view = saved
+            .apply(editor.reader())
+            .map_err(|e| Error::from_reason(e.to_string()))?;
+        let columns = saved
+            .column_indices(editor.reader())
+            .map_err(|e| Error::from_reason(e.to_string()))?;
+        Ok(JsAppliedView {
+            rows: view.rows().iter().map(|&row| row as u32).collect(),
+            columns: columns.into_iter().map(|c| c as u32).collect(),
+        })
+    }
+
+    /// Bookmarked rows, in row order.
+    #[napi]
+    pub fn list_bookmarks(&self) -> Result<Vec<JsBookmark>> {
+        let store = self.view_store()?;
+        Ok(store
+            .bookmarks()
+            .iter()
+            .map(|b| JsBookmark {
+                row: b.row as u32,
+                label: b.label.clone(),
+            })
+            .collect())
+    }
+
+    /// Bookmark a row, replacing the label of an existing bookmark on it.
+    #[napi]
+    pub fn add_bookmark(&self, row: u32, label: Option<String>) -> Result<()> {
+        let mut store = self.view_store()?;
+        store
+            .add_bookmark(row as usize, label)
+            .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
+    /// Remove the bookmark on a row. Returns whether there was one.
+    #[napi]
+    pub fn remove_bookmark(&self, row: u32) -> Result<bool> {
+        let mut store = self.view_store()?;
+        store
+            .remove_bookmark(row as usize)
+            .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
+    /// Number of pending edits.
+    #[napi(getter)]
+    pub fn edit_count(&self) -> Result<u32> {
+        let editor = lock_editor(&self.editor)?;
+        Ok(editor.edit_count() as u32)
+    }
+
+    /// Whether there are unsaved changes.
+    #[napi(getter)]
+    pub fn has_changes(&self) -> Result<bool> {
+        let editor = lock_editor(&self.editor)?;
+        Ok(editor.has_changes())
+    }
+}
+
+impl CsvDocument {
+    fn new(editor: CsvEditor, read_only: bool) -> Self {
+        let computed = ComputedColumns::new(editor.reader().canonical_headers());
+        Self::sharing(
+            Arc::new(Mutex::new(Some(editor))),
+            Arc::new(Mutex::new(computed)),
+            Arc::new(Mutex::new(PageCache::new(PAGE_CACHE_SIZE))),
+            read_only,
+        )
+    }
+
+    /// Another handle on this document: edits, computed columns and closing are
+    /// shared, listeners, index builds and watching are not.
+    fn share(&self) -> Self {
+        Self::sharing(self.editor.clone(), self.computed.clone(), self.pages.clone(), self.read_only)
+    }
+
+    fn sharing(
+        editor: SharedEditor,
+        computed: Arc<Mutex<ComputedColumns>>,
+        pages: Arc<Mutex<PageCache>>,
+        read_only: bool,
+    ) -> Self {
+        Self {
+            editor,
+            read_only,
+            index_builds: Mutex::new(HashMap::new()),
+            computed,
+            listeners: Mutex::new(HashMap::new()),
+            pages,
+            watcher: Mutex::new(None),
+        }
+    }
+
+    /// Fail if the document was opened read-only.
+    fn check_writable(&self) -> Result<()> {
+        if self.read_only {
+            return Err(Error::from_reason("the document was opened read-only".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Lock the editor to change what its rows read as, dropping cached pages.
+    fn editor_mut(&self) -> Result<EditorGuard<'_>> {
+        let editor = lock_editor(&self.editor)?;
+        self.pages.lock().map_err(|e| Error::from_reason(e.to_string()))?.clear();
+        Ok(editor)
+    }
+
+    fn save_task(
+        &self,
+        env: &Env,
+        options: SaveOptions,
+        target: Option<PathBuf>,
+        signal: Option<Object>,
+    ) -> Result<AsyncTask<SaveTask>> {
+        if target.is_none() {
+            self.check_writable()?;
+        }
+        let cancel = cancel_flag(env, signal)?;
+        let progress = self.progress("saveProgress", &cancel)?;
+        Ok(AsyncTask::new(SaveTask {
+            editor: self.editor.clone(),
+            options: SaveOptions { progress, ..options },
+            target,
+            force: false,
+            cancel,
+        }))
+    }
+
+    /// Look computed columns up again after the headers may have changed.
+    fn rebind(&self, editor: &CsvEditor) -> Result<()> {
+        let mut computed = self.computed.lock().map_err(|e| Error::from_reason(e.to_string()))?;
+        rebind_computed(&mut computed, editor.headers());
+        Ok(())
+    }
+
+    /// What a structural edit to `editor` renumbered: rows from `rows.0` by
+    /// `rows.1`, and columns from `columns.0` by `columns.1` if given.
+    fn structure_change(
+        &self,
+        editor: &CsvEditor,
+        rows: (u32, i32),
+        columns: Option<(u32, i32)>,
+    ) -> Result<JsStructureChange> {
+        let mut computed = self.computed.lock().map_err(|e| Error::from_reason(e.to_string()))?;
+        let headers = editor.headers();
+        rebind_computed(&mut computed, headers);
+        let (first_shifted_column, column_shift) = columns.unwrap_or((headers.len() as u32, 0));
+        Ok(JsStructureChange {
+            row_count: editor.row_count() as u32,
+            headers: computed.headers(headers),
+            first_shifted_row: rows.0,
+            row_shift: rows.1,
+            first_shifted_column,
+            column_shift,
+        })
+    }
+
+    /// A reporter calling the listeners for `event` as they are now, stopped by `cancel`.
+    fn progress(&self, event: &str, cancel: &Arc<AtomicBool>) -> Result<Progress> {
+        let listeners = self.listeners.lock().map_err(|e| Error::from_reason(e.to_string()))?;
+        let listeners = listeners.get(event).cloned().unwrap_or_default();
+        Ok(report_to(listeners, cancel.clone()))
+    }
+
+    fn view_store(&self) -> Result<ViewStore> {
+        let editor = lock_editor(&self.editor)?;
+        ViewStore::for_file(editor.reader().path()).map_err(|e| Error::from_reason(e.to_string()))
+    }
+}
+
+/// A builder opening `path` with `options`, checked but not yet opened.
+fn open_builder(path: String, options: Option<JsOpenOptions>) -> Result<OpenBuilder<CsvEditor>> {
+    let mut builder = CsvEditor::builder(path);
+    if let Some(o) = options {
+        if let Some(delimiter) = o.delimiter {
+            builder = builder.delimiter(single_byte(&delimiter, "delimiter")?);
+        }
+        if let Some(quote) = o.quote_char {
+            builder = builder.quote_char(single_byte(&quote, "quoteChar")?);
+        }
+        if let Some(escape) = o.escape {
+            builder = builder.escape(match escape.as_str() {
+                "doubled" => EscapeStyle::Doubled,
+                "backslash" => EscapeStyle::Backslash,
+                _ => return Err(Error::from_reason(format!("unknown escape '{escape}'"))),
+            });
+        }
+        if let Some(widths) = o.widths {
+            builder = builder.fixed_width(widths.into_iter().map(|w| w as usize).collect());
+        }
+        if let Some(label) = o.encoding {
+            let encoding = Encoding::from_label(&label)
+                .ok_or_else(|| Error::from_reason(format!("unknown encoding '{label}'")))?;
+            builder = builder.encoding(encoding);
+        }
+        builder = builder
+            .lenient(o.lenient.unwrap_or(false))
+            .no_headers(!o.has_headers.unwrap_or(true));
+    }
+    Ok(builder)
+}
+
+/// Rows in the layout `getRowsBuffer` documents.
+fn encode_rows(rows: &[Vec<String>]) -> Vec<u8> {
+    let size: usize = rows
+        .iter()
+        .map(|fields| 4 + fields.iter().map(|f| 4 + f.len()).sum::<usize>())
+        .sum();
+    let mut bytes = Vec::with_capacity(4 + size);
+    bytes.extend_from_slice(&(rows.len() as u32).to_le_bytes());
+    for fields in rows {
+        bytes.extend_from_slice(&(fields.len() as u32).to_le_bytes());
+        for field in fields {
+            bytes.extend_from_slice(&(field.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(field.as_bytes());
+        }
+    }
+    bytes
+}
+
+/// Bind `computed` to `headers` again if their number changed, dropping
+/// computed columns whose expression no longer applies.
+fn rebind_computed(computed: &mut ComputedColumns, headers: &[String]) {
+    // Computed columns pad rows to the width they were defined at
+    if computed.headers(headers).len() == headers.len() + computed.columns().len() {
+        return;
+    }
+    let mut rebound = ComputedColumns::new(headers);
+    for column in computed.columns() {
+        let _ = rebound.add(&format!("{} = {}", column.name, column.expression), headers);
+    }
+    *computed = rebound;
+}
+
+/// A document's editor, shared with the tasks running on it; `None` once
+/// the document is closed.
+type SharedEditor = Arc<Mutex<Option<CsvEditor>>>;
+
+/// A locked editor of a document that is still open.
+struct EditorGuard<'a>(MutexGuard<'a, Option<CsvEditor>>);
+
+impl std::ops::Deref for EditorGuard<'_> {
+    type Target = CsvEditor;
+
+    fn deref(&self) -> &CsvEditor {
+        self.0.as_ref().expect("checked open by lock_editor")
+    }
+}
+
+impl std::ops::DerefMut for EditorGuard<'_> {
+    fn deref_mut(&mut self) -> &mut CsvEditor {
+        self.0.as_mut().expect("checked open by lock_editor")
+    }
+}
+
+/// Lock `editor`, failing if its document was closed.
+fn lock_editor(editor: &Mutex<Option<CsvEditor>>) -> Result<EditorGuard<'_>> {
+    let guard = editor.lock().map_err(|e| Error::from_reason(e.to_string()))?;
+    if guard.is_none() {
+        return Err(Error::from_reason("the document is closed".to_string()));
+    }
+    Ok(EditorGuard(guard))
+}
+
+/// Rows `searchStream` scans each time it locks the document.
+const STREAM_ROWS: usize = 64 * 1024;
+
+/// Pages `getPage` keeps.
+const PAGE_CACHE_SIZE: usize = 8;
+
+/// A cached page and when it was last used.
+type CachedPage = (Arc<Vec<Vec<String>>>, u64);
+
+/// Pages of rows with computed columns, keyed by offset and limit, evicting
+/// the least recently used.
+struct PageCache {
+    pages: HashMap<(usize, usize), CachedPage>,
+    capacity: usize,
+    clock: u64,
+    /// Offset of the last page asked for, to tell which way the grid scrolls.
+    last_offset: Option<usize>,
+    /// Page being prefetched.
+    pending: Option<(usize, usize)>,
+    /// A page whose prefetch failed, with the error, for the `getPage` that
+    /// asks for it.
+    failed: Option<((usize, usize), String)>,
+}
+
+impl PageCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            pages: HashMap::with_capacity(capacity),
+            capacity,
+            clock: 0,
+            last_offset: None,
+            pending: None,
+            failed: None,
+        }
+    }
+
+    fn contains(&self, key: (usize, usize)) -> bool {
+        self.pages.contains_key(&key)
+    }
+
+    fn get(&mut self, key: (usize, usize)) -> Option<Arc<Vec<Vec<String>>>> {
+        self.clock += 1;
+        let (page, used) = self.pages.get_mut(&key)?;
+        *used = self.clock;
+        Some(Arc::clone(page))
+    }
+
+    fn insert(&mut self, key: (usize, usize), page: Arc<Vec<Vec<String>>>) {
+        if self.pages.len() >= self.capacity {
+            let oldest = self.pages.iter().min_by_key(|(_, (_, used))| *used).map(|(&k, _)| k);
+            if let Some(oldest) = oldest {
+                self.pages.remove(&oldest);
+            }
+        }
+        self.clock += 1;
+        self.pages.insert(key, (page, self.clock));
+    }
+
+    fn clear(&mut self) {
+        self.pages.clear();
+        self.failed = None;
+    }
+}
+
+/// A page `getPage` reads ahead of the grid.
+struct Prefetch {
+    editor: SharedEditor,
+    computed: Arc<Mutex<ComputedColumns>>,
+    pages: Arc<Mutex<PageCache>>,
+    key: (usize, usize),
+}
+
+/// Read `job`'s page into its cache on the one thread prefetching for every
+/// document, recording a failure for the `getPage` that later asks for it.
+fn prefetch(job: Prefetch) {
+    static WORKER: OnceLock<mpsc::Sender<Prefetch>> = OnceLock::new();
+    let worker = WORKER.get_or_init(|| {
+        let (sender, jobs) = mpsc::channel::<Prefetch>();
+        std::thread::Builder::new()
+            .name("massive-csv-prefetch".to_string())
+            .spawn(move || {
+                for job in jobs {
+                    let (offset, limit) = job.key;
+                    let result = read_page(&job.editor, &job.computed, &job.pages, offset, limit);
+                    if let Ok(mut pages) = job.pages.lock() {
+                        pages.pending = None;
+                        if let Err(e) = result {
+                            pages.failed = Some((job.key, e.reason));
+                        }
+                    }
+                }
+            })
+            .expect("spawn the page prefetch thread");
+        sender
+    });
+    if let Err(mpsc::SendError(job)) = worker.send(job) {
+        if let Ok(mut pages) = job.pages.lock() {
+            pages.pending = None;
+        }
+    }
+}
+
+/// Rows `offset..offset + limit` with computed columns, from `pages` or read
+/// and cached there, and the editor's row count. The editor stays locked
+/// throughout, so a page read before an edit is never cached after it.
+fn read_page(
+    editor: &Mutex<Option<CsvEditor>>,
+    computed: &Mutex<ComputedColumns>,
+    pages: &Mutex<PageCache>,
+    offset: usize,
+    limit: usize,
+) -> Result<(Arc<Vec<Vec<String>>>, usize)> {
+    let editor = lock_editor(editor)?;
+    let row_count = editor.row_count();
+    {
+        let mut pages = pages.lock().map_err(|e| Error::from_reason(e.to_string()))?;
+        if let Some(page) = pages.get((offset, limit)) {
+            return Ok((page, row_count));
+        }
+        if let Some((_, reason)) = pages.failed.take_if(|(key, _)| *key == (offset, limit)) {
+            return Err(Error::from_reason(reason));
+        }
+    }
+    let computed = computed.lock().map_err(|e| Error::from_reason(e.to_string()))?;
+    let end = offset.saturating_add(limit).min(row_count);
+    let mut rows = Vec::with_capacity(end.saturating_sub(offset));
+    for i in offset..end {
+        let mut fields = editor.get_row(i).map_err(|e| Error::from_reason(e.to_string()))?;
+        computed.extend(&mut fields);
+        rows.push(fields);
+    }
+    let page = Arc::new(rows);
+    let mut pages = pages.lock().map_err(|e| Error::from_reason(e.to_string()))?;
+    pages.insert((offset, limit), page.clone());
+    Ok((page, row_count))
+}
+
+/// Search options from JS, reporting to `progress`.
+fn search_options(options: Option<JsSearchOptions>, progress: Progress) -> Result<SearchOptions> {
+    let Some(o) = options else {
+        return Ok(SearchOptions { progress, ..Default::default() });
+    };
+    Ok(SearchOptions {
+        column: o.column,
+        case_insensitive: o.case_insensitive.unwrap_or(false),
+        max_results: o.max_results.unwrap_or(0) as usize,
+        offset: o.offset.unwrap_or(0) as usize,
+        filter: o
+            .filter
+            .map(|f| Expr::parse(&f))
+            .transpose()
+            .map_err(|e| Error::from_reason(e.to_string()))?,
+        rows: None,
+        progress,
+    })
+}
+
+/// A search result for JS, with the values of computed columns added.
+fn js_search_result(mut result: SearchResult, computed: &ComputedColumns) -> JsSearchResult {
+    computed.extend(&mut result.fields);
+    JsSearchResult {
+        row_num: result.row_num as u32,
+        fields: result.fields,
+        matches: result
+            .matches
+            .iter()
+            .map(|m| JsFieldMatch { column: m.column as u32, start: m.start as u32, end: m.end as u32 })
+            .collect(),
+    }
+}
+
+/// A progress reporter calling each of `listeners`, stopped by `cancel`.
+fn report_to(listeners: Vec<Arc<ProgressListener>>, cancel: Arc<AtomicBool>) -> Progress {
+    let progress = if listeners.is_empty() {
+        Progress::default()
+    } else {
+        Progress::new(move |done, total| {
+            for listener in &listeners {
+                let progress = JsProgress {
+                    done: done as f64,
+                    total: total as f64,
+                };
+                listener.call(progress, ThreadsafeFunctionCallMode::NonBlocking);
+            }
+        })
+    };
+    progress.with_cancel(cancel)
+}
+
+/// A flag set once `signal` (an AbortSignal) aborts, or already set if it has.
+///
+/// napi's own AbortSignal support only cancels work that hasn't started, so
+/// the flag is handed to the work's progress reporter instead, to stop it
+/// mid-way too.
+fn cancel_flag(env: &Env, signal: Option<Object>) -> Result<Arc<AtomicBool>> {
+    let cancel = Arc::new(AtomicBool::new(false));
+    let Some(signal) = signal else {
+        return Ok(cancel);
+    };
+    if signal.get_named_property::<bool>("aborted")? {
+        cancel.store(true, Ordering::Relaxed);
+        return Ok(cancel);
+    }
+    let flag = cancel.clone();
+    let on_abort = env.create_function_from_closure::<(), (), _>("onAbort", move |_| {
+        flag.store(true, Ordering::Relaxed);
+        Ok(())
+    })?;
+    let add_event_listener: Function<(&str, Function<(), ()>), ()> =
+        signal.get_named_property("addEventListener")?;
+    add_event_listener.apply(signal, ("abort", on_abort))?;
+    Ok(cancel)
+}
+
+/// Fail with "Cancelled" if `cancel` was set before the work started.
+fn check_cancelled(cancel: &AtomicBool) -> Result<()> {
+    if cancel.load(Ordering::Relaxed) {
+        return Err(Error::from_reason(MassiveCsvError::Cancelled.to_string()));
+    }
+    Ok(())
+}
+
+fn single_byte(value: &str, name: &str) -> Result<u8> {
+    match value.as_bytes() {
+        [b] => Ok(*b),
+        _ => Err(Error::from_reason(format!(
+            "{name} must be a single ASCII character, got '{value}'"
+        ))),
+    }
+}
+
+fn save_options_from_js(options: JsSaveOptions) -> SaveOptions {
+    let defaults = SaveOptions::default();
+    SaveOptions {
+        patch: options.patch_path.map(|path| PatchOutput {
+            path: path.into(),
+            key_columns: options.patch_key.unwrap_or_default(),
+        }),
+        backup: options.backup.unwrap_or(false),
+        backup_suffix: options.backup_suffix.unwrap_or(defaults.backup_suffix),
+        snapshot: options.snapshot.unwrap_or(false),
+        line_ending: options.line_ending.map(|le| match le.as_str() {
+            "crlf" | "\r\n" => LineEnding::CrLf,
+            _ => LineEnding::Lf,
+        }),
+        trailing_newline: options.trailing_newline,
+        delimiter: options.delimiter.and_then(|d| d.bytes().next()),
+        quote_style: options.quote_style.map(|q| match q.as_str() {
+            "always" => QuoteStyle::Always,
+            "non-numeric" => QuoteStyle::NonNumeric,
+            "never" => QuoteStyle::Never,
+            _ => QuoteStyle::Necessary,
+        }),
+        encoding: options.encoding.as_deref().and_then(Encoding::from_label),
+        compression: None,
+        durability: match options.durability.as_deref() {
+            Some("data") => Durability::DataSync,
+            Some("full") => Durability::Full,
+            _ => Durability::None,
+        },
+        progress: defaults.progress,
     }
 }
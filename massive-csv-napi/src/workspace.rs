@@ -0,0 +1,586 @@
+//! `CsvWorkspace`: several documents open at once, looked up by id, with
+//! operations across them.
+
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard, TryLockError};
+
+use napi::bindgen_prelude::*;
+use napi::{Env, Task};
+use napi_derive::napi;
+
+use massive_csv_core::diff::diff;
+use massive_csv_core::{join, CsvEditor, JoinOptions, JoinType, PatchOp};
+
+use crate::{
+    cancel_flag, lock_editor, open_builder, report_to, CsvDocument, JsOpenOptions, OpenTask,
+    PageCache, SharedEditor,
+};
+
+/// A workspace's documents by id.
+type Documents = Arc<Mutex<HashMap<String, Entry>>>;
+
+struct Entry {
+    /// The file's canonical path, to keep one writer per file.
+    path: PathBuf,
+    read_only: bool,
+    /// `None` while the document is opening.
+    document: Option<CsvDocument>,
+}
+
+impl Entry {
+    /// Whether the document is opening or open, rather than closed by `close`
+    /// on one of its handles.
+    fn is_open(&self) -> bool {
+        let Some(document) = &self.document else {
+            return true;
+        };
+        match document.editor.try_lock() {
+            Ok(editor) => editor.is_some(),
+            // Busy running a task, so not closed
+            Err(TryLockError::WouldBlock) => true,
+            Err(TryLockError::Poisoned(e)) => e.into_inner().is_some(),
+        }
+    }
+}
+
+/// A document open in a workspace.
+#[napi(object)]
+pub struct JsWorkspaceDocument {
+    pub id: String,
+    pub path: String,
+    pub read_only: bool,
+}
+
+/// Memory held by the documents of a workspace, in bytes.
+#[napi(object)]
+pub struct JsMemoryUsage {
+    pub documents: u32,
+    /// Bytes of files mapped into memory; the OS pages them in as rows are
+    /// read, so only part of them may be resident.
+    pub mapped: f64,
+    /// Bytes of row indexes.
+    pub index: f64,
+    /// Bytes of pending edits and undo history, roughly.
+    pub edits: f64,
+}
+
+/// What `diff` found between two documents.
+#[napi(object)]
+pub struct JsDiffSummary {
+    /// Rows present in both with at least one changed cell.
+    pub updated_rows: u32,
+    pub updated_cells: u32,
+    pub inserted: u32,
+    pub deleted: u32,
+}
+
+/// Options for `join`.
+#[napi(object)]
+pub struct JsJoinOptions {
+    /// "inner" (default), "left", "right" or "full".
+    pub join_type: Option<String>,
+    /// Key columns of the left document.
+    pub left_on: Vec<String>,
+    /// Key columns of the right document, paired with leftOn (default: the same names).
+    pub right_on: Option<Vec<String>>,
+}
+
+/// What `join` wrote.
+#[napi(object)]
+pub struct JsJoinSummary {
+    pub rows_written: u32,
+    /// Left rows with at least one match.
+    pub left_matched: u32,
+    /// Left rows without a match (written only by left and full joins).
+    pub left_unmatched: u32,
+    /// Right rows no left row matched (written only by right and full joins).
+    pub right_unmatched: u32,
+}
+
+/// Several documents open at once, by id, for an app with a tab per file.
+///
+/// Each file can be open for writing as one document at a time; any number
+/// of `readOnly` documents can read it alongside. `get` returns another
+/// handle on an open document: handles share its edits and closing, but
+/// each has its own progress listeners and watcher.
+#[napi]
+pub struct CsvWorkspace {
+    documents: Documents,
+}
+
+impl Default for CsvWorkspace {
+    fn default() -> Self {
+        Self {
+            documents: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+}
+
+/// Opens a file off the event loop, for `CsvWorkspace.open`.
+pub struct WorkspaceOpenTask {
+    open: OpenTask,
+    documents: Documents,
+    id: String,
+}
+
+#[napi]
+impl Task for WorkspaceOpenTask {
+    type Output = CsvEditor;
+    type JsValue = CsvDocument;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        self.open.compute()
+    }
+
+    fn resolve(&mut self, env: Env, editor: Self::Output) -> Result<Self::JsValue> {
+        let document = self.open.resolve(env, editor)?;
+        let mut documents = lock(&self.documents)?;
+        let Some(entry) = documents.get_mut(&self.id) else {
+            document.close()?;
+            return Err(Error::from_reason(format!(
+                "document '{}' was closed while opening",
+                self.id
+            )));
+        };
+        let handle = document.share();
+        entry.document = Some(document);
+        Ok(handle)
+    }
+
+    fn reject(&mut self, _env: Env, err: Error) -> Result<Self::JsValue> {
+        // Give the id and the file back
+        if let Ok(mut documents) = self.documents.lock() {
+            if documents
+                .get(&self.id)
+                .is_some_and(|entry| entry.document.is_none())
+            {
+                documents.remove(&self.id);
+            }
+        }
+        Err(err)
+    }
+}
+
+/// Compares two documents off the event loop, for `diff`.
+pub struct DiffTask {
+    old: SharedEditor,
+    new: SharedEditor,
+    key_columns: Vec<String>,
+    patch_path: Option<PathBuf>,
+}
+
+#[napi]
+impl Task for DiffTask {
+    type Output = JsDiffSummary;
+    type JsValue = JsDiffSummary;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        let patch = with_editors(&self.old, &self.new, |old, new| {
+            diff(old.reader(), new.reader(), &self.key_columns)
+                .map_err(|e| Error::from_reason(e.to_string()))
+        })?;
+        if let Some(path) = &self.patch_path {
+            patch
+                .save(path)
+                .map_err(|e| Error::from_reason(e.to_string()))?;
+        }
+        let mut updated_rows = HashSet::new();
+        let mut summary = JsDiffSummary {
+            updated_rows: 0,
+            updated_cells: 0,
+            inserted: 0,
+            deleted: 0,
+        };
+        for op in &patch.ops {
+            match op {
+                PatchOp::Update { key, .. } => {
+                    updated_rows.insert(key);
+                    summary.updated_cells += 1;
+                }
+                PatchOp::Insert { .. } => summary.inserted += 1,
+                PatchOp::Delete { .. } => summary.deleted += 1,
+            }
+        }
+        summary.updated_rows = updated_rows.len() as u32;
+        Ok(summary)
+    }
+
+    fn resolve(&mut self, _env: Env, summary: Self::Output) -> Result<Self::JsValue> {
+        Ok(summary)
+    }
+}
+
+/// Joins two documents off the event loop, for `join`.
+pub struct JoinTask {
+    left: SharedEditor,
+    right: SharedEditor,
+    output: PathBuf,
+    options: JoinOptions,
+}
+
+#[napi]
+impl Task for JoinTask {
+    type Output = JsJoinSummary;
+    type JsValue = JsJoinSummary;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        let summary = with_editors(&self.left, &self.right, |left, right| {
+            join(left.reader(), right.reader(), &self.output, &self.options)
+                .map_err(|e| Error::from_reason(e.to_string()))
+        })?;
+        Ok(JsJoinSummary {
+            rows_written: summary.rows_written as u32,
+            left_matched: summary.left_matched as u32,
+            left_unmatched: summary.left_unmatched as u32,
+            right_unmatched: summary.right_unmatched as u32,
+        })
+    }
+
+    fn resolve(&mut self, _env: Env, summary: Self::Output) -> Result<Self::JsValue> {
+        Ok(summary)
+    }
+}
+
+/// Copies rows between documents off the event loop, for `copyRows`.
+pub struct CopyRowsTask {
+    source: SharedEditor,
+    target: SharedEditor,
+    /// The target's page cache, dropped once rows are inserted.
+    pages: Arc<Mutex<PageCache>>,
+    /// Id of the source, for errors.
+    from: String,
+    rows: Range<usize>,
+    at: Option<usize>,
+}
+
+#[napi]
+impl Task for CopyRowsTask {
+    type Output = u32;
+    type JsValue = u32;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        let (headers, rows) = {
+            let editor = lock_editor(&self.source)?;
+            if self.rows.end > editor.row_count() {
+                return Err(Error::from_reason(format!(
+                    "row range {}..{} runs past the {} rows of '{}'",
+                    self.rows.start,
+                    self.rows.end,
+                    editor.row_count(),
+                    self.from
+                )));
+            }
+            let rows = self
+                .rows
+                .clone()
+                .map(|row| editor.get_row(row))
+                .collect::<massive_csv_core::Result<Vec<_>>>()
+                .map_err(|e| Error::from_reason(e.to_string()))?;
+            (editor.headers().to_vec(), rows)
+        };
+        let mut editor = lock_editor(&self.target)?;
+        lock(&self.pages)?.clear();
+        let columns: Vec<Option<usize>> = editor
+            .headers()
+            .iter()
+            .map(|name| headers.iter().position(|h| h == name))
+            .collect();
+        let rows: Vec<Vec<String>> = rows
+            .into_iter()
+            .map(|fields| {
+                columns
+                    .iter()
+                    .map(|c| c.and_then(|c| fields.get(c).cloned()).unwrap_or_default())
+                    .collect()
+            })
+            .collect();
+        let count = rows.len() as u32;
+        let at = self.at.unwrap_or(editor.row_count());
+        editor
+            .insert_rows(at, rows)
+            .map_err(|e| Error::from_reason(e.to_string()))?;
+        Ok(count)
+    }
+
+    fn resolve(&mut self, _env: Env, count: Self::Output) -> Result<Self::JsValue> {
+        Ok(count)
+    }
+}
+
+#[napi]
+impl CsvWorkspace {
+    #[napi(constructor)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Open a file as document `id`, resolving to a handle on it once its rows are
+    /// indexed. Fails if `id` is taken, or if the file is already open for writing
+    /// in this workspace and `readOnly` isn't set.
+    #[napi(
+        ts_args_type = "id: string, path: string, options?: JsOpenOptions | undefined | null, \
+                           signal?: AbortSignal | undefined | null"
+    )]
+    pub fn open(
+        &self,
+        env: Env,
+        id: String,
+        path: String,
+        options: Option<JsOpenOptions>,
+        signal: Option<Object>,
+    ) -> Result<AsyncTask<WorkspaceOpenTask>> {
+        let cancel = cancel_flag(&env, signal)?;
+        let read_only = options.as_ref().and_then(|o| o.read_only).unwrap_or(false);
+        let canonical =
+            std::fs::canonicalize(&path).map_err(|e| Error::from_reason(format!("{path}: {e}")))?;
+        let builder = open_builder(path, options)?.progress(report_to(Vec::new(), cancel.clone()));
+
+        let mut documents = lock(&self.documents)?;
+        documents.retain(|_, entry| entry.is_open());
+        if documents.contains_key(&id) {
+            return Err(Error::from_reason(format!(
+                "a document '{id}' is already open"
+            )));
+        }
+        if !read_only {
+            let writer = documents
+                .iter()
+                .find(|(_, entry)| entry.path == canonical && !entry.read_only);
+            if let Some((other, _)) = writer {
+                return Err(Error::from_reason(format!(
+                    "{} is already open for writing as '{other}'",
+                    canonical.display()
+                )));
+            }
+        }
+        documents.insert(
+            id.clone(),
+            Entry {
+                path: canonical,
+                read_only,
+                document: None,
+            },
+        );
+        Ok(AsyncTask::new(WorkspaceOpenTask {
+            open: OpenTask {
+                builder: Some(builder),
+                read_only,
+                listeners: Vec::new(),
+                cancel,
+            },
+            documents: self.documents.clone(),
+            id,
+        }))
+    }
+
+    /// Another handle on the open document `id`.
+    #[napi]
+    pub fn get(&self, id: String) -> Result<CsvDocument> {
+        self.document(&id)
+    }
+
+    /// The open documents, in no particular order.
+    #[napi]
+    pub fn list(&self) -> Result<Vec<JsWorkspaceDocument>> {
+        let mut documents = lock(&self.documents)?;
+        documents.retain(|_, entry| entry.is_open());
+        Ok(documents
+            .iter()
+            .map(|(id, entry)| JsWorkspaceDocument {
+                id: id.clone(),
+                path: entry.path.to_string_lossy().into_owned(),
+                read_only: entry.read_only,
+            })
+            .collect())
+    }
+
+    /// Close document `id`, discarding its pending edits, as `close` on any of its
+    /// handles would. Returns whether it was open.
+    #[napi]
+    pub fn close(&self, id: String) -> Result<bool> {
+        let entry = lock(&self.documents)?.remove(&id);
+        match entry {
+            Some(Entry {
+                document: Some(document),
+                ..
+            }) => {
+                document.close()?;
+                Ok(true)
+            }
+            Some(_) => Ok(true),
+            None => Ok(false),
+        }
+    }
+
+    /// Close every document.
+    #[napi]
+    pub fn close_all(&self) -> Result<()> {
+        let entries: Vec<Entry> = lock(&self.documents)?
+            .drain()
+            .map(|(_, entry)| entry)
+            .collect();
+        for document in entries.into_iter().filter_map(|entry| entry.document) {
+            document.close()?;
+        }
+        Ok(())
+    }
+
+    /// Memory held by all open documents.
+    #[napi]
+    pub fn memory_usage(&self) -> Result<JsMemoryUsage> {
+        let mut total = JsMemoryUsage {
+            documents: 0,
+            mapped: 0.0,
+            index: 0.0,
+            edits: 0.0,
+        };
+        for document in self.open_documents()? {
+            let Ok(editor) = lock_editor(&document.editor) else {
+                continue;
+            };
+            let usage = editor.memory_usage();
+            total.documents += 1;
+            total.mapped += usage.mapped as f64;
+            total.index += usage.index as f64;
+            total.edits += usage.edits as f64;
+        }
+        Ok(total)
+    }
+
+    /// Copy rows [start, end) of document `from` into document `to`, inserted at
+    /// `at` (default: appended). Columns are matched by name; target columns the
+    /// source lacks are left empty. Resolves to the number of rows copied, and
+    /// rejects a range that is reversed or runs past the source's last row.
+    #[napi]
+    pub fn copy_rows(
+        &self,
+        from: String,
+        start: u32,
+        end: u32,
+        to: String,
+        at: Option<u32>,
+    ) -> Result<AsyncTask<CopyRowsTask>> {
+        let source = self.document(&from)?;
+        let target = self.document(&to)?;
+        target.check_writable()?;
+        if start > end {
+            return Err(Error::from_reason(format!(
+                "invalid row range {start}..{end}: start is past end"
+            )));
+        }
+        Ok(AsyncTask::new(CopyRowsTask {
+            source: source.editor,
+            target: target.editor,
+            pages: target.pages,
+            from,
+            rows: start as usize..end as usize,
+            at: at.map(|at| at as usize),
+        }))
+    }
+
+    /// Compare documents `oldId` and `newId` as saved on disk, matching rows by
+    /// `keyColumns`, and optionally write the changes as a .mcpatch file.
+    #[napi]
+    pub fn diff(
+        &self,
+        old_id: String,
+        new_id: String,
+        key_columns: Vec<String>,
+        patch_path: Option<String>,
+    ) -> Result<AsyncTask<DiffTask>> {
+        Ok(AsyncTask::new(DiffTask {
+            old: self.document(&old_id)?.editor,
+            new: self.document(&new_id)?.editor,
+            key_columns,
+            patch_path: patch_path.map(PathBuf::from),
+        }))
+    }
+
+    /// Join documents `leftId` and `rightId`, as saved on disk, on key columns
+    /// into a CSV file at `output`.
+    #[napi]
+    pub fn join(
+        &self,
+        left_id: String,
+        right_id: String,
+        output: String,
+        options: JsJoinOptions,
+    ) -> Result<AsyncTask<JoinTask>> {
+        let join_type = match options.join_type {
+            Some(name) => JoinType::from_name(&name)
+                .ok_or_else(|| Error::from_reason(format!("unknown join type '{name}'")))?,
+            None => JoinType::Inner,
+        };
+        Ok(AsyncTask::new(JoinTask {
+            left: self.document(&left_id)?.editor,
+            right: self.document(&right_id)?.editor,
+            output: PathBuf::from(output),
+            options: JoinOptions {
+                join_type,
+                left_on: options.left_on,
+                right_on: options.right_on.unwrap_or_default(),
+                ..Default::default()
+            },
+        }))
+    }
+}
+
+impl CsvWorkspace {
+    /// A handle on the open document `id`.
+    fn document(&self, id: &str) -> Result<CsvDocument> {
+        let documents = lock(&self.documents)?;
+        match documents.get(id) {
+            Some(entry) if !entry.is_open() => {
+                Err(Error::from_reason(format!("document '{id}' is closed")))
+            }
+            Some(Entry {
+                document: Some(document),
+                ..
+            }) => Ok(document.share()),
+            Some(_) => Err(Error::from_reason(format!(
+                "document '{id}' is still opening"
+            ))),
+            None => Err(Error::from_reason(format!("no document '{id}'"))),
+        }
+    }
+
+    /// Handles on every open document, taken so the workspace isn't locked while
+    /// they are used.
+    fn open_documents(&self) -> Result<Vec<CsvDocument>> {
+        let documents = lock(&self.documents)?;
+        Ok(documents
+            .values()
+            .filter_map(|entry| entry.document.as_ref())
+            .map(CsvDocument::share)
+            .collect())
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> Result<MutexGuard<'_, T>> {
+    mutex.lock().map_err(|e| Error::from_reason(e.to_string()))
+}
+
+/// Run `f` on the editors of two documents, locked in address order so two tasks
+/// locking the same pair the other way round can't deadlock. The same document
+/// is locked once.
+fn with_editors<T>(
+    first: &SharedEditor,
+    second: &SharedEditor,
+    f: impl FnOnce(&CsvEditor, &CsvEditor) -> Result<T>,
+) -> Result<T> {
+    if Arc::ptr_eq(first, second) {
+        let editor = lock_editor(first)?;
+        return f(&editor, &editor);
+    }
+    if Arc::as_ptr(first) < Arc::as_ptr(second) {
+        let a = lock_editor(first)?;
+        let b = lock_editor(second)?;
+        f(&a, &b)
+    } else {
+        let b = lock_editor(second)?;
+        let a = lock_editor(first)?;
+        f(&a, &b)
+    }
+}
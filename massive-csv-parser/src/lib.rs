@@ -0,0 +1,878 @@
+//! Dialect-aware CSV line parsing and serialization for massive-csv.
+//!
+//! Delimiter and dialect detection, field parsing, quoting and header
+//! handling, working on byte slices and strings only. Nothing here opens
+//! files or maps memory, so the crate can be reused wherever lines come from
+//! (massive-csv-core feeds it rows from a memory map).
+
+use std::collections::HashSet;
+
+use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("CSV parse error: {0}")]
+    Csv(#[from] csv::Error),
+
+    #[error("No header line")]
+    Empty,
+
+    #[error("Invalid UTF-8 at byte position {0}")]
+    InvalidUtf8(usize),
+
+    #[error("Value for column {column} is wider than {width} characters")]
+    FieldTooWide { column: usize, width: usize },
+}
+
+/// Supported CSV delimiters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delimiter {
+    Comma,
+    Tab,
+    Semicolon,
+    Pipe,
+}
+
+impl Delimiter {
+    pub fn as_byte(self) -> u8 {
+        match self {
+            Delimiter::Comma => b',',
+            Delimiter::Tab => b'\t',
+            Delimiter::Semicolon => b';',
+            Delimiter::Pipe => b'|',
+        }
+    }
+
+    fn all() -> &'static [Delimiter] {
+        &[
+            Delimiter::Comma,
+            Delimiter::Tab,
+            Delimiter::Semicolon,
+            Delimiter::Pipe,
+        ]
+    }
+}
+
+/// Line terminator style of a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+}
+
+/// When fields are wrapped in quotes on output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QuoteStyle {
+    /// Only fields containing the delimiter, a quote or a line break (RFC 4180).
+    #[default]
+    Necessary,
+    /// Every field.
+    Always,
+    /// Every field that does not parse as a number.
+    NonNumeric,
+    /// Never; fields needing quotes are written as-is.
+    Never,
+}
+
+impl QuoteStyle {
+    fn to_csv(self) -> csv::QuoteStyle {
+        match self {
+            QuoteStyle::Necessary => csv::QuoteStyle::Necessary,
+            QuoteStyle::Always => csv::QuoteStyle::Always,
+            QuoteStyle::NonNumeric => csv::QuoteStyle::NonNumeric,
+            QuoteStyle::Never => csv::QuoteStyle::Never,
+        }
+    }
+}
+
+/// Bytes sampled from the start of the file by [`detect_line_ending`].
+const LINE_ENDING_SAMPLE: usize = 64 * 1024;
+
+/// Detect the dominant line ending from the start of the file.
+///
+/// Ties (and files without any newline) resolve to `\n`.
+pub fn detect_line_ending(data: &[u8]) -> LineEnding {
+    let sample = &data[..data.len().min(LINE_ENDING_SAMPLE)];
+    let mut lf = 0usize;
+    let mut crlf = 0usize;
+
+    for (i, &b) in sample.iter().enumerate() {
+        if b == b'\n' {
+            if i > 0 && sample[i - 1] == b'\r' {
+                crlf += 1;
+            } else {
+                lf += 1;
+            }
+        }
+    }
+
+    if crlf > lf {
+        LineEnding::CrLf
+    } else {
+        LineEnding::Lf
+    }
+}
+
+/// Column layout of a fixed-width file: each field occupies a set number of
+/// characters, padded with spaces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FixedWidth {
+    pub widths: Vec<usize>,
+}
+
+impl FixedWidth {
+    pub fn new(widths: Vec<usize>) -> Self {
+        Self { widths }
+    }
+
+    /// Character ranges of each field.
+    fn spans(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.widths.iter().scan(0, |start, &width| {
+            let span = (*start, *start + width);
+            *start += width;
+            Some(span)
+        })
+    }
+
+    /// The raw text of field `index`, padding included. Short lines give
+    /// empty fields; text past the last column is ignored.
+    fn raw_field<'a>(&self, line: &'a str, index: usize) -> Option<&'a str> {
+        let (start, end) = self.spans().nth(index)?;
+        let byte_at = |chars: usize| {
+            line.char_indices()
+                .nth(chars)
+                .map(|(i, _)| i)
+                .unwrap_or(line.len())
+        };
+        Some(&line[byte_at(start)..byte_at(end)])
+    }
+
+    /// Field `index` of a line, with its padding trimmed.
+    pub fn field<'a>(&self, line: &'a str, index: usize) -> Option<&'a str> {
+        self.raw_field(line, index).map(|f| f.trim_matches(' '))
+    }
+
+    /// Split a line into its fields, with padding trimmed.
+    pub fn parse(&self, line: &str) -> Vec<String> {
+        (0..self.widths.len())
+            .map(|i| self.field(line, i).unwrap_or_default().to_string())
+            .collect()
+    }
+
+    /// Lay fields out in their columns, left-aligned and space-padded.
+    pub fn serialize(&self, fields: &[String]) -> Result<String> {
+        self.serialize_preserving(fields, "")
+    }
+
+    /// Like [`serialize`](Self::serialize), but keeps the original text
+    /// (alignment and padding) of every field whose value is unchanged.
+    pub fn serialize_preserving(&self, fields: &[String], original: &str) -> Result<String> {
+        let last = self.widths.len().saturating_sub(1);
+        let mut out = String::new();
+        for (column, &width) in self.widths.iter().enumerate() {
+            let value = fields.get(column).map(String::as_str).unwrap_or("");
+            let (text, unchanged) = match self.raw_field(original, column) {
+                Some(raw) if raw.trim_matches(' ') == value => (raw, true),
+                _ if value.chars().count() > width => {
+                    return Err(Error::FieldTooWide { column, width })
+                }
+                _ => (value, false),
+            };
+            out.push_str(text);
+            // A line may end early in its last column; keep it that way
+            if !(unchanged && column == last) {
+                out.extend(std::iter::repeat_n(' ', width - text.chars().count()));
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// Settings for [`detect_delimiter_with`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DetectOptions {
+    /// Lines read at each sample point.
+    pub sample_lines: usize,
+    /// Number of places sampled, spread evenly from the start of the data to
+    /// its end. With 1, only the first lines are read.
+    pub sample_points: usize,
+    /// Delimiters to choose between, in order of preference on ties.
+    pub candidates: Vec<u8>,
+}
+
+impl Default for DetectOptions {
+    fn default() -> Self {
+        Self {
+            sample_lines: 20,
+            sample_points: 4,
+            candidates: Delimiter::all().iter().map(|d| d.as_byte()).collect(),
+        }
+    }
+}
+
+/// Result of [`detect_delimiter_with`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Detection {
+    pub delimiter: u8,
+    /// From 0 to 1: the share of sampled lines with the header's field count,
+    /// discounted when another candidate fits nearly as well. 0 when no
+    /// candidate appears in the header.
+    pub confidence: f64,
+}
+
+/// Detect the delimiter by sampling lines from the start of the file and from
+/// a few later offsets, using the default [`DetectOptions`].
+pub fn detect_delimiter(data: &[u8]) -> Delimiter {
+    let delimiter = detect_delimiter_with(data, &DetectOptions::default()).delimiter;
+    Delimiter::all()
+        .iter()
+        .copied()
+        .find(|d| d.as_byte() == delimiter)
+        .unwrap_or(Delimiter::Comma)
+}
+
+/// Detect the delimiter and how sure the guess is.
+///
+/// Strategy: for each candidate delimiter, count how many fields the header and
+/// each sampled line produce. The best delimiter is the one where most lines
+/// match the header's (>1) field count, preferring more fields when tied.
+pub fn detect_delimiter_with(data: &[u8], options: &DetectOptions) -> Detection {
+    let fallback = Detection {
+        delimiter: options.candidates.first().copied().unwrap_or(b','),
+        confidence: 0.0,
+    };
+    let Some(header) = first_line(data) else {
+        return fallback;
+    };
+    let sample = sample_lines(data, options.sample_lines, options.sample_points);
+
+    // (delimiter, score, share of lines consistent with the header)
+    let mut fits: Vec<(u8, usize, f64)> = Vec::new();
+    for &delim in &options.candidates {
+        let expected = count_fields(header, delim);
+        // Skip if the header only has 1 field (delimiter not present)
+        if expected <= 1 {
+            continue;
+        }
+        let consistent = sample
+            .iter()
+            .filter(|line| count_fields(line, delim) == expected)
+            .count();
+        let share = consistent as f64 / sample.len() as f64;
+        fits.push((delim, consistent * expected, share));
+    }
+    // Stable sort keeps candidate order on ties
+    fits.sort_by_key(|&(_, score, _)| std::cmp::Reverse(score));
+
+    match fits.as_slice() {
+        [] => fallback,
+        [(delimiter, _, share)] => Detection {
+            delimiter: *delimiter,
+            confidence: *share,
+        },
+        [(delimiter, _, share), (_, _, runner_up), ..] => Detection {
+            delimiter: *delimiter,
+            confidence: share * (1.0 - runner_up / 2.0),
+        },
+    }
+}
+
+/// How a quote character inside a quoted field is escaped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EscapeStyle {
+    /// The quote is written twice (`""`), as in RFC 4180.
+    #[default]
+    Doubled,
+    /// The quote is preceded by a backslash (`\"`).
+    Backslash,
+}
+
+/// The flavour of CSV a file is written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Dialect {
+    pub delimiter: u8,
+    pub quote: u8,
+    pub escape: EscapeStyle,
+}
+
+impl Default for Dialect {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            quote: b'"',
+            escape: EscapeStyle::Doubled,
+        }
+    }
+}
+
+/// Standard quoting and escaping with the given delimiter.
+impl From<u8> for Dialect {
+    fn from(delimiter: u8) -> Self {
+        Self {
+            delimiter,
+            ..Self::default()
+        }
+    }
+}
+
+impl Dialect {
+    fn reader_builder(&self) -> csv::ReaderBuilder {
+        let mut builder = csv::ReaderBuilder::new();
+        builder
+            .has_headers(false)
+            .delimiter(self.delimiter)
+            .quote(self.quote);
+        if self.escape == EscapeStyle::Backslash {
+            builder.double_quote(false).escape(Some(b'\\'));
+        }
+        builder
+    }
+
+    fn writer_builder(&self, quote_style: QuoteStyle) -> csv::WriterBuilder {
+        let mut builder = csv::WriterBuilder::new();
+        builder
+            .has_headers(false)
+            .delimiter(self.delimiter)
+            .quote(self.quote)
+            .quote_style(quote_style.to_csv());
+        if self.escape == EscapeStyle::Backslash {
+            builder.double_quote(false).escape(b'\\');
+        }
+        builder
+    }
+}
+
+/// Detect the quote character and escape style from the first lines of the file.
+pub fn detect_dialect(data: &[u8], delimiter: u8) -> Dialect {
+    let sample = first_n_lines(data, 0, 20);
+
+    // Count candidate quote characters sitting at field boundaries
+    let at_boundaries = |q: u8| {
+        sample
+            .iter()
+            .map(|line| {
+                let n = line.len();
+                (0..n)
+                    .filter(|&i| line[i] == q)
+                    .filter(|&i| {
+                        let opens = i == 0 || line[i - 1] == delimiter;
+                        let closes = i + 1 == n || line[i + 1] == delimiter;
+                        opens || closes
+                    })
+                    .count()
+            })
+            .sum::<usize>()
+    };
+    let quote = if at_boundaries(b'\'') > at_boundaries(b'"') {
+        b'\''
+    } else {
+        b'"'
+    };
+
+    let count = |pattern: [u8; 2]| {
+        sample
+            .iter()
+            .map(|line| line.windows(2).filter(|w| *w == pattern).count())
+            .sum::<usize>()
+    };
+    let backslashed = count([b'\\', quote]);
+    let escape = if backslashed > 0 && backslashed >= count([quote, quote]) {
+        EscapeStyle::Backslash
+    } else {
+        EscapeStyle::Doubled
+    };
+
+    Dialect {
+        delimiter,
+        quote,
+        escape,
+    }
+}
+
+/// Parse a raw line into fields using the csv crate (handles quoting properly).
+///
+/// `dialect` may be a full [`Dialect`] or just a delimiter byte.
+pub fn parse_row(line: &str, dialect: impl Into<Dialect>) -> Result<Vec<String>> {
+    let mut reader = dialect.into().reader_builder().from_reader(line.as_bytes());
+
+    let mut record = csv::StringRecord::new();
+    if reader.read_record(&mut record).map_err(Error::Csv)? {
+        Ok(record.iter().map(|f| f.to_string()).collect())
+    } else {
+        Ok(vec![])
+    }
+}
+
+/// Parse the first line of data as headers.
+pub fn parse_headers(data: &[u8], dialect: impl Into<Dialect>) -> Result<Vec<String>> {
+    let first_line = first_line(data).ok_or(Error::Empty)?;
+    let line_str = std::str::from_utf8(first_line).map_err(|_| Error::InvalidUtf8(0))?;
+    parse_row(line_str, dialect)
+}
+
+/// Unique, non-empty names for `headers`.
+///
+/// Empty cells become `column_<n>` (1-based position) and repeats of a name
+/// get a numeric suffix, so `amount,amount,` becomes `amount,amount_2,column_3`.
+/// Suffixes skip names already present in the file.
+pub fn canonical_headers(headers: &[String]) -> Vec<String> {
+    let mut used: HashSet<String> = headers.iter().filter(|h| !h.is_empty()).cloned().collect();
+    let mut seen: HashSet<&str> = HashSet::new();
+
+    headers
+        .iter()
+        .enumerate()
+        .map(|(i, header)| {
+            if !header.is_empty() && seen.insert(header) {
+                return header.clone();
+            }
+            let base = if header.is_empty() {
+                format!("column_{}", i + 1)
+            } else {
+                header.clone()
+            };
+            let name = if header.is_empty() && !used.contains(&base) {
+                base
+            } else {
+                (2..)
+                    .map(|n| format!("{base}_{n}"))
+                    .find(|name| !used.contains(name))
+                    .expect("suffixes are unbounded")
+            };
+            used.insert(name.clone());
+            name
+        })
+        .collect()
+}
+
+/// Position of the column called `name`, by canonical name first and then by
+/// raw header text.
+pub fn column_position(headers: &[String], name: &str) -> Option<usize> {
+    canonical_headers(headers)
+        .iter()
+        .position(|h| h == name)
+        .or_else(|| headers.iter().position(|h| h == name))
+}
+
+/// Serialize fields back into a CSV line (with proper quoting).
+pub fn serialize_row(fields: &[String], dialect: impl Into<Dialect>) -> String {
+    serialize_row_with(fields, dialect, QuoteStyle::Necessary)
+}
+
+/// Serialize fields into a CSV line using the given quoting style.
+pub fn serialize_row_with(
+    fields: &[String],
+    dialect: impl Into<Dialect>,
+    quote_style: QuoteStyle,
+) -> String {
+    let mut writer = dialect
+        .into()
+        .writer_builder(quote_style)
+        .from_writer(Vec::new());
+
+    writer
+        .write_record(fields)
+        .expect("write to Vec cannot fail");
+    writer.flush().expect("flush to Vec cannot fail");
+
+    let mut output = String::from_utf8(writer.into_inner().expect("flush already called"))
+        .expect("csv crate produces valid utf-8");
+
+    // Remove trailing newline that the csv writer adds
+    if output.ends_with('\n') {
+        output.pop();
+        if output.ends_with('\r') {
+            output.pop();
+        }
+    }
+
+    output
+}
+
+/// Serialize fields, reusing the raw text of `original` for every field whose
+/// value is unchanged so untouched cells keep their exact quoting.
+///
+/// Only fields that differ from the original line are re-quoted.
+pub fn serialize_row_preserving(
+    fields: &[String],
+    original: &str,
+    dialect: impl Into<Dialect>,
+) -> String {
+    let dialect = dialect.into();
+    let raw = split_raw_fields(original, &dialect);
+    if fields.len() == 1 && raw.len() <= 1 && fields[0].is_empty() {
+        // A lone empty field must stay quoted to remain a record
+        return serialize_row(fields, dialect);
+    }
+
+    let mut line = String::with_capacity(original.len() + 16);
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            line.push(dialect.delimiter as char);
+        }
+        match raw.get(i) {
+            Some(r) if unquote_raw(r, &dialect) == *field => line.push_str(r),
+            _ => quote_field(field, &dialect, &mut line),
+        }
+    }
+    line
+}
+
+/// Split a raw line into the exact source text of each field, quotes included.
+fn split_raw_fields<'a>(line: &'a str, dialect: &Dialect) -> Vec<&'a str> {
+    let bytes = line.as_bytes();
+    let mut fields = Vec::new();
+    let mut start = 0;
+    let mut in_quotes = false;
+    let mut escaped = false;
+
+    for (i, &b) in bytes.iter().enumerate() {
+        if escaped {
+            escaped = false;
+        } else if in_quotes && b == b'\\' && dialect.escape == EscapeStyle::Backslash {
+            escaped = true;
+        } else if b == dialect.quote {
+            in_quotes = !in_quotes;
+        } else if b == dialect.delimiter && !in_quotes {
+            fields.push(&line[start..i]);
+            start = i + 1;
+        }
+    }
+    fields.push(&line[start..]);
+    fields
+}
+
+/// Number of fields in a line, and whether its quotes are balanced.
+pub fn field_count(line: &str, dialect: &Dialect) -> (usize, bool) {
+    let mut count = 1;
+    let mut in_quotes = false;
+    let mut escaped = false;
+
+    for &b in line.as_bytes() {
+        if escaped {
+            escaped = false;
+        } else if in_quotes && b == b'\\' && dialect.escape == EscapeStyle::Backslash {
+            escaped = true;
+        } else if b == dialect.quote {
+            in_quotes = !in_quotes;
+        } else if b == dialect.delimiter && !in_quotes {
+            count += 1;
+        }
+    }
+    (count, !in_quotes)
+}
+
+/// The value of field `index` of a line, without parsing the other fields.
+pub fn field_at<'a>(
+    line: &'a str,
+    index: usize,
+    dialect: &Dialect,
+) -> Option<std::borrow::Cow<'a, str>> {
+    let raw = split_raw_fields(line, dialect).into_iter().nth(index)?;
+    Some(unquote_raw(raw, dialect))
+}
+
+/// The value of a raw field, as the csv crate would parse it.
+fn unquote_raw<'a>(raw: &'a str, dialect: &Dialect) -> std::borrow::Cow<'a, str> {
+    let quote = dialect.quote as char;
+    match raw.strip_prefix(quote).and_then(|r| r.strip_suffix(quote)) {
+        Some(inner) => {
+            std::borrow::Cow::Owned(inner.replace(&escaped_quote(dialect), &quote.to_string()))
+        }
+        None => std::borrow::Cow::Borrowed(raw),
+    }
+}
+
+/// Append `field` to `out`, quoting it only if needed.
+fn quote_field(field: &str, dialect: &Dialect, out: &mut String) {
+    let needs_quotes = field
+        .bytes()
+        .any(|b| b == dialect.delimiter || b == dialect.quote || b == b'\n' || b == b'\r');
+    if needs_quotes {
+        let quote = dialect.quote as char;
+        out.push(quote);
+        out.push_str(&field.replace(quote, &escaped_quote(dialect)));
+        out.push(quote);
+    } else {
+        out.push_str(field);
+    }
+}
+
+/// How a quote character is written inside a quoted field.
+fn escaped_quote(dialect: &Dialect) -> String {
+    let quote = dialect.quote as char;
+    match dialect.escape {
+        EscapeStyle::Doubled => format!("{quote}{quote}"),
+        EscapeStyle::Backslash => format!("\\{quote}"),
+    }
+}
+
+fn first_line(data: &[u8]) -> Option<&[u8]> {
+    if data.is_empty() {
+        return None;
+    }
+    let end = data.iter().position(|&b| b == b'\n').unwrap_or(data.len());
+    let line = &data[..end];
+    // Strip trailing \r
+    if line.last() == Some(&b'\r') {
+        Some(&line[..line.len() - 1])
+    } else {
+        Some(line)
+    }
+}
+
+/// Up to `n` lines from the start of the data, then up to `n` complete lines
+/// after each of `points - 1` evenly spaced offsets.
+fn sample_lines(data: &[u8], n: usize, points: usize) -> Vec<&[u8]> {
+    let mut lines = first_n_lines(data, 0, n);
+    for k in 1..points {
+        let offset = (data.len() as u128 * k as u128 / points as u128) as usize;
+        // Skip the partial line the offset lands in
+        if let Some(pos) = data[offset..].iter().position(|&b| b == b'\n') {
+            lines.extend(first_n_lines(data, offset + pos + 1, n));
+        }
+    }
+    lines
+}
+
+fn first_n_lines(data: &[u8], mut start: usize, n: usize) -> Vec<&[u8]> {
+    let mut lines = Vec::with_capacity(n);
+
+    for _ in 0..n {
+        if start >= data.len() {
+            break;
+        }
+        let remaining = &data[start..];
+        let end = remaining
+            .iter()
+            .position(|&b| b == b'\n')
+            .unwrap_or(remaining.len());
+        let line = &remaining[..end];
+        // Strip trailing \r
+        let line = if line.last() == Some(&b'\r') {
+            &line[..line.len() - 1]
+        } else {
+            line
+        };
+        lines.push(line);
+        start += end + 1;
+    }
+
+    lines
+}
+
+/// Count fields by counting unquoted delimiters + 1.
+fn count_fields(line: &[u8], delimiter: u8) -> usize {
+    let mut count = 1usize;
+    let mut in_quotes = false;
+
+    for &b in line {
+        if b == b'"' {
+            in_quotes = !in_quotes;
+        } else if b == delimiter && !in_quotes {
+            count += 1;
+        }
+    }
+
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_comma() {
+        let data = b"a,b,c\n1,2,3\n4,5,6\n";
+        assert_eq!(detect_delimiter(data), Delimiter::Comma);
+    }
+
+    #[test]
+    fn detect_tab() {
+        let data = b"a\tb\tc\n1\t2\t3\n4\t5\t6\n";
+        assert_eq!(detect_delimiter(data), Delimiter::Tab);
+    }
+
+    #[test]
+    fn detect_semicolon() {
+        let data = b"a;b;c\n1;2;3\n4;5;6\n";
+        assert_eq!(detect_delimiter(data), Delimiter::Semicolon);
+    }
+
+    #[test]
+    fn detect_pipe() {
+        let data = b"a|b|c\n1|2|3\n4|5|6\n";
+        assert_eq!(detect_delimiter(data), Delimiter::Pipe);
+    }
+
+    #[test]
+    fn parse_and_serialize_round_trip() {
+        let line = r#"hello,"world, ok",test"#;
+        let fields = parse_row(line, b',').unwrap();
+        assert_eq!(fields, vec!["hello", "world, ok", "test"]);
+
+        let serialized = serialize_row(&fields, b',');
+        assert_eq!(serialized, r#"hello,"world, ok",test"#);
+    }
+
+    #[test]
+    fn serialize_preserving_keeps_untouched_quoting() {
+        let original = r#""007","a, b",x,"plain""#;
+        let fields: Vec<String> = vec!["007".into(), "a, b".into(), "y".into(), "plain".into()];
+        assert_eq!(
+            serialize_row_preserving(&fields, original, b','),
+            r#""007","a, b",y,"plain""#
+        );
+
+        let fields: Vec<String> = vec!["007".into(), "say \"hi\"".into(), "x".into()];
+        assert_eq!(
+            serialize_row_preserving(&fields, original, b','),
+            r#""007","say ""hi""",x"#
+        );
+    }
+
+    #[test]
+    fn serialize_with_quote_styles() {
+        let fields: Vec<String> = vec!["a".into(), "1.5".into(), "x;y".into()];
+        assert_eq!(
+            serialize_row_with(&fields, b';', QuoteStyle::Necessary),
+            r#"a;1.5;"x;y""#
+        );
+        assert_eq!(
+            serialize_row_with(&fields, b',', QuoteStyle::NonNumeric),
+            r#""a",1.5,"x;y""#
+        );
+    }
+
+    #[test]
+    fn single_quote_backslash_dialect() {
+        let data = b"id,name\n1,'O\\'Brien, Pat'\n2,'x'\n";
+        let dialect = detect_dialect(data, b',');
+        assert_eq!(dialect.quote, b'\'');
+        assert_eq!(dialect.escape, EscapeStyle::Backslash);
+
+        let line = r"1,'O\'Brien, Pat'";
+        let fields = parse_row(line, dialect).unwrap();
+        assert_eq!(fields, vec!["1", "O'Brien, Pat"]);
+        assert_eq!(serialize_row(&fields, dialect), line);
+
+        let mut edited = fields.clone();
+        edited[0] = "2".to_string();
+        assert_eq!(
+            serialize_row_preserving(&edited, line, dialect),
+            r"2,'O\'Brien, Pat'"
+        );
+    }
+
+    #[test]
+    fn canonical_header_names() {
+        let headers = |names: &[&str]| names.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            canonical_headers(&headers(&["amount", "amount", "", "amount_2", "amount"])),
+            headers(&["amount", "amount_3", "column_3", "amount_2", "amount_4"])
+        );
+        assert_eq!(
+            canonical_headers(&headers(&["", "column_1"])),
+            headers(&["column_1_2", "column_1"])
+        );
+        let regular = headers(&["a", "b"]);
+        assert_eq!(canonical_headers(&regular), regular);
+    }
+
+    #[test]
+    fn parse_headers_works() {
+        let data = b"name,age,city\nAlice,30,NYC\n";
+        let headers = parse_headers(data, b',').unwrap();
+        assert_eq!(headers, vec!["name", "age", "city"]);
+    }
+
+    #[test]
+    fn detect_line_endings() {
+        assert_eq!(detect_line_ending(b"a\r\n1\r\n2\n"), LineEnding::CrLf);
+        assert_eq!(detect_line_ending(b"a\n1\r\n2\n"), LineEnding::Lf);
+        assert_eq!(detect_line_ending(b"a"), LineEnding::Lf);
+    }
+
+    #[test]
+    fn samples_beyond_the_first_lines() {
+        // The first rows fit both ',' and ';'; only later rows tell them apart
+        let mut data = String::from("a,b;c\n");
+        data.push_str(&"1,2;3\n".repeat(20));
+        data.push_str(&"x;y\n".repeat(200));
+
+        let head_only = DetectOptions {
+            sample_points: 1,
+            ..Default::default()
+        };
+        let detection = detect_delimiter_with(data.as_bytes(), &head_only);
+        assert_eq!(detection.delimiter, b',');
+        assert_eq!(detection.confidence, 0.5);
+
+        let detection = detect_delimiter_with(data.as_bytes(), &DetectOptions::default());
+        assert_eq!(detection.delimiter, b';');
+        assert!(detection.confidence > 0.6, "{detection:?}");
+    }
+
+    #[test]
+    fn detection_confidence() {
+        let detection = detect_delimiter_with(b"a,b\n1,2\n3,4\n", &DetectOptions::default());
+        assert_eq!(detection.delimiter, b',');
+        assert_eq!(detection.confidence, 1.0);
+
+        let only_colon = DetectOptions {
+            candidates: vec![b':'],
+            ..Default::default()
+        };
+        let detection = detect_delimiter_with(b"a:b\n1:2\n", &only_colon);
+        assert_eq!((detection.delimiter, detection.confidence), (b':', 1.0));
+
+        let single_column = detect_delimiter_with(b"name\nx\n", &DetectOptions::default());
+        assert_eq!(single_column.confidence, 0.0);
+    }
+
+    #[test]
+    fn fixed_width_fields() {
+        let layout = FixedWidth::new(vec![4, 6, 3]);
+        assert_eq!(layout.parse("id  name  qty"), vec!["id", "name", "qty"]);
+        assert_eq!(layout.parse("7   Zoë     3"), vec!["7", "Zoë", "3"]);
+        assert_eq!(layout.parse("12  ab"), vec!["12", "ab", ""]);
+        assert_eq!(layout.field("7   Zoë     3", 2), Some("3"));
+        assert_eq!(layout.field("7", 3), None);
+
+        let fields = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            layout.serialize(&fields(&["1", "ab", "2"])).unwrap(),
+            "1   ab    2  "
+        );
+        assert_eq!(
+            layout
+                .serialize_preserving(&fields(&["7", "Zoe", "3"]), "7   Zoë     3")
+                .unwrap(),
+            "7   Zoe     3"
+        );
+        assert!(matches!(
+            layout.serialize(&fields(&["12345", "", ""])),
+            Err(Error::FieldTooWide {
+                column: 0,
+                width: 4
+            })
+        ));
+    }
+
+    #[test]
+    fn empty_data_returns_comma() {
+        assert_eq!(detect_delimiter(b""), Delimiter::Comma);
+    }
+}
@@ -12,33 +12,233 @@ export interface CsvInfo {
   headers: string[];
   delimiter: string;
   filePath: string;
+  computedColumns: string[];
+}
+
+export interface JsFieldMatch {
+  column: number;
+  start: number;
+  end: number;
 }
 
 export interface JsSearchResult {
   rowNum: number;
   fields: string[];
+  matches: JsFieldMatch[];
+}
+
+export interface JsSearchResults {
+  results: JsSearchResult[];
+  totalMatches: number;
+}
+
+export interface JsRowDiff {
+  original: string[] | null;
+  current: string[];
+  changedColumns: number[];
+}
+
+export interface JsNumericStats {
+  min: number;
+  max: number;
+  mean: number;
+  stddev: number;
+  p50: number;
+  p95: number;
+  p99: number;
+}
+
+export interface JsColumnStats {
+  name: string;
+  count: number;
+  empty: number;
+  numeric?: JsNumericStats;
+  minLength: number;
+  maxLength: number;
+  distinct: number;
+  nearUnique: boolean;
+  lowCardinality: boolean;
+}
+
+export interface JsInferredColumn {
+  name: string;
+  columnType: string;
+  nullable: boolean;
+}
+
+export interface JsValueCounts {
+  values: { value: string; count: number }[];
+  distinct: number;
+  rows: number;
+}
+
+export interface JsPage {
+  offset: number;
+  rows: string[][];
+  rowCount: number;
+}
+
+export interface JsStructureChange {
+  rowCount: number;
+  headers: string[];
+  firstShiftedRow: number;
+  rowShift: number;
+  firstShiftedColumn: number;
+  columnShift: number;
 }
 
+export interface JsFileChange {
+  path: string;
+  kind: "modified" | "removed";
+}
+
+export interface JsProgress {
+  done: number;
+  total: number;
+}
+
+export type ProgressEvent = "saveProgress" | "searchProgress";
+
 export interface JsSearchOptions {
   column?: string;
   caseSensitive?: boolean;
   maxResults?: number;
+  offset?: number;
+  filter?: string;
+}
+
+export interface JsExportOptions {
+  format?: string;
+  filter?: string;
+  columns?: string[];
+  start?: number;
+  end?: number;
+  table?: string;
+}
+
+export interface JsExportSummary {
+  rowsWritten: number;
+  rowsFiltered: number;
+}
+
+export interface JsSchemaViolation {
+  row: number;
+  column: string;
+  value: string;
+  message: string;
+}
+
+export interface JsSavedView {
+  name: string;
+  filter?: string;
+  sort?: string[];
+  columns?: string[];
+}
+
+export interface JsAppliedView {
+  rows: number[];
+  columns: number[];
 }
 
 export interface CsvDocument {
   getInfo(): CsvInfo;
   getRow(row: number): string[];
-  getRows(start: number, end: number): string[][];
-  search(query: string, options?: JsSearchOptions): JsSearchResult[];
+  getRows(start: number, end: number): Promise<string[][]>;
+  getPage(offset: number, limit: number, columns?: number[]): Promise<JsPage>;
+  getRowsBuffer(start: number, end: number): Promise<Buffer>;
+  getRowsArrow(start: number, end: number): Buffer;
+  search(query: string, options?: JsSearchOptions, signal?: AbortSignal): Promise<JsSearchResults>;
+  searchStream(
+    query: string,
+    options: JsSearchOptions | undefined,
+    onBatch: (results: JsSearchResult[]) => void,
+    signal?: AbortSignal
+  ): Promise<number>;
+  filterRows(expr: string, maxResults?: number): number[];
+  getColumnStats(column: string): Promise<JsColumnStats>;
+  inferSchema(sampleRows?: number): Promise<JsInferredColumn[]>;
+  getValueCounts(column: string, topN?: number): Promise<JsValueCounts>;
+  addComputedColumn(definition: string): void;
+  removeComputedColumn(name: string): boolean;
+  materializeComputedColumns(output?: string): number;
+  export(output: string, options?: JsExportOptions): JsExportSummary;
   setCell(row: number, col: number, value: string): void;
   setRow(row: number, fields: string[]): void;
+  moveRows(start: number, end: number, dest: number): void;
+  insertRow(row: number, fields?: string[]): JsStructureChange;
+  deleteRows(start: number, end: number): JsStructureChange;
+  addColumn(name: string, defaultValue?: string): JsStructureChange;
+  renameColumn(from: string, to: string): JsStructureChange;
+  dropColumn(column: string): JsStructureChange;
   revertRow(row: number): void;
   revertAll(): void;
-  save(): void;
+  undo(): boolean;
+  redo(): boolean;
+  getEditedRows(): number[];
+  getRowDiff(row: number): JsRowDiff;
+  isCellEdited(row: number, col: number): boolean;
+  save(signal?: AbortSignal): Promise<void>;
+  on(event: ProgressEvent, callback: (progress: JsProgress) => void): void;
+  off(event: ProgressEvent): void;
+  setSchema(schema: string | null): void;
+  validateAgainstSchema(maxIssues?: number): JsSchemaViolation[];
+  listViews(): JsSavedView[];
+  saveView(view: JsSavedView): void;
+  applyView(name: string): JsAppliedView;
+  watch(onChanged: (change: JsFileChange) => void): void;
+  unwatch(): void;
+  reload(): Promise<number>;
+  close(): void;
+  dispose(): void;
   readonly editCount: number;
   readonly hasChanges: boolean;
+  readonly canUndo: boolean;
+  readonly canRedo: boolean;
 }
 
-export function openCsvDocument(filePath: string): CsvDocument {
-  return native.CsvDocument.open(filePath) as CsvDocument;
+export function openCsvDocument(
+  filePath: string,
+  onProgress?: (progress: JsProgress) => void,
+  signal?: AbortSignal
+): Promise<CsvDocument> {
+  return native.CsvDocument.open(filePath, null, onProgress, signal) as Promise<CsvDocument>;
+}
+
+/**
+ * Rows from `getRowsBuffer`, decoded one row at a time as they are read.
+ * Construction only walks the length prefixes to find where rows start.
+ */
+export class RowsBuffer {
+  private readonly starts: number[] = [];
+
+  constructor(private readonly buffer: Buffer) {
+    const rows = buffer.readUInt32LE(0);
+    let pos = 4;
+    for (let i = 0; i < rows; i++) {
+      this.starts.push(pos);
+      const fields = buffer.readUInt32LE(pos);
+      pos += 4;
+      for (let f = 0; f < fields; f++) {
+        pos += 4 + buffer.readUInt32LE(pos);
+      }
+    }
+  }
+
+  get length(): number {
+    return this.starts.length;
+  }
+
+  row(i: number): string[] {
+    let pos = this.starts[i];
+    const fields = this.buffer.readUInt32LE(pos);
+    pos += 4;
+    const values: string[] = [];
+    for (let f = 0; f < fields; f++) {
+      const len = this.buffer.readUInt32LE(pos);
+      pos += 4;
+      values.push(this.buffer.toString("utf8", pos, pos + len));
+      pos += len;
+    }
+    return values;
+  }
 }
@@ -16,23 +16,24 @@ export class CsvEditorProvider implements vscode.CustomReadonlyEditorProvider {
     return { uri, dispose: () => {} };
   }
 
-  public resolveCustomEditor(
+  public async resolveCustomEditor(
     document: vscode.CustomDocument,
     webviewPanel: vscode.WebviewPanel,
     _token: vscode.CancellationToken
-  ): void {
+  ): Promise<void> {
     const filePath = document.uri.fsPath;
 
     let doc: CsvDocument;
     try {
-      doc = openCsvDocument(filePath);
+      doc = await openCsvDocument(filePath);
     } catch (err: unknown) {
       const msg = err instanceof Error ? err.message : String(err);
       vscode.window.showErrorMessage(`Failed to open CSV: ${msg}`);
       return;
     }
 
-    const info = doc.getInfo();
+    // Release the file as soon as the editor closes, rather than at GC
+    webviewPanel.onDidDispose(() => doc.close());
 
     // Configure webview
     webviewPanel.webview.options = {
@@ -65,6 +66,7 @@ export class CsvEditorProvider implements vscode.CustomReadonlyEditorProvider {
 
     // Send initial data once webview is ready
     const sendInit = () => {
+      const info = doc.getInfo();
       webviewPanel.webview.postMessage({
         type: "init",
         headers: info.headers,
@@ -77,7 +79,7 @@ export class CsvEditorProvider implements vscode.CustomReadonlyEditorProvider {
 
     // Handle messages from webview
     webviewPanel.webview.onDidReceiveMessage(
-      (message) => {
+      async (message) => {
         switch (message.type) {
           case "ready":
             sendInit();
@@ -85,12 +87,13 @@ export class CsvEditorProvider implements vscode.CustomReadonlyEditorProvider {
 
           case "getRows": {
             try {
-              const rows = doc.getRows(message.start, message.end);
+              const page = await doc.getPage(message.start, message.end - message.start);
               webviewPanel.webview.postMessage({
                 type: "rowData",
                 requestId: message.requestId,
                 start: message.start,
-                rows,
+                rows: page.rows,
+                rowCount: page.rowCount,
               });
             } catch (err: unknown) {
               const msg = err instanceof Error ? err.message : String(err);
@@ -101,7 +104,7 @@ export class CsvEditorProvider implements vscode.CustomReadonlyEditorProvider {
 
           case "search": {
             try {
-              const results = doc.search(message.query, {
+              const { results, totalMatches } = await doc.search(message.query, {
                 column: message.column || undefined,
                 caseSensitive: message.caseSensitive,
                 maxResults: message.maxResults || 1000,
@@ -109,6 +112,7 @@ export class CsvEditorProvider implements vscode.CustomReadonlyEditorProvider {
               webviewPanel.webview.postMessage({
                 type: "searchResults",
                 results,
+                totalMatches,
                 query: message.query,
               });
             } catch (err: unknown) {
@@ -136,7 +140,7 @@ export class CsvEditorProvider implements vscode.CustomReadonlyEditorProvider {
 
           case "save": {
             try {
-              doc.save();
+              await doc.save();
               const newInfo = doc.getInfo();
               webviewPanel.webview.postMessage({
                 type: "saveComplete",
@@ -158,11 +162,56 @@ export class CsvEditorProvider implements vscode.CustomReadonlyEditorProvider {
             });
             break;
           }
+
+          case "undo":
+          case "redo": {
+            try {
+              const changed = message.type === "undo" ? doc.undo() : doc.redo();
+              if (!changed) {
+                break;
+              }
+              const info = doc.getInfo();
+              const editedCells = doc
+                .getEditedRows()
+                .flatMap((row) => doc.getRowDiff(row).changedColumns.map((col) => [row, col]));
+              webviewPanel.webview.postMessage({
+                type: "historyChanged",
+                rowCount: info.rowCount,
+                editCount: doc.editCount,
+                editedCells,
+              });
+            } catch (err: unknown) {
+              const msg = err instanceof Error ? err.message : String(err);
+              vscode.window.showErrorMessage(`Undo error: ${msg}`);
+            }
+            break;
+          }
         }
       },
       undefined,
       this.context.subscriptions
     );
+
+    // Offer to reload when the file is changed outside the editor
+    doc.watch(async (change) => {
+      if (change.kind !== "modified") {
+        return;
+      }
+      const choice = await vscode.window.showWarningMessage(
+        `${path.basename(filePath)} changed on disk. Reload it?`,
+        "Reload"
+      );
+      if (choice !== "Reload") {
+        return;
+      }
+      try {
+        await doc.reload();
+        sendInit();
+      } catch (err: unknown) {
+        const msg = err instanceof Error ? err.message : String(err);
+        vscode.window.showErrorMessage(`Reload error: ${msg}`);
+      }
+    });
   }
 }
 